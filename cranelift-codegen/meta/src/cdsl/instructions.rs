@@ -1292,6 +1292,12 @@ impl Into<InstSpec> for BoundInstruction {
     }
 }
 
+impl Into<InstSpec> for &BoundInstruction {
+    fn into(self) -> InstSpec {
+        InstSpec::Bound(self.clone())
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
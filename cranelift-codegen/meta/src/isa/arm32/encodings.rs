@@ -0,0 +1,427 @@
+use crate::cdsl::encodings::{Encoding, EncodingBuilder};
+use crate::cdsl::instructions::BindParameter::Any;
+use crate::cdsl::instructions::{
+    Bindable, InstSpec, InstructionGroup, InstructionPredicateRegistry,
+};
+use crate::cdsl::recipes::{EncodingRecipeNumber, Recipes};
+use crate::cdsl::settings::SettingGroup;
+use crate::cdsl::types::LaneType;
+
+use crate::shared::types::Bool::B1;
+use crate::shared::types::Int::{I16, I32, I8};
+use crate::shared::Definitions as SharedDefinitions;
+
+use super::recipes::RecipeGroup;
+
+pub(crate) struct PerCpuModeEncodings<'defs> {
+    pub inst_pred_reg: InstructionPredicateRegistry,
+    pub enc_a32: Vec<Encoding>,
+    recipes: &'defs Recipes,
+}
+
+impl<'defs> PerCpuModeEncodings<'defs> {
+    fn new(recipes: &'defs Recipes) -> Self {
+        Self {
+            inst_pred_reg: InstructionPredicateRegistry::new(),
+            enc_a32: Vec::new(),
+            recipes,
+        }
+    }
+    fn enc(
+        &self,
+        inst: impl Into<InstSpec>,
+        recipe: EncodingRecipeNumber,
+        bits: u16,
+    ) -> EncodingBuilder {
+        EncodingBuilder::new(inst.into(), recipe, bits)
+    }
+    fn add_a32(&mut self, encoding: EncodingBuilder) {
+        self.enc_a32
+            .push(encoding.build(self.recipes, &mut self.inst_pred_reg));
+    }
+}
+
+// Data processing opcodes, as they appear in instruction bits 27-20. Bit 25 selects the
+// immediate form and bit 20 is the `S` bit, which makes the instruction set the flags.
+const AND: u16 = 0x00;
+const EOR: u16 = 0x02;
+const SUB: u16 = 0x04;
+const ADD: u16 = 0x08;
+const ADC: u16 = 0x0a;
+const SBC: u16 = 0x0c;
+const CMP: u16 = 0x15;
+const CMN: u16 = 0x17;
+const ORR: u16 = 0x18;
+const MOV: u16 = 0x1a;
+const BIC: u16 = 0x1c;
+const MVN: u16 = 0x1e;
+
+const IMM: u16 = 0x20;
+const S: u16 = 0x01;
+
+// Shift types, in the encbits of the shift recipes.
+const LSL: u16 = 0b00;
+const LSR: u16 = 0b01;
+const ASR: u16 = 0b10;
+const ROR: u16 = 0b11;
+
+// Condition codes for `brz` and `brnz`.
+const COND_EQ: u16 = 0b0000;
+const COND_NE: u16 = 0b0001;
+
+/// Word and byte loads and stores with an immediate offset, in instruction bits 27-20. The
+/// offset is added to the base register, without writeback.
+fn ldst_bits(byte: bool, load: bool) -> u16 {
+    0x58 | (u16::from(byte) << 2) | u16::from(load)
+}
+
+/// Halfword and signed byte loads and stores with an immediate offset: instruction bits 27-20
+/// in the high bits, and bits 7-4 in the low bits.
+fn ldsth_bits(load: bool, op2: u16) -> u16 {
+    assert!(op2 <= 0b1111);
+    ((0x1c | u16::from(load)) << 4) | op2
+}
+
+pub(crate) fn define<'defs>(
+    shared_defs: &'defs SharedDefinitions,
+    isa_settings: &SettingGroup,
+    isa_inst_group: &InstructionGroup,
+    recipes: &'defs RecipeGroup,
+) -> PerCpuModeEncodings<'defs> {
+    // Instructions shorthands.
+    let shared = &shared_defs.instructions;
+
+    let adjust_sp_down_imm = shared.by_name("adjust_sp_down_imm");
+    let adjust_sp_up_imm = shared.by_name("adjust_sp_up_imm");
+    let band = shared.by_name("band");
+    let band_not = shared.by_name("band_not");
+    let bconst = shared.by_name("bconst");
+    let bint = shared.by_name("bint");
+    let bitrev = shared.by_name("bitrev");
+    let bnot = shared.by_name("bnot");
+    let bor = shared.by_name("bor");
+    let br_icmp = shared.by_name("br_icmp");
+    let brnz = shared.by_name("brnz");
+    let brz = shared.by_name("brz");
+    let bxor = shared.by_name("bxor");
+    let call = shared.by_name("call");
+    let call_indirect = shared.by_name("call_indirect");
+    let clz = shared.by_name("clz");
+    let copy = shared.by_name("copy");
+    let copy_nop = shared.by_name("copy_nop");
+    let copy_special = shared.by_name("copy_special");
+    let copy_to_ssa = shared.by_name("copy_to_ssa");
+    let ctz = shared.by_name("ctz");
+    let debugtrap = shared.by_name("debugtrap");
+    let fill = shared.by_name("fill");
+    let fill_nop = shared.by_name("fill_nop");
+    let func_addr = shared.by_name("func_addr");
+    let iadd = shared.by_name("iadd");
+    let iadd_ifcin = shared.by_name("iadd_ifcin");
+    let iadd_ifcout = shared.by_name("iadd_ifcout");
+    let iadd_imm = shared.by_name("iadd_imm");
+    let icmp = shared.by_name("icmp");
+    let icmp_imm = shared.by_name("icmp_imm");
+    let iconst = shared.by_name("iconst");
+    let imul = shared.by_name("imul");
+    let indirect_jump_table_br = shared.by_name("indirect_jump_table_br");
+    let ireduce = shared.by_name("ireduce");
+    let ishl = shared.by_name("ishl");
+    let ishl_imm = shared.by_name("ishl_imm");
+    let istore8 = shared.by_name("istore8");
+    let istore16 = shared.by_name("istore16");
+    let isub = shared.by_name("isub");
+    let isub_ifbin = shared.by_name("isub_ifbin");
+    let isub_ifbout = shared.by_name("isub_ifbout");
+    let jump = shared.by_name("jump");
+    let jump_table_base = shared.by_name("jump_table_base");
+    let jump_table_entry = shared.by_name("jump_table_entry");
+    let load = shared.by_name("load");
+    let regfill = shared.by_name("regfill");
+    let regmove = shared.by_name("regmove");
+    let regspill = shared.by_name("regspill");
+    let return_ = shared.by_name("return");
+    let rotl = shared.by_name("rotl");
+    let rotl_imm = shared.by_name("rotl_imm");
+    let rotr = shared.by_name("rotr");
+    let rotr_imm = shared.by_name("rotr_imm");
+    let sdiv = shared.by_name("sdiv");
+    let select = shared.by_name("select");
+    let sextend = shared.by_name("sextend");
+    let sload8 = shared.by_name("sload8");
+    let sload16 = shared.by_name("sload16");
+    let smulhi = shared.by_name("smulhi");
+    let spill = shared.by_name("spill");
+    let srem = shared.by_name("srem");
+    let sshr = shared.by_name("sshr");
+    let sshr_imm = shared.by_name("sshr_imm");
+    let stack_addr = shared.by_name("stack_addr");
+    let store = shared.by_name("store");
+    let symbol_value = shared.by_name("symbol_value");
+    let trap = shared.by_name("trap");
+    let udiv = shared.by_name("udiv");
+    let uextend = shared.by_name("uextend");
+    let uload8 = shared.by_name("uload8");
+    let uload16 = shared.by_name("uload16");
+    let umulhi = shared.by_name("umulhi");
+    let urem = shared.by_name("urem");
+    let ushr = shared.by_name("ushr");
+    let ushr_imm = shared.by_name("ushr_imm");
+
+    // ISA-specific instructions.
+    let arm32_pop = isa_inst_group.by_name("arm32_pop");
+    let arm32_push = isa_inst_group.by_name("arm32_push");
+
+    // Recipes shorthands, prefixed with r_.
+    let r_addsub_imm = recipes.by_name("addsub_imm");
+    let r_adjustsp = recipes.by_name("adjustsp");
+    let r_adjustsp_big = recipes.by_name("adjustsp_big");
+    let r_b = recipes.by_name("b");
+    let r_bconst = recipes.by_name("bconst");
+    let r_bitop = recipes.by_name("bitop");
+    let r_bkpt = recipes.by_name("bkpt");
+    let r_bricmp = recipes.by_name("bricmp");
+    let r_brz = recipes.by_name("brz");
+    let r_call = recipes.by_name("call");
+    let r_call_r = recipes.by_name("call_r");
+    let r_copy = recipes.by_name("copy");
+    let r_copysp = recipes.by_name("copysp");
+    let r_copytossa = recipes.by_name("copytossa");
+    let r_ctz = recipes.by_name("ctz");
+    let r_extend = recipes.by_name("extend");
+    let r_fill = recipes.by_name("fill");
+    let r_fillnull = recipes.by_name("fillnull");
+    let r_fnaddr = recipes.by_name("fnaddr");
+    let r_gvaddr = recipes.by_name("gvaddr");
+    let r_icmp = recipes.by_name("icmp");
+    let r_icmp_imm = recipes.by_name("icmp_imm");
+    let r_indirect_jmp = recipes.by_name("indirect_jmp");
+    let r_jt_base = recipes.by_name("jt_base");
+    let r_jt_entry = recipes.by_name("jt_entry");
+    let r_ld = recipes.by_name("ld");
+    let r_ld_big = recipes.by_name("ld_big");
+    let r_ldh = recipes.by_name("ldh");
+    let r_ldh_big = recipes.by_name("ldh_big");
+    let r_movw = recipes.by_name("movw");
+    let r_movwt = recipes.by_name("movwt");
+    let r_mul = recipes.by_name("mul");
+    let r_mulhi = recipes.by_name("mulhi");
+    let r_not_b1 = recipes.by_name("not_b1");
+    let r_null = recipes.by_name("null");
+    let r_pop = recipes.by_name("pop");
+    let r_push = recipes.by_name("push");
+    let r_regfill = recipes.by_name("regfill");
+    let r_regspill = recipes.by_name("regspill");
+    let r_rem = recipes.by_name("rem");
+    let r_ret = recipes.by_name("ret");
+    let r_rin = recipes.by_name("rin");
+    let r_rmov = recipes.by_name("rmov");
+    let r_rotl = recipes.by_name("rotl");
+    let r_rout = recipes.by_name("rout");
+    let r_rrr = recipes.by_name("rrr");
+    let r_sdiv = recipes.by_name("sdiv");
+    let r_select = recipes.by_name("select");
+    let r_shift = recipes.by_name("shift");
+    let r_shift_imm = recipes.by_name("shift_imm");
+    let r_spaddr = recipes.by_name("spaddr");
+    let r_spill = recipes.by_name("spill");
+    let r_st = recipes.by_name("st");
+    let r_st_big = recipes.by_name("st_big");
+    let r_stacknull = recipes.by_name("stacknull");
+    let r_sth = recipes.by_name("sth");
+    let r_sth_big = recipes.by_name("sth_big");
+    let r_trap = recipes.by_name("trap");
+    let r_udiv = recipes.by_name("udiv");
+    let r_ur = recipes.by_name("ur");
+
+    // Predicates shorthands.
+    let use_idiv = isa_settings.predicate_by_name("use_idiv");
+
+    // Definitions.
+    let mut e = PerCpuModeEncodings::new(&recipes.recipes);
+
+    // Integer arithmetic and logic. There is no `orn` in A32, so `bor_not` and `bxor_not` are
+    // expanded with a `bnot`.
+    for &(inst, op) in &[
+        (iadd, ADD),
+        (isub, SUB),
+        (band, AND),
+        (bor, ORR),
+        (bxor, EOR),
+        (band_not, BIC),
+    ] {
+        e.add_a32(e.enc(inst.bind(I32), r_rrr, op));
+    }
+    e.add_a32(e.enc(bnot.bind(I32), r_ur, MVN));
+
+    e.add_a32(e.enc(
+        iadd_imm.bind(I32),
+        r_addsub_imm,
+        ((SUB | IMM) << 8) | ADD | IMM,
+    ));
+
+    // Wide additions and subtractions are split into halves linked by the carry flag.
+    e.add_a32(e.enc(iadd_ifcout.bind(I32), r_rout, ADD | S));
+    e.add_a32(e.enc(iadd_ifcin.bind(I32), r_rin, ADC));
+    e.add_a32(e.enc(isub_ifbout.bind(I32), r_rout, SUB | S));
+    e.add_a32(e.enc(isub_ifbin.bind(I32), r_rin, SBC));
+
+    // mul, and the high halves of umull and smull.
+    e.add_a32(e.enc(imul.bind(I32), r_mul, 0x00));
+    e.add_a32(e.enc(umulhi.bind(I32), r_mulhi, 0x08));
+    e.add_a32(e.enc(smulhi.bind(I32), r_mulhi, 0x0c));
+
+    // The division instructions are an optional extension of ARMv7-A.
+    e.add_a32(e.enc(udiv.bind(I32), r_udiv, 0x73).isa_predicate(use_idiv));
+    e.add_a32(e.enc(sdiv.bind(I32), r_sdiv, 0x71).isa_predicate(use_idiv));
+    e.add_a32(e.enc(urem.bind(I32), r_rem, 0x73).isa_predicate(use_idiv));
+    e.add_a32(e.enc(srem.bind(I32), r_rem, 0x71).isa_predicate(use_idiv));
+
+    // Shifts. The amount can be of any integer type that fits in a register.
+    for &amount_ty in &[I8, I16, I32] {
+        for &(inst, shift) in &[(ishl, LSL), (ushr, LSR), (sshr, ASR), (rotr, ROR)] {
+            e.add_a32(e.enc(inst.bind(I32).bind(amount_ty), r_shift, shift));
+        }
+        e.add_a32(e.enc(rotl.bind(I32).bind(amount_ty), r_rotl, ROR));
+    }
+    for &(inst, shift) in &[
+        (ishl_imm, LSL),
+        (ushr_imm, LSR),
+        (sshr_imm, ASR),
+        (rotr_imm, ROR),
+        (rotl_imm, ROR | 0b100),
+    ] {
+        e.add_a32(e.enc(inst.bind(I32), r_shift_imm, shift));
+    }
+
+    // clz and rbit.
+    e.add_a32(e.enc(clz.bind(I32), r_bitop, 0x161));
+    e.add_a32(e.enc(ctz.bind(I32), r_ctz, 0x161));
+    e.add_a32(e.enc(bitrev.bind(I32), r_bitop, 0x6f3));
+
+    // Comparisons and selects.
+    e.add_a32(e.enc(icmp.bind(I32), r_icmp, CMP));
+    e.add_a32(e.enc(
+        icmp_imm.bind(I32),
+        r_icmp_imm,
+        ((CMN | IMM) << 8) | CMP | IMM,
+    ));
+    e.add_a32(e.enc(select.bind(I32).bind(B1), r_select, 0));
+    e.add_a32(e.enc(select.bind(B1).bind(B1), r_select, 0));
+
+    // Boolean logic.
+    for &(inst, op) in &[(band, AND), (bor, ORR), (bxor, EOR)] {
+        e.add_a32(e.enc(inst.bind(B1), r_rrr, op));
+    }
+    e.add_a32(e.enc(bnot.bind(B1), r_not_b1, EOR | IMM));
+
+    // Integer constants. The shorter form comes first so it gets picked when the predicate
+    // allows it.
+    e.add_a32(e.enc(iconst.bind(I32), r_movw, 0));
+    e.add_a32(e.enc(iconst.bind(I32), r_movwt, 0));
+    e.add_a32(e.enc(bconst.bind(B1), r_bconst, MOV | IMM));
+
+    // Extensions and reductions: uxtb, uxth, sxtb and sxth.
+    e.add_a32(e.enc(uextend.bind(I32).bind(I8), r_extend, 0x6e));
+    e.add_a32(e.enc(uextend.bind(I32).bind(I16), r_extend, 0x6f));
+    e.add_a32(e.enc(sextend.bind(I32).bind(I8), r_extend, 0x6a));
+    e.add_a32(e.enc(sextend.bind(I32).bind(I16), r_extend, 0x6b));
+
+    // Narrow values live in the low bits of a register, so reductions are free.
+    e.add_a32(e.enc(ireduce.bind(I8).bind(I32), r_null, 0));
+    e.add_a32(e.enc(ireduce.bind(I16).bind(I32), r_null, 0));
+    e.add_a32(e.enc(bint.bind(I32).bind(B1), r_null, 0));
+
+    // Loads and stores. Small offsets fit in the instruction, anything else goes through the
+    // scratch register.
+    for (inst, bits) in [
+        (load.bind(I32).bind(Any), ldst_bits(false, true)),
+        (uload8.bind(I32).bind(Any), ldst_bits(true, true)),
+    ]
+    .iter()
+    {
+        e.add_a32(e.enc(inst, r_ld, *bits));
+        e.add_a32(e.enc(inst, r_ld_big, *bits));
+    }
+    for (inst, bits) in [
+        (uload16.bind(I32).bind(Any), ldsth_bits(true, 0b1011)),
+        (sload8.bind(I32).bind(Any), ldsth_bits(true, 0b1101)),
+        (sload16.bind(I32).bind(Any), ldsth_bits(true, 0b1111)),
+    ]
+    .iter()
+    {
+        e.add_a32(e.enc(inst, r_ldh, *bits));
+        e.add_a32(e.enc(inst, r_ldh_big, *bits));
+    }
+    for (inst, bits) in [
+        (store.bind(I32).bind(Any), ldst_bits(false, false)),
+        (istore8.bind(I32).bind(Any), ldst_bits(true, false)),
+    ]
+    .iter()
+    {
+        e.add_a32(e.enc(inst, r_st, *bits));
+        e.add_a32(e.enc(inst, r_st_big, *bits));
+    }
+    let strh = ldsth_bits(false, 0b1011);
+    e.add_a32(e.enc(istore16.bind(I32).bind(Any), r_sth, strh));
+    e.add_a32(e.enc(istore16.bind(I32).bind(Any), r_sth_big, strh));
+
+    // Spills, fills and register copies.
+    let gpr_types: [LaneType; 4] = [B1.into(), I8.into(), I16.into(), I32.into()];
+    for &ty in gpr_types.iter() {
+        e.add_a32(e.enc(spill.bind(ty), r_spill, ldst_bits(false, false)));
+        e.add_a32(e.enc(regspill.bind(ty), r_regspill, ldst_bits(false, false)));
+        e.add_a32(e.enc(fill.bind(ty), r_fill, ldst_bits(false, true)));
+        e.add_a32(e.enc(regfill.bind(ty), r_regfill, ldst_bits(false, true)));
+        e.add_a32(e.enc(fill_nop.bind(ty), r_fillnull, 0));
+        e.add_a32(e.enc(copy_nop.bind(ty), r_stacknull, 0));
+
+        e.add_a32(e.enc(copy.bind(ty), r_copy, MOV));
+        e.add_a32(e.enc(regmove.bind(ty), r_rmov, MOV));
+        e.add_a32(e.enc(copy_to_ssa.bind(ty), r_copytossa, MOV));
+    }
+
+    // Addresses.
+    e.add_a32(e.enc(stack_addr.bind(I32), r_spaddr, ADD));
+    e.add_a32(e.enc(func_addr.bind(I32), r_fnaddr, ldst_bits(false, true)));
+    e.add_a32(e.enc(symbol_value.bind(I32), r_gvaddr, ldst_bits(false, true)));
+
+    // Prologue and epilogue support.
+    e.add_a32(e.enc(copy_special, r_copysp, MOV));
+    e.add_a32(e.enc(adjust_sp_down_imm, r_adjustsp, SUB | IMM));
+    e.add_a32(e.enc(adjust_sp_down_imm, r_adjustsp_big, SUB | IMM));
+    e.add_a32(e.enc(adjust_sp_up_imm, r_adjustsp, ADD | IMM));
+    e.add_a32(e.enc(adjust_sp_up_imm, r_adjustsp_big, ADD | IMM));
+
+    // Pre-indexed str, which stores below the stack pointer, and post-indexed ldr.
+    e.add_a32(e.enc(arm32_push, r_push, 0x5a));
+    e.add_a32(e.enc(arm32_pop, r_pop, 0x49));
+
+    // Control flow.
+    e.add_a32(e.enc(jump, r_b, 0));
+
+    let branch_types: [LaneType; 2] = [B1.into(), I32.into()];
+    for &ty in branch_types.iter() {
+        e.add_a32(e.enc(brz.bind(ty), r_brz, COND_EQ));
+        e.add_a32(e.enc(brnz.bind(ty), r_brz, COND_NE));
+    }
+    e.add_a32(e.enc(br_icmp.bind(I32), r_bricmp, CMP));
+
+    // bl, blx, and bx.
+    e.add_a32(e.enc(call, r_call, 0));
+    e.add_a32(e.enc(call_indirect.bind(I32), r_call_r, 0x3));
+    e.add_a32(e.enc(return_, r_ret, 0x1));
+
+    e.add_a32(e.enc(jump_table_base.bind(I32), r_jt_base, ADD));
+    e.add_a32(e.enc(
+        jump_table_entry.bind(I32),
+        r_jt_entry,
+        ldst_bits(false, true) | IMM,
+    ));
+    e.add_a32(e.enc(indirect_jump_table_br.bind(I32), r_indirect_jmp, 0x1));
+
+    e.add_a32(e.enc(trap, r_trap, 0));
+    e.add_a32(e.enc(debugtrap, r_bkpt, 0));
+
+    e
+}
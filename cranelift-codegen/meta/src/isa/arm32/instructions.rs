@@ -0,0 +1,58 @@
+#![allow(non_snake_case)]
+
+use crate::cdsl::instructions::{
+    AllInstructions, InstructionBuilder as Inst, InstructionGroup, InstructionGroupBuilder,
+};
+use crate::cdsl::operands::Operand;
+use crate::cdsl::typevar::{TypeSetBuilder, TypeVar};
+
+use crate::shared::formats::Formats;
+
+pub(crate) fn define(
+    mut all_instructions: &mut AllInstructions,
+    formats: &Formats,
+) -> InstructionGroup {
+    let mut ig = InstructionGroupBuilder::new(&mut all_instructions);
+
+    let iWord = &TypeVar::new(
+        "iWord",
+        "A scalar integer machine word",
+        TypeSetBuilder::new().ints(32..32).build(),
+    );
+
+    let x = &Operand::new("x", iWord);
+
+    ig.push(
+        Inst::new(
+            "arm32_push",
+            r#"
+    Pushes a value onto the stack.
+
+    Decrements the stack pointer by 4 and stores the specified value at the new top of the stack,
+    using a pre-indexed `str`.
+    "#,
+            &formats.unary,
+        )
+        .operands_in(vec![x])
+        .other_side_effects(true)
+        .can_store(true),
+    );
+
+    ig.push(
+        Inst::new(
+            "arm32_pop",
+            r#"
+    Pops a value from the stack.
+
+    Loads a value from the top of the stack and then increments the stack pointer by 4, using a
+    post-indexed `ldr`.
+    "#,
+            &formats.nullary,
+        )
+        .operands_out(vec![x])
+        .other_side_effects(true)
+        .can_load(true),
+    );
+
+    ig.build()
+}
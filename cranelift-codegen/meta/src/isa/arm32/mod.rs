@@ -1,14 +1,28 @@
 use crate::cdsl::cpu_modes::CpuMode;
-use crate::cdsl::instructions::{InstructionGroupBuilder, InstructionPredicateMap};
 use crate::cdsl::isa::TargetIsa;
-use crate::cdsl::recipes::Recipes;
 use crate::cdsl::regs::{IsaRegs, IsaRegsBuilder, RegBankBuilder, RegClassBuilder};
 use crate::cdsl::settings::{SettingGroup, SettingGroupBuilder};
 
+use crate::shared::types::Bool::B1;
+use crate::shared::types::Float::{F32, F64};
+use crate::shared::types::Int::{I16, I32, I8};
 use crate::shared::Definitions as SharedDefinitions;
 
+mod encodings;
+mod instructions;
+mod recipes;
+
 fn define_settings(_shared: &SettingGroup) -> SettingGroup {
-    let setting = SettingGroupBuilder::new("arm32");
+    let mut setting = SettingGroupBuilder::new("arm32");
+
+    let has_idiv = setting.add_bool(
+        "has_idiv",
+        "CPU supports the sdiv and udiv instructions in ARM state",
+        false,
+    );
+
+    setting.add_predicate("use_idiv", predicate!(has_idiv));
+
     setting.build()
 }
 
@@ -53,24 +67,39 @@ pub(crate) fn define(shared_defs: &mut SharedDefinitions) -> TargetIsa {
     let settings = define_settings(&shared_defs.settings);
     let regs = define_regs();
 
-    let inst_group = InstructionGroupBuilder::new(&mut shared_defs.all_instructions).build();
+    let inst_group = instructions::define(&mut shared_defs.all_instructions, &shared_defs.formats);
 
     // CPU modes for 32-bit ARM and Thumb2.
     let mut a32 = CpuMode::new("A32");
     let mut t32 = CpuMode::new("T32");
 
-    // TODO refine these.
+    // 64-bit integers are split in halves, and wide additions and subtractions go through the
+    // carry flag with adds/adc and subs/sbc.
+    let expand = shared_defs.transform_groups.by_name("expand");
     let narrow_flags = shared_defs.transform_groups.by_name("narrow_flags");
-    a32.legalize_default(narrow_flags);
-    t32.legalize_default(narrow_flags);
+    let widen = shared_defs.transform_groups.by_name("widen");
 
-    let cpu_modes = vec![a32, t32];
+    for mode in &mut [&mut a32, &mut t32] {
+        mode.legalize_monomorphic(expand);
+        mode.legalize_default(narrow_flags);
+        mode.legalize_type(B1, expand);
+        mode.legalize_type(I8, widen);
+        mode.legalize_type(I16, widen);
+        mode.legalize_type(I32, expand);
+        mode.legalize_type(F32, expand);
+        mode.legalize_type(F64, expand);
+    }
+
+    let recipes = recipes::define(shared_defs, &regs);
 
-    // TODO implement arm32 recipes.
-    let recipes = Recipes::new();
+    // Only the ARM instruction set has encodings for now. Thumb-2 code can't be generated yet.
+    let encodings = encodings::define(shared_defs, &settings, &inst_group, &recipes);
+    a32.set_encodings(encodings.enc_a32);
+    let encodings_predicates = encodings.inst_pred_reg.extract();
 
-    // TODO implement arm32 encodings and predicates.
-    let encodings_predicates = InstructionPredicateMap::new();
+    let recipes = recipes.collect();
+
+    let cpu_modes = vec![a32, t32];
 
     TargetIsa::new(
         "arm32",
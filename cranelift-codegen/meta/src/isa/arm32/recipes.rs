@@ -0,0 +1,893 @@
+use std::collections::HashMap;
+
+use crate::cdsl::instructions::InstructionPredicate;
+use crate::cdsl::recipes::{
+    EncodingRecipeBuilder, EncodingRecipeNumber, OperandConstraint, Recipes, Register, Stack,
+};
+use crate::cdsl::regs::IsaRegs;
+use crate::shared::Definitions as SharedDefinitions;
+
+/// An helper to create recipes and use them when defining the ARM32 encodings.
+pub(crate) struct RecipeGroup {
+    /// The actual list of recipes explicitly created in this file.
+    pub recipes: Recipes,
+
+    /// Provides fast lookup from a name to an encoding recipe.
+    name_to_recipe: HashMap<String, EncodingRecipeNumber>,
+}
+
+impl RecipeGroup {
+    fn new() -> Self {
+        Self {
+            recipes: Recipes::new(),
+            name_to_recipe: HashMap::new(),
+        }
+    }
+
+    fn push(&mut self, builder: EncodingRecipeBuilder) {
+        assert!(
+            self.name_to_recipe.get(&builder.name).is_none(),
+            format!("arm32 recipe '{}' created twice", builder.name)
+        );
+        let name = builder.name.clone();
+        let number = self.recipes.push(builder.build());
+        self.name_to_recipe.insert(name, number);
+    }
+
+    pub fn by_name(&self, name: &str) -> EncodingRecipeNumber {
+        *self
+            .name_to_recipe
+            .get(name)
+            .unwrap_or_else(|| panic!("unknown arm32 recipe name {}", name))
+    }
+
+    pub fn collect(self) -> Recipes {
+        self.recipes
+    }
+}
+
+pub(crate) fn define(shared_defs: &SharedDefinitions, regs: &IsaRegs) -> RecipeGroup {
+    let formats = &shared_defs.formats;
+
+    // Register classes shorthands.
+    let gpr = regs.class_by_name("GPR");
+    let flag = regs.class_by_name("FLAG");
+
+    // Register shorthands.
+    let reg_nzcv = Register::new(flag, regs.regunit_by_name(flag, "nzcv"));
+
+    // Definitions.
+    let mut recipes = RecipeGroup::new();
+
+    // All A32 instructions are 32 bits wide. The encbits of most recipes hold instruction bits
+    // 27-20, which contain the opcode and the `S` bit of the data processing instructions. The
+    // condition field is filled in by the recipe, and is "always" unless stated otherwise.
+    //
+    // Recipes that need a temporary use `r12`, the intra-procedure-call scratch register. Only
+    // the recipes that test a condition or use the `S` bit clobber the flags.
+
+    // Integer data processing.
+
+    // Three-register data processing instructions, without a shift: add, sub and the logical
+    // operations.
+    recipes.push(
+        EncodingRecipeBuilder::new("rrr", &formats.binary, 4)
+            .operands_in(vec![gpr, gpr])
+            .operands_out(vec![gpr])
+            .clobbers_flags(false)
+            .emit("put_dp(COND_AL, bits, in_reg0, in_reg1, out_reg0, sink);"),
+    );
+
+    // adds and subs, producing the carry flag of the low half of a wide addition or subtraction.
+    recipes.push(
+        EncodingRecipeBuilder::new("rout", &formats.binary, 4)
+            .operands_in(vec![gpr, gpr])
+            .operands_out(vec![
+                OperandConstraint::RegClass(gpr),
+                OperandConstraint::FixedReg(reg_nzcv),
+            ])
+            .emit("put_dp(COND_AL, bits, in_reg0, in_reg1, out_reg0, sink);"),
+    );
+
+    // adc and sbc, consuming the carry flag.
+    recipes.push(
+        EncodingRecipeBuilder::new("rin", &formats.ternary, 4)
+            .operands_in(vec![
+                OperandConstraint::RegClass(gpr),
+                OperandConstraint::RegClass(gpr),
+                OperandConstraint::FixedReg(reg_nzcv),
+            ])
+            .operands_out(vec![gpr])
+            .clobbers_flags(false)
+            .emit("put_dp(COND_AL, bits, in_reg0, in_reg1, out_reg0, sink);"),
+    );
+
+    // mvn.
+    recipes.push(
+        EncodingRecipeBuilder::new("ur", &formats.unary, 4)
+            .operands_in(vec![gpr])
+            .operands_out(vec![gpr])
+            .clobbers_flags(false)
+            .emit("put_dp(COND_AL, bits, 0, in_reg0, out_reg0, sink);"),
+    );
+
+    // Booleans are 0 or 1, so negating one is an exclusive or with 1.
+    recipes.push(
+        EncodingRecipeBuilder::new("not_b1", &formats.unary, 4)
+            .operands_in(vec![gpr])
+            .operands_out(vec![gpr])
+            .clobbers_flags(false)
+            .emit("put_dp_imm(COND_AL, bits, in_reg0, 1, out_reg0, sink);"),
+    );
+
+    // Add or subtract an immediate. The encbits hold the immediate form of the instruction in
+    // the low byte, and of its negated counterpart in the high byte, which is used for negative
+    // immediates. Magnitudes up to 256 can always be encoded as a rotated 8-bit immediate.
+    recipes.push(
+        EncodingRecipeBuilder::new("addsub_imm", &formats.binary_imm, 4)
+            .operands_in(vec![gpr])
+            .operands_out(vec![gpr])
+            .inst_predicate(InstructionPredicate::new_is_signed_int(
+                &*formats.binary_imm,
+                "imm",
+                9,
+                0,
+            ))
+            .clobbers_flags(false)
+            .emit("put_addsub_imm(bits, in_reg0, imm.into(), out_reg0, sink);"),
+    );
+
+    // Multiplications: mul.
+    recipes.push(
+        EncodingRecipeBuilder::new("mul", &formats.binary, 4)
+            .operands_in(vec![gpr, gpr])
+            .operands_out(vec![gpr])
+            .clobbers_flags(false)
+            .emit("put_mul(bits, out_reg0, 0, in_reg1, in_reg0, sink);"),
+    );
+
+    // The high half of a long multiplication: umull and smull, with the low half going to the
+    // scratch register.
+    recipes.push(
+        EncodingRecipeBuilder::new("mulhi", &formats.binary, 4)
+            .operands_in(vec![gpr, gpr])
+            .operands_out(vec![gpr])
+            .clobbers_flags(false)
+            .emit("put_mul(bits, out_reg0, 12, in_reg1, in_reg0, sink);"),
+    );
+
+    // Unsigned division. The division instructions return 0 for a zero divisor, so we need an
+    // explicit check.
+    recipes.push(
+        EncodingRecipeBuilder::new("udiv", &formats.binary, 16)
+            .operands_in(vec![gpr, gpr])
+            .operands_out(vec![gpr])
+            .emit(
+                r#"
+                    put_dp_imm(COND_AL, OP_CMP_IMM, in_reg1, 0, 0, sink);
+                    put_b(COND_NE, 8, sink);
+                    sink.trap(TrapCode::IntegerDivisionByZero, func.srclocs[inst]);
+                    put_udf(sink);
+                    put_div(bits, in_reg0, in_reg1, out_reg0, sink);
+                "#,
+            ),
+    );
+
+    // Signed division also needs to trap when computing `INT_MIN / -1`.
+    recipes.push(
+        EncodingRecipeBuilder::new("sdiv", &formats.binary, 32)
+            .operands_in(vec![gpr, gpr])
+            .operands_out(vec![gpr])
+            .emit(
+                r#"
+                    put_dp_imm(COND_AL, OP_CMP_IMM, in_reg1, 0, 0, sink);
+                    put_b(COND_NE, 8, sink);
+                    sink.trap(TrapCode::IntegerDivisionByZero, func.srclocs[inst]);
+                    put_udf(sink);
+                    // cmn rm, #1
+                    put_dp_imm(COND_AL, OP_CMN_IMM, in_reg1, 1, 0, sink);
+                    // cmpeq rn, #0x80000000
+                    put_dp_imm(COND_EQ, OP_CMP_IMM, in_reg0, 0x102, 0, sink);
+                    put_b(COND_NE, 8, sink);
+                    sink.trap(TrapCode::IntegerOverflow, func.srclocs[inst]);
+                    put_udf(sink);
+                    put_div(bits, in_reg0, in_reg1, out_reg0, sink);
+                "#,
+            ),
+    );
+
+    // Remainders are computed from the quotient with an mls. The quotient goes in the scratch
+    // register. `INT_MIN % -1` doesn't need a check since sdiv produces `INT_MIN` and the mls
+    // then yields the expected 0.
+    recipes.push(
+        EncodingRecipeBuilder::new("rem", &formats.binary, 20)
+            .operands_in(vec![gpr, gpr])
+            .operands_out(vec![gpr])
+            .emit(
+                r#"
+                    put_dp_imm(COND_AL, OP_CMP_IMM, in_reg1, 0, 0, sink);
+                    put_b(COND_NE, 8, sink);
+                    sink.trap(TrapCode::IntegerDivisionByZero, func.srclocs[inst]);
+                    put_udf(sink);
+                    put_div(bits, in_reg0, in_reg1, 12, sink);
+                    // mls rd, r12, rm, rn
+                    put_mul(OP_MLS, out_reg0, in_reg0, in_reg1, 12, sink);
+                "#,
+            ),
+    );
+
+    // Shifts by a register only use the low byte of the amount, and shift out all the bits for
+    // amounts of 32 or more. Mask the amount first to get the modular clif semantics.
+    recipes.push(
+        EncodingRecipeBuilder::new("shift", &formats.binary, 8)
+            .operands_in(vec![gpr, gpr])
+            .operands_out(vec![gpr])
+            .clobbers_flags(false)
+            .emit(
+                r#"
+                    // and r12, rs, #31
+                    put_dp_imm(COND_AL, OP_AND_IMM, in_reg1, 31, 12, sink);
+                    put_shift_reg(bits, in_reg0, 12, out_reg0, sink);
+                "#,
+            ),
+    );
+
+    // There is no rotate left instruction, so negate the amount and rotate right instead. Only
+    // the low 5 bits of the negated amount matter for a rotation.
+    recipes.push(
+        EncodingRecipeBuilder::new("rotl", &formats.binary, 8)
+            .operands_in(vec![gpr, gpr])
+            .operands_out(vec![gpr])
+            .clobbers_flags(false)
+            .emit(
+                r#"
+                    // rsb r12, rs, #0
+                    put_dp_imm(COND_AL, OP_RSB_IMM, in_reg1, 0, 12, sink);
+                    put_shift_reg(bits, in_reg0, 12, out_reg0, sink);
+                "#,
+            ),
+    );
+
+    // Shifts and rotates by an immediate. A set bit 2 in the encbits means rotating left.
+    recipes.push(
+        EncodingRecipeBuilder::new("shift_imm", &formats.binary_imm, 4)
+            .operands_in(vec![gpr])
+            .operands_out(vec![gpr])
+            .clobbers_flags(false)
+            .emit("put_shift_imm(bits, in_reg0, imm.into(), out_reg0, sink);"),
+    );
+
+    // Sign and zero extensions: sxtb, sxth, uxtb and uxth.
+    recipes.push(
+        EncodingRecipeBuilder::new("extend", &formats.unary, 4)
+            .operands_in(vec![gpr])
+            .operands_out(vec![gpr])
+            .clobbers_flags(false)
+            .emit("put_extend(bits, in_reg0, out_reg0, sink);"),
+    );
+
+    // clz and rbit. The encbits hold instruction bits 27-20 and 7-4.
+    recipes.push(
+        EncodingRecipeBuilder::new("bitop", &formats.unary, 4)
+            .operands_in(vec![gpr])
+            .operands_out(vec![gpr])
+            .clobbers_flags(false)
+            .emit("put_bitop(bits, in_reg0, out_reg0, sink);"),
+    );
+
+    // Count trailing zeros as the leading zeros of the bit-reversed input.
+    recipes.push(
+        EncodingRecipeBuilder::new("ctz", &formats.unary, 8)
+            .operands_in(vec![gpr])
+            .operands_out(vec![gpr])
+            .clobbers_flags(false)
+            .emit(
+                r#"
+                    put_bitop(OP_RBIT, in_reg0, out_reg0, sink);
+                    put_bitop(bits, out_reg0, out_reg0, sink);
+                "#,
+            ),
+    );
+
+    // Integer constants.
+
+    // A 16-bit constant can be materialized by a single movw.
+    recipes.push(
+        EncodingRecipeBuilder::new("movw", &formats.unary_imm, 4)
+            .operands_out(vec![gpr])
+            .inst_predicate(InstructionPredicate::new_is_unsigned_int(
+                &*formats.unary_imm,
+                "imm",
+                16,
+                0,
+            ))
+            .clobbers_flags(false)
+            .emit(
+                r#"
+                    let imm: i64 = imm.into();
+                    put_movw(COND_AL, imm as u32, out_reg0, sink);
+                "#,
+            ),
+    );
+
+    // Arbitrary 32-bit constants: movw + movt.
+    recipes.push(
+        EncodingRecipeBuilder::new("movwt", &formats.unary_imm, 8)
+            .operands_out(vec![gpr])
+            .clobbers_flags(false)
+            .emit(
+                r#"
+                    let imm: i64 = imm.into();
+                    put_movw(COND_AL, imm as u32, out_reg0, sink);
+                    put_movt(imm as u32, out_reg0, sink);
+                "#,
+            ),
+    );
+
+    recipes.push(
+        EncodingRecipeBuilder::new("bconst", &formats.unary_bool, 4)
+            .operands_out(vec![gpr])
+            .clobbers_flags(false)
+            .emit("put_dp_imm(COND_AL, bits, 0, imm as u32, out_reg0, sink);"),
+    );
+
+    // Comparisons.
+
+    // Integer comparison: cmp, then a pair of conditional moves to materialize the boolean.
+    recipes.push(
+        EncodingRecipeBuilder::new("icmp", &formats.int_compare, 12)
+            .operands_in(vec![gpr, gpr])
+            .operands_out(vec![gpr])
+            .emit(
+                r#"
+                    put_dp(COND_AL, bits, in_reg0, in_reg1, 0, sink);
+                    put_dp_imm(COND_AL, OP_MOV_IMM, 0, 0, out_reg0, sink);
+                    put_dp_imm(icc2cond(cond), OP_MOV_IMM, 0, 1, out_reg0, sink);
+                "#,
+            ),
+    );
+
+    // Integer comparison with an immediate, using cmn for negative immediates.
+    recipes.push(
+        EncodingRecipeBuilder::new("icmp_imm", &formats.int_compare_imm, 12)
+            .operands_in(vec![gpr])
+            .operands_out(vec![gpr])
+            .inst_predicate(InstructionPredicate::new_is_signed_int(
+                &*formats.int_compare_imm,
+                "imm",
+                9,
+                0,
+            ))
+            .emit(
+                r#"
+                    put_addsub_imm(bits, in_reg0, imm.into(), 0, sink);
+                    put_dp_imm(COND_AL, OP_MOV_IMM, 0, 0, out_reg0, sink);
+                    put_dp_imm(icc2cond(cond), OP_MOV_IMM, 0, 1, out_reg0, sink);
+                "#,
+            ),
+    );
+
+    // Select on a `b1` condition. Each conditional move only reads its own source, so the output
+    // may share a register with either input.
+    recipes.push(
+        EncodingRecipeBuilder::new("select", &formats.ternary, 12)
+            .operands_in(vec![gpr, gpr, gpr])
+            .operands_out(vec![gpr])
+            .emit(
+                r#"
+                    put_dp_imm(COND_AL, OP_CMP_IMM, in_reg0, 0, 0, sink);
+                    put_dp(COND_NE, OP_MOV, 0, in_reg1, out_reg0, sink);
+                    put_dp(COND_EQ, OP_MOV, 0, in_reg2, out_reg0, sink);
+                "#,
+            ),
+    );
+
+    // Memory accesses.
+
+    // Word and byte loads with a 12-bit offset magnitude.
+    recipes.push(
+        EncodingRecipeBuilder::new("ld", &formats.load, 4)
+            .operands_in(vec![gpr])
+            .operands_out(vec![gpr])
+            .inst_predicate(InstructionPredicate::new_is_signed_int(
+                &*formats.load,
+                "offset",
+                13,
+                0,
+            ))
+            .clobbers_flags(false)
+            .emit(
+                r#"
+                    if !flags.notrap() {
+                        sink.trap(TrapCode::HeapOutOfBounds, func.srclocs[inst]);
+                    }
+                    put_ldst(bits, in_reg0, offset.into(), out_reg0, sink);
+                "#,
+            ),
+    );
+
+    // Loads with a 32-bit offset, materialized in the scratch register.
+    recipes.push(
+        EncodingRecipeBuilder::new("ld_big", &formats.load, 12)
+            .operands_in(vec![gpr])
+            .operands_out(vec![gpr])
+            .clobbers_flags(false)
+            .emit(
+                r#"
+                    let offset: i32 = offset.into();
+                    put_movw(COND_AL, offset as u32, 12, sink);
+                    put_movt(offset as u32, 12, sink);
+                    if !flags.notrap() {
+                        sink.trap(TrapCode::HeapOutOfBounds, func.srclocs[inst]);
+                    }
+                    put_ldst_reg(bits, in_reg0, 12, out_reg0, sink);
+                "#,
+            ),
+    );
+
+    // Halfword and signed byte loads, which only have an 8-bit offset magnitude. The encbits hold
+    // instruction bits 27-20 and 7-4.
+    recipes.push(
+        EncodingRecipeBuilder::new("ldh", &formats.load, 4)
+            .operands_in(vec![gpr])
+            .operands_out(vec![gpr])
+            .inst_predicate(InstructionPredicate::new_is_signed_int(
+                &*formats.load,
+                "offset",
+                9,
+                0,
+            ))
+            .clobbers_flags(false)
+            .emit(
+                r#"
+                    if !flags.notrap() {
+                        sink.trap(TrapCode::HeapOutOfBounds, func.srclocs[inst]);
+                    }
+                    put_ldsth(bits, in_reg0, offset.into(), out_reg0, sink);
+                "#,
+            ),
+    );
+
+    recipes.push(
+        EncodingRecipeBuilder::new("ldh_big", &formats.load, 12)
+            .operands_in(vec![gpr])
+            .operands_out(vec![gpr])
+            .clobbers_flags(false)
+            .emit(
+                r#"
+                    let offset: i32 = offset.into();
+                    put_movw(COND_AL, offset as u32, 12, sink);
+                    put_movt(offset as u32, 12, sink);
+                    if !flags.notrap() {
+                        sink.trap(TrapCode::HeapOutOfBounds, func.srclocs[inst]);
+                    }
+                    put_ldsth_reg(bits, in_reg0, 12, out_reg0, sink);
+                "#,
+            ),
+    );
+
+    // Word and byte stores with a 12-bit offset magnitude.
+    recipes.push(
+        EncodingRecipeBuilder::new("st", &formats.store, 4)
+            .operands_in(vec![gpr, gpr])
+            .inst_predicate(InstructionPredicate::new_is_signed_int(
+                &*formats.store,
+                "offset",
+                13,
+                0,
+            ))
+            .clobbers_flags(false)
+            .emit(
+                r#"
+                    if !flags.notrap() {
+                        sink.trap(TrapCode::HeapOutOfBounds, func.srclocs[inst]);
+                    }
+                    put_ldst(bits, in_reg1, offset.into(), in_reg0, sink);
+                "#,
+            ),
+    );
+
+    recipes.push(
+        EncodingRecipeBuilder::new("st_big", &formats.store, 12)
+            .operands_in(vec![gpr, gpr])
+            .clobbers_flags(false)
+            .emit(
+                r#"
+                    let offset: i32 = offset.into();
+                    put_movw(COND_AL, offset as u32, 12, sink);
+                    put_movt(offset as u32, 12, sink);
+                    if !flags.notrap() {
+                        sink.trap(TrapCode::HeapOutOfBounds, func.srclocs[inst]);
+                    }
+                    put_ldst_reg(bits, in_reg1, 12, in_reg0, sink);
+                "#,
+            ),
+    );
+
+    // Halfword stores.
+    recipes.push(
+        EncodingRecipeBuilder::new("sth", &formats.store, 4)
+            .operands_in(vec![gpr, gpr])
+            .inst_predicate(InstructionPredicate::new_is_signed_int(
+                &*formats.store,
+                "offset",
+                9,
+                0,
+            ))
+            .clobbers_flags(false)
+            .emit(
+                r#"
+                    if !flags.notrap() {
+                        sink.trap(TrapCode::HeapOutOfBounds, func.srclocs[inst]);
+                    }
+                    put_ldsth(bits, in_reg1, offset.into(), in_reg0, sink);
+                "#,
+            ),
+    );
+
+    recipes.push(
+        EncodingRecipeBuilder::new("sth_big", &formats.store, 12)
+            .operands_in(vec![gpr, gpr])
+            .clobbers_flags(false)
+            .emit(
+                r#"
+                    let offset: i32 = offset.into();
+                    put_movw(COND_AL, offset as u32, 12, sink);
+                    put_movt(offset as u32, 12, sink);
+                    if !flags.notrap() {
+                        sink.trap(TrapCode::HeapOutOfBounds, func.srclocs[inst]);
+                    }
+                    put_ldsth_reg(bits, in_reg1, 12, in_reg0, sink);
+                "#,
+            ),
+    );
+
+    // Spills and fills are word accesses relative to the stack pointer, since spill slots are
+    // at least 4 bytes.
+    recipes.push(
+        EncodingRecipeBuilder::new("spill", &formats.unary, 4)
+            .operands_in(vec![gpr])
+            .operands_out(vec![Stack::new(gpr)])
+            .clobbers_flags(false)
+            .emit(
+                r#"
+                    sink.trap(TrapCode::StackOverflow, func.srclocs[inst]);
+                    put_ldst_sp(bits, out_stk0.offset, in_reg0, sink);
+                "#,
+            ),
+    );
+
+    recipes.push(
+        EncodingRecipeBuilder::new("regspill", &formats.reg_spill, 4)
+            .operands_in(vec![gpr])
+            .clobbers_flags(false)
+            .emit(
+                r#"
+                    sink.trap(TrapCode::StackOverflow, func.srclocs[inst]);
+                    let dst = StackRef::sp(dst, &func.stack_slots);
+                    put_ldst_sp(bits, dst.offset, src, sink);
+                "#,
+            ),
+    );
+
+    recipes.push(
+        EncodingRecipeBuilder::new("fill", &formats.unary, 4)
+            .operands_in(vec![Stack::new(gpr)])
+            .operands_out(vec![gpr])
+            .clobbers_flags(false)
+            .emit("put_ldst_sp(bits, in_stk0.offset, out_reg0, sink);"),
+    );
+
+    recipes.push(
+        EncodingRecipeBuilder::new("regfill", &formats.reg_fill, 4)
+            .operands_in(vec![Stack::new(gpr)])
+            .clobbers_flags(false)
+            .emit(
+                r#"
+                    let src = StackRef::sp(src, &func.stack_slots);
+                    put_ldst_sp(bits, src.offset, dst, sink);
+                "#,
+            ),
+    );
+
+    // Stack-slot to same stack-slot copy, which is guaranteed to turn into a no-op.
+    recipes.push(
+        EncodingRecipeBuilder::new("stacknull", &formats.unary, 0)
+            .operands_in(vec![Stack::new(gpr)])
+            .operands_out(vec![Stack::new(gpr)])
+            .clobbers_flags(false)
+            .emit(""),
+    );
+
+    // No-op fills, created by late-stage redundant-fill removal.
+    recipes.push(
+        EncodingRecipeBuilder::new("fillnull", &formats.unary, 0)
+            .operands_in(vec![Stack::new(gpr)])
+            .operands_out(vec![gpr])
+            .clobbers_flags(false)
+            .emit(""),
+    );
+
+    // Address of a stack slot: movw r12, #offset followed by add rd, sp, r12.
+    recipes.push(
+        EncodingRecipeBuilder::new("spaddr", &formats.stack_load, 8)
+            .operands_out(vec![gpr])
+            .clobbers_flags(false)
+            .emit(
+                r#"
+                    let sp = StackRef::sp(stack_slot, &func.stack_slots);
+                    let imm : i32 = offset.into();
+                    let offset = sp.offset.checked_add(imm).unwrap();
+                    assert!(offset >= 0 && offset < (1 << 16), "stack offset out of range");
+                    put_movw(COND_AL, offset as u32, 12, sink);
+                    put_dp(COND_AL, bits, 13, 12, out_reg0, sink);
+                "#,
+            ),
+    );
+
+    // Stack pointer adjustments for the prologue and epilogue.
+    recipes.push(
+        EncodingRecipeBuilder::new("adjustsp", &formats.unary_imm, 4)
+            .inst_predicate(InstructionPredicate::new_is_unsigned_int(
+                &*formats.unary_imm,
+                "imm",
+                8,
+                0,
+            ))
+            .clobbers_flags(false)
+            .emit(
+                r#"
+                    let imm: i64 = imm.into();
+                    put_dp_imm(COND_AL, bits, 13, imm as u32, 13, sink);
+                "#,
+            ),
+    );
+
+    recipes.push(
+        EncodingRecipeBuilder::new("adjustsp_big", &formats.unary_imm, 12)
+            .clobbers_flags(false)
+            .emit(
+                r#"
+                    let imm: i64 = imm.into();
+                    put_movw(COND_AL, imm as u32, 12, sink);
+                    put_movt(imm as u32, 12, sink);
+                    put_dp(COND_AL, bits & !0x20, 13, 12, 13, sink);
+                "#,
+            ),
+    );
+
+    // Pushes and pops of a single register.
+    recipes.push(
+        EncodingRecipeBuilder::new("push", &formats.unary, 4)
+            .operands_in(vec![gpr])
+            .clobbers_flags(false)
+            .emit(
+                r#"
+                    sink.trap(TrapCode::StackOverflow, func.srclocs[inst]);
+                    put_ldst(bits, 13, -4, in_reg0, sink);
+                "#,
+            ),
+    );
+
+    recipes.push(
+        EncodingRecipeBuilder::new("pop", &formats.nullary, 4)
+            .operands_out(vec![gpr])
+            .clobbers_flags(false)
+            .emit("put_ldst(bits, 13, 4, out_reg0, sink);"),
+    );
+
+    // Moves.
+
+    recipes.push(
+        EncodingRecipeBuilder::new("copy", &formats.unary, 4)
+            .operands_in(vec![gpr])
+            .operands_out(vec![gpr])
+            .clobbers_flags(false)
+            .emit("put_dp(COND_AL, bits, 0, in_reg0, out_reg0, sink);"),
+    );
+
+    recipes.push(
+        EncodingRecipeBuilder::new("rmov", &formats.reg_move, 4)
+            .operands_in(vec![gpr])
+            .clobbers_flags(false)
+            .emit("put_dp(COND_AL, bits, 0, src, dst, sink);"),
+    );
+
+    recipes.push(
+        EncodingRecipeBuilder::new("copytossa", &formats.copy_to_ssa, 4)
+            // No operands_in to mention, because a source register is specified directly.
+            .operands_out(vec![gpr])
+            .clobbers_flags(false)
+            .emit("put_dp(COND_AL, bits, 0, src, out_reg0, sink);"),
+    );
+
+    recipes.push(
+        EncodingRecipeBuilder::new("copysp", &formats.copy_special, 4)
+            .clobbers_flags(false)
+            .emit("put_dp(COND_AL, bits, 0, src, dst, sink);"),
+    );
+
+    // Reductions, and conversions from booleans which are already 0 or 1.
+    recipes.push(
+        EncodingRecipeBuilder::new("null", &formats.unary, 0)
+            .operands_in(vec![gpr])
+            .operands_out(vec![0])
+            .clobbers_flags(false)
+            .emit(""),
+    );
+
+    // Addresses.
+
+    // Load an absolute address from a literal placed in the instruction stream. The `pc`
+    // register reads as the address of the current instruction plus 8:
+    //
+    //   ldr rd, [pc, #0]
+    //   b #+0
+    //   .word sym
+    recipes.push(
+        EncodingRecipeBuilder::new("fnaddr", &formats.func_addr, 12)
+            .operands_out(vec![gpr])
+            .clobbers_flags(false)
+            .emit(
+                r#"
+                    put_ldst(bits, 15, 0, out_reg0, sink);
+                    put_b(COND_AL, 8, sink);
+                    sink.reloc_external(Reloc::Abs4,
+                                        &func.dfg.ext_funcs[func_ref].name,
+                                        0);
+                    sink.put4(0);
+                "#,
+            ),
+    );
+
+    recipes.push(
+        EncodingRecipeBuilder::new("gvaddr", &formats.unary_global_value, 12)
+            .operands_out(vec![gpr])
+            .clobbers_flags(false)
+            .emit(
+                r#"
+                    put_ldst(bits, 15, 0, out_reg0, sink);
+                    put_b(COND_AL, 8, sink);
+                    sink.reloc_external(Reloc::Abs4,
+                                        &func.global_values[global_value].symbol_name(),
+                                        0);
+                    sink.put4(0);
+                "#,
+            ),
+    );
+
+    // Control flow.
+
+    // Branch displacements are relative to the address of the branch plus 8.
+    recipes.push(
+        EncodingRecipeBuilder::new("b", &formats.jump, 4)
+            .branch_range((8, 26))
+            .clobbers_flags(false)
+            .emit(
+                r#"
+                    let dest = i64::from(func.offsets[destination]);
+                    let disp = dest - i64::from(sink.offset());
+                    put_b(COND_AL, disp, sink);
+                "#,
+            ),
+    );
+
+    // Compare with zero and branch. The encbits hold the condition.
+    recipes.push(
+        EncodingRecipeBuilder::new("brz", &formats.branch, 8)
+            .operands_in(vec![gpr])
+            .branch_range((12, 26))
+            .emit(
+                r#"
+                    put_dp_imm(COND_AL, OP_CMP_IMM, in_reg0, 0, 0, sink);
+                    let dest = i64::from(func.offsets[destination]);
+                    let disp = dest - i64::from(sink.offset());
+                    put_b(u32::from(bits), disp, sink);
+                "#,
+            ),
+    );
+
+    // cmp + b.cond.
+    recipes.push(
+        EncodingRecipeBuilder::new("bricmp", &formats.branch_icmp, 8)
+            .operands_in(vec![gpr, gpr])
+            .branch_range((12, 26))
+            .emit(
+                r#"
+                    put_dp(COND_AL, bits, in_reg0, in_reg1, 0, sink);
+                    let dest = i64::from(func.offsets[destination]);
+                    let disp = dest - i64::from(sink.offset());
+                    put_b(icc2cond(cond), disp, sink);
+                "#,
+            ),
+    );
+
+    // The relocation addend accounts for `pc` reading 8 bytes ahead, which is also what the
+    // displacement encoded in the instruction says.
+    recipes.push(
+        EncodingRecipeBuilder::new("call", &formats.call, 4)
+            .clobbers_flags(false)
+            .emit(
+                r#"
+                    sink.reloc_external(Reloc::Arm32Call,
+                                        &func.dfg.ext_funcs[func_ref].name,
+                                        -8);
+                    put_bl(sink);
+                "#,
+            ),
+    );
+
+    // blx, and bx for indirect jumps.
+    recipes.push(
+        EncodingRecipeBuilder::new("call_r", &formats.call_indirect, 4)
+            .operands_in(vec![gpr])
+            .clobbers_flags(false)
+            .emit("put_bx(bits, in_reg0, sink);"),
+    );
+
+    // The return address is restored into `lr` by the epilogue.
+    recipes.push(
+        EncodingRecipeBuilder::new("ret", &formats.multiary, 4)
+            .clobbers_flags(false)
+            .emit("put_bx(bits, 14, sink);"),
+    );
+
+    recipes.push(
+        EncodingRecipeBuilder::new("trap", &formats.trap, 4)
+            .clobbers_flags(false)
+            .emit(
+                r#"
+                    sink.trap(code, func.srclocs[inst]);
+                    put_udf(sink);
+                "#,
+            ),
+    );
+
+    // bkpt #0.
+    recipes.push(
+        EncodingRecipeBuilder::new("bkpt", &formats.nullary, 4)
+            .clobbers_flags(false)
+            .emit("put_bkpt(sink);"),
+    );
+
+    // Jump tables are emitted right after the function body. Their address is computed relative
+    // to `pc` with a 32-bit displacement.
+    recipes.push(
+        EncodingRecipeBuilder::new("jt_base", &formats.branch_table_base, 12)
+            .operands_out(vec![gpr])
+            .clobbers_flags(false)
+            .emit(
+                r#"
+                    let jt_offset = func.jt_offsets[table];
+                    // `pc` reads as the address of the add, plus 8.
+                    let disp = jt_offset.wrapping_sub(sink.offset() + 16);
+                    put_movw(COND_AL, disp, out_reg0, sink);
+                    put_movt(disp, out_reg0, sink);
+                    put_dp(COND_AL, bits, 15, out_reg0, out_reg0, sink);
+                "#,
+            ),
+    );
+
+    // Load a jump table entry: ldr rd, [base, idx, lsl #2].
+    recipes.push(
+        EncodingRecipeBuilder::new("jt_entry", &formats.branch_table_entry, 4)
+            .operands_in(vec![gpr, gpr])
+            .operands_out(vec![gpr])
+            .inst_predicate(InstructionPredicate::new_is_field_equal(
+                &*formats.branch_table_entry,
+                "imm",
+                "4".into(),
+            ))
+            .clobbers_flags(false)
+            .emit("put_ldst_lsl2(bits, in_reg1, in_reg0, out_reg0, sink);"),
+    );
+
+    recipes.push(
+        EncodingRecipeBuilder::new("indirect_jmp", &formats.indirect_jump, 4)
+            .operands_in(vec![gpr])
+            .clobbers_flags(false)
+            .emit("put_bx(bits, in_reg0, sink);"),
+    );
+
+    recipes
+}
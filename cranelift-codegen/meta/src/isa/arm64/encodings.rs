@@ -0,0 +1,705 @@
+use crate::cdsl::encodings::{Encoding, EncodingBuilder};
+use crate::cdsl::instructions::BindParameter::Any;
+use crate::cdsl::instructions::{
+    Bindable, InstSpec, InstructionGroup, InstructionPredicateRegistry,
+};
+use crate::cdsl::recipes::{EncodingRecipeNumber, Recipes};
+use crate::cdsl::types::LaneType;
+
+use crate::shared::types::Bool::B1;
+use crate::shared::types::Float::{F32, F64};
+use crate::shared::types::Int::{I16, I32, I64, I8};
+use crate::shared::Definitions as SharedDefinitions;
+
+use super::recipes::RecipeGroup;
+
+pub(crate) struct PerCpuModeEncodings<'defs> {
+    pub inst_pred_reg: InstructionPredicateRegistry,
+    pub enc64: Vec<Encoding>,
+    recipes: &'defs Recipes,
+}
+
+impl<'defs> PerCpuModeEncodings<'defs> {
+    fn new(recipes: &'defs Recipes) -> Self {
+        Self {
+            inst_pred_reg: InstructionPredicateRegistry::new(),
+            enc64: Vec::new(),
+            recipes,
+        }
+    }
+    fn enc(
+        &self,
+        inst: impl Into<InstSpec>,
+        recipe: EncodingRecipeNumber,
+        bits: u16,
+    ) -> EncodingBuilder {
+        EncodingBuilder::new(inst.into(), recipe, bits)
+    }
+    fn add64(&mut self, encoding: EncodingBuilder) {
+        self.enc64
+            .push(encoding.build(self.recipes, &mut self.inst_pred_reg));
+    }
+}
+
+// All A64 instructions are 32 bits wide. The encbits of most recipes hold the top 10 or 11 bits of
+// the instruction word, which is where AArch64 puts the opcode and the `sf` bit selecting 32-bit
+// or 64-bit operation. The recipes fill in the register and immediate fields. The functions below
+// compute the encbits from the 32-bit variant of an instruction word.
+
+/// Instructions encoded by `put_rrr`, `put_dp3` and `put_csel`: bits 31-21.
+fn bits_31_21(word: u32, sf: u16) -> u16 {
+    ((word >> 21) as u16) | (sf << 10)
+}
+
+/// Instructions encoded by `put_addsub_imm`: bits 31-22.
+fn bits_31_22(word: u32, sf: u16) -> u16 {
+    ((word >> 22) as u16) | (sf << 9)
+}
+
+/// Bitfield and extract instructions, where the `N` bit must match `sf`.
+fn bitfield_bits(word: u32, sf: u16) -> u16 {
+    bits_31_22(word, sf) | sf
+}
+
+/// Data processing (1 and 2 source) instructions are identified by an opcode in bits 15-10.
+fn dp_bits(opcode: u16, sf: u16) -> u16 {
+    assert!(opcode <= 0b11_1111);
+    opcode | (sf << 6)
+}
+
+/// Loads and stores: bits 31-22 of the unscaled offset form, plus the indexing mode.
+fn ldst_bits(word: u32, mode: u16) -> u16 {
+    ((word >> 22) as u16) | (mode << 10)
+}
+
+/// Floating point data processing, 2 source.
+fn fp2_bits(opcode: u16, ftype: u16) -> u16 {
+    assert!(opcode <= 0b1111);
+    opcode | (ftype << 4)
+}
+
+/// Floating point data processing, 1 source.
+fn fp1_bits(opcode: u16, ftype: u16) -> u16 {
+    assert!(opcode <= 0b11_1111);
+    opcode | (ftype << 6)
+}
+
+/// Conversions between floating point and integer registers.
+fn fpint_bits(rmode: u16, opcode: u16, sf: u16, ftype: u16) -> u16 {
+    assert!(rmode <= 0b11 && opcode <= 0b111);
+    opcode | (rmode << 3) | (ftype << 5) | (sf << 7)
+}
+
+// Index modes for loads and stores.
+const UNSCALED: u16 = 0b00;
+const POST_INDEX: u16 = 0b01;
+const PRE_INDEX: u16 = 0b11;
+
+pub(crate) fn define<'defs>(
+    shared_defs: &'defs SharedDefinitions,
+    isa_inst_group: &InstructionGroup,
+    recipes: &'defs RecipeGroup,
+) -> PerCpuModeEncodings<'defs> {
+    // Instructions shorthands.
+    let shared = &shared_defs.instructions;
+
+    let adjust_sp_down_imm = shared.by_name("adjust_sp_down_imm");
+    let adjust_sp_up_imm = shared.by_name("adjust_sp_up_imm");
+    let band = shared.by_name("band");
+    let band_not = shared.by_name("band_not");
+    let bconst = shared.by_name("bconst");
+    let bint = shared.by_name("bint");
+    let bitcast = shared.by_name("bitcast");
+    let bitrev = shared.by_name("bitrev");
+    let bor = shared.by_name("bor");
+    let bor_not = shared.by_name("bor_not");
+    let br_icmp = shared.by_name("br_icmp");
+    let brnz = shared.by_name("brnz");
+    let brz = shared.by_name("brz");
+    let bxor = shared.by_name("bxor");
+    let bxor_not = shared.by_name("bxor_not");
+    let call = shared.by_name("call");
+    let call_indirect = shared.by_name("call_indirect");
+    let ceil = shared.by_name("ceil");
+    let clz = shared.by_name("clz");
+    let copy = shared.by_name("copy");
+    let copy_nop = shared.by_name("copy_nop");
+    let copy_special = shared.by_name("copy_special");
+    let copy_to_ssa = shared.by_name("copy_to_ssa");
+    let ctz = shared.by_name("ctz");
+    let debugtrap = shared.by_name("debugtrap");
+    let fabs = shared.by_name("fabs");
+    let fadd = shared.by_name("fadd");
+    let fcmp = shared.by_name("fcmp");
+    let fcvt_from_sint = shared.by_name("fcvt_from_sint");
+    let fcvt_from_uint = shared.by_name("fcvt_from_uint");
+    let fcvt_to_sint = shared.by_name("fcvt_to_sint");
+    let fcvt_to_sint_sat = shared.by_name("fcvt_to_sint_sat");
+    let fcvt_to_uint = shared.by_name("fcvt_to_uint");
+    let fcvt_to_uint_sat = shared.by_name("fcvt_to_uint_sat");
+    let fdemote = shared.by_name("fdemote");
+    let fdiv = shared.by_name("fdiv");
+    let fill = shared.by_name("fill");
+    let fill_nop = shared.by_name("fill_nop");
+    let floor = shared.by_name("floor");
+    let fmax = shared.by_name("fmax");
+    let fmin = shared.by_name("fmin");
+    let fmul = shared.by_name("fmul");
+    let fneg = shared.by_name("fneg");
+    let fpromote = shared.by_name("fpromote");
+    let fsub = shared.by_name("fsub");
+    let func_addr = shared.by_name("func_addr");
+    let iadd = shared.by_name("iadd");
+    let iadd_imm = shared.by_name("iadd_imm");
+    let icmp = shared.by_name("icmp");
+    let icmp_imm = shared.by_name("icmp_imm");
+    let iconst = shared.by_name("iconst");
+    let imul = shared.by_name("imul");
+    let indirect_jump_table_br = shared.by_name("indirect_jump_table_br");
+    let ireduce = shared.by_name("ireduce");
+    let ishl = shared.by_name("ishl");
+    let ishl_imm = shared.by_name("ishl_imm");
+    let istore8 = shared.by_name("istore8");
+    let istore16 = shared.by_name("istore16");
+    let istore32 = shared.by_name("istore32");
+    let isub = shared.by_name("isub");
+    let jump = shared.by_name("jump");
+    let jump_table_base = shared.by_name("jump_table_base");
+    let jump_table_entry = shared.by_name("jump_table_entry");
+    let load = shared.by_name("load");
+    let nearest = shared.by_name("nearest");
+    let regfill = shared.by_name("regfill");
+    let regmove = shared.by_name("regmove");
+    let regspill = shared.by_name("regspill");
+    let return_ = shared.by_name("return");
+    let rotl = shared.by_name("rotl");
+    let rotl_imm = shared.by_name("rotl_imm");
+    let rotr = shared.by_name("rotr");
+    let rotr_imm = shared.by_name("rotr_imm");
+    let sdiv = shared.by_name("sdiv");
+    let select = shared.by_name("select");
+    let sextend = shared.by_name("sextend");
+    let sload8 = shared.by_name("sload8");
+    let sload16 = shared.by_name("sload16");
+    let sload32 = shared.by_name("sload32");
+    let smulhi = shared.by_name("smulhi");
+    let spill = shared.by_name("spill");
+    let srem = shared.by_name("srem");
+    let sqrt = shared.by_name("sqrt");
+    let sshr = shared.by_name("sshr");
+    let sshr_imm = shared.by_name("sshr_imm");
+    let stack_addr = shared.by_name("stack_addr");
+    let store = shared.by_name("store");
+    let symbol_value = shared.by_name("symbol_value");
+    let trap = shared.by_name("trap");
+    let trunc = shared.by_name("trunc");
+    let udiv = shared.by_name("udiv");
+    let uextend = shared.by_name("uextend");
+    let uload8 = shared.by_name("uload8");
+    let uload16 = shared.by_name("uload16");
+    let uload32 = shared.by_name("uload32");
+    let umulhi = shared.by_name("umulhi");
+    let urem = shared.by_name("urem");
+    let ushr = shared.by_name("ushr");
+    let ushr_imm = shared.by_name("ushr_imm");
+
+    // ISA-specific instructions.
+    let arm64_pop = isa_inst_group.by_name("arm64_pop");
+    let arm64_pop_pair = isa_inst_group.by_name("arm64_pop_pair");
+    let arm64_push = isa_inst_group.by_name("arm64_push");
+    let arm64_push_pair = isa_inst_group.by_name("arm64_push_pair");
+
+    // Recipes shorthands, prefixed with r_.
+    let r_addsub_imm = recipes.by_name("addsub_imm");
+    let r_adjustsp = recipes.by_name("adjustsp");
+    let r_adjustsp_big = recipes.by_name("adjustsp_big");
+    let r_b = recipes.by_name("b");
+    let r_bconst = recipes.by_name("bconst");
+    let r_bricmp = recipes.by_name("bricmp");
+    let r_brk = recipes.by_name("brk");
+    let r_call = recipes.by_name("call");
+    let r_call_r = recipes.by_name("call_r");
+    let r_cbz = recipes.by_name("cbz");
+    let r_copy = recipes.by_name("copy");
+    let r_copysp = recipes.by_name("copysp");
+    let r_copytossa = recipes.by_name("copytossa");
+    let r_csel = recipes.by_name("csel");
+    let r_ctz = recipes.by_name("ctz");
+    let r_dp1 = recipes.by_name("dp1");
+    let r_dp2 = recipes.by_name("dp2");
+    let r_dp3 = recipes.by_name("dp3");
+    let r_extend = recipes.by_name("extend");
+    let r_fcmp = recipes.by_name("fcmp");
+    let r_fcmp2 = recipes.by_name("fcmp2");
+    let r_fcopy = recipes.by_name("fcopy");
+    let r_fcopytossa = recipes.by_name("fcopytossa");
+    let r_fcsel = recipes.by_name("fcsel");
+    let r_fcvt_fg = recipes.by_name("fcvt_fg");
+    let r_fcvt_gf = recipes.by_name("fcvt_gf");
+    let r_fcvt_trap = recipes.by_name("fcvt_trap");
+    let r_ffill = recipes.by_name("ffill");
+    let r_ffillnull = recipes.by_name("ffillnull");
+    let r_fill = recipes.by_name("fill");
+    let r_fillnull = recipes.by_name("fillnull");
+    let r_fld = recipes.by_name("fld");
+    let r_fld_big = recipes.by_name("fld_big");
+    let r_fnaddr = recipes.by_name("fnaddr");
+    let r_fp1 = recipes.by_name("fp1");
+    let r_fp2 = recipes.by_name("fp2");
+    let r_fp_logic = recipes.by_name("fp_logic");
+    let r_fpop = recipes.by_name("fpop");
+    let r_fpop_pair = recipes.by_name("fpop_pair");
+    let r_fpush = recipes.by_name("fpush");
+    let r_fpush_pair = recipes.by_name("fpush_pair");
+    let r_fregfill = recipes.by_name("fregfill");
+    let r_fregspill = recipes.by_name("fregspill");
+    let r_frmov = recipes.by_name("frmov");
+    let r_fspill = recipes.by_name("fspill");
+    let r_fst = recipes.by_name("fst");
+    let r_fst_big = recipes.by_name("fst_big");
+    let r_gvaddr = recipes.by_name("gvaddr");
+    let r_icmp = recipes.by_name("icmp");
+    let r_icmp_imm = recipes.by_name("icmp_imm");
+    let r_indirect_jmp = recipes.by_name("indirect_jmp");
+    let r_jt_base = recipes.by_name("jt_base");
+    let r_jt_entry = recipes.by_name("jt_entry");
+    let r_ld = recipes.by_name("ld");
+    let r_ld_big = recipes.by_name("ld_big");
+    let r_movi = recipes.by_name("movi");
+    let r_movzk32 = recipes.by_name("movzk32");
+    let r_movzk64 = recipes.by_name("movzk64");
+    let r_null = recipes.by_name("null");
+    let r_pop = recipes.by_name("pop");
+    let r_pop_pair = recipes.by_name("pop_pair");
+    let r_push = recipes.by_name("push");
+    let r_push_pair = recipes.by_name("push_pair");
+    let r_regfill = recipes.by_name("regfill");
+    let r_regspill = recipes.by_name("regspill");
+    let r_rem = recipes.by_name("rem");
+    let r_ret = recipes.by_name("ret");
+    let r_rmov = recipes.by_name("rmov");
+    let r_rot_imm = recipes.by_name("rot_imm");
+    let r_rotl = recipes.by_name("rotl");
+    let r_rrr = recipes.by_name("rrr");
+    let r_sdiv = recipes.by_name("sdiv");
+    let r_shift_imm = recipes.by_name("shift_imm");
+    let r_spaddr = recipes.by_name("spaddr");
+    let r_spill = recipes.by_name("spill");
+    let r_st = recipes.by_name("st");
+    let r_st_big = recipes.by_name("st_big");
+    let r_stacknull = recipes.by_name("stacknull");
+    let r_trap = recipes.by_name("trap");
+    let r_udiv = recipes.by_name("udiv");
+
+    // Definitions.
+    let mut e = PerCpuModeEncodings::new(&recipes.recipes);
+
+    // Integer arithmetic and logic.
+    for &(ty, sf) in &[(I32, 0), (I64, 1)] {
+        for &(inst, word) in &[
+            (iadd, 0x0b00_0000),
+            (isub, 0x4b00_0000),
+            (band, 0x0a00_0000),
+            (bor, 0x2a00_0000),
+            (bxor, 0x4a00_0000),
+            (band_not, 0x0a20_0000),
+            (bor_not, 0x2a20_0000),
+            (bxor_not, 0x4a20_0000),
+        ] {
+            e.add64(e.enc(inst.bind(ty), r_rrr, bits_31_21(word, sf)));
+        }
+
+        e.add64(e.enc(iadd_imm.bind(ty), r_addsub_imm, bits_31_22(0x1100_0000, sf)));
+
+        // madd with the zero register as the addend.
+        e.add64(e.enc(imul.bind(ty), r_dp3, bits_31_21(0x1b00_0000, sf)));
+
+        e.add64(e.enc(udiv.bind(ty), r_udiv, dp_bits(0b00_0010, sf)));
+        e.add64(e.enc(sdiv.bind(ty), r_sdiv, dp_bits(0b00_0011, sf)));
+        e.add64(e.enc(urem.bind(ty), r_rem, dp_bits(0b00_0010, sf)));
+        e.add64(e.enc(srem.bind(ty), r_rem, dp_bits(0b00_0011, sf)));
+
+        // Variable shifts only use the low bits of the amount, like the clif instructions, so the
+        // amount can be of any integer type.
+        for &amount_ty in &[I8, I16, I32, I64] {
+            for &(inst, opcode) in &[
+                (ishl, 0b00_1000),
+                (ushr, 0b00_1001),
+                (sshr, 0b00_1010),
+                (rotr, 0b00_1011),
+            ] {
+                e.add64(e.enc(inst.bind(ty).bind(amount_ty), r_dp2, dp_bits(opcode, sf)));
+            }
+            e.add64(e.enc(
+                rotl.bind(ty).bind(amount_ty),
+                r_rotl,
+                dp_bits(0b00_1011, sf),
+            ));
+        }
+
+        // Immediate shifts are ubfm and sbfm. A set bit 10 means shifting left.
+        e.add64(e.enc(
+            ishl_imm.bind(ty),
+            r_shift_imm,
+            bitfield_bits(0x5300_0000, sf) | (1 << 10),
+        ));
+        e.add64(e.enc(
+            ushr_imm.bind(ty),
+            r_shift_imm,
+            bitfield_bits(0x5300_0000, sf),
+        ));
+        e.add64(e.enc(
+            sshr_imm.bind(ty),
+            r_shift_imm,
+            bitfield_bits(0x1300_0000, sf),
+        ));
+        e.add64(e.enc(rotr_imm.bind(ty), r_rot_imm, bitfield_bits(0x1380_0000, sf)));
+        e.add64(e.enc(
+            rotl_imm.bind(ty),
+            r_rot_imm,
+            bitfield_bits(0x1380_0000, sf) | (1 << 10),
+        ));
+
+        e.add64(e.enc(clz.bind(ty), r_dp1, dp_bits(0b00_0100, sf)));
+        e.add64(e.enc(ctz.bind(ty), r_ctz, dp_bits(0b00_0100, sf)));
+        e.add64(e.enc(bitrev.bind(ty), r_dp1, dp_bits(0b00_0000, sf)));
+
+        // Comparisons are subs with the zero register as destination, followed by a cset.
+        e.add64(e.enc(icmp.bind(ty), r_icmp, bits_31_21(0x6b00_0000, sf)));
+        e.add64(e.enc(icmp_imm.bind(ty), r_icmp_imm, bits_31_22(0x7100_0000, sf)));
+
+        e.add64(e.enc(
+            select.bind(ty).bind(B1),
+            r_csel,
+            bits_31_21(0x1a80_0000, sf),
+        ));
+    }
+
+    // The high half of a 64-bit multiplication.
+    e.add64(e.enc(smulhi.bind(I64), r_dp3, bits_31_21(0x9b40_0000, 0)));
+    e.add64(e.enc(umulhi.bind(I64), r_dp3, bits_31_21(0x9bc0_0000, 0)));
+
+    // Boolean logic.
+    for &(inst, word) in &[(band, 0x0a00_0000), (bor, 0x2a00_0000), (bxor, 0x4a00_0000)] {
+        e.add64(e.enc(inst.bind(B1), r_rrr, bits_31_21(word, 0)));
+    }
+
+    // Integer constants. The shorter forms come first so they get picked when the predicate
+    // allows it.
+    e.add64(e.enc(iconst.bind(I32), r_movi, 0));
+    e.add64(e.enc(iconst.bind(I32), r_movzk32, 0));
+    e.add64(e.enc(iconst.bind(I64), r_movi, 1));
+    e.add64(e.enc(iconst.bind(I64), r_movzk64, 1));
+    e.add64(e.enc(bconst.bind(B1), r_bconst, 0));
+
+    // Extensions and reductions. Writing a 32-bit register always clears the upper half of the
+    // 64-bit register, so zero-extending from `i32` is a 32-bit `mov`.
+    let mov32 = bits_31_21(0x2a00_0000, 0);
+    e.add64(e.enc(uextend.bind(I64).bind(I32), r_copy, mov32));
+    for &(from, imms) in &[(I8, 7), (I16, 15)] {
+        for &to in &[I32, I64] {
+            e.add64(e.enc(
+                uextend.bind(to).bind(from),
+                r_extend,
+                bitfield_bits(0x5300_0000, 0) | (imms << 10),
+            ));
+        }
+        e.add64(e.enc(
+            sextend.bind(I32).bind(from),
+            r_extend,
+            bitfield_bits(0x1300_0000, 0) | (imms << 10),
+        ));
+    }
+    for &(from, imms) in &[(I8, 7), (I16, 15), (I32, 31)] {
+        e.add64(e.enc(
+            sextend.bind(I64).bind(from),
+            r_extend,
+            bitfield_bits(0x1300_0000, 1) | (imms << 10),
+        ));
+    }
+
+    // 32-bit and narrower instructions ignore the upper bits of a register, so reductions are
+    // free.
+    e.add64(e.enc(ireduce.bind(I32).bind(I64), r_null, 0));
+    for &from in &[I32, I64] {
+        e.add64(e.enc(ireduce.bind(I8).bind(from), r_null, 0));
+        e.add64(e.enc(ireduce.bind(I16).bind(from), r_null, 0));
+    }
+
+    e.add64(e.enc(bint.bind(I32).bind(B1), r_copy, mov32));
+    e.add64(e.enc(bint.bind(I64).bind(B1), r_copy, mov32));
+
+    // Floating point arithmetic.
+    for &(ty, ftype) in &[(F32, 0), (F64, 1)] {
+        for &(inst, opcode) in &[
+            (fmul, 0b0000),
+            (fdiv, 0b0001),
+            (fadd, 0b0010),
+            (fsub, 0b0011),
+            (fmax, 0b0100),
+            (fmin, 0b0101),
+        ] {
+            e.add64(e.enc(inst.bind(ty), r_fp2, fp2_bits(opcode, ftype)));
+        }
+
+        for &(inst, opcode) in &[
+            (fabs, 0b00_0001),
+            (fneg, 0b00_0010),
+            (sqrt, 0b00_0011),
+            (nearest, 0b00_1000),
+            (ceil, 0b00_1001),
+            (floor, 0b00_1010),
+            (trunc, 0b00_1011),
+        ] {
+            e.add64(e.enc(inst.bind(ty), r_fp1, fp1_bits(opcode, ftype)));
+        }
+
+        // Bitwise operations, used to legalize `fcopysign`. These are the `8b` vector forms of
+        // `and`, `bic`, `orr`, `orn` and `eor`.
+        for &(inst, bits) in &[
+            (band, 0x071),
+            (band_not, 0x073),
+            (bor, 0x075),
+            (bor_not, 0x077),
+            (bxor, 0x171),
+        ] {
+            e.add64(e.enc(inst.bind(ty), r_fp_logic, bits));
+        }
+
+        e.add64(e.enc(fcmp.bind(ty), r_fcmp, ftype));
+        e.add64(e.enc(fcmp.bind(ty), r_fcmp2, ftype));
+
+        e.add64(e.enc(select.bind(ty).bind(B1), r_fcsel, ftype));
+
+        // Conversions to and from integers. The AArch64 float-to-int conversions saturate, and
+        // turn NaN into 0, which is exactly what the `_sat` variants need.
+        for &(ity, sf) in &[(I32, 0), (I64, 1)] {
+            e.add64(e.enc(
+                fcvt_from_sint.bind(ty).bind(ity),
+                r_fcvt_gf,
+                fpint_bits(0b00, 0b010, sf, ftype),
+            ));
+            e.add64(e.enc(
+                fcvt_from_uint.bind(ty).bind(ity),
+                r_fcvt_gf,
+                fpint_bits(0b00, 0b011, sf, ftype),
+            ));
+            e.add64(e.enc(
+                fcvt_to_sint.bind(ity).bind(ty),
+                r_fcvt_trap,
+                fpint_bits(0b11, 0b000, sf, ftype),
+            ));
+            e.add64(e.enc(
+                fcvt_to_uint.bind(ity).bind(ty),
+                r_fcvt_trap,
+                fpint_bits(0b11, 0b001, sf, ftype),
+            ));
+            e.add64(e.enc(
+                fcvt_to_sint_sat.bind(ity).bind(ty),
+                r_fcvt_fg,
+                fpint_bits(0b11, 0b000, sf, ftype),
+            ));
+            e.add64(e.enc(
+                fcvt_to_uint_sat.bind(ity).bind(ty),
+                r_fcvt_fg,
+                fpint_bits(0b11, 0b001, sf, ftype),
+            ));
+        }
+    }
+
+    e.add64(e.enc(fpromote.bind(F64).bind(F32), r_fp1, fp1_bits(0b00_0101, 0)));
+    e.add64(e.enc(fdemote.bind(F32).bind(F64), r_fp1, fp1_bits(0b00_0100, 1)));
+
+    // Bit casts are fmov between register banks.
+    for &(ity, fty, sf, ftype) in &[(I32, F32, 0, 0), (I64, F64, 1, 1)] {
+        e.add64(e.enc(
+            bitcast.bind(ity).bind(fty),
+            r_fcvt_fg,
+            fpint_bits(0b00, 0b110, sf, ftype),
+        ));
+        e.add64(e.enc(
+            bitcast.bind(fty).bind(ity),
+            r_fcvt_gf,
+            fpint_bits(0b00, 0b111, sf, ftype),
+        ));
+    }
+
+    // Loads and stores. Small offsets fit in the unscaled form, anything else goes through the
+    // scratch register.
+    for (inst, word) in [
+        (load.bind(I32).bind(Any), 0xb840_0000),
+        (load.bind(I64).bind(Any), 0xf840_0000),
+        (uload8.bind(I32).bind(Any), 0x3840_0000),
+        (uload8.bind(I64).bind(Any), 0x3840_0000),
+        (sload8.bind(I32).bind(Any), 0x38c0_0000),
+        (sload8.bind(I64).bind(Any), 0x3880_0000),
+        (uload16.bind(I32).bind(Any), 0x7840_0000),
+        (uload16.bind(I64).bind(Any), 0x7840_0000),
+        (sload16.bind(I32).bind(Any), 0x78c0_0000),
+        (sload16.bind(I64).bind(Any), 0x7880_0000),
+        (uload32.bind(I64), 0xb840_0000),
+        (sload32.bind(I64), 0xb880_0000),
+    ]
+    .iter()
+    {
+        e.add64(e.enc(inst, r_ld, ldst_bits(*word, UNSCALED)));
+        e.add64(e.enc(inst, r_ld_big, ldst_bits(*word, UNSCALED)));
+    }
+
+    for (inst, word) in [
+        (store.bind(I32).bind(Any), 0xb800_0000),
+        (store.bind(I64).bind(Any), 0xf800_0000),
+        (istore8.bind(I32).bind(Any), 0x3800_0000),
+        (istore8.bind(I64).bind(Any), 0x3800_0000),
+        (istore16.bind(I32).bind(Any), 0x7800_0000),
+        (istore16.bind(I64).bind(Any), 0x7800_0000),
+        (istore32.bind(I64).bind(Any), 0xb800_0000),
+    ]
+    .iter()
+    {
+        e.add64(e.enc(inst, r_st, ldst_bits(*word, UNSCALED)));
+        e.add64(e.enc(inst, r_st_big, ldst_bits(*word, UNSCALED)));
+    }
+
+    for &(ty, ld, st) in &[
+        (F32, 0xbc40_0000, 0xbc00_0000),
+        (F64, 0xfc40_0000, 0xfc00_0000),
+    ] {
+        let ld_inst = load.bind(ty).bind(Any);
+        let st_inst = store.bind(ty).bind(Any);
+        e.add64(e.enc(&ld_inst, r_fld, ldst_bits(ld, UNSCALED)));
+        e.add64(e.enc(&ld_inst, r_fld_big, ldst_bits(ld, UNSCALED)));
+        e.add64(e.enc(&st_inst, r_fst, ldst_bits(st, UNSCALED)));
+        e.add64(e.enc(&st_inst, r_fst_big, ldst_bits(st, UNSCALED)));
+    }
+
+    // Spills, fills and register copies. Booleans and narrow integers are spilled with their
+    // own size since their stack slots are not any larger.
+    let gpr_types: [(LaneType, u32, u32, u16); 5] = [
+        (B1.into(), 0x3800_0000, 0x3840_0000, 0),
+        (I8.into(), 0x3800_0000, 0x3840_0000, 0),
+        (I16.into(), 0x7800_0000, 0x7840_0000, 0),
+        (I32.into(), 0xb800_0000, 0xb840_0000, 0),
+        (I64.into(), 0xf800_0000, 0xf840_0000, 1),
+    ];
+    for (ty, st, ld, sf) in gpr_types.iter().copied() {
+        let st = ldst_bits(st, UNSCALED);
+        let ld = ldst_bits(ld, UNSCALED);
+        e.add64(e.enc(spill.bind(ty), r_spill, st));
+        e.add64(e.enc(regspill.bind(ty), r_regspill, st));
+        e.add64(e.enc(fill.bind(ty), r_fill, ld));
+        e.add64(e.enc(regfill.bind(ty), r_regfill, ld));
+        e.add64(e.enc(fill_nop.bind(ty), r_fillnull, 0));
+        e.add64(e.enc(copy_nop.bind(ty), r_stacknull, 0));
+
+        let mov = bits_31_21(0x2a00_0000, sf);
+        e.add64(e.enc(copy.bind(ty), r_copy, mov));
+        e.add64(e.enc(regmove.bind(ty), r_rmov, mov));
+        e.add64(e.enc(copy_to_ssa.bind(ty), r_copytossa, mov));
+    }
+
+    for &(ty, st, ld, ftype) in &[
+        (F32, 0xbc00_0000, 0xbc40_0000, 0),
+        (F64, 0xfc00_0000, 0xfc40_0000, 1),
+    ] {
+        let st = ldst_bits(st, UNSCALED);
+        let ld = ldst_bits(ld, UNSCALED);
+        e.add64(e.enc(spill.bind(ty), r_fspill, st));
+        e.add64(e.enc(regspill.bind(ty), r_fregspill, st));
+        e.add64(e.enc(fill.bind(ty), r_ffill, ld));
+        e.add64(e.enc(regfill.bind(ty), r_fregfill, ld));
+        e.add64(e.enc(fill_nop.bind(ty), r_ffillnull, 0));
+        e.add64(e.enc(copy_nop.bind(ty), r_stacknull, 0));
+
+        let fmov = fp1_bits(0b00_0000, ftype);
+        e.add64(e.enc(copy.bind(ty), r_fcopy, fmov));
+        e.add64(e.enc(regmove.bind(ty), r_frmov, fmov));
+        e.add64(e.enc(copy_to_ssa.bind(ty), r_fcopytossa, fmov));
+    }
+
+    // Addresses.
+    let add_imm64 = bits_31_22(0x1100_0000, 1);
+    let sub_imm64 = bits_31_22(0x5100_0000, 1);
+    e.add64(e.enc(stack_addr.bind(I64), r_spaddr, add_imm64));
+    e.add64(e.enc(func_addr.bind(I64), r_fnaddr, 0));
+    e.add64(e.enc(symbol_value.bind(I64), r_gvaddr, 0));
+
+    // Prologue and epilogue support.
+    e.add64(e.enc(copy_special, r_copysp, add_imm64));
+    e.add64(e.enc(adjust_sp_down_imm, r_adjustsp, sub_imm64));
+    e.add64(e.enc(adjust_sp_down_imm, r_adjustsp_big, sub_imm64));
+    e.add64(e.enc(adjust_sp_up_imm, r_adjustsp, add_imm64));
+    e.add64(e.enc(adjust_sp_up_imm, r_adjustsp_big, add_imm64));
+
+    // str / ldr of a single register and stp / ldp of a pair, always moving the stack pointer by
+    // 16 bytes.
+    e.add64(e.enc(
+        arm64_push.bind(I64),
+        r_push,
+        ldst_bits(0xf800_0000, PRE_INDEX),
+    ));
+    e.add64(e.enc(
+        arm64_pop.bind(I64),
+        r_pop,
+        ldst_bits(0xf840_0000, POST_INDEX),
+    ));
+    e.add64(e.enc(
+        arm64_push.bind(F64),
+        r_fpush,
+        ldst_bits(0xfc00_0000, PRE_INDEX),
+    ));
+    e.add64(e.enc(
+        arm64_pop.bind(F64),
+        r_fpop,
+        ldst_bits(0xfc40_0000, POST_INDEX),
+    ));
+    e.add64(e.enc(
+        arm64_push_pair.bind(I64),
+        r_push_pair,
+        ldst_bits(0xa980_0000, 0),
+    ));
+    e.add64(e.enc(
+        arm64_pop_pair.bind(I64),
+        r_pop_pair,
+        ldst_bits(0xa8c0_0000, 0),
+    ));
+    e.add64(e.enc(
+        arm64_push_pair.bind(F64),
+        r_fpush_pair,
+        ldst_bits(0x6d80_0000, 0),
+    ));
+    e.add64(e.enc(
+        arm64_pop_pair.bind(F64),
+        r_fpop_pair,
+        ldst_bits(0x6cc0_0000, 0),
+    ));
+
+    // Control flow.
+    e.add64(e.enc(jump, r_b, 0));
+
+    let branch_types: [(LaneType, u16); 3] = [(B1.into(), 0), (I32.into(), 0), (I64.into(), 1)];
+    for (ty, sf) in branch_types.iter().copied() {
+        e.add64(e.enc(brz.bind(ty), r_cbz, 0x34 | (sf << 7)));
+        e.add64(e.enc(brnz.bind(ty), r_cbz, 0x35 | (sf << 7)));
+    }
+    e.add64(e.enc(br_icmp.bind(I32), r_bricmp, bits_31_21(0x6b00_0000, 0)));
+    e.add64(e.enc(br_icmp.bind(I64), r_bricmp, bits_31_21(0x6b00_0000, 1)));
+
+    e.add64(e.enc(call, r_call, 0));
+    // blr, br and ret.
+    e.add64(e.enc(call_indirect.bind(I64), r_call_r, 0xd63f));
+    e.add64(e.enc(return_, r_ret, 0xd65f));
+
+    e.add64(e.enc(jump_table_base.bind(I64), r_jt_base, 0));
+    e.add64(e.enc(
+        jump_table_entry.bind(I64),
+        r_jt_entry,
+        ldst_bits(0xb880_0000, UNSCALED),
+    ));
+    e.add64(e.enc(indirect_jump_table_br.bind(I64), r_indirect_jmp, 0xd61f));
+
+    e.add64(e.enc(trap, r_trap, 0));
+    e.add64(e.enc(debugtrap, r_brk, 0));
+
+    e
+}
@@ -0,0 +1,1057 @@
+use std::collections::HashMap;
+
+use crate::cdsl::ast::Literal;
+use crate::cdsl::formats::InstructionFormat;
+use crate::cdsl::instructions::InstructionPredicate;
+use crate::cdsl::recipes::{EncodingRecipeBuilder, EncodingRecipeNumber, Recipes, Stack};
+use crate::cdsl::regs::IsaRegs;
+use crate::shared::Definitions as SharedDefinitions;
+
+/// An helper to create recipes and use them when defining the ARM64 encodings.
+pub(crate) struct RecipeGroup {
+    /// The actual list of recipes explicitly created in this file.
+    pub recipes: Recipes,
+
+    /// Provides fast lookup from a name to an encoding recipe.
+    name_to_recipe: HashMap<String, EncodingRecipeNumber>,
+}
+
+impl RecipeGroup {
+    fn new() -> Self {
+        Self {
+            recipes: Recipes::new(),
+            name_to_recipe: HashMap::new(),
+        }
+    }
+
+    fn push(&mut self, builder: EncodingRecipeBuilder) {
+        assert!(
+            self.name_to_recipe.get(&builder.name).is_none(),
+            format!("arm64 recipe '{}' created twice", builder.name)
+        );
+        let name = builder.name.clone();
+        let number = self.recipes.push(builder.build());
+        self.name_to_recipe.insert(name, number);
+    }
+
+    pub fn by_name(&self, name: &str) -> EncodingRecipeNumber {
+        *self
+            .name_to_recipe
+            .get(name)
+            .unwrap_or_else(|| panic!("unknown arm64 recipe name {}", name))
+    }
+
+    pub fn collect(self) -> Recipes {
+        self.recipes
+    }
+}
+
+/// Returns a predicate checking that the "cond" field of the instruction is one of the listed
+/// floating point condition codes.
+fn floatccs_predicate(
+    supported_cc: &[Literal],
+    format: &InstructionFormat,
+) -> InstructionPredicate {
+    supported_cc
+        .iter()
+        .fold(InstructionPredicate::new(), |pred, literal| {
+            pred.or(InstructionPredicate::new_is_field_equal(
+                format,
+                "cond",
+                literal.to_rust_code(),
+            ))
+        })
+}
+
+pub(crate) fn define(shared_defs: &SharedDefinitions, regs: &IsaRegs) -> RecipeGroup {
+    let formats = &shared_defs.formats;
+
+    // Register classes shorthands.
+    let gpr = regs.class_by_name("GPR");
+    let fpr = regs.class_by_name("FPR");
+
+    // Definitions.
+    let mut recipes = RecipeGroup::new();
+
+    // All floating point condition codes except `one` and `ueq` map to a single AArch64 condition
+    // after an `fcmp`. The remaining two need a pair of conditional instructions.
+    let floatcc = &shared_defs.imm.floatcc;
+    let single_floatccs: Vec<Literal> = [
+        "ord", "uno", "eq", "ne", "lt", "le", "gt", "ge", "ult", "ule", "ugt", "uge",
+    ]
+    .iter()
+    .map(|name| Literal::enumerator_for(floatcc, name))
+    .collect();
+    let double_floatccs: Vec<Literal> = ["one", "ueq"]
+        .iter()
+        .map(|name| Literal::enumerator_for(floatcc, name))
+        .collect();
+
+    // Integer data processing.
+
+    // Three-register instructions in the "shifted register" class, with a zero shift: add, sub,
+    // and the logical operations.
+    recipes.push(
+        EncodingRecipeBuilder::new("rrr", &formats.binary, 4)
+            .operands_in(vec![gpr, gpr])
+            .operands_out(vec![gpr])
+            .emit("put_rrr(bits, in_reg0, in_reg1, out_reg0, sink);"),
+    );
+
+    // Data processing (2 source): variable shifts and rotates.
+    recipes.push(
+        EncodingRecipeBuilder::new("dp2", &formats.binary, 4)
+            .operands_in(vec![gpr, gpr])
+            .operands_out(vec![gpr])
+            .emit("put_dp2(bits, in_reg0, in_reg1, out_reg0, sink);"),
+    );
+
+    // There is no rotate left instruction, so negate the amount into the scratch register and
+    // rotate right instead.
+    recipes.push(
+        EncodingRecipeBuilder::new("rotl", &formats.binary, 8)
+            .operands_in(vec![gpr, gpr])
+            .operands_out(vec![gpr])
+            .emit(
+                r#"
+                    // sub x16, xzr, rm
+                    put_rrr(0x658, 31, in_reg1, 16, sink);
+                    put_dp2(bits, in_reg0, 16, out_reg0, sink);
+                "#,
+            ),
+    );
+
+    // Data processing (3 source) with the addend fixed to the zero register: mul, smulh, umulh.
+    recipes.push(
+        EncodingRecipeBuilder::new("dp3", &formats.binary, 4)
+            .operands_in(vec![gpr, gpr])
+            .operands_out(vec![gpr])
+            .emit("put_dp3(bits, in_reg0, in_reg1, 31, out_reg0, sink);"),
+    );
+
+    // Unsigned division. AArch64 division never traps, so we need an explicit check for a zero
+    // divisor.
+    recipes.push(
+        EncodingRecipeBuilder::new("udiv", &formats.binary, 12)
+            .operands_in(vec![gpr, gpr])
+            .operands_out(vec![gpr])
+            .emit(
+                r#"
+                    put_cbz(0x35 | ((bits >> 6) << 7), in_reg1, 8, sink);
+                    sink.trap(TrapCode::IntegerDivisionByZero, func.srclocs[inst]);
+                    put_udf(sink);
+                    put_dp2(bits, in_reg0, in_reg1, out_reg0, sink);
+                "#,
+            ),
+    );
+
+    // Signed division also needs to trap when computing `INT_MIN / -1`.
+    recipes.push(
+        EncodingRecipeBuilder::new("sdiv", &formats.binary, 28)
+            .operands_in(vec![gpr, gpr])
+            .operands_out(vec![gpr])
+            .emit(
+                r#"
+                    let sf = bits >> 6;
+                    put_cbz(0x35 | (sf << 7), in_reg1, 8, sink);
+                    sink.trap(TrapCode::IntegerDivisionByZero, func.srclocs[inst]);
+                    put_udf(sink);
+                    // cmn rm, #1
+                    put_addsub_imm((sf << 9) | 0x0c4, in_reg1, 1, 31, sink);
+                    // ccmp rn, #1, #0, eq: V is only set when rm == -1 and rn == INT_MIN.
+                    put_ccmp_imm(sf, in_reg0, 1, 0, COND_EQ, sink);
+                    // b.vc over the trap.
+                    put_bcond(COND_VC, 8, sink);
+                    sink.trap(TrapCode::IntegerOverflow, func.srclocs[inst]);
+                    put_udf(sink);
+                    put_dp2(bits, in_reg0, in_reg1, out_reg0, sink);
+                "#,
+            ),
+    );
+
+    // Remainders are computed from the quotient with an msub. The quotient goes in the scratch
+    // register. `INT_MIN % -1` doesn't need a check since sdiv produces `INT_MIN` and the msub
+    // then yields the expected 0.
+    recipes.push(
+        EncodingRecipeBuilder::new("rem", &formats.binary, 16)
+            .operands_in(vec![gpr, gpr])
+            .operands_out(vec![gpr])
+            .emit(
+                r#"
+                    let sf = bits >> 6;
+                    put_cbz(0x35 | (sf << 7), in_reg1, 8, sink);
+                    sink.trap(TrapCode::IntegerDivisionByZero, func.srclocs[inst]);
+                    put_udf(sink);
+                    put_dp2(bits, in_reg0, in_reg1, 16, sink);
+                    // msub rd, x16, rm, rn
+                    put_dp3((1 << 11) | (sf << 10) | 0x0d8, 16, in_reg1, in_reg0, out_reg0, sink);
+                "#,
+            ),
+    );
+
+    // Data processing (1 source): clz and rbit.
+    recipes.push(
+        EncodingRecipeBuilder::new("dp1", &formats.unary, 4)
+            .operands_in(vec![gpr])
+            .operands_out(vec![gpr])
+            .emit("put_dp1(bits, in_reg0, out_reg0, sink);"),
+    );
+
+    // Count trailing zeros as the leading zeros of the bit-reversed input.
+    recipes.push(
+        EncodingRecipeBuilder::new("ctz", &formats.unary, 8)
+            .operands_in(vec![gpr])
+            .operands_out(vec![gpr])
+            .emit(
+                r#"
+                    // rbit rd, rn
+                    put_dp1(bits & !0x3f, in_reg0, out_reg0, sink);
+                    // clz rd, rd
+                    put_dp1(bits, out_reg0, out_reg0, sink);
+                "#,
+            ),
+    );
+
+    // Add/sub with a 12-bit immediate. Negative immediates flip the operation, and -4096 uses
+    // the shifted form.
+    recipes.push(
+        EncodingRecipeBuilder::new("addsub_imm", &formats.binary_imm, 4)
+            .operands_in(vec![gpr])
+            .operands_out(vec![gpr])
+            .inst_predicate(InstructionPredicate::new_is_signed_int(
+                &*formats.binary_imm,
+                "imm",
+                13,
+                0,
+            ))
+            .emit("put_addsub_imm(bits, in_reg0, imm.into(), out_reg0, sink);"),
+    );
+
+    // Shifts by an immediate are aliases of the bitfield move instructions.
+    recipes.push(
+        EncodingRecipeBuilder::new("shift_imm", &formats.binary_imm, 4)
+            .operands_in(vec![gpr])
+            .operands_out(vec![gpr])
+            .emit("put_shift_imm(bits, in_reg0, imm.into(), out_reg0, sink);"),
+    );
+
+    // Rotates by an immediate are `extr` with both sources equal.
+    recipes.push(
+        EncodingRecipeBuilder::new("rot_imm", &formats.binary_imm, 4)
+            .operands_in(vec![gpr])
+            .operands_out(vec![gpr])
+            .emit("put_rot_imm(bits, in_reg0, imm.into(), out_reg0, sink);"),
+    );
+
+    // Sign and zero extensions are also bitfield moves.
+    recipes.push(
+        EncodingRecipeBuilder::new("extend", &formats.unary, 4)
+            .operands_in(vec![gpr])
+            .operands_out(vec![gpr])
+            .emit("put_extend(bits, in_reg0, out_reg0, sink);"),
+    );
+
+    // Integer constants.
+
+    // A 16-bit constant, possibly inverted, can be materialized by a single movz or movn.
+    recipes.push(
+        EncodingRecipeBuilder::new("movi", &formats.unary_imm, 4)
+            .operands_out(vec![gpr])
+            .inst_predicate(InstructionPredicate::new_is_signed_int(
+                &*formats.unary_imm,
+                "imm",
+                17,
+                0,
+            ))
+            .emit(
+                r#"
+                    let imm: i64 = imm.into();
+                    if imm < 0 {
+                        put_movn(bits, (!imm) as u16, 0, out_reg0, sink);
+                    } else {
+                        put_movz(bits, imm as u16, 0, out_reg0, sink);
+                    }
+                "#,
+            ),
+    );
+
+    // Arbitrary 32-bit constants: movz + movk.
+    recipes.push(
+        EncodingRecipeBuilder::new("movzk32", &formats.unary_imm, 8)
+            .operands_out(vec![gpr])
+            .emit(
+                r#"
+                    let imm: i64 = imm.into();
+                    put_movz(bits, imm as u16, 0, out_reg0, sink);
+                    put_movk(bits, (imm >> 16) as u16, 1, out_reg0, sink);
+                "#,
+            ),
+    );
+
+    // Arbitrary 64-bit constants: movz + 3 movk.
+    recipes.push(
+        EncodingRecipeBuilder::new("movzk64", &formats.unary_imm, 16)
+            .operands_out(vec![gpr])
+            .emit(
+                r#"
+                    let imm: i64 = imm.into();
+                    put_movz(bits, imm as u16, 0, out_reg0, sink);
+                    for hw in 1..4 {
+                        put_movk(bits, (imm >> (16 * hw)) as u16, hw, out_reg0, sink);
+                    }
+                "#,
+            ),
+    );
+
+    recipes.push(
+        EncodingRecipeBuilder::new("bconst", &formats.unary_bool, 4)
+            .operands_out(vec![gpr])
+            .emit("put_movz(bits, imm as u16, 0, out_reg0, sink);"),
+    );
+
+    // Comparisons.
+
+    // Integer comparison: cmp + cset.
+    recipes.push(
+        EncodingRecipeBuilder::new("icmp", &formats.int_compare, 8)
+            .operands_in(vec![gpr, gpr])
+            .operands_out(vec![gpr])
+            .emit(
+                r#"
+                    put_rrr(bits, in_reg0, in_reg1, 31, sink);
+                    put_cset(icc2cond(cond), out_reg0, sink);
+                "#,
+            ),
+    );
+
+    // Integer comparison with an immediate, using cmn for negative immediates.
+    recipes.push(
+        EncodingRecipeBuilder::new("icmp_imm", &formats.int_compare_imm, 8)
+            .operands_in(vec![gpr])
+            .operands_out(vec![gpr])
+            .inst_predicate(InstructionPredicate::new_is_signed_int(
+                &*formats.int_compare_imm,
+                "imm",
+                13,
+                0,
+            ))
+            .emit(
+                r#"
+                    put_addsub_imm(bits, in_reg0, imm.into(), 31, sink);
+                    put_cset(icc2cond(cond), out_reg0, sink);
+                "#,
+            ),
+    );
+
+    // Floating point comparison for the condition codes with a direct AArch64 equivalent.
+    recipes.push(
+        EncodingRecipeBuilder::new("fcmp", &formats.float_compare, 8)
+            .operands_in(vec![fpr, fpr])
+            .operands_out(vec![gpr])
+            .inst_predicate(floatccs_predicate(
+                &single_floatccs,
+                &*formats.float_compare,
+            ))
+            .emit(
+                r#"
+                    put_fcmp(bits, in_reg0, in_reg1, sink);
+                    put_cset(fcc2cond(cond), out_reg0, sink);
+                "#,
+            ),
+    );
+
+    // Floating point comparison for `one` and `ueq`, which are the union of two conditions.
+    recipes.push(
+        EncodingRecipeBuilder::new("fcmp2", &formats.float_compare, 12)
+            .operands_in(vec![fpr, fpr])
+            .operands_out(vec![gpr])
+            .inst_predicate(floatccs_predicate(
+                &double_floatccs,
+                &*formats.float_compare,
+            ))
+            .emit(
+                r#"
+                    let (first, second) = match cond {
+                        FloatCC::OrderedNotEqual => (COND_MI, COND_GT),
+                        FloatCC::UnorderedOrEqual => (COND_EQ, COND_VS),
+                        _ => panic!("{} is a single condition", cond),
+                    };
+                    put_fcmp(bits, in_reg0, in_reg1, sink);
+                    put_cset(first, out_reg0, sink);
+                    // csinc rd, rd, xzr, !second
+                    put_csel((1 << 11) | 0x0d4, out_reg0, 31, second ^ 1, out_reg0, sink);
+                "#,
+            ),
+    );
+
+    // Integer select on a `b1` condition: cmp + csel.
+    recipes.push(
+        EncodingRecipeBuilder::new("csel", &formats.ternary, 8)
+            .operands_in(vec![gpr, gpr, gpr])
+            .operands_out(vec![gpr])
+            .emit(
+                r#"
+                    // cmp wc, #0
+                    put_addsub_imm(0x1c4, in_reg0, 0, 31, sink);
+                    put_csel(bits, in_reg1, in_reg2, COND_NE, out_reg0, sink);
+                "#,
+            ),
+    );
+
+    // Floating point select on a `b1` condition: cmp + fcsel.
+    recipes.push(
+        EncodingRecipeBuilder::new("fcsel", &formats.ternary, 8)
+            .operands_in(vec![gpr, fpr, fpr])
+            .operands_out(vec![fpr])
+            .emit(
+                r#"
+                    // cmp wc, #0
+                    put_addsub_imm(0x1c4, in_reg0, 0, 31, sink);
+                    put_fcsel(bits, in_reg1, in_reg2, COND_NE, out_reg0, sink);
+                "#,
+            ),
+    );
+
+    // Floating point.
+
+    // Floating point data processing (2 source).
+    recipes.push(
+        EncodingRecipeBuilder::new("fp2", &formats.binary, 4)
+            .operands_in(vec![fpr, fpr])
+            .operands_out(vec![fpr])
+            .emit("put_fp2(bits, in_reg0, in_reg1, out_reg0, sink);"),
+    );
+
+    // Floating point data processing (1 source).
+    recipes.push(
+        EncodingRecipeBuilder::new("fp1", &formats.unary, 4)
+            .operands_in(vec![fpr])
+            .operands_out(vec![fpr])
+            .emit("put_fp1(bits, in_reg0, out_reg0, sink);"),
+    );
+
+    // Conversions from a general purpose register to a floating point register.
+    recipes.push(
+        EncodingRecipeBuilder::new("fcvt_gf", &formats.unary, 4)
+            .operands_in(vec![gpr])
+            .operands_out(vec![fpr])
+            .emit("put_fpint(bits, in_reg0, out_reg0, sink);"),
+    );
+
+    // Conversions from a floating point register to a general purpose register.
+    recipes.push(
+        EncodingRecipeBuilder::new("fcvt_fg", &formats.unary, 4)
+            .operands_in(vec![fpr])
+            .operands_out(vec![gpr])
+            .emit("put_fpint(bits, in_reg0, out_reg0, sink);"),
+    );
+
+    // Trapping conversions from floating point to integer. The conversion itself saturates, so
+    // the out of range case is detected through the invalid operation flag in `fpsr`.
+    recipes.push(
+        EncodingRecipeBuilder::new("fcvt_trap", &formats.unary, 32)
+            .operands_in(vec![fpr])
+            .operands_out(vec![gpr])
+            .emit(
+                r#"
+                    // fcmp sn, sn is only unordered for a NaN.
+                    put_fcmp((bits >> 5) & 3, in_reg0, in_reg0, sink);
+                    put_bcond(COND_VC, 8, sink);
+                    sink.trap(TrapCode::BadConversionToInteger, func.srclocs[inst]);
+                    put_udf(sink);
+                    // msr fpsr, xzr
+                    put_sysreg(0xd51b_4420, 31, sink);
+                    put_fpint(bits, in_reg0, out_reg0, sink);
+                    // mrs x16, fpsr
+                    put_sysreg(0xd53b_4420, 16, sink);
+                    // tbz w16, #0, over the trap: bit 0 is the invalid operation flag.
+                    put_tbz(16, 0, 8, sink);
+                    sink.trap(TrapCode::IntegerOverflow, func.srclocs[inst]);
+                    put_udf(sink);
+                "#,
+            ),
+    );
+
+    // Bitwise operations on floating point values, using the 64-bit vector forms.
+    recipes.push(
+        EncodingRecipeBuilder::new("fp_logic", &formats.binary, 4)
+            .operands_in(vec![fpr, fpr])
+            .operands_out(vec![fpr])
+            .emit("put_vlogic(bits, in_reg0, in_reg1, out_reg0, sink);"),
+    );
+
+    // Memory accesses.
+
+    // Loads with a signed 9-bit unscaled offset: ldur and friends.
+    recipes.push(
+        EncodingRecipeBuilder::new("ld", &formats.load, 4)
+            .operands_in(vec![gpr])
+            .operands_out(vec![gpr])
+            .inst_predicate(InstructionPredicate::new_is_signed_int(
+                &*formats.load,
+                "offset",
+                9,
+                0,
+            ))
+            .emit(
+                r#"
+                    if !flags.notrap() {
+                        sink.trap(TrapCode::HeapOutOfBounds, func.srclocs[inst]);
+                    }
+                    put_ldst_imm9(bits, in_reg0, offset.into(), out_reg0, sink);
+                "#,
+            ),
+    );
+
+    // Loads with a 32-bit offset, materialized in the scratch register.
+    recipes.push(
+        EncodingRecipeBuilder::new("ld_big", &formats.load, 12)
+            .operands_in(vec![gpr])
+            .operands_out(vec![gpr])
+            .emit(
+                r#"
+                    let offset: i32 = offset.into();
+                    put_movz(0, offset as u16, 0, 16, sink);
+                    put_movk(0, (offset >> 16) as u16, 1, 16, sink);
+                    if !flags.notrap() {
+                        sink.trap(TrapCode::HeapOutOfBounds, func.srclocs[inst]);
+                    }
+                    put_ldst_sxtw(bits, in_reg0, 16, out_reg0, sink);
+                "#,
+            ),
+    );
+
+    // Same for a floating point destination.
+    recipes.push(
+        EncodingRecipeBuilder::new("fld", &formats.load, 4)
+            .operands_in(vec![gpr])
+            .operands_out(vec![fpr])
+            .inst_predicate(InstructionPredicate::new_is_signed_int(
+                &*formats.load,
+                "offset",
+                9,
+                0,
+            ))
+            .emit(
+                r#"
+                    if !flags.notrap() {
+                        sink.trap(TrapCode::HeapOutOfBounds, func.srclocs[inst]);
+                    }
+                    put_ldst_imm9(bits, in_reg0, offset.into(), out_reg0, sink);
+                "#,
+            ),
+    );
+
+    recipes.push(
+        EncodingRecipeBuilder::new("fld_big", &formats.load, 12)
+            .operands_in(vec![gpr])
+            .operands_out(vec![fpr])
+            .emit(
+                r#"
+                    let offset: i32 = offset.into();
+                    put_movz(0, offset as u16, 0, 16, sink);
+                    put_movk(0, (offset >> 16) as u16, 1, 16, sink);
+                    if !flags.notrap() {
+                        sink.trap(TrapCode::HeapOutOfBounds, func.srclocs[inst]);
+                    }
+                    put_ldst_sxtw(bits, in_reg0, 16, out_reg0, sink);
+                "#,
+            ),
+    );
+
+    // Stores with a signed 9-bit unscaled offset: stur and friends.
+    recipes.push(
+        EncodingRecipeBuilder::new("st", &formats.store, 4)
+            .operands_in(vec![gpr, gpr])
+            .inst_predicate(InstructionPredicate::new_is_signed_int(
+                &*formats.store,
+                "offset",
+                9,
+                0,
+            ))
+            .emit(
+                r#"
+                    if !flags.notrap() {
+                        sink.trap(TrapCode::HeapOutOfBounds, func.srclocs[inst]);
+                    }
+                    put_ldst_imm9(bits, in_reg1, offset.into(), in_reg0, sink);
+                "#,
+            ),
+    );
+
+    recipes.push(
+        EncodingRecipeBuilder::new("st_big", &formats.store, 12)
+            .operands_in(vec![gpr, gpr])
+            .emit(
+                r#"
+                    let offset: i32 = offset.into();
+                    put_movz(0, offset as u16, 0, 16, sink);
+                    put_movk(0, (offset >> 16) as u16, 1, 16, sink);
+                    if !flags.notrap() {
+                        sink.trap(TrapCode::HeapOutOfBounds, func.srclocs[inst]);
+                    }
+                    put_ldst_sxtw(bits, in_reg1, 16, in_reg0, sink);
+                "#,
+            ),
+    );
+
+    recipes.push(
+        EncodingRecipeBuilder::new("fst", &formats.store, 4)
+            .operands_in(vec![fpr, gpr])
+            .inst_predicate(InstructionPredicate::new_is_signed_int(
+                &*formats.store,
+                "offset",
+                9,
+                0,
+            ))
+            .emit(
+                r#"
+                    if !flags.notrap() {
+                        sink.trap(TrapCode::HeapOutOfBounds, func.srclocs[inst]);
+                    }
+                    put_ldst_imm9(bits, in_reg1, offset.into(), in_reg0, sink);
+                "#,
+            ),
+    );
+
+    recipes.push(
+        EncodingRecipeBuilder::new("fst_big", &formats.store, 12)
+            .operands_in(vec![fpr, gpr])
+            .emit(
+                r#"
+                    let offset: i32 = offset.into();
+                    put_movz(0, offset as u16, 0, 16, sink);
+                    put_movk(0, (offset >> 16) as u16, 1, 16, sink);
+                    if !flags.notrap() {
+                        sink.trap(TrapCode::HeapOutOfBounds, func.srclocs[inst]);
+                    }
+                    put_ldst_sxtw(bits, in_reg1, 16, in_reg0, sink);
+                "#,
+            ),
+    );
+
+    // Spills and fills use the scaled unsigned offset form relative to the stack pointer.
+    recipes.push(
+        EncodingRecipeBuilder::new("spill", &formats.unary, 4)
+            .operands_in(vec![gpr])
+            .operands_out(vec![Stack::new(gpr)])
+            .emit(
+                r#"
+                    sink.trap(TrapCode::StackOverflow, func.srclocs[inst]);
+                    put_ldst_sp(bits, out_stk0.offset, in_reg0, sink);
+                "#,
+            ),
+    );
+
+    recipes.push(
+        EncodingRecipeBuilder::new("fspill", &formats.unary, 4)
+            .operands_in(vec![fpr])
+            .operands_out(vec![Stack::new(fpr)])
+            .emit(
+                r#"
+                    sink.trap(TrapCode::StackOverflow, func.srclocs[inst]);
+                    put_ldst_sp(bits, out_stk0.offset, in_reg0, sink);
+                "#,
+            ),
+    );
+
+    recipes.push(
+        EncodingRecipeBuilder::new("regspill", &formats.reg_spill, 4)
+            .operands_in(vec![gpr])
+            .emit(
+                r#"
+                    sink.trap(TrapCode::StackOverflow, func.srclocs[inst]);
+                    let dst = StackRef::sp(dst, &func.stack_slots);
+                    put_ldst_sp(bits, dst.offset, src, sink);
+                "#,
+            ),
+    );
+
+    recipes.push(
+        EncodingRecipeBuilder::new("fregspill", &formats.reg_spill, 4)
+            .operands_in(vec![fpr])
+            .emit(
+                r#"
+                    sink.trap(TrapCode::StackOverflow, func.srclocs[inst]);
+                    let dst = StackRef::sp(dst, &func.stack_slots);
+                    put_ldst_sp(bits, dst.offset, src, sink);
+                "#,
+            ),
+    );
+
+    recipes.push(
+        EncodingRecipeBuilder::new("fill", &formats.unary, 4)
+            .operands_in(vec![Stack::new(gpr)])
+            .operands_out(vec![gpr])
+            .emit("put_ldst_sp(bits, in_stk0.offset, out_reg0, sink);"),
+    );
+
+    recipes.push(
+        EncodingRecipeBuilder::new("ffill", &formats.unary, 4)
+            .operands_in(vec![Stack::new(fpr)])
+            .operands_out(vec![fpr])
+            .emit("put_ldst_sp(bits, in_stk0.offset, out_reg0, sink);"),
+    );
+
+    recipes.push(
+        EncodingRecipeBuilder::new("regfill", &formats.reg_fill, 4)
+            .operands_in(vec![Stack::new(gpr)])
+            .emit(
+                r#"
+                    let src = StackRef::sp(src, &func.stack_slots);
+                    put_ldst_sp(bits, src.offset, dst, sink);
+                "#,
+            ),
+    );
+
+    recipes.push(
+        EncodingRecipeBuilder::new("fregfill", &formats.reg_fill, 4)
+            .operands_in(vec![Stack::new(fpr)])
+            .emit(
+                r#"
+                    let src = StackRef::sp(src, &func.stack_slots);
+                    put_ldst_sp(bits, src.offset, dst, sink);
+                "#,
+            ),
+    );
+
+    // Stack-slot to same stack-slot copy, which is guaranteed to turn into a no-op.
+    recipes.push(
+        EncodingRecipeBuilder::new("stacknull", &formats.unary, 0)
+            .operands_in(vec![Stack::new(gpr)])
+            .operands_out(vec![Stack::new(gpr)])
+            .emit(""),
+    );
+
+    // No-op fills, created by late-stage redundant-fill removal.
+    recipes.push(
+        EncodingRecipeBuilder::new("fillnull", &formats.unary, 0)
+            .operands_in(vec![Stack::new(gpr)])
+            .operands_out(vec![gpr])
+            .emit(""),
+    );
+
+    recipes.push(
+        EncodingRecipeBuilder::new("ffillnull", &formats.unary, 0)
+            .operands_in(vec![Stack::new(fpr)])
+            .operands_out(vec![fpr])
+            .emit(""),
+    );
+
+    // Address of a stack slot: add xd, sp, #lo12 followed by add xd, xd, #hi12, lsl #12.
+    recipes.push(
+        EncodingRecipeBuilder::new("spaddr", &formats.stack_load, 8)
+            .operands_out(vec![gpr])
+            .emit(
+                r#"
+                    let sp = StackRef::sp(stack_slot, &func.stack_slots);
+                    let imm : i32 = offset.into();
+                    let offset = sp.offset.checked_add(imm).unwrap();
+                    assert!(offset >= 0 && offset < (1 << 24), "stack offset out of range");
+                    put_addsub_imm(bits, 31, i64::from(offset & 0xfff), out_reg0, sink);
+                    put_addsub_imm(bits, out_reg0, i64::from(offset & !0xfff), out_reg0, sink);
+                "#,
+            ),
+    );
+
+    // Stack pointer adjustments for the prologue and epilogue.
+    recipes.push(
+        EncodingRecipeBuilder::new("adjustsp", &formats.unary_imm, 4)
+            .inst_predicate(InstructionPredicate::new_is_unsigned_int(
+                &*formats.unary_imm,
+                "imm",
+                12,
+                0,
+            ))
+            .emit("put_addsub_imm(bits, 31, imm.into(), 31, sink);"),
+    );
+
+    recipes.push(
+        EncodingRecipeBuilder::new("adjustsp_big", &formats.unary_imm, 8)
+            .inst_predicate(InstructionPredicate::new_is_unsigned_int(
+                &*formats.unary_imm,
+                "imm",
+                24,
+                0,
+            ))
+            .emit(
+                r#"
+                    let imm: i64 = imm.into();
+                    put_addsub_imm(bits, 31, imm & 0xfff, 31, sink);
+                    put_addsub_imm(bits, 31, imm & !0xfff, 31, sink);
+                "#,
+            ),
+    );
+
+    // Pushes and pops. The stack pointer is always kept 16-byte aligned.
+    recipes.push(
+        EncodingRecipeBuilder::new("push", &formats.unary, 4)
+            .operands_in(vec![gpr])
+            .emit(
+                r#"
+                    sink.trap(TrapCode::StackOverflow, func.srclocs[inst]);
+                    put_ldst_imm9(bits, 31, -16, in_reg0, sink);
+                "#,
+            ),
+    );
+
+    recipes.push(
+        EncodingRecipeBuilder::new("fpush", &formats.unary, 4)
+            .operands_in(vec![fpr])
+            .emit(
+                r#"
+                    sink.trap(TrapCode::StackOverflow, func.srclocs[inst]);
+                    put_ldst_imm9(bits, 31, -16, in_reg0, sink);
+                "#,
+            ),
+    );
+
+    recipes.push(
+        EncodingRecipeBuilder::new("pop", &formats.nullary, 4)
+            .operands_out(vec![gpr])
+            .emit("put_ldst_imm9(bits, 31, 16, out_reg0, sink);"),
+    );
+
+    recipes.push(
+        EncodingRecipeBuilder::new("fpop", &formats.nullary, 4)
+            .operands_out(vec![fpr])
+            .emit("put_ldst_imm9(bits, 31, 16, out_reg0, sink);"),
+    );
+
+    recipes.push(
+        EncodingRecipeBuilder::new("push_pair", &formats.binary, 4)
+            .operands_in(vec![gpr, gpr])
+            .emit(
+                r#"
+                    sink.trap(TrapCode::StackOverflow, func.srclocs[inst]);
+                    put_ldst_pair(bits, 31, -16, in_reg0, in_reg1, sink);
+                "#,
+            ),
+    );
+
+    recipes.push(
+        EncodingRecipeBuilder::new("fpush_pair", &formats.binary, 4)
+            .operands_in(vec![fpr, fpr])
+            .emit(
+                r#"
+                    sink.trap(TrapCode::StackOverflow, func.srclocs[inst]);
+                    put_ldst_pair(bits, 31, -16, in_reg0, in_reg1, sink);
+                "#,
+            ),
+    );
+
+    recipes.push(
+        EncodingRecipeBuilder::new("pop_pair", &formats.nullary, 4)
+            .operands_out(vec![gpr, gpr])
+            .emit("put_ldst_pair(bits, 31, 16, out_reg0, out_reg1, sink);"),
+    );
+
+    recipes.push(
+        EncodingRecipeBuilder::new("fpop_pair", &formats.nullary, 4)
+            .operands_out(vec![fpr, fpr])
+            .emit("put_ldst_pair(bits, 31, 16, out_reg0, out_reg1, sink);"),
+    );
+
+    // Moves.
+
+    // Copy of a GPR is an orr with the zero register.
+    recipes.push(
+        EncodingRecipeBuilder::new("copy", &formats.unary, 4)
+            .operands_in(vec![gpr])
+            .operands_out(vec![gpr])
+            .emit("put_rrr(bits, 31, in_reg0, out_reg0, sink);"),
+    );
+
+    recipes.push(
+        EncodingRecipeBuilder::new("rmov", &formats.reg_move, 4)
+            .operands_in(vec![gpr])
+            .emit("put_rrr(bits, 31, src, dst, sink);"),
+    );
+
+    recipes.push(
+        EncodingRecipeBuilder::new("copytossa", &formats.copy_to_ssa, 4)
+            // No operands_in to mention, because a source register is specified directly.
+            .operands_out(vec![gpr])
+            .emit("put_rrr(bits, 31, src, out_reg0, sink);"),
+    );
+
+    // FPR copies are fmov.
+    recipes.push(
+        EncodingRecipeBuilder::new("fcopy", &formats.unary, 4)
+            .operands_in(vec![fpr])
+            .operands_out(vec![fpr])
+            .emit("put_fp1(bits, in_reg0, out_reg0, sink);"),
+    );
+
+    recipes.push(
+        EncodingRecipeBuilder::new("frmov", &formats.reg_move, 4)
+            .operands_in(vec![fpr])
+            .emit("put_fp1(bits, src, dst, sink);"),
+    );
+
+    recipes.push(
+        EncodingRecipeBuilder::new("fcopytossa", &formats.copy_to_ssa, 4)
+            .operands_out(vec![fpr])
+            .emit("put_fp1(bits, src, out_reg0, sink);"),
+    );
+
+    // Copies involving the stack pointer must use `add #0`, since an orr would read the zero
+    // register instead.
+    recipes.push(
+        EncodingRecipeBuilder::new("copysp", &formats.copy_special, 4)
+            .emit("put_addsub_imm(bits, src, 0, dst, sink);"),
+    );
+
+    // Reductions and bit casts between integer types which don't need any code, since 32-bit
+    // instructions ignore the upper half of the register.
+    recipes.push(
+        EncodingRecipeBuilder::new("null", &formats.unary, 0)
+            .operands_in(vec![gpr])
+            .operands_out(vec![0])
+            .emit(""),
+    );
+
+    // Addresses.
+
+    // Load an absolute address from a literal placed in the instruction stream:
+    //
+    //   ldr xd, #8
+    //   b #12
+    //   .quad sym
+    recipes.push(
+        EncodingRecipeBuilder::new("fnaddr", &formats.func_addr, 16)
+            .operands_out(vec![gpr])
+            .emit(
+                r#"
+                    put_ldr_literal(8, out_reg0, sink);
+                    put_b(12, sink);
+                    sink.reloc_external(Reloc::Abs8,
+                                        &func.dfg.ext_funcs[func_ref].name,
+                                        0);
+                    sink.put8(0);
+                "#,
+            ),
+    );
+
+    recipes.push(
+        EncodingRecipeBuilder::new("gvaddr", &formats.unary_global_value, 16)
+            .operands_out(vec![gpr])
+            .emit(
+                r#"
+                    put_ldr_literal(8, out_reg0, sink);
+                    put_b(12, sink);
+                    sink.reloc_external(Reloc::Abs8,
+                                        &func.global_values[global_value].symbol_name(),
+                                        0);
+                    sink.put8(0);
+                "#,
+            ),
+    );
+
+    // Control flow.
+
+    recipes.push(
+        EncodingRecipeBuilder::new("b", &formats.jump, 4)
+            .branch_range((0, 28))
+            .emit(
+                r#"
+                    let dest = i64::from(func.offsets[destination]);
+                    let disp = dest - i64::from(sink.offset());
+                    put_b(disp, sink);
+                "#,
+            ),
+    );
+
+    // cbz/cbnz.
+    recipes.push(
+        EncodingRecipeBuilder::new("cbz", &formats.branch, 4)
+            .operands_in(vec![gpr])
+            .branch_range((0, 21))
+            .emit(
+                r#"
+                    let dest = i64::from(func.offsets[destination]);
+                    let disp = dest - i64::from(sink.offset());
+                    put_cbz(bits, in_reg0, disp, sink);
+                "#,
+            ),
+    );
+
+    // cmp + b.cond.
+    recipes.push(
+        EncodingRecipeBuilder::new("bricmp", &formats.branch_icmp, 8)
+            .operands_in(vec![gpr, gpr])
+            .branch_range((4, 21))
+            .emit(
+                r#"
+                    put_rrr(bits, in_reg0, in_reg1, 31, sink);
+                    let dest = i64::from(func.offsets[destination]);
+                    let disp = dest - i64::from(sink.offset());
+                    put_bcond(icc2cond(cond), disp, sink);
+                "#,
+            ),
+    );
+
+    recipes.push(EncodingRecipeBuilder::new("call", &formats.call, 4).emit(
+        r#"
+                    sink.reloc_external(Reloc::Arm64Call,
+                                        &func.dfg.ext_funcs[func_ref].name,
+                                        0);
+                    put_bl(sink);
+                "#,
+    ));
+
+    recipes.push(
+        EncodingRecipeBuilder::new("call_r", &formats.call_indirect, 4)
+            .operands_in(vec![gpr])
+            .emit("put_br(bits, in_reg0, sink);"),
+    );
+
+    // The return address is restored into %x30 by the epilogue.
+    recipes.push(
+        EncodingRecipeBuilder::new("ret", &formats.multiary, 4).emit("put_br(bits, 30, sink);"),
+    );
+
+    recipes.push(EncodingRecipeBuilder::new("trap", &formats.trap, 4).emit(
+        r#"
+                    sink.trap(code, func.srclocs[inst]);
+                    put_udf(sink);
+                "#,
+    ));
+
+    // brk #0.
+    recipes.push(EncodingRecipeBuilder::new("brk", &formats.nullary, 4).emit("put_brk(sink);"));
+
+    // Jump tables are emitted right after the function body, so they are in range of adr.
+    recipes.push(
+        EncodingRecipeBuilder::new("jt_base", &formats.branch_table_base, 4)
+            .operands_out(vec![gpr])
+            .emit(
+                r#"
+                    let jt_offset = func.jt_offsets[table];
+                    let disp = i64::from(jt_offset) - i64::from(sink.offset());
+                    put_adr(disp, out_reg0, sink);
+                "#,
+            ),
+    );
+
+    // Load a sign-extended 32-bit jump table entry: ldrsw xd, [base, idx, lsl #2].
+    recipes.push(
+        EncodingRecipeBuilder::new("jt_entry", &formats.branch_table_entry, 4)
+            .operands_in(vec![gpr, gpr])
+            .operands_out(vec![gpr])
+            .inst_predicate(InstructionPredicate::new_is_field_equal(
+                &*formats.branch_table_entry,
+                "imm",
+                "4".into(),
+            ))
+            .emit("put_ldst_lsl(bits, in_reg1, in_reg0, out_reg0, sink);"),
+    );
+
+    recipes.push(
+        EncodingRecipeBuilder::new("indirect_jmp", &formats.indirect_jump, 4)
+            .operands_in(vec![gpr])
+            .emit("put_br(bits, in_reg0, sink);"),
+    );
+
+    recipes
+}
@@ -1,7 +1,9 @@
 use crate::cdsl::ast::{Apply, Expr, Literal, VarPool};
 use crate::cdsl::encodings::{Encoding, EncodingBuilder};
+use crate::cdsl::instructions::BindParameter::Any;
 use crate::cdsl::instructions::{
-    Bindable, BoundInstruction, InstSpec, InstructionPredicateNode, InstructionPredicateRegistry,
+    Bindable, BoundInstruction, InstSpec, InstructionGroup, InstructionPredicateNode,
+    InstructionPredicateRegistry,
 };
 use crate::cdsl::recipes::{EncodingRecipeNumber, Recipes};
 use crate::cdsl::settings::SettingGroup;
@@ -105,9 +107,44 @@ fn lui_bits() -> u16 {
     0b01101
 }
 
+fn load_fp_bits(funct3: u16) -> u16 {
+    assert!(funct3 <= 0b111);
+    0b00001 | (funct3 << 5)
+}
+
+fn store_fp_bits(funct3: u16) -> u16 {
+    assert!(funct3 <= 0b111);
+    0b01001 | (funct3 << 5)
+}
+
+fn opfp_bits(funct3: u16, funct7: u16) -> u16 {
+    assert!(funct3 <= 0b111);
+    assert!(funct7 <= 0b111_1111);
+    0b10100 | (funct3 << 5) | (funct7 << 8)
+}
+
+// OP-FP instructions without an rs2 operand use rs2 as part of the opcode, so their encbits
+// replace opcode[6:2] with rs2.
+fn opfp_unary_bits(rs2: u16, funct3: u16, funct7: u16) -> u16 {
+    assert!(rs2 <= 0b1_1111);
+    assert!(funct3 <= 0b111);
+    assert!(funct7 <= 0b111_1111);
+    rs2 | (funct3 << 5) | (funct7 << 8)
+}
+
+fn fma_bits(fmt: u16) -> u16 {
+    assert!(fmt <= 0b11);
+    // fmadd with a dynamic rounding mode.
+    0b10000 | (RM_DYN << 5) | (fmt << 8)
+}
+
+// The rounding mode used by floating point instructions: use the dynamic mode from `fcsr`.
+const RM_DYN: u16 = 0b111;
+
 pub(crate) fn define<'defs>(
     shared_defs: &'defs SharedDefinitions,
     isa_settings: &SettingGroup,
+    riscv: &InstructionGroup,
     recipes: &'defs RecipeGroup,
 ) -> PerCpuModeEncodings<'defs> {
     // Instructions shorthands.
@@ -115,6 +152,7 @@ pub(crate) fn define<'defs>(
 
     let band = shared.by_name("band");
     let band_imm = shared.by_name("band_imm");
+    let bitcast = shared.by_name("bitcast");
     let bor = shared.by_name("bor");
     let bor_imm = shared.by_name("bor_imm");
     let br_icmp = shared.by_name("br_icmp");
@@ -127,50 +165,113 @@ pub(crate) fn define<'defs>(
     let copy = shared.by_name("copy");
     let copy_nop = shared.by_name("copy_nop");
     let copy_to_ssa = shared.by_name("copy_to_ssa");
+    let fabs = shared.by_name("fabs");
+    let fadd = shared.by_name("fadd");
+    let fcmp = shared.by_name("fcmp");
+    let fcopysign = shared.by_name("fcopysign");
+    let fcvt_from_sint = shared.by_name("fcvt_from_sint");
+    let fcvt_from_uint = shared.by_name("fcvt_from_uint");
+    let fdemote = shared.by_name("fdemote");
+    let fdiv = shared.by_name("fdiv");
     let fill = shared.by_name("fill");
     let fill_nop = shared.by_name("fill_nop");
+    let fma = shared.by_name("fma");
+    let fmul = shared.by_name("fmul");
+    let fneg = shared.by_name("fneg");
+    let fpromote = shared.by_name("fpromote");
+    let fsub = shared.by_name("fsub");
     let iadd = shared.by_name("iadd");
     let iadd_imm = shared.by_name("iadd_imm");
     let iconst = shared.by_name("iconst");
     let icmp = shared.by_name("icmp");
     let icmp_imm = shared.by_name("icmp_imm");
     let imul = shared.by_name("imul");
+    let ireduce = shared.by_name("ireduce");
     let ishl = shared.by_name("ishl");
     let ishl_imm = shared.by_name("ishl_imm");
+    let istore16 = shared.by_name("istore16");
+    let istore32 = shared.by_name("istore32");
+    let istore8 = shared.by_name("istore8");
     let isub = shared.by_name("isub");
     let jump = shared.by_name("jump");
+    let load = shared.by_name("load");
     let regmove = shared.by_name("regmove");
+    let sextend = shared.by_name("sextend");
+    let sload16 = shared.by_name("sload16");
+    let sload32 = shared.by_name("sload32");
+    let sload8 = shared.by_name("sload8");
+    let smulhi = shared.by_name("smulhi");
     let spill = shared.by_name("spill");
+    let sqrt = shared.by_name("sqrt");
+    let srem = shared.by_name("srem");
     let sshr = shared.by_name("sshr");
     let sshr_imm = shared.by_name("sshr_imm");
+    let store = shared.by_name("store");
+    let trap = shared.by_name("trap");
+    let udiv = shared.by_name("udiv");
+    let uextend = shared.by_name("uextend");
+    let uload16 = shared.by_name("uload16");
+    let uload32 = shared.by_name("uload32");
+    let uload8 = shared.by_name("uload8");
+    let umulhi = shared.by_name("umulhi");
+    let urem = shared.by_name("urem");
     let ushr = shared.by_name("ushr");
     let ushr_imm = shared.by_name("ushr_imm");
     let return_ = shared.by_name("return");
 
+    let riscv_div = riscv.by_name("riscv_div");
+
     // Recipes shorthands, prefixed with r_.
     let r_copytossa = recipes.by_name("copytossa");
+    let r_fcopytossa = recipes.by_name("Fcopytossa");
+    let r_ffillnull = recipes.by_name("ffillnull");
+    let r_fi_load = recipes.by_name("FIload");
+    let r_fp_fi = recipes.by_name("FPfi");
+    let r_fp_sp = recipes.by_name("FPsp");
+    let r_fr = recipes.by_name("FR");
+    let r_fr4 = recipes.by_name("FR4");
+    let r_frcmp = recipes.by_name("FRcmp");
+    let r_frcmpn = recipes.by_name("FRcmpn");
+    let r_frcmpr = recipes.by_name("FRcmpr");
+    let r_frcmprn = recipes.by_name("FRcmprn");
+    let r_frdup = recipes.by_name("FRdup");
+    let r_frfg = recipes.by_name("FRfg");
+    let r_frgf = recipes.by_name("FRgf");
+    let r_frrmov = recipes.by_name("FRrmov");
+    let r_fru = recipes.by_name("FRu");
+    let r_fs = recipes.by_name("FS");
     let r_fillnull = recipes.by_name("fillnull");
     let r_icall = recipes.by_name("Icall");
     let r_icopy = recipes.by_name("Icopy");
     let r_ii = recipes.by_name("Ii");
     let r_iicmp = recipes.by_name("Iicmp");
+    let r_iload = recipes.by_name("Iload");
     let r_iret = recipes.by_name("Iret");
     let r_irmov = recipes.by_name("Irmov");
+    let r_iuext32 = recipes.by_name("Iuext32");
     let r_iz = recipes.by_name("Iz");
     let r_gp_sp = recipes.by_name("GPsp");
     let r_gp_fi = recipes.by_name("GPfi");
     let r_r = recipes.by_name("R");
+    let r_rdiv = recipes.by_name("Rdiv");
     let r_ricmp = recipes.by_name("Ricmp");
     let r_rshamt = recipes.by_name("Rshamt");
+    let r_s = recipes.by_name("S");
     let r_sb = recipes.by_name("SB");
     let r_sb_zero = recipes.by_name("SBzero");
+    let r_null = recipes.by_name("null");
     let r_stacknull = recipes.by_name("stacknull");
+    let r_trap = recipes.by_name("trap");
     let r_u = recipes.by_name("U");
     let r_uj = recipes.by_name("UJ");
     let r_uj_call = recipes.by_name("UJcall");
 
     // Predicates shorthands.
+    let use_d = isa_settings.predicate_by_name("use_d");
+    let use_f = isa_settings.predicate_by_name("use_f");
     let use_m = isa_settings.predicate_by_name("use_m");
+    let soft_d = isa_settings.predicate_by_name("soft_d");
+    let soft_f = isa_settings.predicate_by_name("soft_f");
 
     // Definitions.
     let mut e = PerCpuModeEncodings::new(&recipes.recipes);
@@ -305,6 +406,328 @@ pub(crate) fn define<'defs>(
             .isa_predicate(use_m),
     );
 
+    for &(inst, f3) in &[(smulhi, 0b001), (umulhi, 0b011)] {
+        e.add32(
+            e.enc(inst.bind(I32), r_r, op_bits(f3, 0b0000_0001))
+                .isa_predicate(use_m),
+        );
+        e.add64(
+            e.enc(inst.bind(I64), r_r, op_bits(f3, 0b0000_0001))
+                .isa_predicate(use_m),
+        );
+    }
+
+    // RISC-V division doesn't trap, so the recipe checks for a zero divisor. `INT_MIN % -1` is 0
+    // as expected. `sdiv` is legalized into explicit checks and `riscv_div`, because it would
+    // also need to check for `INT_MIN / -1`.
+    for &(inst, f3) in &[(udiv, 0b101), (srem, 0b110), (urem, 0b111)] {
+        e.add32(
+            e.enc(inst.bind(I32), r_rdiv, op_bits(f3, 0b0000_0001))
+                .isa_predicate(use_m),
+        );
+        e.add64(
+            e.enc(inst.bind(I64), r_rdiv, op_bits(f3, 0b0000_0001))
+                .isa_predicate(use_m),
+        );
+        e.add64(
+            e.enc(inst.bind(I32), r_rdiv, op32_bits(f3, 0b0000_0001))
+                .isa_predicate(use_m),
+        );
+    }
+
+    e.add32(
+        e.enc(riscv_div.bind(I32), r_r, op_bits(0b100, 0b0000_0001))
+            .isa_predicate(use_m),
+    );
+    e.add64(
+        e.enc(riscv_div.bind(I64), r_r, op_bits(0b100, 0b0000_0001))
+            .isa_predicate(use_m),
+    );
+    e.add64(
+        e.enc(riscv_div.bind(I32), r_r, op32_bits(0b100, 0b0000_0001))
+            .isa_predicate(use_m),
+    );
+
+    // 32-bit values are kept sign-extended in RV64 registers, which is what the `*w` instructions
+    // produce. `sext.w` is an alias for `addiw rd, rs, 0`.
+    e.add64(e.enc(sextend.bind(I64).bind(I32), r_icopy, opimm32_bits(0b000, 0)));
+    e.add64(e.enc(ireduce.bind(I32).bind(I64), r_icopy, opimm32_bits(0b000, 0)));
+    e.add64(e.enc(uextend.bind(I64).bind(I32), r_iuext32, opimm_bits(0b001, 0)));
+
+    // Loads and stores.
+    for &(inst, f3) in &[
+        (load, 0b010),
+        (sload8, 0b000),
+        (sload16, 0b001),
+        (uload8, 0b100),
+        (uload16, 0b101),
+    ] {
+        e.add32(e.enc(inst.bind(I32).bind(Any), r_iload, load_bits(f3)));
+        e.add64(e.enc(inst.bind(I32).bind(Any), r_iload, load_bits(f3)));
+    }
+    for &(inst, f3) in &[
+        (load, 0b011),
+        (sload8, 0b000),
+        (sload16, 0b001),
+        (uload8, 0b100),
+        (uload16, 0b101),
+    ] {
+        e.add64(e.enc(inst.bind(I64).bind(Any), r_iload, load_bits(f3)));
+    }
+    // The 32-bit extending loads always produce an i64, so only the address type is bound.
+    e.add64(e.enc(sload32.bind(I64), r_iload, load_bits(0b010)));
+    e.add64(e.enc(uload32.bind(I64), r_iload, load_bits(0b110)));
+    for &(inst, f3) in &[(store, 0b010), (istore8, 0b000), (istore16, 0b001)] {
+        e.add32(e.enc(inst.bind(I32).bind(Any), r_s, store_bits(f3)));
+        e.add64(e.enc(inst.bind(I32).bind(Any), r_s, store_bits(f3)));
+    }
+    for &(inst, f3) in &[
+        (store, 0b011),
+        (istore8, 0b000),
+        (istore16, 0b001),
+        (istore32, 0b010),
+    ] {
+        e.add64(e.enc(inst.bind(I64).bind(Any), r_s, store_bits(f3)));
+    }
+
+    // "F" and "D" Standard Extensions for floating point. Gated by the `use_f` and `use_d` flags.
+    // The `fmt` field of the floating point instructions is 0 for single and 1 for double
+    // precision, and shows up as the low bit of funct7.
+    for &(ty, fmt, pred, ld_f3) in &[(F32, 0, use_f, 0b010), (F64, 1, use_d, 0b011)] {
+        let mut enc_both = |inst: BoundInstruction, recipe, bits| {
+            e.add32(e.enc(inst.clone(), recipe, bits).isa_predicate(pred));
+            e.add64(e.enc(inst, recipe, bits).isa_predicate(pred));
+        };
+
+        for &(inst, f7) in &[
+            (fadd, 0b000_0000),
+            (fsub, 0b000_0100),
+            (fmul, 0b000_1000),
+            (fdiv, 0b000_1100),
+        ] {
+            enc_both(inst.bind(ty), r_fr, opfp_bits(RM_DYN, f7 | fmt));
+        }
+        enc_both(
+            sqrt.bind(ty),
+            r_fru,
+            opfp_unary_bits(0, RM_DYN, 0b010_1100 | fmt),
+        );
+        enc_both(fma.bind(ty), r_fr4, fma_bits(fmt));
+
+        // Sign injection.
+        enc_both(fcopysign.bind(ty), r_fr, opfp_bits(0b000, 0b001_0000 | fmt));
+        enc_both(fneg.bind(ty), r_frdup, opfp_bits(0b001, 0b001_0000 | fmt));
+        enc_both(fabs.bind(ty), r_frdup, opfp_bits(0b010, 0b001_0000 | fmt));
+
+        // Register copies are `fmv`, which is `fsgnj` with the same register twice.
+        enc_both(copy.bind(ty), r_frdup, opfp_bits(0b000, 0b001_0000 | fmt));
+        enc_both(
+            regmove.bind(ty),
+            r_frrmov,
+            opfp_bits(0b000, 0b001_0000 | fmt),
+        );
+        enc_both(
+            copy_to_ssa.bind(ty),
+            r_fcopytossa,
+            opfp_bits(0b000, 0b001_0000 | fmt),
+        );
+
+        // Loads, stores, spills and fills.
+        enc_both(load.bind(ty).bind(Any), r_fi_load, load_fp_bits(ld_f3));
+        enc_both(store.bind(ty).bind(Any), r_fs, store_fp_bits(ld_f3));
+        enc_both(spill.bind(ty), r_fp_sp, store_fp_bits(ld_f3));
+        enc_both(fill.bind(ty), r_fp_fi, load_fp_bits(ld_f3));
+        enc_both(fill_nop.bind(ty), r_ffillnull, 0);
+
+        // Conversions from 32-bit integers. The 64-bit ones only exist in RV64. Converting a
+        // 32-bit integer to a double is exact, so it doesn't take a rounding mode.
+        let rm_w = if fmt == 1 { 0b000 } else { RM_DYN };
+        enc_both(
+            fcvt_from_sint.bind(ty).bind(I32),
+            r_frgf,
+            opfp_unary_bits(0b00, rm_w, 0b110_1000 | fmt),
+        );
+        enc_both(
+            fcvt_from_uint.bind(ty).bind(I32),
+            r_frgf,
+            opfp_unary_bits(0b01, rm_w, 0b110_1000 | fmt),
+        );
+        e.add64(
+            e.enc(
+                fcvt_from_sint.bind(ty).bind(I64),
+                r_frgf,
+                opfp_unary_bits(0b10, RM_DYN, 0b110_1000 | fmt),
+            )
+            .isa_predicate(pred),
+        );
+        e.add64(
+            e.enc(
+                fcvt_from_uint.bind(ty).bind(I64),
+                r_frgf,
+                opfp_unary_bits(0b11, RM_DYN, 0b110_1000 | fmt),
+            )
+            .isa_predicate(pred),
+        );
+    }
+
+    // `fcvt.d.s` is exact, but `fcvt.s.d` rounds.
+    let fpromote_bits = opfp_unary_bits(0b00, 0b000, 0b010_0001);
+    let fdemote_bits = opfp_unary_bits(0b01, RM_DYN, 0b010_0000);
+    e.add32(
+        e.enc(fpromote.bind(F64).bind(F32), r_fru, fpromote_bits)
+            .isa_predicate(use_d),
+    );
+    e.add64(
+        e.enc(fpromote.bind(F64).bind(F32), r_fru, fpromote_bits)
+            .isa_predicate(use_d),
+    );
+    e.add32(
+        e.enc(fdemote.bind(F32).bind(F64), r_fru, fdemote_bits)
+            .isa_predicate(use_d),
+    );
+    e.add64(
+        e.enc(fdemote.bind(F32).bind(F64), r_fru, fdemote_bits)
+            .isa_predicate(use_d),
+    );
+
+    // Bit casts between the register banks, `fmv.x.w`, `fmv.w.x`, `fmv.x.d` and `fmv.d.x`.
+    e.add32(
+        e.enc(
+            bitcast.bind(I32).bind(F32),
+            r_frfg,
+            opfp_unary_bits(0, 0b000, 0b111_0000),
+        )
+        .isa_predicate(use_f),
+    );
+    e.add64(
+        e.enc(
+            bitcast.bind(I32).bind(F32),
+            r_frfg,
+            opfp_unary_bits(0, 0b000, 0b111_0000),
+        )
+        .isa_predicate(use_f),
+    );
+    e.add32(
+        e.enc(
+            bitcast.bind(F32).bind(I32),
+            r_frgf,
+            opfp_unary_bits(0, 0b000, 0b111_1000),
+        )
+        .isa_predicate(use_f),
+    );
+    e.add64(
+        e.enc(
+            bitcast.bind(F32).bind(I32),
+            r_frgf,
+            opfp_unary_bits(0, 0b000, 0b111_1000),
+        )
+        .isa_predicate(use_f),
+    );
+    e.add64(
+        e.enc(
+            bitcast.bind(I64).bind(F64),
+            r_frfg,
+            opfp_unary_bits(0, 0b000, 0b111_0001),
+        )
+        .isa_predicate(use_d),
+    );
+    e.add64(
+        e.enc(
+            bitcast.bind(F64).bind(I64),
+            r_frgf,
+            opfp_unary_bits(0, 0b000, 0b111_1001),
+        )
+        .isa_predicate(use_d),
+    );
+
+    // Soft-float values live in the integer registers, where bit casts are no-ops and only the
+    // bitwise operations used to expand `fneg`, `fabs` and `fcopysign` are encoded. Everything
+    // else is a runtime library call. Doubles only fit in the integer registers of RV64.
+    {
+        let mut enc_soft = |rv32: bool, inst: BoundInstruction, recipe, bits, pred| {
+            if rv32 {
+                e.add32(e.enc(inst.clone(), recipe, bits).isa_predicate(pred));
+            }
+            e.add64(e.enc(inst, recipe, bits).isa_predicate(pred));
+        };
+
+        for &(ty, int_ty, pred, f3, rv32) in &[
+            (F32, I32, soft_f, 0b010, true),
+            (F64, I64, soft_d, 0b011, false),
+        ] {
+            enc_soft(rv32, bitcast.bind(ty).bind(int_ty), r_null, 0, pred);
+            enc_soft(rv32, bitcast.bind(int_ty).bind(ty), r_null, 0, pred);
+
+            for &(inst, f3) in &[(bxor, 0b100), (bor, 0b110), (band, 0b111)] {
+                enc_soft(rv32, inst.bind(ty), r_r, op_bits(f3, 0b000_0000), pred);
+            }
+
+            enc_soft(rv32, copy.bind(ty), r_icopy, opimm_bits(0b000, 0), pred);
+            enc_soft(rv32, regmove.bind(ty), r_irmov, opimm_bits(0b000, 0), pred);
+            enc_soft(
+                rv32,
+                copy_to_ssa.bind(ty),
+                r_copytossa,
+                opimm_bits(0b000, 0),
+                pred,
+            );
+            enc_soft(rv32, load.bind(ty).bind(Any), r_iload, load_bits(f3), pred);
+            enc_soft(rv32, store.bind(ty).bind(Any), r_s, store_bits(f3), pred);
+            enc_soft(rv32, spill.bind(ty), r_gp_sp, store_bits(f3), pred);
+            enc_soft(rv32, fill.bind(ty), r_gp_fi, load_bits(f3), pred);
+            enc_soft(rv32, fill_nop.bind(ty), r_fillnull, 0, pred);
+        }
+    }
+
+    // Floating point comparisons. `feq`, `flt` and `fle` are false when either operand is NaN,
+    // so the unordered conditions are their inverses with swapped operands.
+    {
+        let mut var_pool = VarPool::new();
+
+        // Helper that creates an instruction predicate for an instruction in the fcmp family.
+        let mut fcmp_instp = |bound_inst: &BoundInstruction,
+                              floatcc_field: &'static str|
+         -> InstructionPredicateNode {
+            let x = var_pool.create("x");
+            let y = var_pool.create("y");
+            let cc = Literal::enumerator_for(&shared_defs.imm.floatcc, floatcc_field);
+            Apply::new(
+                bound_inst.clone().into(),
+                vec![Expr::Literal(cc), Expr::Var(x), Expr::Var(y)],
+            )
+            .inst_predicate(&var_pool)
+            .unwrap()
+        };
+
+        for &(ty, fmt, pred) in &[(F32, 0, use_f), (F64, 1, use_d)] {
+            let fcmp_ty = fcmp.bind(ty);
+            for &(cond, recipe, f3) in &[
+                ("eq", r_frcmp, 0b010),
+                ("lt", r_frcmp, 0b001),
+                ("le", r_frcmp, 0b000),
+                ("gt", r_frcmpr, 0b001),
+                ("ge", r_frcmpr, 0b000),
+                ("ne", r_frcmpn, 0b010),
+                ("uge", r_frcmpn, 0b001),
+                ("ugt", r_frcmpn, 0b000),
+                ("ult", r_frcmprn, 0b000),
+                ("ule", r_frcmprn, 0b001),
+            ] {
+                let bits = opfp_bits(f3, 0b101_0000 | fmt);
+                e.add32(
+                    e.enc(fcmp_ty.clone(), recipe, bits)
+                        .inst_predicate(fcmp_instp(&fcmp_ty, cond))
+                        .isa_predicate(pred),
+                );
+                e.add64(
+                    e.enc(fcmp_ty.clone(), recipe, bits)
+                        .inst_predicate(fcmp_instp(&fcmp_ty, cond))
+                        .isa_predicate(pred),
+                );
+            }
+        }
+    }
+
     // Control flow.
 
     // Unconditional branches.
@@ -376,6 +799,9 @@ pub(crate) fn define<'defs>(
     e.add32(e.enc(call_indirect.bind(I32), r_icall, jalr_bits()));
     e.add64(e.enc(call_indirect.bind(I64), r_icall, jalr_bits()));
 
+    e.add32(e.enc(trap, r_trap, 0));
+    e.add64(e.enc(trap, r_trap, 0));
+
     // Spill and fill.
     e.add32(e.enc(spill.bind(I32), r_gp_sp, store_bits(0b010)));
     e.add64(e.enc(spill.bind(I32), r_gp_sp, store_bits(0b010)));
@@ -0,0 +1,43 @@
+#![allow(non_snake_case)]
+
+use crate::cdsl::instructions::{
+    AllInstructions, InstructionBuilder as Inst, InstructionGroup, InstructionGroupBuilder,
+};
+use crate::cdsl::operands::Operand;
+use crate::cdsl::typevar::{TypeSetBuilder, TypeVar};
+use crate::shared::formats::Formats;
+
+pub(crate) fn define(
+    mut all_instructions: &mut AllInstructions,
+    formats: &Formats,
+) -> InstructionGroup {
+    let mut ig = InstructionGroupBuilder::new(&mut all_instructions);
+
+    let iWord = &TypeVar::new(
+        "iWord",
+        "A scalar integer machine word",
+        TypeSetBuilder::new().ints(32..64).build(),
+    );
+    let x = &Operand::new("x", iWord);
+    let y = &Operand::new("y", iWord);
+    let a = &Operand::new("a", iWord);
+
+    ig.push(
+        Inst::new(
+            "riscv_div",
+            r#"
+        Signed integer division which never traps.
+
+        This is the RISC-V `div` instruction. A division by zero returns -1,
+        and the division of the smallest integer by -1 returns the smallest
+        integer. `sdiv` is legalized into explicit checks for those cases
+        followed by `riscv_div`.
+        "#,
+            &formats.binary,
+        )
+        .operands_in(vec![x, y])
+        .operands_out(vec![a]),
+    );
+
+    ig.build()
+}
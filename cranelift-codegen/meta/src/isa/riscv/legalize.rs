@@ -0,0 +1,21 @@
+use crate::cdsl::xform::TransformGroupBuilder;
+use crate::shared::Definitions as SharedDefinitions;
+
+pub(crate) fn define(shared: &mut SharedDefinitions) {
+    let mut group = TransformGroupBuilder::new(
+        "riscv_expand",
+        r#"
+    Legalize instructions by expansion.
+
+    Use RISC-V-specific instructions if needed."#,
+    )
+    .isa("riscv")
+    .chain_with(shared.transform_groups.by_name("expand").id);
+
+    let sdiv = shared.instructions.by_name("sdiv");
+
+    // RISC-V division never traps, so the trapping cases of `sdiv` are checked explicitly.
+    group.custom_legalize(sdiv, "expand_sdiv");
+
+    group.build_and_add_to(&mut shared.transform_groups);
+}
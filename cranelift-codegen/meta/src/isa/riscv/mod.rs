@@ -1,14 +1,16 @@
 use crate::cdsl::cpu_modes::CpuMode;
-use crate::cdsl::instructions::InstructionGroupBuilder;
 use crate::cdsl::isa::TargetIsa;
 use crate::cdsl::regs::{IsaRegs, IsaRegsBuilder, RegBankBuilder, RegClassBuilder};
 use crate::cdsl::settings::{PredicateNode, SettingGroup, SettingGroupBuilder};
 
+use crate::shared::types::Bool::B1;
 use crate::shared::types::Float::{F32, F64};
 use crate::shared::types::Int::{I32, I64};
 use crate::shared::Definitions as SharedDefinitions;
 
 mod encodings;
+mod instructions;
+mod legalize;
 mod recipes;
 
 fn define_settings(shared: &SettingGroup) -> SettingGroup {
@@ -60,6 +62,17 @@ fn define_settings(shared: &SettingGroup) -> SettingGroup {
         predicate!(shared_enable_simd && supports_f && supports_d),
     );
 
+    // Without the F or D extension, floats of that size are kept in the integer registers and
+    // operated on by runtime library calls.
+    setting.add_predicate(
+        "soft_f",
+        PredicateNode::Not(Box::new(predicate!(supports_f && shared_enable_float))),
+    );
+    setting.add_predicate(
+        "soft_d",
+        PredicateNode::Not(Box::new(predicate!(supports_d && shared_enable_float))),
+    );
+
     setting.build()
 }
 
@@ -89,31 +102,35 @@ pub(crate) fn define(shared_defs: &mut SharedDefinitions) -> TargetIsa {
     let settings = define_settings(&shared_defs.settings);
     let regs = define_registers();
 
-    let inst_group = InstructionGroupBuilder::new(&mut shared_defs.all_instructions).build();
+    let inst_group = instructions::define(&mut shared_defs.all_instructions, &shared_defs.formats);
+    legalize::define(shared_defs);
 
     // CPU modes for 32-bit and 64-bit operation.
     let mut rv_32 = CpuMode::new("RV32");
     let mut rv_64 = CpuMode::new("RV64");
 
     let expand = shared_defs.transform_groups.by_name("expand");
+    let riscv_expand = shared_defs.transform_groups.by_name("riscv_expand");
     let narrow_no_flags = shared_defs.transform_groups.by_name("narrow_no_flags");
 
     rv_32.legalize_monomorphic(expand);
     rv_32.legalize_default(narrow_no_flags);
-    rv_32.legalize_type(I32, expand);
+    rv_32.legalize_type(B1, expand);
+    rv_32.legalize_type(I32, riscv_expand);
     rv_32.legalize_type(F32, expand);
     rv_32.legalize_type(F64, expand);
 
     rv_64.legalize_monomorphic(expand);
     rv_64.legalize_default(narrow_no_flags);
-    rv_64.legalize_type(I32, expand);
-    rv_64.legalize_type(I64, expand);
+    rv_64.legalize_type(B1, expand);
+    rv_64.legalize_type(I32, riscv_expand);
+    rv_64.legalize_type(I64, riscv_expand);
     rv_64.legalize_type(F32, expand);
     rv_64.legalize_type(F64, expand);
 
     let recipes = recipes::define(shared_defs, &regs);
 
-    let encodings = encodings::define(shared_defs, &settings, &recipes);
+    let encodings = encodings::define(shared_defs, &settings, &inst_group, &recipes);
     rv_32.set_encodings(encodings.enc32);
     rv_64.set_encodings(encodings.enc64);
     let encodings_predicates = encodings.inst_pred_reg.extract();
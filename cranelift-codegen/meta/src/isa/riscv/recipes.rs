@@ -49,6 +49,7 @@ pub(crate) fn define(shared_defs: &SharedDefinitions, regs: &IsaRegs) -> RecipeG
 
     // Register classes shorthands.
     let gpr = regs.class_by_name("GPR");
+    let fpr = regs.class_by_name("FPR");
 
     // Definitions.
     let mut recipes = RecipeGroup::new();
@@ -78,6 +79,23 @@ pub(crate) fn define(shared_defs: &SharedDefinitions, regs: &IsaRegs) -> RecipeG
             .emit("put_r(bits, in_reg0, in_reg1, out_reg0, sink);"),
     );
 
+    // R-type integer division, with an explicit check for a zero divisor since RISC-V division
+    // never traps.
+    recipes.push(
+        EncodingRecipeBuilder::new("Rdiv", &formats.binary, 12)
+            .operands_in(vec![gpr, gpr])
+            .operands_out(vec![gpr])
+            .emit(
+                r#"
+                    // bnez rs2, 8 over the trap.
+                    put_sb(0b001_11000, 8, in_reg1, 0, sink);
+                    sink.trap(TrapCode::IntegerDivisionByZero, func.srclocs[inst]);
+                    put_unimp(sink);
+                    put_r(bits, in_reg0, in_reg1, out_reg0, sink);
+                "#,
+            ),
+    );
+
     recipes.push(
         EncodingRecipeBuilder::new("Ii", &formats.binary_imm, 4)
             .operands_in(vec![gpr])
@@ -162,6 +180,20 @@ pub(crate) fn define(shared_defs: &SharedDefinitions, regs: &IsaRegs) -> RecipeG
             .emit("put_i(bits, in_reg0, 0, out_reg0, sink);"),
     );
 
+    // Zero-extension from 32 to 64 bits, done with a pair of shifts.
+    recipes.push(
+        EncodingRecipeBuilder::new("Iuext32", &formats.unary, 8)
+            .operands_in(vec![gpr])
+            .operands_out(vec![gpr])
+            .emit(
+                r#"
+                    // slli rd, rs, 32; srli rd, rd, 32.
+                    put_rshamt(bits, in_reg0, 32, out_reg0, sink);
+                    put_rshamt(bits | (0b100 << 5), out_reg0, 32, out_reg0, sink);
+                "#,
+            ),
+    );
+
     // Same for a GPR regmove.
     recipes.push(
         EncodingRecipeBuilder::new("Irmov", &formats.reg_move, 4)
@@ -241,12 +273,94 @@ pub(crate) fn define(shared_defs: &SharedDefinitions, regs: &IsaRegs) -> RecipeG
             ),
     );
 
-    // Spill of a GPR.
+    // I-type loads with a 12-bit signed offset.
+    recipes.push(
+        EncodingRecipeBuilder::new("Iload", &formats.load, 4)
+            .operands_in(vec![gpr])
+            .operands_out(vec![gpr])
+            .inst_predicate(InstructionPredicate::new_is_signed_int(
+                &*formats.load,
+                "offset",
+                12,
+                0,
+            ))
+            .emit(
+                r#"
+                    if !flags.notrap() {
+                        sink.trap(TrapCode::HeapOutOfBounds, func.srclocs[inst]);
+                    }
+                    put_i(bits, in_reg0, offset.into(), out_reg0, sink);
+                "#,
+            ),
+    );
+
+    // Same for a floating point destination.
+    recipes.push(
+        EncodingRecipeBuilder::new("FIload", &formats.load, 4)
+            .operands_in(vec![gpr])
+            .operands_out(vec![fpr])
+            .inst_predicate(InstructionPredicate::new_is_signed_int(
+                &*formats.load,
+                "offset",
+                12,
+                0,
+            ))
+            .emit(
+                r#"
+                    if !flags.notrap() {
+                        sink.trap(TrapCode::HeapOutOfBounds, func.srclocs[inst]);
+                    }
+                    put_i(bits, in_reg0, offset.into(), out_reg0, sink);
+                "#,
+            ),
+    );
+
+    // S-type stores with a 12-bit signed offset.
+    recipes.push(
+        EncodingRecipeBuilder::new("S", &formats.store, 4)
+            .operands_in(vec![gpr, gpr])
+            .inst_predicate(InstructionPredicate::new_is_signed_int(
+                &*formats.store,
+                "offset",
+                12,
+                0,
+            ))
+            .emit(
+                r#"
+                    if !flags.notrap() {
+                        sink.trap(TrapCode::HeapOutOfBounds, func.srclocs[inst]);
+                    }
+                    put_s(bits, in_reg1, in_reg0, offset.into(), sink);
+                "#,
+            ),
+    );
+
+    // Same for a floating point value.
+    recipes.push(
+        EncodingRecipeBuilder::new("FS", &formats.store, 4)
+            .operands_in(vec![fpr, gpr])
+            .inst_predicate(InstructionPredicate::new_is_signed_int(
+                &*formats.store,
+                "offset",
+                12,
+                0,
+            ))
+            .emit(
+                r#"
+                    if !flags.notrap() {
+                        sink.trap(TrapCode::HeapOutOfBounds, func.srclocs[inst]);
+                    }
+                    put_s(bits, in_reg1, in_reg0, offset.into(), sink);
+                "#,
+            ),
+    );
+
+    // Spill of a GPR, stored relative to %x2, the stack pointer.
     recipes.push(
         EncodingRecipeBuilder::new("GPsp", &formats.unary, 4)
             .operands_in(vec![gpr])
             .operands_out(vec![Stack::new(gpr)])
-            .emit("unimplemented!();"),
+            .emit("put_s(bits, 2, in_reg0, out_stk0.offset.into(), sink);"),
     );
 
     // Fill of a GPR.
@@ -254,7 +368,23 @@ pub(crate) fn define(shared_defs: &SharedDefinitions, regs: &IsaRegs) -> RecipeG
         EncodingRecipeBuilder::new("GPfi", &formats.unary, 4)
             .operands_in(vec![Stack::new(gpr)])
             .operands_out(vec![gpr])
-            .emit("unimplemented!();"),
+            .emit("put_i(bits, 2, in_stk0.offset.into(), out_reg0, sink);"),
+    );
+
+    // Spill of an FPR.
+    recipes.push(
+        EncodingRecipeBuilder::new("FPsp", &formats.unary, 4)
+            .operands_in(vec![fpr])
+            .operands_out(vec![Stack::new(fpr)])
+            .emit("put_s(bits, 2, in_reg0, out_stk0.offset.into(), sink);"),
+    );
+
+    // Fill of an FPR.
+    recipes.push(
+        EncodingRecipeBuilder::new("FPfi", &formats.unary, 4)
+            .operands_in(vec![Stack::new(fpr)])
+            .operands_out(vec![fpr])
+            .emit("put_i(bits, 2, in_stk0.offset.into(), out_reg0, sink);"),
     );
 
     // Stack-slot to same stack-slot copy, which is guaranteed to turn into a no-op.
@@ -274,5 +404,137 @@ pub(crate) fn define(shared_defs: &SharedDefinitions, regs: &IsaRegs) -> RecipeG
             .emit(""),
     );
 
+    // Same for an FPR.
+    recipes.push(
+        EncodingRecipeBuilder::new("ffillnull", &formats.unary, 0)
+            .operands_in(vec![Stack::new(fpr)])
+            .operands_out(vec![fpr])
+            .clobbers_flags(false)
+            .emit(""),
+    );
+
+    // No-op bit casts of soft-float values, which live in the integer registers.
+    recipes.push(
+        EncodingRecipeBuilder::new("null", &formats.unary, 0)
+            .operands_in(vec![gpr])
+            .operands_out(vec![0])
+            .emit(""),
+    );
+
+    // `unimp`, the canonical illegal instruction.
+    recipes.push(EncodingRecipeBuilder::new("trap", &formats.trap, 4).emit(
+        r#"
+                    sink.trap(code, func.srclocs[inst]);
+                    put_unimp(sink);
+                "#,
+    ));
+
+    // R-type floating point arithmetic.
+    recipes.push(
+        EncodingRecipeBuilder::new("FR", &formats.binary, 4)
+            .operands_in(vec![fpr, fpr])
+            .operands_out(vec![fpr])
+            .emit("put_r(bits, in_reg0, in_reg1, out_reg0, sink);"),
+    );
+
+    // R-type sign injection with the same register as both sources. This is used for `fmv`,
+    // `fneg` and `fabs`.
+    recipes.push(
+        EncodingRecipeBuilder::new("FRdup", &formats.unary, 4)
+            .operands_in(vec![fpr])
+            .operands_out(vec![fpr])
+            .emit("put_r(bits, in_reg0, in_reg0, out_reg0, sink);"),
+    );
+
+    // Same for an FPR regmove.
+    recipes.push(
+        EncodingRecipeBuilder::new("FRrmov", &formats.reg_move, 4)
+            .operands_in(vec![fpr])
+            .emit("put_r(bits, src, src, dst, sink);"),
+    );
+
+    // Same for copy-to-SSA -- FPR regmove.
+    recipes.push(
+        EncodingRecipeBuilder::new("Fcopytossa", &formats.copy_to_ssa, 4)
+            // No operands_in to mention, because a source register is specified directly.
+            .operands_out(vec![fpr])
+            .emit("put_r(bits, src, src, out_reg0, sink);"),
+    );
+
+    // Unary OP-FP instructions where rs2 selects the operation. The encbits are
+    // `rs2 | (funct3 << 5) | (funct7 << 8)`.
+    recipes.push(
+        EncodingRecipeBuilder::new("FRu", &formats.unary, 4)
+            .operands_in(vec![fpr])
+            .operands_out(vec![fpr])
+            .emit("put_opfp_unary(bits, in_reg0, out_reg0, sink);"),
+    );
+
+    // Same, from a GPR to an FPR.
+    recipes.push(
+        EncodingRecipeBuilder::new("FRgf", &formats.unary, 4)
+            .operands_in(vec![gpr])
+            .operands_out(vec![fpr])
+            .emit("put_opfp_unary(bits, in_reg0, out_reg0, sink);"),
+    );
+
+    // Same, from an FPR to a GPR.
+    recipes.push(
+        EncodingRecipeBuilder::new("FRfg", &formats.unary, 4)
+            .operands_in(vec![fpr])
+            .operands_out(vec![gpr])
+            .emit("put_opfp_unary(bits, in_reg0, out_reg0, sink);"),
+    );
+
+    // R4-type fused multiply-add.
+    recipes.push(
+        EncodingRecipeBuilder::new("FR4", &formats.ternary, 4)
+            .operands_in(vec![fpr, fpr, fpr])
+            .operands_out(vec![fpr])
+            .emit("put_r4(bits, in_reg0, in_reg1, in_reg2, out_reg0, sink);"),
+    );
+
+    // R-type floating point comparisons. RISC-V only has `feq`, `flt` and `fle`, so the other
+    // condition codes are handled by swapping the operands and inverting the result.
+    recipes.push(
+        EncodingRecipeBuilder::new("FRcmp", &formats.float_compare, 4)
+            .operands_in(vec![fpr, fpr])
+            .operands_out(vec![gpr])
+            .emit("put_r(bits, in_reg0, in_reg1, out_reg0, sink);"),
+    );
+
+    recipes.push(
+        EncodingRecipeBuilder::new("FRcmpr", &formats.float_compare, 4)
+            .operands_in(vec![fpr, fpr])
+            .operands_out(vec![gpr])
+            .emit("put_r(bits, in_reg1, in_reg0, out_reg0, sink);"),
+    );
+
+    recipes.push(
+        EncodingRecipeBuilder::new("FRcmpn", &formats.float_compare, 8)
+            .operands_in(vec![fpr, fpr])
+            .operands_out(vec![gpr])
+            .emit(
+                r#"
+                    put_r(bits, in_reg0, in_reg1, out_reg0, sink);
+                    // xori rd, rd, 1.
+                    put_i(0b100_00100, out_reg0, 1, out_reg0, sink);
+                "#,
+            ),
+    );
+
+    recipes.push(
+        EncodingRecipeBuilder::new("FRcmprn", &formats.float_compare, 8)
+            .operands_in(vec![fpr, fpr])
+            .operands_out(vec![gpr])
+            .emit(
+                r#"
+                    put_r(bits, in_reg1, in_reg0, out_reg0, sink);
+                    // xori rd, rd, 1.
+                    put_i(0b100_00100, out_reg0, 1, out_reg0, sink);
+                "#,
+            ),
+    );
+
     recipes
 }
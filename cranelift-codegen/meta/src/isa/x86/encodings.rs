@@ -3,6 +3,7 @@
 use cranelift_codegen_shared::condcodes::IntCC;
 use std::collections::HashMap;
 
+use crate::cdsl::ast::Literal;
 use crate::cdsl::encodings::{Encoding, EncodingBuilder};
 use crate::cdsl::instructions::{
     vector, Bindable, Immediate, InstSpec, Instruction, InstructionGroup, InstructionPredicate,
@@ -381,6 +382,10 @@ pub(crate) fn define(
     let adjust_sp_down = shared.by_name("adjust_sp_down");
     let adjust_sp_down_imm = shared.by_name("adjust_sp_down_imm");
     let adjust_sp_up_imm = shared.by_name("adjust_sp_up_imm");
+    let atomic_cas = shared.by_name("atomic_cas");
+    let atomic_load = shared.by_name("atomic_load");
+    let atomic_rmw = shared.by_name("atomic_rmw");
+    let atomic_store = shared.by_name("atomic_store");
     let band = shared.by_name("band");
     let band_imm = shared.by_name("band_imm");
     let band_not = shared.by_name("band_not");
@@ -413,10 +418,12 @@ pub(crate) fn define(
     let fcvt_from_sint = shared.by_name("fcvt_from_sint");
     let fdemote = shared.by_name("fdemote");
     let fdiv = shared.by_name("fdiv");
+    let fence = shared.by_name("fence");
     let ffcmp = shared.by_name("ffcmp");
     let fill = shared.by_name("fill");
     let fill_nop = shared.by_name("fill_nop");
     let floor = shared.by_name("floor");
+    let fma = shared.by_name("fma");
     let fmul = shared.by_name("fmul");
     let fpromote = shared.by_name("fpromote");
     let fsub = shared.by_name("fsub");
@@ -435,6 +442,8 @@ pub(crate) fn define(
     let ifcmp_sp = shared.by_name("ifcmp_sp");
     let imul = shared.by_name("imul");
     let indirect_jump_table_br = shared.by_name("indirect_jump_table_br");
+    let invoke = shared.by_name("invoke");
+    let invoke_indirect = shared.by_name("invoke_indirect");
     let ireduce = shared.by_name("ireduce");
     let ishl = shared.by_name("ishl");
     let ishl_imm = shared.by_name("ishl_imm");
@@ -462,6 +471,8 @@ pub(crate) fn define(
     let regmove = shared.by_name("regmove");
     let regspill = shared.by_name("regspill");
     let return_ = shared.by_name("return");
+    let return_call = shared.by_name("return_call");
+    let return_call_indirect = shared.by_name("return_call_indirect");
     let rotl = shared.by_name("rotl");
     let rotl_imm = shared.by_name("rotl_imm");
     let rotr = shared.by_name("rotr");
@@ -509,6 +520,8 @@ pub(crate) fn define(
     let x86_bsf = x86.by_name("x86_bsf");
     let x86_bsr = x86.by_name("x86_bsr");
     let x86_cvtt2si = x86.by_name("x86_cvtt2si");
+    let x86_elf_tls_gd_addr = x86.by_name("x86_elf_tls_gd_addr");
+    let x86_elf_tls_ie_addr = x86.by_name("x86_elf_tls_ie_addr");
     let x86_fmax = x86.by_name("x86_fmax");
     let x86_fmin = x86.by_name("x86_fmin");
     let x86_insertps = x86.by_name("x86_insertps");
@@ -530,8 +543,11 @@ pub(crate) fn define(
     let x86_push = x86.by_name("x86_push");
     let x86_sdivmodx = x86.by_name("x86_sdivmodx");
     let x86_smulx = x86.by_name("x86_smulx");
+    let x86_stack_probe = x86.by_name("x86_stack_probe");
+    let x86_stack_probe_loop = x86.by_name("x86_stack_probe_loop");
     let x86_udivmodx = x86.by_name("x86_udivmodx");
     let x86_umulx = x86.by_name("x86_umulx");
+    let x86_vpbroadcast = x86.by_name("x86_vpbroadcast");
 
     // Shorthands for recipes.
     let rec_adjustsp = r.template("adjustsp");
@@ -539,6 +555,15 @@ pub(crate) fn define(
     let rec_adjustsp_id = r.template("adjustsp_id");
     let rec_allones_fnaddr4 = r.template("allones_fnaddr4");
     let rec_allones_fnaddr8 = r.template("allones_fnaddr8");
+    let rec_atomic_cas = r.template("atomic_cas");
+    let rec_atomic_cas_abcd = r.template("atomic_cas_abcd");
+    let rec_atomic_ld = r.template("atomic_ld");
+    let rec_atomic_rmw = r.template("atomic_rmw");
+    let rec_atomic_rmw_abcd = r.template("atomic_rmw_abcd");
+    let rec_atomic_st = r.template("atomic_st");
+    let rec_atomic_st_abcd = r.template("atomic_st_abcd");
+    let rec_atomic_st_mfence = r.template("atomic_st_mfence");
+    let rec_atomic_st_mfence_abcd = r.template("atomic_st_mfence_abcd");
     let rec_brfb = r.template("brfb");
     let rec_brfd = r.template("brfd");
     let rec_brib = r.template("brib");
@@ -551,6 +576,8 @@ pub(crate) fn define(
     let rec_copysp = r.template("copysp");
     let rec_div = r.template("div");
     let rec_debugtrap = r.recipe("debugtrap");
+    let rec_elf_tls_gd_addr = r.recipe("elf_tls_gd_addr");
+    let rec_elf_tls_ie_addr = r.recipe("elf_tls_ie_addr");
     let rec_f32imm_z = r.template("f32imm_z");
     let rec_f64imm_z = r.template("f64imm_z");
     let rec_fa = r.template("fa");
@@ -560,6 +587,7 @@ pub(crate) fn define(
     let rec_fcscc = r.template("fcscc");
     let rec_ffillnull = r.recipe("ffillnull");
     let rec_ffillSib32 = r.template("ffillSib32");
+    let rec_fence_nop = r.recipe("fence_nop");
     let rec_fillnull = r.recipe("fillnull");
     let rec_fillSib32 = r.template("fillSib32");
     let rec_fld = r.template("fld");
@@ -594,6 +622,9 @@ pub(crate) fn define(
     let rec_icscc_ib = r.template("icscc_ib");
     let rec_icscc_id = r.template("icscc_id");
     let rec_indirect_jmp = r.template("indirect_jmp");
+    let rec_invoke_id = r.template("invoke_id");
+    let rec_invoke_plt_id = r.template("invoke_plt_id");
+    let rec_invoke_r = r.template("invoke_r");
     let rec_is_zero = r.template("is_zero");
     let rec_jmpb = r.template("jmpb");
     let rec_jmpd = r.template("jmpd");
@@ -605,11 +636,13 @@ pub(crate) fn define(
     let rec_ldWithIndex = r.template("ldWithIndex");
     let rec_ldWithIndexDisp32 = r.template("ldWithIndexDisp32");
     let rec_ldWithIndexDisp8 = r.template("ldWithIndexDisp8");
+    let rec_mfence = r.template("mfence");
     let rec_mulx = r.template("mulx");
     let rec_null = r.recipe("null");
     let rec_null_fpr = r.recipe("null_fpr");
     let rec_pcrel_fnaddr8 = r.template("pcrel_fnaddr8");
     let rec_pcrel_gvaddr8 = r.template("pcrel_gvaddr8");
+    let rec_pfcmp = r.template("pfcmp");
     let rec_popq = r.template("popq");
     let rec_pu_id = r.template("pu_id");
     let rec_pu_id_bool = r.template("pu_id_bool");
@@ -646,6 +679,8 @@ pub(crate) fn define(
     let rec_spillSib32 = r.template("spillSib32");
     let rec_st = r.template("st");
     let rec_stacknull = r.recipe("stacknull");
+    let rec_stackprobe = r.template("stackprobe");
+    let rec_stackprobe_loop = r.template("stackprobe_loop");
     let rec_stDisp32 = r.template("stDisp32");
     let rec_stDisp32_abcd = r.template("stDisp32_abcd");
     let rec_stDisp8 = r.template("stDisp8");
@@ -661,6 +696,9 @@ pub(crate) fn define(
     let rec_t8jccd_abcd = r.template("t8jccd_abcd");
     let rec_t8jccd_long = r.template("t8jccd_long");
     let rec_tjccb = r.template("tjccb");
+    let rec_tailcall_id = r.template("tailcall_id");
+    let rec_tailcall_plt_id = r.template("tailcall_plt_id");
+    let rec_tailcall_r = r.template("tailcall_r");
     let rec_tjccd = r.template("tjccd");
     let rec_trap = r.template("trap");
     let rec_trapif = r.recipe("trapif");
@@ -675,6 +713,24 @@ pub(crate) fn define(
     let rec_urm_noflags_abcd = r.template("urm_noflags_abcd");
     let rec_vconst = r.template("vconst");
     let rec_vconst_optimized = r.template("vconst_optimized");
+    let rec_vfa = r.template("vfa");
+    let rec_vfax = r.template("vfax");
+    let rec_vffillSib32 = r.template("vffillSib32");
+    let rec_vfld = r.template("vfld");
+    let rec_vfldDisp32 = r.template("vfldDisp32");
+    let rec_vfldDisp8 = r.template("vfldDisp8");
+    let rec_vfma = r.template("vfma");
+    let rec_vfregfill32 = r.template("vfregfill32");
+    let rec_vfregspill32 = r.template("vfregspill32");
+    let rec_vfrmov = r.template("vfrmov");
+    let rec_vfrurm = r.template("vfrurm");
+    let rec_vfspillSib32 = r.template("vfspillSib32");
+    let rec_vfst = r.template("vfst");
+    let rec_vfstDisp32 = r.template("vfstDisp32");
+    let rec_vfstDisp8 = r.template("vfstDisp8");
+    let rec_vfurm = r.template("vfurm");
+    let rec_vicscc_fpr = r.template("vicscc_fpr");
+    let rec_vpfcmp = r.template("vpfcmp");
 
     // Predicates shorthands.
     let all_ones_funcaddrs_and_not_is_pic =
@@ -690,6 +746,9 @@ pub(crate) fn define(
     let use_ssse3_simd = settings.predicate_by_name("use_ssse3_simd");
     let use_sse41_simd = settings.predicate_by_name("use_sse41_simd");
     let use_sse42_simd = settings.predicate_by_name("use_sse42_simd");
+    let use_avx_simd = settings.predicate_by_name("use_avx_simd");
+    let use_avx2_simd = settings.predicate_by_name("use_avx2_simd");
+    let use_fma = settings.predicate_by_name("use_fma");
 
     // Definitions.
     let mut e = PerCpuModeEncodings::new();
@@ -1016,6 +1075,86 @@ pub(crate) fn define(
         e.enc_i32_i64_ld_st(sload8, true, recipe.opcodes(&MOVSX_BYTE));
     }
 
+    // Atomic memory accesses. Aligned loads and stores are atomic on x86, so they share their
+    // opcodes with the plain loads and stores above.
+    e.enc_i32_i64_ld_st(atomic_load, true, rec_atomic_ld.opcodes(&MOV_LOAD));
+    e.enc_both(
+        atomic_load.bind(I16).bind(Any),
+        rec_atomic_ld.opcodes(&MOVZX_WORD),
+    );
+    e.enc_both(
+        atomic_load.bind(I8).bind(Any),
+        rec_atomic_ld.opcodes(&MOVZX_BYTE),
+    );
+
+    for recipe in &[rec_atomic_st, rec_atomic_st_mfence] {
+        e.enc_i32_i64_ld_st(atomic_store, true, recipe.opcodes(&MOV_STORE));
+        e.enc_both(
+            atomic_store.bind(I16).bind(Any),
+            recipe.opcodes(&MOV_STORE_16),
+        );
+    }
+    for recipe in &[rec_atomic_st_abcd, rec_atomic_st_mfence_abcd] {
+        e.enc_both(
+            atomic_store.bind(I8).bind(Any),
+            recipe.opcodes(&MOV_BYTE_STORE),
+        );
+    }
+
+    // Only `add` and `xchg` have a direct encoding. The other read-modify-write operations are
+    // legalized into a compare-and-swap loop.
+    let atomic_rmw_op = |name| {
+        InstructionPredicate::new_is_field_equal(
+            &*formats.atomic_rmw,
+            "op",
+            Literal::enumerator_for(&shared_defs.imm.atomic_rmw_op, name).to_rust_code(),
+        )
+    };
+    for &(name, opcodes, opcodes_16, opcodes_byte) in &[
+        ("add", &XADD[..], &XADD_16[..], &XADD_BYTE[..]),
+        ("xchg", &XCHG[..], &XCHG_16[..], &XCHG_BYTE[..]),
+    ] {
+        let instp = atomic_rmw_op(name);
+        e.enc32_instp(
+            atomic_rmw.bind(I32).bind(Any),
+            rec_atomic_rmw.opcodes(opcodes),
+            instp.clone(),
+        );
+        e.enc_x86_64_instp(
+            atomic_rmw.bind(I32).bind(Any),
+            rec_atomic_rmw.opcodes(opcodes),
+            instp.clone(),
+        );
+        e.enc64_instp(
+            atomic_rmw.bind(I64).bind(Any),
+            rec_atomic_rmw.opcodes(opcodes).rex().w(),
+            instp.clone(),
+        );
+        e.enc_both_instp(
+            atomic_rmw.bind(I16).bind(Any),
+            rec_atomic_rmw.opcodes(opcodes_16),
+            instp.clone(),
+        );
+        e.enc_both_instp(
+            atomic_rmw.bind(I8).bind(Any),
+            rec_atomic_rmw_abcd.opcodes(opcodes_byte),
+            instp,
+        );
+    }
+
+    e.enc_i32_i64_ld_st(atomic_cas, true, rec_atomic_cas.opcodes(&CMPXCHG));
+    e.enc_both(
+        atomic_cas.bind(I16).bind(Any),
+        rec_atomic_cas.opcodes(&CMPXCHG_16),
+    );
+    e.enc_both(
+        atomic_cas.bind(I8).bind(Any),
+        rec_atomic_cas_abcd.opcodes(&CMPXCHG_BYTE),
+    );
+
+    e.enc_32_64(fence, rec_mfence.opcodes(&MFENCE).rrr(6));
+    e.enc_32_64_rec(fence, rec_fence_nop, 0);
+
     e.enc_i32_i64(fill, rec_fillSib32.opcodes(&MOV_LOAD));
     e.enc_i32_i64(regfill, rec_regfill32.opcodes(&MOV_LOAD));
     e.enc_r32_r64_rex_only(fill, rec_fillSib32.opcodes(&MOV_LOAD));
@@ -1121,6 +1260,21 @@ pub(crate) fn define(
         rec_adjustsp_id.opcodes(&CMP_IMM).rrr(5).rex().w(),
     );
 
+    // Inline stack probes.
+    e.enc32(x86_stack_probe, rec_stackprobe.opcodes(&CMP_IMM).rrr(5));
+    e.enc64(
+        x86_stack_probe,
+        rec_stackprobe.opcodes(&CMP_IMM).rrr(5).rex().w(),
+    );
+    e.enc32(
+        x86_stack_probe_loop.bind(I32),
+        rec_stackprobe_loop.opcodes(&CMP_IMM).rrr(5),
+    );
+    e.enc64(
+        x86_stack_probe_loop.bind(I64),
+        rec_stackprobe_loop.opcodes(&CMP_IMM).rrr(5).rex().w(),
+    );
+
     // Float loads and stores.
     e.enc_both(load.bind(F32).bind(Any), rec_fld.opcodes(&MOVSS_LOAD));
     e.enc_both(load.bind(F32).bind(Any), rec_fldDisp8.opcodes(&MOVSS_LOAD));
@@ -1286,6 +1440,10 @@ pub(crate) fn define(
         is_pic,
     );
 
+    // Thread local storage.
+    e.enc64_rec(x86_elf_tls_gd_addr.bind(I64), rec_elf_tls_gd_addr, 0);
+    e.enc64_rec(x86_elf_tls_ie_addr.bind(I64), rec_elf_tls_ie_addr, 0);
+
     // Stack addresses.
     //
     // TODO: Add encoding rules for stack_load and stack_store, so that they
@@ -1300,7 +1458,11 @@ pub(crate) fn define(
 
     // 64-bit, colocated, both PIC and non-PIC. Use the call instruction's pc-relative field.
     let is_colocated_func = InstructionPredicate::new_is_colocated_func(&*formats.call, "func_ref");
-    e.enc64_instp(call, rec_call_id.opcodes(&CALL_RELATIVE), is_colocated_func);
+    e.enc64_instp(
+        call,
+        rec_call_id.opcodes(&CALL_RELATIVE),
+        is_colocated_func.clone(),
+    );
 
     // 64-bit, non-colocated, PIC. There is no 64-bit non-colocated non-PIC version, since non-PIC
     // is currently using the large model, which requires calls be lowered to
@@ -1320,9 +1482,57 @@ pub(crate) fn define(
         rec_call_r.opcodes(&JUMP_ABSOLUTE).rrr(2),
     );
 
+    // Invokes use the same encodings as calls.
+    e.enc32(invoke, rec_invoke_id.opcodes(&CALL_RELATIVE));
+    e.enc64_instp(
+        invoke,
+        rec_invoke_id.opcodes(&CALL_RELATIVE),
+        InstructionPredicate::new_is_colocated_func(&*formats.invoke, "func_ref"),
+    );
+    e.enc64_isap(invoke, rec_invoke_plt_id.opcodes(&CALL_RELATIVE), is_pic);
+
+    e.enc32(
+        invoke_indirect.bind(I32),
+        rec_invoke_r.opcodes(&JUMP_ABSOLUTE).rrr(2),
+    );
+    e.enc64(
+        invoke_indirect.bind(I64),
+        rec_invoke_r.opcodes(&JUMP_ABSOLUTE).rrr(2).rex(),
+    );
+    e.enc64(
+        invoke_indirect.bind(I64),
+        rec_invoke_r.opcodes(&JUMP_ABSOLUTE).rrr(2),
+    );
+
     e.enc32(return_, rec_ret.opcodes(&RET_NEAR));
     e.enc64(return_, rec_ret.opcodes(&RET_NEAR));
 
+    // Tail calls are encoded like calls, with jumps in place of the call opcodes.
+    e.enc32(return_call, rec_tailcall_id.opcodes(&JUMP_NEAR_RELATIVE));
+    e.enc64_instp(
+        return_call,
+        rec_tailcall_id.opcodes(&JUMP_NEAR_RELATIVE),
+        is_colocated_func,
+    );
+    e.enc64_isap(
+        return_call,
+        rec_tailcall_plt_id.opcodes(&JUMP_NEAR_RELATIVE),
+        is_pic,
+    );
+
+    e.enc32(
+        return_call_indirect.bind(I32),
+        rec_tailcall_r.opcodes(&JUMP_ABSOLUTE).rrr(4),
+    );
+    e.enc64(
+        return_call_indirect.bind(I64),
+        rec_tailcall_r.opcodes(&JUMP_ABSOLUTE).rrr(4).rex(),
+    );
+    e.enc64(
+        return_call_indirect.bind(I64),
+        rec_tailcall_r.opcodes(&JUMP_ABSOLUTE).rrr(4),
+    );
+
     // Branches.
     e.enc32(jump, rec_jmpb.opcodes(&JUMP_SHORT));
     e.enc64(jump, rec_jmpb.opcodes(&JUMP_SHORT));
@@ -1706,9 +1916,9 @@ pub(crate) fn define(
     e.enc_both(ffcmp.bind(F32), rec_fcmp.opcodes(&UCOMISS));
     e.enc_both(ffcmp.bind(F64), rec_fcmp.opcodes(&UCOMISD));
 
-    // SIMD vector size: eventually multiple vector sizes may be supported but for now only
-    // SSE-sized vectors are available.
+    // SIMD vector sizes: SSE-sized vectors, and the wider vectors available with AVX.
     let sse_vector_size: u64 = 128;
+    let avx_vector_size: u64 = 256;
 
     // SIMD splat: before x86 can use vector data, it must be moved to XMM registers; see
     // legalize.rs for how this is done; once there, x86_pshuf* (below) is used for broadcasting the
@@ -1716,6 +1926,217 @@ pub(crate) fn define(
 
     let allowed_simd_type = |t: &LaneType| t.lane_bits() >= 8 && t.lane_bits() < 128;
 
+    // AVX: VEX-encoded forms of the SSE instructions below, for both 128-bit vectors and the
+    // 256-bit vectors that only AVX supports. They come first so that they are preferred when
+    // available: the three-operand forms don't tie their output to the first input, and the VEX
+    // prefix reaches all the XMM registers without a REX prefix. Floating point instructions on
+    // 256-bit vectors only need AVX, but the integer ones need AVX2.
+    for &vector_size in &[sse_vector_size, avx_vector_size] {
+        let is_256 = vector_size == avx_vector_size;
+        let int_isap = if is_256 { use_avx2_simd } else { use_avx_simd };
+        let vex: fn(Template) -> Template = if is_256 { |t| t.l() } else { |t| t };
+
+        // Integer arithmetic.
+        for (ty, inst, opcodes) in &[
+            (I8, iadd, &PADDB[..]),
+            (I16, iadd, &PADDW[..]),
+            (I32, iadd, &PADDD[..]),
+            (I64, iadd, &PADDQ[..]),
+            (I8, sadd_sat, &PADDSB[..]),
+            (I16, sadd_sat, &PADDSW[..]),
+            (I8, uadd_sat, &PADDUSB[..]),
+            (I16, uadd_sat, &PADDUSW[..]),
+            (I8, isub, &PSUBB[..]),
+            (I16, isub, &PSUBW[..]),
+            (I32, isub, &PSUBD[..]),
+            (I64, isub, &PSUBQ[..]),
+            (I8, ssub_sat, &PSUBSB[..]),
+            (I16, ssub_sat, &PSUBSW[..]),
+            (I8, usub_sat, &PSUBUSB[..]),
+            (I16, usub_sat, &PSUBUSW[..]),
+            (I16, imul, &PMULLW[..]),
+            (I32, imul, &PMULLD[..]),
+            (I8, x86_pmaxs, &PMAXSB[..]),
+            (I16, x86_pmaxs, &PMAXSW[..]),
+            (I32, x86_pmaxs, &PMAXSD[..]),
+            (I8, x86_pmaxu, &PMAXUB[..]),
+            (I16, x86_pmaxu, &PMAXUW[..]),
+            (I32, x86_pmaxu, &PMAXUD[..]),
+            (I8, x86_pmins, &PMINSB[..]),
+            (I16, x86_pmins, &PMINSW[..]),
+            (I32, x86_pmins, &PMINSD[..]),
+            (I8, x86_pminu, &PMINUB[..]),
+            (I16, x86_pminu, &PMINUW[..]),
+            (I32, x86_pminu, &PMINUD[..]),
+        ] {
+            let inst = inst.bind(vector(*ty, vector_size));
+            e.enc_32_64_maybe_isap(inst, vex(rec_vfa.opcodes(opcodes)), Some(int_isap));
+        }
+
+        // Integer comparisons.
+        {
+            use IntCC::*;
+            for (ty, cc, opcodes) in &[
+                (I8, Equal, &PCMPEQB[..]),
+                (I16, Equal, &PCMPEQW[..]),
+                (I32, Equal, &PCMPEQD[..]),
+                (I64, Equal, &PCMPEQQ[..]),
+                (I8, SignedGreaterThan, &PCMPGTB[..]),
+                (I16, SignedGreaterThan, &PCMPGTW[..]),
+                (I32, SignedGreaterThan, &PCMPGTD[..]),
+                (I64, SignedGreaterThan, &PCMPGTQ[..]),
+            ] {
+                let instruction = icmp
+                    .bind(Immediate::IntCC(*cc))
+                    .bind(vector(*ty, vector_size));
+                let template = vex(rec_vicscc_fpr.opcodes(opcodes));
+                e.enc_32_64_maybe_isap(instruction, template, Some(int_isap));
+            }
+        }
+
+        // Logical operations.
+        for ty in ValueType::all_lane_types().filter(allowed_simd_type) {
+            let band = band.bind(vector(ty, vector_size));
+            e.enc_32_64_maybe_isap(band, vex(rec_vfa.opcodes(&PAND)), Some(int_isap));
+            let band_not = band_not.bind(vector(ty, vector_size));
+            e.enc_32_64_maybe_isap(band_not, vex(rec_vfax.opcodes(&PANDN)), Some(int_isap));
+            let bor = bor.bind(vector(ty, vector_size));
+            e.enc_32_64_maybe_isap(bor, vex(rec_vfa.opcodes(&POR)), Some(int_isap));
+            let bxor = bxor.bind(vector(ty, vector_size));
+            e.enc_32_64_maybe_isap(bxor, vex(rec_vfa.opcodes(&PXOR)), Some(int_isap));
+        }
+
+        // Float arithmetic.
+        for (ty, inst, opcodes) in &[
+            (F32, fadd, &ADDPS[..]),
+            (F64, fadd, &ADDPD[..]),
+            (F32, fsub, &SUBPS[..]),
+            (F64, fsub, &SUBPD[..]),
+            (F32, fmul, &MULPS[..]),
+            (F64, fmul, &MULPD[..]),
+            (F32, fdiv, &DIVPS[..]),
+            (F64, fdiv, &DIVPD[..]),
+            (F32, x86_fmin, &MINPS[..]),
+            (F64, x86_fmin, &MINPD[..]),
+            (F32, x86_fmax, &MAXPS[..]),
+            (F64, x86_fmax, &MAXPD[..]),
+        ] {
+            let inst = inst.bind(vector(*ty, vector_size));
+            e.enc_32_64_maybe_isap(inst, vex(rec_vfa.opcodes(opcodes)), Some(use_avx_simd));
+        }
+        for (ty, opcodes) in &[(F32, &SQRTPS[..]), (F64, &SQRTPD[..])] {
+            let sqrt = sqrt.bind(vector(*ty, vector_size));
+            e.enc_32_64_maybe_isap(sqrt, vex(rec_vfurm.opcodes(opcodes)), Some(use_avx_simd));
+        }
+        for (ty, opcodes) in &[(F32, &CMPPS[..]), (F64, &CMPPD[..])] {
+            let fcmp = fcmp.bind(vector(*ty, vector_size));
+            e.enc_32_64_maybe_isap(fcmp, vex(rec_vpfcmp.opcodes(opcodes)), Some(use_avx_simd));
+        }
+        e.enc_32_64_maybe_isap(
+            fcvt_from_sint
+                .bind(vector(F32, vector_size))
+                .bind(vector(I32, vector_size)),
+            vex(rec_vfurm.opcodes(&CVTDQ2PS)),
+            Some(use_avx_simd),
+        );
+        e.enc_32_64_maybe_isap(
+            x86_cvtt2si
+                .bind(vector(I32, vector_size))
+                .bind(vector(F32, vector_size)),
+            vex(rec_vfurm.opcodes(&CVTTPS2DQ)),
+            Some(use_avx_simd),
+        );
+
+        // Register movement: store, load, spill, fill, regmove, copy.
+        for ty in ValueType::all_lane_types().filter(allowed_simd_type) {
+            let isap = Some(use_avx_simd);
+
+            let bound_store = store.bind(vector(ty, vector_size)).bind(Any);
+            let template = vex(rec_vfst.opcodes(&MOVUPS_STORE));
+            e.enc_32_64_maybe_isap(bound_store.clone(), template, isap);
+            let template = vex(rec_vfstDisp8.opcodes(&MOVUPS_STORE));
+            e.enc_32_64_maybe_isap(bound_store.clone(), template, isap);
+            let template = vex(rec_vfstDisp32.opcodes(&MOVUPS_STORE));
+            e.enc_32_64_maybe_isap(bound_store, template, isap);
+
+            let bound_load = load.bind(vector(ty, vector_size)).bind(Any);
+            let template = vex(rec_vfld.opcodes(&MOVUPS_LOAD));
+            e.enc_32_64_maybe_isap(bound_load.clone(), template, isap);
+            let template = vex(rec_vfldDisp8.opcodes(&MOVUPS_LOAD));
+            e.enc_32_64_maybe_isap(bound_load.clone(), template, isap);
+            let template = vex(rec_vfldDisp32.opcodes(&MOVUPS_LOAD));
+            e.enc_32_64_maybe_isap(bound_load, template, isap);
+
+            let bound_spill = spill.bind(vector(ty, vector_size));
+            let template = vex(rec_vfspillSib32.opcodes(&MOVUPS_STORE));
+            e.enc_32_64_maybe_isap(bound_spill, template, isap);
+            let bound_regspill = regspill.bind(vector(ty, vector_size));
+            let template = vex(rec_vfregspill32.opcodes(&MOVUPS_STORE));
+            e.enc_32_64_maybe_isap(bound_regspill, template, isap);
+
+            let bound_fill = fill.bind(vector(ty, vector_size));
+            let template = vex(rec_vffillSib32.opcodes(&MOVUPS_LOAD));
+            e.enc_32_64_maybe_isap(bound_fill, template, isap);
+            let bound_regfill = regfill.bind(vector(ty, vector_size));
+            let template = vex(rec_vfregfill32.opcodes(&MOVUPS_LOAD));
+            e.enc_32_64_maybe_isap(bound_regfill, template, isap);
+
+            let bound_regmove = regmove.bind(vector(ty, vector_size));
+            let template = vex(rec_vfrmov.opcodes(&MOVAPS_LOAD));
+            e.enc_32_64_maybe_isap(bound_regmove, template, isap);
+
+            let bound_copy = copy.bind(vector(ty, vector_size));
+            let template = vex(rec_vfurm.opcodes(&MOVAPS_LOAD));
+            e.enc_32_64_maybe_isap(bound_copy, template, isap);
+        }
+    }
+
+    // AVX: instructions on 128-bit vectors with no 256-bit equivalent in Cranelift.
+    for ty in ValueType::all_lane_types().filter(allowed_simd_type) {
+        let instruction = x86_pshufb.bind(vector(ty, sse_vector_size));
+        let template = rec_vfa.opcodes(&PSHUFB);
+        e.enc_32_64_maybe_isap(instruction, template, Some(use_avx_simd));
+    }
+    for (ty, inst, opcodes) in &[
+        (I16, x86_psll, &PSLLW[..]),
+        (I32, x86_psll, &PSLLD[..]),
+        (I64, x86_psll, &PSLLQ[..]),
+        (I16, x86_psrl, &PSRLW[..]),
+        (I32, x86_psrl, &PSRLD[..]),
+        (I64, x86_psrl, &PSRLQ[..]),
+        (I16, x86_psra, &PSRAW[..]),
+        (I32, x86_psra, &PSRAD[..]),
+    ] {
+        let inst = inst.bind(vector(*ty, sse_vector_size));
+        e.enc_32_64_maybe_isap(inst, rec_vfa.opcodes(opcodes), Some(use_avx_simd));
+    }
+
+    // FMA: fused multiply-add of scalars and 128-bit and 256-bit vectors.
+    e.enc_32_64_maybe_isap(fma.bind(F32), rec_vfma.opcodes(&VFMADD213SS), Some(use_fma));
+    e.enc_32_64_maybe_isap(
+        fma.bind(F64),
+        rec_vfma.opcodes(&VFMADD213SS).w(),
+        Some(use_fma),
+    );
+    for &vector_size in &[sse_vector_size, avx_vector_size] {
+        let template = rec_vfma.opcodes(&VFMADD213PS);
+        let template = if vector_size == avx_vector_size {
+            template.l()
+        } else {
+            template
+        };
+        e.enc_32_64_maybe_isap(
+            fma.bind(vector(F32, vector_size)),
+            template.clone(),
+            Some(use_fma),
+        );
+        e.enc_32_64_maybe_isap(
+            fma.bind(vector(F64, vector_size)),
+            template.w(),
+            Some(use_fma),
+        );
+    }
+
     // PSHUFB, 8-bit shuffle using two XMM registers.
     for ty in ValueType::all_lane_types().filter(allowed_simd_type) {
         let instruction = x86_pshufb.bind(vector(ty, sse_vector_size));
@@ -1742,10 +2163,13 @@ pub(crate) fn define(
         } else {
             let template = rec_frurm.opcodes(&MOVD_LOAD_XMM);
             if ty.lane_bits() < 64 {
-                // no 32-bit encodings for 64-bit widths
                 e.enc32(instruction.clone(), template.clone());
+                e.enc_x86_64(instruction, template);
+            } else {
+                // No 32-bit encodings for 64-bit widths; on x86_64 this needs REX.W to become MOVQ
+                // and copy the whole 64-bit register.
+                e.enc64(instruction, template.rex().w());
             }
-            e.enc_x86_64(instruction, template);
         }
     }
 
@@ -1892,15 +2316,19 @@ pub(crate) fn define(
         e.enc_32_64(bound_load.clone(), rec_fldDisp8.opcodes(&MOVUPS_LOAD));
         e.enc_32_64(bound_load, rec_fldDisp32.opcodes(&MOVUPS_LOAD));
 
-        // Spill
+        // Spill. The REX forms reaching all the XMM registers come last, so that register
+        // allocation keeps using the others, and are only picked for values already in a register,
+        // like the XMM registers that cold functions save.
         let bound_spill = spill.bind(vector(ty, sse_vector_size));
-        e.enc_32_64(bound_spill, rec_fspillSib32.opcodes(&MOVUPS_STORE));
+        e.enc_32_64(bound_spill.clone(), rec_fspillSib32.opcodes(&MOVUPS_STORE));
+        e.enc64(bound_spill, rec_fspillSib32.opcodes(&MOVUPS_STORE).rex());
         let bound_regspill = regspill.bind(vector(ty, sse_vector_size));
         e.enc_32_64(bound_regspill, rec_fregspill32.opcodes(&MOVUPS_STORE));
 
         // Fill
         let bound_fill = fill.bind(vector(ty, sse_vector_size));
-        e.enc_32_64(bound_fill, rec_ffillSib32.opcodes(&MOVUPS_LOAD));
+        e.enc_32_64(bound_fill.clone(), rec_ffillSib32.opcodes(&MOVUPS_LOAD));
+        e.enc64(bound_fill, rec_ffillSib32.opcodes(&MOVUPS_LOAD).rex());
         let bound_regfill = regfill.bind(vector(ty, sse_vector_size));
         e.enc_32_64(bound_regfill, rec_fregfill32.opcodes(&MOVUPS_LOAD));
         let bound_fill_nop = fill_nop.bind(vector(ty, sse_vector_size));
@@ -2070,6 +2498,102 @@ pub(crate) fn define(
         e.enc_32_64_maybe_isap(inst_, rec_fa.opcodes(opcodes), *isa_predicate);
     }
 
+    // SIMD float arithmetic; `fmin` and `fmax` are legalized in terms of the x86 variants.
+    for (ty, inst, opcodes) in &[
+        (F32, fadd, &ADDPS[..]),
+        (F64, fadd, &ADDPD[..]),
+        (F32, fsub, &SUBPS[..]),
+        (F64, fsub, &SUBPD[..]),
+        (F32, fmul, &MULPS[..]),
+        (F64, fmul, &MULPD[..]),
+        (F32, fdiv, &DIVPS[..]),
+        (F64, fdiv, &DIVPD[..]),
+        (F32, x86_fmin, &MINPS[..]),
+        (F64, x86_fmin, &MINPD[..]),
+        (F32, x86_fmax, &MAXPS[..]),
+        (F64, x86_fmax, &MAXPD[..]),
+    ] {
+        let inst_ = inst.bind(vector(*ty, sse_vector_size));
+        e.enc_32_64(inst_, rec_fa.opcodes(opcodes));
+    }
+    for (ty, opcodes) in &[(F32, &SQRTPS[..]), (F64, &SQRTPD[..])] {
+        let sqrt = sqrt.bind(vector(*ty, sse_vector_size));
+        e.enc_32_64(sqrt, rec_furm.opcodes(opcodes));
+    }
+
+    // SIMD float comparisons; the condition codes not supported by `pfcmp` are legalized by
+    // swapping the operands.
+    for (ty, opcodes) in &[(F32, &CMPPS[..]), (F64, &CMPPD[..])] {
+        let fcmp = fcmp.bind(vector(*ty, sse_vector_size));
+        e.enc_32_64(fcmp, rec_pfcmp.opcodes(opcodes));
+    }
+
+    // SIMD conversions between I32x4 and F32x4; there are no SSE conversions for 64-bit lanes.
+    e.enc_32_64(
+        fcvt_from_sint
+            .bind(vector(F32, sse_vector_size))
+            .bind(vector(I32, sse_vector_size)),
+        rec_furm.opcodes(&CVTDQ2PS),
+    );
+    e.enc_32_64(
+        x86_cvtt2si
+            .bind(vector(I32, sse_vector_size))
+            .bind(vector(F32, sse_vector_size)),
+        rec_furm.opcodes(&CVTTPS2DQ),
+    );
+
+    // AVX: the 256-bit vector instructions that have no 128-bit VEX counterpart above.
+
+    // 256-bit splat: move the scalar to the low lane of an XMM register with VEX.128 MOVD/MOVQ,
+    // then broadcast it with AVX2's VPBROADCAST*/VBROADCASTS*; see legalize.rs.
+    for ty in ValueType::all_lane_types().filter(allowed_simd_type) {
+        let instruction = scalar_to_vector.bind(vector(ty, avx_vector_size));
+        if ty.is_float() {
+            e.enc_32_64_rec(instruction, rec_null_fpr, 0);
+        } else {
+            let template = rec_vfrurm.opcodes(&MOVD_LOAD_XMM);
+            if ty.lane_bits() < 64 {
+                e.enc_32_64_maybe_isap(instruction, template, Some(use_avx2_simd));
+            } else {
+                // VMOVQ from a GPR is only available on x86_64.
+                e.enc64_isap(instruction, template.w(), use_avx2_simd);
+            }
+        }
+
+        let opcodes = match (ty.is_float(), ty.lane_bits()) {
+            (false, 8) => &VPBROADCASTB,
+            (false, 16) => &VPBROADCASTW,
+            (false, 32) => &VPBROADCASTD,
+            (false, 64) => &VPBROADCASTQ,
+            (true, 32) => &VBROADCASTSS,
+            (true, 64) => &VBROADCASTSD,
+            _ => panic!("invalid lane type for x86_vpbroadcast"),
+        };
+        let instruction = x86_vpbroadcast.bind(vector(ty, avx_vector_size));
+        let template = rec_vfurm.opcodes(opcodes).l();
+        e.enc_32_64_maybe_isap(instruction, template, Some(use_avx2_simd));
+    }
+
+    // 256-bit bitcasts between vector types, which don't need any instruction.
+    for from_type in ValueType::all_lane_types().filter(allowed_simd_type) {
+        for to_type in
+            ValueType::all_lane_types().filter(|t| allowed_simd_type(t) && *t != from_type)
+        {
+            let instruction = raw_bitcast
+                .bind(vector(to_type, avx_vector_size))
+                .bind(vector(from_type, avx_vector_size));
+            e.enc_32_64_rec(instruction, rec_null_fpr, 0);
+        }
+    }
+
+    // 256-bit no-op fills and copies.
+    for ty in ValueType::all_lane_types().filter(allowed_simd_type) {
+        let bound_fill_nop = fill_nop.bind(vector(ty, avx_vector_size));
+        e.enc_32_64_rec(bound_fill_nop, rec_ffillnull, 0);
+        let bound_copy_nop = copy_nop.bind(vector(ty, avx_vector_size));
+        e.enc_32_64_rec(bound_copy_nop, rec_stacknull, 0);
+    }
+
     // Reference type instructions
 
     // Null references implemented as iconst 0.
@@ -7,6 +7,7 @@ use crate::cdsl::operands::Operand;
 use crate::cdsl::types::ValueType;
 use crate::cdsl::typevar::{Interval, TypeSetBuilder, TypeVar};
 
+use crate::shared::entities::EntityRefs;
 use crate::shared::formats::Formats;
 use crate::shared::immediates::Immediates;
 use crate::shared::types;
@@ -16,6 +17,7 @@ pub(crate) fn define(
     mut all_instructions: &mut AllInstructions,
     formats: &Formats,
     immediates: &Immediates,
+    entities: &EntityRefs,
 ) -> InstructionGroup {
     let mut ig = InstructionGroupBuilder::new(&mut all_instructions);
 
@@ -225,6 +227,49 @@ pub(crate) fn define(
         .can_load(true),
     );
 
+    let Offset = &Operand::new("Offset", &immediates.imm64)
+        .with_doc("Bytes to subtract from the stack pointer");
+
+    ig.push(
+        Inst::new(
+            "x86_stack_probe",
+            r#"
+    Extends the stack and probes the new top of the stack.
+
+    Subtracts ``Offset`` from the stack pointer and then writes to the memory
+    it points to, so a guard page is hit before any part of the stack below it
+    can be used. ``Offset`` is constrained to the size of a signed 32-bit
+    integer.
+    "#,
+            &formats.unary_imm,
+        )
+        .operands_in(vec![Offset])
+        .other_side_effects(true)
+        .can_store(true),
+    );
+
+    let count = &Operand::new("count", iWord).with_doc("Number of probes");
+    let zero = &Operand::new("zero", iWord);
+
+    ig.push(
+        Inst::new(
+            "x86_stack_probe_loop",
+            r#"
+    Extends the stack by ``count`` times ``Offset`` bytes in a loop, probing
+    the new top of the stack after each step.
+
+    This is ``x86_stack_probe`` repeated ``count`` times, for frames too large
+    to unroll the probes. The counter is decremented in place, so the result
+    is always zero.
+    "#,
+            &formats.binary_imm,
+        )
+        .operands_in(vec![count, Offset])
+        .operands_out(vec![zero])
+        .other_side_effects(true)
+        .can_store(true),
+    );
+
     let y = &Operand::new("y", iWord);
     let rflags = &Operand::new("rflags", iflags);
 
@@ -301,6 +346,19 @@ pub(crate) fn define(
         .operands_out(vec![a]),
     );
 
+    ig.push(
+        Inst::new(
+            "x86_vpbroadcast",
+            r#"
+    Broadcast -- copies the lowest lane of an extended register to all the lanes of the result
+    (AVX2).
+    "#,
+            &formats.unary,
+        )
+        .operands_in(vec![b])
+        .operands_out(vec![a]),
+    );
+
     let Idx = &Operand::new("Idx", uimm8).with_doc("Lane index");
     let x = &Operand::new("x", TxN);
     let a = &Operand::new("a", &TxN.lane_of());
@@ -542,5 +600,39 @@ pub(crate) fn define(
         .operands_out(vec![a]),
     );
 
+    let GV = &Operand::new("GV", &entities.global_value);
+    let addr = &Operand::new("addr", iWord);
+
+    ig.push(
+        Inst::new(
+            "x86_elf_tls_gd_addr",
+            r#"
+        Compute the address of the thread local symbol GV using the ELF general dynamic model.
+
+        This is a call to `__tls_get_addr`, so all caller-saved registers are clobbered. The
+        result is returned in `rax`.
+        "#,
+            &formats.unary_global_value,
+        )
+        .operands_in(vec![GV])
+        .operands_out(vec![addr])
+        .other_side_effects(true),
+    );
+
+    ig.push(
+        Inst::new(
+            "x86_elf_tls_ie_addr",
+            r#"
+        Compute the address of the thread local symbol GV using the ELF initial exec model.
+
+        The offset of GV from the thread pointer is loaded from the GOT and added to the thread
+        pointer read through `%fs`.
+        "#,
+            &formats.unary_global_value,
+        )
+        .operands_in(vec![GV])
+        .operands_out(vec![addr]),
+    );
+
     ig.build()
 }
@@ -2,7 +2,7 @@ use crate::cdsl::ast::{constant, var, ExprBuilder, Literal};
 use crate::cdsl::instructions::{vector, Bindable, InstructionGroup};
 use crate::cdsl::types::{LaneType, ValueType};
 use crate::cdsl::xform::TransformGroupBuilder;
-use crate::shared::types::Float::F64;
+use crate::shared::types::Float::{F32, F64};
 use crate::shared::types::Int::{I16, I32, I64, I8};
 use crate::shared::Definitions as SharedDefinitions;
 
@@ -31,7 +31,10 @@ pub(crate) fn define(shared: &mut SharedDefinitions, x86_instructions: &Instruct
     let ctz = insts.by_name("ctz");
     let extractlane = insts.by_name("extractlane");
     let f64const = insts.by_name("f64const");
+    let fabs = insts.by_name("fabs");
+    let fadd = insts.by_name("fadd");
     let fcmp = insts.by_name("fcmp");
+    let fcvt_from_sint = insts.by_name("fcvt_from_sint");
     let fcvt_from_uint = insts.by_name("fcvt_from_uint");
     let fcvt_to_sint = insts.by_name("fcvt_to_sint");
     let fcvt_to_uint = insts.by_name("fcvt_to_uint");
@@ -39,12 +42,16 @@ pub(crate) fn define(shared: &mut SharedDefinitions, x86_instructions: &Instruct
     let fcvt_to_uint_sat = insts.by_name("fcvt_to_uint_sat");
     let fmax = insts.by_name("fmax");
     let fmin = insts.by_name("fmin");
+    let fmul = insts.by_name("fmul");
+    let fneg = insts.by_name("fneg");
+    let fsub = insts.by_name("fsub");
     let iadd = insts.by_name("iadd");
     let icmp = insts.by_name("icmp");
     let iconst = insts.by_name("iconst");
     let imul = insts.by_name("imul");
     let ineg = insts.by_name("ineg");
     let insertlane = insts.by_name("insertlane");
+    let ireduce = insts.by_name("ireduce");
     let ishl = insts.by_name("ishl");
     let isub = insts.by_name("isub");
     let popcnt = insts.by_name("popcnt");
@@ -57,7 +64,10 @@ pub(crate) fn define(shared: &mut SharedDefinitions, x86_instructions: &Instruct
     let shuffle = insts.by_name("shuffle");
     let srem = insts.by_name("srem");
     let sshr = insts.by_name("sshr");
+    let swizzle = insts.by_name("swizzle");
+    let tls_value = insts.by_name("tls_value");
     let trueif = insts.by_name("trueif");
+    let uadd_sat = insts.by_name("uadd_sat");
     let udiv = insts.by_name("udiv");
     let umulhi = insts.by_name("umulhi");
     let ushr_imm = insts.by_name("ushr_imm");
@@ -69,6 +79,9 @@ pub(crate) fn define(shared: &mut SharedDefinitions, x86_instructions: &Instruct
 
     let x86_bsf = x86_instructions.by_name("x86_bsf");
     let x86_bsr = x86_instructions.by_name("x86_bsr");
+    let x86_cvtt2si = x86_instructions.by_name("x86_cvtt2si");
+    let x86_fmax = x86_instructions.by_name("x86_fmax");
+    let x86_fmin = x86_instructions.by_name("x86_fmin");
     let x86_pmaxu = x86_instructions.by_name("x86_pmaxu");
     let x86_pmins = x86_instructions.by_name("x86_pmins");
     let x86_pminu = x86_instructions.by_name("x86_pminu");
@@ -79,6 +92,7 @@ pub(crate) fn define(shared: &mut SharedDefinitions, x86_instructions: &Instruct
     let x86_psrl = x86_instructions.by_name("x86_psrl");
     let x86_ptest = x86_instructions.by_name("x86_ptest");
     let x86_umulx = x86_instructions.by_name("x86_umulx");
+    let x86_vpbroadcast = x86_instructions.by_name("x86_vpbroadcast");
     let x86_smulx = x86_instructions.by_name("x86_smulx");
 
     let imm = &shared.imm;
@@ -173,6 +187,9 @@ pub(crate) fn define(shared: &mut SharedDefinitions, x86_instructions: &Instruct
     group.custom_legalize(fcvt_to_sint_sat, "expand_fcvt_to_sint_sat");
     group.custom_legalize(fcvt_to_uint_sat, "expand_fcvt_to_uint_sat");
 
+    // The thread local storage access sequence depends on the `tls_model` setting.
+    group.custom_legalize(tls_value, "expand_tls_value");
+
     // Count leading and trailing zeroes, for baseline x86_64
     let c_minus_one = var("c_minus_one");
     let c_thirty_one = var("c_thirty_one");
@@ -337,8 +354,9 @@ pub(crate) fn define(shared: &mut SharedDefinitions, x86_instructions: &Instruct
     let c = var("c");
     let d = var("d");
 
-    // SIMD vector size: eventually multiple vector sizes may be supported but for now only SSE-sized vectors are available
+    // SIMD vector sizes: SSE-sized vectors, and the wider vectors available with AVX.
     let sse_vector_size: u64 = 128;
+    let avx_vector_size: u64 = 256;
     let allowed_simd_type = |t: &LaneType| t.lane_bits() >= 8 && t.lane_bits() < 128;
 
     // SIMD splat: 8-bits
@@ -402,6 +420,18 @@ pub(crate) fn define(shared: &mut SharedDefinitions, x86_instructions: &Instruct
         );
     }
 
+    // SIMD splat: 256-bit vectors, broadcasting the lowest lane with AVX2
+    for ty in ValueType::all_lane_types().filter(allowed_simd_type) {
+        let splat_any = splat.bind(vector(ty, avx_vector_size));
+        narrow.legalize(
+            def!(y = splat_any(x)),
+            vec![
+                def!(a = scalar_to_vector(x)), // move into the lowest lane of an XMM register
+                def!(y = x86_vpbroadcast(a)),  // copy it to all the lanes of the YMM register
+            ],
+        );
+    }
+
     // SIMD bnot
     let ones = constant(vec![0xff; 16]);
     for ty in ValueType::all_lane_types().filter(allowed_simd_type) {
@@ -432,8 +462,8 @@ pub(crate) fn define(shared: &mut SharedDefinitions, x86_instructions: &Instruct
         );
     }
 
-    // SIMD shift left (arithmetic)
-    for ty in &[I16, I32, I64] {
+    // SIMD shift right (arithmetic); there is no PSRAQ, see below for I64x2.
+    for ty in &[I16, I32] {
         let sshr = sshr.bind(vector(*ty, sse_vector_size));
         let bitcast = bitcast.bind(vector(I64, sse_vector_size));
         narrow.legalize(
@@ -547,6 +577,326 @@ pub(crate) fn define(shared: &mut SharedDefinitions, x86_instructions: &Instruct
         narrow.legalize(def!(c = icmp_(ule, a, b)), vec![def!(c = icmp(uge, b, a))]);
     }
 
+    // Additional temporaries for the longer SIMD expansions below.
+    let e = var("e");
+    let f = var("f");
+    let g = var("g");
+    let h = var("h");
+    let i = var("i");
+    let j = var("j");
+    let k = var("k");
+    let z = var("z");
+    let iconst_i32 = &iconst.bind(I32);
+    let ireduce_i8 = &ireduce.bind(I8);
+    let splat_i8x16 = &splat.bind(vector(I8, sse_vector_size));
+    let raw_bitcast_i8x16 = &raw_bitcast.bind(vector(I8, sse_vector_size));
+    let raw_bitcast_i16x8 = &raw_bitcast.bind(vector(I16, sse_vector_size));
+    let raw_bitcast_i32x4 = &raw_bitcast.bind(vector(I32, sse_vector_size));
+    let bitcast_i64x2 = &bitcast.bind(vector(I64, sse_vector_size));
+
+    // SIMD shifts of I8x16: there are no byte shifts on x86, so shift 16-bit lanes and mask off the
+    // bits that crossed from one byte to its neighbour.
+    let imm64_0xff = Literal::constant(&imm.imm64, 0xff);
+    let ishl_i8x16 = ishl.bind(vector(I8, sse_vector_size));
+    narrow.legalize(
+        def!(a = ishl_i8x16(x, y)),
+        vec![
+            def!(b = raw_bitcast_i16x8(x)),
+            def!(c = bitcast_i64x2(y)),
+            def!(d = x86_psll(b, c)),
+            def!(e = raw_bitcast_i8x16(d)),
+            def!(f = iconst_i32(imm64_0xff)),
+            def!(g = ishl(f, y)),
+            def!(h = ireduce_i8(g)),
+            def!(i = splat_i8x16(h)),
+            def!(a = band(e, i)),
+        ],
+    );
+    let ushr_i8x16 = ushr.bind(vector(I8, sse_vector_size));
+    narrow.legalize(
+        def!(a = ushr_i8x16(x, y)),
+        vec![
+            def!(b = raw_bitcast_i16x8(x)),
+            def!(c = bitcast_i64x2(y)),
+            def!(d = x86_psrl(b, c)),
+            def!(e = raw_bitcast_i8x16(d)),
+            def!(f = iconst_i32(imm64_0xff)),
+            def!(g = ushr(f, y)),
+            def!(h = ireduce_i8(g)),
+            def!(i = splat_i8x16(h)),
+            def!(a = band(e, i)),
+        ],
+    );
+
+    // SIMD arithmetic shifts without a native instruction: flipping the sign bit biases each lane
+    // to an unsigned value, which can be shifted logically and then unbiased by subtracting the
+    // shifted sign bit.
+    let sshr_i8x16 = sshr.bind(vector(I8, sse_vector_size));
+    let sign_bits_i8x16 = constant(vec![0x80; 16]);
+    let imm64_0x80 = Literal::constant(&imm.imm64, 0x80);
+    narrow.legalize(
+        def!(a = sshr_i8x16(x, y)),
+        vec![
+            def!(b = vconst(sign_bits_i8x16)),
+            def!(c = bxor(x, b)),
+            def!(d = ushr(c, y)),
+            def!(e = iconst_i32(imm64_0x80)),
+            def!(f = ushr(e, y)),
+            def!(g = ireduce_i8(f)),
+            def!(h = splat_i8x16(g)),
+            def!(a = isub(d, h)),
+        ],
+    );
+    let sshr_i64x2 = sshr.bind(vector(I64, sse_vector_size));
+    let sign_bits_i64x2 = constant(vec![0, 0, 0, 0, 0, 0, 0, 0x80, 0, 0, 0, 0, 0, 0, 0, 0x80]);
+    let imm64_sign_bit = Literal::constant(&imm.imm64, i64::min_value());
+    let iconst_i64 = iconst.bind(I64);
+    let splat_i64x2 = splat.bind(vector(I64, sse_vector_size));
+    narrow.legalize(
+        def!(a = sshr_i64x2(x, y)),
+        vec![
+            def!(b = vconst(sign_bits_i64x2)),
+            def!(c = bxor(x, b)),
+            def!(d = ushr(c, y)),
+            def!(e = iconst_i64(imm64_sign_bit)),
+            def!(f = ushr(e, y)),
+            def!(g = splat_i64x2(f)),
+            def!(a = isub(d, g)),
+        ],
+    );
+
+    // SIMD integer multiplication of I8x16: multiply the even and odd bytes separately as 16-bit
+    // lanes, keeping only the low byte of each product.
+    let imul_i8x16 = imul.bind(vector(I8, sse_vector_size));
+    let low_bytes = constant([0xff, 0x00].repeat(8));
+    let high_bytes = constant([0x00, 0xff].repeat(8));
+    let imm64_8 = Literal::constant(&imm.imm64, 8);
+    narrow.legalize(
+        def!(a = imul_i8x16(x, y)),
+        vec![
+            def!(b = raw_bitcast_i16x8(x)),
+            def!(c = raw_bitcast_i16x8(y)),
+            def!(d = imul(b, c)),
+            def!(e = vconst(low_bytes)),
+            def!(f = band(d, e)),
+            def!(g = iconst_i32(imm64_8)),
+            def!(h = ushr(b, g)),
+            def!(i = vconst(high_bytes)),
+            def!(j = band(c, i)),
+            def!(k = imul(h, j)),
+            def!(z = bor(f, k)),
+            def!(a = raw_bitcast_i8x16(z)),
+        ],
+    );
+
+    // SIMD swizzle: PSHUFB zeroes the lanes whose index has the high bit set, so saturate every
+    // out-of-range index to at least 0x80.
+    let index_bias = constant(vec![0x70; 16]);
+    let swizzle = swizzle.bind(vector(I8, sse_vector_size));
+    narrow.legalize(
+        def!(a = swizzle(x, y)),
+        vec![
+            def!(b = vconst(index_bias)),
+            def!(c = uadd_sat(y, b)),
+            def!(a = x86_pshufb(x, c)),
+        ],
+    );
+
+    // SIMD fabs and fneg: clear or flip the sign bit of each lane.
+    for (ty, lane_bytes) in &[(F32, 4), (F64, 8)] {
+        let mut sign_bit = vec![0u8; *lane_bytes];
+        sign_bit[lane_bytes - 1] = 0x80;
+        let mut all_but_sign_bit = vec![0xffu8; *lane_bytes];
+        all_but_sign_bit[lane_bytes - 1] = 0x7f;
+
+        let fabs = fabs.bind(vector(*ty, sse_vector_size));
+        narrow.legalize(
+            def!(a = fabs(x)),
+            vec![
+                def!(b = vconst(constant(all_but_sign_bit.repeat(16 / lane_bytes)))),
+                def!(a = band(x, b)),
+            ],
+        );
+        let fneg = fneg.bind(vector(*ty, sse_vector_size));
+        narrow.legalize(
+            def!(a = fneg(x)),
+            vec![
+                def!(b = vconst(constant(sign_bit.repeat(16 / lane_bytes)))),
+                def!(a = bxor(x, b)),
+            ],
+        );
+    }
+
+    // SIMD fmin and fmax: the x86 instructions return their second operand when the operands are
+    // equal or either is NaN, so combine both operand orders. OR-ing the two minimums yields -0.0
+    // for min(0.0, -0.0) and propagates NaNs; AND-ing the two maximums yields 0.0 for
+    // max(0.0, -0.0) but can lose a NaN, so unordered lanes are set to all ones afterwards.
+    let uno = Literal::enumerator_for(&imm.floatcc, "uno");
+    for ty in &[F32, F64] {
+        let fmin = fmin.bind(vector(*ty, sse_vector_size));
+        narrow.legalize(
+            def!(a = fmin(x, y)),
+            vec![
+                def!(b = x86_fmin(x, y)),
+                def!(c = x86_fmin(y, x)),
+                def!(a = bor(b, c)),
+            ],
+        );
+        let fmax = fmax.bind(vector(*ty, sse_vector_size));
+        let raw_bitcast_to_float = raw_bitcast.bind(vector(*ty, sse_vector_size));
+        narrow.legalize(
+            def!(a = fmax(x, y)),
+            vec![
+                def!(b = x86_fmax(x, y)),
+                def!(c = x86_fmax(y, x)),
+                def!(d = band(b, c)),
+                def!(e = fcmp(uno, x, y)),
+                def!(f = raw_bitcast_to_float(e)),
+                def!(a = bor(d, f)),
+            ],
+        );
+    }
+
+    // SIMD fcmp: CMPPS/CMPPD only encode some of the condition codes, the others are obtained by
+    // swapping the operands or combining two comparisons.
+    let floatcc = |name| Literal::enumerator_for(&imm.floatcc, name);
+    for ty in &[F32, F64] {
+        for (cc, swapped_cc) in &[("gt", "lt"), ("ge", "le"), ("ult", "ugt"), ("ule", "uge")] {
+            let fcmp_ = fcmp.bind(vector(*ty, sse_vector_size));
+            narrow.legalize(
+                def!(c = fcmp_(floatcc(*cc), a, b)),
+                vec![def!(c = fcmp(floatcc(*swapped_cc), b, a))],
+            );
+        }
+        let fcmp_ = fcmp.bind(vector(*ty, sse_vector_size));
+        narrow.legalize(
+            def!(c = fcmp_(floatcc("one"), a, b)),
+            vec![
+                def!(x = fcmp(floatcc("ord"), a, b)),
+                def!(y = fcmp(floatcc("ne"), a, b)),
+                def!(c = band(x, y)),
+            ],
+        );
+        let fcmp_ = fcmp.bind(vector(*ty, sse_vector_size));
+        narrow.legalize(
+            def!(c = fcmp_(floatcc("ueq"), a, b)),
+            vec![
+                def!(x = fcmp(floatcc("uno"), a, b)),
+                def!(y = fcmp(floatcc("eq"), a, b)),
+                def!(c = bor(x, y)),
+            ],
+        );
+    }
+
+    // SIMD conversion from unsigned I32x4 to F32x4: convert the (exactly representable) high and
+    // low halves of each lane separately, then combine them with a single rounding.
+    let fcvt_from_uint_f32x4 = fcvt_from_uint
+        .bind(vector(F32, sse_vector_size))
+        .bind(vector(I32, sse_vector_size));
+    let fcvt_from_sint_f32x4 = &fcvt_from_sint
+        .bind(vector(F32, sse_vector_size))
+        .bind(vector(I32, sse_vector_size));
+    let low_halves = constant([0xff, 0xff, 0x00, 0x00].repeat(4));
+    let two_pow_16 = constant(65536f32.to_bits().to_le_bytes().repeat(4));
+    let imm64_16 = Literal::constant(&imm.imm64, 16);
+    narrow.legalize(
+        def!(a = fcvt_from_uint_f32x4(x)),
+        vec![
+            def!(b = vconst(low_halves)),
+            def!(c = band(x, b)),
+            def!(d = iconst_i32(imm64_16)),
+            def!(e = ushr(x, d)),
+            def!(f = fcvt_from_sint_f32x4(c)),
+            def!(g = fcvt_from_sint_f32x4(e)),
+            def!(h = vconst(two_pow_16)),
+            def!(i = fmul(g, h)),
+            def!(a = fadd(i, f)),
+        ],
+    );
+
+    // SIMD saturating conversion from F32x4 to I32x4. CVTTPS2DQ returns 0x80000000 for NaN and
+    // out-of-range lanes: NaN lanes are zeroed beforehand, and lanes that were positive before
+    // the conversion but are negative after it are flipped to 0x7fffffff.
+    let fcvt_to_sint_sat_i32x4 = fcvt_to_sint_sat
+        .bind(vector(I32, sse_vector_size))
+        .bind(vector(F32, sse_vector_size));
+    let x86_cvtt2si_i32x4 = &x86_cvtt2si
+        .bind(vector(I32, sse_vector_size))
+        .bind(vector(F32, sse_vector_size));
+    let raw_bitcast_f32x4 = &raw_bitcast.bind(vector(F32, sse_vector_size));
+    let eq = Literal::enumerator_for(&imm.floatcc, "eq");
+    let imm64_31 = Literal::constant(&imm.imm64, 31);
+    narrow.legalize(
+        def!(a = fcvt_to_sint_sat_i32x4(x)),
+        vec![
+            def!(b = fcmp(eq, x, x)),
+            def!(c = raw_bitcast_f32x4(b)),
+            def!(d = band(x, c)),
+            def!(e = bxor(c, d)),
+            def!(f = x86_cvtt2si_i32x4(d)),
+            def!(g = raw_bitcast_i32x4(e)),
+            def!(h = band(g, f)),
+            def!(i = iconst_i32(imm64_31)),
+            def!(j = sshr(h, i)),
+            def!(a = bxor(f, j)),
+        ],
+    );
+
+    // SIMD saturating conversion from F32x4 to unsigned I32x4. After clamping negative and NaN
+    // lanes to 0, lanes below 2^31 convert directly; for larger lanes 2^31 is subtracted before
+    // the conversion and OR-ed back in afterwards, and lanes that still overflow saturate to all
+    // ones.
+    let fcvt_to_uint_sat_i32x4 = fcvt_to_uint_sat
+        .bind(vector(I32, sse_vector_size))
+        .bind(vector(F32, sse_vector_size));
+    let two_pow_31 = constant(2147483648f32.to_bits().to_le_bytes().repeat(4));
+    let le = Literal::enumerator_for(&imm.floatcc, "le");
+    narrow.legalize(
+        def!(a = fcvt_to_uint_sat_i32x4(x)),
+        vec![
+            def!(b = vconst(zeroes)),
+            def!(c = x86_fmax(x, b)),
+            def!(d = vconst(two_pow_31)),
+            def!(e = x86_cvtt2si_i32x4(c)),
+            def!(f = fsub(c, d)),
+            def!(g = x86_cvtt2si_i32x4(f)),
+            def!(h = fcmp(le, d, c)),
+            def!(i = raw_bitcast_i32x4(h)),
+            def!(j = band(g, i)),
+            def!(k = iconst_i32(imm64_31)),
+            def!(y = sshr(j, k)),
+            def!(z = bor(e, j)),
+            def!(a = bor(z, y)),
+        ],
+    );
+
+    // SIMD conversions between I64x2 and F64x2: SSE has no instructions for these, so convert
+    // each lane with the scalar instructions.
+    let (f64_, i64_): (LaneType, LaneType) = (F64.into(), I64.into());
+    for (ty_from, ty_to, inst) in &[
+        (f64_, i64_, fcvt_to_sint_sat),
+        (f64_, i64_, fcvt_to_uint_sat),
+        (i64_, f64_, fcvt_from_sint),
+        (i64_, f64_, fcvt_from_uint),
+    ] {
+        let vector_inst = inst
+            .bind(vector(*ty_to, sse_vector_size))
+            .bind(vector(*ty_from, sse_vector_size));
+        let scalar_inst = &inst.bind(*ty_to).bind(*ty_from);
+        let scalar_to_vector = scalar_to_vector.bind(vector(*ty_to, sse_vector_size));
+        narrow.legalize(
+            def!(a = vector_inst(x)),
+            vec![
+                def!(b = extractlane(x, uimm8_zero)),
+                def!(c = extractlane(x, uimm8_one)),
+                def!(d = scalar_inst(b)),
+                def!(e = scalar_inst(c)),
+                def!(f = scalar_to_vector(d)),
+                def!(a = insertlane(f, uimm8_one, e)),
+            ],
+        );
+    }
+
     narrow.custom_legalize(shuffle, "convert_shuffle");
     narrow.custom_legalize(extractlane, "convert_extractlane");
     narrow.custom_legalize(insertlane, "convert_insertlane");
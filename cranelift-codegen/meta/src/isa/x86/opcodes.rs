@@ -15,6 +15,14 @@ pub static ADD_IMM: [u8; 1] = [0x81];
 /// Add sign-extended imm8 to r/m{16,32,64}.
 pub static ADD_IMM8_SIGN_EXTEND: [u8; 1] = [0x83];
 
+/// Add packed double-precision floating-point values from xmm2/mem to xmm1 and store result in
+/// xmm1 (SSE2).
+pub static ADDPD: [u8; 3] = [0x66, 0x0f, 0x58];
+
+/// Add packed single-precision floating-point values from xmm2/mem to xmm1 and store result in
+/// xmm1 (SSE).
+pub static ADDPS: [u8; 2] = [0x0f, 0x58];
+
 /// Add the low double-precision floating-point value from xmm2/mem to xmm1
 /// and store the result in xmm1.
 pub static ADDSD: [u8; 3] = [0xf2, 0x0f, 0x58];
@@ -52,6 +60,14 @@ pub static CALL_RELATIVE: [u8; 1] = [0xe8];
 /// Move r/m{16,32,64} if overflow (OF=1).
 pub static CMOV_OVERFLOW: [u8; 2] = [0x0f, 0x40];
 
+/// Compare packed double-precision floating-point value in xmm2/m128 and xmm1 using bits 2:0 of
+/// imm8 as comparison predicate (SSE2).
+pub static CMPPD: [u8; 3] = [0x66, 0x0f, 0xc2];
+
+/// Compare packed single-precision floating-point value in xmm2/m128 and xmm1 using bits 2:0 of
+/// imm8 as comparison predicate (SSE).
+pub static CMPPS: [u8; 2] = [0x0f, 0xc2];
+
 /// Compare imm{16,32} with r/m{16,32,64} (sign-extended if 64).
 pub static CMP_IMM: [u8; 1] = [0x81];
 
@@ -61,6 +77,20 @@ pub static CMP_IMM8: [u8; 1] = [0x83];
 /// Compare r{16,32,64} with r/m of the same size.
 pub static CMP_REG: [u8; 1] = [0x39];
 
+/// Compare AL with r/m8; if equal, store r8 into r/m8, otherwise load r/m8 into AL.
+pub static CMPXCHG_BYTE: [u8; 2] = [0x0f, 0xb0];
+
+/// Compare {E,R}AX with r/m{32,64}; if equal, store the register into r/m, otherwise load r/m
+/// into {E,R}AX.
+pub static CMPXCHG: [u8; 2] = [0x0f, 0xb1];
+
+/// Compare AX with r/m16; if equal, store r16 into r/m16, otherwise load r/m16 into AX.
+pub static CMPXCHG_16: [u8; 3] = [0x66, 0x0f, 0xb1];
+
+/// Convert four packed signed doubleword integers from xmm2/mem to four packed single-precision
+/// floating-point values in xmm1 (SSE2).
+pub static CVTDQ2PS: [u8; 2] = [0x0f, 0x5b];
+
 /// Convert scalar double-precision floating-point value to scalar single-precision
 /// floating-point value.
 pub static CVTSD2SS: [u8; 3] = [0xf2, 0x0f, 0x5a];
@@ -75,6 +105,10 @@ pub static CVTSI2SS: [u8; 3] = [0xf3, 0x0f, 0x2a];
 /// float-point value.
 pub static CVTSS2SD: [u8; 3] = [0xf3, 0x0f, 0x5a];
 
+/// Convert four packed single-precision floating-point values from xmm2/mem to four packed signed
+/// doubleword values in xmm1 using truncation (SSE2).
+pub static CVTTPS2DQ: [u8; 3] = [0xf3, 0x0f, 0x5b];
+
 /// Convert with truncation scalar double-precision floating-point value to signed
 /// integer.
 pub static CVTTSD2SI: [u8; 3] = [0xf2, 0x0f, 0x2c];
@@ -85,6 +119,14 @@ pub static CVTTSS2SI: [u8; 3] = [0xf3, 0x0f, 0x2c];
 /// Unsigned divide for {16,32,64}-bit.
 pub static DIV: [u8; 1] = [0xf7];
 
+/// Divide packed double-precision floating-point values in xmm1 by packed double-precision
+/// floating-point values in xmm2/m128 (SSE2).
+pub static DIVPD: [u8; 3] = [0x66, 0x0f, 0x5e];
+
+/// Divide packed single-precision floating-point values in xmm1 by packed single-precision
+/// floating-point values in xmm2/m128 (SSE).
+pub static DIVPS: [u8; 2] = [0x0f, 0x5e];
+
 /// Divide low double-precision floating-point value in xmm1 by low double-precision
 /// floating-point value in xmm2/m64.
 pub static DIVSD: [u8; 3] = [0xf2, 0x0f, 0x5e];
@@ -134,6 +176,14 @@ pub static LEA: [u8; 1] = [0x8d];
 /// Count the number of leading zero bits.
 pub static LZCNT: [u8; 3] = [0xf3, 0x0f, 0xbd];
 
+/// Return the maximum packed double-precision floating-point values between xmm1 and
+/// xmm2/m128 (SSE2).
+pub static MAXPD: [u8; 3] = [0x66, 0x0f, 0x5f];
+
+/// Return the maximum packed single-precision floating-point values between xmm1 and
+/// xmm2/m128 (SSE).
+pub static MAXPS: [u8; 2] = [0x0f, 0x5f];
+
 /// Return the maximum scalar double-precision floating-point value between
 /// xmm2/m64 and xmm1.
 pub static MAXSD: [u8; 3] = [0xf2, 0x0f, 0x5f];
@@ -142,6 +192,17 @@ pub static MAXSD: [u8; 3] = [0xf2, 0x0f, 0x5f];
 /// xmm2/m32 and xmm1.
 pub static MAXSS: [u8; 3] = [0xf3, 0x0f, 0x5f];
 
+/// Serialize all preceding loads and stores with respect to the following ones (0F AE /6).
+pub static MFENCE: [u8; 2] = [0x0f, 0xae];
+
+/// Return the minimum packed double-precision floating-point values between xmm1 and
+/// xmm2/m128 (SSE2).
+pub static MINPD: [u8; 3] = [0x66, 0x0f, 0x5d];
+
+/// Return the minimum packed single-precision floating-point values between xmm1 and
+/// xmm2/m128 (SSE).
+pub static MINPS: [u8; 2] = [0x0f, 0x5d];
+
 /// Return the minimum scalar double-precision floating-point value between
 /// xmm2/m64 and xmm1.
 pub static MINSD: [u8; 3] = [0xf2, 0x0f, 0x5d];
@@ -216,6 +277,14 @@ pub static MOVZX_WORD: [u8; 2] = [0x0f, 0xb7];
 /// Unsigned multiply for {16,32,64}-bit.
 pub static MUL: [u8; 1] = [0xf7];
 
+/// Multiply packed double-precision floating-point values from xmm2/mem to xmm1 and store result
+/// in xmm1 (SSE2).
+pub static MULPD: [u8; 3] = [0x66, 0x0f, 0x59];
+
+/// Multiply packed single-precision floating-point values from xmm2/mem to xmm1 and store result
+/// in xmm1 (SSE).
+pub static MULPS: [u8; 2] = [0x0f, 0x59];
+
 /// Multiply the low double-precision floating-point value in xmm2/m64 by the
 /// low double-precision floating-point value in xmm1.
 pub static MULSD: [u8; 3] = [0xf2, 0x0f, 0x59];
@@ -466,6 +535,14 @@ pub static SBB: [u8; 1] = [0x19];
 /// Set byte if overflow (OF=1).
 pub static SET_BYTE_IF_OVERFLOW: [u8; 2] = [0x0f, 0x90];
 
+/// Compute the square root of the packed double-precision floating-point values and store the
+/// result in xmm1 (SSE2).
+pub static SQRTPD: [u8; 3] = [0x66, 0x0f, 0x51];
+
+/// Compute the square root of the packed single-precision floating-point values and store the
+/// result in xmm1 (SSE).
+pub static SQRTPS: [u8; 2] = [0x0f, 0x51];
+
 /// Compute square root of scalar double-precision floating-point value.
 pub static SQRTSD: [u8; 3] = [0xf2, 0x0f, 0x51];
 
@@ -475,6 +552,14 @@ pub static SQRTSS: [u8; 3] = [0xf3, 0x0f, 0x51];
 /// Subtract r{16,32,64} from r/m of same size.
 pub static SUB: [u8; 1] = [0x29];
 
+/// Subtract packed double-precision floating-point values in xmm2/mem from xmm1 and store result
+/// in xmm1 (SSE2).
+pub static SUBPD: [u8; 3] = [0x66, 0x0f, 0x5c];
+
+/// Subtract packed single-precision floating-point values in xmm2/mem from xmm1 and store result
+/// in xmm1 (SSE).
+pub static SUBPS: [u8; 2] = [0x0f, 0x5c];
+
 /// Subtract the low double-precision floating-point value in xmm2/m64 from xmm1
 /// and store the result in xmm1.
 pub static SUBSD: [u8; 3] = [0xf2, 0x0f, 0x5c];
@@ -503,6 +588,52 @@ pub static UCOMISS: [u8; 2] = [0x0f, 0x2e];
 /// Raise invalid opcode instruction.
 pub static UNDEFINED2: [u8; 2] = [0x0f, 0x0b];
 
+/// Broadcast the low double-precision floating-point value of xmm2 to all the lanes of ymm1
+/// (AVX2). The legacy prefix and escape bytes stand for the VEX.pp and VEX.mmmmm fields.
+pub static VBROADCASTSD: [u8; 4] = [0x66, 0x0f, 0x38, 0x19];
+
+/// Broadcast the low single-precision floating-point value of xmm2 to all the lanes of xmm1 or
+/// ymm1 (AVX2).
+pub static VBROADCASTSS: [u8; 4] = [0x66, 0x0f, 0x38, 0x18];
+
+/// Multiply packed single-precision floating-point values from xmm1 and xmm2, add xmm3/mem and
+/// store the result in xmm1 (FMA); with VEX.W set, the double-precision version.
+pub static VFMADD213PS: [u8; 4] = [0x66, 0x0f, 0x38, 0xa8];
+
+/// Multiply the low single-precision floating-point values from xmm1 and xmm2, add xmm3/mem and
+/// store the result in xmm1 (FMA); with VEX.W set, the double-precision version.
+pub static VFMADD213SS: [u8; 4] = [0x66, 0x0f, 0x38, 0xa9];
+
+/// Broadcast the low byte of xmm2 to all the lanes of xmm1 or ymm1 (AVX2).
+pub static VPBROADCASTB: [u8; 4] = [0x66, 0x0f, 0x38, 0x78];
+
+/// Broadcast the low doubleword of xmm2 to all the lanes of xmm1 or ymm1 (AVX2).
+pub static VPBROADCASTD: [u8; 4] = [0x66, 0x0f, 0x38, 0x58];
+
+/// Broadcast the low quadword of xmm2 to all the lanes of xmm1 or ymm1 (AVX2).
+pub static VPBROADCASTQ: [u8; 4] = [0x66, 0x0f, 0x38, 0x59];
+
+/// Broadcast the low word of xmm2 to all the lanes of xmm1 or ymm1 (AVX2).
+pub static VPBROADCASTW: [u8; 4] = [0x66, 0x0f, 0x38, 0x79];
+
+/// Exchange r8 and r/m8, then load their sum into r/m8.
+pub static XADD_BYTE: [u8; 2] = [0x0f, 0xc0];
+
+/// Exchange r{32,64} and r/m of the same size, then load their sum into r/m.
+pub static XADD: [u8; 2] = [0x0f, 0xc1];
+
+/// Exchange r16 and r/m16, then load their sum into r/m16.
+pub static XADD_16: [u8; 3] = [0x66, 0x0f, 0xc1];
+
+/// Exchange r8 with r/m8.
+pub static XCHG_BYTE: [u8; 1] = [0x86];
+
+/// Exchange r{32,64} with r/m of the same size.
+pub static XCHG: [u8; 1] = [0x87];
+
+/// Exchange r16 with r/m16.
+pub static XCHG_16: [u8; 2] = [0x66, 0x87];
+
 /// imm{16,32} XOR r/m{16,32,64}, possibly sign-extended.
 pub static XOR_IMM: [u8; 1] = [0x81];
 
@@ -3,6 +3,7 @@ use std::rc::Rc;
 use crate::cdsl::ast::Literal;
 use crate::cdsl::formats::InstructionFormat;
 use crate::cdsl::instructions::InstructionPredicate;
+use crate::cdsl::operands::OperandKind;
 use crate::cdsl::recipes::{
     EncodingRecipe, EncodingRecipeBuilder, OperandConstraint, Register, Stack,
 };
@@ -69,8 +70,9 @@ impl<'builder> RecipeGroup<'builder> {
 // opcode format is indicated by the recipe name prefix.
 //
 // The match case below does not include the REX prefix which goes after the mandatory prefix.
-// VEX/XOP and EVEX prefixes are not yet supported. Encodings using any of these prefixes are
-// represented by separate recipes.
+// VEX-prefixed encodings use the Vex128/Vex256 recipe name prefixes instead, with the pp and mm
+// bits folded into the VEX prefix and the vector length given by the name. XOP and EVEX prefixes
+// are not yet supported.
 //
 // The encoding bits are:
 //
@@ -176,8 +178,12 @@ pub(crate) struct Template<'builder> {
     // Specialized parameters.
     /// Should we include the REX prefix?
     rex: bool,
+    /// Should we use a VEX prefix instead of the legacy and REX prefixes?
+    vex: bool,
     /// Value of the W bit (0 or 1).
     w_bit: u16,
+    /// Value of the VEX.L bit (0 for 128-bit vectors, 1 for 256-bit vectors).
+    l_bit: u16,
     /// Value of the RRR bits (between 0 and 0b111).
     rrr_bits: u16,
     /// Opcode bytes.
@@ -192,7 +198,9 @@ impl<'builder> Template<'builder> {
             requires_prefix: false,
             when_prefixed: None,
             rex: false,
+            vex: false,
             w_bit: 0,
+            l_bit: 0,
             rrr_bits: 0,
             op_bytes: &opcodes::EMPTY,
         }
@@ -207,6 +215,10 @@ impl<'builder> Template<'builder> {
             ..self
         }
     }
+    fn vex(self) -> Self {
+        assert!(self.when_prefixed.is_none());
+        Self { vex: true, ..self }
+    }
     fn when_prefixed(self, template: Rc<Template<'builder>>) -> Self {
         assert!(self.when_prefixed.is_none());
         Self {
@@ -227,6 +239,12 @@ impl<'builder> Template<'builder> {
         copy.w_bit = 1;
         copy
     }
+    pub fn l(&self) -> Self {
+        assert!(self.vex, "VEX.L requires a VEX prefix.");
+        let mut copy = self.clone();
+        copy.l_bit = 1;
+        copy
+    }
     pub fn rrr(&self, value: u16) -> Self {
         assert!(value <= 0b111);
         let mut copy = self.clone();
@@ -240,6 +258,7 @@ impl<'builder> Template<'builder> {
         copy
     }
     pub fn rex(&self) -> Self {
+        assert!(!self.vex, "VEX recipes can't have a REX prefix.");
         if let Some(prefixed) = &self.when_prefixed {
             let mut ret = prefixed.rex();
             // Forward specialized parameters.
@@ -256,13 +275,18 @@ impl<'builder> Template<'builder> {
     pub fn build(mut self) -> (EncodingRecipe, u16) {
         let (name, bits) = decode_opcodes(&self.op_bytes, self.rrr_bits, self.w_bit);
 
-        let (name, rex_prefix_size) = if self.rex {
-            ("Rex".to_string() + name, 1)
+        let (name, size_addendum) = if self.vex {
+            assert_ne!(bits & 0x0c00, 0, "VEX encodings need an escaped opcode.");
+            // The VEX prefix always uses its three-byte form, and replaces the mandatory prefix
+            // and the opcode escape bytes.
+            let name = if self.l_bit == 1 { "Vex256" } else { "Vex128" };
+            (name.to_string(), 4)
+        } else if self.rex {
+            ("Rex".to_string() + name, self.op_bytes.len() as u64 + 1)
         } else {
-            (name.into(), 0)
+            (name.into(), self.op_bytes.len() as u64)
         };
 
-        let size_addendum = self.op_bytes.len() as u64 + rex_prefix_size;
         self.recipe.base_size += size_addendum;
 
         // Branch ranges are relative to the end of the instruction.
@@ -273,7 +297,8 @@ impl<'builder> Template<'builder> {
         self.recipe.emit = replace_put_op(self.recipe.emit, &name);
         self.recipe.name = name + &self.recipe.name;
 
-        if !self.rex {
+        // VEX prefixes can address all the registers.
+        if !self.rex && !self.vex {
             let operands_in = self.recipe.operands_in.unwrap_or_default();
             self.recipe.operands_in = Some(replace_nonrex_constraints(self.regs, operands_in));
             let operands_out = self.recipe.operands_out.unwrap_or_default();
@@ -301,6 +326,72 @@ fn supported_floatccs_predicate(
         })
 }
 
+/// Returns a predicate checking that the "ordering" field of the instruction is one of the named
+/// memory orderings.
+fn atomic_ordering_predicate(
+    atomic_ordering: &OperandKind,
+    names: &[&'static str],
+    format: &InstructionFormat,
+) -> InstructionPredicate {
+    names
+        .iter()
+        .fold(InstructionPredicate::new(), |pred, name| {
+            pred.or(InstructionPredicate::new_is_field_equal(
+                format,
+                "ordering",
+                Literal::enumerator_for(atomic_ordering, name).to_rust_code(),
+            ))
+        })
+}
+
+/// Returns the emit code of an atomic memory access to the address in `addr`, with `reg` in the
+/// reg field of the ModR/M byte. The access is optionally preceded by a `lock` prefix (F0) and
+/// followed by an `mfence` (0F AE F0).
+fn atomic_access_emit(addr: &str, reg: &str, lock: bool, mfence: bool) -> String {
+    let mut lines = vec![
+        "if !flags.notrap() {",
+        "    sink.trap(TrapCode::HeapOutOfBounds, func.srclocs[inst]);",
+        "}",
+    ];
+    if lock {
+        lines.push("sink.put1(0xf0);");
+    }
+    lines.extend(&[
+        "{{PUT_OP}}(bits, rex2(ADDR, REG), sink);",
+        "if needs_sib_byte(ADDR) {",
+        "    modrm_sib(REG, sink);",
+        "    sib_noindex(ADDR, sink);",
+        "} else if needs_offset(ADDR) {",
+        "    modrm_disp8(ADDR, REG, sink);",
+        "    sink.put1(0);",
+        "} else {",
+        "    modrm_rm(ADDR, REG, sink);",
+        "}",
+    ]);
+    if mfence {
+        lines.extend(&["sink.put1(0x0f);", "sink.put1(0xae);", "sink.put1(0xf0);"]);
+    }
+    lines.join("\n").replace("ADDR", addr).replace("REG", reg)
+}
+
+/// Adds the atomic access template `recipe` with the `operands_in` constraints, and a variant of
+/// it with an `_abcd` suffix and the `abcd_operands_in` constraints, to use when a byte operand
+/// can't have a REX prefix.
+fn add_atomic_recipes<'builder>(
+    recipes: &mut RecipeGroup<'builder>,
+    recipe: EncodingRecipeBuilder,
+    operands_in: Vec<impl Into<OperandConstraint>>,
+    abcd_operands_in: Vec<impl Into<OperandConstraint>>,
+) {
+    let mut abcd_recipe = recipe.clone();
+    abcd_recipe.name = format!("{}_abcd", recipe.name);
+    let template = recipes.add_template_recipe(recipe.operands_in(operands_in));
+    let regs = recipes.regs;
+    recipes.add_template(
+        Template::new(abcd_recipe.operands_in(abcd_operands_in), regs).when_prefixed(template),
+    );
+}
+
 /// Return an instruction predicate that checks if `iform.imm` is a valid `scale` for a SIB byte.
 fn valid_scale(format: &InstructionFormat) -> InstructionPredicate {
     ["1", "2", "4", "8"]
@@ -327,6 +418,13 @@ pub(crate) fn define<'shared>(
         .map(|name| Literal::enumerator_for(floatcc, name))
         .collect();
 
+    // The condition codes encodable in the immediate of the packed CMPPS/CMPPD comparisons.
+    let supported_packed_floatccs: Vec<Literal> =
+        ["eq", "lt", "le", "uno", "ne", "uge", "ugt", "ord"]
+            .iter()
+            .map(|name| Literal::enumerator_for(floatcc, name))
+            .collect();
+
     // Register classes shorthands.
     let abcd = regs.class_by_name("ABCD");
     let gpr = regs.class_by_name("GPR");
@@ -1109,6 +1207,65 @@ pub(crate) fn define<'shared>(
                     "#,
                 ),
         );
+
+        // Like adjustsp_id, followed by a `mov dword [rsp], 0` probe.
+        recipes.add_template_recipe(
+            EncodingRecipeBuilder::new("stackprobe", &formats.unary_imm, 5 + 7)
+                .inst_predicate(InstructionPredicate::new_is_signed_int(
+                    &*formats.unary_imm,
+                    "imm",
+                    32,
+                    0,
+                ))
+                .emit(
+                    r#"
+                        {{PUT_OP}}(bits, rex1(RU::rsp.into()), sink);
+                        modrm_r_bits(RU::rsp.into(), bits, sink);
+                        let imm: i64 = imm.into();
+                        sink.put4(imm as u32);
+                        sink.put1(0xc7);
+                        modrm_sib(0, sink);
+                        sib_noindex(RU::rsp.into(), sink);
+                        sink.put4(0);
+                    "#,
+                ),
+        );
+
+        // The stackprobe sequence in a loop counted down in %rax:
+        //
+        //     sub rsp, imm
+        //     mov dword [rsp], 0
+        //     dec eax
+        //     jnz <start>
+        recipes.add_template_recipe(
+            EncodingRecipeBuilder::new("stackprobe_loop", &formats.binary_imm, 5 + 7 + 2 + 2)
+                .operands_in(vec![reg_rax])
+                .operands_out(vec![reg_rax])
+                .inst_predicate(InstructionPredicate::new_is_signed_int(
+                    &*formats.binary_imm,
+                    "imm",
+                    32,
+                    0,
+                ))
+                .emit(
+                    r#"
+                        let start = sink.offset();
+                        {{PUT_OP}}(bits, rex1(RU::rsp.into()), sink);
+                        modrm_r_bits(RU::rsp.into(), bits, sink);
+                        let imm: i64 = imm.into();
+                        sink.put4(imm as u32);
+                        sink.put1(0xc7);
+                        modrm_sib(0, sink);
+                        sib_noindex(RU::rsp.into(), sink);
+                        sink.put4(0);
+                        sink.put1(0xff);
+                        modrm_r_bits(RU::rax.into(), 0x1000, sink);
+                        sink.put1(0x75);
+                        let disp = start.wrapping_sub(sink.offset() + 1);
+                        sink.put1(disp as u8);
+                    "#,
+                ),
+        );
     }
 
     // XX+rd id with Abs4 function relocation.
@@ -1277,6 +1434,63 @@ pub(crate) fn define<'shared>(
             ),
     );
 
+    // ELF general dynamic TLS access: the address is returned by a call to `__tls_get_addr`.
+    recipes.add_recipe(
+        EncodingRecipeBuilder::new("elf_tls_gd_addr", &formats.unary_global_value, 16)
+            .operands_out(vec![reg_rax])
+            .clobbers_flags(true)
+            .emit(
+                r#"
+                    // data16 lea gv@tlsgd(%rip), %rdi
+                    sink.put1(0x66);
+                    sink.put1(0b01001000); // rex.w
+                    sink.put1(0x8d);
+                    sink.put1(0x3d);
+                    sink.reloc_external(Reloc::ElfX86_64TlsGd,
+                                        &func.global_values[global_value].symbol_name(),
+                                        -4);
+                    sink.put4(0);
+
+                    // data16 data16 call __tls_get_addr@plt
+                    sink.put1(0x66);
+                    sink.put1(0x66);
+                    sink.put1(0b01001000); // rex.w
+                    sink.put1(0xe8);
+                    sink.reloc_external(Reloc::X86CallPLTRel4,
+                                        &ExternalName::LibCall(LibCall::ElfTlsGetAddr),
+                                        -4);
+                    sink.put4(0);
+                "#,
+            ),
+    );
+
+    // ELF initial exec TLS access: add the GOT entry's offset to the thread pointer.
+    recipes.add_recipe(
+        EncodingRecipeBuilder::new("elf_tls_ie_addr", &formats.unary_global_value, 16)
+            .operands_out(vec![gpr])
+            .clobbers_flags(true)
+            .emit(
+                r#"
+                    // mov %fs:0, %out_reg0
+                    sink.put1(0x64);
+                    sink.put1(rex2(0, out_reg0) | 0b1000); // rex.w
+                    sink.put1(0x8b);
+                    modrm_sib(out_reg0, sink);
+                    sink.put1(0x25);
+                    sink.put4(0);
+
+                    // add gv@gottpoff(%rip), %out_reg0
+                    sink.put1(rex2(0, out_reg0) | 0b1000); // rex.w
+                    sink.put1(0x03);
+                    modrm_riprel(out_reg0, sink);
+                    sink.reloc_external(Reloc::ElfX86_64GotTpOff,
+                                        &func.global_values[global_value].symbol_name(),
+                                        -4);
+                    sink.put4(0);
+                "#,
+            ),
+    );
+
     // Stack addresses.
     //
     // TODO Alternative forms for 8-bit immediates, when applicable.
@@ -2179,6 +2393,111 @@ pub(crate) fn define<'shared>(
         );
     }
 
+    // Atomic memory access recipes.
+
+    {
+        // Loads and stores that are naturally aligned are atomic on x86, and the hardware memory
+        // model already gives plain loads acquire semantics and plain stores release semantics.
+        // Only sequentially consistent stores need an additional `mfence`.
+        let atomic_ordering = &shared_defs.imm.atomic_ordering;
+        let is_seq_cst_store =
+            atomic_ordering_predicate(atomic_ordering, &["seq_cst"], &*formats.atomic_store);
+        let is_weak_store = atomic_ordering_predicate(
+            atomic_ordering,
+            &["relaxed", "release"],
+            &*formats.atomic_store,
+        );
+
+        // XX /r atomic load.
+        recipes.add_template_recipe(
+            EncodingRecipeBuilder::new("atomic_ld", &formats.atomic_load, 1)
+                .operands_in(vec![gpr])
+                .operands_out(vec![gpr])
+                .clobbers_flags(false)
+                .compute_size("size_plus_maybe_sib_or_offset_for_in_reg_0")
+                .emit(atomic_access_emit("in_reg0", "out_reg0", false, false)),
+        );
+
+        // XX /r atomic stores, without a trailing fence or followed by `mfence` (0F AE F0).
+        // The `_abcd` variants only allow ABCD for the stored value. They are for byte stores
+        // with no REX.
+        for (name, size, predicate, mfence) in vec![
+            ("atomic_st", 1, is_weak_store, false),
+            ("atomic_st_mfence", 1 + 3, is_seq_cst_store, true),
+        ] {
+            let recipe = EncodingRecipeBuilder::new(name, &formats.atomic_store, size)
+                .inst_predicate(predicate)
+                .clobbers_flags(false)
+                .compute_size("size_plus_maybe_sib_or_offset_for_in_reg_1")
+                .emit(atomic_access_emit("in_reg1", "in_reg0", false, mfence));
+            add_atomic_recipes(&mut recipes, recipe, vec![gpr, gpr], vec![abcd, gpr]);
+        }
+
+        // LOCK XX /r read-modify-write of memory with the old value returned in the operand
+        // register. Used for `xadd` and `xchg`.
+        // The `_abcd` variant only allows ABCD for the operand. This is for byte operations with
+        // no REX.
+        add_atomic_recipes(
+            &mut recipes,
+            EncodingRecipeBuilder::new("atomic_rmw", &formats.atomic_rmw, 1 + 1)
+                .operands_out(vec![1])
+                .compute_size("size_plus_maybe_sib_or_offset_for_in_reg_0")
+                .emit(atomic_access_emit("in_reg0", "in_reg1", true, false)),
+            vec![gpr, gpr],
+            vec![gpr, abcd],
+        );
+
+        // LOCK XX /r compare-and-swap: expected value in %rax, old value returned in %rax.
+        // The `_abcd` variant only allows ABCD for the replacement value. This is for byte
+        // operations with no REX.
+        add_atomic_recipes(
+            &mut recipes,
+            EncodingRecipeBuilder::new("atomic_cas", &formats.atomic_cas, 1 + 1)
+                .operands_out(vec![reg_rax])
+                .compute_size("size_plus_maybe_sib_or_offset_for_in_reg_0")
+                .emit(atomic_access_emit("in_reg0", "in_reg2", true, false)),
+            vec![
+                OperandConstraint::RegClass(gpr),
+                OperandConstraint::FixedReg(reg_rax),
+                OperandConstraint::RegClass(gpr),
+            ],
+            vec![
+                OperandConstraint::RegClass(gpr),
+                OperandConstraint::FixedReg(reg_rax),
+                OperandConstraint::RegClass(abcd),
+            ],
+        );
+
+        // XX /n with no operands, for `mfence`.
+        recipes.add_template_recipe(
+            EncodingRecipeBuilder::new("mfence", &formats.fence, 1)
+                .inst_predicate(atomic_ordering_predicate(
+                    atomic_ordering,
+                    &["seq_cst"],
+                    &*formats.fence,
+                ))
+                .clobbers_flags(false)
+                .emit(
+                    r#"
+                        {{PUT_OP}}(bits, BASE_REX, sink);
+                        modrm_r_bits(0, bits, sink);
+                    "#,
+                ),
+        );
+
+        // Fences weaker than `seq_cst` only constrain the compiler on x86.
+        recipes.add_recipe(
+            EncodingRecipeBuilder::new("fence_nop", &formats.fence, 0)
+                .inst_predicate(atomic_ordering_predicate(
+                    atomic_ordering,
+                    &["acquire", "release", "acq_rel"],
+                    &*formats.fence,
+                ))
+                .clobbers_flags(false)
+                .emit(""),
+        );
+    }
+
     // Unary fill with SIB and 32-bit displacement.
     recipes.add_template_recipe(
         EncodingRecipeBuilder::new("fillSib32", &formats.unary, 6)
@@ -2289,6 +2608,83 @@ pub(crate) fn define<'shared>(
             ),
     );
 
+    // Invokes are encoded like calls. The landing pad is only reached through the unwinder, so it
+    // doesn't appear in the instruction.
+    recipes.add_template_recipe(
+        EncodingRecipeBuilder::new("invoke_id", &formats.invoke, 4).emit(
+            r#"
+            sink.trap(TrapCode::StackOverflow, func.srclocs[inst]);
+            {{PUT_OP}}(bits, BASE_REX, sink);
+            sink.reloc_external(Reloc::X86CallPCRel4,
+                                &func.dfg.ext_funcs[func_ref].name,
+                                -4);
+            sink.put4(0);
+        "#,
+        ),
+    );
+
+    recipes.add_template_recipe(
+        EncodingRecipeBuilder::new("invoke_plt_id", &formats.invoke, 4).emit(
+            r#"
+            sink.trap(TrapCode::StackOverflow, func.srclocs[inst]);
+            {{PUT_OP}}(bits, BASE_REX, sink);
+            sink.reloc_external(Reloc::X86CallPLTRel4,
+                                &func.dfg.ext_funcs[func_ref].name,
+                                -4);
+            sink.put4(0);
+        "#,
+        ),
+    );
+
+    recipes.add_template_recipe(
+        EncodingRecipeBuilder::new("invoke_r", &formats.invoke_indirect, 1)
+            .operands_in(vec![gpr])
+            .emit(
+                r#"
+                    sink.trap(TrapCode::StackOverflow, func.srclocs[inst]);
+                    {{PUT_OP}}(bits, rex1(in_reg0), sink);
+                    modrm_r_bits(in_reg0, bits, sink);
+                "#,
+            ),
+    );
+
+    // Tail calls jump to the callee instead of pushing a return address, so unlike calls they
+    // can't overflow the stack.
+    recipes.add_template_recipe(
+        EncodingRecipeBuilder::new("tailcall_id", &formats.call, 4).emit(
+            r#"
+            {{PUT_OP}}(bits, BASE_REX, sink);
+            sink.reloc_external(Reloc::X86CallPCRel4,
+                                &func.dfg.ext_funcs[func_ref].name,
+                                -4);
+            sink.put4(0);
+        "#,
+        ),
+    );
+
+    recipes.add_template_recipe(
+        EncodingRecipeBuilder::new("tailcall_plt_id", &formats.call, 4).emit(
+            r#"
+            {{PUT_OP}}(bits, BASE_REX, sink);
+            sink.reloc_external(Reloc::X86CallPLTRel4,
+                                &func.dfg.ext_funcs[func_ref].name,
+                                -4);
+            sink.put4(0);
+        "#,
+        ),
+    );
+
+    recipes.add_template_recipe(
+        EncodingRecipeBuilder::new("tailcall_r", &formats.call_indirect, 1)
+            .operands_in(vec![gpr])
+            .emit(
+                r#"
+                    {{PUT_OP}}(bits, rex1(in_reg0), sink);
+                    modrm_r_bits(in_reg0, bits, sink);
+                "#,
+            ),
+    );
+
     recipes.add_template_recipe(
         EncodingRecipeBuilder::new("ret", &formats.multiary, 0)
             .emit("{{PUT_OP}}(bits, BASE_REX, sink);"),
@@ -3015,6 +3411,39 @@ pub(crate) fn define<'shared>(
             ),
     );
 
+    // XX /r ib with FPR ins and outs; the packed comparison writes a mask of all ones or all zeroes
+    // to each lane of the first operand.
+    recipes.add_template_recipe(
+        EncodingRecipeBuilder::new("pfcmp", &formats.float_compare, 2)
+            .operands_in(vec![fpr, fpr])
+            .operands_out(vec![0])
+            .inst_predicate(supported_floatccs_predicate(
+                &supported_packed_floatccs,
+                &*formats.float_compare,
+            ))
+            .emit(
+                r#"
+                    // Comparison instruction.
+                    {{PUT_OP}}(bits, rex2(in_reg1, in_reg0), sink);
+                    modrm_rr(in_reg1, in_reg0, sink);
+                    // Comparison predicate immediate.
+                    use crate::ir::condcodes::FloatCC::*;
+                    let imm = match cond {
+                        Equal                         => 0x00,
+                        LessThan                      => 0x01,
+                        LessThanOrEqual               => 0x02,
+                        Unordered                     => 0x03,
+                        NotEqual                      => 0x04,
+                        UnorderedOrGreaterThanOrEqual => 0x05,
+                        UnorderedOrGreaterThan        => 0x06,
+                        Ordered                       => 0x07,
+                        _ => panic!("{} not supported by pfcmp", cond),
+                    };
+                    sink.put1(imm);
+                "#,
+            ),
+    );
+
     recipes.add_template_recipe(
         EncodingRecipeBuilder::new("is_zero", &formats.unary, 2 + 2)
             .operands_in(vec![gpr])
@@ -3032,6 +3461,449 @@ pub(crate) fn define<'shared>(
             ),
     );
 
+    // VEX-encoded recipes. The `vvvv` field holds a second source register in the three-operand
+    // forms, so unlike the legacy SSE recipes above the output doesn't have to be tied to the
+    // first input. Instructions that don't use `vvvv` pass register 0, which encodes as 0b1111.
+
+    // VEX.NDS XX /r with FPR ins and outs. A form.
+    recipes.add_template(
+        Template::new(
+            EncodingRecipeBuilder::new("vfa", &formats.binary, 1)
+                .operands_in(vec![fpr, fpr])
+                .operands_out(vec![fpr])
+                .emit(
+                    r#"
+                        {{PUT_OP}}(bits, rex2(in_reg1, out_reg0), in_reg0, sink);
+                        modrm_rr(in_reg1, out_reg0, sink);
+                    "#,
+                ),
+            regs,
+        )
+        .vex(),
+    );
+
+    // VEX.NDS XX /r with FPR ins and outs. A form with input operands swapped.
+    recipes.add_template(
+        Template::new(
+            EncodingRecipeBuilder::new("vfax", &formats.binary, 1)
+                .operands_in(vec![fpr, fpr])
+                .operands_out(vec![fpr])
+                .emit(
+                    r#"
+                        {{PUT_OP}}(bits, rex2(in_reg0, out_reg0), in_reg1, sink);
+                        modrm_rr(in_reg0, out_reg0, sink);
+                    "#,
+                ),
+            regs,
+        )
+        .vex(),
+    );
+
+    // VEX XX /r, RM form, FPR -> FPR.
+    recipes.add_template(
+        Template::new(
+            EncodingRecipeBuilder::new("vfurm", &formats.unary, 1)
+                .operands_in(vec![fpr])
+                .operands_out(vec![fpr])
+                .clobbers_flags(false)
+                .emit(
+                    r#"
+                        {{PUT_OP}}(bits, rex2(in_reg0, out_reg0), 0, sink);
+                        modrm_rr(in_reg0, out_reg0, sink);
+                    "#,
+                ),
+            regs,
+        )
+        .vex(),
+    );
+
+    // VEX XX /r, RM form, GPR -> FPR.
+    recipes.add_template(
+        Template::new(
+            EncodingRecipeBuilder::new("vfrurm", &formats.unary, 1)
+                .operands_in(vec![gpr])
+                .operands_out(vec![fpr])
+                .clobbers_flags(false)
+                .emit(
+                    r#"
+                        {{PUT_OP}}(bits, rex2(in_reg0, out_reg0), 0, sink);
+                        modrm_rr(in_reg0, out_reg0, sink);
+                    "#,
+                ),
+            regs,
+        )
+        .vex(),
+    );
+
+    // VEX XX /r, for regmove instructions (FPR version, RM encoded).
+    recipes.add_template(
+        Template::new(
+            EncodingRecipeBuilder::new("vfrmov", &formats.reg_move, 1)
+                .operands_in(vec![fpr])
+                .clobbers_flags(false)
+                .emit(
+                    r#"
+                        {{PUT_OP}}(bits, rex2(src, dst), 0, sink);
+                        modrm_rr(src, dst, sink);
+                    "#,
+                ),
+            regs,
+        )
+        .vex(),
+    );
+
+    // VEX.DDS XX /r, fused multiply-add of three FPRs. The 213 forms multiply the first two
+    // operands and add the third, and the result replaces the first operand.
+    recipes.add_template(
+        Template::new(
+            EncodingRecipeBuilder::new("vfma", &formats.ternary, 1)
+                .operands_in(vec![fpr, fpr, fpr])
+                .operands_out(vec![0])
+                .emit(
+                    r#"
+                        {{PUT_OP}}(bits, rex2(in_reg2, in_reg0), in_reg1, sink);
+                        modrm_rr(in_reg2, in_reg0, sink);
+                    "#,
+                ),
+            regs,
+        )
+        .vex(),
+    );
+
+    // VEX.NDS XX /r, packed integer comparison writing a mask to each lane of the output.
+    recipes.add_template(
+        Template::new(
+            EncodingRecipeBuilder::new("vicscc_fpr", &formats.int_compare, 1)
+                .operands_in(vec![fpr, fpr])
+                .operands_out(vec![fpr])
+                .emit(
+                    r#"
+                        // Comparison instruction.
+                        {{PUT_OP}}(bits, rex2(in_reg1, out_reg0), in_reg0, sink);
+                        modrm_rr(in_reg1, out_reg0, sink);
+                    "#,
+                ),
+            regs,
+        )
+        .vex(),
+    );
+
+    // VEX.NDS XX /r ib, the three-operand form of `pfcmp`.
+    recipes.add_template(
+        Template::new(
+            EncodingRecipeBuilder::new("vpfcmp", &formats.float_compare, 2)
+                .operands_in(vec![fpr, fpr])
+                .operands_out(vec![fpr])
+                .inst_predicate(supported_floatccs_predicate(
+                    &supported_packed_floatccs,
+                    &*formats.float_compare,
+                ))
+                .emit(
+                    r#"
+                        // Comparison instruction.
+                        {{PUT_OP}}(bits, rex2(in_reg1, out_reg0), in_reg0, sink);
+                        modrm_rr(in_reg1, out_reg0, sink);
+                        // Comparison predicate immediate.
+                        use crate::ir::condcodes::FloatCC::*;
+                        let imm = match cond {
+                            Equal                         => 0x00,
+                            LessThan                      => 0x01,
+                            LessThanOrEqual               => 0x02,
+                            Unordered                     => 0x03,
+                            NotEqual                      => 0x04,
+                            UnorderedOrGreaterThanOrEqual => 0x05,
+                            UnorderedOrGreaterThan        => 0x06,
+                            Ordered                       => 0x07,
+                            _ => panic!("{} not supported by vpfcmp", cond),
+                        };
+                        sink.put1(imm);
+                    "#,
+                ),
+            regs,
+        )
+        .vex(),
+    );
+
+    {
+        // VEX loads and stores of FPRs.
+
+        let has_no_offset =
+            InstructionPredicate::new_is_field_equal(&*formats.store, "offset", "0".into());
+        let has_small_offset =
+            InstructionPredicate::new_is_signed_int(&*formats.store, "offset", 8, 0);
+
+        // VEX XX /r register-indirect store of FPR with no offset.
+        recipes.add_template(
+            Template::new(
+                EncodingRecipeBuilder::new("vfst", &formats.store, 1)
+                    .operands_in(vec![fpr, gpr])
+                    .inst_predicate(has_no_offset)
+                    .clobbers_flags(false)
+                    .compute_size("size_plus_maybe_sib_or_offset_for_in_reg_1")
+                    .emit(
+                        r#"
+                        if !flags.notrap() {
+                            sink.trap(TrapCode::HeapOutOfBounds, func.srclocs[inst]);
+                        }
+                        {{PUT_OP}}(bits, rex2(in_reg1, in_reg0), 0, sink);
+                        if needs_sib_byte(in_reg1) {
+                            modrm_sib(in_reg0, sink);
+                            sib_noindex(in_reg1, sink);
+                        } else if needs_offset(in_reg1) {
+                            modrm_disp8(in_reg1, in_reg0, sink);
+                            sink.put1(0);
+                        } else {
+                            modrm_rm(in_reg1, in_reg0, sink);
+                        }
+                    "#,
+                    ),
+                regs,
+            )
+            .vex(),
+        );
+
+        // VEX XX /r register-indirect store of FPR with 8-bit offset.
+        recipes.add_template(
+            Template::new(
+                EncodingRecipeBuilder::new("vfstDisp8", &formats.store, 2)
+                    .operands_in(vec![fpr, gpr])
+                    .inst_predicate(has_small_offset)
+                    .clobbers_flags(false)
+                    .compute_size("size_plus_maybe_sib_for_in_reg_1")
+                    .emit(
+                        r#"
+                        if !flags.notrap() {
+                            sink.trap(TrapCode::HeapOutOfBounds, func.srclocs[inst]);
+                        }
+                        {{PUT_OP}}(bits, rex2(in_reg1, in_reg0), 0, sink);
+                        if needs_sib_byte(in_reg1) {
+                            modrm_sib_disp8(in_reg0, sink);
+                            sib_noindex(in_reg1, sink);
+                        } else {
+                            modrm_disp8(in_reg1, in_reg0, sink);
+                        }
+                        let offset: i32 = offset.into();
+                        sink.put1(offset as u8);
+                    "#,
+                    ),
+                regs,
+            )
+            .vex(),
+        );
+
+        // VEX XX /r register-indirect store of FPR with 32-bit offset.
+        recipes.add_template(
+            Template::new(
+                EncodingRecipeBuilder::new("vfstDisp32", &formats.store, 5)
+                    .operands_in(vec![fpr, gpr])
+                    .clobbers_flags(false)
+                    .compute_size("size_plus_maybe_sib_for_in_reg_1")
+                    .emit(
+                        r#"
+                        if !flags.notrap() {
+                            sink.trap(TrapCode::HeapOutOfBounds, func.srclocs[inst]);
+                        }
+                        {{PUT_OP}}(bits, rex2(in_reg1, in_reg0), 0, sink);
+                        if needs_sib_byte(in_reg1) {
+                            modrm_sib_disp32(in_reg0, sink);
+                            sib_noindex(in_reg1, sink);
+                        } else {
+                            modrm_disp32(in_reg1, in_reg0, sink);
+                        }
+                        let offset: i32 = offset.into();
+                        sink.put4(offset as u32);
+                    "#,
+                    ),
+                regs,
+            )
+            .vex(),
+        );
+
+        let has_no_offset =
+            InstructionPredicate::new_is_field_equal(&*formats.load, "offset", "0".into());
+        let has_small_offset =
+            InstructionPredicate::new_is_signed_int(&*formats.load, "offset", 8, 0);
+        let has_big_offset =
+            InstructionPredicate::new_is_signed_int(&*formats.load, "offset", 32, 0);
+
+        // VEX XX /r float load with no offset.
+        recipes.add_template(
+            Template::new(
+                EncodingRecipeBuilder::new("vfld", &formats.load, 1)
+                    .operands_in(vec![gpr])
+                    .operands_out(vec![fpr])
+                    .inst_predicate(has_no_offset)
+                    .clobbers_flags(false)
+                    .compute_size("size_plus_maybe_sib_or_offset_for_in_reg_0")
+                    .emit(
+                        r#"
+                        if !flags.notrap() {
+                            sink.trap(TrapCode::HeapOutOfBounds, func.srclocs[inst]);
+                        }
+                        {{PUT_OP}}(bits, rex2(in_reg0, out_reg0), 0, sink);
+                        if needs_sib_byte(in_reg0) {
+                            modrm_sib(out_reg0, sink);
+                            sib_noindex(in_reg0, sink);
+                        } else if needs_offset(in_reg0) {
+                            modrm_disp8(in_reg0, out_reg0, sink);
+                            sink.put1(0);
+                        } else {
+                            modrm_rm(in_reg0, out_reg0, sink);
+                        }
+                    "#,
+                    ),
+                regs,
+            )
+            .vex(),
+        );
+
+        // VEX XX /r float load with 8-bit offset.
+        recipes.add_template(
+            Template::new(
+                EncodingRecipeBuilder::new("vfldDisp8", &formats.load, 2)
+                    .operands_in(vec![gpr])
+                    .operands_out(vec![fpr])
+                    .inst_predicate(has_small_offset)
+                    .clobbers_flags(false)
+                    .compute_size("size_plus_maybe_sib_for_in_reg_0")
+                    .emit(
+                        r#"
+                        if !flags.notrap() {
+                            sink.trap(TrapCode::HeapOutOfBounds, func.srclocs[inst]);
+                        }
+                        {{PUT_OP}}(bits, rex2(in_reg0, out_reg0), 0, sink);
+                        if needs_sib_byte(in_reg0) {
+                            modrm_sib_disp8(out_reg0, sink);
+                            sib_noindex(in_reg0, sink);
+                        } else {
+                            modrm_disp8(in_reg0, out_reg0, sink);
+                        }
+                        let offset: i32 = offset.into();
+                        sink.put1(offset as u8);
+                    "#,
+                    ),
+                regs,
+            )
+            .vex(),
+        );
+
+        // VEX XX /r float load with 32-bit offset.
+        recipes.add_template(
+            Template::new(
+                EncodingRecipeBuilder::new("vfldDisp32", &formats.load, 5)
+                    .operands_in(vec![gpr])
+                    .operands_out(vec![fpr])
+                    .inst_predicate(has_big_offset)
+                    .clobbers_flags(false)
+                    .compute_size("size_plus_maybe_sib_for_in_reg_0")
+                    .emit(
+                        r#"
+                        if !flags.notrap() {
+                            sink.trap(TrapCode::HeapOutOfBounds, func.srclocs[inst]);
+                        }
+                        {{PUT_OP}}(bits, rex2(in_reg0, out_reg0), 0, sink);
+                        if needs_sib_byte(in_reg0) {
+                            modrm_sib_disp32(out_reg0, sink);
+                            sib_noindex(in_reg0, sink);
+                        } else {
+                            modrm_disp32(in_reg0, out_reg0, sink);
+                        }
+                        let offset: i32 = offset.into();
+                        sink.put4(offset as u32);
+                    "#,
+                    ),
+                regs,
+            )
+            .vex(),
+        );
+    }
+
+    // Like fspillSib32, but with a VEX prefix.
+    recipes.add_template(
+        Template::new(
+            EncodingRecipeBuilder::new("vfspillSib32", &formats.unary, 6)
+                .operands_in(vec![fpr])
+                .operands_out(vec![stack_fpr32])
+                .clobbers_flags(false)
+                .emit(
+                    r#"
+                        sink.trap(TrapCode::StackOverflow, func.srclocs[inst]);
+                        let base = stk_base(out_stk0.base);
+                        {{PUT_OP}}(bits, rex2(base, in_reg0), 0, sink);
+                        modrm_sib_disp32(in_reg0, sink);
+                        sib_noindex(base, sink);
+                        sink.put4(out_stk0.offset as u32);
+                    "#,
+                ),
+            regs,
+        )
+        .vex(),
+    );
+
+    // Like fregspill32, but with a VEX prefix.
+    recipes.add_template(
+        Template::new(
+            EncodingRecipeBuilder::new("vfregspill32", &formats.reg_spill, 6)
+                .operands_in(vec![fpr])
+                .clobbers_flags(false)
+                .emit(
+                    r#"
+                        sink.trap(TrapCode::StackOverflow, func.srclocs[inst]);
+                        let dst = StackRef::sp(dst, &func.stack_slots);
+                        let base = stk_base(dst.base);
+                        {{PUT_OP}}(bits, rex2(base, src), 0, sink);
+                        modrm_sib_disp32(src, sink);
+                        sib_noindex(base, sink);
+                        sink.put4(dst.offset as u32);
+                    "#,
+                ),
+            regs,
+        )
+        .vex(),
+    );
+
+    // Like ffillSib32, but with a VEX prefix.
+    recipes.add_template(
+        Template::new(
+            EncodingRecipeBuilder::new("vffillSib32", &formats.unary, 6)
+                .operands_in(vec![stack_fpr32])
+                .operands_out(vec![fpr])
+                .clobbers_flags(false)
+                .emit(
+                    r#"
+                        let base = stk_base(in_stk0.base);
+                        {{PUT_OP}}(bits, rex2(base, out_reg0), 0, sink);
+                        modrm_sib_disp32(out_reg0, sink);
+                        sib_noindex(base, sink);
+                        sink.put4(in_stk0.offset as u32);
+                    "#,
+                ),
+            regs,
+        )
+        .vex(),
+    );
+
+    // Like fregfill32, but with a VEX prefix.
+    recipes.add_template(
+        Template::new(
+            EncodingRecipeBuilder::new("vfregfill32", &formats.reg_fill, 6)
+                .operands_in(vec![stack_fpr32])
+                .clobbers_flags(false)
+                .emit(
+                    r#"
+                        let src = StackRef::sp(src, &func.stack_slots);
+                        let base = stk_base(src.base);
+                        {{PUT_OP}}(bits, rex2(base, dst), 0, sink);
+                        modrm_sib_disp32(dst, sink);
+                        sib_noindex(base, sink);
+                        sink.put4(src.offset as u32);
+                    "#,
+                ),
+            regs,
+        )
+        .vex(),
+    );
+
     recipes.add_recipe(
         EncodingRecipeBuilder::new("safepoint", &formats.multiary, 0).emit(
             r#"
@@ -3,6 +3,10 @@ use crate::shared::{entities::EntityRefs, immediates::Immediates};
 use std::rc::Rc;
 
 pub(crate) struct Formats {
+    pub(crate) atomic_cas: Rc<InstructionFormat>,
+    pub(crate) atomic_load: Rc<InstructionFormat>,
+    pub(crate) atomic_rmw: Rc<InstructionFormat>,
+    pub(crate) atomic_store: Rc<InstructionFormat>,
     pub(crate) binary: Rc<InstructionFormat>,
     pub(crate) binary_imm: Rc<InstructionFormat>,
     pub(crate) branch: Rc<InstructionFormat>,
@@ -18,6 +22,7 @@ pub(crate) struct Formats {
     pub(crate) copy_special: Rc<InstructionFormat>,
     pub(crate) copy_to_ssa: Rc<InstructionFormat>,
     pub(crate) extract_lane: Rc<InstructionFormat>,
+    pub(crate) fence: Rc<InstructionFormat>,
     pub(crate) float_compare: Rc<InstructionFormat>,
     pub(crate) float_cond: Rc<InstructionFormat>,
     pub(crate) float_cond_trap: Rc<InstructionFormat>,
@@ -30,6 +35,8 @@ pub(crate) struct Formats {
     pub(crate) int_cond: Rc<InstructionFormat>,
     pub(crate) int_cond_trap: Rc<InstructionFormat>,
     pub(crate) int_select: Rc<InstructionFormat>,
+    pub(crate) invoke: Rc<InstructionFormat>,
+    pub(crate) invoke_indirect: Rc<InstructionFormat>,
     pub(crate) jump: Rc<InstructionFormat>,
     pub(crate) load: Rc<InstructionFormat>,
     pub(crate) load_complex: Rc<InstructionFormat>,
@@ -203,6 +210,19 @@ impl Formats {
                 .varargs()
                 .build(),
 
+            invoke: Builder::new("Invoke")
+                .imm(&entities.func_ref)
+                .imm(&entities.ebb)
+                .varargs()
+                .build(),
+
+            invoke_indirect: Builder::new("InvokeIndirect")
+                .imm(&entities.sig_ref)
+                .value()
+                .imm(&entities.ebb)
+                .varargs()
+                .build(),
+
             func_addr: Builder::new("FuncAddr").imm(&entities.func_ref).build(),
 
             load: Builder::new("Load")
@@ -231,6 +251,41 @@ impl Formats {
                 .imm(&imm.offset32)
                 .build(),
 
+            atomic_load: Builder::new("AtomicLoad")
+                .imm(&imm.atomic_ordering)
+                .imm(&imm.memflags)
+                .value()
+                .build(),
+
+            atomic_store: Builder::new("AtomicStore")
+                .imm(&imm.atomic_ordering)
+                .imm(&imm.memflags)
+                .value()
+                .value()
+                .build(),
+
+            // The atomic read-modify-write and compare-and-swap instructions are controlled by the
+            // last VALUE operand. The first VALUE operand is always the address.
+            atomic_rmw: Builder::new("AtomicRmw")
+                .imm(&imm.atomic_rmw_op)
+                .imm(&imm.atomic_ordering)
+                .imm(&imm.memflags)
+                .value()
+                .value()
+                .typevar_operand(1)
+                .build(),
+
+            atomic_cas: Builder::new("AtomicCas")
+                .imm(&imm.atomic_ordering)
+                .imm(&imm.memflags)
+                .value()
+                .value()
+                .value()
+                .typevar_operand(2)
+                .build(),
+
+            fence: Builder::new("Fence").imm(&imm.atomic_ordering).build(),
+
             stack_load: Builder::new("StackLoad")
                 .imm(&entities.stack_slot)
                 .imm(&imm.offset32)
@@ -71,6 +71,15 @@ pub(crate) struct Immediates {
     ///
     /// The Rust enum type also has a `User(u16)` variant for user-provided trap codes.
     pub trapcode: OperandKind,
+
+    /// The operation performed by an atomic read-modify-write instruction.
+    ///
+    /// This enumerated operand kind is used for the `atomic_rmw` instruction and corresponds to
+    /// the `ir::AtomicRmwOp` Rust type.
+    pub atomic_rmw_op: OperandKind,
+
+    /// A memory ordering constraint for atomic instructions and fences.
+    pub atomic_ordering: OperandKind,
 }
 
 fn new_imm(format_field_name: &'static str, rust_type: &'static str) -> OperandKind {
@@ -156,6 +165,27 @@ impl Immediates {
                 trapcode_values.insert("int_divz", "IntegerDivisionByZero");
                 new_enum("code", "ir::TrapCode", trapcode_values).with_doc("A trap reason code.")
             },
+            atomic_rmw_op: {
+                let mut atomic_rmw_op_values = HashMap::new();
+                atomic_rmw_op_values.insert("add", "Add");
+                atomic_rmw_op_values.insert("sub", "Sub");
+                atomic_rmw_op_values.insert("and", "And");
+                atomic_rmw_op_values.insert("or", "Or");
+                atomic_rmw_op_values.insert("xor", "Xor");
+                atomic_rmw_op_values.insert("xchg", "Xchg");
+                new_enum("op", "ir::AtomicRmwOp", atomic_rmw_op_values)
+                    .with_doc("An atomic read-modify-write operation.")
+            },
+            atomic_ordering: {
+                let mut atomic_ordering_values = HashMap::new();
+                atomic_ordering_values.insert("relaxed", "Relaxed");
+                atomic_ordering_values.insert("acquire", "Acquire");
+                atomic_ordering_values.insert("release", "Release");
+                atomic_ordering_values.insert("acq_rel", "AcqRel");
+                atomic_ordering_values.insert("seq_cst", "SeqCst");
+                new_enum("ordering", "ir::AtomicOrdering", atomic_ordering_values)
+                    .with_doc("A memory ordering constraint.")
+            },
         }
     }
 }
@@ -517,6 +517,91 @@ pub(crate) fn define(
         .is_call(true),
     );
 
+    ig.push(
+        Inst::new(
+            "return_call",
+            r#"
+        Direct tail call.
+
+        Transfer control to a function declared in the preamble, reusing the
+        current function's frame. The callee's return values become the return
+        values of the current function, so its return types must match the
+        function signature's return types, and both signatures must use the
+        same calling convention.
+
+        Unless the calling convention is ``tail``, the callee's stack
+        arguments must fit in the area the current function received its own
+        stack arguments in.
+        "#,
+            &formats.call,
+        )
+        .operands_in(vec![FN, args])
+        .is_call(true)
+        .is_terminator(true),
+    );
+
+    ig.push(
+        Inst::new(
+            "return_call_indirect",
+            r#"
+        Indirect tail call.
+
+        Transfer control to the function pointed to by `callee`, reusing the
+        current function's frame. The same signature restrictions as for
+        `return_call` apply.
+        "#,
+            &formats.call_indirect,
+        )
+        .operands_in(vec![SIG, callee, args])
+        .is_call(true)
+        .is_terminator(true),
+    );
+
+    let landing_pad = &Operand::new("landing_pad", &entities.ebb)
+        .with_doc("EBB to continue in if the callee unwinds");
+
+    ig.push(
+        Inst::new(
+            "invoke",
+            r#"
+        Direct function call with an unwind destination.
+
+        Call a function like `call`. When the callee returns, execution
+        continues with the next instruction. When an exception unwinds through
+        the call, execution continues at ``landing_pad`` instead, where the
+        return values aren't available.
+
+        The landing pad receives the exception pointer as its only parameter.
+        It can't be the destination of any other kind of branch.
+        "#,
+            &formats.invoke,
+        )
+        .operands_in(vec![FN, landing_pad, args])
+        .operands_out(vec![rvals])
+        .is_call(true)
+        .is_branch(true)
+        .is_indirect_branch(true),
+    );
+
+    ig.push(
+        Inst::new(
+            "invoke_indirect",
+            r#"
+        Indirect function call with an unwind destination.
+
+        Call the function pointed to by `callee` like `call_indirect`, and
+        continue at ``landing_pad`` if an exception unwinds through the call,
+        like `invoke`.
+        "#,
+            &formats.invoke_indirect,
+        )
+        .operands_in(vec![SIG, callee, landing_pad, args])
+        .operands_out(vec![rvals])
+        .is_call(true)
+        .is_branch(true)
+        .is_indirect_branch(true),
+    );
+
     ig.push(
         Inst::new(
             "func_addr",
@@ -893,6 +978,114 @@ pub(crate) fn define(
         .can_store(true),
     );
 
+    let AtomicMem = &TypeVar::new(
+        "AtomicMem",
+        "An integer type that can be accessed atomically",
+        TypeSetBuilder::new().ints(8..64).build(),
+    );
+    let Ordering = &Operand::new("Ordering", &imm.atomic_ordering);
+    let Op = &Operand::new("Op", &imm.atomic_rmw_op);
+    let x = &Operand::new("x", AtomicMem).with_doc("Value to be stored");
+    let a = &Operand::new("a", AtomicMem).with_doc("Value loaded");
+
+    ig.push(
+        Inst::new(
+            "atomic_load",
+            r#"
+        Atomically load from memory at ``p``.
+
+        The ``Ordering`` immediate constrains how surrounding memory accesses
+        may be reordered around the load. It can't be ``release`` or
+        ``acq_rel``.
+        "#,
+            &formats.atomic_load,
+        )
+        .operands_in(vec![Ordering, MemFlags, p])
+        .operands_out(vec![a])
+        .can_load(true)
+        .other_side_effects(true),
+    );
+
+    ig.push(
+        Inst::new(
+            "atomic_store",
+            r#"
+        Atomically store ``x`` to memory at ``p``.
+
+        The ``Ordering`` immediate constrains how surrounding memory accesses
+        may be reordered around the store. It can't be ``acquire`` or
+        ``acq_rel``.
+        "#,
+            &formats.atomic_store,
+        )
+        .operands_in(vec![Ordering, MemFlags, x, p])
+        .can_store(true)
+        .other_side_effects(true),
+    );
+
+    let x = &Operand::new("x", AtomicMem).with_doc("Operand of the read-modify-write operation");
+    let a = &Operand::new("a", AtomicMem).with_doc("Value in memory before the operation");
+
+    ig.push(
+        Inst::new(
+            "atomic_rmw",
+            r#"
+        Atomically read-modify-write memory at ``p``.
+
+        Load the value at ``p``, combine it with ``x`` using ``Op``, and store
+        the result back to ``p`` as a single indivisible operation. The
+        original value in memory is returned.
+
+        Targets that can't perform ``Op`` natively expand this instruction into
+        a loop around ``atomic_cas``.
+        "#,
+            &formats.atomic_rmw,
+        )
+        .operands_in(vec![Op, Ordering, MemFlags, p, x])
+        .operands_out(vec![a])
+        .can_load(true)
+        .can_store(true)
+        .other_side_effects(true),
+    );
+
+    let e = &Operand::new("e", AtomicMem).with_doc("Expected value in memory");
+    let x = &Operand::new("x", AtomicMem).with_doc("Replacement value");
+    let a = &Operand::new("a", AtomicMem).with_doc("Value in memory before the operation");
+
+    ig.push(
+        Inst::new(
+            "atomic_cas",
+            r#"
+        Atomically compare-and-swap memory at ``p``.
+
+        If the value at ``p`` is equal to ``e``, replace it with ``x``. The
+        original value in memory is returned either way, so the exchange
+        succeeded if and only if ``a == e``.
+        "#,
+            &formats.atomic_cas,
+        )
+        .operands_in(vec![Ordering, MemFlags, p, e, x])
+        .operands_out(vec![a])
+        .can_load(true)
+        .can_store(true)
+        .other_side_effects(true),
+    );
+
+    ig.push(
+        Inst::new(
+            "fence",
+            r#"
+        A memory fence.
+
+        Prevent memory accesses from being reordered across this instruction
+        as described by ``Ordering``, which can't be ``relaxed``.
+        "#,
+            &formats.fence,
+        )
+        .operands_in(vec![Ordering])
+        .other_side_effects(true),
+    );
+
     let x = &Operand::new("x", Mem).with_doc("Value to be stored");
     let a = &Operand::new("a", Mem).with_doc("Value loaded");
     let Offset =
@@ -979,6 +1172,21 @@ pub(crate) fn define(
         .operands_out(vec![a]),
     );
 
+    ig.push(
+        Inst::new(
+            "tls_value",
+            r#"
+        Compute the value of global GV, which is a thread local storage symbol.
+
+        The address of a thread local symbol depends on the current thread, so this can't be
+        computed by `symbol_value`. ISAs legalize it according to the `tls_model` setting.
+        "#,
+            &formats.unary_global_value,
+        )
+        .operands_in(vec![GV])
+        .operands_out(vec![a]),
+    );
+
     let HeapOffset = &TypeVar::new(
         "HeapOffset",
         "An unsigned heap offset",
@@ -1192,6 +1400,35 @@ pub(crate) fn define(
         .operands_out(vec![a]),
     );
 
+    let I8xN = &TypeVar::new(
+        "I8xN",
+        "A SIMD vector of 8-bit integers",
+        TypeSetBuilder::new()
+            .ints(8..8)
+            .simd_lanes(Interval::All)
+            .includes_scalars(false)
+            .build(),
+    );
+    let x = &Operand::new("x", I8xN).with_doc("Vector to select lanes from");
+    let s = &Operand::new("s", I8xN).with_doc("Vector of lane indices");
+    let a = &Operand::new("a", I8xN);
+
+    ig.push(
+        Inst::new(
+            "swizzle",
+            r#"
+        SIMD vector swizzle.
+
+        Build a vector by selecting lanes of `x` at run time. For each lane of `s`, a value i
+        smaller than the number of lanes selects the i-th lane of `x`; any other value places a 0
+        in the resulting vector lane.
+        "#,
+            &formats.binary,
+        )
+        .operands_in(vec![x, s])
+        .operands_out(vec![a]),
+    );
+
     let a = &Operand::new("a", Ref).with_doc("A constant reference null value");
 
     ig.push(
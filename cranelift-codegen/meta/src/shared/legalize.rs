@@ -46,6 +46,7 @@ pub(crate) fn define(insts: &InstructionGroup, imm: &Immediates) -> TransformGro
     let band_imm = insts.by_name("band_imm");
     let band_not = insts.by_name("band_not");
     let bint = insts.by_name("bint");
+    let bitcast = insts.by_name("bitcast");
     let bitrev = insts.by_name("bitrev");
     let bnot = insts.by_name("bnot");
     let bor = insts.by_name("bor");
@@ -132,6 +133,8 @@ pub(crate) fn define(insts: &InstructionGroup, imm: &Immediates) -> TransformGro
 
     // Custom expansions for calls.
     expand.custom_legalize(insts.by_name("call"), "expand_call");
+    expand.custom_legalize(insts.by_name("return_call"), "expand_call");
+    expand.custom_legalize(insts.by_name("invoke"), "expand_call");
 
     // Custom expansions that need to change the CFG.
     // TODO: Add sufficient XForm syntax that we don't need to hand-code these.
@@ -149,6 +152,11 @@ pub(crate) fn define(insts: &InstructionGroup, imm: &Immediates) -> TransformGro
     expand.custom_legalize(insts.by_name("stack_load"), "expand_stack_load");
     expand.custom_legalize(insts.by_name("stack_store"), "expand_stack_store");
 
+    // Custom expansion of atomic read-modify-write operations into a compare-and-swap loop. The
+    // loop only needs `atomic_cas` to be legal, so it also works for narrow integer types.
+    expand.custom_legalize(insts.by_name("atomic_rmw"), "expand_atomic_rmw");
+    widen.custom_legalize(insts.by_name("atomic_rmw"), "expand_atomic_rmw");
+
     // List of variables to reuse in patterns.
     let x = var("x");
     let y = var("y");
@@ -357,6 +365,197 @@ pub(crate) fn define(insts: &InstructionGroup, imm: &Immediates) -> TransformGro
         );
     }
 
+    // Shifts and rotates of double-width integers. The halves are shifted by the amount modulo
+    // the half width, relying on the shift instructions masking their amount, and the bit of the
+    // amount worth `half_bits` selects whether the halves trade places. The bits carried across
+    // halves are shifted by one first, so that a zero amount carries nothing.
+    for &(int_ty, int_ty_half, half_bits) in &[(I64, I32, 32), (I128, I64, 64)] {
+        let sign_shift = Literal::constant(&imm.imm64, half_bits - 1);
+        let half_bits = Literal::constant(&imm.imm64, half_bits);
+        let zero = Literal::constant(&imm.imm64, 0);
+        let one = Literal::constant(&imm.imm64, 1);
+
+        // The amount is taken modulo the width of `x`, so only its low half matters.
+        for &op in &[ishl, ushr, sshr, rotl, rotr] {
+            let bound_op = op.bind(int_ty).bind(int_ty);
+            narrow.legalize(
+                def!(a = bound_op(x, y)),
+                vec![def!((yl, yh) = isplit(y)), def!(a = op(x, yl))],
+            );
+        }
+
+        narrow.legalize(
+            def!(a = ishl.int_ty(x, y)),
+            vec![
+                def!((xl, xh) = isplit(x)),
+                def!(a1 = ishl(xl, y)),
+                def!(a2 = ishl(xh, y)),
+                def!(b1 = ushr_imm(xl, one)),
+                def!(b2 = bnot(y)),
+                def!(b3 = ushr(b1, b2)),
+                def!(c1 = bor(a2, b3)),
+                def!(e = band_imm(y, half_bits)),
+                def!(c = icmp_imm(intcc_ne, e, zero)),
+                def!(z = iconst.int_ty_half(zero)),
+                def!(al = select(c, z, a1)),
+                def!(ah = select(c, a1, c1)),
+                def!(a = iconcat(al, ah)),
+            ],
+        );
+
+        narrow.legalize(
+            def!(a = ushr.int_ty(x, y)),
+            vec![
+                def!((xl, xh) = isplit(x)),
+                def!(a1 = ushr(xh, y)),
+                def!(a2 = ushr(xl, y)),
+                def!(b1 = ishl_imm(xh, one)),
+                def!(b2 = bnot(y)),
+                def!(b3 = ishl(b1, b2)),
+                def!(c1 = bor(a2, b3)),
+                def!(e = band_imm(y, half_bits)),
+                def!(c = icmp_imm(intcc_ne, e, zero)),
+                def!(z = iconst.int_ty_half(zero)),
+                def!(al = select(c, a1, c1)),
+                def!(ah = select(c, z, a1)),
+                def!(a = iconcat(al, ah)),
+            ],
+        );
+
+        narrow.legalize(
+            def!(a = sshr.int_ty(x, y)),
+            vec![
+                def!((xl, xh) = isplit(x)),
+                def!(a1 = sshr(xh, y)),
+                def!(a2 = ushr(xl, y)),
+                def!(b1 = ishl_imm(xh, one)),
+                def!(b2 = bnot(y)),
+                def!(b3 = ishl(b1, b2)),
+                def!(c1 = bor(a2, b3)),
+                def!(e = band_imm(y, half_bits)),
+                def!(c = icmp_imm(intcc_ne, e, zero)),
+                def!(d = sshr_imm(xh, sign_shift)),
+                def!(al = select(c, a1, c1)),
+                def!(ah = select(c, d, a1)),
+                def!(a = iconcat(al, ah)),
+            ],
+        );
+
+        narrow.legalize(
+            def!(a = rotl.int_ty(x, y)),
+            vec![
+                def!((xl, xh) = isplit(x)),
+                def!(e = band_imm(y, half_bits)),
+                def!(c = icmp_imm(intcc_ne, e, zero)),
+                def!(d1 = select(c, xh, xl)),
+                def!(d2 = select(c, xl, xh)),
+                def!(b = bnot(y)),
+                def!(a1 = ishl(d1, y)),
+                def!(b1 = ushr_imm(d2, one)),
+                def!(b2 = ushr(b1, b)),
+                def!(al = bor(a1, b2)),
+                def!(a2 = ishl(d2, y)),
+                def!(b3 = ushr_imm(d1, one)),
+                def!(b4 = ushr(b3, b)),
+                def!(ah = bor(a2, b4)),
+                def!(a = iconcat(al, ah)),
+            ],
+        );
+
+        narrow.legalize(
+            def!(a = rotr.int_ty(x, y)),
+            vec![
+                def!((xl, xh) = isplit(x)),
+                def!(e = band_imm(y, half_bits)),
+                def!(c = icmp_imm(intcc_ne, e, zero)),
+                def!(d1 = select(c, xh, xl)),
+                def!(d2 = select(c, xl, xh)),
+                def!(b = bnot(y)),
+                def!(a1 = ushr(d1, y)),
+                def!(b1 = ishl_imm(d2, one)),
+                def!(b2 = ishl(b1, b)),
+                def!(al = bor(a1, b2)),
+                def!(a2 = ushr(d2, y)),
+                def!(b3 = ishl_imm(d1, one)),
+                def!(b4 = ishl(b3, b)),
+                def!(ah = bor(a2, b4)),
+                def!(a = iconcat(al, ah)),
+            ],
+        );
+
+        // Bit counts fit in the low half. The leading zeros of the low half only count when the
+        // high half is zero, and conversely for the trailing zeros.
+        narrow.legalize(
+            def!(a = clz.int_ty(x)),
+            vec![
+                def!((xl, xh) = isplit(x)),
+                def!(a1 = clz(xh)),
+                def!(a2 = clz(xl)),
+                def!(z = iconst.int_ty_half(zero)),
+                def!(b = select(xh, z, a2)),
+                def!(al = iadd(a1, b)),
+                def!(a = iconcat(al, z)),
+            ],
+        );
+
+        narrow.legalize(
+            def!(a = ctz.int_ty(x)),
+            vec![
+                def!((xl, xh) = isplit(x)),
+                def!(a1 = ctz(xl)),
+                def!(a2 = ctz(xh)),
+                def!(z = iconst.int_ty_half(zero)),
+                def!(b = select(xl, z, a2)),
+                def!(al = iadd(a1, b)),
+                def!(a = iconcat(al, z)),
+            ],
+        );
+
+        narrow.legalize(
+            def!(a = popcnt.int_ty(x)),
+            vec![
+                def!((xl, xh) = isplit(x)),
+                def!(a1 = popcnt(xl)),
+                def!(a2 = popcnt(xh)),
+                def!(al = iadd(a1, a2)),
+                def!(z = iconst.int_ty_half(zero)),
+                def!(a = iconcat(al, z)),
+            ],
+        );
+    }
+
+    // Materialize i128 immediates with `narrow_iconst`, so the operations above and the library
+    // calls for divisions apply.
+    for &(inst_imm, inst) in &[
+        (iadd_imm, iadd),
+        (imul_imm, imul),
+        (sdiv_imm, sdiv),
+        (udiv_imm, udiv),
+        (srem_imm, srem),
+        (urem_imm, urem),
+        (band_imm, band),
+        (bor_imm, bor),
+        (bxor_imm, bxor),
+    ] {
+        narrow.legalize(
+            def!(a = inst_imm.I128(x, y)),
+            vec![def!(a1 = iconst(y)), def!(a = inst(x, a1))],
+        );
+    }
+
+    for &(inst_imm, inst) in &[
+        (rotl_imm, rotl),
+        (rotr_imm, rotr),
+        (ishl_imm, ishl),
+        (sshr_imm, sshr),
+        (ushr_imm, ushr),
+    ] {
+        narrow.legalize(
+            def!(a = inst_imm.I128(x, y)),
+            vec![def!(a1 = iconst.I32(y)), def!(a = inst(x, a1))],
+        );
+    }
+
     // Widen instructions with one input operand.
     for &op in &[bnot, popcnt] {
         for &int_ty in &[I8, I16] {
@@ -706,6 +905,21 @@ pub(crate) fn define(insts: &InstructionGroup, imm: &Immediates) -> TransformGro
         vec![def!(y = iconst(minus_one)), def!(a = bxor(x, y))],
     );
 
+    //# Expand float bnot on the integer bits, for targets with soft floats.
+    for &(ty, int_ty) in &[(F32, I32), (F64, I64)] {
+        let bnot_float = bnot.bind(ty);
+        let bitcast_to_int = bitcast.bind(int_ty).bind(ty);
+        let bitcast_to_float = bitcast.bind(ty).bind(int_ty);
+        expand.legalize(
+            def!(a = bnot_float(x)),
+            vec![
+                def!(b = bitcast_to_int(x)),
+                def!(c = bnot(b)),
+                def!(a = bitcast_to_float(c)),
+            ],
+        );
+    }
+
     //# Expand bitrev
     //# Adapted from Stack Overflow.
     //# https://stackoverflow.com/questions/746171/most-efficient-algorithm-for-bit-reversal-from-msb-lsb-to-lsb-msb-in-c
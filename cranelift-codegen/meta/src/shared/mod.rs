@@ -1,6 +1,6 @@
 //! Shared definitions for the Cranelift intermediate language.
 
-mod entities;
+pub mod entities;
 pub mod formats;
 pub mod immediates;
 pub mod instructions;
@@ -27,6 +27,7 @@ pub(crate) struct Definitions {
     pub instructions: InstructionGroup,
     pub imm: Immediates,
     pub formats: Formats,
+    pub entities: EntityRefs,
     pub transform_groups: TransformGroups,
 }
 
@@ -46,6 +47,7 @@ pub(crate) fn define() -> Definitions {
         instructions,
         imm: immediates,
         formats,
+        entities,
         transform_groups,
     }
 }
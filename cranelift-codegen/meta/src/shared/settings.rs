@@ -16,6 +16,21 @@ pub(crate) fn define() -> SettingGroup {
         vec!["none", "speed", "speed_and_size"],
     );
 
+    settings.add_enum(
+        "tls_model",
+        r#"
+        Defines the model used to access thread local storage values:
+
+        - none: Thread local storage isn't supported.
+        - elf_gd: The ELF general-dynamic model, which calls `__tls_get_addr` and works for any
+          symbol, including ones defined in dynamically loaded libraries.
+        - elf_ie: The ELF initial-exec model, which loads the symbol's offset from the thread
+          pointer from the GOT. This only works for symbols defined in the executable or in
+          libraries loaded at program startup.
+        "#,
+        vec!["none", "elf_gd", "elf_ie"],
+    );
+
     settings.add_bool(
         "enable_verifier",
         r#"
@@ -131,6 +146,19 @@ pub(crate) fn define() -> SettingGroup {
         false,
     );
 
+    settings.add_num(
+        "inline_threshold",
+        r#"
+            The largest number of instructions in a function that can be inlined.
+
+            Calls to functions whose body is known, such as the functions defined
+            earlier in the same module, are replaced by the body of the callee if
+            it has at most this many instructions. The default of 0 disables
+            inlining.
+            "#,
+        0,
+    );
+
     // Settings specific to the `baldrdash` calling convention.
 
     settings.add_enum(
@@ -208,13 +236,28 @@ pub(crate) fn define() -> SettingGroup {
             The log2 of the size of the stack guard region.
 
             Stack frames larger than this size will have stack overflow checked
-            by calling the probestack function.
+            by probing each page of the frame, as selected by
+            ``probestack_strategy``.
 
             The default is 12, which translates to a size of 4096.
             "#,
         12,
     );
 
+    settings.add_enum(
+        "probestack_strategy",
+        r#"
+            Controls how stack probes are emitted.
+
+            - outline: Call the ``Probestack`` libcall, which must be provided
+              by the runtime (e.g. ``__rust_probestack`` or ``__chkstk``).
+            - inline: Touch each page of the frame with code emitted in the
+              prologue. Small frames get one probe per page; larger ones get a
+              loop. This needs no runtime support.
+            "#,
+        vec!["outline", "inline"],
+    );
+
     // Jump table options.
 
     settings.add_bool(
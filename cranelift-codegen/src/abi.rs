@@ -167,10 +167,15 @@ pub fn legalize_abi_value(have: Type, arg: &AbiParam) -> ValueConversion {
         }
         // We have the same number of bits as the argument.
         Ordering::Equal => {
-            // This must be an integer vector that is split and then extended.
             debug_assert!(arg.value_type.is_int());
-            debug_assert!(have.is_vector(), "expected vector type, got {}", have);
-            ValueConversion::VectorSplit
+            if have.is_vector() {
+                // This must be an integer vector that is split and then extended.
+                ValueConversion::VectorSplit
+            } else {
+                // A float passed in an integer register of the same size.
+                debug_assert!(have.is_float(), "expected vector or float, got {}", have);
+                ValueConversion::IntBits
+            }
         }
         // We have more bits than the argument.
         Ordering::Greater => {
@@ -226,5 +231,12 @@ mod tests {
             legalize_abi_value(types::F64, &arg),
             ValueConversion::IntBits
         );
+
+        // 32-bit float is passed as the same size integer.
+        arg.extension = ArgumentExtension::None;
+        assert_eq!(
+            legalize_abi_value(types::F32, &arg),
+            ValueConversion::IntBits
+        );
     }
 }
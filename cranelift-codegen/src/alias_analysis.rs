@@ -0,0 +1,526 @@
+//! Alias analysis, and the memory optimizations it enables.
+//!
+//! The address of every load and store is decomposed into a base and a constant offset. The base
+//! identifies the memory region that is accessed:
+//!
+//! - Stack slots are disjoint from each other, and from all other memory as long as their address
+//!   is never taken with `stack_addr`.
+//! - Heaps and tables are disjoint from each other and from the stack. Two accesses to the same
+//!   heap or table are only known to be at the same address if they use the same index.
+//! - Accesses whose flags declare different memory regions, like `heap` and `vmctx`, are
+//!   disjoint. Accesses through `heap_addr`, `table_addr` and stack slots are in the `heap`,
+//!   `table` and `stack` regions even without a flag.
+//! - Loads with the `readonly` flag read memory that is never written.
+//!
+//! Accesses with the same base and offsets that don't overlap never alias. Anything else may.
+//!
+//! A forward data flow analysis over the CFG then computes which values are known to be in memory
+//! at each program point. This is used to replace loads of a known value with that value, both
+//! when it was loaded before and when it was just stored. Stores that are overwritten before
+//! anything can observe them, or that store the value already in memory, are removed.
+//!
+//! This pass must run before legalization, which expands heap and table accesses into plain
+//! address arithmetic.
+
+use crate::cursor::{Cursor, FuncCursor};
+use crate::dominator_tree::DominatorTree;
+use crate::entity::EntitySet;
+use crate::flowgraph::ControlFlowGraph;
+use crate::fx::FxHashMap;
+use crate::ir::{
+    DataFlowGraph, Ebb, Function, GlobalValue, Heap, Inst, InstructionData, MemRegion, Opcode,
+    StackSlot, Table, Type, Value, ValueDef,
+};
+use crate::timing;
+use alloc::vec::Vec;
+
+/// The start of the memory accessed by a load or store.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+enum Base {
+    /// A stack slot.
+    StackSlot(StackSlot),
+    /// The address of an index in a heap.
+    Heap(Heap, Value),
+    /// The address of an element in a table.
+    Table(Table, Value),
+    /// The address computed by a global value.
+    GlobalValue(GlobalValue),
+    /// Any other address.
+    Value(Value),
+}
+
+/// A memory location, identified by its base and a constant offset.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+struct Location {
+    base: Base,
+    offset: i64,
+}
+
+/// The memory accessed by a load or a store.
+#[derive(Clone, Copy, Debug)]
+struct Access {
+    location: Location,
+    size: u32,
+    /// The memory region of the access, if it is known.
+    region: Option<MemRegion>,
+}
+
+/// A value known to be in memory.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Fact {
+    value: Value,
+    /// The value was loaded from memory that is never written.
+    readonly: bool,
+    /// The memory region of the access that produced the fact.
+    region: Option<MemRegion>,
+}
+
+/// The values known to be in memory, indexed by their location and type.
+type Facts = FxHashMap<(Location, Type), Fact>;
+
+/// What to do with an instruction, given the values known to be in memory.
+enum Action {
+    /// Keep the instruction.
+    Keep,
+    /// The instruction loads a value that is known to be in memory already.
+    ReplaceLoad(Value),
+    /// The instruction stores a value that is known to be in memory already.
+    RemoveStore,
+}
+
+/// Decompose `addr` into a base and a constant offset.
+fn address_base(dfg: &DataFlowGraph, addr: Value) -> (Base, i64) {
+    let mut addr = dfg.resolve_aliases(addr);
+    let mut offset = 0i64;
+    while let ValueDef::Result(inst, _) = dfg.value_def(addr) {
+        match dfg[inst] {
+            InstructionData::BinaryImm {
+                opcode: Opcode::IaddImm,
+                arg,
+                imm,
+            } => {
+                let imm: i64 = imm.into();
+                offset = offset.wrapping_add(imm);
+                addr = dfg.resolve_aliases(arg);
+            }
+            InstructionData::Binary {
+                opcode: Opcode::Iadd,
+                args,
+            } => match (iconst_value(dfg, args[0]), iconst_value(dfg, args[1])) {
+                (_, Some(imm)) => {
+                    offset = offset.wrapping_add(imm);
+                    addr = dfg.resolve_aliases(args[0]);
+                }
+                (Some(imm), None) => {
+                    offset = offset.wrapping_add(imm);
+                    addr = dfg.resolve_aliases(args[1]);
+                }
+                (None, None) => break,
+            },
+            InstructionData::HeapAddr { heap, arg, .. } => {
+                return (Base::Heap(heap, dfg.resolve_aliases(arg)), offset);
+            }
+            InstructionData::TableAddr {
+                table,
+                arg,
+                offset: table_offset,
+                ..
+            } => {
+                let table_offset: i32 = table_offset.into();
+                let offset = offset.wrapping_add(table_offset.into());
+                return (Base::Table(table, dfg.resolve_aliases(arg)), offset);
+            }
+            InstructionData::StackLoad {
+                opcode: Opcode::StackAddr,
+                stack_slot,
+                offset: slot_offset,
+            } => {
+                let slot_offset: i32 = slot_offset.into();
+                let offset = offset.wrapping_add(slot_offset.into());
+                return (Base::StackSlot(stack_slot), offset);
+            }
+            InstructionData::UnaryGlobalValue {
+                opcode: Opcode::GlobalValue,
+                global_value,
+            } => return (Base::GlobalValue(global_value), offset),
+            _ => break,
+        }
+    }
+    (Base::Value(addr), offset)
+}
+
+/// Get the memory region implied by the base of an address.
+fn base_region(base: Base) -> Option<MemRegion> {
+    match base {
+        Base::StackSlot(_) => Some(MemRegion::Stack),
+        Base::Heap(..) => Some(MemRegion::Heap),
+        Base::Table(..) => Some(MemRegion::Table),
+        Base::GlobalValue(_) | Base::Value(_) => None,
+    }
+}
+
+/// Get the value of `value` if it is defined by an `iconst` instruction.
+fn iconst_value(dfg: &DataFlowGraph, value: Value) -> Option<i64> {
+    if let ValueDef::Result(inst, _) = dfg.value_def(dfg.resolve_aliases(value)) {
+        if let InstructionData::UnaryImm {
+            opcode: Opcode::Iconst,
+            imm,
+        } = dfg[inst]
+        {
+            return Some(imm.into());
+        }
+    }
+    None
+}
+
+/// Get the number of bytes accessed by the load or store `opcode` of a value of type `ty`.
+fn access_size(opcode: Opcode, ty: Type) -> u32 {
+    match opcode {
+        Opcode::Uload8 | Opcode::Sload8 | Opcode::Istore8 => 1,
+        Opcode::Uload16 | Opcode::Sload16 | Opcode::Istore16 => 2,
+        Opcode::Uload32 | Opcode::Sload32 | Opcode::Istore32 => 4,
+        _ => ty.bytes(),
+    }
+}
+
+/// The alias analysis of a function.
+struct AliasAnalysis {
+    /// The stack slots whose address is taken.
+    escaped: EntitySet<StackSlot>,
+}
+
+impl AliasAnalysis {
+    fn new(func: &Function) -> Self {
+        let mut escaped = EntitySet::new();
+        for ebb in func.layout.ebbs() {
+            for inst in func.layout.ebb_insts(ebb) {
+                if let InstructionData::StackLoad {
+                    opcode: Opcode::StackAddr,
+                    stack_slot,
+                    ..
+                } = func.dfg[inst]
+                {
+                    escaped.insert(stack_slot);
+                }
+            }
+        }
+        Self { escaped }
+    }
+
+    /// Get the memory accessed by `inst`, if it is a load or store with a simple address.
+    fn access(&self, dfg: &DataFlowGraph, inst: Inst) -> Option<Access> {
+        let (base, base_offset, offset, size, region) = match dfg[inst] {
+            InstructionData::Load {
+                opcode,
+                flags,
+                arg,
+                offset,
+            } => {
+                let (base, base_offset) = address_base(dfg, arg);
+                let ty = dfg.value_type(dfg.first_result(inst));
+                let size = access_size(opcode, ty);
+                (base, base_offset, offset, size, flags.region())
+            }
+            InstructionData::Store {
+                opcode,
+                flags,
+                args,
+                offset,
+            } => {
+                let (base, base_offset) = address_base(dfg, args[1]);
+                let ty = dfg.value_type(args[0]);
+                let size = access_size(opcode, ty);
+                (base, base_offset, offset, size, flags.region())
+            }
+            InstructionData::StackLoad {
+                opcode: Opcode::StackLoad,
+                stack_slot,
+                offset,
+            } => {
+                let ty = dfg.value_type(dfg.first_result(inst));
+                (Base::StackSlot(stack_slot), 0, offset, ty.bytes(), None)
+            }
+            InstructionData::StackStore {
+                arg,
+                stack_slot,
+                offset,
+                ..
+            } => {
+                let ty = dfg.value_type(arg);
+                (Base::StackSlot(stack_slot), 0, offset, ty.bytes(), None)
+            }
+            _ => return None,
+        };
+        let offset: i32 = offset.into();
+        Some(Access {
+            location: Location {
+                base,
+                offset: base_offset.wrapping_add(offset.into()),
+            },
+            size,
+            region: region.or_else(|| base_region(base)),
+        })
+    }
+
+    /// Can the memory accessed by `a` and `b` overlap?
+    fn may_alias(&self, a: &Access, b: &Access) -> bool {
+        use self::Base::*;
+        let (a_loc, b_loc) = (a.location, b.location);
+        if a_loc.base == b_loc.base {
+            return a_loc.offset < b_loc.offset.saturating_add(b.size.into())
+                && b_loc.offset < a_loc.offset.saturating_add(a.size.into());
+        }
+        if let (Some(a_region), Some(b_region)) = (a.region, b.region) {
+            if a_region != b_region {
+                return false;
+            }
+        }
+        match (a_loc.base, b_loc.base) {
+            (StackSlot(_), StackSlot(_)) => false,
+            (StackSlot(ss), GlobalValue(_))
+            | (StackSlot(ss), Value(_))
+            | (GlobalValue(_), StackSlot(ss))
+            | (Value(_), StackSlot(ss)) => self.escaped.contains(ss),
+            (StackSlot(_), _) | (_, StackSlot(_)) => false,
+            (Heap(a, _), Heap(b, _)) => a == b,
+            (Table(a, _), Table(b, _)) => a == b,
+            (Heap(..), Table(..)) | (Table(..), Heap(..)) => false,
+            _ => true,
+        }
+    }
+
+    /// Forget the facts that may be clobbered by a store to `access`.
+    fn clobber(&self, facts: &mut Facts, access: &Access) {
+        facts.retain(|&(location, ty), fact| {
+            let fact_access = Access {
+                location,
+                size: ty.bytes(),
+                region: fact.region,
+            };
+            fact.readonly || !self.may_alias(access, &fact_access)
+        });
+    }
+
+    /// Forget the facts that may be clobbered by an instruction that writes to unknown memory,
+    /// like a call.
+    fn clobber_all(&self, facts: &mut Facts) {
+        facts.retain(|&(location, _), fact| {
+            fact.readonly
+                || match location.base {
+                    Base::StackSlot(ss) => !self.escaped.contains(ss),
+                    _ => false,
+                }
+        });
+    }
+
+    /// Update `facts` with the effects of `inst`, and determine whether it is redundant.
+    fn step(&self, dfg: &DataFlowGraph, inst: Inst, facts: &mut Facts) -> Action {
+        let opcode = dfg[inst].opcode();
+        match dfg[inst] {
+            InstructionData::Load { flags, .. } if opcode == Opcode::Load => {
+                self.load(dfg, inst, flags.readonly(), facts)
+            }
+            InstructionData::StackLoad {
+                opcode: Opcode::StackLoad,
+                ..
+            } => self.load(dfg, inst, false, facts),
+            InstructionData::Store { args, .. } if opcode == Opcode::Store => {
+                self.store(dfg, inst, args[0], facts)
+            }
+            InstructionData::StackStore { arg, .. } => self.store(dfg, inst, arg, facts),
+            _ => {
+                if opcode.is_call() || opcode.other_side_effects() || opcode.can_store() {
+                    match self.access(dfg, inst) {
+                        Some(access) if !opcode.is_call() => self.clobber(facts, &access),
+                        _ => self.clobber_all(facts),
+                    }
+                }
+                Action::Keep
+            }
+        }
+    }
+
+    fn load(&self, dfg: &DataFlowGraph, inst: Inst, readonly: bool, facts: &mut Facts) -> Action {
+        let access = self.access(dfg, inst).unwrap();
+        let result = dfg.first_result(inst);
+        let key = (access.location, dfg.value_type(result));
+        if let Some(fact) = facts.get(&key) {
+            return Action::ReplaceLoad(fact.value);
+        }
+        facts.insert(
+            key,
+            Fact {
+                value: result,
+                readonly,
+                region: access.region,
+            },
+        );
+        Action::Keep
+    }
+
+    fn store(&self, dfg: &DataFlowGraph, inst: Inst, value: Value, facts: &mut Facts) -> Action {
+        let access = self.access(dfg, inst).unwrap();
+        let value = dfg.resolve_aliases(value);
+        let key = (access.location, dfg.value_type(value));
+        if facts.get(&key).map(|fact| fact.value) == Some(value) {
+            return Action::RemoveStore;
+        }
+        self.clobber(facts, &access);
+        facts.insert(
+            key,
+            Fact {
+                value,
+                readonly: false,
+                region: access.region,
+            },
+        );
+        Action::Keep
+    }
+}
+
+/// Keep the facts in `facts` that are also in `other`.
+fn intersect(facts: &mut Facts, other: &Facts) {
+    facts.retain(|key, fact| other.get(key) == Some(fact));
+}
+
+/// Remove redundant loads and stores from `func`.
+pub fn do_alias_analysis(func: &mut Function, cfg: &ControlFlowGraph, domtree: &DominatorTree) {
+    let _tt = timing::alias_analysis();
+    debug_assert!(cfg.is_valid());
+    debug_assert!(domtree.is_valid());
+
+    let analysis = AliasAnalysis::new(func);
+    let ebbs: Vec<Ebb> = domtree.cfg_postorder().iter().rev().cloned().collect();
+    let entry = match ebbs.first() {
+        Some(&entry) => entry,
+        None => return,
+    };
+
+    // Compute the facts at the start of each EBB, and after each branch. The facts at the start
+    // of an EBB are the ones that hold after all of its predecessors that have been visited, and
+    // they only ever shrink, so this terminates.
+    let mut entry_facts: FxHashMap<Ebb, Facts> = FxHashMap::default();
+    let mut branch_facts: FxHashMap<Inst, Facts> = FxHashMap::default();
+    loop {
+        let mut changed = false;
+        for &ebb in &ebbs {
+            let mut facts = if ebb == entry {
+                Facts::default()
+            } else {
+                let mut facts: Option<Facts> = entry_facts.get(&ebb).cloned();
+                for pred in cfg.pred_iter(ebb) {
+                    if let Some(pred_facts) = branch_facts.get(&pred.inst) {
+                        match facts {
+                            Some(ref mut facts) => intersect(facts, pred_facts),
+                            None => facts = Some(pred_facts.clone()),
+                        }
+                    }
+                }
+                facts.unwrap_or_default()
+            };
+            if entry_facts.get(&ebb) != Some(&facts) {
+                entry_facts.insert(ebb, facts.clone());
+                changed = true;
+            }
+
+            for inst in func.layout.ebb_insts(ebb) {
+                analysis.step(&func.dfg, inst, &mut facts);
+                if func.dfg[inst].opcode().is_branch() {
+                    branch_facts.insert(inst, facts.clone());
+                }
+            }
+        }
+        if !changed {
+            break;
+        }
+    }
+
+    let mut pos = FuncCursor::new(func);
+    for &ebb in &ebbs {
+        let mut facts = entry_facts.remove(&ebb).unwrap_or_default();
+        // The stores in this EBB that haven't been observed yet.
+        let mut unobserved: Vec<(Access, Inst)> = Vec::new();
+
+        pos.goto_top(ebb);
+        while let Some(inst) = pos.next_inst() {
+            match analysis.step(&pos.func.dfg, inst, &mut facts) {
+                Action::ReplaceLoad(value) => {
+                    let result = pos.func.dfg.first_result(inst);
+                    pos.func.dfg.clear_results(inst);
+                    pos.func.dfg.change_to_alias(result, value);
+                    pos.remove_inst_and_step_back();
+                    continue;
+                }
+                Action::RemoveStore => {
+                    pos.remove_inst_and_step_back();
+                    continue;
+                }
+                Action::Keep => {}
+            }
+            observe_stores(&analysis, &mut pos, inst, &mut unobserved);
+        }
+    }
+}
+
+/// Update the stores of the current EBB that haven't been observed yet after `inst`, and remove
+/// the ones that `inst` overwrites.
+fn observe_stores(
+    analysis: &AliasAnalysis,
+    pos: &mut FuncCursor,
+    inst: Inst,
+    unobserved: &mut Vec<(Access, Inst)>,
+) {
+    let dfg = &pos.func.dfg;
+    let opcode = dfg[inst].opcode();
+    let (access, notrap) = match dfg[inst] {
+        InstructionData::Load { flags, .. } | InstructionData::Store { flags, .. } => {
+            (analysis.access(dfg, inst), flags.notrap())
+        }
+        InstructionData::StackLoad {
+            opcode: Opcode::StackLoad,
+            ..
+        }
+        | InstructionData::StackStore { .. } => (analysis.access(dfg, inst), true),
+        _ => (None, false),
+    };
+
+    match access {
+        Some(access) if opcode.can_store() => {
+            // A store is only overwritten by a store of the same size to the same address, which
+            // traps if and only if the overwritten store would have. A wider store could trap on
+            // the bytes that the overwritten store doesn't access.
+            let layout = &mut pos.func.layout;
+            unobserved.retain(|&(other, other_inst)| {
+                let overwritten = other.location == access.location && other.size == access.size;
+                if overwritten {
+                    layout.remove_inst(other_inst);
+                }
+                !overwritten
+            });
+            if !notrap {
+                unobserved.clear();
+            }
+            unobserved.push((access, inst));
+        }
+        Some(access) if notrap => {
+            unobserved.retain(|(other, _)| !analysis.may_alias(&access, other));
+        }
+        _ => {
+            // The stores must be visible to a trap handler, so an instruction that can trap
+            // observes all of them. This includes the bounds checks of `heap_addr` and
+            // `table_addr`.
+            if opcode.can_load()
+                || opcode.can_trap()
+                || opcode == Opcode::HeapAddr
+                || opcode == Opcode::TableAddr
+                || opcode.is_call()
+                || opcode.is_branch()
+                || opcode.is_terminator()
+                || opcode.other_side_effects()
+                || opcode.can_store()
+            {
+                unobserved.clear();
+            }
+        }
+    }
+}
@@ -56,6 +56,11 @@ pub enum Reloc {
     Arm64Call,
     /// RISC-V call target
     RiscvCall,
+
+    /// Elf x86_64 32 bit signed PC relative offset to two GOT entries for GD symbol.
+    ElfX86_64TlsGd,
+    /// Elf x86_64 32 bit signed PC relative offset to GOT entry for IE symbol.
+    ElfX86_64GotTpOff,
 }
 
 impl fmt::Display for Reloc {
@@ -71,6 +76,8 @@ impl fmt::Display for Reloc {
             Self::X86CallPLTRel4 => write!(f, "CallPLTRel4"),
             Self::X86GOTPCRel4 => write!(f, "GOTPCRel4"),
             Self::Arm32Call | Self::Arm64Call | Self::RiscvCall => write!(f, "Call"),
+            Self::ElfX86_64TlsGd => write!(f, "ElfX86_64TlsGd"),
+            Self::ElfX86_64GotTpOff => write!(f, "ElfX86_64GotTpOff"),
         }
     }
 }
@@ -155,6 +162,53 @@ pub trait CodeSink {
     fn add_stackmap(&mut self, _: &[Value], _: &Function, _: &dyn TargetIsa);
 }
 
+/// Offset in bytes from the beginning of a frame unwind information buffer.
+pub type FrameUnwindOffset = usize;
+
+/// The kinds of frame unwind information a `TargetIsa` can produce.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum FrameUnwindKind {
+    /// Windows x64 `UNWIND_INFO`, as referenced from the `.pdata` section.
+    Fastcall,
+    /// A DWARF CIE followed by an FDE, in the `.eh_frame` format understood by libgcc and
+    /// libunwind.
+    Libunwind,
+}
+
+/// Abstract interface for receiving frame unwind information.
+///
+/// The unwind information is emitted as a stream of bytes. Positions in the stream which refer to
+/// the function's code are reported as relocations against the start of the function.
+pub trait FrameUnwindSink {
+    /// Get the number of bytes emitted so far.
+    fn len(&self) -> FrameUnwindOffset;
+
+    /// Has nothing been emitted yet?
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Add bytes to the unwind information.
+    fn bytes(&mut self, _: &[u8]);
+
+    /// Add a relocation at `offset` referencing the start of the function.
+    fn reloc(&mut self, _: Reloc, _: FrameUnwindOffset);
+
+    /// Add a relocation at `offset` referencing the external symbol `name`, such as the
+    /// function's personality routine.
+    fn reloc_external(&mut self, _: Reloc, _: FrameUnwindOffset, _: &ExternalName);
+
+    /// Add the function's language-specific data area (LSDA), along with a relocation at `offset`
+    /// referencing its start.
+    ///
+    /// The LSDA isn't part of the unwind information itself. Object files put it in the
+    /// `.gcc_except_table` section.
+    fn lsda(&mut self, _: &[u8], _: Reloc, _: FrameUnwindOffset);
+
+    /// Record the offset of the entry describing the function (an FDE for `Libunwind`).
+    fn set_entry_offset(&mut self, _: FrameUnwindOffset);
+}
+
 /// Report a bad encoding error.
 #[cold]
 pub fn bad_encoding(func: &Function, inst: Inst) -> ! {
@@ -9,13 +9,15 @@
 //! contexts concurrently. Typically, you would have one context per compilation thread and only a
 //! single ISA instance.
 
+use crate::alias_analysis::do_alias_analysis;
 use crate::binemit::{
-    relax_branches, shrink_instructions, CodeInfo, MemoryCodeSink, RelocSink, StackmapSink,
-    TrapSink,
+    relax_branches, shrink_instructions, CodeInfo, FrameUnwindKind, FrameUnwindSink,
+    MemoryCodeSink, RelocSink, StackmapSink, TrapSink,
 };
 use crate::dce::do_dce;
 use crate::dominator_tree::DominatorTree;
 use crate::flowgraph::ControlFlowGraph;
+use crate::inline::{do_inlining, InlineCallees};
 use crate::ir::Function;
 use crate::isa::TargetIsa;
 use crate::legalize_function;
@@ -29,8 +31,10 @@ use crate::result::CodegenResult;
 use crate::settings::{FlagsOrIsa, OptLevel};
 use crate::simple_gvn::do_simple_gvn;
 use crate::simple_preopt::do_preopt;
+use crate::strength_reduction::do_strength_reduction;
 use crate::timing;
 use crate::unreachable_code::eliminate_unreachable_code;
+use crate::unroll::do_loop_unrolling;
 use crate::value_label::{build_value_labels_ranges, ComparableSourceLoc, ValueLabelsRanges};
 use crate::verifier::{verify_context, verify_locations, VerifierErrors, VerifierResult};
 use alloc::vec::Vec;
@@ -138,6 +142,12 @@ impl Context {
         if opt_level != OptLevel::None {
             self.preopt(isa)?;
         }
+        if opt_level == OptLevel::Speed {
+            self.flowgraph();
+            self.compute_loop_analysis();
+            self.strength_reduce(isa)?;
+            self.unroll_loops(isa)?;
+        }
         if isa.flags().enable_nan_canonicalization() {
             self.canonicalize_nans(isa)?;
         }
@@ -197,10 +207,16 @@ impl Context {
     ///
     /// Requires that the function layout be calculated (see `relax_branches`).
     ///
-    /// Only some calling conventions (e.g. Windows fastcall) will have unwind information.
-    /// This is a no-op if the function has no unwind information.
-    pub fn emit_unwind_info(&self, isa: &dyn TargetIsa, mem: &mut Vec<u8>) {
-        isa.emit_unwind_info(&self.func, mem);
+    /// Only some calling conventions (e.g. Windows fastcall and System V on x86-64) will have
+    /// unwind information. This is a no-op if the function has no unwind information of the
+    /// requested `kind`.
+    pub fn emit_unwind_info(
+        &self,
+        isa: &dyn TargetIsa,
+        kind: FrameUnwindKind,
+        sink: &mut dyn FrameUnwindSink,
+    ) {
+        isa.emit_unwind_info(&self.func, kind, sink);
     }
 
     /// Run the verifier on the function.
@@ -312,6 +328,18 @@ impl Context {
         self.verify_if(fisa)
     }
 
+    /// Remove the redundant loads and stores from the function.
+    ///
+    /// This needs the CFG and dominator tree, and must run before legalization. It isn't part of
+    /// `compile`, so embedders that want it must call it before compiling.
+    pub fn alias_analysis<'a, FOI: Into<FlagsOrIsa<'a>>>(
+        &mut self,
+        fisa: FOI,
+    ) -> CodegenResult<()> {
+        do_alias_analysis(&mut self.func, &self.cfg, &self.domtree);
+        self.verify_if(fisa)
+    }
+
     /// Perform LICM on the function.
     pub fn licm(&mut self, isa: &dyn TargetIsa) -> CodegenResult<()> {
         do_licm(
@@ -324,6 +352,56 @@ impl Context {
         self.verify_if(isa)
     }
 
+    /// Replace the multiplications of induction variables in loops with additions.
+    ///
+    /// This needs the CFG, dominator tree and loop analysis, and must run before legalization.
+    pub fn strength_reduce<'a, FOI: Into<FlagsOrIsa<'a>>>(
+        &mut self,
+        fisa: FOI,
+    ) -> CodegenResult<()> {
+        do_strength_reduction(
+            &mut self.func,
+            &self.cfg,
+            &self.domtree,
+            &self.loop_analysis,
+        );
+        self.verify_if(fisa)
+    }
+
+    /// Partially unroll the small loops with a constant number of iterations.
+    ///
+    /// This needs the CFG, dominator tree and loop analysis, and must run before legalization.
+    pub fn unroll_loops<'a, FOI: Into<FlagsOrIsa<'a>>>(&mut self, fisa: FOI) -> CodegenResult<()> {
+        do_loop_unrolling(
+            &mut self.func,
+            &mut self.cfg,
+            &mut self.domtree,
+            &mut self.loop_analysis,
+        );
+        self.verify_if(fisa)
+    }
+
+    /// Inline the calls to the small functions known by `callees`.
+    ///
+    /// Functions with at most `inline_threshold` instructions are inlined. This does nothing if
+    /// the `inline_threshold` setting is 0.
+    pub fn inline<'a, FOI, C>(&mut self, fisa: FOI, callees: &C) -> CodegenResult<()>
+    where
+        FOI: Into<FlagsOrIsa<'a>>,
+        C: InlineCallees + ?Sized,
+    {
+        let fisa = fisa.into();
+        let threshold = usize::from(fisa.flags.inline_threshold());
+        if threshold != 0 {
+            // Inlining changes the CFG.
+            self.domtree.clear();
+            self.loop_analysis.clear();
+            do_inlining(&mut self.func, callees, threshold);
+            self.compute_cfg();
+        }
+        self.verify_if(fisa)
+    }
+
     /// Perform unreachable code elimination.
     pub fn eliminate_unreachable_code<'a, FOI>(&mut self, fisa: FOI) -> CodegenResult<()>
     where
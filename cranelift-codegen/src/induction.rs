@@ -0,0 +1,168 @@
+//! Induction variable analysis.
+//!
+//! A basic induction variable is a parameter of a loop header that every back edge of the loop
+//! passes the parameter plus a constant. Within an iteration of the loop, its value is
+//! `init + i * step`, where `init` is the value it had when the loop was entered and `i` counts
+//! the iterations.
+
+use crate::flowgraph::{BasicBlock, ControlFlowGraph};
+use crate::ir::instructions::BranchInfo;
+use crate::ir::types::{I32, I64};
+use crate::ir::{
+    DataFlowGraph, Ebb, Function, Inst, InstructionData, Opcode, Type, Value, ValueDef,
+};
+use crate::loop_analysis::{Loop, LoopAnalysis};
+use alloc::vec::Vec;
+
+/// A basic induction variable of a loop.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct InductionVariable {
+    /// The header parameter holding the value of the variable in the current iteration.
+    pub param: Value,
+    /// The index of `param` in the parameters of the loop header.
+    pub index: usize,
+    /// The non-zero constant added to the variable on every back edge, sign-extended from the
+    /// width of its type.
+    pub step: i64,
+}
+
+/// Get the types of induction variables that are analyzed.
+pub fn is_induction_type(ty: Type) -> bool {
+    ty == I32 || ty == I64
+}
+
+/// Sign-extend the low `bits` bits of `value`.
+pub fn sign_extend(value: i64, bits: u16) -> i64 {
+    let shift = 64 - u32::from(bits);
+    value.wrapping_shl(shift).wrapping_shr(shift)
+}
+
+/// Get the EBB in which `value` is defined.
+pub fn value_ebb(func: &Function, value: Value) -> Option<Ebb> {
+    match func.dfg.value_def(value) {
+        ValueDef::Result(inst, _) => func.layout.inst_ebb(inst),
+        ValueDef::Param(ebb, _) => Some(ebb),
+    }
+}
+
+/// Get the value of `value` if it is defined by an `iconst` instruction.
+pub fn iconst_value(dfg: &DataFlowGraph, value: Value) -> Option<i64> {
+    if let ValueDef::Result(inst, _) = dfg.value_def(dfg.resolve_aliases(value)) {
+        if let InstructionData::UnaryImm {
+            opcode: Opcode::Iconst,
+            imm,
+        } = dfg[inst]
+        {
+            return Some(imm.into());
+        }
+    }
+    None
+}
+
+/// If `value` is `base` plus a constant, get the constant.
+pub fn offset_from(dfg: &DataFlowGraph, value: Value, base: Value) -> Option<i64> {
+    let value = dfg.resolve_aliases(value);
+    if value == base {
+        return Some(0);
+    }
+    let inst = match dfg.value_def(value) {
+        ValueDef::Result(inst, _) => inst,
+        ValueDef::Param(..) => return None,
+    };
+    match dfg[inst] {
+        InstructionData::BinaryImm {
+            opcode: Opcode::IaddImm,
+            arg,
+            imm,
+        } if dfg.resolve_aliases(arg) == base => Some(imm.into()),
+        InstructionData::Binary {
+            opcode: Opcode::Iadd,
+            args,
+        } => {
+            if dfg.resolve_aliases(args[0]) == base {
+                iconst_value(dfg, args[1])
+            } else if dfg.resolve_aliases(args[1]) == base {
+                iconst_value(dfg, args[0])
+            } else {
+                None
+            }
+        }
+        InstructionData::Binary {
+            opcode: Opcode::Isub,
+            args,
+        } if dfg.resolve_aliases(args[0]) == base => {
+            iconst_value(dfg, args[1]).map(i64::wrapping_neg)
+        }
+        _ => None,
+    }
+}
+
+/// Get the arguments that `branch` passes to its destination.
+///
+/// Returns `None` for branches through a jump table, which can't pass arguments.
+pub fn branch_args(dfg: &DataFlowGraph, branch: Inst) -> Option<&[Value]> {
+    match dfg.analyze_branch(branch) {
+        BranchInfo::SingleDest(_, args) => Some(args),
+        BranchInfo::Table(..) | BranchInfo::NotABranch => None,
+    }
+}
+
+/// Split the predecessors of the header of `lp` into the back edges and the entry edges.
+///
+/// Returns `None` if a predecessor branches through a jump table.
+pub fn loop_edges(
+    cfg: &ControlFlowGraph,
+    loop_analysis: &LoopAnalysis,
+    func: &Function,
+    lp: Loop,
+) -> Option<(Vec<Inst>, Vec<Inst>)> {
+    let header = loop_analysis.loop_header(lp);
+    let mut back_edges = Vec::new();
+    let mut entry_edges = Vec::new();
+    for BasicBlock { ebb, inst } in cfg.pred_iter(header) {
+        branch_args(&func.dfg, inst)?;
+        if loop_analysis.is_in_loop(ebb, lp) {
+            back_edges.push(inst);
+        } else {
+            entry_edges.push(inst);
+        }
+    }
+    Some((back_edges, entry_edges))
+}
+
+/// Find the basic induction variables of the loop `lp`.
+pub fn find_induction_variables(
+    func: &Function,
+    cfg: &ControlFlowGraph,
+    loop_analysis: &LoopAnalysis,
+    lp: Loop,
+) -> Vec<InductionVariable> {
+    let mut ivs = Vec::new();
+    let (back_edges, _) = match loop_edges(cfg, loop_analysis, func, lp) {
+        Some(edges) => edges,
+        None => return ivs,
+    };
+    if back_edges.is_empty() {
+        return ivs;
+    }
+
+    let header = loop_analysis.loop_header(lp);
+    for (index, &param) in func.dfg.ebb_params(header).iter().enumerate() {
+        let ty = func.dfg.value_type(param);
+        if !is_induction_type(ty) {
+            continue;
+        }
+        let mut steps = back_edges.iter().map(|&branch| {
+            let arg = branch_args(&func.dfg, branch).unwrap()[index];
+            offset_from(&func.dfg, arg, param).map(|step| sign_extend(step, ty.bits()))
+        });
+        let step = match steps.next() {
+            Some(Some(step)) if step != 0 => step,
+            _ => continue,
+        };
+        if steps.all(|other| other == Some(step)) {
+            ivs.push(InductionVariable { param, index, step });
+        }
+    }
+    ivs
+}
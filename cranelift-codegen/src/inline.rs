@@ -0,0 +1,394 @@
+//! Function inlining.
+//!
+//! The inliner replaces a direct `call` instruction with a copy of the body of the callee:
+//!
+//! - The instructions following the call are moved to a new EBB whose parameters are the results
+//!   of the call.
+//! - The EBBs of the callee are copied after the EBB containing the call, which now ends with a
+//!   jump to the copy of the callee's entry EBB, passing the call arguments.
+//! - The `return` instructions of the callee become jumps to the new EBB.
+//!
+//! The stack slots, global values, heaps, tables, jump tables, signatures and external functions
+//! used by the callee are copied into the caller.
+
+use crate::entity::SecondaryMap;
+use crate::ir::{
+    ArgumentPurpose, Ebb, ExtFuncData, ExternalName, FuncRef, Function, GlobalValue,
+    GlobalValueData, Heap, HeapStyle, Inst, InstBuilder, InstructionData, JumpTable, Opcode,
+    SigRef, StackSlot, StackSlotKind, Table, Value, ValueList,
+};
+use crate::packed_option::PackedOption;
+use crate::timing;
+use alloc::vec::Vec;
+
+/// The functions whose bodies can be inlined into their callers.
+pub trait InlineCallees {
+    /// Get the body of the function called `name`, if it is known.
+    fn get_callee(&self, name: &ExternalName) -> Option<&Function>;
+}
+
+impl InlineCallees for [Function] {
+    fn get_callee(&self, name: &ExternalName) -> Option<&Function> {
+        self.iter().find(|func| func.name == *name)
+    }
+}
+
+/// Inline the direct calls in `func` to the callees known by `callees` that have at most
+/// `threshold` instructions.
+///
+/// The calls in the inlined bodies are left alone, so recursive callees are only inlined once.
+pub fn do_inlining<C>(func: &mut Function, callees: &C, threshold: usize)
+where
+    C: InlineCallees + ?Sized,
+{
+    let _tt = timing::inline();
+
+    let mut calls = Vec::new();
+    for ebb in func.layout.ebbs() {
+        for inst in func.layout.ebb_insts(ebb) {
+            if let InstructionData::Call {
+                opcode: Opcode::Call,
+                func_ref,
+                ..
+            } = func.dfg[inst]
+            {
+                calls.push((inst, func_ref));
+            }
+        }
+    }
+
+    for (inst, func_ref) in calls {
+        let name = &func.dfg.ext_funcs[func_ref].name;
+        if *name == func.name {
+            continue;
+        }
+        if let Some(callee) = callees.get_callee(name) {
+            if num_insts(callee) <= threshold {
+                inline_call(func, inst, callee);
+            }
+        }
+    }
+}
+
+/// Count the instructions in `func`.
+fn num_insts(func: &Function) -> usize {
+    func.layout
+        .ebbs()
+        .map(|ebb| func.layout.ebb_insts(ebb).count())
+        .sum()
+}
+
+/// Can the body of `callee` be copied into another function?
+///
+/// The callee must not be legalized yet, and only use features that don't depend on being a
+/// separate function: it can't have special-purpose parameters, unwind through its calls, or make
+/// tail calls.
+fn can_inline(callee: &Function) -> bool {
+    let sig = &callee.signature;
+    callee.layout.entry_block().is_some()
+        && callee.old_signature.is_none()
+        && callee.personality.is_none()
+        && !sig.is_variadic()
+        && sig
+            .params
+            .iter()
+            .chain(&sig.returns)
+            .all(|p| p.purpose == ArgumentPurpose::Normal && !p.location.is_assigned())
+        && callee
+            .stack_slots
+            .values()
+            .all(|ss| ss.kind == StackSlotKind::ExplicitSlot)
+        && callee.global_values.values().all(|gv| match gv {
+            GlobalValueData::VMContext => false,
+            _ => true,
+        })
+        && callee.layout.ebbs().all(|ebb| {
+            callee.layout.ebb_insts(ebb).all(|inst| {
+                let data = &callee.dfg[inst];
+                let opcode = data.opcode();
+                data.landing_pad().is_none() && !(opcode.is_call() && opcode.is_terminator())
+            })
+        })
+}
+
+/// Replace the `call` instruction `inst` in `func` with a copy of the body of `callee`.
+///
+/// Returns `false`, leaving `func` unchanged, if `callee` can't be inlined or its signature
+/// doesn't match the call.
+pub fn inline_call(func: &mut Function, inst: Inst, callee: &Function) -> bool {
+    if func.dfg[inst].opcode() != Opcode::Call || !can_inline(callee) {
+        return false;
+    }
+
+    // The call must pass arguments and expect results of the types of the callee.
+    let sig = &callee.signature;
+    let args = func.dfg.inst_args(inst).to_vec();
+    let results = func.dfg.inst_results(inst);
+    if args.len() != sig.params.len()
+        || results.len() != sig.returns.len()
+        || args
+            .iter()
+            .zip(&sig.params)
+            .any(|(&arg, param)| func.dfg.value_type(arg) != param.value_type)
+        || results
+            .iter()
+            .zip(&sig.returns)
+            .any(|(&res, ret)| func.dfg.value_type(res) != ret.value_type)
+    {
+        return false;
+    }
+
+    let call_ebb = func.layout.inst_ebb(inst).expect("call not in layout");
+    let srcloc = func.srclocs[inst];
+    let mut map = EntityMap::new(func, callee);
+
+    // Move the instructions after the call to a new EBB receiving the results of the call.
+    let return_ebb = func.dfg.make_ebb();
+    let next_inst = func
+        .layout
+        .next_inst(inst)
+        .expect("call must not end an EBB");
+    func.layout.split_ebb(return_ebb, next_inst);
+    let results = func.dfg.detach_results(inst);
+    for i in 0..results.len(&func.dfg.value_lists) {
+        let result = results.get(i, &func.dfg.value_lists).unwrap();
+        func.dfg.attach_ebb_param(return_ebb, result);
+    }
+
+    // Create the EBBs of the callee with their parameters.
+    let mut last_ebb = call_ebb;
+    for ebb in callee.layout.ebbs() {
+        let new_ebb = func.dfg.make_ebb();
+        for &param in callee.dfg.ebb_params(ebb) {
+            let new_param = func
+                .dfg
+                .append_ebb_param(new_ebb, callee.dfg.value_type(param));
+            map.values[param] = new_param.into();
+        }
+        func.layout.insert_ebb_after(new_ebb, last_ebb);
+        map.ebbs[ebb] = new_ebb.into();
+        last_ebb = new_ebb;
+    }
+    map.copy_jump_tables(func, callee);
+
+    // Copy the instructions, and then the values they use, which may be defined later in the
+    // layout.
+    let mut new_insts = Vec::new();
+    for ebb in callee.layout.ebbs() {
+        for callee_inst in callee.layout.ebb_insts(ebb) {
+            let new_inst = map.copy_inst(func, callee, callee_inst, return_ebb);
+            func.layout.append_inst(new_inst, map.ebbs[ebb].unwrap());
+            func.srclocs[new_inst] = srcloc;
+            new_insts.push(new_inst);
+        }
+    }
+    for new_inst in new_insts {
+        for arg in func.dfg.inst_args_mut(new_inst) {
+            let value = callee.dfg.resolve_aliases(*arg);
+            *arg = map.values[value].expect("value used before it is defined");
+        }
+    }
+
+    // Jump to the inlined entry EBB instead of calling the callee.
+    let entry = callee.layout.entry_block().unwrap();
+    func.dfg.replace(inst).jump(map.ebbs[entry].unwrap(), &args);
+
+    true
+}
+
+/// The entities of the callee and their copies in the caller.
+struct EntityMap {
+    values: SecondaryMap<Value, PackedOption<Value>>,
+    ebbs: SecondaryMap<Ebb, PackedOption<Ebb>>,
+    stack_slots: SecondaryMap<StackSlot, PackedOption<StackSlot>>,
+    global_values: SecondaryMap<GlobalValue, PackedOption<GlobalValue>>,
+    heaps: SecondaryMap<Heap, PackedOption<Heap>>,
+    tables: SecondaryMap<Table, PackedOption<Table>>,
+    jump_tables: SecondaryMap<JumpTable, PackedOption<JumpTable>>,
+    signatures: SecondaryMap<SigRef, PackedOption<SigRef>>,
+    func_refs: SecondaryMap<FuncRef, PackedOption<FuncRef>>,
+}
+
+impl EntityMap {
+    /// Copy the entities of `callee` that don't depend on its EBBs into `func`.
+    fn new(func: &mut Function, callee: &Function) -> Self {
+        let mut map = Self {
+            values: SecondaryMap::new(),
+            ebbs: SecondaryMap::new(),
+            stack_slots: SecondaryMap::new(),
+            global_values: SecondaryMap::new(),
+            heaps: SecondaryMap::new(),
+            tables: SecondaryMap::new(),
+            jump_tables: SecondaryMap::new(),
+            signatures: SecondaryMap::new(),
+            func_refs: SecondaryMap::new(),
+        };
+
+        for (ss, data) in callee.stack_slots.iter() {
+            map.stack_slots[ss] = func.create_stack_slot(data.clone()).into();
+        }
+
+        // Global values can refer to each other in any order, so create them all first.
+        for (gv, data) in callee.global_values.iter() {
+            map.global_values[gv] = func.create_global_value(data.clone()).into();
+        }
+        for gv in callee.global_values.keys() {
+            let new_gv = map.global_values[gv].unwrap();
+            match func.global_values[new_gv] {
+                GlobalValueData::Load { ref mut base, .. }
+                | GlobalValueData::IAddImm { ref mut base, .. } => {
+                    *base = map.global_values[*base].unwrap()
+                }
+                GlobalValueData::VMContext | GlobalValueData::Symbol { .. } => {}
+            }
+        }
+
+        for (heap, data) in callee.heaps.iter() {
+            let mut data = data.clone();
+            data.base = map.global_values[data.base].unwrap();
+            if let HeapStyle::Dynamic { ref mut bound_gv } = data.style {
+                *bound_gv = map.global_values[*bound_gv].unwrap();
+            }
+            map.heaps[heap] = func.create_heap(data).into();
+        }
+
+        for (table, data) in callee.tables.iter() {
+            let mut data = data.clone();
+            data.base_gv = map.global_values[data.base_gv].unwrap();
+            data.bound_gv = map.global_values[data.bound_gv].unwrap();
+            map.tables[table] = func.create_table(data).into();
+        }
+
+        for (sig_ref, sig) in callee.dfg.signatures.iter() {
+            map.signatures[sig_ref] = func.import_signature(sig.clone()).into();
+        }
+
+        for (func_ref, data) in callee.dfg.ext_funcs.iter() {
+            map.func_refs[func_ref] = func
+                .import_function(ExtFuncData {
+                    signature: map.signatures[data.signature].unwrap(),
+                    ..data.clone()
+                })
+                .into();
+        }
+
+        map
+    }
+
+    /// Copy the jump tables of `callee` into `func`, once the EBBs have been created.
+    fn copy_jump_tables(&mut self, func: &mut Function, callee: &Function) {
+        for (jt, data) in callee.jump_tables.iter() {
+            let mut data = data.clone();
+            for ebb in data.iter_mut() {
+                *ebb = self.ebbs[*ebb].unwrap();
+            }
+            self.jump_tables[jt] = func.create_jump_table(data).into();
+        }
+    }
+
+    /// Create a copy of the instruction `inst` of `callee` in `func`, using the copies of the
+    /// entities it refers to.
+    ///
+    /// A `return` becomes a jump to `return_ebb`. The arguments of the new instruction are still
+    /// the values of the callee.
+    fn copy_inst(
+        &mut self,
+        func: &mut Function,
+        callee: &Function,
+        inst: Inst,
+        return_ebb: Ebb,
+    ) -> Inst {
+        let mut data = callee.dfg[inst].clone();
+
+        // The value list belongs to the value list pool of the callee.
+        let args = data
+            .take_value_list()
+            .map(|_| ValueList::from_slice(callee.dfg.inst_args(inst), &mut func.dfg.value_lists));
+        match data.opcode() {
+            Opcode::Return | Opcode::FallthroughReturn => {
+                data = InstructionData::Jump {
+                    opcode: Opcode::Jump,
+                    destination: return_ebb,
+                    args: args.unwrap(),
+                };
+            }
+            _ => {
+                if let Some(args) = args {
+                    data.put_value_list(args);
+                }
+                self.map_entities(func, callee, &mut data);
+            }
+        }
+
+        let new_inst = func.dfg.make_inst(data);
+        func.dfg
+            .make_inst_results(new_inst, callee.dfg.ctrl_typevar(inst));
+        for (&result, &new_result) in callee
+            .dfg
+            .inst_results(inst)
+            .iter()
+            .zip(func.dfg.inst_results(new_inst))
+        {
+            self.values[result] = new_result.into();
+        }
+        new_inst
+    }
+
+    /// Replace the entities of `callee` that `data` refers to with their copies in `func`.
+    fn map_entities(&self, func: &mut Function, callee: &Function, data: &mut InstructionData) {
+        if let Some(destination) = data.branch_destination_mut() {
+            *destination = self.ebbs[*destination].unwrap();
+        }
+
+        match *data {
+            InstructionData::Call {
+                ref mut func_ref, ..
+            }
+            | InstructionData::FuncAddr {
+                ref mut func_ref, ..
+            } => *func_ref = self.func_refs[*func_ref].unwrap(),
+            InstructionData::CallIndirect {
+                ref mut sig_ref, ..
+            } => *sig_ref = self.signatures[*sig_ref].unwrap(),
+            InstructionData::StackLoad {
+                ref mut stack_slot, ..
+            }
+            | InstructionData::StackStore {
+                ref mut stack_slot, ..
+            } => *stack_slot = self.stack_slots[*stack_slot].unwrap(),
+            InstructionData::UnaryGlobalValue {
+                ref mut global_value,
+                ..
+            } => *global_value = self.global_values[*global_value].unwrap(),
+            InstructionData::HeapAddr { ref mut heap, .. } => *heap = self.heaps[*heap].unwrap(),
+            InstructionData::TableAddr { ref mut table, .. } => {
+                *table = self.tables[*table].unwrap()
+            }
+            InstructionData::BranchTable {
+                ref mut table,
+                ref mut destination,
+                ..
+            } => {
+                *table = self.jump_tables[*table].unwrap();
+                *destination = self.ebbs[*destination].unwrap();
+            }
+            InstructionData::IndirectJump { ref mut table, .. }
+            | InstructionData::BranchTableBase { ref mut table, .. }
+            | InstructionData::BranchTableEntry { ref mut table, .. } => {
+                *table = self.jump_tables[*table].unwrap()
+            }
+            InstructionData::UnaryConst {
+                ref mut constant_handle,
+                ..
+            } => {
+                let constant = callee.dfg.constants.get(*constant_handle).clone();
+                *constant_handle = func.dfg.constants.insert(constant);
+            }
+            InstructionData::Shuffle { ref mut mask, .. } => {
+                let immediate = callee.dfg.immediates[*mask].clone();
+                *mask = func.dfg.immediates.push(immediate);
+            }
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,142 @@
+//! Atomic memory operations and memory orderings.
+
+use core::fmt::{self, Display, Formatter};
+use core::str::FromStr;
+#[cfg(feature = "enable-serde")]
+use serde::{Deserialize, Serialize};
+
+/// The read-modify-write operation performed by an `atomic_rmw` instruction.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Hash)]
+#[cfg_attr(feature = "enable-serde", derive(Serialize, Deserialize))]
+pub enum AtomicRmwOp {
+    /// Wrapping addition.
+    Add,
+    /// Wrapping subtraction.
+    Sub,
+    /// Bitwise and.
+    And,
+    /// Bitwise or.
+    Or,
+    /// Bitwise exclusive or.
+    Xor,
+    /// Replace the stored value with the operand.
+    Xchg,
+}
+
+impl Display for AtomicRmwOp {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        use self::AtomicRmwOp::*;
+        f.write_str(match *self {
+            Add => "add",
+            Sub => "sub",
+            And => "and",
+            Or => "or",
+            Xor => "xor",
+            Xchg => "xchg",
+        })
+    }
+}
+
+impl FromStr for AtomicRmwOp {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        use self::AtomicRmwOp::*;
+        match s {
+            "add" => Ok(Add),
+            "sub" => Ok(Sub),
+            "and" => Ok(And),
+            "or" => Ok(Or),
+            "xor" => Ok(Xor),
+            "xchg" => Ok(Xchg),
+            _ => Err(()),
+        }
+    }
+}
+
+/// The memory ordering constraint of an atomic instruction.
+///
+/// These follow the C++11 memory model, minus `consume`.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Hash)]
+#[cfg_attr(feature = "enable-serde", derive(Serialize, Deserialize))]
+pub enum AtomicOrdering {
+    /// No ordering constraints beyond the atomicity of the access itself.
+    Relaxed,
+    /// Later memory accesses can't be reordered before this one.
+    Acquire,
+    /// Earlier memory accesses can't be reordered after this one.
+    Release,
+    /// Both `Acquire` and `Release`.
+    AcqRel,
+    /// `AcqRel`, plus a single total order shared by all sequentially consistent operations.
+    SeqCst,
+}
+
+impl AtomicOrdering {
+    /// Does this ordering prevent later accesses from moving before the operation?
+    pub fn is_acquire(self) -> bool {
+        match self {
+            AtomicOrdering::Acquire | AtomicOrdering::AcqRel | AtomicOrdering::SeqCst => true,
+            AtomicOrdering::Relaxed | AtomicOrdering::Release => false,
+        }
+    }
+
+    /// Does this ordering prevent earlier accesses from moving after the operation?
+    pub fn is_release(self) -> bool {
+        match self {
+            AtomicOrdering::Release | AtomicOrdering::AcqRel | AtomicOrdering::SeqCst => true,
+            AtomicOrdering::Relaxed | AtomicOrdering::Acquire => false,
+        }
+    }
+}
+
+impl Display for AtomicOrdering {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        use self::AtomicOrdering::*;
+        f.write_str(match *self {
+            Relaxed => "relaxed",
+            Acquire => "acquire",
+            Release => "release",
+            AcqRel => "acq_rel",
+            SeqCst => "seq_cst",
+        })
+    }
+}
+
+impl FromStr for AtomicOrdering {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        use self::AtomicOrdering::*;
+        match s {
+            "relaxed" => Ok(Relaxed),
+            "acquire" => Ok(Acquire),
+            "release" => Ok(Release),
+            "acq_rel" => Ok(AcqRel),
+            "seq_cst" => Ok(SeqCst),
+            _ => Err(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::string::ToString;
+
+    #[test]
+    fn display() {
+        use self::AtomicRmwOp::*;
+        for op in &[Add, Sub, And, Or, Xor, Xchg] {
+            assert_eq!(op.to_string().parse(), Ok(*op));
+        }
+        assert_eq!("nand".parse::<AtomicRmwOp>(), Err(()));
+
+        use self::AtomicOrdering::*;
+        for ordering in &[Relaxed, Acquire, Release, AcqRel, SeqCst] {
+            assert_eq!(ordering.to_string().parse(), Ok(*ordering));
+        }
+        assert_eq!(AcqRel.to_string(), "acq_rel");
+        assert_eq!("consume".parse::<AtomicOrdering>(), Err(()));
+    }
+}
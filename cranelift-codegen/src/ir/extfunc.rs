@@ -27,6 +27,10 @@ pub struct Signature {
 
     /// Calling convention.
     pub call_conv: CallConv,
+
+    /// The number of fixed parameters of a variadic function, or `None` if the function isn't
+    /// variadic.
+    fixed_params: Option<usize>,
 }
 
 impl Signature {
@@ -36,6 +40,7 @@ impl Signature {
             params: Vec::new(),
             returns: Vec::new(),
             call_conv,
+            fixed_params: None,
         }
     }
 
@@ -43,9 +48,30 @@ impl Signature {
     pub fn clear(&mut self, call_conv: CallConv) {
         self.params.clear();
         self.returns.clear();
+        self.fixed_params = None;
         self.call_conv = call_conv;
     }
 
+    /// Make this the signature of a variadic function, whose fixed parameters are the ones
+    /// added so far.
+    ///
+    /// The parameters added after this call are the variadic arguments of a call. A call to a
+    /// variadic signature may pass more arguments than it has parameters.
+    pub fn make_variadic(&mut self) {
+        self.fixed_params = Some(self.params.len());
+    }
+
+    /// Is this the signature of a variadic function?
+    pub fn is_variadic(&self) -> bool {
+        self.fixed_params.is_some()
+    }
+
+    /// Get the number of fixed parameters of a variadic function, or `None` if the function
+    /// isn't variadic.
+    pub fn fixed_params(&self) -> Option<usize> {
+        self.fixed_params
+    }
+
     /// Return an object that can display `self` with correct register names.
     pub fn display<'a, R: Into<Option<&'a RegInfo>>>(&'a self, regs: R) -> DisplaySignature<'a> {
         DisplaySignature(self, regs.into())
@@ -93,6 +119,24 @@ impl Signature {
         self.uses_special_param(ArgumentPurpose::StructReturn)
     }
 
+    /// Is this signature legalized, with locations assigned to all its parameters?
+    pub fn params_assigned(&self) -> bool {
+        self.params.iter().all(|p| p.location.is_assigned())
+    }
+
+    /// Get the number of bytes of stack argument area used by the parameters of this legalized
+    /// signature.
+    pub fn stack_args_size(&self) -> u32 {
+        self.params
+            .iter()
+            .filter_map(|p| match p.location {
+                ArgumentLoc::Stack(offset) => Some(offset as u32 + p.value_type.bytes()),
+                _ => None,
+            })
+            .max()
+            .unwrap_or(0)
+    }
+
     /// Does this return more than one normal value? (Pre-struct return
     /// legalization)
     pub fn is_multi_return(&self) -> bool {
@@ -123,7 +167,20 @@ fn write_list(f: &mut fmt::Formatter, args: &[AbiParam], regs: Option<&RegInfo>)
 impl<'a> fmt::Display for DisplaySignature<'a> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "(")?;
-        write_list(f, &self.0.params, self.1)?;
+        match self.0.fixed_params {
+            None => write_list(f, &self.0.params, self.1)?,
+            Some(fixed) => {
+                let (fixed, variadic) = self.0.params.split_at(fixed);
+                write_list(f, fixed, self.1)?;
+                if !fixed.is_empty() {
+                    write!(f, ", ")?;
+                }
+                write!(f, "...")?;
+                for arg in variadic {
+                    write!(f, ", {}", arg.display(self.1))?;
+                }
+            }
+        }
         write!(f, ")")?;
         if !self.0.returns.is_empty() {
             write!(f, " -> ")?;
@@ -317,10 +374,41 @@ pub enum ArgumentPurpose {
     /// This is a pointer to a stack limit. It is used to check the current stack pointer
     /// against. Can only appear once in a signature.
     StackLimit,
+
+    /// A struct passed by value.
+    ///
+    /// The argument value is a pointer to the struct, which is passed the way the calling
+    /// convention passes a C struct with the given layout. This only appears as a parameter.
+    ///
+    /// When the signature is legalized, the struct is replaced by the parts that are passed in
+    /// registers or on the stack. Each part holds the next eightbyte of the struct, and has the
+    /// same `StructArgument` purpose.
+    StructArgument(StructLayout),
+
+    /// A pointer to a copy of a struct passed by value.
+    ///
+    /// Some calling conventions pass structs by reference instead of splitting them into parts.
+    /// The caller makes a copy of the struct and passes its address. This only appears in
+    /// legalized signatures, replacing a `StructArgument`.
+    StructReference(StructLayout),
+
+    /// The number of vector registers used by the arguments of a variadic call.
+    ///
+    /// System V x86-64 passes an upper bound of this number in `%al`, so the callee knows which
+    /// registers to save for `va_arg`. The legalizer adds this argument to the signatures of
+    /// variadic calls, and counts the float and vector arguments passed in registers.
+    VectorCount,
+
+    /// A copy of a variadic float argument in an integer register.
+    ///
+    /// Windows x64 passes variadic float arguments in both the float and the integer register of
+    /// their position. The legalizer adds one `FloatCopy` argument for each variadic float
+    /// argument passed in a register, in order, holding the bits of the float.
+    FloatCopy,
 }
 
-/// Text format names of the `ArgumentPurpose` variants.
-static PURPOSE_NAMES: [&str; 8] = [
+/// Text format names of the `ArgumentPurpose` variants without a struct layout.
+static PURPOSE_NAMES: [&str; 10] = [
     "normal",
     "sret",
     "link",
@@ -329,11 +417,36 @@ static PURPOSE_NAMES: [&str; 8] = [
     "vmctx",
     "sigid",
     "stack_limit",
+    "vcount",
+    "fcopy",
 ];
 
+impl ArgumentPurpose {
+    /// Get the layout of the struct passed by this argument, if it passes one.
+    pub fn struct_layout(self) -> Option<StructLayout> {
+        match self {
+            Self::StructArgument(layout) | Self::StructReference(layout) => Some(layout),
+            _ => None,
+        }
+    }
+}
+
 impl fmt::Display for ArgumentPurpose {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        f.write_str(PURPOSE_NAMES[*self as usize])
+        match *self {
+            Self::Normal => f.write_str(PURPOSE_NAMES[0]),
+            Self::StructReturn => f.write_str(PURPOSE_NAMES[1]),
+            Self::Link => f.write_str(PURPOSE_NAMES[2]),
+            Self::FramePointer => f.write_str(PURPOSE_NAMES[3]),
+            Self::CalleeSaved => f.write_str(PURPOSE_NAMES[4]),
+            Self::VMContext => f.write_str(PURPOSE_NAMES[5]),
+            Self::SignatureId => f.write_str(PURPOSE_NAMES[6]),
+            Self::StackLimit => f.write_str(PURPOSE_NAMES[7]),
+            Self::VectorCount => f.write_str(PURPOSE_NAMES[8]),
+            Self::FloatCopy => f.write_str(PURPOSE_NAMES[9]),
+            Self::StructArgument(layout) => write!(f, "sarg{}", layout),
+            Self::StructReference(layout) => write!(f, "sref{}", layout),
+        }
     }
 }
 
@@ -349,11 +462,60 @@ impl FromStr for ArgumentPurpose {
             "vmctx" => Ok(Self::VMContext),
             "sigid" => Ok(Self::SignatureId),
             "stack_limit" => Ok(Self::StackLimit),
+            "vcount" => Ok(Self::VectorCount),
+            "fcopy" => Ok(Self::FloatCopy),
             _ => Err(()),
         }
     }
 }
 
+/// The memory layout of a struct passed by value.
+///
+/// This is the information calling conventions need to classify a C struct: its size, its
+/// alignment, and which of its eightbytes (the aligned 8-byte chunks of the struct) contain only
+/// floating point fields.
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Hash)]
+pub struct StructLayout {
+    /// Size of the struct in bytes. Must not be zero.
+    pub size: u32,
+    /// Alignment of the struct in bytes. Must be a power of two.
+    pub align: u32,
+    /// Bit mask of the eightbytes of the struct containing only `f32` and `f64` fields. Bit `n`
+    /// describes the bytes `8 * n .. 8 * n + 8`.
+    pub float_eightbytes: u8,
+}
+
+impl StructLayout {
+    /// Create the layout of a struct without floating point fields.
+    pub fn new(size: u32, align: u32) -> Self {
+        Self {
+            size,
+            align,
+            float_eightbytes: 0,
+        }
+    }
+
+    /// Get the number of eightbytes covering the struct.
+    pub fn eightbytes(self) -> u32 {
+        (self.size + 7) / 8
+    }
+
+    /// Does the eightbyte at index `n` contain only floating point fields?
+    pub fn is_float_eightbyte(self, n: u32) -> bool {
+        n < 8 && self.float_eightbytes & (1 << n) != 0
+    }
+}
+
+impl fmt::Display for StructLayout {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "({}, {}", self.size, self.align)?;
+        if self.float_eightbytes != 0 {
+            write!(f, ", {:#x}", self.float_eightbytes)?;
+        }
+        write!(f, ")")
+    }
+}
+
 /// An external function.
 ///
 /// Information about a function that can be called directly with a direct `call` instruction.
@@ -406,6 +568,8 @@ mod tests {
             ArgumentPurpose::VMContext,
             ArgumentPurpose::SignatureId,
             ArgumentPurpose::StackLimit,
+            ArgumentPurpose::VectorCount,
+            ArgumentPurpose::FloatCopy,
         ];
         for (&e, &n) in all_purpose.iter().zip(PURPOSE_NAMES.iter()) {
             assert_eq!(e.to_string(), n);
@@ -413,6 +577,26 @@ mod tests {
         }
     }
 
+    #[test]
+    fn struct_argument() {
+        let layout = StructLayout::new(12, 4);
+        assert_eq!(layout.eightbytes(), 2);
+        assert_eq!(
+            ArgumentPurpose::StructArgument(layout).to_string(),
+            "sarg(12, 4)"
+        );
+        let layout = StructLayout {
+            float_eightbytes: 0b10,
+            ..layout
+        };
+        assert!(!layout.is_float_eightbyte(0));
+        assert!(layout.is_float_eightbyte(1));
+        assert_eq!(
+            ArgumentPurpose::StructReference(layout).to_string(),
+            "sref(12, 4, 0x2)"
+        );
+    }
+
     #[test]
     fn call_conv() {
         for &cc in &[
@@ -422,6 +606,7 @@ mod tests {
             CallConv::WindowsFastcall,
             CallConv::BaldrdashSystemV,
             CallConv::BaldrdashWindows,
+            CallConv::Tail,
         ] {
             assert_eq!(Ok(cc), cc.to_string().parse())
         }
@@ -453,4 +638,20 @@ mod tests {
             "(i32 [24], i32x4 [8]) -> f32, b8 baldrdash_system_v"
         );
     }
+
+    #[test]
+    fn variadic_signatures() {
+        let mut sig = Signature::new(CallConv::SystemV);
+        sig.make_variadic();
+        assert_eq!(sig.to_string(), "(...) system_v");
+        sig.params.push(AbiParam::new(I32));
+        assert_eq!(sig.to_string(), "(..., i32) system_v");
+        sig.make_variadic();
+        assert_eq!(sig.to_string(), "(i32, ...) system_v");
+        sig.params.push(AbiParam::new(F32));
+        assert_eq!(sig.to_string(), "(i32, ..., f32) system_v");
+        assert_eq!(sig.fixed_params(), Some(1));
+        sig.clear(CallConv::SystemV);
+        assert!(!sig.is_variadic());
+    }
 }
@@ -92,6 +92,10 @@ pub struct Function {
     ///
     /// This is used for some calling conventions to track the end of unwind information.
     pub prologue_end: Option<Inst>,
+
+    /// Personality routine consulted by the unwinder when an exception unwinds through one of the
+    /// function's `invoke` instructions.
+    pub personality: Option<FuncRef>,
 }
 
 impl Function {
@@ -115,6 +119,7 @@ impl Function {
             jt_offsets: SecondaryMap::new(),
             srclocs: SecondaryMap::new(),
             prologue_end: None,
+            personality: None,
         }
     }
 
@@ -135,6 +140,7 @@ impl Function {
         self.jt_offsets.clear();
         self.srclocs.clear();
         self.prologue_end = None;
+        self.personality = None;
     }
 
     /// Create a new empty, anonymous function with a Fast calling convention.
@@ -196,6 +196,10 @@ impl InstructionData {
             Self::BranchTable {
                 table, destination, ..
             } => BranchInfo::Table(table, Some(destination)),
+            // The landing pad parameters are supplied by the unwinder, not by the branch.
+            Self::Invoke { destination, .. } | Self::InvokeIndirect { destination, .. } => {
+                BranchInfo::SingleDest(destination, &[])
+            }
             Self::IndirectJump { table, .. } => BranchInfo::Table(table, None),
             _ => {
                 debug_assert!(!self.opcode().is_branch());
@@ -214,7 +218,9 @@ impl InstructionData {
             | Self::Branch { destination, .. }
             | Self::BranchInt { destination, .. }
             | Self::BranchFloat { destination, .. }
-            | Self::BranchIcmp { destination, .. } => Some(destination),
+            | Self::BranchIcmp { destination, .. }
+            | Self::Invoke { destination, .. }
+            | Self::InvokeIndirect { destination, .. } => Some(destination),
             Self::BranchTable { .. } | Self::IndirectJump { .. } => None,
             _ => {
                 debug_assert!(!self.opcode().is_branch());
@@ -248,6 +254,14 @@ impl InstructionData {
             | Self::BranchIcmp {
                 ref mut destination,
                 ..
+            }
+            | Self::Invoke {
+                ref mut destination,
+                ..
+            }
+            | Self::InvokeIndirect {
+                ref mut destination,
+                ..
             } => Some(destination),
             Self::BranchTable { .. } => None,
             _ => {
@@ -257,6 +271,19 @@ impl InstructionData {
         }
     }
 
+    /// Get the landing pad of an `invoke` or `invoke_indirect` instruction.
+    ///
+    /// The landing pad is also the branch destination of the instruction, but unlike other
+    /// destinations, its parameters are not passed by the branch.
+    pub fn landing_pad(&self) -> Option<Ebb> {
+        match *self {
+            Self::Invoke { destination, .. } | Self::InvokeIndirect { destination, .. } => {
+                Some(destination)
+            }
+            _ => None,
+        }
+    }
+
     /// Return information about a call instruction.
     ///
     /// Any instruction that can call another function reveals its call signature here.
@@ -264,9 +291,15 @@ impl InstructionData {
         match *self {
             Self::Call {
                 func_ref, ref args, ..
+            }
+            | Self::Invoke {
+                func_ref, ref args, ..
             } => CallInfo::Direct(func_ref, args.as_slice(pool)),
             Self::CallIndirect {
                 sig_ref, ref args, ..
+            }
+            | Self::InvokeIndirect {
+                sig_ref, ref args, ..
             } => CallInfo::Indirect(sig_ref, &args.as_slice(pool)[1..]),
             _ => {
                 debug_assert!(!self.opcode().is_call());
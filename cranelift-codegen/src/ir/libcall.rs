@@ -1,5 +1,6 @@
 //! Naming well-known routines in the runtime library.
 
+use crate::ir::condcodes::{FloatCC, IntCC};
 use crate::ir::{
     types, AbiParam, ArgumentPurpose, ExtFuncData, ExternalName, FuncRef, Function, Inst, Opcode,
     Signature, Type,
@@ -46,6 +47,121 @@ pub enum LibCall {
     Memset,
     /// libc.memmove
     Memmove,
+
+    /// udiv.i128
+    UdivI128,
+    /// sdiv.i128
+    SdivI128,
+    /// urem.i128
+    UremI128,
+    /// srem.i128
+    SremI128,
+    /// fcvt_from_sint.f32 of an i128
+    FcvtFromSintF32I128,
+    /// fcvt_from_sint.f64 of an i128
+    FcvtFromSintF64I128,
+    /// fcvt_from_uint.f32 of an i128
+    FcvtFromUintF32I128,
+    /// fcvt_from_uint.f64 of an i128
+    FcvtFromUintF64I128,
+    /// fcvt_to_sint.i128 of an f32, which is only called with values in range
+    FcvtToSintI128F32,
+    /// fcvt_to_sint.i128 of an f64, which is only called with values in range
+    FcvtToSintI128F64,
+    /// fcvt_to_uint.i128 of an f32, which is only called with values in range
+    FcvtToUintI128F32,
+    /// fcvt_to_uint.i128 of an f64, which is only called with values in range
+    FcvtToUintI128F64,
+
+    // Soft-float routines, for targets without floating point instructions.
+    /// fadd.f32
+    FaddF32,
+    /// fadd.f64
+    FaddF64,
+    /// fsub.f32
+    FsubF32,
+    /// fsub.f64
+    FsubF64,
+    /// fmul.f32
+    FmulF32,
+    /// fmul.f64
+    FmulF64,
+    /// fdiv.f32
+    FdivF32,
+    /// fdiv.f64
+    FdivF64,
+    /// sqrt.f32
+    SqrtF32,
+    /// sqrt.f64
+    SqrtF64,
+    /// fma.f32
+    FmaF32,
+    /// fma.f64
+    FmaF64,
+    /// fpromote.f64 of an f32
+    FpromoteF64F32,
+    /// fdemote.f32 of an f64
+    FdemoteF32F64,
+    /// fcvt_from_sint.f32 of an i32
+    FcvtFromSintF32I32,
+    /// fcvt_from_sint.f32 of an i64
+    FcvtFromSintF32I64,
+    /// fcvt_from_sint.f64 of an i32
+    FcvtFromSintF64I32,
+    /// fcvt_from_sint.f64 of an i64
+    FcvtFromSintF64I64,
+    /// fcvt_from_uint.f32 of an i32
+    FcvtFromUintF32I32,
+    /// fcvt_from_uint.f32 of an i64
+    FcvtFromUintF32I64,
+    /// fcvt_from_uint.f64 of an i32
+    FcvtFromUintF64I32,
+    /// fcvt_from_uint.f64 of an i64
+    FcvtFromUintF64I64,
+    /// fcvt_to_sint.i32 of an f32, which is only called with values in range
+    FcvtToSintI32F32,
+    /// fcvt_to_sint.i32 of an f64, which is only called with values in range
+    FcvtToSintI32F64,
+    /// fcvt_to_sint.i64 of an f32, which is only called with values in range
+    FcvtToSintI64F32,
+    /// fcvt_to_sint.i64 of an f64, which is only called with values in range
+    FcvtToSintI64F64,
+    /// fcvt_to_uint.i32 of an f32, which is only called with values in range
+    FcvtToUintI32F32,
+    /// fcvt_to_uint.i32 of an f64, which is only called with values in range
+    FcvtToUintI32F64,
+    /// fcvt_to_uint.i64 of an f32, which is only called with values in range
+    FcvtToUintI64F32,
+    /// fcvt_to_uint.i64 of an f64, which is only called with values in range
+    FcvtToUintI64F64,
+    // The soft-float comparisons return an `i32` to compare with zero.
+    /// fcmp uno of two f32s, nonzero when true
+    FcmpUnordF32,
+    /// fcmp uno of two f64s, nonzero when true
+    FcmpUnordF64,
+    /// fcmp eq of two f32s, zero when true
+    FcmpEqF32,
+    /// fcmp eq of two f64s, zero when true
+    FcmpEqF64,
+    /// fcmp lt of two f32s, negative when true and positive for NaN
+    FcmpLtF32,
+    /// fcmp lt of two f64s, negative when true and positive for NaN
+    FcmpLtF64,
+    /// fcmp le of two f32s, non-positive when true and positive for NaN
+    FcmpLeF32,
+    /// fcmp le of two f64s, non-positive when true and positive for NaN
+    FcmpLeF64,
+    /// fcmp gt of two f32s, positive when true and negative for NaN
+    FcmpGtF32,
+    /// fcmp gt of two f64s, positive when true and negative for NaN
+    FcmpGtF64,
+    /// fcmp ge of two f32s, non-negative when true and negative for NaN
+    FcmpGeF32,
+    /// fcmp ge of two f64s, non-negative when true and negative for NaN
+    FcmpGeF64,
+
+    /// Elf __tls_get_addr
+    ElfTlsGetAddr,
 }
 
 impl fmt::Display for LibCall {
@@ -71,6 +187,64 @@ impl FromStr for LibCall {
             "Memcpy" => Ok(Self::Memcpy),
             "Memset" => Ok(Self::Memset),
             "Memmove" => Ok(Self::Memmove),
+
+            "UdivI128" => Ok(Self::UdivI128),
+            "SdivI128" => Ok(Self::SdivI128),
+            "UremI128" => Ok(Self::UremI128),
+            "SremI128" => Ok(Self::SremI128),
+            "FcvtFromSintF32I128" => Ok(Self::FcvtFromSintF32I128),
+            "FcvtFromSintF64I128" => Ok(Self::FcvtFromSintF64I128),
+            "FcvtFromUintF32I128" => Ok(Self::FcvtFromUintF32I128),
+            "FcvtFromUintF64I128" => Ok(Self::FcvtFromUintF64I128),
+            "FcvtToSintI128F32" => Ok(Self::FcvtToSintI128F32),
+            "FcvtToSintI128F64" => Ok(Self::FcvtToSintI128F64),
+            "FcvtToUintI128F32" => Ok(Self::FcvtToUintI128F32),
+            "FcvtToUintI128F64" => Ok(Self::FcvtToUintI128F64),
+
+            "FaddF32" => Ok(Self::FaddF32),
+            "FaddF64" => Ok(Self::FaddF64),
+            "FsubF32" => Ok(Self::FsubF32),
+            "FsubF64" => Ok(Self::FsubF64),
+            "FmulF32" => Ok(Self::FmulF32),
+            "FmulF64" => Ok(Self::FmulF64),
+            "FdivF32" => Ok(Self::FdivF32),
+            "FdivF64" => Ok(Self::FdivF64),
+            "SqrtF32" => Ok(Self::SqrtF32),
+            "SqrtF64" => Ok(Self::SqrtF64),
+            "FmaF32" => Ok(Self::FmaF32),
+            "FmaF64" => Ok(Self::FmaF64),
+            "FpromoteF64F32" => Ok(Self::FpromoteF64F32),
+            "FdemoteF32F64" => Ok(Self::FdemoteF32F64),
+            "FcvtFromSintF32I32" => Ok(Self::FcvtFromSintF32I32),
+            "FcvtFromSintF32I64" => Ok(Self::FcvtFromSintF32I64),
+            "FcvtFromSintF64I32" => Ok(Self::FcvtFromSintF64I32),
+            "FcvtFromSintF64I64" => Ok(Self::FcvtFromSintF64I64),
+            "FcvtFromUintF32I32" => Ok(Self::FcvtFromUintF32I32),
+            "FcvtFromUintF32I64" => Ok(Self::FcvtFromUintF32I64),
+            "FcvtFromUintF64I32" => Ok(Self::FcvtFromUintF64I32),
+            "FcvtFromUintF64I64" => Ok(Self::FcvtFromUintF64I64),
+            "FcvtToSintI32F32" => Ok(Self::FcvtToSintI32F32),
+            "FcvtToSintI32F64" => Ok(Self::FcvtToSintI32F64),
+            "FcvtToSintI64F32" => Ok(Self::FcvtToSintI64F32),
+            "FcvtToSintI64F64" => Ok(Self::FcvtToSintI64F64),
+            "FcvtToUintI32F32" => Ok(Self::FcvtToUintI32F32),
+            "FcvtToUintI32F64" => Ok(Self::FcvtToUintI32F64),
+            "FcvtToUintI64F32" => Ok(Self::FcvtToUintI64F32),
+            "FcvtToUintI64F64" => Ok(Self::FcvtToUintI64F64),
+            "FcmpUnordF32" => Ok(Self::FcmpUnordF32),
+            "FcmpUnordF64" => Ok(Self::FcmpUnordF64),
+            "FcmpEqF32" => Ok(Self::FcmpEqF32),
+            "FcmpEqF64" => Ok(Self::FcmpEqF64),
+            "FcmpLtF32" => Ok(Self::FcmpLtF32),
+            "FcmpLtF64" => Ok(Self::FcmpLtF64),
+            "FcmpLeF32" => Ok(Self::FcmpLeF32),
+            "FcmpLeF64" => Ok(Self::FcmpLeF64),
+            "FcmpGtF32" => Ok(Self::FcmpGtF32),
+            "FcmpGtF64" => Ok(Self::FcmpGtF64),
+            "FcmpGeF32" => Ok(Self::FcmpGeF32),
+            "FcmpGeF64" => Ok(Self::FcmpGeF64),
+
+            "ElfTlsGetAddr" => Ok(Self::ElfTlsGetAddr),
             _ => Err(()),
         }
     }
@@ -80,26 +254,165 @@ impl LibCall {
     /// Get the well-known library call name to use as a replacement for an instruction with the
     /// given opcode and controlling type variable.
     ///
+    /// The type of the first argument, `arg_type`, only matters for conversions.
+    ///
     /// Returns `None` if no well-known library routine name exists for that instruction.
-    pub fn for_inst(opcode: Opcode, ctrl_type: Type) -> Option<Self> {
-        Some(match ctrl_type {
-            types::F32 => match opcode {
+    pub fn for_inst(opcode: Opcode, ctrl_type: Type, arg_type: Type) -> Option<Self> {
+        Some(match (ctrl_type, arg_type) {
+            (types::F32, types::I32) => match opcode {
+                Opcode::FcvtFromSint => Self::FcvtFromSintF32I32,
+                Opcode::FcvtFromUint => Self::FcvtFromUintF32I32,
+                _ => return None,
+            },
+            (types::F32, types::I64) => match opcode {
+                Opcode::FcvtFromSint => Self::FcvtFromSintF32I64,
+                Opcode::FcvtFromUint => Self::FcvtFromUintF32I64,
+                _ => return None,
+            },
+            (types::F32, types::I128) => match opcode {
+                Opcode::FcvtFromSint => Self::FcvtFromSintF32I128,
+                Opcode::FcvtFromUint => Self::FcvtFromUintF32I128,
+                _ => return None,
+            },
+            (types::F64, types::I32) => match opcode {
+                Opcode::FcvtFromSint => Self::FcvtFromSintF64I32,
+                Opcode::FcvtFromUint => Self::FcvtFromUintF64I32,
+                _ => return None,
+            },
+            (types::F64, types::I64) => match opcode {
+                Opcode::FcvtFromSint => Self::FcvtFromSintF64I64,
+                Opcode::FcvtFromUint => Self::FcvtFromUintF64I64,
+                _ => return None,
+            },
+            (types::F64, types::I128) => match opcode {
+                Opcode::FcvtFromSint => Self::FcvtFromSintF64I128,
+                Opcode::FcvtFromUint => Self::FcvtFromUintF64I128,
+                _ => return None,
+            },
+            (types::F32, types::F64) => match opcode {
+                Opcode::Fdemote => Self::FdemoteF32F64,
+                _ => return None,
+            },
+            (types::F64, types::F32) => match opcode {
+                Opcode::Fpromote => Self::FpromoteF64F32,
+                _ => return None,
+            },
+            (types::F32, _) => match opcode {
                 Opcode::Ceil => Self::CeilF32,
                 Opcode::Floor => Self::FloorF32,
                 Opcode::Trunc => Self::TruncF32,
                 Opcode::Nearest => Self::NearestF32,
+                Opcode::Fadd => Self::FaddF32,
+                Opcode::Fsub => Self::FsubF32,
+                Opcode::Fmul => Self::FmulF32,
+                Opcode::Fdiv => Self::FdivF32,
+                Opcode::Sqrt => Self::SqrtF32,
+                Opcode::Fma => Self::FmaF32,
                 _ => return None,
             },
-            types::F64 => match opcode {
+            (types::F64, _) => match opcode {
                 Opcode::Ceil => Self::CeilF64,
                 Opcode::Floor => Self::FloorF64,
                 Opcode::Trunc => Self::TruncF64,
                 Opcode::Nearest => Self::NearestF64,
+                Opcode::Fadd => Self::FaddF64,
+                Opcode::Fsub => Self::FsubF64,
+                Opcode::Fmul => Self::FmulF64,
+                Opcode::Fdiv => Self::FdivF64,
+                Opcode::Sqrt => Self::SqrtF64,
+                Opcode::Fma => Self::FmaF64,
+                _ => return None,
+            },
+            // The saturating conversions are guarded like the trapping ones, so they use the same
+            // routines.
+            (types::I32, types::F32) => match opcode {
+                Opcode::FcvtToSint | Opcode::FcvtToSintSat => Self::FcvtToSintI32F32,
+                Opcode::FcvtToUint | Opcode::FcvtToUintSat => Self::FcvtToUintI32F32,
+                _ => return None,
+            },
+            (types::I32, types::F64) => match opcode {
+                Opcode::FcvtToSint | Opcode::FcvtToSintSat => Self::FcvtToSintI32F64,
+                Opcode::FcvtToUint | Opcode::FcvtToUintSat => Self::FcvtToUintI32F64,
+                _ => return None,
+            },
+            (types::I64, types::F32) => match opcode {
+                Opcode::FcvtToSint | Opcode::FcvtToSintSat => Self::FcvtToSintI64F32,
+                Opcode::FcvtToUint | Opcode::FcvtToUintSat => Self::FcvtToUintI64F32,
+                _ => return None,
+            },
+            (types::I64, types::F64) => match opcode {
+                Opcode::FcvtToSint | Opcode::FcvtToSintSat => Self::FcvtToSintI64F64,
+                Opcode::FcvtToUint | Opcode::FcvtToUintSat => Self::FcvtToUintI64F64,
+                _ => return None,
+            },
+            (types::I128, types::F32) => match opcode {
+                Opcode::FcvtToSint | Opcode::FcvtToSintSat => Self::FcvtToSintI128F32,
+                Opcode::FcvtToUint | Opcode::FcvtToUintSat => Self::FcvtToUintI128F32,
+                _ => return None,
+            },
+            (types::I128, types::F64) => match opcode {
+                Opcode::FcvtToSint | Opcode::FcvtToSintSat => Self::FcvtToSintI128F64,
+                Opcode::FcvtToUint | Opcode::FcvtToUintSat => Self::FcvtToUintI128F64,
+                _ => return None,
+            },
+            (types::I128, _) => match opcode {
+                Opcode::Udiv => Self::UdivI128,
+                Opcode::Sdiv => Self::SdivI128,
+                Opcode::Urem => Self::UremI128,
+                Opcode::Srem => Self::SremI128,
                 _ => return None,
             },
             _ => return None,
         })
     }
+
+    /// Get the library routine comparing two floats of type `ty`, for the `fcmp` conditions that
+    /// can be answered by comparing its result with zero.
+    ///
+    /// The other conditions combine two of these routines.
+    pub fn for_fcmp(cond: FloatCC, ty: Type) -> Option<(Self, IntCC)> {
+        let (f32_call, f64_call, int_cond) = match cond {
+            FloatCC::Ordered => (Self::FcmpUnordF32, Self::FcmpUnordF64, IntCC::Equal),
+            FloatCC::Unordered => (Self::FcmpUnordF32, Self::FcmpUnordF64, IntCC::NotEqual),
+            FloatCC::Equal => (Self::FcmpEqF32, Self::FcmpEqF64, IntCC::Equal),
+            FloatCC::NotEqual => (Self::FcmpEqF32, Self::FcmpEqF64, IntCC::NotEqual),
+            FloatCC::LessThan => (Self::FcmpLtF32, Self::FcmpLtF64, IntCC::SignedLessThan),
+            FloatCC::LessThanOrEqual => (
+                Self::FcmpLeF32,
+                Self::FcmpLeF64,
+                IntCC::SignedLessThanOrEqual,
+            ),
+            FloatCC::GreaterThan => (Self::FcmpGtF32, Self::FcmpGtF64, IntCC::SignedGreaterThan),
+            FloatCC::GreaterThanOrEqual => (
+                Self::FcmpGeF32,
+                Self::FcmpGeF64,
+                IntCC::SignedGreaterThanOrEqual,
+            ),
+            // The unordered conditions are the inverses of the ordered ones.
+            FloatCC::UnorderedOrLessThan => {
+                (Self::FcmpGeF32, Self::FcmpGeF64, IntCC::SignedLessThan)
+            }
+            FloatCC::UnorderedOrLessThanOrEqual => (
+                Self::FcmpGtF32,
+                Self::FcmpGtF64,
+                IntCC::SignedLessThanOrEqual,
+            ),
+            FloatCC::UnorderedOrGreaterThan => {
+                (Self::FcmpLeF32, Self::FcmpLeF64, IntCC::SignedGreaterThan)
+            }
+            FloatCC::UnorderedOrGreaterThanOrEqual => (
+                Self::FcmpLtF32,
+                Self::FcmpLtF64,
+                IntCC::SignedGreaterThanOrEqual,
+            ),
+            FloatCC::OrderedNotEqual | FloatCC::UnorderedOrEqual => return None,
+        };
+        match ty {
+            types::F32 => Some((f32_call, int_cond)),
+            types::F64 => Some((f64_call, int_cond)),
+            _ => None,
+        }
+    }
 }
 
 /// Get a function reference for `libcall` in `func`, following the signature
@@ -117,6 +430,21 @@ pub fn get_libcall_funcref(
         .unwrap_or_else(|| make_funcref_for_inst(libcall, call_conv, func, inst, isa))
 }
 
+/// Get a function reference for the float comparison `libcall` in `func`, which takes two
+/// operands of type `ty` and returns an `i32`.
+///
+/// If there is an existing reference, use it, otherwise make a new one.
+pub fn get_fcmp_funcref(
+    libcall: LibCall,
+    call_conv: CallConv,
+    func: &mut Function,
+    ty: Type,
+    isa: &dyn TargetIsa,
+) -> FuncRef {
+    find_funcref(libcall, func)
+        .unwrap_or_else(|| make_funcref_for_fcmp(libcall, call_conv, func, ty, isa))
+}
+
 /// Get a function reference for the probestack function in `func`.
 ///
 /// If there is an existing reference, use it, otherwise make a new one.
@@ -163,6 +491,30 @@ fn make_funcref_for_probestack(
     make_funcref(LibCall::Probestack, func, sig, isa)
 }
 
+/// Create a funcref for a float comparison `libcall`.
+fn make_funcref_for_fcmp(
+    libcall: LibCall,
+    call_conv: CallConv,
+    func: &mut Function,
+    ty: Type,
+    isa: &dyn TargetIsa,
+) -> FuncRef {
+    let mut sig = Signature::new(call_conv);
+    sig.params.push(AbiParam::new(ty));
+    sig.params.push(AbiParam::new(ty));
+    sig.returns.push(AbiParam::new(types::I32));
+
+    if call_conv.extends_baldrdash() {
+        // Adds the special VMContext parameter to the signature.
+        sig.params.push(AbiParam::special(
+            isa.pointer_type(),
+            ArgumentPurpose::VMContext,
+        ));
+    }
+
+    make_funcref(libcall, func, sig, isa)
+}
+
 /// Create a funcref for `libcall` with a signature matching `inst`.
 fn make_funcref_for_inst(
     libcall: LibCall,
@@ -220,5 +572,7 @@ mod tests {
     #[test]
     fn parsing() {
         assert_eq!("FloorF32".parse(), Ok(LibCall::FloorF32));
+        assert_eq!("UdivI128".parse(), Ok(LibCall::UdivI128));
+        assert_eq!("FcvtToSintI64F32".parse(), Ok(LibCall::FcvtToSintI64F32));
     }
 }
@@ -1,21 +1,68 @@
 //! Memory operation flags.
 
 use core::fmt;
+#[cfg(feature = "enable-serde")]
+use serde::{Deserialize, Serialize};
 
 enum FlagBit {
     Notrap,
     Aligned,
     Readonly,
+    Heap,
+    Table,
+    Vmctx,
+    Stack,
 }
 
-const NAMES: [&str; 3] = ["notrap", "aligned", "readonly"];
+const NAMES: [&str; 7] = [
+    "notrap", "aligned", "readonly", "heap", "table", "vmctx", "stack",
+];
+
+/// The bits of the region flags.
+const REGION_MASK: u8 = 0b111_1000;
+
+/// A memory region, declared by the flags of a load or store.
+///
+/// Memory in different regions never overlaps, so accesses to different regions never alias.
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
+pub enum MemRegion {
+    /// A WebAssembly linear memory, or another region described by a `heap` declaration.
+    Heap,
+    /// The elements of a table, as described by a `table` declaration.
+    Table,
+    /// The VM context structure, and the runtime data structures it points to.
+    Vmctx,
+    /// The stack frame of the current function.
+    Stack,
+}
+
+impl MemRegion {
+    fn flag_bit(self) -> FlagBit {
+        match self {
+            MemRegion::Heap => FlagBit::Heap,
+            MemRegion::Table => FlagBit::Table,
+            MemRegion::Vmctx => FlagBit::Vmctx,
+            MemRegion::Stack => FlagBit::Stack,
+        }
+    }
+}
+
+impl fmt::Display for MemRegion {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(NAMES[self.flag_bit() as usize])
+    }
+}
 
 /// Flags for memory operations like load/store.
 ///
 /// Each of these flags introduce a limited form of undefined behavior. The flags each enable
 /// certain optimizations that need to make additional assumptions. Generally, the semantics of a
 /// program does not change when a flag is removed, but adding a flag will.
+///
+/// The region flags `heap`, `table`, `vmctx` and `stack` declare which region of memory is
+/// accessed. At most one of them can be set.
 #[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
+#[cfg_attr(feature = "enable-serde", derive(Serialize, Deserialize))]
 pub struct MemFlags {
     bits: u8,
 }
@@ -103,6 +150,37 @@ impl MemFlags {
     pub fn set_readonly(&mut self) {
         self.set(FlagBit::Readonly)
     }
+
+    /// Get the memory region declared by the region flags.
+    ///
+    /// Accesses with a region only access memory in that region, and the behavior is undefined
+    /// otherwise. Returns `None` if no region flag is set, which means that any memory may be
+    /// accessed, or if more than one is set, which the verifier rejects.
+    pub fn region(self) -> Option<MemRegion> {
+        if self.has_multiple_regions() {
+            return None;
+        }
+        [
+            MemRegion::Heap,
+            MemRegion::Table,
+            MemRegion::Vmctx,
+            MemRegion::Stack,
+        ]
+        .iter()
+        .cloned()
+        .find(|&region| self.read(region.flag_bit()))
+    }
+
+    /// Set the region flag for `region`, clearing any other region flag.
+    pub fn set_region(&mut self, region: MemRegion) {
+        self.bits &= !REGION_MASK;
+        self.set(region.flag_bit())
+    }
+
+    /// Test if more than one region flag is set.
+    pub fn has_multiple_regions(self) -> bool {
+        (self.bits & REGION_MASK).count_ones() > 1
+    }
 }
 
 impl fmt::Display for MemFlags {
@@ -115,3 +193,28 @@ impl fmt::Display for MemFlags {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::string::ToString;
+
+    #[test]
+    fn regions() {
+        let mut flags = MemFlags::trusted();
+        assert_eq!(flags.region(), None);
+
+        flags.set_region(MemRegion::Table);
+        assert_eq!(flags.region(), Some(MemRegion::Table));
+        assert_eq!(flags.to_string(), " notrap aligned table");
+
+        flags.set_region(MemRegion::Vmctx);
+        assert_eq!(flags.region(), Some(MemRegion::Vmctx));
+        assert!(!flags.has_multiple_regions());
+
+        assert!(flags.set_by_name("stack"));
+        assert!(flags.has_multiple_regions());
+        assert_eq!(flags.region(), None);
+        assert_eq!(flags.to_string(), " notrap aligned vmctx stack");
+    }
+}
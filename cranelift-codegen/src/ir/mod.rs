@@ -1,5 +1,6 @@
 //! Representation of Cranelift IR functions.
 
+mod atomic;
 mod builder;
 pub mod constant;
 pub mod dfg;
@@ -26,6 +27,7 @@ mod valueloc;
 #[cfg(feature = "enable-serde")]
 use serde::{Deserialize, Serialize};
 
+pub use crate::ir::atomic::{AtomicOrdering, AtomicRmwOp};
 pub use crate::ir::builder::{
     InsertBuilder, InstBuilder, InstBuilderBase, InstInserterBase, ReplaceBuilder,
 };
@@ -36,7 +38,7 @@ pub use crate::ir::entities::{
     Table, Value,
 };
 pub use crate::ir::extfunc::{
-    AbiParam, ArgumentExtension, ArgumentPurpose, ExtFuncData, Signature,
+    AbiParam, ArgumentExtension, ArgumentPurpose, ExtFuncData, Signature, StructLayout,
 };
 pub use crate::ir::extname::ExternalName;
 pub use crate::ir::function::{DisplayFunctionAnnotations, Function};
@@ -47,8 +49,10 @@ pub use crate::ir::instructions::{
 };
 pub use crate::ir::jumptable::JumpTableData;
 pub use crate::ir::layout::Layout;
-pub use crate::ir::libcall::{get_libcall_funcref, get_probestack_funcref, LibCall};
-pub use crate::ir::memflags::MemFlags;
+pub use crate::ir::libcall::{
+    get_fcmp_funcref, get_libcall_funcref, get_probestack_funcref, LibCall,
+};
+pub use crate::ir::memflags::{MemFlags, MemRegion};
 pub use crate::ir::progpoint::{ExpandedProgramPoint, ProgramOrder, ProgramPoint};
 pub use crate::ir::sourceloc::SourceLoc;
 pub use crate::ir::stackslot::{StackSlotData, StackSlotKind, StackSlots};
@@ -1,35 +1,71 @@
-//! ARM ABI implementation.
-//! This is from the RISC-V target and will need to be updated for ARM32.
+//! ARM 32-bit ABI implementation.
+//!
+//! This module implements the AAPCS procedure call standard. Integer arguments are passed in
+//! `r0`-`r3`, with 64-bit values taking an even-odd register pair, and the rest goes on the
+//! stack. With a hard-float environment, floating point arguments are passed in VFP registers;
+//! otherwise they are passed like integers of the same size.
+//!
+//! The generated frames keep a frame pointer in `r11`, pointing at the saved frame pointer of
+//! the caller, with the return address right above it.
 
-use super::registers::{D, GPR, Q, S};
+use super::registers::{D, GPR, Q, RU, S};
 use crate::abi::{legalize_args, ArgAction, ArgAssigner, ValueConversion};
-use crate::ir::{self, AbiParam, ArgumentExtension, ArgumentLoc, Type};
-use crate::isa::RegClass;
+use crate::cursor::{Cursor, CursorPosition, EncCursor};
+use crate::ir::immediates::Imm64;
+use crate::ir::stackslot::StackOffset;
+use crate::ir::{
+    self, AbiParam, ArgumentExtension, ArgumentLoc, ArgumentPurpose, InstBuilder, Type, ValueLoc,
+};
+use crate::isa::{CallConv, RegClass, RegUnit, TargetIsa};
 use crate::regalloc::RegisterSet;
+use crate::result::{CodegenError, CodegenResult};
+use crate::stack_layout::layout_stack;
 use alloc::borrow::Cow;
+use alloc::vec::Vec;
 use core::i32;
-use target_lexicon::Triple;
+use target_lexicon::{Environment, Triple};
+
+/// Number of general purpose registers used for passing arguments and return values.
+const GPR_ARG_REGS: u32 = 4;
+
+/// Number of single precision VFP registers used for passing arguments and return values.
+const VFP_ARG_REGS: u32 = 16;
+
+/// Callee-saved general purpose registers, excluding the frame pointer.
+static CALLEE_SAVED_GPRS: [RU; 7] = [RU::r4, RU::r5, RU::r6, RU::r7, RU::r8, RU::r9, RU::r10];
 
 struct Args {
-    pointer_bits: u8,
-    pointer_bytes: u8,
-    pointer_type: Type,
-    regs: u32,
-    reg_limit: u32,
+    hard_float: bool,
+    gpr_used: u32,
+    /// Bit mask of the `s0`-`s15` registers in use. Single precision values can back-fill a
+    /// register left free by the alignment of a double precision one.
+    vfp_used: u32,
     offset: u32,
 }
 
 impl Args {
-    fn new(bits: u8) -> Self {
+    fn new(triple: &Triple) -> Self {
+        let hard_float = match triple.environment {
+            Environment::Eabihf | Environment::Gnueabihf | Environment::Musleabihf => true,
+            _ => false,
+        };
         Self {
-            pointer_bits: bits,
-            pointer_bytes: bits / 8,
-            pointer_type: Type::int(u16::from(bits)).unwrap(),
-            regs: 0,
-            reg_limit: 8,
+            hard_float,
+            gpr_used: 0,
+            vfp_used: 0,
             offset: 0,
         }
     }
+
+    /// Allocate `count` consecutive single precision registers, aligned to `count`.
+    fn alloc_vfp(&mut self, count: u32) -> Option<u32> {
+        let mask = (1 << count) - 1;
+        let index = (0..VFP_ARG_REGS)
+            .step_by(count as usize)
+            .find(|&i| self.vfp_used & (mask << i) == 0)?;
+        self.vfp_used |= mask << index;
+        Some(index)
+    }
 }
 
 impl ArgAssigner for Args {
@@ -40,69 +76,276 @@ impl ArgAssigner for Args {
 
         let ty = arg.value_type;
 
-        // Check for a legal type.
-        // SIMD instructions are currently no implemented, so break down vectors
+        if arg.purpose.struct_layout().is_some() {
+            unreachable!("the verifier rejects struct arguments on arm32");
+        }
+
+        // SIMD types are not supported yet, so break all vectors down.
         if ty.is_vector() {
             return ValueConversion::VectorSplit.into();
         }
 
-        // Large integers and booleans are broken down to fit in a register.
-        if !ty.is_float() && ty.bits() > u16::from(self.pointer_bits) {
-            // Align registers and stack to a multiple of two pointers.
-            self.regs = align(self.regs, 2);
-            self.offset = align(self.offset, 2 * u32::from(self.pointer_bytes));
+        if ty.is_float() {
+            if !self.hard_float {
+                // With the base standard, floats are passed in the integer registers.
+                return ValueConversion::IntBits.into();
+            }
+
+            let count = u32::from(ty.bytes()) / 4;
+            if let Some(index) = self.alloc_vfp(count) {
+                let reg = if count == 1 {
+                    S.unit(index as usize)
+                } else {
+                    D.unit(index as usize / 2)
+                };
+                return ArgumentLoc::Reg(reg).into();
+            }
+
+            // Once a VFP argument goes on the stack, all the following ones do too.
+            self.vfp_used = (1 << VFP_ARG_REGS) - 1;
+            self.offset = align(self.offset, u32::from(ty.bytes()));
+            let loc = ArgumentLoc::Stack(self.offset as i32);
+            self.offset += u32::from(ty.bytes());
+            debug_assert!(self.offset <= i32::MAX as u32);
+            return loc.into();
+        }
+
+        // Large integers and booleans are broken down to fit in a register. A 64-bit value
+        // starts at an even-numbered register or an 8-byte aligned stack slot.
+        if ty.bits() > 32 {
+            self.gpr_used = align(self.gpr_used, 2);
+            self.offset = align(self.offset, 8);
             return ValueConversion::IntSplit.into();
         }
 
-        // Small integers are extended to the size of a pointer register.
-        if ty.is_int() && ty.bits() < u16::from(self.pointer_bits) {
+        // Small integers are extended to the size of a register.
+        if ty.is_int() && ty.bits() < 32 {
             match arg.extension {
                 ArgumentExtension::None => {}
-                ArgumentExtension::Uext => return ValueConversion::Uext(self.pointer_type).into(),
-                ArgumentExtension::Sext => return ValueConversion::Sext(self.pointer_type).into(),
+                ArgumentExtension::Uext => return ValueConversion::Uext(ir::types::I32).into(),
+                ArgumentExtension::Sext => return ValueConversion::Sext(ir::types::I32).into(),
             }
         }
 
-        if self.regs < self.reg_limit {
-            // Assign to a register.
-            let reg = GPR.unit(10 + self.regs as usize);
-            self.regs += 1;
-            ArgumentLoc::Reg(reg).into()
-        } else {
-            // Assign a stack location.
-            let loc = ArgumentLoc::Stack(self.offset as i32);
-            self.offset += u32::from(self.pointer_bytes);
-            debug_assert!(self.offset <= i32::MAX as u32);
-            loc.into()
+        if self.gpr_used < GPR_ARG_REGS {
+            let reg = GPR.unit(self.gpr_used as usize);
+            self.gpr_used += 1;
+            return ArgumentLoc::Reg(reg).into();
         }
+
+        // Assign a stack location.
+        let loc = ArgumentLoc::Stack(self.offset as i32);
+        self.offset += 4;
+        debug_assert!(self.offset <= i32::MAX as u32);
+        loc.into()
     }
 }
 
 /// Legalize `sig`.
 pub fn legalize_signature(sig: &mut Cow<ir::Signature>, triple: &Triple, _current: bool) {
-    let bits = triple.pointer_width().unwrap().bits();
-
-    let mut args = Args::new(bits);
+    let mut args = Args::new(triple);
     if let Some(new_params) = legalize_args(&sig.params, &mut args) {
         sig.to_mut().params = new_params;
     }
+
+    let mut rets = Args::new(triple);
+    if let Some(new_returns) = legalize_args(&sig.returns, &mut rets) {
+        sig.to_mut().returns = new_returns;
+    }
 }
 
 /// Get register class for a type appearing in a legalized signature.
 pub fn regclass_for_abi_type(ty: ir::Type) -> RegClass {
-    if ty.is_int() {
-        GPR
-    } else {
+    if ty.is_float() || ty.is_vector() {
         match ty.bits() {
             32 => S,
             64 => D,
             128 => Q,
             _ => panic!("Unexpected {} ABI type for arm32", ty),
         }
+    } else {
+        GPR
     }
 }
 
 /// Get the set of allocatable registers for `func`.
 pub fn allocatable_registers(_func: &ir::Function) -> RegisterSet {
-    unimplemented!()
+    let mut regs = RegisterSet::new();
+    regs.take(GPR, RU::r11 as RegUnit); // Frame pointer.
+                                        // The intra-procedure-call scratch register is used by encoding recipes that need a
+                                        // temporary, so it can't hold values.
+    regs.take(GPR, RU::r12 as RegUnit);
+    regs.take(GPR, RU::r13 as RegUnit); // Stack pointer.
+    regs.take(GPR, RU::r15 as RegUnit); // Program counter.
+
+    // Only 16 double precision registers are available with VFPv3-D16.
+    for index in 16..32 {
+        regs.take(D, D.unit(index));
+    }
+    regs
+}
+
+/// Get the callee-saved registers used by `func`.
+fn callee_saved_gprs_used(func: &ir::Function) -> Vec<RegUnit> {
+    let mut used = RegisterSet::empty();
+    let mut note = |ru: RegUnit| {
+        if GPR.contains(ru) && !used.is_avail(GPR, ru) {
+            used.free(GPR, ru);
+        }
+    };
+
+    for value_loc in func.locations.values() {
+        if let ValueLoc::Reg(ru) = *value_loc {
+            note(ru);
+        }
+    }
+
+    // regmove and regfill instructions may temporarily divert values into other registers,
+    // and these are not reflected in `func.locations`.
+    for ebb in &func.layout {
+        for inst in func.layout.ebb_insts(ebb) {
+            match func.dfg[inst] {
+                ir::InstructionData::RegMove { dst, .. }
+                | ir::InstructionData::RegFill { dst, .. } => note(dst),
+                _ => (),
+            }
+        }
+    }
+
+    CALLEE_SAVED_GPRS
+        .iter()
+        .map(|&reg| reg as RegUnit)
+        .filter(|&reg| used.is_avail(GPR, reg))
+        .collect()
+}
+
+/// Insert the prologue and epilogues for `func`, and compute its stack layout.
+pub fn prologue_epilogue(func: &mut ir::Function, isa: &dyn TargetIsa) -> CodegenResult<()> {
+    match func.signature.call_conv {
+        // For now, just translate fast and cold as system_v.
+        CallConv::Fast | CallConv::Cold | CallConv::SystemV => aapcs_prologue_epilogue(func, isa),
+        CallConv::WindowsFastcall
+        | CallConv::BaldrdashSystemV
+        | CallConv::BaldrdashWindows
+        | CallConv::Probestack
+        | CallConv::Tail
+        | CallConv::Custom(_) => Err(CodegenError::Unsupported(format!(
+            "{} calling convention on arm32",
+            func.signature.call_conv
+        ))),
+    }
+}
+
+/// Insert an AAPCS prologue and epilogue.
+///
+/// The frame looks like this, from higher to lower addresses:
+///
+/// - incoming stack arguments,
+/// - the link register and the saved frame pointer, with `r11` pointing at the latter,
+/// - the used callee-saved registers,
+/// - the local stack slots and outgoing arguments.
+///
+/// Floating point code isn't supported yet, so there are no VFP registers to save.
+fn aapcs_prologue_epilogue(func: &mut ir::Function, isa: &dyn TargetIsa) -> CodegenResult<()> {
+    // The stack pointer must be 8-byte aligned at public interfaces.
+    let stack_align = 8;
+
+    let gprs = callee_saved_gprs_used(func);
+
+    let csr_stack_size = (4 * (2 + gprs.len())) as StackOffset;
+    func.create_stack_slot(ir::StackSlotData {
+        kind: ir::StackSlotKind::IncomingArg,
+        size: csr_stack_size as u32,
+        offset: Some(-csr_stack_size),
+    });
+
+    let total_stack_size = layout_stack(&mut func.stack_slots, stack_align)? as i32;
+    let local_stack_size = i64::from(total_stack_size - csr_stack_size);
+
+    // Add the link register, frame pointer and CSRs to the function signature.
+    let mut specials = vec![
+        (ir::types::I32, ArgumentPurpose::Link, RU::r14 as RegUnit),
+        (
+            ir::types::I32,
+            ArgumentPurpose::FramePointer,
+            RU::r11 as RegUnit,
+        ),
+    ];
+    specials.extend(
+        gprs.iter()
+            .map(|&reg| (ir::types::I32, ArgumentPurpose::CalleeSaved, reg)),
+    );
+    for &(ty, purpose, reg) in &specials {
+        let arg = AbiParam::special_reg(ty, purpose, reg);
+        func.signature.params.push(arg);
+        func.signature.returns.push(arg);
+    }
+
+    // Set up the cursor and insert the prologue.
+    let entry_ebb = func.layout.entry_block().expect("missing entry block");
+    let mut pos = EncCursor::new(func, isa).at_first_insertion_point(entry_ebb);
+    insert_prologue(&mut pos, local_stack_size, &specials);
+
+    // Reset the cursor and insert the epilogues.
+    let mut pos = pos.at_position(CursorPosition::Nowhere);
+    while let Some(ebb) = pos.next_ebb() {
+        pos.goto_last_inst(ebb);
+        if let Some(inst) = pos.current_inst() {
+            if pos.func.dfg[inst].opcode().is_return() {
+                insert_epilogue(inst, local_stack_size, &mut pos, &specials);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Insert the prologue, pushing the registers in `specials` one at a time.
+fn insert_prologue(
+    pos: &mut EncCursor,
+    stack_size: i64,
+    specials: &[(Type, ArgumentPurpose, RegUnit)],
+) {
+    let ebb = pos.current_ebb().expect("missing ebb under cursor");
+    let mut args = Vec::with_capacity(specials.len());
+    for &(ty, _, reg) in specials {
+        let arg = pos.func.dfg.append_ebb_param(ebb, ty);
+        pos.func.locations[arg] = ValueLoc::Reg(reg);
+        args.push(arg);
+    }
+
+    // Save the link register and the frame pointer, and point the frame pointer at the latter.
+    pos.ins().arm32_push(args[0]);
+    pos.ins().arm32_push(args[1]);
+    pos.ins()
+        .copy_special(RU::r13 as RegUnit, RU::r11 as RegUnit);
+
+    for &arg in &args[2..] {
+        pos.ins().arm32_push(arg);
+    }
+
+    if stack_size > 0 {
+        pos.func.prologue_end = Some(pos.ins().adjust_sp_down_imm(Imm64::new(stack_size)));
+    }
+}
+
+/// Insert an epilogue before the `return` instruction `inst`, restoring the registers saved by
+/// the prologue in the opposite order.
+fn insert_epilogue(
+    inst: ir::Inst,
+    stack_size: i64,
+    pos: &mut EncCursor,
+    specials: &[(Type, ArgumentPurpose, RegUnit)],
+) {
+    if stack_size > 0 {
+        pos.ins().adjust_sp_up_imm(Imm64::new(stack_size));
+    }
+
+    // Pop the registers in reverse, stepping backward each time to preserve the correct order.
+    for &(_, _, reg) in specials {
+        let value = pos.ins().arm32_pop();
+        pos.prev_inst();
+        pos.func.locations[value] = ValueLoc::Reg(reg);
+        pos.func.dfg.append_inst_arg(inst, value);
+    }
 }
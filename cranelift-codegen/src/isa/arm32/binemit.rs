@@ -1,8 +1,429 @@
 //! Emitting binary ARM32 machine code.
 
-use crate::binemit::{bad_encoding, CodeSink};
-use crate::ir::{Function, Inst};
-use crate::isa::TargetIsa;
+use crate::binemit::{bad_encoding, CodeSink, Reloc};
+use crate::ir::condcodes::IntCC;
+use crate::ir::{Function, Inst, InstructionData, TrapCode};
+use crate::isa::{RegUnit, StackBaseMask, StackRef, TargetIsa};
 use crate::regalloc::RegDiversions;
 
 include!(concat!(env!("OUT_DIR"), "/binemit-arm32.rs"));
+
+// Condition codes, as encoded in bits 31-28 of every A32 instruction.
+const COND_EQ: u32 = 0b0000;
+const COND_NE: u32 = 0b0001;
+const COND_HS: u32 = 0b0010;
+const COND_LO: u32 = 0b0011;
+const COND_VS: u32 = 0b0110;
+const COND_VC: u32 = 0b0111;
+const COND_HI: u32 = 0b1000;
+const COND_LS: u32 = 0b1001;
+const COND_GE: u32 = 0b1010;
+const COND_LT: u32 = 0b1011;
+const COND_GT: u32 = 0b1100;
+const COND_LE: u32 = 0b1101;
+const COND_AL: u32 = 0b1110;
+
+// Opcodes used by recipes in addition to the one in their encoding bits.
+const OP_AND_IMM: u16 = 0x20;
+const OP_RSB_IMM: u16 = 0x26;
+const OP_CMP_IMM: u16 = 0x35;
+const OP_CMN_IMM: u16 = 0x37;
+const OP_MOV: u16 = 0x1a;
+const OP_MOV_IMM: u16 = 0x3a;
+const OP_MLS: u16 = 0x06;
+const OP_RBIT: u16 = 0x6f3;
+
+/// Get the condition code that tests `cc` after a `cmp` instruction.
+fn icc2cond(cc: IntCC) -> u32 {
+    use crate::ir::condcodes::IntCC::*;
+    match cc {
+        Equal => COND_EQ,
+        NotEqual => COND_NE,
+        SignedLessThan => COND_LT,
+        SignedGreaterThanOrEqual => COND_GE,
+        SignedGreaterThan => COND_GT,
+        SignedLessThanOrEqual => COND_LE,
+        UnsignedLessThan => COND_LO,
+        UnsignedGreaterThanOrEqual => COND_HS,
+        UnsignedGreaterThan => COND_HI,
+        UnsignedLessThanOrEqual => COND_LS,
+        Overflow => COND_VS,
+        NotOverflow => COND_VC,
+    }
+}
+
+/// Encode a register number into a 4-bit field.
+fn reg(r: RegUnit) -> u32 {
+    u32::from(r) & 0xf
+}
+
+/// Encode `value` as a modified immediate: an 8-bit value rotated right by an even amount.
+fn encode_imm(value: u32) -> Option<u32> {
+    (0..16).find_map(|rot| {
+        let imm8 = value.rotate_left(2 * rot);
+        if imm8 < 0x100 {
+            Some((rot << 8) | imm8)
+        } else {
+            None
+        }
+    })
+}
+
+/// Data processing, register.
+///
+///   31   27     19 15 11     3
+///   cond opcode rn rd 00000000 rm
+///     28     20 16 12        4  0
+///
+/// Encoding bits: instruction bits 27-20.
+fn put_dp<CS: CodeSink + ?Sized>(
+    cond: u32,
+    bits: u16,
+    rn: RegUnit,
+    rm: RegUnit,
+    rd: RegUnit,
+    sink: &mut CS,
+) {
+    let mut i = cond << 28;
+    i |= u32::from(bits) << 20;
+    i |= reg(rn) << 16;
+    i |= reg(rd) << 12;
+    i |= reg(rm);
+    sink.put4(i);
+}
+
+/// Data processing, immediate. `op2` is the 12-bit modified immediate field, already encoded.
+///
+/// Encoding bits: instruction bits 27-20.
+fn put_dp_imm<CS: CodeSink + ?Sized>(
+    cond: u32,
+    bits: u16,
+    rn: RegUnit,
+    op2: u32,
+    rd: RegUnit,
+    sink: &mut CS,
+) {
+    debug_assert!(op2 < 0x1000);
+    let mut i = cond << 28;
+    i |= u32::from(bits) << 20;
+    i |= reg(rn) << 16;
+    i |= reg(rd) << 12;
+    i |= op2;
+    sink.put4(i);
+}
+
+/// Add, subtract or compare with an immediate.
+///
+/// Encoding bits: the immediate form of the instruction in the low byte, and of the one used
+/// for negative immediates in the high byte.
+fn put_addsub_imm<CS: CodeSink + ?Sized>(
+    bits: u16,
+    rn: RegUnit,
+    imm: i64,
+    rd: RegUnit,
+    sink: &mut CS,
+) {
+    let (bits, imm) = if imm < 0 {
+        (bits >> 8, -imm)
+    } else {
+        (bits & 0xff, imm)
+    };
+    let op2 = encode_imm(imm as u32).expect("immediate can't be encoded");
+    put_dp_imm(COND_AL, bits, rn, op2, rd, sink);
+}
+
+/// Multiply, and multiply long.
+///
+///   31   27     19 15 11  7    3
+///   cond opcode rd ra rm 1001 rn
+///     28     20 16 12  8    4  0
+///
+/// Encoding bits: instruction bits 27-20. For the long multiplications, `rd` is the high half of
+/// the result and `ra` the low half.
+fn put_mul<CS: CodeSink + ?Sized>(
+    bits: u16,
+    rd: RegUnit,
+    ra: RegUnit,
+    rm: RegUnit,
+    rn: RegUnit,
+    sink: &mut CS,
+) {
+    let mut i = COND_AL << 28;
+    i |= u32::from(bits) << 20;
+    i |= reg(rd) << 16;
+    i |= reg(ra) << 12;
+    i |= reg(rm) << 8;
+    i |= 0b1001 << 4;
+    i |= reg(rn);
+    sink.put4(i);
+}
+
+/// Divide: `sdiv` or `udiv`.
+///
+///   31   27     19 15   11  7    3
+///   cond opcode rd 1111 rm 0001 rn
+///     28     20 16   12  8    4  0
+///
+/// Encoding bits: instruction bits 27-20.
+fn put_div<CS: CodeSink + ?Sized>(bits: u16, rn: RegUnit, rm: RegUnit, rd: RegUnit, sink: &mut CS) {
+    let mut i = COND_AL << 28;
+    i |= u32::from(bits) << 20;
+    i |= reg(rd) << 16;
+    i |= 0b1111 << 12;
+    i |= reg(rm) << 8;
+    i |= 0b0001 << 4;
+    i |= reg(rn);
+    sink.put4(i);
+}
+
+/// `mov rd, rm, <shift> rs`.
+///
+/// Encoding bits: the shift type.
+fn put_shift_reg<CS: CodeSink + ?Sized>(
+    bits: u16,
+    rm: RegUnit,
+    rs: RegUnit,
+    rd: RegUnit,
+    sink: &mut CS,
+) {
+    let mut i = (COND_AL << 28) | (u32::from(OP_MOV) << 20);
+    i |= reg(rd) << 12;
+    i |= reg(rs) << 8;
+    i |= u32::from(bits) << 5;
+    i |= 1 << 4;
+    i |= reg(rm);
+    sink.put4(i);
+}
+
+/// `mov rd, rm, <shift> #imm`.
+///
+/// Encoding bits: the shift type, with bit 2 set for a left rotation.
+///
+/// A zero amount is encoded as a plain `mov`, since `lsr #0` and `asr #0` mean a shift by 32,
+/// and `ror #0` means a rotation through the carry flag.
+fn put_shift_imm<CS: CodeSink + ?Sized>(
+    bits: u16,
+    rm: RegUnit,
+    imm: i64,
+    rd: RegUnit,
+    sink: &mut CS,
+) {
+    let mut amount = imm as u32 & 0x1f;
+    if bits & 0b100 != 0 {
+        amount = (32 - amount) & 0x1f;
+    }
+    let shift = if amount == 0 {
+        0
+    } else {
+        u32::from(bits) & 0b11
+    };
+    let mut i = (COND_AL << 28) | (u32::from(OP_MOV) << 20);
+    i |= reg(rd) << 12;
+    i |= amount << 7;
+    i |= shift << 5;
+    i |= reg(rm);
+    sink.put4(i);
+}
+
+/// Extend a byte or halfword, without rotation: `sxtb`, `sxth`, `uxtb` or `uxth`.
+///
+/// Encoding bits: instruction bits 27-20.
+fn put_extend<CS: CodeSink + ?Sized>(bits: u16, rm: RegUnit, rd: RegUnit, sink: &mut CS) {
+    let mut i = (COND_AL << 28) | (u32::from(bits) << 20);
+    i |= 0b1111 << 16;
+    i |= reg(rd) << 12;
+    i |= 0b0111 << 4;
+    i |= reg(rm);
+    sink.put4(i);
+}
+
+/// Bit operations with a single source register: `clz` and `rbit`.
+///
+///   31   27     19   15 11   7  3
+///   cond opcode 1111 rd 1111 op rm
+///     28     20   16 12    8  4  0
+///
+/// Encoding bits: instruction bits 27-20 and 7-4.
+fn put_bitop<CS: CodeSink + ?Sized>(bits: u16, rm: RegUnit, rd: RegUnit, sink: &mut CS) {
+    let bits = u32::from(bits);
+    let mut i = (COND_AL << 28) | ((bits >> 4) << 20);
+    i |= 0b1111 << 16;
+    i |= reg(rd) << 12;
+    i |= 0b1111 << 8;
+    i |= (bits & 0xf) << 4;
+    i |= reg(rm);
+    sink.put4(i);
+}
+
+/// `movw rd, #imm`, with the low 16 bits of `imm`.
+fn put_movw<CS: CodeSink + ?Sized>(cond: u32, imm: u32, rd: RegUnit, sink: &mut CS) {
+    let mut i = (cond << 28) | (0x30 << 20);
+    i |= ((imm >> 12) & 0xf) << 16;
+    i |= reg(rd) << 12;
+    i |= imm & 0xfff;
+    sink.put4(i);
+}
+
+/// `movt rd, #imm`, with the high 16 bits of `imm`.
+fn put_movt<CS: CodeSink + ?Sized>(imm: u32, rd: RegUnit, sink: &mut CS) {
+    let imm = imm >> 16;
+    let mut i = (COND_AL << 28) | (0x34 << 20);
+    i |= ((imm >> 12) & 0xf) << 16;
+    i |= reg(rd) << 12;
+    i |= imm & 0xfff;
+    sink.put4(i);
+}
+
+/// Load or store a word or a byte, with a 12-bit immediate offset.
+///
+///   31   27     19 15 11
+///   cond opcode rn rt imm12
+///     28     20 16 12     0
+///
+/// Encoding bits: instruction bits 27-20, with the `U` bit set. The `U` bit is cleared for
+/// negative offsets.
+fn put_ldst<CS: CodeSink + ?Sized>(
+    bits: u16,
+    rn: RegUnit,
+    offset: i32,
+    rt: RegUnit,
+    sink: &mut CS,
+) {
+    let (bits, offset) = if offset < 0 {
+        (bits & !0x08, -offset)
+    } else {
+        (bits, offset)
+    };
+    assert!(offset < 0x1000, "offset {} out of range", offset);
+    let mut i = (COND_AL << 28) | (u32::from(bits) << 20);
+    i |= reg(rn) << 16;
+    i |= reg(rt) << 12;
+    i |= offset as u32;
+    sink.put4(i);
+}
+
+/// Load or store a word or a byte, with a register offset: `ldr rt, [rn, rm]`.
+///
+/// Encoding bits: instruction bits 27-20 of the immediate form.
+fn put_ldst_reg<CS: CodeSink + ?Sized>(
+    bits: u16,
+    rn: RegUnit,
+    rm: RegUnit,
+    rt: RegUnit,
+    sink: &mut CS,
+) {
+    let mut i = (COND_AL << 28) | (u32::from(bits | 0x20) << 20);
+    i |= reg(rn) << 16;
+    i |= reg(rt) << 12;
+    i |= reg(rm);
+    sink.put4(i);
+}
+
+/// Load a word with a register offset shifted by 2: `ldr rt, [rn, rm, lsl #2]`.
+///
+/// Encoding bits: instruction bits 27-20.
+fn put_ldst_lsl2<CS: CodeSink + ?Sized>(
+    bits: u16,
+    rn: RegUnit,
+    rm: RegUnit,
+    rt: RegUnit,
+    sink: &mut CS,
+) {
+    let mut i = (COND_AL << 28) | (u32::from(bits) << 20);
+    i |= reg(rn) << 16;
+    i |= reg(rt) << 12;
+    i |= 2 << 7;
+    i |= reg(rm);
+    sink.put4(i);
+}
+
+/// Load or store a halfword or a signed byte, with an 8-bit immediate offset.
+///
+///   31   27     19 15 11       7  3
+///   cond opcode rn rt imm4H 1 op 1 imm4L
+///     28     20 16 12     8      4     0
+///
+/// Encoding bits: instruction bits 27-20 in the high bits, and bits 7-4 in the low bits.
+fn put_ldsth<CS: CodeSink + ?Sized>(
+    bits: u16,
+    rn: RegUnit,
+    offset: i32,
+    rt: RegUnit,
+    sink: &mut CS,
+) {
+    let (opcode, offset) = if offset < 0 {
+        ((bits >> 4) & !0x08, -offset)
+    } else {
+        (bits >> 4, offset)
+    };
+    assert!(offset < 0x100, "offset {} out of range", offset);
+    let offset = offset as u32;
+    let mut i = (COND_AL << 28) | (u32::from(opcode) << 20);
+    i |= reg(rn) << 16;
+    i |= reg(rt) << 12;
+    i |= (offset >> 4) << 8;
+    i |= u32::from(bits & 0xf) << 4;
+    i |= offset & 0xf;
+    sink.put4(i);
+}
+
+/// Load or store a halfword or a signed byte, with a register offset.
+///
+/// Encoding bits: the same as the immediate form, which sets bit 22.
+fn put_ldsth_reg<CS: CodeSink + ?Sized>(
+    bits: u16,
+    rn: RegUnit,
+    rm: RegUnit,
+    rt: RegUnit,
+    sink: &mut CS,
+) {
+    let opcode = (bits >> 4) & !0x04;
+    let mut i = (COND_AL << 28) | (u32::from(opcode) << 20);
+    i |= reg(rn) << 16;
+    i |= reg(rt) << 12;
+    i |= u32::from(bits & 0xf) << 4;
+    i |= reg(rm);
+    sink.put4(i);
+}
+
+/// Load or store a word relative to the stack pointer.
+///
+/// Encoding bits: instruction bits 27-20.
+fn put_ldst_sp<CS: CodeSink + ?Sized>(bits: u16, offset: i32, rt: RegUnit, sink: &mut CS) {
+    assert!(
+        offset >= 0 && offset < 0x1000,
+        "stack offset {} out of range",
+        offset
+    );
+    put_ldst(bits, 13, offset, rt, sink);
+}
+
+/// `b<cond> #disp`, where the displacement is relative to the branch itself.
+fn put_b<CS: CodeSink + ?Sized>(cond: u32, disp: i64, sink: &mut CS) {
+    let mut i = (cond << 28) | 0x0a00_0000;
+    i |= ((disp - 8) >> 2) as u32 & 0xff_ffff;
+    sink.put4(i);
+}
+
+/// `bl #0`. The displacement is filled in by an `Arm32Call` relocation, and the encoded
+/// displacement of -8 is what the ELF relocation expects as the implicit addend.
+fn put_bl<CS: CodeSink + ?Sized>(sink: &mut CS) {
+    sink.put4(0xebff_fffe);
+}
+
+/// Branch and exchange to a register: `bx` or `blx`.
+///
+/// Encoding bits: instruction bits 7-4.
+fn put_bx<CS: CodeSink + ?Sized>(bits: u16, rm: RegUnit, sink: &mut CS) {
+    sink.put4(0xe12f_ff00 | (u32::from(bits) << 4) | reg(rm));
+}
+
+/// `udf #0`, the permanently undefined instruction used for traps.
+fn put_udf<CS: CodeSink + ?Sized>(sink: &mut CS) {
+    sink.put4(0xe7f0_00f0);
+}
+
+/// `bkpt #0`.
+fn put_bkpt<CS: CodeSink + ?Sized>(sink: &mut CS) {
+    sink.put4(0xe120_0070);
+}
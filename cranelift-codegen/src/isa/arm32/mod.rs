@@ -13,10 +13,13 @@ use crate::binemit::{emit_function, MemoryCodeSink};
 use crate::ir;
 use crate::isa::enc_tables::{self as shared_enc_tables, lookup_enclist, Encodings};
 use crate::isa::Builder as IsaBuilder;
-use crate::isa::{EncInfo, RegClass, RegInfo, TargetIsa};
+use crate::isa::{CustomCallConv, EncInfo, RegClass, RegInfo, TargetIsa};
 use crate::regalloc;
+use crate::result::CodegenResult;
+use crate::timing;
 use alloc::borrow::Cow;
 use alloc::boxed::Box;
+use alloc::vec::Vec;
 use core::fmt;
 use target_lexicon::{Architecture, Triple};
 
@@ -33,6 +36,8 @@ pub fn isa_builder(triple: Triple) -> IsaBuilder {
     IsaBuilder {
         triple,
         setup: settings::builder(),
+        register_info: registers::INFO.clone(),
+        call_convs: Vec::new(),
         constructor: isa_constructor,
     }
 }
@@ -41,6 +46,8 @@ fn isa_constructor(
     triple: Triple,
     shared_flags: shared_settings::Flags,
     builder: shared_settings::Builder,
+    // Custom calling conventions aren't supported, so the verifier rejects their uses.
+    _call_convs: Vec<CustomCallConv>,
 ) -> Box<dyn TargetIsa> {
     let level1 = match triple.architecture {
         Architecture::Arm(arm) => {
@@ -113,6 +120,11 @@ impl TargetIsa for Isa {
         abi::allocatable_registers(func)
     }
 
+    fn prologue_epilogue(&self, func: &mut ir::Function) -> CodegenResult<()> {
+        let _tt = timing::prologue_epilogue();
+        abi::prologue_epilogue(func, self)
+    }
+
     #[cfg(feature = "testing_hooks")]
     fn emit_inst(
         &self,
@@ -1,31 +1,409 @@
 //! ARM 64 ABI implementation.
+//!
+//! This module implements the AAPCS64 procedure call standard: integer arguments and return
+//! values are passed in `x0`-`x7`, floating point ones in `v0`-`v7`, and anything else goes on
+//! the stack in 8-byte slots.
+//!
+//! The generated frames always keep a frame pointer in `x29`, which makes them walkable by
+//! debuggers and profilers without any extra unwind information.
 
-use super::registers::{FPR, GPR};
-use crate::ir;
-use crate::isa::RegClass;
+use super::registers::{FPR, GPR, RU};
+use crate::abi::{legalize_args, ArgAction, ArgAssigner, ValueConversion};
+use crate::cursor::{Cursor, CursorPosition, EncCursor};
+use crate::ir::immediates::Imm64;
+use crate::ir::stackslot::StackOffset;
+use crate::ir::{
+    self, AbiParam, ArgumentExtension, ArgumentLoc, ArgumentPurpose, InstBuilder, Type, ValueLoc,
+};
+use crate::isa::{CallConv, RegClass, RegUnit, TargetIsa};
 use crate::regalloc::RegisterSet;
+use crate::result::{CodegenError, CodegenResult};
 use crate::settings as shared_settings;
+use crate::stack_layout::layout_stack;
 use alloc::borrow::Cow;
+use alloc::vec::Vec;
+use core::i32;
+
+/// Number of registers of each class used for passing arguments and return values.
+const ARG_REGS: u32 = 8;
+
+/// Callee-saved general purpose registers, excluding the frame pointer and link register.
+static CALLEE_SAVED_GPRS: [RU; 10] = [
+    RU::x19,
+    RU::x20,
+    RU::x21,
+    RU::x22,
+    RU::x23,
+    RU::x24,
+    RU::x25,
+    RU::x26,
+    RU::x27,
+    RU::x28,
+];
+
+/// Callee-saved floating point registers. Only the low 64 bits of these are preserved across
+/// calls.
+static CALLEE_SAVED_FPRS: [RU; 8] = [
+    RU::v8,
+    RU::v9,
+    RU::v10,
+    RU::v11,
+    RU::v12,
+    RU::v13,
+    RU::v14,
+    RU::v15,
+];
+
+struct Args {
+    gpr_used: u32,
+    fpr_used: u32,
+    offset: u32,
+}
+
+impl Args {
+    fn new() -> Self {
+        Self {
+            gpr_used: 0,
+            fpr_used: 0,
+            offset: 0,
+        }
+    }
+}
+
+impl ArgAssigner for Args {
+    fn assign(&mut self, arg: &AbiParam) -> ArgAction {
+        fn align(value: u32, to: u32) -> u32 {
+            (value + to - 1) & !(to - 1)
+        }
+
+        let ty = arg.value_type;
+
+        if arg.purpose.struct_layout().is_some() {
+            unreachable!("the verifier rejects struct arguments on arm64");
+        }
+
+        // SIMD types are not supported yet, so break all vectors down.
+        if ty.is_vector() {
+            return ValueConversion::VectorSplit.into();
+        }
+
+        // Large integers and booleans are broken down to fit in a register. A 128-bit value
+        // starts at an even-numbered register or a 16-byte aligned stack slot.
+        if !ty.is_float() && ty.bits() > 64 {
+            self.gpr_used = align(self.gpr_used, 2);
+            self.offset = align(self.offset, 16);
+            return ValueConversion::IntSplit.into();
+        }
+
+        // Small integers are extended to the size of a register.
+        if ty.is_int() && ty.bits() < 64 {
+            match arg.extension {
+                ArgumentExtension::None => {}
+                ArgumentExtension::Uext => return ValueConversion::Uext(ir::types::I64).into(),
+                ArgumentExtension::Sext => return ValueConversion::Sext(ir::types::I64).into(),
+            }
+        }
+
+        if ty.is_float() {
+            if self.fpr_used < ARG_REGS {
+                let reg = FPR.unit(self.fpr_used as usize);
+                self.fpr_used += 1;
+                return ArgumentLoc::Reg(reg).into();
+            }
+        } else if self.gpr_used < ARG_REGS {
+            let reg = GPR.unit(self.gpr_used as usize);
+            self.gpr_used += 1;
+            return ArgumentLoc::Reg(reg).into();
+        }
+
+        // Assign a stack location.
+        let loc = ArgumentLoc::Stack(self.offset as i32);
+        self.offset += 8;
+        debug_assert!(self.offset <= i32::MAX as u32);
+        loc.into()
+    }
+}
 
 /// Legalize `sig`.
 pub fn legalize_signature(
-    _sig: &mut Cow<ir::Signature>,
+    sig: &mut Cow<ir::Signature>,
     _flags: &shared_settings::Flags,
     _current: bool,
 ) {
-    unimplemented!()
+    let mut args = Args::new();
+    if let Some(new_params) = legalize_args(&sig.params, &mut args) {
+        sig.to_mut().params = new_params;
+    }
+
+    let mut rets = Args::new();
+    if let Some(new_returns) = legalize_args(&sig.returns, &mut rets) {
+        sig.to_mut().returns = new_returns;
+    }
 }
 
 /// Get register class for a type appearing in a legalized signature.
-pub fn regclass_for_abi_type(ty: ir::Type) -> RegClass {
-    if ty.is_int() {
-        GPR
-    } else {
+pub fn regclass_for_abi_type(ty: Type) -> RegClass {
+    if ty.is_float() {
         FPR
+    } else {
+        GPR
     }
 }
 
 /// Get the set of allocatable registers for `func`.
 pub fn allocatable_registers(_func: &ir::Function) -> RegisterSet {
-    unimplemented!()
+    let mut regs = RegisterSet::new();
+    // The intra-procedure-call scratch registers are used by encoding recipes that need a
+    // temporary, so they can't hold values.
+    regs.take(GPR, RU::x16 as RegUnit);
+    regs.take(GPR, RU::x17 as RegUnit);
+    regs.take(GPR, RU::x18 as RegUnit); // Platform register.
+    regs.take(GPR, RU::x29 as RegUnit); // Frame pointer.
+    regs.take(GPR, RU::x31 as RegUnit); // Stack pointer / zero register.
+    regs
+}
+
+/// Get the callee-saved registers of class `rc` that are used by `func`.
+fn callee_saved_regs_used(func: &ir::Function, rc: RegClass, csrs: &[RU]) -> Vec<RegUnit> {
+    let mut used = RegisterSet::empty();
+    let mut note = |ru: RegUnit| {
+        if rc.contains(ru) && !used.is_avail(rc, ru) {
+            used.free(rc, ru);
+        }
+    };
+
+    for value_loc in func.locations.values() {
+        if let ValueLoc::Reg(ru) = *value_loc {
+            note(ru);
+        }
+    }
+
+    // regmove and regfill instructions may temporarily divert values into other registers,
+    // and these are not reflected in `func.locations`.
+    for ebb in &func.layout {
+        for inst in func.layout.ebb_insts(ebb) {
+            match func.dfg[inst] {
+                ir::InstructionData::RegMove { dst, .. }
+                | ir::InstructionData::RegFill { dst, .. } => note(dst),
+                _ => (),
+            }
+        }
+    }
+
+    csrs.iter()
+        .map(|&reg| reg as RegUnit)
+        .filter(|&reg| used.is_avail(rc, reg))
+        .collect()
+}
+
+/// Insert the prologue and epilogues for `func`, and compute its stack layout.
+pub fn prologue_epilogue(func: &mut ir::Function, isa: &dyn TargetIsa) -> CodegenResult<()> {
+    match func.signature.call_conv {
+        // For now, just translate fast and cold as system_v.
+        CallConv::Fast | CallConv::Cold | CallConv::SystemV => aapcs_prologue_epilogue(func, isa),
+        CallConv::WindowsFastcall
+        | CallConv::BaldrdashSystemV
+        | CallConv::BaldrdashWindows
+        | CallConv::Probestack
+        | CallConv::Tail
+        | CallConv::Custom(_) => Err(CodegenError::Unsupported(format!(
+            "{} calling convention on arm64",
+            func.signature.call_conv
+        ))),
+    }
+}
+
+/// Insert an AAPCS64 prologue and epilogue.
+///
+/// The frame looks like this, from higher to lower addresses:
+///
+/// - incoming stack arguments,
+/// - the saved frame pointer and link register, with `x29` pointing at them,
+/// - the used callee-saved registers, two per 16-byte slot,
+/// - the local stack slots and outgoing arguments.
+fn aapcs_prologue_epilogue(func: &mut ir::Function, isa: &dyn TargetIsa) -> CodegenResult<()> {
+    // The stack pointer must always be 16-byte aligned when it is used to access memory.
+    let stack_align = 16;
+
+    let gprs = callee_saved_regs_used(func, GPR, &CALLEE_SAVED_GPRS);
+    let fprs = callee_saved_regs_used(func, FPR, &CALLEE_SAVED_FPRS);
+
+    // Registers are pushed in pairs, so an odd register out still takes a full 16-byte slot.
+    let csr_slots = 1 + (gprs.len() + 1) / 2 + (fprs.len() + 1) / 2;
+    let csr_stack_size = (csr_slots * 16) as StackOffset;
+    func.create_stack_slot(ir::StackSlotData {
+        kind: ir::StackSlotKind::IncomingArg,
+        size: csr_stack_size as u32,
+        offset: Some(-csr_stack_size),
+    });
+
+    let total_stack_size = layout_stack(&mut func.stack_slots, stack_align)? as i32;
+    let local_stack_size = i64::from(total_stack_size - csr_stack_size);
+
+    // Add the frame pointer, link register and CSRs to the function signature.
+    let mut specials = vec![
+        (
+            ir::types::I64,
+            ArgumentPurpose::FramePointer,
+            RU::x29 as RegUnit,
+        ),
+        (ir::types::I64, ArgumentPurpose::Link, RU::x30 as RegUnit),
+    ];
+    specials.extend(
+        gprs.iter()
+            .map(|&reg| (ir::types::I64, ArgumentPurpose::CalleeSaved, reg)),
+    );
+    specials.extend(
+        fprs.iter()
+            .map(|&reg| (ir::types::F64, ArgumentPurpose::CalleeSaved, reg)),
+    );
+    for &(ty, purpose, reg) in &specials {
+        let arg = AbiParam::special_reg(ty, purpose, reg);
+        func.signature.params.push(arg);
+        func.signature.returns.push(arg);
+    }
+
+    // Set up the cursor and insert the prologue.
+    let entry_ebb = func.layout.entry_block().expect("missing entry block");
+    let mut pos = EncCursor::new(func, isa).at_first_insertion_point(entry_ebb);
+    insert_prologue(&mut pos, local_stack_size, &specials);
+
+    // Reset the cursor and insert the epilogues.
+    let mut pos = pos.at_position(CursorPosition::Nowhere);
+    while let Some(ebb) = pos.next_ebb() {
+        pos.goto_last_inst(ebb);
+        if let Some(inst) = pos.current_inst() {
+            if pos.func.dfg[inst].opcode().is_return() {
+                insert_epilogue(inst, local_stack_size, &mut pos, &specials);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Insert the prologue, saving the registers in `specials` in pairs.
+fn insert_prologue(
+    pos: &mut EncCursor,
+    stack_size: i64,
+    specials: &[(Type, ArgumentPurpose, RegUnit)],
+) {
+    let ebb = pos.current_ebb().expect("missing ebb under cursor");
+    let mut args = Vec::with_capacity(specials.len());
+    for &(ty, _, reg) in specials {
+        let arg = pos.func.dfg.append_ebb_param(ebb, ty);
+        pos.func.locations[arg] = ValueLoc::Reg(reg);
+        args.push(arg);
+    }
+
+    // Save the frame pointer and link register, and point the frame pointer at them.
+    pos.ins().arm64_push_pair(args[0], args[1]);
+    pos.ins()
+        .copy_special(RU::x31 as RegUnit, RU::x29 as RegUnit);
+
+    for group in group_by_type(specials, &args[2..]) {
+        match *group {
+            [x, y] => pos.ins().arm64_push_pair(x, y),
+            [x] => pos.ins().arm64_push(x),
+            _ => unreachable!(),
+        };
+    }
+
+    if stack_size > 0 {
+        pos.func.prologue_end = Some(pos.ins().adjust_sp_down_imm(Imm64::new(stack_size)));
+    }
+}
+
+/// Insert an epilogue before the `return` instruction `inst`, restoring the registers saved by
+/// the prologue in the opposite order.
+fn insert_epilogue(
+    inst: ir::Inst,
+    stack_size: i64,
+    pos: &mut EncCursor,
+    specials: &[(Type, ArgumentPurpose, RegUnit)],
+) {
+    if stack_size > 0 {
+        pos.ins().adjust_sp_up_imm(Imm64::new(stack_size));
+    }
+
+    // Pop the registers in reverse, stepping backward each time to preserve the correct order.
+    let pop = |pos: &mut EncCursor, ty: Type, regs: &[RegUnit]| {
+        let results = if regs.len() == 2 {
+            let (x, y) = pos.ins().arm64_pop_pair(ty);
+            vec![x, y]
+        } else {
+            vec![pos.ins().arm64_pop(ty)]
+        };
+        pos.prev_inst();
+        for (&value, &reg) in results.iter().zip(regs) {
+            pos.func.locations[value] = ValueLoc::Reg(reg);
+            pos.func.dfg.append_inst_arg(inst, value);
+        }
+    };
+
+    let regs: Vec<RegUnit> = specials.iter().map(|&(_, _, reg)| reg).collect();
+    pop(pos, ir::types::I64, &regs[..2]);
+    for group in group_by_type(specials, &regs[2..]) {
+        let ty = if FPR.contains(group[0]) {
+            ir::types::F64
+        } else {
+            ir::types::I64
+        };
+        pop(pos, ty, group);
+    }
+}
+
+/// Split `items`, which correspond to the callee-saved registers at the end of `specials`, into
+/// the groups that are pushed together: pairs of registers of the same class, followed by a
+/// single register if the class has an odd number of them.
+fn group_by_type<'a, T>(
+    specials: &[(Type, ArgumentPurpose, RegUnit)],
+    items: &'a [T],
+) -> impl Iterator<Item = &'a [T]> {
+    let csrs = &specials[specials.len() - items.len()..];
+    let num_gprs = csrs.iter().filter(|&&(ty, _, _)| !ty.is_float()).count();
+    let (gprs, fprs) = items.split_at(num_gprs);
+    gprs.chunks(2).chain(fprs.chunks(2))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cursor::{Cursor, FuncCursor};
+    use crate::ir::{ExternalName, Function, InstBuilder, Signature};
+    use crate::isa::lookup;
+    use crate::settings::{builder, Flags};
+    use crate::Context;
+    use std::str::FromStr;
+    use target_lexicon::triple;
+
+    #[test]
+    fn unsupported_call_convs() {
+        let isa = lookup(triple!("aarch64"))
+            .expect("expect arm64 ISA")
+            .finish(Flags::new(builder()));
+
+        for &call_conv in &[
+            CallConv::WindowsFastcall,
+            CallConv::BaldrdashSystemV,
+            CallConv::Probestack,
+            CallConv::Tail,
+        ] {
+            let mut func =
+                Function::with_name_signature(ExternalName::user(0, 0), Signature::new(call_conv));
+            let ebb0 = func.dfg.make_ebb();
+            let mut pos = FuncCursor::new(&mut func);
+            pos.insert_ebb(ebb0);
+            pos.ins().return_(&[]);
+
+            let mut context = Context::for_function(func);
+            assert_eq!(
+                context.compile(&*isa).err(),
+                Some(CodegenError::Unsupported(format!(
+                    "{} calling convention on arm64",
+                    call_conv
+                )))
+            );
+        }
+    }
 }
@@ -1,8 +1,649 @@
 //! Emitting binary ARM64 machine code.
 
-use crate::binemit::{bad_encoding, CodeSink};
-use crate::ir::{Function, Inst};
-use crate::isa::TargetIsa;
+use crate::binemit::{bad_encoding, CodeSink, Reloc};
+use crate::ir::condcodes::{FloatCC, IntCC};
+use crate::ir::{Function, Inst, InstructionData, TrapCode};
+use crate::isa::{RegUnit, StackBaseMask, StackRef, TargetIsa};
 use crate::regalloc::RegDiversions;
 
 include!(concat!(env!("OUT_DIR"), "/binemit-arm64.rs"));
+
+// Condition codes, as encoded in `b.cond`, `csel` and friends.
+const COND_EQ: u32 = 0b0000;
+const COND_NE: u32 = 0b0001;
+const COND_HS: u32 = 0b0010;
+const COND_LO: u32 = 0b0011;
+const COND_MI: u32 = 0b0100;
+const COND_PL: u32 = 0b0101;
+const COND_VS: u32 = 0b0110;
+const COND_VC: u32 = 0b0111;
+const COND_HI: u32 = 0b1000;
+const COND_LS: u32 = 0b1001;
+const COND_GE: u32 = 0b1010;
+const COND_LT: u32 = 0b1011;
+const COND_GT: u32 = 0b1100;
+const COND_LE: u32 = 0b1101;
+
+/// Get the condition code that tests `cc` after a `cmp` instruction.
+fn icc2cond(cc: IntCC) -> u32 {
+    use crate::ir::condcodes::IntCC::*;
+    match cc {
+        Equal => COND_EQ,
+        NotEqual => COND_NE,
+        SignedLessThan => COND_LT,
+        SignedGreaterThanOrEqual => COND_GE,
+        SignedGreaterThan => COND_GT,
+        SignedLessThanOrEqual => COND_LE,
+        UnsignedLessThan => COND_LO,
+        UnsignedGreaterThanOrEqual => COND_HS,
+        UnsignedGreaterThan => COND_HI,
+        UnsignedLessThanOrEqual => COND_LS,
+        Overflow => COND_VS,
+        NotOverflow => COND_VC,
+    }
+}
+
+/// Get the condition code that tests `cc` after an `fcmp` instruction.
+///
+/// An unordered `fcmp` sets NZCV to 0011, which is what makes the choices below work.
+fn fcc2cond(cc: FloatCC) -> u32 {
+    use crate::ir::condcodes::FloatCC::*;
+    match cc {
+        Ordered => COND_VC,
+        Unordered => COND_VS,
+        Equal => COND_EQ,
+        NotEqual => COND_NE,
+        LessThan => COND_MI,
+        LessThanOrEqual => COND_LS,
+        GreaterThan => COND_GT,
+        GreaterThanOrEqual => COND_GE,
+        UnorderedOrLessThan => COND_LT,
+        UnorderedOrLessThanOrEqual => COND_LE,
+        UnorderedOrGreaterThan => COND_HI,
+        UnorderedOrGreaterThanOrEqual => COND_PL,
+        OrderedNotEqual | UnorderedOrEqual => panic!("{} needs two conditions", cc),
+    }
+}
+
+/// Encode a register number into a 5-bit field. Both `%xN` and `%vN` map to `N`, and `%x31` is
+/// the zero register or the stack pointer depending on the instruction.
+fn reg(r: RegUnit) -> u32 {
+    u32::from(r) & 0x1f
+}
+
+/// Data processing, shifted register, with a zero shift amount.
+///
+///   31   20 15  9  4
+///   opcode rm rn rd
+///       21 16  5  0
+///
+/// Encoding bits: instruction bits 31-21, including `sf`.
+/// This also covers `subs` with `%x31` as `rd`, which is `cmp`.
+fn put_rrr<CS: CodeSink + ?Sized>(bits: u16, rn: RegUnit, rm: RegUnit, rd: RegUnit, sink: &mut CS) {
+    let mut i = u32::from(bits) << 21;
+    i |= reg(rm) << 16;
+    i |= reg(rn) << 5;
+    i |= reg(rd);
+    sink.put4(i);
+}
+
+/// Data processing, 2 source.
+///
+///   31 30   20 15     9  4
+///   sf 0011010110 rm opcode rn rd
+///
+/// Encoding bits: `opcode | (sf << 6)`.
+fn put_dp2<CS: CodeSink + ?Sized>(bits: u16, rn: RegUnit, rm: RegUnit, rd: RegUnit, sink: &mut CS) {
+    let bits = u32::from(bits);
+    let mut i = 0x1ac0_0000;
+    i |= (bits >> 6) << 31;
+    i |= reg(rm) << 16;
+    i |= (bits & 0x3f) << 10;
+    i |= reg(rn) << 5;
+    i |= reg(rd);
+    sink.put4(i);
+}
+
+/// Data processing, 1 source.
+///
+///   31 30      15     9  4
+///   sf 101101011000000 opcode rn rd
+///
+/// Encoding bits: `opcode | (sf << 6)`.
+fn put_dp1<CS: CodeSink + ?Sized>(bits: u16, rn: RegUnit, rd: RegUnit, sink: &mut CS) {
+    let bits = u32::from(bits);
+    let mut i = 0x5ac0_0000;
+    i |= (bits >> 6) << 31;
+    i |= (bits & 0x3f) << 10;
+    i |= reg(rn) << 5;
+    i |= reg(rd);
+    sink.put4(i);
+}
+
+/// Data processing, 3 source.
+///
+///   31   20 15 14 9  4
+///   opcode rm o0 ra rn rd
+///       21 16 15 10  5  0
+///
+/// Encoding bits: instruction bits 31-21 | (o0 << 11).
+fn put_dp3<CS: CodeSink + ?Sized>(
+    bits: u16,
+    rn: RegUnit,
+    rm: RegUnit,
+    ra: RegUnit,
+    rd: RegUnit,
+    sink: &mut CS,
+) {
+    let bits = u32::from(bits);
+    let mut i = (bits & 0x7ff) << 21;
+    i |= reg(rm) << 16;
+    i |= (bits >> 11) << 15;
+    i |= reg(ra) << 10;
+    i |= reg(rn) << 5;
+    i |= reg(rd);
+    sink.put4(i);
+}
+
+/// Add/subtract with an immediate.
+///
+///   31   22 21    9  4
+///   opcode sh imm12 rn rd
+///       23 22    10  5  0
+///
+/// Encoding bits: instruction bits 31-22, with `sh` clear.
+///
+/// A negative immediate is encoded by flipping the add/sub bit, and immediates with the low 12
+/// bits clear use the shifted form. `%x31` is the stack pointer as `rn`, and also as `rd` unless
+/// the instruction sets flags, in which case it is the zero register.
+fn put_addsub_imm<CS: CodeSink + ?Sized>(
+    bits: u16,
+    rn: RegUnit,
+    imm: i64,
+    rd: RegUnit,
+    sink: &mut CS,
+) {
+    let (bits, imm) = if imm < 0 {
+        (bits ^ 0x100, -imm)
+    } else {
+        (bits, imm)
+    };
+    let (bits, imm) = if imm < 0x1000 {
+        (bits, imm)
+    } else {
+        debug_assert!(
+            imm & 0xfff == 0 && imm < 0x100_0000,
+            "bad immediate {}",
+            imm
+        );
+        (bits | 1, imm >> 12)
+    };
+    let mut i = u32::from(bits) << 22;
+    i |= (imm as u32) << 10;
+    i |= reg(rn) << 5;
+    i |= reg(rd);
+    sink.put4(i);
+}
+
+/// Conditional compare with an immediate.
+///
+///   31 30     20   15   11   4 3
+///   sf 111010010 imm5 cond 10 rn 0 nzcv
+fn put_ccmp_imm<CS: CodeSink + ?Sized>(
+    sf: u16,
+    rn: RegUnit,
+    imm5: u32,
+    nzcv: u32,
+    cond: u32,
+    sink: &mut CS,
+) {
+    let mut i = 0x7a40_0800;
+    i |= u32::from(sf) << 31;
+    i |= (imm5 & 0x1f) << 16;
+    i |= cond << 12;
+    i |= reg(rn) << 5;
+    i |= nzcv;
+    sink.put4(i);
+}
+
+/// Bitfield move instructions implementing an immediate shift.
+///
+///   31   22 21   15   9  4
+///   opcode immr imms rn rd
+///       22   16   10  5  0
+///
+/// Encoding bits: instruction bits 31-22 (`ubfm` or `sbfm`, including `sf` and `N`), plus
+/// `1 << 10` for a left shift.
+fn put_shift_imm<CS: CodeSink + ?Sized>(
+    bits: u16,
+    rn: RegUnit,
+    amount: i64,
+    rd: RegUnit,
+    sink: &mut CS,
+) {
+    let bits = u32::from(bits);
+    let size = if bits & 0x200 != 0 { 64 } else { 32 };
+    let amount = amount as u32 & (size - 1);
+    let (immr, imms) = if bits & 0x400 != 0 {
+        ((size - amount) & (size - 1), size - 1 - amount)
+    } else {
+        (amount, size - 1)
+    };
+    let mut i = (bits & 0x3ff) << 22;
+    i |= immr << 16;
+    i |= imms << 10;
+    i |= reg(rn) << 5;
+    i |= reg(rd);
+    sink.put4(i);
+}
+
+/// Sign and zero extensions, which are bitfield moves with a zero `immr`.
+///
+/// Encoding bits: instruction bits 31-22 (`ubfm` or `sbfm`, including `sf` and `N`), plus the
+/// source width in bits minus one shifted left by 10.
+fn put_extend<CS: CodeSink + ?Sized>(bits: u16, rn: RegUnit, rd: RegUnit, sink: &mut CS) {
+    let bits = u32::from(bits);
+    let mut i = (bits & 0x3ff) << 22;
+    i |= (bits >> 10) << 10;
+    i |= reg(rn) << 5;
+    i |= reg(rd);
+    sink.put4(i);
+}
+
+/// Rotate right by an immediate, which is `extr` with both sources equal.
+///
+///   31   21 20 15   9  4
+///   opcode rm imms rn rd
+///
+/// Encoding bits: instruction bits 31-22 (including `sf` and `N`), plus `1 << 10` for a left
+/// rotate.
+fn put_rot_imm<CS: CodeSink + ?Sized>(
+    bits: u16,
+    rn: RegUnit,
+    amount: i64,
+    rd: RegUnit,
+    sink: &mut CS,
+) {
+    let bits = u32::from(bits);
+    let size = if bits & 0x200 != 0 { 64 } else { 32 };
+    let amount = amount as u32 & (size - 1);
+    let amount = if bits & 0x400 != 0 {
+        (size - amount) & (size - 1)
+    } else {
+        amount
+    };
+    let mut i = (bits & 0x3ff) << 22;
+    i |= reg(rn) << 16;
+    i |= amount << 10;
+    i |= reg(rn) << 5;
+    i |= reg(rd);
+    sink.put4(i);
+}
+
+/// Move wide with an immediate.
+///
+///   31 30  28     22 20    4
+///   sf opc 100101 hw imm16 rd
+fn put_movewide<CS: CodeSink + ?Sized>(
+    opc: u32,
+    sf: u16,
+    imm16: u16,
+    hw: u32,
+    rd: RegUnit,
+    sink: &mut CS,
+) {
+    let mut i = 0x1280_0000;
+    i |= u32::from(sf) << 31;
+    i |= opc << 29;
+    i |= hw << 21;
+    i |= u32::from(imm16) << 5;
+    i |= reg(rd);
+    sink.put4(i);
+}
+
+/// `movn rd, #imm16, lsl #(16 * hw)`. The encoding bits are `sf`.
+fn put_movn<CS: CodeSink + ?Sized>(sf: u16, imm16: u16, hw: u32, rd: RegUnit, sink: &mut CS) {
+    put_movewide(0b00, sf, imm16, hw, rd, sink);
+}
+
+/// `movz rd, #imm16, lsl #(16 * hw)`. The encoding bits are `sf`.
+fn put_movz<CS: CodeSink + ?Sized>(sf: u16, imm16: u16, hw: u32, rd: RegUnit, sink: &mut CS) {
+    put_movewide(0b10, sf, imm16, hw, rd, sink);
+}
+
+/// `movk rd, #imm16, lsl #(16 * hw)`. The encoding bits are `sf`.
+fn put_movk<CS: CodeSink + ?Sized>(sf: u16, imm16: u16, hw: u32, rd: RegUnit, sink: &mut CS) {
+    put_movewide(0b11, sf, imm16, hw, rd, sink);
+}
+
+/// Conditional select.
+///
+///   31   20 15   11  9  4
+///   opcode rm cond op2 rn rd
+///
+/// Encoding bits: instruction bits 31-21 | (op2 << 11).
+fn put_csel<CS: CodeSink + ?Sized>(
+    bits: u16,
+    rn: RegUnit,
+    rm: RegUnit,
+    cond: u32,
+    rd: RegUnit,
+    sink: &mut CS,
+) {
+    let bits = u32::from(bits);
+    let mut i = (bits & 0x7ff) << 21;
+    i |= reg(rm) << 16;
+    i |= cond << 12;
+    i |= (bits >> 11) << 10;
+    i |= reg(rn) << 5;
+    i |= reg(rd);
+    sink.put4(i);
+}
+
+/// `cset wd, cond`, an alias of `csinc wd, wzr, wzr, !cond`.
+fn put_cset<CS: CodeSink + ?Sized>(cond: u32, rd: RegUnit, sink: &mut CS) {
+    put_csel((1 << 11) | 0x0d4, 31, 31, cond ^ 1, rd, sink);
+}
+
+/// Floating point compare.
+///
+///   31      23   21 20 15     9  4
+///   00011110 type 1  rm 001000 rn 00000
+///
+/// Encoding bits: `type`.
+fn put_fcmp<CS: CodeSink + ?Sized>(bits: u16, rn: RegUnit, rm: RegUnit, sink: &mut CS) {
+    let mut i = 0x1e20_2000;
+    i |= u32::from(bits) << 22;
+    i |= reg(rm) << 16;
+    i |= reg(rn) << 5;
+    sink.put4(i);
+}
+
+/// Floating point conditional select.
+///
+///   31      23   21 20 15   11 9  4
+///   00011110 type 1  rm cond 11 rn rd
+///
+/// Encoding bits: `type`.
+fn put_fcsel<CS: CodeSink + ?Sized>(
+    bits: u16,
+    rn: RegUnit,
+    rm: RegUnit,
+    cond: u32,
+    rd: RegUnit,
+    sink: &mut CS,
+) {
+    let mut i = 0x1e20_0c00;
+    i |= u32::from(bits) << 22;
+    i |= reg(rm) << 16;
+    i |= cond << 12;
+    i |= reg(rn) << 5;
+    i |= reg(rd);
+    sink.put4(i);
+}
+
+/// Floating point data processing, 2 source.
+///
+///   31      23   21 20 15     11 9  4
+///   00011110 type 1  rm opcode 10 rn rd
+///
+/// Encoding bits: `opcode | (type << 4)`.
+fn put_fp2<CS: CodeSink + ?Sized>(bits: u16, rn: RegUnit, rm: RegUnit, rd: RegUnit, sink: &mut CS) {
+    let bits = u32::from(bits);
+    let mut i = 0x1e20_0800;
+    i |= (bits >> 4) << 22;
+    i |= reg(rm) << 16;
+    i |= (bits & 0xf) << 12;
+    i |= reg(rn) << 5;
+    i |= reg(rd);
+    sink.put4(i);
+}
+
+/// Floating point data processing, 1 source.
+///
+///   31      23   21 20     14    9  4
+///   00011110 type 1  opcode 10000 rn rd
+///
+/// Encoding bits: `opcode | (type << 6)`.
+fn put_fp1<CS: CodeSink + ?Sized>(bits: u16, rn: RegUnit, rd: RegUnit, sink: &mut CS) {
+    let bits = u32::from(bits);
+    let mut i = 0x1e20_4000;
+    i |= (bits >> 6) << 22;
+    i |= (bits & 0x3f) << 15;
+    i |= reg(rn) << 5;
+    i |= reg(rd);
+    sink.put4(i);
+}
+
+/// Conversions between floating point and integer registers.
+///
+///   31 30      23   21 20    18     15     9  4
+///   sf 0011110 type 1  rmode opcode 000000 rn rd
+///
+/// Encoding bits: `opcode | (rmode << 3) | (type << 5) | (sf << 7)`.
+fn put_fpint<CS: CodeSink + ?Sized>(bits: u16, rn: RegUnit, rd: RegUnit, sink: &mut CS) {
+    let bits = u32::from(bits);
+    let mut i = 0x1e20_0000;
+    i |= (bits >> 7) << 31;
+    i |= ((bits >> 5) & 3) << 22;
+    i |= ((bits >> 3) & 3) << 19;
+    i |= (bits & 7) << 16;
+    i |= reg(rn) << 5;
+    i |= reg(rd);
+    sink.put4(i);
+}
+
+/// Bitwise logic on 64-bit vectors: `and`, `bic`, `orr`, `orn` and `eor` with the `8b`
+/// arrangement.
+///
+///   31          21 20 15     9  4
+///   0 0 opcode 1   rm 000111 rn rd
+///
+/// Encoding bits: instruction bits 31-21.
+fn put_vlogic<CS: CodeSink + ?Sized>(
+    bits: u16,
+    rn: RegUnit,
+    rm: RegUnit,
+    rd: RegUnit,
+    sink: &mut CS,
+) {
+    let mut i = u32::from(bits) << 21;
+    i |= reg(rm) << 16;
+    i |= 0b00_0111 << 10;
+    i |= reg(rn) << 5;
+    i |= reg(rd);
+    sink.put4(i);
+}
+
+/// Loads and stores with a signed 9-bit byte offset: unscaled, pre-indexed or post-indexed.
+///
+///   31   21 20   11   9  4
+///   opcode 0 imm9 mode rn rt
+///       22   12   10  5  0
+///
+/// Encoding bits: instruction bits 31-22 | (mode << 10).
+fn put_ldst_imm9<CS: CodeSink + ?Sized>(
+    bits: u16,
+    rn: RegUnit,
+    offset: i32,
+    rt: RegUnit,
+    sink: &mut CS,
+) {
+    let bits = u32::from(bits);
+    let mut i = (bits & 0x3ff) << 22;
+    i |= (offset as u32 & 0x1ff) << 12;
+    i |= (bits >> 10) << 10;
+    i |= reg(rn) << 5;
+    i |= reg(rt);
+    sink.put4(i);
+}
+
+/// Loads and stores with a register offset.
+///
+///   31   21 20 15     12 11 9  4
+///   opcode 1  rm option S  10 rn rt
+///
+/// Encoding bits: instruction bits 31-22 of the unscaled form.
+fn put_ldst_reg<CS: CodeSink + ?Sized>(
+    bits: u16,
+    rn: RegUnit,
+    rm: RegUnit,
+    option_s: u32,
+    rt: RegUnit,
+    sink: &mut CS,
+) {
+    let mut i = u32::from(bits) << 22;
+    i |= 1 << 21;
+    i |= reg(rm) << 16;
+    i |= option_s << 12;
+    i |= 0b10 << 10;
+    i |= reg(rn) << 5;
+    i |= reg(rt);
+    sink.put4(i);
+}
+
+/// Load or store at `[rn, wm, sxtw]`.
+fn put_ldst_sxtw<CS: CodeSink + ?Sized>(
+    bits: u16,
+    rn: RegUnit,
+    rm: RegUnit,
+    rt: RegUnit,
+    sink: &mut CS,
+) {
+    put_ldst_reg(bits, rn, rm, 0b1100, rt, sink);
+}
+
+/// Load or store at `[rn, xm, lsl #size]`.
+fn put_ldst_lsl<CS: CodeSink + ?Sized>(
+    bits: u16,
+    rn: RegUnit,
+    rm: RegUnit,
+    rt: RegUnit,
+    sink: &mut CS,
+) {
+    put_ldst_reg(bits, rn, rm, 0b0111, rt, sink);
+}
+
+/// Load or store relative to the stack pointer, using the scaled unsigned 12-bit offset form.
+///
+/// Encoding bits: instruction bits 31-22 of the unscaled form. The access size is taken from the
+/// `size` field in bits 31-30.
+fn put_ldst_sp<CS: CodeSink + ?Sized>(bits: u16, offset: i32, rt: RegUnit, sink: &mut CS) {
+    let scale = 1 << (bits >> 8);
+    assert!(
+        offset >= 0 && offset % scale == 0 && offset / scale < 0x1000,
+        "stack offset {} out of range",
+        offset
+    );
+    let mut i = (u32::from(bits) | 0b100) << 22;
+    i |= ((offset / scale) as u32) << 10;
+    i |= 31 << 5;
+    i |= reg(rt);
+    sink.put4(i);
+}
+
+/// Load or store a pair of 64-bit registers, with a 7-bit offset scaled by 8.
+///
+///   31   21   14  9  4
+///   opcode imm7 rt2 rn rt
+///       22   15  10  5  0
+///
+/// Encoding bits: instruction bits 31-22.
+fn put_ldst_pair<CS: CodeSink + ?Sized>(
+    bits: u16,
+    rn: RegUnit,
+    offset: i32,
+    rt: RegUnit,
+    rt2: RegUnit,
+    sink: &mut CS,
+) {
+    debug_assert_eq!(offset % 8, 0);
+    let mut i = u32::from(bits) << 22;
+    i |= ((offset / 8) as u32 & 0x7f) << 15;
+    i |= reg(rt2) << 10;
+    i |= reg(rn) << 5;
+    i |= reg(rt);
+    sink.put4(i);
+}
+
+/// `ldr xt, #disp`, loading a 64-bit PC-relative literal.
+fn put_ldr_literal<CS: CodeSink + ?Sized>(disp: i64, rt: RegUnit, sink: &mut CS) {
+    let mut i = 0x5800_0000;
+    i |= ((disp >> 2) as u32 & 0x7ffff) << 5;
+    i |= reg(rt);
+    sink.put4(i);
+}
+
+/// `adr xd, #disp`.
+fn put_adr<CS: CodeSink + ?Sized>(disp: i64, rd: RegUnit, sink: &mut CS) {
+    let disp = disp as u32;
+    let mut i = 0x1000_0000;
+    i |= (disp & 3) << 29;
+    i |= ((disp >> 2) & 0x7ffff) << 5;
+    i |= reg(rd);
+    sink.put4(i);
+}
+
+/// `b #disp`.
+fn put_b<CS: CodeSink + ?Sized>(disp: i64, sink: &mut CS) {
+    sink.put4(0x1400_0000 | ((disp >> 2) as u32 & 0x3ff_ffff));
+}
+
+/// `bl #0`. The displacement is filled in by an `Arm64Call` relocation.
+fn put_bl<CS: CodeSink + ?Sized>(sink: &mut CS) {
+    sink.put4(0x9400_0000);
+}
+
+/// `b.cond #disp`.
+fn put_bcond<CS: CodeSink + ?Sized>(cond: u32, disp: i64, sink: &mut CS) {
+    let mut i = 0x5400_0000;
+    i |= ((disp >> 2) as u32 & 0x7ffff) << 5;
+    i |= cond;
+    sink.put4(i);
+}
+
+/// Compare and branch.
+///
+///   31     23   4
+///   opcode imm19 rt
+///       24     5  0
+///
+/// Encoding bits: instruction bits 31-24 (`cbz` or `cbnz`, including `sf`).
+fn put_cbz<CS: CodeSink + ?Sized>(bits: u16, rt: RegUnit, disp: i64, sink: &mut CS) {
+    let mut i = u32::from(bits) << 24;
+    i |= ((disp >> 2) as u32 & 0x7ffff) << 5;
+    i |= reg(rt);
+    sink.put4(i);
+}
+
+/// Test bit and branch if zero: `tbz rt, #bit, #disp`.
+fn put_tbz<CS: CodeSink + ?Sized>(rt: RegUnit, bit: u32, disp: i64, sink: &mut CS) {
+    let mut i = 0x3600_0000;
+    i |= (bit >> 5) << 31;
+    i |= (bit & 0x1f) << 19;
+    i |= ((disp >> 2) as u32 & 0x3fff) << 5;
+    i |= reg(rt);
+    sink.put4(i);
+}
+
+/// System register move, `msr` or `mrs`, with the system register already in `word`.
+fn put_sysreg<CS: CodeSink + ?Sized>(word: u32, rt: RegUnit, sink: &mut CS) {
+    sink.put4(word | reg(rt));
+}
+
+/// Unconditional branch to a register: `br`, `blr` or `ret`.
+///
+/// Encoding bits: instruction bits 31-16.
+fn put_br<CS: CodeSink + ?Sized>(bits: u16, rn: RegUnit, sink: &mut CS) {
+    sink.put4((u32::from(bits) << 16) | (reg(rn) << 5));
+}
+
+/// `udf #0`, the permanently undefined instruction used for traps.
+fn put_udf<CS: CodeSink + ?Sized>(sink: &mut CS) {
+    sink.put4(0x0000_0000);
+}
+
+/// `brk #0`.
+fn put_brk<CS: CodeSink + ?Sized>(sink: &mut CS) {
+    sink.put4(0xd420_0000);
+}
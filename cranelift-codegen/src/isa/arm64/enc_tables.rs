@@ -1,10 +1,17 @@
 //! Encoding tables for ARM64 ISA.
 
+use super::registers::*;
 use crate::ir;
 use crate::isa;
 use crate::isa::constraints::*;
 use crate::isa::enc_tables::*;
-use crate::isa::encoding::RecipeSizing;
+use crate::isa::encoding::{base_size, RecipeSizing};
+use crate::predicates;
 
+// Include the generated encoding tables:
+// - `LEVEL1_A64`
+// - `LEVEL2`
+// - `ENCLIST`
+// - `INFO`
 include!(concat!(env!("OUT_DIR"), "/encoding-arm64.rs"));
 include!(concat!(env!("OUT_DIR"), "/legalize-arm64.rs"));
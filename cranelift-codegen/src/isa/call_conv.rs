@@ -1,5 +1,7 @@
-use crate::isa::TargetIsa;
+use crate::ir::ArgumentPurpose;
+use crate::isa::{RegUnit, TargetIsa};
 use crate::settings::LibcallCallConv;
+use alloc::vec::Vec;
 use core::fmt;
 use core::str;
 use target_lexicon::{CallingConvention, Triple};
@@ -21,6 +23,13 @@ pub enum CallConv {
     BaldrdashWindows,
     /// Specialized convention for the probestack function
     Probestack,
+    /// Convention supporting guaranteed tail calls: the callee pops its stack
+    /// arguments, so `return_call` may pass a larger argument area than it
+    /// received
+    Tail,
+    /// Convention described by the embedder, numbered in the order it was
+    /// registered with `isa::Builder::add_call_conv`
+    Custom(u8),
 }
 
 impl CallConv {
@@ -57,6 +66,14 @@ impl CallConv {
         }
     }
 
+    /// Does the callee pop its own stack arguments before returning?
+    pub fn callee_pops_args(self) -> bool {
+        match self {
+            Self::Tail => true,
+            _ => false,
+        }
+    }
+
     /// Is the calling convention extending the Baldrdash ABI?
     pub fn extends_baldrdash(self) -> bool {
         match self {
@@ -76,6 +93,8 @@ impl fmt::Display for CallConv {
             Self::BaldrdashSystemV => "baldrdash_system_v",
             Self::BaldrdashWindows => "baldrdash_windows",
             Self::Probestack => "probestack",
+            Self::Tail => "tail",
+            Self::Custom(index) => return write!(f, "custom{}", index),
         })
     }
 }
@@ -91,7 +110,55 @@ impl str::FromStr for CallConv {
             "baldrdash_system_v" => Ok(Self::BaldrdashSystemV),
             "baldrdash_windows" => Ok(Self::BaldrdashWindows),
             "probestack" => Ok(Self::Probestack),
+            "tail" => Ok(Self::Tail),
+            _ if s.starts_with("custom") => s["custom".len()..]
+                .parse()
+                .map(Self::Custom)
+                .map_err(|_| ()),
             _ => Err(()),
         }
     }
 }
+
+/// A calling convention described as data by the embedder.
+///
+/// Register it with `isa::Builder::add_call_conv`, which returns the `CallConv` to use in
+/// signatures. Registers are numbered as in the ISA's `RegInfo`, which is available from
+/// `isa::Builder::register_info` before the `TargetIsa` is built.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CustomCallConv {
+    /// Registers for integer, boolean and reference arguments, in order.
+    pub int_args: Vec<RegUnit>,
+    /// Registers for float and vector arguments, in order.
+    pub float_args: Vec<RegUnit>,
+    /// Registers for integer, boolean and reference return values, in order.
+    pub int_rets: Vec<RegUnit>,
+    /// Registers for float and vector return values, in order.
+    pub float_rets: Vec<RegUnit>,
+    /// Fixed registers for special-purpose arguments, such as the `VMContext` pointer.
+    pub special_args: Vec<(ArgumentPurpose, RegUnit)>,
+    /// Registers that a callee must preserve.
+    pub callee_saved: Vec<RegUnit>,
+    /// Registers that are never allocated in functions using this convention, such as pinned
+    /// context registers.
+    pub reserved: Vec<RegUnit>,
+    /// Alignment of the stack pointer at calls, in bytes.
+    pub stack_align: u32,
+    /// Bytes reserved by the caller above the return address, before the stack arguments.
+    pub shadow_space: u32,
+}
+
+impl CustomCallConv {
+    /// Is `reg` used by this convention to pass values or preserve state across calls?
+    pub fn uses_reg(&self, reg: RegUnit) -> bool {
+        self.int_args
+            .iter()
+            .chain(&self.float_args)
+            .chain(&self.int_rets)
+            .chain(&self.float_rets)
+            .chain(self.special_args.iter().map(|(_, reg)| reg))
+            .chain(&self.callee_saved)
+            .chain(&self.reserved)
+            .any(|&r| r == reg)
+    }
+}
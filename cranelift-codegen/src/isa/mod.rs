@@ -46,7 +46,7 @@
 //! The configured target ISA trait object is a `Box<TargetIsa>` which can be used for multiple
 //! concurrent function compilations.
 
-pub use crate::isa::call_conv::CallConv;
+pub use crate::isa::call_conv::{CallConv, CustomCallConv};
 pub use crate::isa::constraints::{
     BranchRange, ConstraintKind, OperandConstraint, RecipeConstraints,
 };
@@ -142,14 +142,37 @@ pub enum LookupError {
 pub struct Builder {
     triple: Triple,
     setup: settings::Builder,
-    constructor: fn(Triple, settings::Flags, settings::Builder) -> Box<dyn TargetIsa>,
+    register_info: RegInfo,
+    call_convs: Vec<CustomCallConv>,
+    constructor:
+        fn(Triple, settings::Flags, settings::Builder, Vec<CustomCallConv>) -> Box<dyn TargetIsa>,
 }
 
 impl Builder {
+    /// Get the register information of the ISA, to name registers in a `CustomCallConv`.
+    pub fn register_info(&self) -> RegInfo {
+        self.register_info.clone()
+    }
+
+    /// Register a calling convention described by the embedder, returning the `CallConv` that
+    /// refers to it in signatures.
+    ///
+    /// Only x86 supports custom calling conventions. Other ISAs ignore them, and the verifier
+    /// rejects the signatures using them, as well as those using a convention that the ISA's
+    /// `check_custom_call_conv` refuses.
+    pub fn add_call_conv(&mut self, call_conv: CustomCallConv) -> CallConv {
+        assert!(
+            self.call_convs.len() <= usize::from(u8::max_value()),
+            "too many custom calling conventions"
+        );
+        self.call_convs.push(call_conv);
+        CallConv::Custom((self.call_convs.len() - 1) as u8)
+    }
+
     /// Combine the ISA-specific settings with the provided ISA-independent settings and allocate a
     /// fully configured `TargetIsa` trait object.
     pub fn finish(self, shared_flags: settings::Flags) -> Box<dyn TargetIsa> {
-        (self.constructor)(self.triple, shared_flags, self.setup)
+        (self.constructor)(self.triple, shared_flags, self.setup, self.call_convs)
     }
 }
 
@@ -215,6 +238,18 @@ pub trait TargetIsa: fmt::Display + Sync {
         CallConv::triple_default(self.triple())
     }
 
+    /// Get the convention registered as `CallConv::Custom(index)` with `Builder::add_call_conv`.
+    fn custom_call_conv(&self, _index: u8) -> Option<&CustomCallConv> {
+        None
+    }
+
+    /// Check that the ABI code of this ISA can handle the registered convention `conv`.
+    ///
+    /// The verifier rejects functions that use or call a convention failing this check.
+    fn check_custom_call_conv(&self, _conv: &CustomCallConv) -> CodegenResult<()> {
+        Ok(())
+    }
+
     /// Get the pointer type of this ISA.
     fn pointer_type(&self) -> ir::Type {
         ir::Type::int(u16::from(self.pointer_bits())).unwrap()
@@ -332,6 +367,43 @@ pub trait TargetIsa: fmt::Display + Sync {
     /// registers.
     fn allocatable_registers(&self, func: &ir::Function) -> regalloc::RegisterSet;
 
+    /// Can a value of type `ty` in a register of class `rc` stay there across a call with the
+    /// signature `sig`, instead of being spilled?
+    ///
+    /// The callee must preserve all the registers of `rc`, except for the ones holding its return
+    /// values, which register allocation moves live values out of.
+    fn is_preserved_across_call(&self, _sig: &ir::Signature, _rc: RegClass, _ty: ir::Type) -> bool {
+        false
+    }
+
+    /// Can this ISA pass structs by value in `StructArgument` parameters?
+    ///
+    /// The verifier rejects signatures with struct arguments on ISAs that can't.
+    fn supports_struct_args(&self) -> bool {
+        false
+    }
+
+    /// Can this ISA compute the address of thread-local symbols?
+    ///
+    /// The verifier rejects thread-local symbols on ISAs that can't.
+    fn supports_tls_values(&self) -> bool {
+        false
+    }
+
+    /// Can this ISA emit `return_call` and `return_call_indirect` instructions?
+    ///
+    /// The verifier rejects tail calls on ISAs that can't.
+    fn supports_tail_calls(&self) -> bool {
+        false
+    }
+
+    /// Get the register in which the unwinder passes the exception pointer to a landing pad.
+    ///
+    /// This is `None` if the ISA doesn't support `invoke` instructions.
+    fn exception_pointer_reg(&self) -> Option<RegUnit> {
+        None
+    }
+
     /// Compute the stack layout and insert prologue and epilogue code into `func`.
     ///
     /// Return an error if the stack frame is too large.
@@ -380,10 +452,16 @@ pub trait TargetIsa: fmt::Display + Sync {
     /// IntCC condition for Unsigned Subtraction Overflow (Borrow/Carry).
     fn unsigned_sub_overflow_condition(&self) -> ir::condcodes::IntCC;
 
-    /// Emit unwind information for the given function.
+    /// Emit unwind information of the given `kind` for the given function.
     ///
-    /// Only some calling conventions (e.g. Windows fastcall) will have unwind information.
-    fn emit_unwind_info(&self, _func: &ir::Function, _mem: &mut Vec<u8>) {
+    /// Only some calling conventions (e.g. Windows fastcall and System V on x86-64) will have
+    /// unwind information, and nothing is emitted if the function has none of the requested kind.
+    fn emit_unwind_info(
+        &self,
+        _func: &ir::Function,
+        _kind: binemit::FrameUnwindKind,
+        _sink: &mut dyn binemit::FrameUnwindSink,
+    ) {
         // No-op by default
     }
 }
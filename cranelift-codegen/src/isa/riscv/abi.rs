@@ -3,12 +3,14 @@
 //! This module implements the RISC-V calling convention through the primary `legalize_signature()`
 //! entry point.
 //!
-//! This doesn't support the soft-float ABI at the moment.
+//! Floating point arguments are passed in the `fa0-fa7` registers when the F or D extension is
+//! enabled and the value fits in a floating point register. Otherwise they are passed like
+//! integers, which makes this the soft-float ABI when neither extension is enabled.
 
 use super::registers::{FPR, GPR};
 use super::settings;
 use crate::abi::{legalize_args, ArgAction, ArgAssigner, ValueConversion};
-use crate::ir::{self, AbiParam, ArgumentExtension, ArgumentLoc, ArgumentPurpose, Type};
+use crate::ir::{self, types, AbiParam, ArgumentExtension, ArgumentLoc, ArgumentPurpose, Type};
 use crate::isa::RegClass;
 use crate::regalloc::RegisterSet;
 use alloc::borrow::Cow;
@@ -21,17 +23,29 @@ struct Args {
     pointer_type: Type,
     regs: u32,
     reg_limit: u32,
+    /// Width of the floating point registers, or 0 when there are none.
+    flen: u16,
+    fpr_regs: u32,
     offset: u32,
 }
 
 impl Args {
-    fn new(bits: u8, enable_e: bool) -> Self {
+    fn new(bits: u8, isa_flags: &settings::Flags) -> Self {
+        let flen = if isa_flags.use_d() {
+            64
+        } else if isa_flags.use_f() {
+            32
+        } else {
+            0
+        };
         Self {
             pointer_bits: bits,
             pointer_bytes: bits / 8,
             pointer_type: Type::int(u16::from(bits)).unwrap(),
             regs: 0,
-            reg_limit: if enable_e { 6 } else { 8 },
+            reg_limit: if isa_flags.enable_e() { 6 } else { 8 },
+            flen,
+            fpr_regs: 0,
             offset: 0,
         }
     }
@@ -45,14 +59,30 @@ impl ArgAssigner for Args {
 
         let ty = arg.value_type;
 
+        if arg.purpose.struct_layout().is_some() {
+            unreachable!("the verifier rejects struct arguments on riscv");
+        }
+
         // Check for a legal type.
         // RISC-V doesn't have SIMD at all, so break all vectors down.
         if ty.is_vector() {
             return ValueConversion::VectorSplit.into();
         }
 
+        // Floats go in their own argument registers while they last. Floats that don't fit in a
+        // floating point register, or that come after the registers run out, are passed according
+        // to the integer calling convention.
+        if ty.is_float() {
+            if ty.bits() <= self.flen && self.fpr_regs < 8 {
+                let reg = FPR.unit(10 + self.fpr_regs as usize);
+                self.fpr_regs += 1;
+                return ArgumentLoc::Reg(reg).into();
+            }
+            return ValueConversion::IntBits.into();
+        }
+
         // Large integers and booleans are broken down to fit in a register.
-        if !ty.is_float() && ty.bits() > u16::from(self.pointer_bits) {
+        if ty.bits() > u16::from(self.pointer_bits) {
             // Align registers and stack to a multiple of two pointers.
             self.regs = align(self.regs, 2);
             self.offset = align(self.offset, 2 * u32::from(self.pointer_bytes));
@@ -70,11 +100,7 @@ impl ArgAssigner for Args {
 
         if self.regs < self.reg_limit {
             // Assign to a register.
-            let reg = if ty.is_float() {
-                FPR.unit(10 + self.regs as usize)
-            } else {
-                GPR.unit(10 + self.regs as usize)
-            };
+            let reg = GPR.unit(10 + self.regs as usize);
             self.regs += 1;
             ArgumentLoc::Reg(reg).into()
         } else {
@@ -96,12 +122,12 @@ pub fn legalize_signature(
 ) {
     let bits = triple.pointer_width().unwrap().bits();
 
-    let mut args = Args::new(bits, isa_flags.enable_e());
+    let mut args = Args::new(bits, isa_flags);
     if let Some(new_params) = legalize_args(&sig.params, &mut args) {
         sig.to_mut().params = new_params;
     }
 
-    let mut rets = Args::new(bits, isa_flags.enable_e());
+    let mut rets = Args::new(bits, isa_flags);
     if let Some(new_returns) = legalize_args(&sig.returns, &mut rets) {
         sig.to_mut().returns = new_returns;
     }
@@ -121,8 +147,15 @@ pub fn legalize_signature(
 }
 
 /// Get register class for a type appearing in a legalized signature.
-pub fn regclass_for_abi_type(ty: Type) -> RegClass {
-    if ty.is_float() {
+///
+/// Soft floats live in the integer registers.
+pub fn regclass_for_abi_type(ty: Type, isa_flags: &settings::Flags) -> RegClass {
+    let hard_float = match ty {
+        types::F32 => isa_flags.use_f(),
+        types::F64 => isa_flags.use_d(),
+        _ => false,
+    };
+    if hard_float {
         FPR
     } else {
         GPR
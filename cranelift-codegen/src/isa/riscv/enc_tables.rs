@@ -1,7 +1,9 @@
 //! Encoding tables for RISC-V.
 
 use super::registers::*;
-use crate::ir;
+use crate::cursor::{Cursor, FuncCursor};
+use crate::flowgraph::ControlFlowGraph;
+use crate::ir::{self, InstBuilder};
 use crate::isa;
 use crate::isa::constraints::*;
 use crate::isa::enc_tables::*;
@@ -16,3 +18,71 @@ use crate::predicates;
 // - `INFO`
 include!(concat!(env!("OUT_DIR"), "/encoding-riscv.rs"));
 include!(concat!(env!("OUT_DIR"), "/legalize-riscv.rs"));
+
+/// Expand the `sdiv` instruction into explicit checks for a zero divisor and for an overflowing
+/// `INT_MIN / -1`, followed by a `riscv_div` which doesn't trap.
+fn expand_sdiv(
+    inst: ir::Inst,
+    func: &mut ir::Function,
+    _cfg: &mut ControlFlowGraph,
+    isa: &dyn isa::TargetIsa,
+) {
+    let (x, y) = match func.dfg[inst] {
+        ir::InstructionData::Binary {
+            opcode: ir::Opcode::Sdiv,
+            args,
+        } => (args[0], args[1]),
+        _ => panic!("Need sdiv: {}", func.dfg.display_inst(inst, None)),
+    };
+    let ty = func.dfg.ctrl_typevar(inst);
+
+    let mut pos = FuncCursor::new(func).at_inst(inst);
+    pos.use_srcloc(inst);
+
+    // A constant divisor other than 0 and -1 needs no checks.
+    let (could_be_zero, could_be_minus_one) = match iconst_imm(&pos.func.dfg, y) {
+        Some(imm) => (imm == 0, imm == -1),
+        None => (true, true),
+    };
+
+    // RV64 has no 32-bit branches, so the checks are done on whole registers, in which 32-bit
+    // values are kept sign-extended.
+    let (x_word, y_word, word) = if ty == ir::types::I32 && isa.pointer_bits() == 64 {
+        let x = pos.ins().sextend(ir::types::I64, x);
+        let y = pos.ins().sextend(ir::types::I64, y);
+        (x, y, ir::types::I64)
+    } else {
+        (x, y, ty)
+    };
+
+    if could_be_zero {
+        pos.ins().trapz(y_word, ir::TrapCode::IntegerDivisionByZero);
+    }
+
+    if could_be_minus_one {
+        // The division overflows if `y + 1` and `x ^ INT_MIN` are both zero. `INT_MIN` is
+        // computed with a shift, since most of its bits are clear.
+        let y_plus_one = pos.ins().iadd_imm(y_word, 1);
+        let minus_one = pos.ins().iconst(word, -1);
+        let min = pos.ins().ishl_imm(minus_one, i64::from(ty.lane_bits() - 1));
+        let x_min = pos.ins().bxor(x_word, min);
+        let overflow = pos.ins().bor(y_plus_one, x_min);
+        pos.ins().trapz(overflow, ir::TrapCode::IntegerOverflow);
+    }
+
+    pos.func.dfg.replace(inst).riscv_div(x, y);
+}
+
+/// Get the value of `value` if it is defined by an `iconst` instruction.
+fn iconst_imm(dfg: &ir::DataFlowGraph, value: ir::Value) -> Option<i64> {
+    if let ir::ValueDef::Result(inst, _) = dfg.value_def(value) {
+        if let ir::InstructionData::UnaryImm {
+            opcode: ir::Opcode::Iconst,
+            imm,
+        } = dfg[inst]
+        {
+            return Some(imm.into());
+        }
+    }
+    None
+}
@@ -13,10 +13,11 @@ use crate::binemit::{emit_function, MemoryCodeSink};
 use crate::ir;
 use crate::isa::enc_tables::{self as shared_enc_tables, lookup_enclist, Encodings};
 use crate::isa::Builder as IsaBuilder;
-use crate::isa::{EncInfo, RegClass, RegInfo, TargetIsa};
+use crate::isa::{CustomCallConv, EncInfo, RegClass, RegInfo, TargetIsa};
 use crate::regalloc;
 use alloc::borrow::Cow;
 use alloc::boxed::Box;
+use alloc::vec::Vec;
 use core::fmt;
 use target_lexicon::{PointerWidth, Triple};
 
@@ -33,6 +34,8 @@ pub fn isa_builder(triple: Triple) -> IsaBuilder {
     IsaBuilder {
         triple,
         setup: settings::builder(),
+        register_info: registers::INFO.clone(),
+        call_convs: Vec::new(),
         constructor: isa_constructor,
     }
 }
@@ -41,6 +44,8 @@ fn isa_constructor(
     triple: Triple,
     shared_flags: shared_settings::Flags,
     builder: shared_settings::Builder,
+    // Custom calling conventions aren't supported, so the verifier rejects their uses.
+    _call_convs: Vec<CustomCallConv>,
 ) -> Box<dyn TargetIsa> {
     let level1 = match triple.pointer_width().unwrap() {
         PointerWidth::U16 => panic!("16-bit RISC-V unrecognized"),
@@ -101,7 +106,7 @@ impl TargetIsa for Isa {
     }
 
     fn regclass_for_abi_type(&self, ty: ir::Type) -> RegClass {
-        abi::regclass_for_abi_type(ty)
+        abi::regclass_for_abi_type(ty, &self.isa_flags)
     }
 
     fn allocatable_registers(&self, func: &ir::Function) -> regalloc::RegisterSet {
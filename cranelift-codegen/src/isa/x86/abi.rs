@@ -1,58 +1,105 @@
 //! x86 ABI implementation.
 
 use super::super::settings as shared_settings;
+use super::fde::emit_fde;
 use super::registers::{FPR, GPR, RU};
 use super::settings as isa_settings;
 use super::unwind::UnwindInfo;
 use crate::abi::{legalize_args, ArgAction, ArgAssigner, ValueConversion};
+use crate::binemit::{FrameUnwindKind, FrameUnwindSink};
 use crate::cursor::{Cursor, CursorPosition, EncCursor};
 use crate::ir;
 use crate::ir::immediates::Imm64;
 use crate::ir::stackslot::{StackOffset, StackSize};
 use crate::ir::{
-    get_probestack_funcref, AbiParam, ArgumentExtension, ArgumentLoc, ArgumentPurpose, InstBuilder,
-    ValueLoc,
+    get_probestack_funcref, types, AbiParam, ArgumentExtension, ArgumentLoc, ArgumentPurpose,
+    InstBuilder, StructLayout, ValueLoc,
 };
-use crate::isa::{CallConv, RegClass, RegUnit, TargetIsa};
-use crate::regalloc::RegisterSet;
-use crate::result::CodegenResult;
+use crate::isa::{CallConv, CustomCallConv, RegClass, RegUnit, TargetIsa};
+use crate::regalloc::{RegDiversions, RegisterSet};
+use crate::result::{CodegenError, CodegenResult};
 use crate::stack_layout::layout_stack;
 use alloc::borrow::Cow;
+use alloc::string::ToString;
 use alloc::vec::Vec;
+use core::cmp::max;
 use core::i32;
 use target_lexicon::{PointerWidth, Triple};
 
 /// Argument registers for x86-64
-static ARG_GPRS: [RU; 6] = [RU::rdi, RU::rsi, RU::rdx, RU::rcx, RU::r8, RU::r9];
+static ARG_GPRS: [RegUnit; 6] = [
+    RU::rdi as RegUnit,
+    RU::rsi as RegUnit,
+    RU::rdx as RegUnit,
+    RU::rcx as RegUnit,
+    RU::r8 as RegUnit,
+    RU::r9 as RegUnit,
+];
 
 /// Return value registers.
-static RET_GPRS: [RU; 3] = [RU::rax, RU::rdx, RU::rcx];
+static RET_GPRS: [RegUnit; 3] = [RU::rax as RegUnit, RU::rdx as RegUnit, RU::rcx as RegUnit];
+
+/// Argument registers for x86-64, when using the fast calling convention.
+///
+/// R12 and R13 are caller-saved under this convention. R10 and R11 are kept free as scratch
+/// registers for prologues and tail calls.
+static ARG_GPRS_FAST_X64: [RegUnit; 8] = [
+    RU::rdi as RegUnit,
+    RU::rsi as RegUnit,
+    RU::rdx as RegUnit,
+    RU::rcx as RegUnit,
+    RU::r8 as RegUnit,
+    RU::r9 as RegUnit,
+    RU::r12 as RegUnit,
+    RU::r13 as RegUnit,
+];
+
+/// Return value registers for x86-64, when using the fast calling convention.
+static RET_GPRS_FAST_X64: [RegUnit; 6] = [
+    RU::rax as RegUnit,
+    RU::rdx as RegUnit,
+    RU::rcx as RegUnit,
+    RU::rsi as RegUnit,
+    RU::rdi as RegUnit,
+    RU::r8 as RegUnit,
+];
 
 /// Argument registers for x86-64, when using windows fastcall
-static ARG_GPRS_WIN_FASTCALL_X64: [RU; 4] = [RU::rcx, RU::rdx, RU::r8, RU::r9];
+static ARG_GPRS_WIN_FASTCALL_X64: [RegUnit; 4] = [
+    RU::rcx as RegUnit,
+    RU::rdx as RegUnit,
+    RU::r8 as RegUnit,
+    RU::r9 as RegUnit,
+];
 
 /// Return value registers for x86-64, when using windows fastcall
-static RET_GPRS_WIN_FASTCALL_X64: [RU; 1] = [RU::rax];
+static RET_GPRS_WIN_FASTCALL_X64: [RegUnit; 1] = [RU::rax as RegUnit];
 
-struct Args {
+struct Args<'a> {
     pointer_bytes: u8,
     pointer_bits: u8,
     pointer_type: ir::Type,
-    gpr: &'static [RU],
+    gpr: &'a [RegUnit],
     gpr_used: usize,
+    /// The float registers of a custom convention, instead of the first `fpr_limit` XMM registers.
+    fpr: Option<&'a [RegUnit]>,
     fpr_limit: usize,
     fpr_used: usize,
     offset: u32,
     call_conv: CallConv,
+    special: &'a [(ArgumentPurpose, RegUnit)],
+    /// The number of parts left of the struct argument being assigned.
+    struct_parts: u32,
+    /// Are the parts of the current struct argument passed on the stack?
+    struct_in_memory: bool,
     shared_flags: shared_settings::Flags,
-    #[allow(dead_code)]
     isa_flags: isa_settings::Flags,
 }
 
-impl Args {
+impl<'a> Args<'a> {
     fn new(
         bits: u8,
-        gpr: &'static [RU],
+        gpr: &'a [RegUnit],
         fpr_limit: usize,
         call_conv: CallConv,
         shared_flags: &shared_settings::Flags,
@@ -72,24 +119,116 @@ impl Args {
             pointer_type: ir::Type::int(u16::from(bits)).unwrap(),
             gpr,
             gpr_used: 0,
+            fpr: None,
             fpr_limit,
             fpr_used: 0,
             offset,
             call_conv,
+            special: &[],
+            struct_parts: 0,
+            struct_in_memory: false,
             shared_flags: shared_flags.clone(),
             isa_flags: isa_flags.clone(),
         }
     }
+
+    /// Create an assigner for the arguments, or the return values if `rets` is set, of the
+    /// custom convention `conv`.
+    fn custom(
+        bits: u8,
+        conv: &'a CustomCallConv,
+        rets: bool,
+        call_conv: CallConv,
+        shared_flags: &shared_settings::Flags,
+        isa_flags: &isa_settings::Flags,
+    ) -> Self {
+        let (gpr, fpr) = if rets {
+            (&conv.int_rets, &conv.float_rets)
+        } else {
+            (&conv.int_args, &conv.float_args)
+        };
+        let mut args = Self::new(bits, gpr, fpr.len(), call_conv, shared_flags, isa_flags);
+        args.fpr = Some(fpr);
+        args.offset = conv.shadow_space;
+        if !rets {
+            args.special = &conv.special_args;
+        }
+        args
+    }
+
+    /// Get the `index`th float or vector register.
+    fn fpr(&self, index: usize) -> RegUnit {
+        match self.fpr {
+            Some(fpr) => fpr[index],
+            None => FPR.unit(index),
+        }
+    }
+
+    /// Decide where to pass the struct argument with `layout`, whose parts are assigned next.
+    ///
+    /// This implements the classification of the System V x86-64 ABI: a struct larger than two
+    /// eightbytes has class MEMORY, and is passed on the stack. Otherwise, each of its eightbytes
+    /// has class SSE if it only contains floats, and INTEGER if not. The struct is passed in
+    /// registers if there are enough left for all its eightbytes, and on the stack if not.
+    ///
+    /// Windows x64 passes the one part of a struct like an integer. 32-bit x86 passes structs on
+    /// the stack.
+    fn start_struct(&mut self, layout: StructLayout) {
+        if self.pointer_bits == 32 {
+            self.struct_parts = (layout.size + 3) / 4;
+            self.struct_in_memory = true;
+        } else if self.call_conv.extends_windows_fastcall() {
+            self.struct_parts = 1;
+            self.struct_in_memory = false;
+        } else {
+            let parts = layout.eightbytes();
+            let floats = (0..parts).filter(|&n| layout.is_float_eightbyte(n)).count();
+            let ints = parts as usize - floats;
+            self.struct_parts = parts;
+            self.struct_in_memory = layout.size > 16
+                || self.gpr_used + ints > self.gpr.len()
+                || self.fpr_used + floats > self.fpr_limit;
+        }
+
+        if self.struct_in_memory {
+            // Keep the struct aligned on the stack, up to the stack alignment.
+            let align = layout.align.max(u32::from(self.pointer_bytes)).min(16);
+            self.offset = (self.offset + align - 1) & !(align - 1);
+        }
+    }
+
+    /// Assign the next stack location.
+    fn stack(&mut self) -> ArgAction {
+        let loc = ArgumentLoc::Stack(self.offset as i32);
+        self.offset += u32::from(self.pointer_bytes);
+        debug_assert!(self.offset <= i32::MAX as u32);
+        loc.into()
+    }
 }
 
-impl ArgAssigner for Args {
+impl<'a> ArgAssigner for Args<'a> {
     fn assign(&mut self, arg: &AbiParam) -> ArgAction {
         let ty = arg.value_type;
 
-        // Vectors should stay in vector registers unless SIMD is not enabled--then they are split
+        // The parts of a struct argument either all go in registers, or all on the stack.
+        if let ArgumentPurpose::StructArgument(layout) = arg.purpose {
+            if self.struct_parts == 0 {
+                self.start_struct(layout);
+            }
+            self.struct_parts -= 1;
+            if self.struct_in_memory {
+                return self.stack();
+            }
+        }
+
+        // Vectors should stay in vector registers unless SIMD is not enabled--then they are split.
+        // Without AVX, vectors wider than an XMM register are split as well.
         if ty.is_vector() {
-            if self.shared_flags.enable_simd() {
-                let reg = FPR.unit(self.fpr_used);
+            if self.shared_flags.enable_simd()
+                && (ty.bits() <= 128 || self.isa_flags.has_avx())
+                && (self.fpr.is_none() || self.fpr_used < self.fpr_limit)
+            {
+                let reg = self.fpr(self.fpr_used);
                 self.fpr_used += 1;
                 return ArgumentLoc::Reg(reg).into();
             }
@@ -135,9 +274,14 @@ impl ArgAssigner for Args {
             }
         }
 
+        // Special-purpose arguments of a custom convention go in their fixed registers.
+        if let Some(&(_, reg)) = self.special.iter().find(|&&(p, _)| p == arg.purpose) {
+            return ArgumentLoc::Reg(reg).into();
+        }
+
         // Try to use a GPR.
         if !ty.is_float() && self.gpr_used < self.gpr.len() {
-            let reg = self.gpr[self.gpr_used] as RegUnit;
+            let reg = self.gpr[self.gpr_used];
             self.gpr_used += 1;
             return ArgumentLoc::Reg(reg).into();
         }
@@ -154,19 +298,75 @@ impl ArgAssigner for Args {
         };
 
         if ty.is_float() && *fpr_offset < self.fpr_limit {
-            let reg = FPR.unit(*fpr_offset);
+            let index = *fpr_offset;
             *fpr_offset += 1;
+            let reg = self.fpr(index);
             return ArgumentLoc::Reg(reg).into();
         }
 
         // Assign a stack location.
-        let loc = ArgumentLoc::Stack(self.offset as i32);
-        self.offset += u32::from(self.pointer_bytes);
-        debug_assert!(self.offset <= i32::MAX as u32);
-        loc.into()
+        self.stack()
     }
 }
 
+/// Replace the struct arguments in `params` with the parts that are passed for them.
+///
+/// On System V x86-64, each eightbyte of a struct is a part, which is an `f64` if it only
+/// contains floats, or an `f32` if these are the last four bytes of the struct, and an `i64`
+/// otherwise. `Args` then decides if the parts go in registers or on the stack.
+///
+/// Windows x64 passes structs of 1, 2, 4 or 8 bytes as an `i64`, and other structs by reference.
+/// 32-bit x86 copies structs onto the stack, one `i32` at a time.
+fn expand_struct_args(params: &[AbiParam], bits: u8, call_conv: CallConv) -> Option<Vec<AbiParam>> {
+    let is_struct_arg = |p: &AbiParam| match p.purpose {
+        ArgumentPurpose::StructArgument(_) => p.location == ArgumentLoc::Unassigned,
+        _ => false,
+    };
+    if !params.iter().any(is_struct_arg) {
+        return None;
+    }
+
+    let mut new_params = Vec::with_capacity(params.len());
+    for param in params {
+        let layout = match param.purpose {
+            ArgumentPurpose::StructArgument(layout) if is_struct_arg(param) => layout,
+            _ => {
+                new_params.push(*param);
+                continue;
+            }
+        };
+        debug_assert!(layout.size > 0, "empty struct argument");
+        let part = |value_type| AbiParam::special(value_type, param.purpose);
+
+        if bits == 32 {
+            for _ in 0..(layout.size + 3) / 4 {
+                new_params.push(part(types::I32));
+            }
+        } else if call_conv.extends_windows_fastcall() {
+            match layout.size {
+                1 | 2 | 4 | 8 => new_params.push(part(types::I64)),
+                _ => new_params.push(AbiParam::special(
+                    types::I64,
+                    ArgumentPurpose::StructReference(layout),
+                )),
+            }
+        } else {
+            for n in 0..layout.eightbytes() {
+                let left = layout.size - 8 * n;
+                let float = layout.size <= 16 && layout.is_float_eightbyte(n);
+                new_params.push(part(if float && left >= 8 {
+                    types::F64
+                } else if float && left == 4 {
+                    types::F32
+                } else {
+                    types::I64
+                }));
+            }
+        }
+    }
+    Some(new_params)
+}
+
 /// Get the number of general-purpose and floating-point registers required to
 /// hold the given `AbiParam` returns.
 fn num_return_registers_required<'a>(
@@ -179,7 +379,7 @@ fn num_return_registers_required<'a>(
     // Pretend we have "infinite" registers to give out, since we aren't
     // actually assigning `AbiParam`s to registers yet, just seeing how many
     // registers we would need in order to fit all the `AbiParam`s in registers.
-    let gprs = &[RU::rax; 128];
+    let gprs = &[RU::rax as RegUnit; 128];
     let fpr_limit = std::usize::MAX;
 
     let mut assigner = Args::new(
@@ -279,9 +479,10 @@ fn num_return_registers_required<'a>(
 pub fn legalize_signature(
     sig: &mut Cow<ir::Signature>,
     triple: &Triple,
-    _current: bool,
+    current: bool,
     shared_flags: &shared_settings::Flags,
     isa_flags: &isa_settings::Flags,
+    call_convs: &[CustomCallConv],
 ) {
     let bits;
     let mut args;
@@ -303,6 +504,15 @@ pub fn legalize_signature(
                     shared_flags,
                     isa_flags,
                 )
+            } else if sig.call_conv == CallConv::Fast {
+                Args::new(
+                    bits,
+                    &ARG_GPRS_FAST_X64[..],
+                    12,
+                    sig.call_conv,
+                    shared_flags,
+                    isa_flags,
+                )
             } else {
                 Args::new(
                     bits,
@@ -319,6 +529,8 @@ pub fn legalize_signature(
     let (ret_regs, ret_fpr_limit) = if sig.call_conv.extends_windows_fastcall() {
         // windows-x64 calling convention only uses XMM0 or RAX for return values
         (&RET_GPRS_WIN_FASTCALL_X64[..], 1)
+    } else if sig.call_conv == CallConv::Fast && bits == 64 {
+        (&RET_GPRS_FAST_X64[..], 4)
     } else {
         (&RET_GPRS[..], 2)
     };
@@ -332,6 +544,14 @@ pub fn legalize_signature(
         isa_flags,
     );
 
+    if let CallConv::Custom(index) = sig.call_conv {
+        let conv = call_convs
+            .get(usize::from(index))
+            .expect("unregistered custom calling convention");
+        args = Args::custom(bits, conv, false, sig.call_conv, shared_flags, isa_flags);
+        rets = Args::custom(bits, conv, true, sig.call_conv, shared_flags, isa_flags);
+    }
+
     if sig.is_multi_return() && {
         // Even if it is multi-return, see if the return values will fit into
         // our available return registers.
@@ -342,7 +562,7 @@ pub fn legalize_signature(
             isa_flags,
             &sig.returns,
         );
-        gprs_required > ret_regs.len() || fprs_required > ret_fpr_limit
+        gprs_required > rets.gpr.len() || fprs_required > rets.fpr_limit
     } {
         debug_assert!(!sig.uses_struct_return_param());
 
@@ -389,8 +609,16 @@ pub fn legalize_signature(
         });
     }
 
-    if let Some(new_params) = legalize_args(&sig.params, &mut args) {
-        sig.to_mut().params = new_params;
+    if let Some(fixed) = sig.fixed_params() {
+        legalize_variadic_params(sig, fixed, bits, current, &mut args);
+    } else {
+        if let Some(new_params) = expand_struct_args(&sig.params, bits, sig.call_conv) {
+            sig.to_mut().params = new_params;
+        }
+
+        if let Some(new_params) = legalize_args(&sig.params, &mut args) {
+            sig.to_mut().params = new_params;
+        }
     }
 
     if let Some(new_returns) = legalize_args(&sig.returns, &mut rets) {
@@ -398,6 +626,71 @@ pub fn legalize_signature(
     }
 }
 
+/// Legalize the parameters of the variadic signature `sig`, whose first `fixed` parameters are
+/// the fixed ones.
+///
+/// The fixed and the variadic parameters are legalized one after the other, to keep track of
+/// where the variadic ones start. The signature of a call also gets the hidden arguments passed to
+/// variadic functions: on System V x86-64, the number of vector registers used, in `%al`. On
+/// Windows x64, a copy of each float argument in the integer register of its position.
+fn legalize_variadic_params(
+    sig: &mut Cow<ir::Signature>,
+    fixed: usize,
+    bits: u8,
+    current: bool,
+    args: &mut Args,
+) {
+    let call_conv = sig.call_conv;
+    let legalize = |params: &[AbiParam], args: &mut Args| {
+        let expanded = expand_struct_args(params, bits, call_conv);
+        let params = expanded.as_ref().map_or(params, |p| &p[..]);
+        legalize_args(params, args).unwrap_or_else(|| params.to_vec())
+    };
+    let (fixed_params, variadic_params) = sig.params.split_at(fixed);
+    let mut params = legalize(fixed_params, args);
+    let fixed = params.len();
+    params.extend(legalize(variadic_params, args));
+
+    let has_hidden_args = params.iter().any(|p| match p.purpose {
+        ArgumentPurpose::VectorCount | ArgumentPurpose::FloatCopy => true,
+        _ => false,
+    });
+    if !current && !has_hidden_args && bits == 64 {
+        if call_conv.extends_windows_fastcall() {
+            let copies = params[fixed..]
+                .iter()
+                .filter_map(|p| match p.location {
+                    ArgumentLoc::Reg(reg) if p.value_type.is_float() => {
+                        let index =
+                            (0..ARG_GPRS_WIN_FASTCALL_X64.len()).find(|&i| FPR.unit(i) == reg)?;
+                        Some(AbiParam::special_reg(
+                            ir::Type::int(p.value_type.bits()).unwrap(),
+                            ArgumentPurpose::FloatCopy,
+                            ARG_GPRS_WIN_FASTCALL_X64[index],
+                        ))
+                    }
+                    _ => None,
+                })
+                .collect::<Vec<_>>();
+            params.extend(copies);
+        } else {
+            params.push(AbiParam::special_reg(
+                types::I64,
+                ArgumentPurpose::VectorCount,
+                RU::rax as RegUnit,
+            ));
+        }
+    }
+
+    if params != sig.params || sig.fixed_params() != Some(fixed) {
+        let variadic_params = params.split_off(fixed);
+        let sig = sig.to_mut();
+        sig.params = params;
+        sig.make_variadic();
+        sig.params.extend(variadic_params);
+    }
+}
+
 /// Get register class for a type appearing in a legalized signature.
 pub fn regclass_for_abi_type(ty: ir::Type) -> RegClass {
     if ty.is_int() || ty.is_bool() {
@@ -408,13 +701,14 @@ pub fn regclass_for_abi_type(ty: ir::Type) -> RegClass {
 }
 
 /// Get the set of allocatable registers for `func`.
-pub fn allocatable_registers(triple: &Triple, flags: &shared_settings::Flags) -> RegisterSet {
+pub fn allocatable_registers(func: &ir::Function, isa: &dyn TargetIsa) -> RegisterSet {
+    let flags = isa.flags();
     let mut regs = RegisterSet::new();
     regs.take(GPR, RU::rsp as RegUnit);
     regs.take(GPR, RU::rbp as RegUnit);
 
     // 32-bit arch only has 8 registers.
-    if triple.pointer_width().unwrap() != PointerWidth::U64 {
+    if isa.triple().pointer_width().unwrap() != PointerWidth::U64 {
         for i in 8..16 {
             regs.take(GPR, GPR.unit(i));
             regs.take(FPR, FPR.unit(i));
@@ -430,9 +724,59 @@ pub fn allocatable_registers(triple: &Triple, flags: &shared_settings::Flags) ->
         }
     }
 
+    if let CallConv::Custom(index) = func.signature.call_conv {
+        for &reg in &custom_call_conv(isa, index).reserved {
+            let rc = if GPR.contains(reg) { GPR } else { FPR };
+            if regs.is_avail(rc, reg) {
+                regs.take(rc, reg);
+            }
+        }
+    }
+
     regs
 }
 
+/// Get the custom calling convention registered with `isa` as `CallConv::Custom(index)`.
+fn custom_call_conv(isa: &dyn TargetIsa, index: u8) -> &CustomCallConv {
+    isa.custom_call_conv(index)
+        .expect("unregistered custom calling convention")
+}
+
+/// Check that `conv` only uses registers the x86 ABI code knows how to handle.
+pub fn check_custom_call_conv(conv: &CustomCallConv) -> CodegenResult<()> {
+    let unsupported = |msg: &str| Err(CodegenError::Unsupported(msg.to_string()));
+    if !conv
+        .int_args
+        .iter()
+        .chain(&conv.int_rets)
+        .all(|&reg| GPR.contains(reg))
+    {
+        return unsupported("integer argument registers must be GPRs");
+    }
+    if !conv
+        .float_args
+        .iter()
+        .chain(&conv.float_rets)
+        .all(|&reg| FPR.contains(reg))
+    {
+        return unsupported("float argument registers must be FPRs");
+    }
+    if !conv.callee_saved.iter().all(|&reg| GPR.contains(reg)) {
+        return unsupported("only GPRs can be callee-saved on x86");
+    }
+    if !conv.stack_align.is_power_of_two() {
+        return unsupported("the stack alignment must be a power of two");
+    }
+    Ok(())
+}
+
+/// Get the register the unwinder uses to pass the exception pointer to a landing pad.
+///
+/// This is the first return register on both x86-32 and x86-64, as with `__builtin_eh_return_data_regno(0)`.
+pub fn exception_pointer_reg() -> RegUnit {
+    RU::rax as RegUnit
+}
+
 /// Get the set of callee-saved registers.
 fn callee_saved_gprs(isa: &dyn TargetIsa, call_conv: CallConv) -> &'static [RU] {
     match isa.triple().pointer_width().unwrap() {
@@ -454,6 +798,9 @@ fn callee_saved_gprs(isa: &dyn TargetIsa, call_conv: CallConv) -> &'static [RU]
                     RU::r14,
                     RU::r15,
                 ]
+            } else if call_conv == CallConv::Fast {
+                // R12 and R13 are used for arguments instead.
+                &[RU::rbx, RU::r14, RU::r15]
             } else {
                 &[RU::rbx, RU::r12, RU::r13, RU::r14, RU::r15]
             }
@@ -461,13 +808,76 @@ fn callee_saved_gprs(isa: &dyn TargetIsa, call_conv: CallConv) -> &'static [RU]
     }
 }
 
-/// Get the set of callee-saved registers that are used.
-fn callee_saved_gprs_used(isa: &dyn TargetIsa, func: &ir::Function) -> RegisterSet {
-    let mut all_callee_saved = RegisterSet::empty();
-    for reg in callee_saved_gprs(isa, func.signature.call_conv) {
-        all_callee_saved.free(GPR, *reg as RegUnit);
+/// Get the set of callee-saved registers of a function with the cold calling convention and the
+/// signature `sig`.
+///
+/// Cold functions preserve every general purpose register, except for the ones holding return
+/// values and the scratch registers that prologues and tail call epilogues clobber before the
+/// registers are saved or after they are restored. This keeps call sites small, at the expense
+/// of saving more registers in the rarely executed callee.
+fn cold_callee_saved_gprs(isa: &dyn TargetIsa, sig: &ir::Signature) -> RegisterSet {
+    let (count, scratch): (_, &[RU]) = match isa.triple().pointer_width().unwrap() {
+        PointerWidth::U16 => panic!(),
+        PointerWidth::U32 => (8, &[RU::rax, RU::rcx]),
+        PointerWidth::U64 => (16, &[RU::rax, RU::r10, RU::r11]),
+    };
+
+    let mut regs = RegisterSet::empty();
+    for reg in (0..count).map(|i| GPR.unit(i)) {
+        if reg == RU::rsp as RegUnit || reg == RU::rbp as RegUnit {
+            continue;
+        }
+        if scratch.iter().any(|&r| r as RegUnit == reg) {
+            continue;
+        }
+        if sig
+            .returns
+            .iter()
+            .any(|ret| ret.location == ArgumentLoc::Reg(reg))
+        {
+            continue;
+        }
+        regs.free(GPR, reg);
     }
+    regs
+}
+
+/// Get the set of XMM registers preserved by a function with the cold calling convention and the
+/// signature `sig`.
+///
+/// Cold functions save the low 128 bits of every XMM register, except for the ones holding return
+/// values, so that callers can keep their float and vector values in registers across the call.
+fn cold_callee_saved_fprs(isa: &dyn TargetIsa, sig: &ir::Signature) -> RegisterSet {
+    let count = match isa.triple().pointer_width().unwrap() {
+        PointerWidth::U16 => panic!(),
+        PointerWidth::U32 => 8,
+        PointerWidth::U64 => 16,
+    };
+
+    let mut regs = RegisterSet::empty();
+    for reg in (0..count).map(|i| FPR.unit(i)) {
+        if sig
+            .returns
+            .iter()
+            .any(|ret| ret.location == ArgumentLoc::Reg(reg))
+        {
+            continue;
+        }
+        regs.free(FPR, reg);
+    }
+    regs
+}
 
+/// Can a value of type `ty` in a register of class `rc` stay there across a call with the
+/// signature `sig`?
+///
+/// Only cold callees preserve XMM registers, and only their low 128 bits.
+pub fn is_preserved_across_call(sig: &ir::Signature, rc: RegClass, ty: ir::Type) -> bool {
+    sig.call_conv == CallConv::Cold && FPR.has_subclass(rc) && ty.bits() <= 128
+}
+
+/// Get the set of registers that `func` assigns values to.
+fn used_registers(func: &ir::Function) -> RegisterSet {
     let mut used = RegisterSet::empty();
     for value_loc in func.locations.values() {
         // Note that `value_loc` here contains only a single unit of a potentially multi-unit
@@ -499,15 +909,68 @@ fn callee_saved_gprs_used(isa: &dyn TargetIsa, func: &ir::Function) -> RegisterS
             }
         }
     }
+    used
+}
+
+/// Get the set of callee-saved registers that are used.
+fn callee_saved_gprs_used(isa: &dyn TargetIsa, func: &ir::Function) -> RegisterSet {
+    let mut all_callee_saved = RegisterSet::empty();
+    if let CallConv::Custom(index) = func.signature.call_conv {
+        for &reg in &custom_call_conv(isa, index).callee_saved {
+            all_callee_saved.free(GPR, reg);
+        }
+    } else if func.signature.call_conv == CallConv::Cold {
+        all_callee_saved = cold_callee_saved_gprs(isa, &func.signature);
+    } else {
+        for reg in callee_saved_gprs(isa, func.signature.call_conv) {
+            all_callee_saved.free(GPR, *reg as RegUnit);
+        }
+    }
+
+    // The registers clobbered by the callees of a cold function don't show up in its value
+    // locations, so save all of them when it makes any calls.
+    if func.signature.call_conv == CallConv::Cold && has_non_tail_calls(func) {
+        return all_callee_saved;
+    }
+
+    let mut used = used_registers(func);
+    used.intersect(&all_callee_saved);
+    used
+}
+
+/// Get the set of XMM registers that a cold function saves. Other functions don't save any.
+fn cold_callee_saved_fprs_used(isa: &dyn TargetIsa, func: &ir::Function) -> RegisterSet {
+    if func.signature.call_conv != CallConv::Cold {
+        return RegisterSet::empty();
+    }
+    let all_callee_saved = cold_callee_saved_fprs(isa, &func.signature);
+    if has_non_tail_calls(func) {
+        return all_callee_saved;
+    }
 
+    let mut used = used_registers(func);
     used.intersect(&all_callee_saved);
     used
 }
 
+/// Does `func` contain any calls returning to it?
+fn has_non_tail_calls(func: &ir::Function) -> bool {
+    func.layout.ebbs().any(|ebb| {
+        func.layout.ebb_insts(ebb).any(|inst| {
+            let opcode = func.dfg[inst].opcode();
+            opcode.is_call() && !opcode.is_terminator()
+        })
+    })
+}
+
 pub fn prologue_epilogue(func: &mut ir::Function, isa: &dyn TargetIsa) -> CodegenResult<()> {
+    insert_callee_pops_adjustments(func, isa);
+
     match func.signature.call_conv {
-        // For now, just translate fast and cold as system_v.
-        CallConv::Fast | CallConv::Cold | CallConv::SystemV => {
+        // Fast and cold only differ from system_v in which registers are callee-saved, which is
+        // handled by `callee_saved_gprs_used` and `cold_callee_saved_fprs_used`. The tail convention only differs in who pops the
+        // stack arguments, which is handled by `ArgArea`.
+        CallConv::Fast | CallConv::Cold | CallConv::SystemV | CallConv::Tail => {
             system_v_prologue_epilogue(func, isa)
         }
         CallConv::WindowsFastcall => fastcall_prologue_epilogue(func, isa),
@@ -515,6 +978,10 @@ pub fn prologue_epilogue(func: &mut ir::Function, isa: &dyn TargetIsa) -> Codege
             baldrdash_prologue_epilogue(func, isa)
         }
         CallConv::Probestack => unimplemented!("probestack calling convention"),
+        CallConv::Custom(index) => {
+            let stack_align = custom_call_conv(isa, index).stack_align;
+            frame_prologue_epilogue(func, isa, stack_align)
+        }
     }
 }
 
@@ -559,6 +1026,7 @@ fn fastcall_prologue_epilogue(func: &mut ir::Function, isa: &dyn TargetIsa) -> C
     let reg_type = isa.pointer_type();
 
     let csrs = callee_saved_gprs_used(isa, func);
+    let arg_area = ArgArea::new(func);
 
     // [1] "Space is allocated on the call stack as a shadow store for callees to save"
     // This shadow store contains the parameters which are passed through registers (ARG_GPRS)
@@ -612,7 +1080,15 @@ fn fastcall_prologue_epilogue(func: &mut ir::Function, isa: &dyn TargetIsa) -> C
 
     // Reset the cursor and insert the epilogue
     let mut pos = pos.at_position(CursorPosition::Nowhere);
-    insert_common_epilogues(&mut pos, local_stack_size, reg_type, &csrs);
+    insert_common_epilogues(
+        &mut pos,
+        local_stack_size,
+        reg_type,
+        &csrs,
+        &[],
+        arg_area,
+        isa,
+    );
 
     Ok(())
 }
@@ -621,12 +1097,23 @@ fn fastcall_prologue_epilogue(func: &mut ir::Function, isa: &dyn TargetIsa) -> C
 fn system_v_prologue_epilogue(func: &mut ir::Function, isa: &dyn TargetIsa) -> CodegenResult<()> {
     // The original 32-bit x86 ELF ABI had a 4-byte aligned stack pointer, but
     // newer versions use a 16-byte aligned stack pointer.
-    let stack_align = 16;
+    frame_prologue_epilogue(func, isa, 16)
+}
+
+/// Insert a prologue and epilogue setting up a frame pointer and saving the callee-saved registers
+/// that are used, keeping the stack pointer aligned to `stack_align` bytes.
+fn frame_prologue_epilogue(
+    func: &mut ir::Function,
+    isa: &dyn TargetIsa,
+    stack_align: StackSize,
+) -> CodegenResult<()> {
     let pointer_width = isa.triple().pointer_width().unwrap();
     let word_size = pointer_width.bytes() as usize;
     let reg_type = ir::Type::int(u16::from(pointer_width.bits())).unwrap();
 
     let csrs = callee_saved_gprs_used(isa, func);
+    let fpr_csrs = cold_callee_saved_fprs_used(isa, func);
+    let arg_area = ArgArea::new(func);
 
     // The reserved stack area is composed of:
     //   return address + frame pointer + all callee-saved registers
@@ -642,6 +1129,12 @@ fn system_v_prologue_epilogue(func: &mut ir::Function, isa: &dyn TargetIsa) -> C
         offset: Some(-csr_stack_size),
     });
 
+    // XMM registers can't be pushed, so they are saved to spill slots in the local frame.
+    let fpr_slots: Vec<_> = fpr_csrs
+        .iter(FPR)
+        .map(|reg| (reg, func.stack_slots.make_spill_slot(types::I8X16)))
+        .collect();
+
     let total_stack_size = layout_stack(&mut func.stack_slots, stack_align)? as i32;
     let local_stack_size = i64::from(total_stack_size - csr_stack_size);
 
@@ -660,14 +1153,33 @@ fn system_v_prologue_epilogue(func: &mut ir::Function, isa: &dyn TargetIsa) -> C
         func.signature.returns.push(csr_arg);
     }
 
+    for &(csr, _) in &fpr_slots {
+        let csr_arg =
+            ir::AbiParam::special_reg(types::I8X16, ir::ArgumentPurpose::CalleeSaved, csr);
+        func.signature.params.push(csr_arg);
+        func.signature.returns.push(csr_arg);
+    }
+
     // Set up the cursor and insert the prologue
     let entry_ebb = func.layout.entry_block().expect("missing entry block");
     let mut pos = EncCursor::new(func, isa).at_first_insertion_point(entry_ebb);
+    if arg_area.grow > 0 {
+        insert_return_address_move(&mut pos, -arg_area.grow, reg_type, isa);
+    }
     insert_common_prologue(&mut pos, local_stack_size, reg_type, &csrs, isa);
+    let fpr_saves = insert_fpr_saves(&mut pos, entry_ebb, &fpr_slots);
 
     // Reset the cursor and insert the epilogue
     let mut pos = pos.at_position(CursorPosition::Nowhere);
-    insert_common_epilogues(&mut pos, local_stack_size, reg_type, &csrs);
+    insert_common_epilogues(
+        &mut pos,
+        local_stack_size,
+        reg_type,
+        &csrs,
+        &fpr_saves,
+        arg_area,
+        isa,
+    );
 
     Ok(())
 }
@@ -718,42 +1230,15 @@ fn insert_common_prologue(
 
     // Allocate stack frame storage.
     if stack_size > 0 {
-        if isa.flags().probestack_enabled()
-            && stack_size > (1 << isa.flags().probestack_size_log2())
-        {
-            // Emit a stack probe.
-            let rax = RU::rax as RegUnit;
-            let rax_val = ir::ValueLoc::Reg(rax);
-
-            // The probestack function expects its input in %rax.
-            let arg = pos.ins().iconst(reg_type, stack_size);
-            pos.func.locations[arg] = rax_val;
-
-            // Call the probestack function.
-            let callee = get_probestack_funcref(pos.func, reg_type, rax, isa);
-
-            // Make the call.
-            let call = if !isa.flags().is_pic()
-                && isa.triple().pointer_width().unwrap() == PointerWidth::U64
-                && !pos.func.dfg.ext_funcs[callee].colocated
-            {
-                // 64-bit non-PIC non-colocated calls need to be legalized to call_indirect.
-                // Use r11 as it may be clobbered under all supported calling conventions.
-                let r11 = RU::r11 as RegUnit;
-                let sig = pos.func.dfg.ext_funcs[callee].signature;
-                let addr = pos.ins().func_addr(reg_type, callee);
-                pos.func.locations[addr] = ir::ValueLoc::Reg(r11);
-                pos.ins().call_indirect(sig, addr, &[arg])
-            } else {
-                // Otherwise just do a normal call.
-                pos.ins().call(callee, &[arg])
-            };
-
-            // If the probestack function doesn't adjust sp, do it ourselves.
-            if !isa.flags().probestack_func_adjusts_sp() {
-                let result = pos.func.dfg.inst_results(call)[0];
-                pos.func.locations[result] = rax_val;
-                pos.func.prologue_end = Some(pos.ins().adjust_sp_down(result));
+        let probe_size = 1 << isa.flags().probestack_size_log2();
+        if isa.flags().probestack_enabled() && stack_size > probe_size {
+            match isa.flags().probestack_strategy() {
+                shared_settings::ProbestackStrategy::Outline => {
+                    insert_probestack_call(pos, stack_size, reg_type, isa)
+                }
+                shared_settings::ProbestackStrategy::Inline => {
+                    insert_inline_stack_probes(pos, stack_size, probe_size, reg_type)
+                }
             }
         } else {
             // Simply decrement the stack pointer.
@@ -762,6 +1247,125 @@ fn insert_common_prologue(
     }
 }
 
+/// Save the XMM registers to their spill slots in `slots`, once the frame has been allocated.
+///
+/// Returns the registers with the saved values for the epilogues to restore them from.
+fn insert_fpr_saves(
+    pos: &mut EncCursor,
+    entry_ebb: ir::Ebb,
+    slots: &[(RegUnit, ir::StackSlot)],
+) -> Vec<(RegUnit, ir::Value)> {
+    slots
+        .iter()
+        .map(|&(reg, ss)| {
+            let csr_arg = pos.func.dfg.append_ebb_param(entry_ebb, types::I8X16);
+            pos.func.locations[csr_arg] = ir::ValueLoc::Reg(reg);
+            let saved = pos.ins().spill(csr_arg);
+            pos.func.locations[saved] = ir::ValueLoc::Stack(ss);
+            reencode(pos, pos.func.dfg.value_def(saved).unwrap_inst());
+            (reg, saved)
+        })
+        .collect()
+}
+
+/// Pick an encoding for `inst` whose constraints the locations of its operands satisfy.
+///
+/// `EncCursor` picks the first legal encoding when inserting an instruction, before its results
+/// are assigned a location, and that encoding may not reach all the XMM registers.
+fn reencode(pos: &mut EncCursor, inst: ir::Inst) {
+    let encinfo = pos.isa.encoding_info();
+    let divert = RegDiversions::new();
+    let func = &*pos.func;
+    let enc = pos
+        .isa
+        .legal_encodings(func, &func.dfg[inst], func.dfg.ctrl_typevar(inst))
+        .find(|enc| encinfo.constraints[enc.recipe()].satisfied(inst, &divert, func))
+        .expect("no encoding satisfies the register constraints");
+    pos.func.encodings[inst] = enc;
+}
+
+/// Allocate a `stack_size` byte frame by calling the probestack function.
+fn insert_probestack_call(
+    pos: &mut EncCursor,
+    stack_size: i64,
+    reg_type: ir::types::Type,
+    isa: &dyn TargetIsa,
+) {
+    let rax = RU::rax as RegUnit;
+    let rax_val = ir::ValueLoc::Reg(rax);
+
+    // The probestack function expects its input in %rax.
+    let arg = pos.ins().iconst(reg_type, stack_size);
+    pos.func.locations[arg] = rax_val;
+
+    // Call the probestack function.
+    let callee = get_probestack_funcref(pos.func, reg_type, rax, isa);
+
+    // Make the call.
+    let call = if !isa.flags().is_pic()
+        && isa.triple().pointer_width().unwrap() == PointerWidth::U64
+        && !pos.func.dfg.ext_funcs[callee].colocated
+    {
+        // 64-bit non-PIC non-colocated calls need to be legalized to call_indirect.
+        // Use r11 as it may be clobbered under all supported calling conventions.
+        let r11 = RU::r11 as RegUnit;
+        let sig = pos.func.dfg.ext_funcs[callee].signature;
+        let addr = pos.ins().func_addr(reg_type, callee);
+        pos.func.locations[addr] = ir::ValueLoc::Reg(r11);
+        pos.ins().call_indirect(sig, addr, &[arg])
+    } else {
+        // Otherwise just do a normal call.
+        pos.ins().call(callee, &[arg])
+    };
+
+    // If the probestack function doesn't adjust sp, do it ourselves.
+    if !isa.flags().probestack_func_adjusts_sp() {
+        let result = pos.func.dfg.inst_results(call)[0];
+        pos.func.locations[result] = rax_val;
+        pos.func.prologue_end = Some(pos.ins().adjust_sp_down(result));
+    }
+}
+
+/// The largest number of inline stack probes emitted one after the other. Frames needing more are
+/// probed in a loop.
+const PROBE_MAX_UNROLL: i64 = 4;
+
+/// Allocate a `stack_size` byte frame, writing to each `probe_size` byte page of it in turn so
+/// that the guard page below the stack is hit before anything beyond it.
+fn insert_inline_stack_probes(
+    pos: &mut EncCursor,
+    stack_size: i64,
+    probe_size: i64,
+    reg_type: ir::types::Type,
+) {
+    let probe_count = stack_size / probe_size;
+    let remainder = stack_size % probe_size;
+
+    let mut last = if probe_count <= PROBE_MAX_UNROLL {
+        for _ in 1..probe_count {
+            pos.ins().x86_stack_probe(Imm64::new(probe_size));
+        }
+        pos.ins().x86_stack_probe(Imm64::new(probe_size))
+    } else {
+        // Count the probes down in %rax, which is free in the prologue since the outline
+        // probestack call uses it too.
+        let rax_val = ir::ValueLoc::Reg(RU::rax as RegUnit);
+        let count = pos.ins().iconst(reg_type, probe_count);
+        pos.func.locations[count] = rax_val;
+        let zero = pos
+            .ins()
+            .x86_stack_probe_loop(count, Imm64::new(probe_size));
+        pos.func.locations[zero] = rax_val;
+        pos.func.dfg.value_def(zero).unwrap_inst()
+    };
+
+    // What remains of the frame is smaller than a page, so it is covered by the last probe.
+    if remainder > 0 {
+        last = pos.ins().adjust_sp_down_imm(Imm64::new(remainder));
+    }
+    pos.func.prologue_end = Some(last);
+}
+
 /// Insert a check that generates a trap if the stack pointer goes
 /// below a value in `stack_limit_arg`.
 fn insert_stack_check(pos: &mut EncCursor, stack_size: i64, stack_limit_arg: ir::Value) {
@@ -785,18 +1389,30 @@ fn insert_stack_check(pos: &mut EncCursor, stack_size: i64, stack_limit_arg: ir:
     );
 }
 
-/// Find all `return` instructions and insert epilogues before them.
+/// Find all `return` and tail call instructions and insert epilogues before them.
 fn insert_common_epilogues(
     pos: &mut EncCursor,
     stack_size: i64,
     reg_type: ir::types::Type,
     csrs: &RegisterSet,
+    fpr_saves: &[(RegUnit, ir::Value)],
+    arg_area: ArgArea,
+    isa: &dyn TargetIsa,
 ) {
     while let Some(ebb) = pos.next_ebb() {
         pos.goto_last_inst(ebb);
         if let Some(inst) = pos.current_inst() {
-            if pos.func.dfg[inst].opcode().is_return() {
-                insert_common_epilogue(inst, stack_size, pos, reg_type, csrs);
+            let opcode = pos.func.dfg[inst].opcode();
+            if opcode.is_return() {
+                if arg_area.callee_pops && arg_area.size > 0 {
+                    let ra_pop = insert_return_address_move(pos, arg_area.size, reg_type, isa);
+                    pos.goto_inst(ra_pop);
+                }
+                insert_common_epilogue(inst, stack_size, pos, reg_type, csrs, fpr_saves);
+            } else if opcode.is_call() && opcode.is_terminator() {
+                // Cold functions, the only ones saving XMM registers, can't make tail calls.
+                debug_assert!(fpr_saves.is_empty());
+                insert_tail_call_epilogue(inst, stack_size, pos, reg_type, csrs, arg_area, isa);
             }
         }
     }
@@ -810,32 +1426,450 @@ fn insert_common_epilogue(
     pos: &mut EncCursor,
     reg_type: ir::types::Type,
     csrs: &RegisterSet,
+    fpr_saves: &[(RegUnit, ir::Value)],
 ) {
+    // Restore the XMM registers before freeing the frame holding their spill slots.
+    let fpr_rets: Vec<_> = fpr_saves
+        .iter()
+        .map(|&(reg, saved)| {
+            let csr_ret = pos.ins().fill(saved);
+            pos.func.locations[csr_ret] = ir::ValueLoc::Reg(reg);
+            reencode(pos, pos.func.dfg.value_def(csr_ret).unwrap_inst());
+            csr_ret
+        })
+        .collect();
+
     if stack_size > 0 {
         pos.ins().adjust_sp_up_imm(Imm64::new(stack_size));
     }
 
     // Pop all the callee-saved registers, stepping backward each time to
     // preserve the correct order.
+    //
+    // A tail call doesn't return the restored registers; the callee preserves them for us.
+    let is_return = pos.func.dfg[inst].opcode().is_return();
     let fp_ret = pos.ins().x86_pop(reg_type);
     pos.prev_inst();
 
     pos.func.locations[fp_ret] = ir::ValueLoc::Reg(RU::rbp as RegUnit);
-    pos.func.dfg.append_inst_arg(inst, fp_ret);
+    if is_return {
+        pos.func.dfg.append_inst_arg(inst, fp_ret);
+    }
 
     for reg in csrs.iter(GPR) {
         let csr_ret = pos.ins().x86_pop(reg_type);
         pos.prev_inst();
 
         pos.func.locations[csr_ret] = ir::ValueLoc::Reg(reg);
-        pos.func.dfg.append_inst_arg(inst, csr_ret);
+        if is_return {
+            pos.func.dfg.append_inst_arg(inst, csr_ret);
+        }
+    }
+
+    if is_return {
+        for csr_ret in fpr_rets {
+            pos.func.dfg.append_inst_arg(inst, csr_ret);
+        }
+    }
+}
+
+/// Insert an epilogue given a specific tail call instruction, and move the stack arguments of the
+/// tail call into place.
+///
+/// The stack arguments are written to our outgoing argument area like for any other call. Before
+/// the frame is torn down, they are copied up to where the callee expects them: at the bottom of
+/// our own incoming argument area if our caller pops it, or at its top if the callee does. In the
+/// latter case, the return address is then moved down to sit right below them.
+fn insert_tail_call_epilogue(
+    inst: ir::Inst,
+    stack_size: i64,
+    pos: &mut EncCursor,
+    reg_type: ir::types::Type,
+    csrs: &RegisterSet,
+    arg_area: ArgArea,
+    isa: &dyn TargetIsa,
+) {
+    let (gpr_scratch, fpr_scratch) = tail_call_scratch_regs(pos.func.signature.call_conv, isa);
+    let sig_ref = pos.func.dfg.call_signature(inst).unwrap();
+    let callee_area = stack_args_area(&pos.func.dfg.signatures[sig_ref]);
+    let delta = if arg_area.callee_pops {
+        arg_area.size - callee_area
+    } else {
+        0
+    };
+    debug_assert!(
+        delta >= 0,
+        "tail call arguments don't fit in the argument area"
+    );
+
+    // The callee address of an indirect tail call must not live in a callee-saved register, since
+    // those are restored before the jump.
+    if pos.func.dfg[inst].opcode() == ir::Opcode::ReturnCallIndirect {
+        let callee = pos.func.dfg.inst_args(inst)[0];
+        let ebb = pos.current_ebb().expect("missing ebb under cursor");
+        let mut divert = RegDiversions::new();
+        divert.at_ebb(&pos.func.entry_diversions, ebb);
+        for i in pos.func.layout.ebb_insts(ebb).take_while(|&i| i != inst) {
+            divert.apply(&pos.func.dfg[i]);
+        }
+        let from = divert.reg(callee, &pos.func.locations);
+        let to = tail_call_callee_reg(isa);
+        if from != to {
+            pos.ins().regmove(callee, from, to);
+        }
+    }
+
+    let params = pos.func.dfg.signatures[sig_ref].params.clone();
+    let args = pos.func.dfg.inst_variable_args(inst).to_vec();
+    for (abi, &arg) in params.iter().zip(&args) {
+        if let ArgumentLoc::Stack(offset) = abi.location {
+            let scratch = if abi.value_type.is_float() {
+                fpr_scratch
+            } else {
+                gpr_scratch
+            };
+            let tmp = pos.ins().fill(arg);
+            pos.func.locations[tmp] = ir::ValueLoc::Reg(scratch);
+            let ss = pos
+                .func
+                .stack_slots
+                .make_incoming_arg(abi.value_type, (delta + i64::from(offset)) as StackOffset);
+            let copy = pos.ins().spill(tmp);
+            pos.func.locations[copy] = ir::ValueLoc::Stack(ss);
+        }
+    }
+
+    insert_common_epilogue(inst, stack_size, pos, reg_type, csrs, &[]);
+
+    if delta > 0 {
+        pos.goto_inst(inst);
+        insert_return_address_move(pos, delta, reg_type, isa);
+    }
+}
+
+/// Move the return address on top of the stack `offset` bytes up, or down if `offset` is
+/// negative, and return the instruction popping it.
+fn insert_return_address_move(
+    pos: &mut EncCursor,
+    offset: i64,
+    reg_type: ir::types::Type,
+    isa: &dyn TargetIsa,
+) -> ir::Inst {
+    let (scratch, _) = tail_call_scratch_regs(pos.func.signature.call_conv, isa);
+    let ra = pos.ins().x86_pop(reg_type);
+    pos.func.locations[ra] = ir::ValueLoc::Reg(scratch);
+    if offset > 0 {
+        pos.ins().adjust_sp_up_imm(Imm64::new(offset));
+    } else {
+        pos.ins().adjust_sp_down_imm(Imm64::new(-offset));
+    }
+    pos.ins().x86_push(ra);
+    pos.func.dfg.value_def(ra).unwrap_inst()
+}
+
+/// Get the general purpose and floating point registers that are free to shuffle stack arguments
+/// and the return address around in epilogues, since they are neither argument nor callee-saved
+/// registers.
+fn tail_call_scratch_regs(call_conv: CallConv, isa: &dyn TargetIsa) -> (RegUnit, RegUnit) {
+    if let CallConv::Custom(index) = call_conv {
+        // Pick the last registers that the convention doesn't use.
+        let conv = custom_call_conv(isa, index);
+        let count = if isa.pointer_bits() == 64 { 16 } else { 8 };
+        let free = |rc: RegClass| {
+            (0..count)
+                .rev()
+                .map(|i| rc.unit(i))
+                .find(|&reg| {
+                    reg != RU::rsp as RegUnit && reg != RU::rbp as RegUnit && !conv.uses_reg(reg)
+                })
+                .expect("custom calling convention leaves no scratch register")
+        };
+        return (free(GPR), free(FPR));
+    }
+    match isa.triple().pointer_width().unwrap() {
+        PointerWidth::U16 => panic!(),
+        PointerWidth::U32 => (RU::rcx as RegUnit, RU::xmm7 as RegUnit),
+        PointerWidth::U64 => {
+            if call_conv.extends_windows_fastcall() {
+                (RU::r11 as RegUnit, RU::xmm5 as RegUnit)
+            } else {
+                (RU::r11 as RegUnit, RU::xmm15 as RegUnit)
+            }
+        }
+    }
+}
+
+/// Get the register holding the callee address of an indirect tail call during its epilogue.
+fn tail_call_callee_reg(isa: &dyn TargetIsa) -> RegUnit {
+    match isa.triple().pointer_width().unwrap() {
+        PointerWidth::U16 => panic!(),
+        PointerWidth::U32 => RU::rax as RegUnit,
+        PointerWidth::U64 => RU::r10 as RegUnit,
+    }
+}
+
+/// Get the size of the stack argument area for the legalized signature `sig`.
+///
+/// The area is rounded up to the stack alignment, so that popping it or moving the return address
+/// by the difference between two such areas keeps the stack pointer aligned.
+fn stack_args_area(sig: &ir::Signature) -> i64 {
+    const STACK_ALIGN: i64 = 16;
+    (i64::from(sig.stack_args_size()) + STACK_ALIGN - 1) & !(STACK_ALIGN - 1)
+}
+
+/// The stack argument area of a function, right above its return address.
+#[derive(Clone, Copy)]
+struct ArgArea {
+    /// The size of the area, in bytes.
+    size: i64,
+    /// The number of bytes the area was grown by in the prologue.
+    grow: i64,
+    /// Does the function pop the area when returning?
+    callee_pops: bool,
+}
+
+impl ArgArea {
+    /// Compute the argument area of `func`.
+    ///
+    /// A function that pops its own arguments may tail call functions that need a larger argument
+    /// area than the one it received. Its prologue then grows the area by moving the return
+    /// address down, so the slots holding its incoming arguments are moved up here.
+    fn new(func: &mut ir::Function) -> Self {
+        let callee_pops = func.signature.call_conv.callee_pops_args();
+        let received = stack_args_area(&func.signature);
+
+        let mut grow = 0;
+        if callee_pops {
+            for ebb in func.layout.ebbs() {
+                if let Some(inst) = func.layout.last_inst(ebb) {
+                    let opcode = func.dfg[inst].opcode();
+                    if opcode.is_call() && opcode.is_terminator() {
+                        let sig_ref = func.dfg.call_signature(inst).unwrap();
+                        let needed = stack_args_area(&func.dfg.signatures[sig_ref]);
+                        grow = max(grow, needed - received);
+                    }
+                }
+            }
+        }
+
+        if grow > 0 {
+            for slot in func.stack_slots.values_mut() {
+                if slot.kind == ir::StackSlotKind::IncomingArg {
+                    slot.offset = slot.offset.map(|offset| offset + grow as StackOffset);
+                }
+            }
+        }
+
+        Self {
+            size: received + grow,
+            grow,
+            callee_pops,
+        }
+    }
+}
+
+/// Reallocate the stack arguments popped by callees that pop their own arguments.
+///
+/// The outgoing argument area is allocated once in the prologue, so it has to be restored after
+/// each such call. It is also made large enough to hold the whole argument area the callee pops,
+/// including the padding up to the stack alignment, which the callee may use for the arguments of
+/// its own tail calls.
+fn insert_callee_pops_adjustments(func: &mut ir::Function, isa: &dyn TargetIsa) {
+    let mut calls = Vec::new();
+    for ebb in func.layout.ebbs() {
+        for inst in func.layout.ebb_insts(ebb) {
+            let opcode = func.dfg[inst].opcode();
+            if !opcode.is_call() || opcode.is_terminator() {
+                continue;
+            }
+            let sig = &func.dfg.signatures[func.dfg.call_signature(inst).unwrap()];
+            if sig.call_conv.callee_pops_args() {
+                let size = stack_args_area(sig);
+                if size > 0 {
+                    calls.push((inst, size));
+                }
+            }
+        }
+    }
+
+    if let Some(size) = calls.iter().map(|&(_, size)| size).max() {
+        let mut area = ir::StackSlotData::new(ir::StackSlotKind::OutgoingArg, size as StackSize);
+        area.offset = Some(0);
+        func.stack_slots.push(area);
+    }
+
+    let mut pos = EncCursor::new(func, isa);
+    for (inst, size) in calls {
+        pos.goto_after_inst(inst);
+        pos.ins().adjust_sp_down_imm(Imm64::new(size));
+    }
+}
+
+pub fn emit_unwind_info(
+    func: &ir::Function,
+    isa: &dyn TargetIsa,
+    kind: FrameUnwindKind,
+    sink: &mut dyn FrameUnwindSink,
+) {
+    match kind {
+        FrameUnwindKind::Fastcall => {
+            // Assumption: RBP is being used as the frame pointer
+            // In the future, Windows fastcall codegen should usually omit the frame pointer
+            if let Some(info) = UnwindInfo::try_from_func(func, isa, Some(RU::rbp.into())) {
+                let mut mem = Vec::new();
+                info.emit(&mut mem)
+                    .expect("failed to emit unwind information");
+                sink.set_entry_offset(sink.len());
+                sink.bytes(&mem);
+            }
+        }
+        FrameUnwindKind::Libunwind => {
+            // Only the conventions set up by `system_v_prologue_epilogue` have a frame we know
+            // how to describe.
+            let system_v = match func.signature.call_conv {
+                CallConv::Fast | CallConv::Cold | CallConv::SystemV | CallConv::Custom(_) => true,
+                _ => false,
+            };
+            if system_v && isa.pointer_bits() == 64 {
+                emit_fde(func, isa, sink);
+            }
+        }
     }
 }
 
-pub fn emit_unwind_info(func: &ir::Function, isa: &dyn TargetIsa, mem: &mut Vec<u8>) {
-    // Assumption: RBP is being used as the frame pointer
-    // In the future, Windows fastcall codegen should usually omit the frame pointer
-    if let Some(info) = UnwindInfo::try_from_func(func, isa, Some(RU::rbp.into())) {
-        info.emit(mem).expect("failed to emit unwind information");
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cursor::{Cursor, FuncCursor};
+    use crate::ir::{types, ExternalName, Function, Signature};
+    use crate::isa::{lookup, RegInfo};
+    use crate::settings::{builder, Flags};
+    use crate::Context;
+    use alloc::boxed::Box;
+    use alloc::string::ToString;
+    use std::str::FromStr;
+    use target_lexicon::triple;
+
+    /// A convention passing the VM context in %r14 and keeping a pinned register in %r15.
+    fn vm_call_conv(reginfo: &RegInfo) -> CustomCallConv {
+        let reg = |name| reginfo.parse_regunit(name).unwrap();
+        CustomCallConv {
+            int_args: vec![reg("r12"), reg("r13")],
+            float_args: vec![reg("xmm2")],
+            int_rets: vec![reg("rbx")],
+            float_rets: vec![reg("xmm3")],
+            special_args: vec![(ArgumentPurpose::VMContext, reg("r14"))],
+            callee_saved: vec![reg("rdi"), reg("rsi")],
+            reserved: vec![reg("r15")],
+            stack_align: 16,
+            shadow_space: 16,
+        }
+    }
+
+    fn vm_isa() -> (Box<dyn TargetIsa>, CallConv) {
+        let mut isa_builder = lookup(triple!("x86_64")).expect("expect x86 ISA");
+        let call_conv = isa_builder.add_call_conv(vm_call_conv(&isa_builder.register_info()));
+        (isa_builder.finish(Flags::new(builder())), call_conv)
+    }
+
+    #[test]
+    fn custom_signature() {
+        let (isa, call_conv) = vm_isa();
+        assert_eq!(call_conv, CallConv::Custom(0));
+
+        let mut sig = Signature::new(call_conv);
+        sig.params.push(AbiParam::new(types::I64));
+        sig.params
+            .push(AbiParam::special(types::I64, ArgumentPurpose::VMContext));
+        sig.params.push(AbiParam::new(types::I32));
+        sig.params.push(AbiParam::new(types::F64));
+        sig.params.push(AbiParam::new(types::I64));
+        sig.returns.push(AbiParam::new(types::I64));
+        sig.returns.push(AbiParam::new(types::F32));
+
+        let mut sig = Cow::Borrowed(&sig);
+        isa.legalize_signature(&mut sig, false);
+        assert_eq!(
+            sig.display(&isa.register_info()).to_string(),
+            "(i64 [%r12], i64 vmctx [%r14], i32 [%r13], f64 [%xmm2], i64 [16]) \
+             -> i64 [%rbx], f32 [%xmm3] custom0"
+        );
+    }
+
+    #[test]
+    fn custom_prologue_epilogue() {
+        let (isa, call_conv) = vm_isa();
+        let reginfo = isa.register_info();
+
+        let mut func =
+            Function::with_name_signature(ExternalName::user(0, 0), Signature::new(call_conv));
+        func.signature.params.push(AbiParam::new(types::I64));
+        func.signature.returns.push(AbiParam::new(types::I64));
+        let ebb = func.dfg.make_ebb();
+        let x = func.dfg.append_ebb_param(ebb, types::I64);
+        let mut pos = FuncCursor::new(&mut func);
+        pos.insert_ebb(ebb);
+        // Keep enough values live to use all of the allocatable registers.
+        let vals: Vec<_> = (0..14).map(|i| pos.ins().iadd_imm(x, i)).collect();
+        let sum = vals.iter().fold(x, |acc, &v| pos.ins().imul(acc, v));
+        let sum = vals.iter().fold(sum, |acc, &v| pos.ins().iadd(acc, v));
+        pos.ins().return_(&[sum]);
+
+        let mut context = Context::for_function(func);
+        context.compile(&*isa).expect("expected compilation");
+
+        // The pinned register is never allocated.
+        let r15 = reginfo.parse_regunit("r15").unwrap();
+        assert!(context
+            .func
+            .locations
+            .values()
+            .all(|loc| *loc != ValueLoc::Reg(r15)));
+
+        // Only the registers the convention calls callee-saved are saved.
+        let saved: Vec<_> = context
+            .func
+            .signature
+            .params
+            .iter()
+            .filter(|p| p.purpose == ArgumentPurpose::CalleeSaved)
+            .map(|p| match p.location {
+                ArgumentLoc::Reg(reg) => reg,
+                _ => panic!("callee-saved register not in a register"),
+            })
+            .collect();
+        let callee_saved = &vm_call_conv(&reginfo).callee_saved;
+        assert!(!saved.is_empty());
+        assert!(saved.iter().all(|reg| callee_saved.contains(reg)));
+        assert_eq!(
+            context.func.signature.params[0].location,
+            ArgumentLoc::Reg(reginfo.parse_regunit("r12").unwrap())
+        );
+    }
+
+    #[test]
+    fn unsupported_custom_call_conv() {
+        let mut isa_builder = lookup(triple!("x86_64")).expect("expect x86 ISA");
+        let mut conv = vm_call_conv(&isa_builder.register_info());
+        conv.int_args = conv.float_args.clone();
+        let call_conv = isa_builder.add_call_conv(conv);
+        let isa = isa_builder.finish(Flags::new(builder()));
+
+        let mut func =
+            Function::with_name_signature(ExternalName::user(0, 0), Signature::new(call_conv));
+        let ebb = func.dfg.make_ebb();
+        let mut pos = FuncCursor::new(&mut func);
+        pos.insert_ebb(ebb);
+        pos.ins().return_(&[]);
+
+        let mut context = Context::for_function(func);
+        match context.compile(&*isa) {
+            Err(CodegenError::Verifier(errors)) => assert_eq!(
+                errors.to_string(),
+                "- function: Calling convention custom0: Unsupported feature: integer argument \
+                 registers must be GPRs\n"
+            ),
+            _ => panic!("expected a verifier error"),
+        }
     }
 }
@@ -4,7 +4,10 @@ use super::enc_tables::{needs_offset, needs_sib_byte};
 use super::registers::RU;
 use crate::binemit::{bad_encoding, CodeSink, Reloc};
 use crate::ir::condcodes::{CondCode, FloatCC, IntCC};
-use crate::ir::{Constant, Ebb, Function, Inst, InstructionData, JumpTable, Opcode, TrapCode};
+use crate::ir::{
+    Constant, Ebb, ExternalName, Function, Inst, InstructionData, JumpTable, LibCall, Opcode,
+    TrapCode,
+};
 use crate::isa::{RegUnit, StackBase, StackBaseMask, StackRef, TargetIsa};
 use crate::regalloc::RegDiversions;
 
@@ -161,6 +164,38 @@ fn put_rexmp3<CS: CodeSink + ?Sized>(bits: u16, rex: u8, sink: &mut CS) {
     sink.put1(bits as u8);
 }
 
+// Emit a three-byte VEX prefix followed by the opcode byte.
+//
+// The inverted R, X, and B bits are taken from a REX prefix computed with the functions above, and
+// the pp, mm and W fields from `bits`. `vvvv` is the additional source register, and `l` selects
+// the 256-bit vector length.
+//
+// The two-byte C5 form would be shorter for some register and opcode combinations, but always
+// using the C4 form gives every VEX recipe a fixed size.
+fn vex_prefix<CS: CodeSink + ?Sized>(bits: u16, rex: u8, vvvv: RegUnit, l: u8, sink: &mut CS) {
+    debug_assert_eq!(rex & 0xf8, BASE_REX);
+    let mm = ((bits >> 10) & 3) as u8;
+    debug_assert_ne!(mm, 0, "Invalid encoding bits for Vex*");
+    let pp = ((bits >> 8) & 3) as u8;
+    let w = ((bits >> 15) & 1) as u8;
+    let rxb = (!rex & 0b111) << 5;
+    let vvvv = !(vvvv as u8) & 0xf;
+    sink.put1(0xc4);
+    sink.put1(rxb | mm);
+    sink.put1((w << 7) | (vvvv << 3) | (l << 2) | pp);
+    sink.put1(bits as u8);
+}
+
+// Emit a VEX-prefixed opcode operating on 128-bit vectors (VEX.L = 0).
+fn put_vex128<CS: CodeSink + ?Sized>(bits: u16, rex: u8, vvvv: RegUnit, sink: &mut CS) {
+    vex_prefix(bits, rex, vvvv, 0, sink);
+}
+
+// Emit a VEX-prefixed opcode operating on 256-bit vectors (VEX.L = 1).
+fn put_vex256<CS: CodeSink + ?Sized>(bits: u16, rex: u8, vvvv: RegUnit, sink: &mut CS) {
+    vex_prefix(bits, rex, vvvv, 1, sink);
+}
+
 /// Emit a ModR/M byte for reg-reg operands.
 fn modrm_rr<CS: CodeSink + ?Sized>(rm: RegUnit, reg: RegUnit, sink: &mut CS) {
     let reg = reg as u8 & 7;
@@ -13,6 +13,7 @@ use crate::isa::encoding::base_size;
 use crate::isa::encoding::RecipeSizing;
 use crate::isa::RegUnit;
 use crate::isa::{self, TargetIsa};
+use crate::legalizer::expand_as_libcall;
 use crate::predicates;
 use crate::regalloc::RegDiversions;
 
@@ -390,7 +391,7 @@ fn expand_fcvt_from_uint(
     inst: ir::Inst,
     func: &mut ir::Function,
     cfg: &mut ControlFlowGraph,
-    _isa: &dyn TargetIsa,
+    isa: &dyn TargetIsa,
 ) {
     let x;
     match func.dfg[inst] {
@@ -414,7 +415,17 @@ fn expand_fcvt_from_uint(
             pos.func.dfg.replace(inst).fcvt_from_sint(ty, wide);
             return;
         }
-        ir::types::I64 => {}
+        ir::types::I64 if isa.pointer_bits() == 64 => {}
+        ir::types::I64 | ir::types::I128 => {
+            // There is no instruction for this conversion, so call the runtime library instead.
+            if !expand_as_libcall(inst, pos.func, isa) {
+                panic!(
+                    "Missing libcall for {}",
+                    pos.func.dfg.display_inst(inst, None)
+                );
+            }
+            return;
+        }
         _ => unimplemented!(),
     }
 
@@ -899,6 +910,43 @@ fn expand_fcvt_to_uint_sat(
     cfg.recompute_ebb(pos.func, done);
 }
 
+/// Expand `tls_value` to the thread local access sequence selected by the `tls_model` setting.
+fn expand_tls_value(
+    inst: ir::Inst,
+    func: &mut ir::Function,
+    _cfg: &mut ControlFlowGraph,
+    isa: &dyn TargetIsa,
+) {
+    use crate::settings::TlsModel;
+
+    debug_assert!(
+        isa.supports_tls_values(),
+        "the verifier rejects thread-local symbols on {}",
+        isa.name()
+    );
+
+    let global_value = match func.dfg[inst] {
+        ir::InstructionData::UnaryGlobalValue {
+            opcode: ir::Opcode::TlsValue,
+            global_value,
+        } => global_value,
+        _ => panic!("Need tls_value: {}", func.dfg.display_inst(inst, None)),
+    };
+    let ty = func.dfg.ctrl_typevar(inst);
+
+    match isa.flags().tls_model() {
+        TlsModel::None => {
+            unreachable!("the verifier rejects thread-local symbols without a tls_model")
+        }
+        TlsModel::ElfGd => {
+            func.dfg.replace(inst).x86_elf_tls_gd_addr(ty, global_value);
+        }
+        TlsModel::ElfIe => {
+            func.dfg.replace(inst).x86_elf_tls_ie_addr(ty, global_value);
+        }
+    }
+}
+
 /// Convert shuffle instructions.
 fn convert_shuffle(
     inst: ir::Inst,
@@ -0,0 +1,550 @@
+//! DWARF call frame information (`.eh_frame` CIE/FDE) for System V x86-64.
+//!
+//! See the "DWARF Debugging Information Format", version 4, section 6.4 for the call frame
+//! instructions, and the Linux Standard Base Core Specification, section 10.6, for the layout of
+//! `.eh_frame` entries. Functions with a personality routine also get a language-specific data
+//! area in the format used by GCC's `.gcc_except_table`, which maps each call site to its landing
+//! pad.
+
+use super::registers::RU;
+use crate::binemit::{FrameUnwindOffset, FrameUnwindSink, Reloc};
+use crate::ir::{ExternalName, Function, InstructionData, Opcode};
+use crate::isa::{RegUnit, TargetIsa};
+use alloc::vec::Vec;
+
+/// The DWARF number of the pseudo-register holding the return address.
+const RETURN_ADDRESS_REG: u8 = 16;
+
+/// The DWARF numbers of `%rsp` and `%rbp`.
+const DW_RSP: u8 = 7;
+const DW_RBP: u8 = 6;
+
+/// The factor applied to every offset operand of `DW_CFA_offset`.
+const DATA_ALIGNMENT_FACTOR: i64 = -8;
+
+// Call frame instruction opcodes.
+const DW_CFA_ADVANCE_LOC: u8 = 0x40;
+const DW_CFA_OFFSET: u8 = 0x80;
+const DW_CFA_NOP: u8 = 0x00;
+const DW_CFA_ADVANCE_LOC1: u8 = 0x02;
+const DW_CFA_ADVANCE_LOC2: u8 = 0x03;
+const DW_CFA_ADVANCE_LOC4: u8 = 0x04;
+const DW_CFA_REMEMBER_STATE: u8 = 0x0a;
+const DW_CFA_RESTORE_STATE: u8 = 0x0b;
+const DW_CFA_DEF_CFA: u8 = 0x0c;
+const DW_CFA_DEF_CFA_REGISTER: u8 = 0x0d;
+const DW_CFA_DEF_CFA_OFFSET: u8 = 0x0e;
+
+/// Pointers in the CIE and FDE are absolute and target-sized.
+const DW_EH_PE_ABSPTR: u8 = 0x00;
+
+/// Position-independent code refers to the personality routine and the LSDA with 4-byte offsets
+/// from the pointer itself. The personality routine lives in another module, so it is reached
+/// through its GOT entry.
+const DW_EH_PE_SDATA4: u8 = 0x0b;
+const DW_EH_PE_PCREL: u8 = 0x10;
+const DW_EH_PE_INDIRECT: u8 = 0x80;
+
+/// Encodings used in the LSDA header.
+const DW_EH_PE_ULEB128: u8 = 0x01;
+const DW_EH_PE_OMIT: u8 = 0xff;
+
+/// Map a general purpose register to its DWARF register number.
+///
+/// The x86-64 psABI numbers the first eight registers in a different order than their hardware
+/// encoding, which is what `RU` follows.
+fn map_reg(reg: RegUnit) -> u8 {
+    const MAP: [u8; 16] = [0, 2, 1, 3, 7, 6, 4, 5, 8, 9, 10, 11, 12, 13, 14, 15];
+    assert!(reg <= RU::r15 as RegUnit, "no DWARF number for {}", reg);
+    MAP[reg as usize]
+}
+
+fn write_uleb128(mem: &mut Vec<u8>, mut value: u64) {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            mem.push(byte);
+            return;
+        }
+        mem.push(byte | 0x80);
+    }
+}
+
+fn write_sleb128(mem: &mut Vec<u8>, mut value: i64) {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if (value == 0 && byte & 0x40 == 0) || (value == -1 && byte & 0x40 != 0) {
+            mem.push(byte);
+            return;
+        }
+        mem.push(byte | 0x80);
+    }
+}
+
+/// A sequence of call frame instructions, tracking the code offset they have advanced to.
+struct CallFrameInstructions {
+    mem: Vec<u8>,
+    loc: u32,
+}
+
+impl CallFrameInstructions {
+    fn new() -> Self {
+        Self {
+            mem: Vec::new(),
+            loc: 0,
+        }
+    }
+
+    /// Make the following rules apply from code offset `loc` on.
+    fn advance_to(&mut self, loc: u32) {
+        debug_assert!(loc >= self.loc);
+        let delta = loc - self.loc;
+        if delta == 0 {
+            return;
+        } else if delta < 0x40 {
+            self.mem.push(DW_CFA_ADVANCE_LOC | delta as u8);
+        } else if delta <= 0xff {
+            self.mem.push(DW_CFA_ADVANCE_LOC1);
+            self.mem.push(delta as u8);
+        } else if delta <= 0xffff {
+            self.mem.push(DW_CFA_ADVANCE_LOC2);
+            self.mem.extend_from_slice(&(delta as u16).to_le_bytes());
+        } else {
+            self.mem.push(DW_CFA_ADVANCE_LOC4);
+            self.mem.extend_from_slice(&delta.to_le_bytes());
+        }
+        self.loc = loc;
+    }
+
+    fn def_cfa(&mut self, reg: u8, offset: u64) {
+        self.mem.push(DW_CFA_DEF_CFA);
+        write_uleb128(&mut self.mem, u64::from(reg));
+        write_uleb128(&mut self.mem, offset);
+    }
+
+    fn def_cfa_register(&mut self, reg: u8) {
+        self.mem.push(DW_CFA_DEF_CFA_REGISTER);
+        write_uleb128(&mut self.mem, u64::from(reg));
+    }
+
+    fn def_cfa_offset(&mut self, offset: u64) {
+        self.mem.push(DW_CFA_DEF_CFA_OFFSET);
+        write_uleb128(&mut self.mem, offset);
+    }
+
+    /// Register `reg` is saved at `CFA + offset`.
+    fn offset(&mut self, reg: u8, offset: i64) {
+        debug_assert!(reg < 0x40);
+        debug_assert_eq!(offset % DATA_ALIGNMENT_FACTOR, 0);
+        self.mem.push(DW_CFA_OFFSET | reg);
+        write_uleb128(&mut self.mem, (offset / DATA_ALIGNMENT_FACTOR) as u64);
+    }
+
+    fn remember_state(&mut self) {
+        self.mem.push(DW_CFA_REMEMBER_STATE);
+    }
+
+    fn restore_state(&mut self) {
+        self.mem.push(DW_CFA_RESTORE_STATE);
+    }
+}
+
+/// Write a length-prefixed CIE or FDE, padding it so the next entry stays 8-byte aligned.
+fn write_entry(sink: &mut dyn FrameUnwindSink, mut body: Vec<u8>) {
+    while (body.len() + 4) % 8 != 0 {
+        body.push(DW_CFA_NOP);
+    }
+    sink.bytes(&(body.len() as u32).to_le_bytes());
+    sink.bytes(&body);
+}
+
+/// Write the CIE shared by every FDE: on entry the CFA is `%rsp + 8` and the return address is
+/// stored right below it.
+///
+/// When the function has a `personality` routine, the CIE also references it and announces that
+/// the FDE points to an LSDA. Both pointers are PC-relative when `is_pic` is set.
+fn write_cie(sink: &mut dyn FrameUnwindSink, personality: Option<&ExternalName>, is_pic: bool) {
+    let cie_offset = sink.len();
+    let mut cie = Vec::new();
+    // CIE id, version and augmentation string.
+    cie.extend_from_slice(&0u32.to_le_bytes());
+    cie.push(1);
+    if personality.is_some() {
+        cie.extend_from_slice(b"zPLR\0");
+    } else {
+        cie.extend_from_slice(b"zR\0");
+    }
+    write_uleb128(&mut cie, 1);
+    write_sleb128(&mut cie, DATA_ALIGNMENT_FACTOR);
+    cie.push(RETURN_ADDRESS_REG);
+    // Augmentation data: the personality routine and LSDA pointer encoding, if any, and the FDE
+    // pointer encoding.
+    let mut personality_offset = None;
+    if personality.is_some() && is_pic {
+        write_uleb128(&mut cie, 7);
+        cie.push(DW_EH_PE_INDIRECT | DW_EH_PE_PCREL | DW_EH_PE_SDATA4);
+        personality_offset = Some(cie.len());
+        cie.extend_from_slice(&0u32.to_le_bytes());
+        cie.push(DW_EH_PE_PCREL | DW_EH_PE_SDATA4);
+    } else if personality.is_some() {
+        write_uleb128(&mut cie, 11);
+        cie.push(DW_EH_PE_ABSPTR);
+        personality_offset = Some(cie.len());
+        cie.extend_from_slice(&0u64.to_le_bytes());
+        cie.push(DW_EH_PE_ABSPTR);
+    } else {
+        write_uleb128(&mut cie, 1);
+    }
+    cie.push(DW_EH_PE_ABSPTR);
+
+    let mut insts = CallFrameInstructions::new();
+    insts.def_cfa(DW_RSP, 8);
+    insts.offset(RETURN_ADDRESS_REG, -8);
+    cie.extend_from_slice(&insts.mem);
+
+    write_entry(sink, cie);
+    if let (Some(name), Some(offset)) = (personality, personality_offset) {
+        let reloc = if is_pic {
+            Reloc::X86GOTPCRel4
+        } else {
+            Reloc::Abs8
+        };
+        sink.reloc_external(reloc, cie_offset + 4 + offset, name);
+    }
+}
+
+/// Compute the LSDA for `func`.
+///
+/// Every call that returns to this function gets an entry in the call-site table, so the
+/// personality routine can tell it apart from a call that must not unwind. Calls made by `invoke`
+/// instructions continue at their landing pad.
+///
+/// There is no action table: every call site has action 0, so landing pads are only ever entered
+/// as cleanups, and catching specific exceptions is left to the code in the landing pad.
+fn lsda(func: &Function, isa: &dyn TargetIsa) -> Vec<u8> {
+    let encinfo = isa.encoding_info();
+    let mut call_sites = Vec::new();
+    for ebb in func.layout.ebbs() {
+        for (offset, inst, size) in func.inst_offsets(ebb, &encinfo) {
+            let opcode = func.dfg[inst].opcode();
+            if !opcode.is_call() || opcode.is_terminator() {
+                continue;
+            }
+            let landing_pad = func.dfg[inst]
+                .landing_pad()
+                .map_or(0, |lp| func.offsets[lp]);
+            // Start, length, landing pad and action.
+            write_uleb128(&mut call_sites, u64::from(offset));
+            write_uleb128(&mut call_sites, u64::from(size));
+            write_uleb128(&mut call_sites, u64::from(landing_pad));
+            write_uleb128(&mut call_sites, 0);
+        }
+    }
+
+    // Landing pads are relative to the start of the function, and there is no type table.
+    let mut lsda = vec![DW_EH_PE_OMIT, DW_EH_PE_OMIT, DW_EH_PE_ULEB128];
+    write_uleb128(&mut lsda, call_sites.len() as u64);
+    lsda.extend_from_slice(&call_sites);
+    lsda
+}
+
+/// Compute the call frame instructions for `func` and the size of its code.
+///
+/// This follows the frame set up by `insert_common_prologue`: once `%rbp` has been pushed and
+/// loaded with `%rsp`, the CFA is `%rbp + 16` for the rest of the function body, so the stack
+/// pointer adjustments that follow don't need to be described. Each epilogue ends with `pop %rbp`
+/// right before the return or tail call, after which the CFA is `%rsp + 8` again.
+fn call_frame_instructions(func: &Function, isa: &dyn TargetIsa) -> (CallFrameInstructions, u32) {
+    let encinfo = isa.encoding_info();
+    let word_size = i64::from(isa.pointer_bytes());
+    let mut insts = CallFrameInstructions::new();
+    let mut code_size = 0;
+    let mut saved_regs = 0;
+    let mut after_return = None;
+
+    for ebb in func.layout.ebbs() {
+        for (offset, inst, size) in func.inst_offsets(ebb, &encinfo) {
+            // Code following an epilogue runs in the frame as it was before the epilogue.
+            if let Some(loc) = after_return.take() {
+                insts.advance_to(loc);
+                insts.restore_state();
+            }
+
+            let end = offset + size;
+            code_size = end;
+
+            match func.dfg[inst] {
+                InstructionData::Unary {
+                    opcode: Opcode::X86Push,
+                    arg,
+                } => {
+                    let reg = func.locations[arg].unwrap_reg();
+                    insts.advance_to(end);
+                    if reg == RU::rbp as RegUnit {
+                        insts.def_cfa_offset(2 * word_size as u64);
+                        insts.offset(DW_RBP, -2 * word_size);
+                    } else {
+                        saved_regs += 1;
+                        insts.offset(map_reg(reg), -(2 + saved_regs) * word_size);
+                    }
+                }
+                InstructionData::CopySpecial { src, dst, .. }
+                    if src == RU::rsp as RegUnit && dst == RU::rbp as RegUnit =>
+                {
+                    insts.advance_to(end);
+                    insts.def_cfa_register(DW_RBP);
+                }
+                InstructionData::NullAry {
+                    opcode: Opcode::X86Pop,
+                } => {
+                    let result = func.dfg.first_result(inst);
+                    if func.locations[result].unwrap_reg() == RU::rbp as RegUnit {
+                        insts.advance_to(end);
+                        insts.remember_state();
+                        insts.def_cfa(DW_RSP, word_size as u64);
+                    }
+                }
+                ref data
+                    if data.opcode().is_return()
+                        || data.opcode().is_terminator() && data.opcode().is_call() =>
+                {
+                    after_return = Some(end);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    (insts, code_size)
+}
+
+/// Emit a CIE followed by an FDE describing `func` to `sink`.
+///
+/// The FDE's initial location is reported as an `Abs8` relocation against the start of the
+/// function, and `sink.set_entry_offset` is given the offset of the FDE. Functions with a
+/// personality routine also pass their LSDA to `sink.lsda`, along with an `X86PCRel4` relocation
+/// when the ISA is compiling position-independent code, and an `Abs8` relocation otherwise.
+pub fn emit_fde(func: &Function, isa: &dyn TargetIsa, sink: &mut dyn FrameUnwindSink) {
+    debug_assert_eq!(isa.pointer_bytes(), 8);
+
+    let is_pic = isa.flags().is_pic();
+    let personality = func.personality.map(|fref| &func.dfg.ext_funcs[fref].name);
+    let cie_offset = sink.len();
+    write_cie(sink, personality, is_pic);
+
+    let (insts, code_size) = call_frame_instructions(func, isa);
+
+    let fde_offset: FrameUnwindOffset = sink.len();
+    let mut fde = Vec::new();
+    // The CIE pointer is relative to the position of the pointer itself.
+    fde.extend_from_slice(&((fde_offset + 4 - cie_offset) as u32).to_le_bytes());
+    // The initial location is filled in by the relocation.
+    fde.extend_from_slice(&0u64.to_le_bytes());
+    fde.extend_from_slice(&u64::from(code_size).to_le_bytes());
+    // The augmentation data is the LSDA pointer, if there is a personality routine.
+    let mut lsda_offset = None;
+    if personality.is_some() && is_pic {
+        write_uleb128(&mut fde, 4);
+        lsda_offset = Some(fde.len());
+        fde.extend_from_slice(&0u32.to_le_bytes());
+    } else if personality.is_some() {
+        write_uleb128(&mut fde, 8);
+        lsda_offset = Some(fde.len());
+        fde.extend_from_slice(&0u64.to_le_bytes());
+    } else {
+        write_uleb128(&mut fde, 0);
+    }
+    fde.extend_from_slice(&insts.mem);
+
+    sink.set_entry_offset(fde_offset);
+    write_entry(sink, fde);
+    sink.reloc(Reloc::Abs8, fde_offset + 8);
+    if let Some(offset) = lsda_offset {
+        let reloc = if is_pic {
+            Reloc::X86PCRel4
+        } else {
+            Reloc::Abs8
+        };
+        sink.lsda(&lsda(func, isa), reloc, fde_offset + 4 + offset);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cursor::{Cursor, FuncCursor};
+    use crate::ir::{
+        types, AbiParam, ExternalName, InstBuilder, Signature, StackSlotData, StackSlotKind,
+    };
+    use crate::isa::{lookup, CallConv};
+    use crate::settings::{builder, Flags};
+    use crate::Context;
+    use std::str::FromStr;
+    use target_lexicon::triple;
+
+    struct SimpleUnwindSink(Vec<u8>, FrameUnwindOffset, Vec<(Reloc, FrameUnwindOffset)>);
+
+    impl FrameUnwindSink for SimpleUnwindSink {
+        fn len(&self) -> FrameUnwindOffset {
+            self.0.len()
+        }
+
+        fn bytes(&mut self, b: &[u8]) {
+            self.0.extend_from_slice(b);
+        }
+
+        fn reloc(&mut self, r: Reloc, off: FrameUnwindOffset) {
+            self.2.push((r, off));
+        }
+
+        fn reloc_external(&mut self, _: Reloc, _: FrameUnwindOffset, _: &ExternalName) {
+            unreachable!("no personality routine");
+        }
+
+        fn lsda(&mut self, _: &[u8], _: Reloc, _: FrameUnwindOffset) {
+            unreachable!("no personality routine");
+        }
+
+        fn set_entry_offset(&mut self, off: FrameUnwindOffset) {
+            self.1 = off;
+        }
+    }
+
+    const CIE: [u8; 24] = [
+        0x14, 0x00, 0x00, 0x00, // Length
+        0x00, 0x00, 0x00, 0x00, // CIE id
+        0x01, // Version
+        b'z', b'R', 0x00, // Augmentation
+        0x01, // Code alignment factor
+        0x78, // Data alignment factor (-8)
+        0x10, // Return address register
+        0x01, // Augmentation data length
+        0x00, // FDE pointer encoding (absolute)
+        0x0c, 0x07, 0x08, // DW_CFA_def_cfa %rsp, 8
+        0x90, 0x01, // DW_CFA_offset %ra, cfa-8
+        0x00, 0x00, // Padding
+    ];
+
+    #[test]
+    fn test_simple_func() {
+        let isa = lookup(triple!("x86_64"))
+            .expect("expect x86 ISA")
+            .finish(Flags::new(builder()));
+
+        let mut context = Context::for_function(create_function(
+            CallConv::SystemV,
+            Some(StackSlotData::new(StackSlotKind::ExplicitSlot, 64)),
+        ));
+
+        context.compile(&*isa).expect("expected compilation");
+
+        let mut sink = SimpleUnwindSink(Vec::new(), 0, Vec::new());
+        emit_fde(&context.func, &*isa, &mut sink);
+
+        assert_eq!(sink.1, 24);
+        assert_eq!(sink.2, vec![(Reloc::Abs8, 32)]);
+        assert_eq!(&sink.0[..24], &CIE[..]);
+        assert_eq!(
+            &sink.0[24..],
+            &[
+                0x24, 0x00, 0x00, 0x00, // Length
+                0x1c, 0x00, 0x00, 0x00, // CIE pointer
+                0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // Initial location
+                0x10, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // Address range
+                0x00, // Augmentation data length
+                0x42, // DW_CFA_advance_loc 2
+                0x0e, 0x10, // DW_CFA_def_cfa_offset 16
+                0x86, 0x02, // DW_CFA_offset %rbp, cfa-16
+                0x43, // DW_CFA_advance_loc 3
+                0x0d, 0x06, // DW_CFA_def_cfa_register %rbp
+                0x4a, // DW_CFA_advance_loc 10
+                0x0a, // DW_CFA_remember_state
+                0x0c, 0x07, 0x08, // DW_CFA_def_cfa %rsp, 8
+                0x00, 0x00, // Padding
+            ][..]
+        );
+    }
+
+    #[test]
+    fn test_multi_return_func() {
+        let isa = lookup(triple!("x86_64"))
+            .expect("expect x86 ISA")
+            .finish(Flags::new(builder()));
+
+        let mut context = Context::for_function(create_multi_return_function(CallConv::SystemV));
+
+        context.compile(&*isa).expect("expected compilation");
+
+        let mut sink = SimpleUnwindSink(Vec::new(), 0, Vec::new());
+        emit_fde(&context.func, &*isa, &mut sink);
+
+        assert_eq!(sink.1, 24);
+        assert_eq!(sink.2, vec![(Reloc::Abs8, 32)]);
+        assert_eq!(
+            &sink.0[24..],
+            &[
+                0x2c, 0x00, 0x00, 0x00, // Length
+                0x1c, 0x00, 0x00, 0x00, // CIE pointer
+                0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // Initial location
+                0x10, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // Address range
+                0x00, // Augmentation data length
+                0x42, // DW_CFA_advance_loc 2
+                0x0e, 0x10, // DW_CFA_def_cfa_offset 16
+                0x86, 0x02, // DW_CFA_offset %rbp, cfa-16
+                0x43, // DW_CFA_advance_loc 3
+                0x0d, 0x06, // DW_CFA_def_cfa_register %rbp
+                0x47, // DW_CFA_advance_loc 7
+                0x0a, // DW_CFA_remember_state
+                0x0c, 0x07, 0x08, // DW_CFA_def_cfa %rsp, 8
+                0x41, // DW_CFA_advance_loc 1
+                0x0b, // DW_CFA_restore_state
+                0x42, // DW_CFA_advance_loc 2
+                0x0a, // DW_CFA_remember_state
+                0x0c, 0x07, 0x08, // DW_CFA_def_cfa %rsp, 8
+                0x00, 0x00, 0x00, // Padding
+            ][..]
+        );
+    }
+
+    fn create_function(call_conv: CallConv, stack_slot: Option<StackSlotData>) -> Function {
+        let mut func =
+            Function::with_name_signature(ExternalName::user(0, 0), Signature::new(call_conv));
+
+        let ebb0 = func.dfg.make_ebb();
+        let mut pos = FuncCursor::new(&mut func);
+        pos.insert_ebb(ebb0);
+        pos.ins().return_(&[]);
+
+        if let Some(stack_slot) = stack_slot {
+            func.stack_slots.push(stack_slot);
+        }
+
+        func
+    }
+
+    fn create_multi_return_function(call_conv: CallConv) -> Function {
+        let mut sig = Signature::new(call_conv);
+        sig.params.push(AbiParam::new(types::I32));
+        let mut func = Function::with_name_signature(ExternalName::user(0, 0), sig);
+
+        let ebb0 = func.dfg.make_ebb();
+        let v0 = func.dfg.append_ebb_param(ebb0, types::I32);
+        let ebb1 = func.dfg.make_ebb();
+        let ebb2 = func.dfg.make_ebb();
+
+        let mut pos = FuncCursor::new(&mut func);
+        pos.insert_ebb(ebb0);
+        pos.ins().brnz(v0, ebb2, &[]);
+        pos.ins().jump(ebb1, &[]);
+
+        pos.insert_ebb(ebb1);
+        pos.ins().return_(&[]);
+
+        pos.insert_ebb(ebb2);
+        pos.ins().return_(&[]);
+
+        func
+    }
+}
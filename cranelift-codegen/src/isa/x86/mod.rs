@@ -3,6 +3,7 @@
 mod abi;
 mod binemit;
 mod enc_tables;
+mod fde;
 mod registers;
 pub mod settings;
 mod unwind;
@@ -10,11 +11,11 @@ mod unwind;
 use super::super::settings as shared_settings;
 #[cfg(feature = "testing_hooks")]
 use crate::binemit::CodeSink;
-use crate::binemit::{emit_function, MemoryCodeSink};
+use crate::binemit::{emit_function, FrameUnwindKind, FrameUnwindSink, MemoryCodeSink};
 use crate::ir;
 use crate::isa::enc_tables::{self as shared_enc_tables, lookup_enclist, Encodings};
 use crate::isa::Builder as IsaBuilder;
-use crate::isa::{EncInfo, RegClass, RegInfo, TargetIsa};
+use crate::isa::{CustomCallConv, EncInfo, RegClass, RegInfo, RegUnit, TargetIsa};
 use crate::regalloc;
 use crate::result::CodegenResult;
 use crate::timing;
@@ -30,6 +31,7 @@ struct Isa {
     shared_flags: shared_settings::Flags,
     isa_flags: settings::Flags,
     cpumode: &'static [shared_enc_tables::Level1Entry<u16>],
+    call_convs: Vec<CustomCallConv>,
 }
 
 /// Get an ISA builder for creating x86 targets.
@@ -37,6 +39,8 @@ pub fn isa_builder(triple: Triple) -> IsaBuilder {
     IsaBuilder {
         triple,
         setup: settings::builder(),
+        register_info: registers::INFO.clone(),
+        call_convs: Vec::new(),
         constructor: isa_constructor,
     }
 }
@@ -45,6 +49,7 @@ fn isa_constructor(
     triple: Triple,
     shared_flags: shared_settings::Flags,
     builder: shared_settings::Builder,
+    call_convs: Vec<CustomCallConv>,
 ) -> Box<dyn TargetIsa> {
     let level1 = match triple.pointer_width().unwrap() {
         PointerWidth::U16 => unimplemented!("x86-16"),
@@ -56,6 +61,7 @@ fn isa_constructor(
         isa_flags: settings::Flags::new(&shared_flags, builder),
         shared_flags,
         cpumode: level1,
+        call_convs,
     })
 }
 
@@ -72,6 +78,14 @@ impl TargetIsa for Isa {
         &self.shared_flags
     }
 
+    fn custom_call_conv(&self, index: u8) -> Option<&CustomCallConv> {
+        self.call_convs.get(usize::from(index))
+    }
+
+    fn check_custom_call_conv(&self, conv: &CustomCallConv) -> CodegenResult<()> {
+        abi::check_custom_call_conv(conv)
+    }
+
     fn uses_cpu_flags(&self) -> bool {
         true
     }
@@ -115,6 +129,7 @@ impl TargetIsa for Isa {
             current,
             &self.shared_flags,
             &self.isa_flags,
+            &self.call_convs,
         )
     }
 
@@ -122,8 +137,28 @@ impl TargetIsa for Isa {
         abi::regclass_for_abi_type(ty)
     }
 
-    fn allocatable_registers(&self, _func: &ir::Function) -> regalloc::RegisterSet {
-        abi::allocatable_registers(&self.triple, &self.shared_flags)
+    fn allocatable_registers(&self, func: &ir::Function) -> regalloc::RegisterSet {
+        abi::allocatable_registers(func, self)
+    }
+
+    fn is_preserved_across_call(&self, sig: &ir::Signature, rc: RegClass, ty: ir::Type) -> bool {
+        abi::is_preserved_across_call(sig, rc, ty)
+    }
+
+    fn supports_struct_args(&self) -> bool {
+        true
+    }
+
+    fn supports_tail_calls(&self) -> bool {
+        true
+    }
+
+    fn supports_tls_values(&self) -> bool {
+        self.triple.architecture == target_lexicon::Architecture::X86_64
+    }
+
+    fn exception_pointer_reg(&self) -> Option<RegUnit> {
+        Some(abi::exception_pointer_reg())
     }
 
     #[cfg(feature = "testing_hooks")]
@@ -156,9 +191,14 @@ impl TargetIsa for Isa {
 
     /// Emit unwind information for the given function.
     ///
-    /// Only some calling conventions (e.g. Windows fastcall) will have unwind information.
-    fn emit_unwind_info(&self, func: &ir::Function, mem: &mut Vec<u8>) {
-        abi::emit_unwind_info(func, self, mem);
+    /// Windows fastcall functions have `UNWIND_INFO`; System V functions on x86-64 have an FDE.
+    fn emit_unwind_info(
+        &self,
+        func: &ir::Function,
+        kind: FrameUnwindKind,
+        sink: &mut dyn FrameUnwindSink,
+    ) {
+        abi::emit_unwind_info(func, self, kind, sink);
     }
 }
 
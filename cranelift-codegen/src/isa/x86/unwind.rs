@@ -169,7 +169,7 @@ impl UnwindInfo {
                             // instruction (which will adjust via the register assigned to this instruction).
                             stack_size = Some(imm as u32);
                         }
-                        Opcode::AdjustSpDownImm => {
+                        Opcode::AdjustSpDownImm | Opcode::X86StackProbe => {
                             let imm: i64 = imm.into();
                             assert!(imm <= std::u32::MAX as i64);
 
@@ -181,6 +181,22 @@ impl UnwindInfo {
                         _ => {}
                     }
                 }
+                InstructionData::BinaryImm {
+                    opcode: Opcode::X86StackProbeLoop,
+                    imm,
+                    ..
+                } => {
+                    // The probe count was loaded by the preceding `iconst`.
+                    let imm: i64 = imm.into();
+                    let size =
+                        u64::from(stack_size.take().expect("expected a probe count")) * imm as u64;
+                    assert!(size <= std::u32::MAX as u64);
+
+                    unwind_codes.push(UnwindCode::StackAlloc {
+                        offset: unwind_offset,
+                        size: size as u32,
+                    });
+                }
                 _ => {}
             };
 
@@ -22,8 +22,9 @@ use crate::cursor::{Cursor, FuncCursor};
 use crate::flowgraph::ControlFlowGraph;
 use crate::ir::instructions::CallInfo;
 use crate::ir::{
-    AbiParam, ArgumentLoc, ArgumentPurpose, DataFlowGraph, Ebb, Function, Inst, InstBuilder,
-    MemFlags, SigRef, Signature, StackSlotData, StackSlotKind, Type, Value, ValueLoc,
+    AbiParam, ArgumentLoc, ArgumentPurpose, DataFlowGraph, Ebb, ExtFuncData, Function, Inst,
+    InstBuilder, InstructionData, MemFlags, SigRef, Signature, StackSlotData, StackSlotKind, Type,
+    Value, ValueLoc,
 };
 use crate::isa::TargetIsa;
 use crate::legalizer::split::{isplit, vsplit};
@@ -39,6 +40,8 @@ use log::debug;
 /// change the entry block arguments, calls, or return instructions, so this can leave the function
 /// in a state with type discrepancies.
 pub fn legalize_signatures(func: &mut Function, isa: &dyn TargetIsa) {
+    expand_variadic_calls(func);
+
     if let Some(new) = legalize_signature(&func.signature, true, isa) {
         let old = mem::replace(&mut func.signature, new);
         func.old_signature = Some(old);
@@ -55,6 +58,9 @@ pub fn legalize_signatures(func: &mut Function, isa: &dyn TargetIsa) {
         legalize_entry_params(func, entry);
         spill_entry_params(func, entry);
     }
+
+    legalize_variadic_call_args(func);
+    legalize_struct_call_args(func);
 }
 
 /// Legalize the libcall signature, which we may generate on the fly after
@@ -113,7 +119,25 @@ fn legalize_entry_params(func: &mut Function, entry: Ebb) {
 
         let abi_type = pos.func.signature.params[abi_arg];
         let arg_type = pos.func.dfg.value_type(arg);
-        if arg_type == abi_type.value_type {
+        if let ArgumentPurpose::StructArgument(layout) = abi_type.purpose {
+            // Store the parts of the struct into a stack slot, and pass on its address.
+            let ss = pos
+                .func
+                .create_stack_slot(StackSlotData::new(StackSlotKind::ExplicitSlot, 0));
+            let mut offset = 0;
+            while offset < layout.size {
+                let part = pos.func.signature.params[abi_arg];
+                debug_assert_eq!(part.purpose, abi_type.purpose);
+                let value = pos.func.dfg.append_ebb_param(entry, part.value_type);
+                pos.ins().stack_store(value, ss, offset as i32);
+                offset += part.value_type.bytes();
+                abi_arg += 1;
+            }
+            pos.func.stack_slots[ss].size = offset;
+            pos.ins()
+                .with_results([Some(arg)])
+                .stack_addr(arg_type, ss, 0);
+        } else if arg_type == abi_type.value_type {
             // No value translation is necessary, this argument matches the ABI type.
             // Just use the original EBB argument value. This is the most common case.
             pos.func.dfg.attach_ebb_param(entry, arg);
@@ -137,6 +161,8 @@ fn legalize_entry_params(func: &mut Function, entry: Ebb) {
                     debug_assert!(!has_stack_limit, "Multiple stack_limit arguments found");
                     has_stack_limit = true;
                 }
+                // The caller made a copy of the struct for us.
+                ArgumentPurpose::StructReference(_) => {}
                 _ => panic!("Unexpected special-purpose arg {}", abi_type),
             }
             abi_arg += 1;
@@ -168,7 +194,9 @@ fn legalize_entry_params(func: &mut Function, entry: Ebb) {
     for &arg in &pos.func.signature.params[abi_arg..] {
         match arg.purpose {
             // Any normal parameters should have been processed above.
-            ArgumentPurpose::Normal => {
+            ArgumentPurpose::Normal
+            | ArgumentPurpose::StructArgument(_)
+            | ArgumentPurpose::StructReference(_) => {
                 panic!("Leftover arg: {}", arg);
             }
             // The callee-save parameters should not appear until after register allocation is
@@ -176,6 +204,10 @@ fn legalize_entry_params(func: &mut Function, entry: Ebb) {
             ArgumentPurpose::FramePointer | ArgumentPurpose::CalleeSaved => {
                 panic!("Premature callee-saved arg {}", arg);
             }
+            // These are only passed to variadic calls.
+            ArgumentPurpose::VectorCount | ArgumentPurpose::FloatCopy => {
+                panic!("Unexpected variadic call arg {}", arg);
+            }
             // These can be meaningfully added by `legalize_signature()`.
             ArgumentPurpose::Link => {
                 debug_assert!(!has_link, "Multiple link parameters found");
@@ -205,6 +237,285 @@ fn legalize_entry_params(func: &mut Function, entry: Ebb) {
     }
 }
 
+/// Give each call in `func` that passes variadic arguments a signature listing them.
+///
+/// A call to a variadic signature may pass more arguments than the signature has parameters. The
+/// ABI needs to know the types of all the arguments, so this imports a copy of the signature with
+/// parameters for the extra arguments, and makes the call use it.
+fn expand_variadic_calls(func: &mut Function) {
+    let mut pos = FuncCursor::new(func);
+    while let Some(_ebb) = pos.next_ebb() {
+        while let Some(inst) = pos.next_inst() {
+            let sig_ref = match pos.func.dfg.call_signature(inst) {
+                Some(sig_ref) => sig_ref,
+                None => continue,
+            };
+            let sig = &pos.func.dfg.signatures[sig_ref];
+            let args = pos.func.dfg.inst_variable_args(inst);
+            if !sig.is_variadic() || args.len() <= sig.params.len() {
+                continue;
+            }
+
+            let mut new_sig = sig.clone();
+            for &arg in &args[sig.params.len()..] {
+                new_sig
+                    .params
+                    .push(AbiParam::new(pos.func.dfg.value_type(arg)));
+            }
+            let new_sig_ref = pos.func.import_signature(new_sig);
+
+            // A direct call needs a new function reference with the new signature.
+            let new_func_ref = match pos.func.dfg[inst].analyze_call(&pos.func.dfg.value_lists) {
+                CallInfo::Direct(func_ref, _) => Some(pos.func.import_function(ExtFuncData {
+                    signature: new_sig_ref,
+                    ..pos.func.dfg.ext_funcs[func_ref].clone()
+                })),
+                _ => None,
+            };
+
+            match pos.func.dfg[inst] {
+                InstructionData::Call {
+                    ref mut func_ref, ..
+                }
+                | InstructionData::Invoke {
+                    ref mut func_ref, ..
+                } => *func_ref = new_func_ref.unwrap(),
+                InstructionData::CallIndirect {
+                    ref mut sig_ref, ..
+                }
+                | InstructionData::InvokeIndirect {
+                    ref mut sig_ref, ..
+                } => *sig_ref = new_sig_ref,
+                _ => panic!(
+                    "Unexpected call instruction {}",
+                    pos.func.dfg.display_inst(inst, None)
+                ),
+            }
+        }
+    }
+}
+
+/// Pass the hidden arguments that the legalized signatures of variadic calls in `func` expect.
+///
+/// These are the `FloatCopy` arguments, which are the bits of the variadic float arguments passed
+/// in registers, and the `VectorCount` argument, which counts the float and vector arguments
+/// passed in registers. They are appended to the arguments of the call, matching the end of the
+/// legalized signature.
+fn legalize_variadic_call_args(func: &mut Function) {
+    let mut pos = FuncCursor::new(func);
+    while let Some(_ebb) = pos.next_ebb() {
+        while let Some(inst) = pos.next_inst() {
+            let sig_ref = match pos.func.dfg.call_signature(inst) {
+                Some(sig_ref) => sig_ref,
+                None => continue,
+            };
+            let old_sig = match pos.func.dfg.old_signatures[sig_ref] {
+                Some(ref old_sig) if old_sig.is_variadic() => old_sig,
+                _ => continue,
+            };
+            let sig = &pos.func.dfg.signatures[sig_ref];
+            let is_hidden = |p: &&AbiParam| match p.purpose {
+                ArgumentPurpose::VectorCount | ArgumentPurpose::FloatCopy => true,
+                _ => false,
+            };
+            if !sig.params.iter().any(|p| is_hidden(&p)) {
+                continue;
+            }
+
+            // Floats are never split, so the variadic float arguments appear in the same order in
+            // the legalized signature.
+            let old_fixed = old_sig.fixed_params().unwrap();
+            let variadic_floats = old_sig.params[old_fixed..]
+                .iter()
+                .zip(&pos.func.dfg.inst_variable_args(inst)[old_fixed..])
+                .filter(|(param, _)| param.value_type.is_float())
+                .map(|(_, &arg)| arg)
+                .collect::<Vec<_>>();
+            let in_regs = sig.params[sig.fixed_params().unwrap()..]
+                .iter()
+                .filter(|p| p.purpose == ArgumentPurpose::Normal && p.value_type.is_float())
+                .map(|p| p.location.is_reg())
+                .collect::<Vec<_>>();
+            let vector_count = sig
+                .params
+                .iter()
+                .filter(|p| {
+                    p.location.is_reg() && (p.value_type.is_float() || p.value_type.is_vector())
+                })
+                .count();
+            let hidden = sig
+                .params
+                .iter()
+                .filter(is_hidden)
+                .cloned()
+                .collect::<Vec<_>>();
+
+            pos.use_srcloc(inst);
+            let mut copies = variadic_floats
+                .into_iter()
+                .zip(in_regs)
+                .filter(|&(_, in_reg)| in_reg)
+                .map(|(arg, _)| arg);
+            for param in hidden {
+                let value = match param.purpose {
+                    ArgumentPurpose::FloatCopy => {
+                        let arg = copies.next().expect("missing variadic float argument");
+                        pos.ins().bitcast(param.value_type, arg)
+                    }
+                    _ => pos.ins().iconst(param.value_type, vector_count as i64),
+                };
+                pos.func.dfg.append_inst_arg(inst, value);
+            }
+        }
+    }
+}
+
+/// Pass the struct arguments of all the calls in `func` the way their legalized signatures expect.
+///
+/// This happens once, before the calls are legalized. A struct argument is a pointer, and its one
+/// part may have the same type, so `handle_call_abi` couldn't tell if a call has been rewritten
+/// already.
+fn legalize_struct_call_args(func: &mut Function) {
+    let mut pos = FuncCursor::new(func);
+    while let Some(_ebb) = pos.next_ebb() {
+        while let Some(inst) = pos.next_inst() {
+            let sig_ref = match pos.func.dfg.call_signature(inst) {
+                Some(sig_ref) => sig_ref,
+                None => continue,
+            };
+            let has_struct_args =
+                pos.func.dfg.old_signatures[sig_ref]
+                    .as_ref()
+                    .map_or(false, |old_sig| {
+                        old_sig
+                            .params
+                            .iter()
+                            .any(|p| p.purpose.struct_layout().is_some())
+                    });
+            if has_struct_args {
+                pos.use_srcloc(inst);
+                legalize_struct_args(&mut pos, inst, sig_ref);
+            }
+        }
+    }
+}
+
+/// Replace the struct arguments of the call `inst` with their parts, or with pointers to copies
+/// of the structs, as the legalized signature `sig_ref` expects.
+fn legalize_struct_args(pos: &mut FuncCursor, inst: Inst, sig_ref: SigRef) {
+    let num_fixed_values = pos.func.dfg[inst]
+        .opcode()
+        .constraints()
+        .num_fixed_value_arguments();
+    let old_args = pos.func.dfg.inst_args(inst).to_vec();
+    let (fixed_args, old_args) = old_args.split_at(num_fixed_values);
+    let old_params = pos.func.dfg.old_signatures[sig_ref]
+        .as_ref()
+        .unwrap()
+        .params
+        .clone();
+    let new_params = pos.func.dfg.signatures[sig_ref].params.clone();
+
+    // The parts of the struct arguments appear in the same order in the legalized signature.
+    let mut parts = new_params
+        .iter()
+        .filter(|p| p.purpose.struct_layout().is_some());
+
+    let mut args = fixed_args.to_vec();
+    for (param, &arg) in old_params.iter().zip(old_args) {
+        let layout = match param.purpose {
+            ArgumentPurpose::StructArgument(layout) => layout,
+            _ => {
+                args.push(arg);
+                continue;
+            }
+        };
+
+        let first = *parts.next().expect("missing struct argument part");
+        if let ArgumentPurpose::StructReference(_) = first.purpose {
+            // Copy the struct into a stack slot, and pass its address.
+            let word = first.value_type;
+            let size = round_up_to_multiple_of_pow2(layout.size, word.bytes());
+            let ss = pos
+                .func
+                .create_stack_slot(StackSlotData::new(StackSlotKind::ExplicitSlot, size));
+            for offset in (0..layout.size).step_by(word.bytes() as usize) {
+                let value = load_struct_part(pos, word, arg, offset, layout.size);
+                pos.ins().stack_store(value, ss, offset as i32);
+            }
+            args.push(pos.ins().stack_addr(word, ss, 0));
+            continue;
+        }
+
+        // Load each part of the struct.
+        let mut part = first;
+        let mut offset = 0;
+        loop {
+            debug_assert_eq!(part.purpose, param.purpose);
+            args.push(load_struct_part(
+                pos,
+                part.value_type,
+                arg,
+                offset,
+                layout.size,
+            ));
+            offset += part.value_type.bytes();
+            if offset >= layout.size {
+                break;
+            }
+            part = *parts.next().expect("missing struct argument part");
+        }
+    }
+
+    // Keep the hidden arguments of a variadic call.
+    args.extend_from_slice(&old_args[old_params.len()..]);
+
+    let mut vlist = pos.func.dfg[inst]
+        .take_value_list()
+        .expect("Call must have a value list");
+    vlist.clear(&mut pos.func.dfg.value_lists);
+    vlist.extend(args, &mut pos.func.dfg.value_lists);
+    pos.func.dfg[inst].put_value_list(vlist);
+}
+
+/// Load the part of type `ty` at `offset` bytes into the struct of `size` bytes at `ptr`.
+///
+/// The last part of a struct may extend past its end, in which case only the bytes within the
+/// struct are loaded.
+fn load_struct_part(pos: &mut FuncCursor, ty: Type, ptr: Value, offset: u32, size: u32) -> Value {
+    let mut flags = MemFlags::new();
+    flags.set_notrap();
+
+    let bytes = size - offset;
+    if bytes >= ty.bytes() {
+        return pos.ins().load(ty, flags, ptr, offset as i32);
+    }
+
+    debug_assert!(ty.is_int(), "partial {} struct argument part", ty);
+    let mut value = None;
+    let mut loaded = 0;
+    for &width in &[4, 2, 1] {
+        if bytes - loaded < width {
+            continue;
+        }
+        let at = (offset + loaded) as i32;
+        let mut piece = match width {
+            4 => pos.ins().uload32(flags, ptr, at),
+            2 => pos.ins().uload16(ty, flags, ptr, at),
+            _ => pos.ins().uload8(ty, flags, ptr, at),
+        };
+        if loaded > 0 {
+            piece = pos.ins().ishl_imm(piece, i64::from(loaded * 8));
+        }
+        value = Some(match value {
+            Some(value) => pos.ins().bor(value, piece),
+            None => piece,
+        });
+        loaded += width;
+    }
+    value.unwrap()
+}
+
 /// Legalize the results returned from a call instruction to match the ABI signature.
 ///
 /// The cursor `pos` points to a call instruction with at least one return value. The cursor will
@@ -236,8 +547,7 @@ where
     let mut next_res = 0;
     let mut abi_res = 0;
 
-    // Point immediately after the call.
-    pos.next_inst();
+    goto_after_call(pos, call);
 
     while let Some(res) = results.get(next_res, &pos.func.dfg.value_lists) {
         next_res += 1;
@@ -282,12 +592,12 @@ fn assert_is_valid_sret_legalization(
     let old_special_params: Vec<_> = old_sig
         .params
         .iter()
-        .filter(|r| r.purpose != ArgumentPurpose::Normal)
+        .filter(|r| r.purpose != ArgumentPurpose::Normal && r.purpose.struct_layout().is_none())
         .collect();
     let new_special_params: Vec<_> = new_sig
         .params
         .iter()
-        .filter(|r| r.purpose != ArgumentPurpose::Normal)
+        .filter(|r| r.purpose != ArgumentPurpose::Normal && r.purpose.struct_layout().is_none())
         .collect();
     debug_assert_eq!(old_special_params.len() + 1, new_special_params.len());
     debug_assert!(old_special_params
@@ -387,7 +697,7 @@ fn legalize_sret_call(isa: &dyn TargetIsa, pos: &mut FuncCursor, sig_ref: SigRef
 
     // Finally, load each of the call's return values out of the sret stack
     // slot.
-    pos.goto_after_inst(call);
+    goto_after_call(pos, call);
     let mut offset = 0;
     for i in 0..old_ret_list.len(&pos.func.dfg.value_lists) {
         if old_sig.returns[i].purpose != ArgumentPurpose::Normal {
@@ -573,6 +883,19 @@ fn check_arg_types(dfg: &DataFlowGraph, args: &[Value], types: &[AbiParam]) -> b
     arg_types.eq(sig_types)
 }
 
+/// Legalize a tail call whose callee returns its values through an `sret` pointer.
+///
+/// The tail call's return values are our own, so the callee can store them directly into the
+/// return area our caller passed in. The signature compatibility enforced by the verifier
+/// guarantees that the current function has an `sret` parameter too.
+fn legalize_sret_tail_call(pos: &mut FuncCursor, call: Inst) {
+    let sret = pos
+        .func
+        .special_param(ArgumentPurpose::StructReturn)
+        .expect("tail call with an `sret` callee in a function without an `sret` parameter");
+    pos.func.dfg.append_inst_arg(call, sret);
+}
+
 /// Check if the arguments of the call `inst` match the signature.
 ///
 /// Returns `Ok(())` if the signature matches and no changes are needed, or `Err(sig_ref)` if the
@@ -586,8 +909,11 @@ fn check_call_signature(dfg: &DataFlowGraph, inst: Inst) -> Result<(), SigRef> {
     };
     let sig = &dfg.signatures[sig_ref];
 
+    // Tail calls have no results; the callee's return values go straight to our caller.
+    let is_tail_call = dfg[inst].opcode().is_terminator();
+
     if check_arg_types(dfg, args, &sig.params[..])
-        && check_arg_types(dfg, dfg.inst_results(inst), &sig.returns[..])
+        && (is_tail_call || check_arg_types(dfg, dfg.inst_results(inst), &sig.returns[..]))
     {
         // All types check out.
         Ok(())
@@ -722,6 +1048,40 @@ fn legalize_type_for_sret_store(pos: &mut FuncCursor, val: Value, ty: Type) -> (
     }
 }
 
+/// Move the jump following the `invoke` at `inst` to a new EBB that the invoke falls through to,
+/// so the results of the invoke can be converted there.
+///
+/// The cursor is left pointing at `inst`.
+fn split_invoke_results(pos: &mut FuncCursor, cfg: &mut ControlFlowGraph, inst: Inst) {
+    let ebb = pos.func.layout.pp_ebb(inst);
+    let jump = pos
+        .func
+        .layout
+        .next_inst(inst)
+        .expect("invoke must be followed by a jump");
+    let results_ebb = pos.func.dfg.make_ebb();
+    pos.func.layout.split_ebb(results_ebb, jump);
+    pos.goto_bottom(ebb);
+    pos.ins().jump(results_ebb, &[]);
+    cfg.recompute_ebb(pos.func, ebb);
+    cfg.recompute_ebb(pos.func, results_ebb);
+    pos.goto_inst(inst);
+}
+
+/// Point `pos` where the results of `call` become available: right after a call, or at the top
+/// of the EBB an `invoke` falls through to.
+fn goto_after_call(pos: &mut FuncCursor, call: Inst) {
+    if pos.func.dfg[call].landing_pad().is_some() {
+        let jump = pos.func.layout.next_inst(call).unwrap();
+        let dest = pos.func.dfg[jump]
+            .branch_destination()
+            .expect("invoke must be followed by a jump");
+        pos.goto_first_inst(dest);
+    } else {
+        pos.goto_after_inst(call);
+    }
+}
+
 /// Insert ABI conversion code before and after the call instruction at `pos`.
 ///
 /// Instructions inserted before the call will compute the appropriate ABI values for the
@@ -730,13 +1090,15 @@ fn legalize_type_for_sret_store(pos: &mut FuncCursor, val: Value, ty: Type) -> (
 ///
 /// Instructions will be inserted after the call to convert returned ABI values back to the
 /// original return values. The call's result values will be adapted to match the new signature.
+/// For an `invoke`, these instructions go in a new EBB that it falls through to, so the invoke
+/// still ends its basic block.
 ///
 /// Returns `true` if any instructions were inserted.
 pub fn handle_call_abi(
     isa: &dyn TargetIsa,
     mut inst: Inst,
     func: &mut Function,
-    cfg: &ControlFlowGraph,
+    cfg: &mut ControlFlowGraph,
 ) -> bool {
     let pos = &mut FuncCursor::new(func).at_inst(inst);
     pos.use_srcloc(inst);
@@ -747,6 +1109,16 @@ pub fn handle_call_abi(
         Err(s) => s,
     };
 
+    if pos.func.dfg[inst].landing_pad().is_some()
+        && !check_arg_types(
+            &pos.func.dfg,
+            pos.func.dfg.inst_results(inst),
+            &pos.func.dfg.signatures[sig_ref].returns,
+        )
+    {
+        split_invoke_results(pos, cfg, inst);
+    }
+
     let sig = &pos.func.dfg.signatures[sig_ref];
     let old_sig = &pos.func.dfg.old_signatures[sig_ref];
 
@@ -755,7 +1127,11 @@ pub fn handle_call_abi(
             .as_ref()
             .map_or(false, |s| !s.uses_struct_return_param())
     {
-        legalize_sret_call(isa, pos, sig_ref, inst);
+        if pos.func.dfg[inst].opcode().is_terminator() {
+            legalize_sret_tail_call(pos, inst);
+        } else {
+            legalize_sret_call(isa, pos, sig_ref, inst);
+        }
     } else {
         // OK, we need to fix the call arguments to match the ABI signature.
         let abi_args = pos.func.dfg.signatures[sig_ref].params.len();
@@ -763,7 +1139,9 @@ pub fn handle_call_abi(
             func.dfg.signatures[sig_ref].params[abi_arg]
         });
 
-        if !pos.func.dfg.signatures[sig_ref].returns.is_empty() {
+        if !pos.func.dfg.signatures[sig_ref].returns.is_empty()
+            && !pos.func.dfg[inst].opcode().is_terminator()
+        {
             inst = legalize_inst_results(pos, |func, abi_res| {
                 func.dfg.signatures[sig_ref].returns[abi_res]
             });
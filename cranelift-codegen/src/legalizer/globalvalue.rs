@@ -40,7 +40,8 @@ pub fn expand_global_value(
             global_type,
             readonly,
         } => load_addr(inst, func, base, offset, global_type, readonly, isa),
-        ir::GlobalValueData::Symbol { .. } => symbol(inst, func, gv, isa),
+        ir::GlobalValueData::Symbol { tls: false, .. } => symbol(inst, func, gv, isa),
+        ir::GlobalValueData::Symbol { tls: true, .. } => tls_symbol(inst, func, gv, isa),
     }
 }
 
@@ -127,3 +128,10 @@ fn symbol(inst: ir::Inst, func: &mut ir::Function, gv: ir::GlobalValue, isa: &dy
     let ptr_ty = isa.pointer_type();
     func.dfg.replace(inst).symbol_value(ptr_ty, gv);
 }
+
+/// Expand a `global_value` instruction for a thread local storage symbol.
+fn tls_symbol(inst: ir::Inst, func: &mut ir::Function, gv: ir::GlobalValue, isa: &dyn TargetIsa) {
+    // The ISA legalizes `tls_value` further according to the `tls_model` setting.
+    let ptr_ty = isa.pointer_type();
+    func.dfg.replace(inst).tls_value(ptr_ty, gv);
+}
@@ -1,16 +1,30 @@
 //! Expanding instructions as runtime library calls.
 
+use crate::cursor::{Cursor, FuncCursor};
 use crate::ir;
-use crate::ir::{get_libcall_funcref, InstBuilder};
+use crate::ir::condcodes::{FloatCC, IntCC};
+use crate::ir::immediates::{Ieee32, Ieee64};
+use crate::ir::{get_fcmp_funcref, get_libcall_funcref, InstBuilder};
 use crate::isa::{CallConv, TargetIsa};
 use crate::legalizer::boundary::legalize_libcall_signature;
+use alloc::vec;
 use alloc::vec::Vec;
 
 /// Try to expand `inst` as a library call, returning true is successful.
+///
+/// The library routines don't trap, so the checks required by integer divisions and float to
+/// integer conversions are inserted before the call.
 pub fn expand_as_libcall(inst: ir::Inst, func: &mut ir::Function, isa: &dyn TargetIsa) -> bool {
     // Does the opcode/ctrl_type combo even have a well-known runtime library name.
-    let libcall = match ir::LibCall::for_inst(func.dfg[inst].opcode(), func.dfg.ctrl_typevar(inst))
-    {
+    let opcode = func.dfg[inst].opcode();
+    if opcode == ir::Opcode::Fcmp {
+        return expand_fcmp_as_libcall(inst, func, isa);
+    }
+    let arg_type = match func.dfg.inst_args(inst).first() {
+        Some(&arg) => func.dfg.value_type(arg),
+        None => return false,
+    };
+    let libcall = match ir::LibCall::for_inst(opcode, func.dfg.ctrl_typevar(inst), arg_type) {
         Some(lc) => lc,
         None => return false,
     };
@@ -27,9 +41,66 @@ pub fn expand_as_libcall(inst: ir::Inst, func: &mut ir::Function, isa: &dyn Targ
         args.push(vmctx);
     }
 
-    // The replace builder will preserve the instruction result values.
     let funcref = get_libcall_funcref(libcall, call_conv, func, inst, isa);
-    func.dfg.replace(inst).call(funcref, &args);
+
+    let mut pos = FuncCursor::new(func).at_inst(inst);
+    pos.use_srcloc(inst);
+    match opcode {
+        ir::Opcode::Udiv | ir::Opcode::Urem => {
+            check_divisor(&mut pos, args[1]);
+            pos.func.dfg.replace(inst).call(funcref, &args);
+        }
+        ir::Opcode::Sdiv | ir::Opcode::Srem => {
+            check_divisor(&mut pos, args[1]);
+            // `INT_MIN / -1` overflows, but `INT_MIN % -1` is 0.
+            if opcode == ir::Opcode::Sdiv {
+                let ty = pos.func.dfg.value_type(args[0]);
+                let min = int_min(&mut pos, ty);
+                let is_min = pos.ins().icmp(IntCC::Equal, args[0], min);
+                let is_minus_one = pos.ins().icmp_imm(IntCC::Equal, args[1], -1);
+                let overflow = pos.ins().band(is_min, is_minus_one);
+                pos.ins().trapnz(overflow, ir::TrapCode::IntegerOverflow);
+            }
+            pos.func.dfg.replace(inst).call(funcref, &args);
+        }
+        ir::Opcode::FcvtToSint | ir::Opcode::FcvtToUint => {
+            let signed = opcode == ir::Opcode::FcvtToSint;
+            let ty = pos.func.dfg.ctrl_typevar(inst);
+            let x = args[0];
+            let is_nan = pos.ins().fcmp(FloatCC::Unordered, x, x);
+            pos.ins()
+                .trapnz(is_nan, ir::TrapCode::BadConversionToInteger);
+            let (underflow, overflow) = fcvt_to_int_overflow(&mut pos, signed, ty, x);
+            pos.ins().trapnz(underflow, ir::TrapCode::IntegerOverflow);
+            pos.ins().trapnz(overflow, ir::TrapCode::IntegerOverflow);
+            pos.func.dfg.replace(inst).call(funcref, &args);
+        }
+        ir::Opcode::FcvtToSintSat | ir::Opcode::FcvtToUintSat => {
+            // The library routine is called unconditionally, and its result is replaced if the
+            // input is NaN or out of range.
+            let signed = opcode == ir::Opcode::FcvtToSintSat;
+            let ty = pos.func.dfg.ctrl_typevar(inst);
+            let x = args[0];
+            let call = pos.ins().call(funcref, &args);
+            let result = pos.func.dfg.first_result(call);
+            let (min, max) = if signed {
+                let min = int_min(&mut pos, ty);
+                (min, pos.ins().bnot(min))
+            } else {
+                (pos.ins().iconst(ty, 0), pos.ins().iconst(ty, -1))
+            };
+            let is_nan = pos.ins().fcmp(FloatCC::Unordered, x, x);
+            let (underflow, overflow) = fcvt_to_int_overflow(&mut pos, signed, ty, x);
+            let zero = pos.ins().iconst(ty, 0);
+            let a1 = pos.ins().select(overflow, max, result);
+            let a2 = pos.ins().select(underflow, min, a1);
+            pos.func.dfg.replace(inst).select(is_nan, zero, a2);
+        }
+        // The replace builder will preserve the instruction result values.
+        _ => {
+            pos.func.dfg.replace(inst).call(funcref, &args);
+        }
+    }
 
     // Ask the ISA to legalize the signature.
     let fn_data = &func.dfg.ext_funcs[funcref];
@@ -38,3 +109,139 @@ pub fn expand_as_libcall(inst: ir::Inst, func: &mut ir::Function, isa: &dyn Targ
 
     true
 }
+
+/// Expand a float comparison as calls to the soft-float comparison routines.
+fn expand_fcmp_as_libcall(inst: ir::Inst, func: &mut ir::Function, isa: &dyn TargetIsa) -> bool {
+    let (cond, x, y) = match func.dfg[inst] {
+        ir::InstructionData::FloatCompare {
+            opcode: ir::Opcode::Fcmp,
+            cond,
+            args,
+        } => (cond, args[0], args[1]),
+        _ => panic!("Need fcmp: {}", func.dfg.display_inst(inst, None)),
+    };
+    let ty = func.dfg.value_type(x);
+    if ty != ir::types::F32 && ty != ir::types::F64 {
+        return false;
+    }
+
+    let mut pos = FuncCursor::new(func).at_inst(inst);
+    pos.use_srcloc(inst);
+    match cond {
+        // There is no routine for these, so they combine two comparisons.
+        FloatCC::OrderedNotEqual => {
+            let ordered = fcmp_libcall(&mut pos, FloatCC::Ordered, x, y, isa);
+            let not_equal = fcmp_libcall(&mut pos, FloatCC::NotEqual, x, y, isa);
+            pos.func.dfg.replace(inst).band(ordered, not_equal);
+        }
+        FloatCC::UnorderedOrEqual => {
+            let unordered = fcmp_libcall(&mut pos, FloatCC::Unordered, x, y, isa);
+            let equal = fcmp_libcall(&mut pos, FloatCC::Equal, x, y, isa);
+            pos.func.dfg.replace(inst).bor(unordered, equal);
+        }
+        _ => {
+            let (libcall, int_cond) = ir::LibCall::for_fcmp(cond, ty).unwrap();
+            let result = fcmp_call(&mut pos, libcall, x, y, isa);
+            pos.func.dfg.replace(inst).icmp_imm(int_cond, result, 0);
+        }
+    }
+    true
+}
+
+/// Insert a call to the comparison routine for `cond`, returning the boolean result.
+fn fcmp_libcall(
+    pos: &mut FuncCursor,
+    cond: FloatCC,
+    x: ir::Value,
+    y: ir::Value,
+    isa: &dyn TargetIsa,
+) -> ir::Value {
+    let ty = pos.func.dfg.value_type(x);
+    let (libcall, int_cond) = ir::LibCall::for_fcmp(cond, ty).unwrap();
+    let result = fcmp_call(pos, libcall, x, y, isa);
+    pos.ins().icmp_imm(int_cond, result, 0)
+}
+
+/// Insert a call to the comparison routine `libcall`, returning its `i32` result.
+fn fcmp_call(
+    pos: &mut FuncCursor,
+    libcall: ir::LibCall,
+    x: ir::Value,
+    y: ir::Value,
+    isa: &dyn TargetIsa,
+) -> ir::Value {
+    let ty = pos.func.dfg.value_type(x);
+    let mut args = vec![x, y];
+    let call_conv = CallConv::for_libcall(isa);
+    if call_conv.extends_baldrdash() {
+        let vmctx = pos
+            .func
+            .special_param(ir::ArgumentPurpose::VMContext)
+            .expect("Missing vmctx parameter for baldrdash libcall");
+        args.push(vmctx);
+    }
+
+    let funcref = get_fcmp_funcref(libcall, call_conv, pos.func, ty, isa);
+    let fn_data = &pos.func.dfg.ext_funcs[funcref];
+    let sig_data = &mut pos.func.dfg.signatures[fn_data.signature];
+    legalize_libcall_signature(sig_data, isa);
+
+    let call = pos.ins().call(funcref, &args);
+    pos.func.dfg.first_result(call)
+}
+
+/// Trap if the divisor `y` is zero.
+fn check_divisor(pos: &mut FuncCursor, y: ir::Value) {
+    let is_zero = pos.ins().icmp_imm(IntCC::Equal, y, 0);
+    pos.ins()
+        .trapnz(is_zero, ir::TrapCode::IntegerDivisionByZero);
+}
+
+/// Materialize the smallest signed integer of type `ty`, which may be wider than an immediate.
+fn int_min(pos: &mut FuncCursor, ty: ir::Type) -> ir::Value {
+    let bits = ty.bits();
+    if bits <= 64 {
+        return pos.ins().iconst(ty, i64::min_value() >> (64 - bits));
+    }
+    let half = ty.half_width().expect("integer type too wide");
+    let low = pos.ins().iconst(half, 0);
+    let high = int_min(pos, half);
+    pos.ins().iconcat(low, high)
+}
+
+/// Compare the float `x` against the range of the integer type `ty`, returning booleans that are
+/// true when `x` truncates to a value below and above the range respectively.
+fn fcvt_to_int_overflow(
+    pos: &mut FuncCursor,
+    signed: bool,
+    ty: ir::Type,
+    x: ir::Value,
+) -> (ir::Value, ir::Value) {
+    let xty = pos.func.dfg.value_type(x);
+    let bits = ty.bits();
+
+    // Signed values below -2^(N-1) overflow; unsigned values overflow from -1.0 down.
+    let (low_cc, low_bound) = if signed {
+        (FloatCC::LessThan, bits - 1)
+    } else {
+        (FloatCC::LessThanOrEqual, 0)
+    };
+    // Values from 2^(N-1) or 2^N up overflow. 2^128 doesn't fit in an f32, which rounds it up
+    // to infinity.
+    let high_bound = if signed { bits - 1 } else { bits };
+
+    let (flow, fhigh) = match xty {
+        ir::types::F32 => (
+            pos.ins().f32const(Ieee32::pow2(low_bound).neg()),
+            pos.ins().f32const(Ieee32::pow2(high_bound)),
+        ),
+        ir::types::F64 => (
+            pos.ins().f64const(Ieee64::pow2(low_bound).neg()),
+            pos.ins().f64const(Ieee64::pow2(high_bound)),
+        ),
+        _ => panic!("Can't convert {}", xty),
+    };
+    let underflow = pos.ins().fcmp(low_cc, x, flow);
+    let overflow = pos.ins().fcmp(FloatCC::GreaterThanOrEqual, x, fhigh);
+    (underflow, overflow)
+}
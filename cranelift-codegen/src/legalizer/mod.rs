@@ -16,13 +16,14 @@
 use crate::bitset::BitSet;
 use crate::cursor::{Cursor, FuncCursor};
 use crate::flowgraph::ControlFlowGraph;
-use crate::ir::types::{I32, I64};
+use crate::ir::types::{I128, I32, I64};
 use crate::ir::{self, InstBuilder, MemFlags};
 use crate::isa::TargetIsa;
 use crate::predicates;
 use crate::timing;
 use alloc::collections::BTreeSet;
 use alloc::vec::Vec;
+use target_lexicon::Endianness;
 
 mod boundary;
 mod call;
@@ -35,7 +36,7 @@ mod table;
 use self::call::expand_call;
 use self::globalvalue::expand_global_value;
 use self::heap::expand_heap_addr;
-use self::libcall::expand_as_libcall;
+pub(crate) use self::libcall::expand_as_libcall;
 use self::table::expand_table_addr;
 
 enum LegalizeInstResult {
@@ -486,12 +487,83 @@ fn expand_br_icmp(
     cfg.recompute_ebb(pos.func, old_ebb);
 }
 
+/// Expand an `atomic_rmw` instruction that has no native encoding into a compare-and-swap loop.
+fn expand_atomic_rmw(
+    inst: ir::Inst,
+    func: &mut ir::Function,
+    cfg: &mut ControlFlowGraph,
+    _isa: &dyn TargetIsa,
+) {
+    use crate::ir::condcodes::IntCC;
+
+    let (op, ordering, flags, addr, arg) = match func.dfg[inst] {
+        ir::InstructionData::AtomicRmw {
+            opcode: ir::Opcode::AtomicRmw,
+            op,
+            ordering,
+            flags,
+            args,
+        } => (op, ordering, flags, args[0], args[1]),
+        _ => panic!("Expected atomic_rmw: {}", func.dfg.display_inst(inst, None)),
+    };
+    let ty = func.dfg.ctrl_typevar(inst);
+
+    // Replace `result = atomic_rmw op ordering flags addr, arg` with:
+    //
+    //   initial = atomic_load relaxed flags addr
+    //   jump loop_ebb(initial)
+    // loop_ebb(old):
+    //   new = op old, arg
+    //   prev = atomic_cas ordering flags addr, old, new
+    //   ok = icmp eq prev, old
+    //   brnz ok, done_ebb(prev)
+    //   jump loop_ebb(prev)
+    // done_ebb(result):
+    let old_ebb = func.layout.pp_ebb(inst);
+    let result = func.dfg.first_result(inst);
+    func.dfg.clear_results(inst);
+    let loop_ebb = func.dfg.make_ebb();
+    let done_ebb = func.dfg.make_ebb();
+    let old = func.dfg.append_ebb_param(loop_ebb, ty);
+    func.dfg.attach_ebb_param(done_ebb, result);
+
+    let mut pos = FuncCursor::new(func).at_inst(inst);
+    pos.use_srcloc(inst);
+    let initial = pos
+        .ins()
+        .atomic_load(ty, ir::AtomicOrdering::Relaxed, flags, addr);
+    pos.func.dfg.replace(inst).jump(loop_ebb, &[initial]);
+
+    // Everything following `inst` moves to `done_ebb`, and the loop goes in between.
+    pos.goto_after_inst(inst);
+    pos.insert_ebb(done_ebb);
+    pos.goto_top(done_ebb);
+    pos.insert_ebb(loop_ebb);
+
+    let new = match op {
+        ir::AtomicRmwOp::Add => pos.ins().iadd(old, arg),
+        ir::AtomicRmwOp::Sub => pos.ins().isub(old, arg),
+        ir::AtomicRmwOp::And => pos.ins().band(old, arg),
+        ir::AtomicRmwOp::Or => pos.ins().bor(old, arg),
+        ir::AtomicRmwOp::Xor => pos.ins().bxor(old, arg),
+        ir::AtomicRmwOp::Xchg => arg,
+    };
+    let prev = pos.ins().atomic_cas(ordering, flags, addr, old, new);
+    let ok = pos.ins().icmp(IntCC::Equal, prev, old);
+    pos.ins().brnz(ok, done_ebb, &[prev]);
+    pos.ins().jump(loop_ebb, &[prev]);
+
+    cfg.recompute_ebb(pos.func, old_ebb);
+    cfg.recompute_ebb(pos.func, loop_ebb);
+    cfg.recompute_ebb(pos.func, done_ebb);
+}
+
 /// Expand illegal `f32const` and `f64const` instructions.
 fn expand_fconst(
     inst: ir::Inst,
     func: &mut ir::Function,
     _cfg: &mut ControlFlowGraph,
-    _isa: &dyn TargetIsa,
+    isa: &dyn TargetIsa,
 ) {
     let ty = func.dfg.value_type(func.dfg.first_result(inst));
     debug_assert!(!ty.is_vector(), "Only scalar fconst supported: {}", ty);
@@ -500,6 +572,32 @@ fn expand_fconst(
     // now use an `iconst` and a bit cast.
     let mut pos = FuncCursor::new(func).at_inst(inst);
     pos.use_srcloc(inst);
+
+    // A 64-bit integer can't be bit cast on 32-bit targets, so store its halves to the stack
+    // instead.
+    if let ir::InstructionData::UnaryIeee64 {
+        opcode: ir::Opcode::F64const,
+        imm,
+    } = pos.func.dfg[inst]
+    {
+        if isa.pointer_bits() < 64 {
+            let bits = imm.bits();
+            let (first, second) = match isa.triple().endianness() {
+                Ok(Endianness::Big) => (bits >> 32, bits),
+                _ => (bits, bits >> 32),
+            };
+            let slot = pos
+                .func
+                .create_stack_slot(ir::StackSlotData::new(ir::StackSlotKind::ExplicitSlot, 8));
+            let first = pos.ins().iconst(I32, i64::from(first as u32));
+            let second = pos.ins().iconst(I32, i64::from(second as u32));
+            pos.ins().stack_store(first, slot, 0);
+            pos.ins().stack_store(second, slot, 4);
+            pos.func.dfg.replace(inst).stack_load(ty, slot, 0);
+            return;
+        }
+    }
+
     let ival = match pos.func.dfg[inst] {
         ir::InstructionData::UnaryIeee32 {
             opcode: ir::Opcode::F32const,
@@ -608,7 +706,9 @@ fn narrow_load(
         small_ty,
         flags,
         ptr,
-        offset.try_add_i64(8).expect("load offset overflow"),
+        offset
+            .try_add_i64(i64::from(small_ty.bytes()))
+            .expect("load offset overflow"),
     );
     pos.func.dfg.replace(inst).iconcat(al, ah);
 }
@@ -633,13 +733,22 @@ fn narrow_store(
         _ => panic!("Expected store: {}", pos.func.dfg.display_inst(inst, None)),
     };
 
+    let small_ty = pos
+        .func
+        .dfg
+        .value_type(val)
+        .half_width()
+        .expect("Can't narrow store");
+
     let (al, ah) = pos.ins().isplit(val);
     pos.ins().store(flags, al, ptr, offset);
     pos.ins().store(
         flags,
         ah,
         ptr,
-        offset.try_add_i64(8).expect("store offset overflow"),
+        offset
+            .try_add_i64(i64::from(small_ty.bytes()))
+            .expect("store offset overflow"),
     );
     pos.remove_inst();
 }
@@ -672,6 +781,13 @@ fn narrow_iconst(
         pos.func.dfg.replace(inst).iconcat(low, high);
         return;
     }
+    if isa.pointer_bits() == 64 && ty == I128 {
+        // The immediate is sign-extended to 128 bits.
+        let low = pos.ins().iconst(I64, imm);
+        let high = pos.ins().iconst(I64, imm >> 63);
+        pos.func.dfg.replace(inst).iconcat(low, high);
+        return;
+    }
 
     unimplemented!("missing encoding or legalization for iconst.{:?}", ty);
 }
@@ -700,12 +816,14 @@ fn narrow_icmp_imm(
     let ty = pos.func.dfg.ctrl_typevar(inst);
     let ty_half = ty.half_width().unwrap();
 
+    // The immediate is sign-extended to the width of `ty`.
+    let half_bits = u32::from(ty_half.bits());
     let imm_low = pos
         .ins()
-        .iconst(ty_half, imm & (1u128 << (ty_half.bits() - 1)) as i64);
+        .iconst(ty_half, imm & (u64::max_value() >> (64 - half_bits)) as i64);
     let imm_high = pos
         .ins()
-        .iconst(ty_half, imm.wrapping_shr(ty_half.bits().into()));
+        .iconst(ty_half, imm.checked_shr(half_bits).unwrap_or(imm >> 63));
     let (arg_low, arg_high) = pos.ins().isplit(arg);
 
     match cond {
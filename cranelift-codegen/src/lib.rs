@@ -54,6 +54,7 @@ use hashbrown::{hash_map, HashMap, HashSet};
 use std::collections::{hash_map, HashMap, HashSet};
 
 pub use crate::context::Context;
+pub use crate::inline::{inline_call, InlineCallees};
 pub use crate::legalizer::legalize_function;
 pub use crate::value_label::{ValueLabelsRanges, ValueLocRange};
 pub use crate::verifier::verify_function;
@@ -80,12 +81,15 @@ pub mod write;
 pub use crate::entity::packed_option;
 
 mod abi;
+mod alias_analysis;
 mod bitset;
 mod constant_hash;
 mod context;
 mod dce;
 mod divconst_magic_numbers;
 mod fx;
+mod induction;
+mod inline;
 mod iterators;
 mod legalizer;
 mod licm;
@@ -100,8 +104,10 @@ mod scoped_hash_map;
 mod simple_gvn;
 mod simple_preopt;
 mod stack_layout;
+mod strength_reduction;
 mod topo_order;
 mod unreachable_code;
+mod unroll;
 mod value_label;
 
 pub use crate::result::{CodegenError, CodegenResult};
@@ -597,6 +597,8 @@ impl RedundantReloadRemover {
             | InstructionData::RegFill { .. }
             | InstructionData::Call { .. }
             | InstructionData::CallIndirect { .. }
+            | InstructionData::Invoke { .. }
+            | InstructionData::InvokeIndirect { .. }
             | InstructionData::StackLoad { .. }
             | InstructionData::StackStore { .. }
             | InstructionData::Unary {
@@ -610,13 +612,21 @@ impl RedundantReloadRemover {
             | InstructionData::UnaryImm {
                 opcode: Opcode::AdjustSpDownImm,
                 ..
+            }
+            | InstructionData::UnaryImm {
+                opcode: Opcode::X86StackProbe,
+                ..
+            }
+            | InstructionData::BinaryImm {
+                opcode: Opcode::X86StackProbeLoop,
+                ..
             } => {
                 // All of these change, or might change, the memory-register bindings tracked in
                 // `avail_env` in some way we don't know about, or at least, we might be able to
                 // track, but for which the effort-to-benefit ratio seems too low to bother.  So
                 // play safe: forget everything we know.
                 //
-                // For Call/CallIndirect, we could do better when compiling for calling
+                // For calls and invokes, we could do better when compiling for calling
                 // conventions that have callee-saved registers, since bindings for them would
                 // remain valid across the call.
                 avail_env.invalidate_all();
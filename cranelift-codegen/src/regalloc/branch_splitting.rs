@@ -2,6 +2,9 @@
 //!
 //! One of the reason for splitting edges is to be able to insert `copy` and `regmove` instructions
 //! between a conditional branch and the following terminator.
+//!
+//! The results of an `invoke` are copied at the top of its normal successor for the same reason:
+//! the register allocator can't insert the spills of the results right after the `invoke`.
 #![cfg(feature = "basic-blocks")]
 
 use alloc::vec::Vec;
@@ -55,9 +58,16 @@ impl<'a> Context<'a> {
             if let Some(inst) = self.cur.prev_inst() {
                 let opcode = self.cur.func.dfg[inst].opcode();
                 if opcode.is_branch() {
-                    self.visit_conditional_branch(inst, opcode);
+                    // The edge to the landing pad of an invoke is taken by the unwinder, and
+                    // passes no arguments, so there is nothing to split.
+                    if self.cur.func.dfg[inst].landing_pad().is_none() {
+                        self.visit_conditional_branch(inst, opcode);
+                    }
                     self.cur.goto_inst(terminator_inst);
                     self.visit_terminator_branch(terminator_inst);
+                    if self.cur.func.dfg[inst].landing_pad().is_some() {
+                        self.visit_invoke_results(inst);
+                    }
                 }
             }
         }
@@ -156,6 +166,32 @@ impl<'a> Context<'a> {
         }
     }
 
+    /// Copy the results of the `invoke` instruction `inst` at the top of its normal successor.
+    ///
+    /// The successor has a single predecessor and no parameters after `visit_terminator_branch`.
+    /// The results are only live until the copies, so they never need to be spilled.
+    fn visit_invoke_results(&mut self, inst: Inst) {
+        if self.cur.func.dfg.inst_results(inst).is_empty() {
+            return;
+        }
+
+        let ebb = self.cur.func.layout.pp_ebb(inst);
+        let jump = self.cur.func.layout.last_inst(ebb).expect("terminator");
+        let target = self.cur.func.dfg[jump]
+            .branch_destination()
+            .expect("jump after invoke");
+        debug_assert!(self.cur.func.dfg.ebb_params(target).is_empty());
+
+        let results = self.cur.func.dfg.inst_results(inst).to_vec();
+        self.cur.goto_first_insertion_point(target);
+        for result in results {
+            let ty = self.cur.func.dfg.value_type(result);
+            let value = self.cur.func.dfg.replace_result(result, ty);
+            self.cur.ins().with_result(result).copy(value);
+        }
+        self.cur.goto_inst(jump);
+    }
+
     /// Returns whether we should introduce a new branch.
     fn should_split_edge(&self, target: Ebb) -> bool {
         // We should split the edge if the target has any parameters.
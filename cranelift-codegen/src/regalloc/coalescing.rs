@@ -159,6 +159,17 @@ impl<'a> Context<'a> {
             return;
         }
 
+        // The parameter of a landing pad is supplied by the unwinder, not by the `invoke`
+        // instructions branching to it.
+        let func = &self.func;
+        if self
+            .cfg
+            .pred_iter(ebb)
+            .any(|BasicBlock { inst, .. }| func.dfg[inst].landing_pad() == Some(ebb))
+        {
+            return;
+        }
+
         self.isolate_conflicting_params(ebb, num_params);
 
         for i in 0..num_params {
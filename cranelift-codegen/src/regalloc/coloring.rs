@@ -442,6 +442,9 @@ impl<'a> Context<'a> {
         let call_sig = self.cur.func.dfg.call_signature(inst);
         if let Some(sig) = call_sig {
             self.program_input_abi(inst, AbiParams::Parameters(sig));
+            if let Some(lp) = self.cur.func.dfg[inst].landing_pad() {
+                self.program_landing_pad(lp);
+            }
         } else if self.cur.func.dfg[inst].opcode().is_return() {
             self.program_input_abi(inst, AbiParams::Returns);
         } else if self.cur.func.dfg[inst].opcode().is_branch() {
@@ -526,7 +529,7 @@ impl<'a> Context<'a> {
             }
         }
 
-        if let Some(sig) = call_sig {
+        if let Some(sig) = self.cur.func.dfg.call_result_signature(inst) {
             self.program_output_abi(
                 sig,
                 defs,
@@ -824,6 +827,25 @@ impl<'a> Context<'a> {
         }
     }
 
+    /// Prepare for an `invoke` instruction that may unwind to the landing pad `lp`.
+    ///
+    /// Values that are live-in to `lp` must be in their global registers, and the exception
+    /// pointer parameter of `lp` always arrives in the ISA's exception pointer register.
+    fn program_landing_pad(&mut self, lp: Ebb) {
+        self.undivert_regs(|lr, layout| lr.is_livein(lp, layout));
+
+        let reg = self
+            .cur
+            .isa
+            .exception_pointer_reg()
+            .expect("invoke needs an exception pointer register");
+        for &param in self.cur.func.dfg.ebb_params(lp) {
+            if self.liveness[param].affinity.is_reg() {
+                self.cur.func.locations[param] = ValueLoc::Reg(reg);
+            }
+        }
+    }
+
     /// Find all diverted registers where `pred` returns `true` and undo their diversion so they
     /// are reallocated to their global register assignments.
     fn undivert_regs<Pred>(&mut self, mut pred: Pred)
@@ -173,6 +173,7 @@ impl Context {
         self.reload.run(
             isa,
             func,
+            cfg,
             domtree,
             &mut self.liveness,
             &mut self.topo,
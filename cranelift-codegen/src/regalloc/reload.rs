@@ -12,6 +12,7 @@
 use crate::cursor::{Cursor, EncCursor};
 use crate::dominator_tree::DominatorTree;
 use crate::entity::{SparseMap, SparseMapValue};
+use crate::flowgraph::{BasicBlock, ControlFlowGraph};
 use crate::ir::{AbiParam, ArgumentLoc, InstBuilder};
 use crate::ir::{Ebb, Function, Inst, InstructionData, Opcode, Value, ValueLoc};
 use crate::isa::RegClass;
@@ -39,6 +40,7 @@ struct Context<'a> {
     encinfo: EncInfo,
 
     // References to contextual data structures we need.
+    cfg: &'a ControlFlowGraph,
     domtree: &'a DominatorTree,
     liveness: &'a mut Liveness,
     topo: &'a mut TopoOrder,
@@ -67,6 +69,7 @@ impl Reload {
         &mut self,
         isa: &dyn TargetIsa,
         func: &mut Function,
+        cfg: &ControlFlowGraph,
         domtree: &DominatorTree,
         liveness: &mut Liveness,
         topo: &mut TopoOrder,
@@ -77,6 +80,7 @@ impl Reload {
         let mut ctx = Context {
             cur: EncCursor::new(func, isa),
             encinfo: isa.encoding_info(),
+            cfg,
             domtree,
             liveness,
             topo,
@@ -189,8 +193,28 @@ impl<'a> Context<'a> {
         }
     }
 
-    fn visit_ebb_params(&mut self, ebb: Ebb, _args: &[LiveValue]) {
+    fn visit_ebb_params(&mut self, ebb: Ebb, args: &[LiveValue]) {
         self.cur.goto_first_inst(ebb);
+
+        // The exception pointer parameter of a landing pad arrives in a register, even when the
+        // value was spilled.
+        let func = &self.cur.func;
+        let is_landing_pad = self
+            .cfg
+            .pred_iter(ebb)
+            .any(|BasicBlock { inst, .. }| func.dfg[inst].landing_pad() == Some(ebb));
+        if !is_landing_pad {
+            return;
+        }
+        for arg in args {
+            if arg.affinity.is_stack() {
+                let ty = self.cur.func.dfg.value_type(arg.value);
+                let reg = self.cur.func.dfg.replace_ebb_param(arg.value, ty);
+                let affinity = Affinity::Reg(self.cur.isa.regclass_for_abi_type(ty).into());
+                self.liveness.create_dead(reg, ebb, affinity);
+                self.insert_spill(ebb, arg.value, reg);
+            }
+        }
     }
 
     /// Process the instruction pointed to by `pos`, and advance the cursor to the next instruction
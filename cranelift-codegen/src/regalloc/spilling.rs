@@ -235,6 +235,44 @@ impl<'a> Context<'a> {
         self.free_dead_regs(params);
     }
 
+    /// Can the register value `lv`, which is live across the call `inst`, stay in its register?
+    ///
+    /// The unwinder doesn't restore registers the callee preserves when it enters a landing pad,
+    /// so values live across an `invoke` are always spilled.
+    fn is_preserved_across_call(&self, inst: Inst, lv: &LiveValue) -> bool {
+        let sig = match self.cur.func.dfg.call_signature(inst) {
+            Some(sig) => sig,
+            None => return false,
+        };
+        if self.cur.func.dfg[inst].landing_pad().is_some() {
+            return false;
+        }
+        match lv.affinity {
+            Affinity::Reg(rci) => self.cur.isa.is_preserved_across_call(
+                &self.cur.func.dfg.signatures[sig],
+                self.reginfo.rc(rci),
+                self.cur.func.dfg.value_type(lv.value),
+            ),
+            _ => false,
+        }
+    }
+
+    /// Is `inst` an instruction that is encoded as a call without having a call signature?
+    ///
+    /// The x86 ELF general dynamic TLS sequence calls `__tls_get_addr`, which clobbers the same
+    /// registers as any other call.
+    fn is_hidden_call(&self, inst: Inst) -> bool {
+        #[cfg(feature = "x86")]
+        {
+            self.cur.func.dfg[inst].opcode() == crate::ir::Opcode::X86ElfTlsGdAddr
+        }
+        #[cfg(not(feature = "x86"))]
+        {
+            let _ = inst;
+            false
+        }
+    }
+
     fn visit_inst(&mut self, inst: Inst, ebb: Ebb, tracker: &mut LiveValueTracker) {
         debug!("Inst {}, {}", self.cur.display_inst(inst), self.pressure);
         debug_assert_eq!(self.cur.current_inst(), Some(inst));
@@ -261,17 +299,57 @@ impl<'a> Context<'a> {
         // Update the live value tracker with this instruction.
         let (throughs, kills, defs) = tracker.process_inst(inst, &self.cur.func.dfg, self.liveness);
 
+        // Values that are killed by an `invoke` but live-in to its landing pad must survive the
+        // call too.
+        if let Some(lp) = self.cur.func.dfg[inst].landing_pad() {
+            for lv in kills {
+                if lv.affinity.is_reg()
+                    && !self.spills.contains(&lv.value)
+                    && self.liveness[lv.value].is_livein(lp, &self.cur.func.layout)
+                {
+                    self.spill_reg(lv.value);
+                }
+            }
+        }
+
         // Remove kills from the pressure tracker.
         self.free_regs(kills);
 
-        // If inst is a call, spill all register values that are live across the call.
+        // If inst is a call, spill all register values that are live across the call, unless the
+        // callee's calling convention preserves them.
         // This means that we don't currently take advantage of callee-saved registers.
         // TODO: Be more sophisticated.
-        if call_sig.is_some() {
+        if call_sig.is_some() || self.is_hidden_call(inst) {
+            let mut preserved = false;
             for lv in throughs {
                 if lv.affinity.is_reg() && !self.spills.contains(&lv.value) {
-                    self.spill_reg(lv.value);
+                    if self.is_preserved_across_call(inst, lv) {
+                        preserved = true;
+                    } else {
+                        self.spill_reg(lv.value);
+                    }
+                }
+            }
+
+            // The preserved values are still live when the call defines its return values, so
+            // make sure there are registers left for those.
+            if preserved {
+                for lv in defs {
+                    if let Affinity::Reg(rci) = lv.affinity {
+                        let rc = self.reginfo.rc(rci);
+                        while let Err(mask) = self.pressure.take_transient(rc) {
+                            match self.spill_candidate(mask, throughs) {
+                                Some(cand) => self.spill_reg(cand),
+                                None => panic!(
+                                    "Ran out of {} registers for {}",
+                                    rc,
+                                    self.cur.display_inst(inst)
+                                ),
+                            }
+                        }
+                    }
                 }
+                self.pressure.reset_transient();
             }
         }
 
@@ -453,9 +531,11 @@ impl<'a> Context<'a> {
                     // Do allow spilling of EBB arguments on branches. This is safe since we spill
                     // the whole virtual register which includes the matching EBB parameter value
                     // at the branch destination. It is also necessary since there can be
-                    // arbitrarily many EBB arguments.
+                    // arbitrarily many EBB arguments. The variable arguments of an `invoke` are
+                    // call arguments, not EBB arguments.
                     match {
-                        let args = if self.cur.func.dfg[inst].opcode().is_branch() {
+                        let opcode = self.cur.func.dfg[inst].opcode();
+                        let args = if opcode.is_branch() && !opcode.is_call() {
                             self.cur.func.dfg.inst_fixed_args(inst)
                         } else {
                             self.cur.func.dfg.inst_args(inst)
@@ -1,6 +1,7 @@
 //! Result and error types representing the outcome of compiling a function.
 
 use crate::verifier::VerifierErrors;
+use alloc::string::String;
 use thiserror::Error;
 
 /// A compilation error.
@@ -30,6 +31,11 @@ pub enum CodegenError {
     /// is exceeded, compilation fails.
     #[error("Code for function is too large")]
     CodeTooLarge,
+
+    /// The function uses a feature the target ISA doesn't support yet, such as a calling
+    /// convention it doesn't implement.
+    #[error("Unsupported feature: {0}")]
+    Unsupported(String),
 }
 
 /// A convenient alias for a `Result` that uses `CodegenError` as the error type.
@@ -379,9 +379,12 @@ mod tests {
             f.to_string(),
             "[shared]\n\
              opt_level = \"none\"\n\
+             tls_model = \"none\"\n\
+             inline_threshold = 0\n\
              libcall_call_conv = \"isa_default\"\n\
              baldrdash_prologue_words = 0\n\
              probestack_size_log2 = 12\n\
+             probestack_strategy = \"outline\"\n\
              enable_verifier = true\n\
              is_pic = false\n\
              colocated_libcalls = false\n\
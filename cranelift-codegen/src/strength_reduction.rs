@@ -0,0 +1,224 @@
+//! Strength reduction of induction variable multiplications.
+//!
+//! Inside a loop, the product of a basic induction variable and a loop-invariant factor is an
+//! induction variable too: it starts at `init * factor`, and grows by `step * factor` on every
+//! iteration. This pass replaces the `imul`, `imul_imm` and `ishl_imm` instructions computing such
+//! products with a new parameter of the loop header, which the back edges update with an addition.
+//!
+//! The pass doesn't change the CFG. It must run before legalization, because it doesn't encode
+//! the instructions it inserts.
+
+use crate::cursor::{Cursor, FuncCursor};
+use crate::dominator_tree::DominatorTree;
+use crate::flowgraph::ControlFlowGraph;
+use crate::fx::FxHashMap;
+use crate::induction::{
+    branch_args, find_induction_variables, iconst_value, loop_edges, sign_extend, value_ebb,
+    InductionVariable,
+};
+use crate::ir::{Ebb, Function, Inst, InstBuilder, InstructionData, Opcode, Value, ValueDef};
+use crate::loop_analysis::{Loop, LoopAnalysis};
+use crate::timing;
+use alloc::vec::Vec;
+
+/// The loop-invariant factor an induction variable is multiplied by.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+enum Factor {
+    Const(i64),
+    Value(Value),
+}
+
+/// A loop, and the information about it needed to reduce multiplications.
+struct LoopInfo<'a> {
+    lp: Loop,
+    header: Ebb,
+    ivs: Vec<InductionVariable>,
+    back_edges: Vec<Inst>,
+    entry_edges: Vec<Inst>,
+    domtree: &'a DominatorTree,
+    loop_analysis: &'a LoopAnalysis,
+}
+
+/// Replace the multiplications of induction variables by loop-invariant factors with additions.
+pub fn do_strength_reduction(
+    func: &mut Function,
+    cfg: &ControlFlowGraph,
+    domtree: &DominatorTree,
+    loop_analysis: &LoopAnalysis,
+) {
+    let _tt = timing::strength_reduction();
+    debug_assert!(cfg.is_valid());
+    debug_assert!(domtree.is_valid());
+    debug_assert!(loop_analysis.is_valid());
+
+    for lp in loop_analysis.loops() {
+        let ivs = find_induction_variables(func, cfg, loop_analysis, lp);
+        if ivs.is_empty() {
+            continue;
+        }
+        let (back_edges, entry_edges) = loop_edges(cfg, loop_analysis, func, lp).unwrap();
+        let info = LoopInfo {
+            lp,
+            header: loop_analysis.loop_header(lp),
+            ivs,
+            back_edges,
+            entry_edges,
+            domtree,
+            loop_analysis,
+        };
+
+        // The values replacing the products that have been reduced already.
+        let mut reduced: FxHashMap<(Value, Factor), Value> = FxHashMap::default();
+        let ebbs: Vec<Ebb> = func
+            .layout
+            .ebbs()
+            .filter(|&ebb| loop_analysis.is_in_loop(ebb, lp))
+            .collect();
+        let mut pos = FuncCursor::new(func);
+        for ebb in ebbs {
+            pos.goto_top(ebb);
+            while let Some(inst) = pos.next_inst() {
+                let (iv, factor) = match product(pos.func, &info, inst) {
+                    Some(product) => product,
+                    None => continue,
+                };
+                let value = *reduced
+                    .entry((iv.param, factor))
+                    .or_insert_with(|| reduce(pos.func, &info, &iv, factor));
+                let result = pos.func.dfg.first_result(inst);
+                pos.func.dfg.clear_results(inst);
+                pos.func.dfg.change_to_alias(result, value);
+                pos.remove_inst_and_step_back();
+            }
+        }
+    }
+}
+
+/// If `inst` multiplies an induction variable by a loop-invariant factor, get them.
+fn product(func: &Function, info: &LoopInfo, inst: Inst) -> Option<(InductionVariable, Factor)> {
+    let dfg = &func.dfg;
+    let (arg, factor) = match dfg[inst] {
+        InstructionData::BinaryImm {
+            opcode: Opcode::ImulImm,
+            arg,
+            imm,
+        } => (arg, Factor::Const(imm.into())),
+        InstructionData::BinaryImm {
+            opcode: Opcode::IshlImm,
+            arg,
+            imm,
+        } => {
+            // The shift amount is taken modulo the width of the type.
+            let shift: i64 = imm.into();
+            let mask = u32::from(dfg.value_type(arg).bits()) - 1;
+            (arg, Factor::Const(1i64.wrapping_shl(shift as u32 & mask)))
+        }
+        InstructionData::Binary {
+            opcode: Opcode::Imul,
+            args,
+        } => {
+            let is_iv = |arg| {
+                info.ivs
+                    .iter()
+                    .any(|iv| iv.param == dfg.resolve_aliases(arg))
+            };
+            let (arg, other) = if is_iv(args[0]) {
+                (args[0], args[1])
+            } else {
+                (args[1], args[0])
+            };
+            let factor = match iconst_value(dfg, other) {
+                Some(imm) => Factor::Const(imm),
+                None => Factor::Value(invariant_value(func, info, other)?),
+            };
+            (arg, factor)
+        }
+        _ => return None,
+    };
+    let arg = dfg.resolve_aliases(arg);
+    let iv = info.ivs.iter().find(|iv| iv.param == arg)?;
+    Some((*iv, factor))
+}
+
+/// Check that `value` is defined outside the loop, and is available on all of its entry edges.
+fn invariant_value(func: &Function, info: &LoopInfo, value: Value) -> Option<Value> {
+    let value = func.dfg.resolve_aliases(value);
+    let ebb = value_ebb(func, value)?;
+    if info.loop_analysis.is_in_loop(ebb, info.lp) {
+        return None;
+    }
+    let def = func.dfg.value_def(value);
+    if info
+        .entry_edges
+        .iter()
+        .all(|&branch| info.domtree.dominates(def, branch, &func.layout))
+    {
+        Some(value)
+    } else {
+        None
+    }
+}
+
+/// Create the induction variable `iv * factor` as a new parameter of the loop header.
+fn reduce(func: &mut Function, info: &LoopInfo, iv: &InductionVariable, factor: Factor) -> Value {
+    let ty = func.dfg.value_type(iv.param);
+    let value = func.dfg.append_ebb_param(info.header, ty);
+
+    // The amount to add on every iteration. A variable one is computed where the factor is
+    // defined, outside the loop.
+    let increment = match factor {
+        Factor::Const(imm) => Factor::Const(iv.step.wrapping_mul(imm)),
+        Factor::Value(factor) if iv.step == 1 => Factor::Value(factor),
+        Factor::Value(factor) => {
+            let mut pos = FuncCursor::new(func);
+            match pos.func.dfg.value_def(factor) {
+                ValueDef::Result(inst, _) => pos.goto_after_inst(inst),
+                ValueDef::Param(ebb, _) => pos.goto_first_insertion_point(ebb),
+            }
+            Factor::Value(pos.ins().imul_imm(factor, iv.step))
+        }
+    };
+
+    for &branch in &info.entry_edges {
+        let init = branch_args(&func.dfg, branch).unwrap()[iv.index];
+        let point = insertion_point(func, branch);
+        let mut pos = FuncCursor::new(func).at_inst(point);
+        let start = match factor {
+            Factor::Const(imm) => match iconst_value(&pos.func.dfg, init) {
+                Some(init) => pos
+                    .ins()
+                    .iconst(ty, sign_extend(init.wrapping_mul(imm), ty.bits())),
+                None => pos.ins().imul_imm(init, imm),
+            },
+            Factor::Value(factor) => pos.ins().imul(init, factor),
+        };
+        pos.func.dfg.append_inst_arg(branch, start);
+    }
+
+    for &branch in &info.back_edges {
+        let point = insertion_point(func, branch);
+        let mut pos = FuncCursor::new(func).at_inst(point);
+        let next = match increment {
+            Factor::Const(imm) => pos.ins().iadd_imm(value, imm),
+            Factor::Value(increment) => pos.ins().iadd(value, increment),
+        };
+        pos.func.dfg.append_inst_arg(branch, next);
+    }
+
+    value
+}
+
+/// Get the instruction to insert the computation of a new argument of `branch` before.
+///
+/// Instructions can't be inserted between the branches at the end of an EBB, so this is the first
+/// of them.
+fn insertion_point(func: &Function, branch: Inst) -> Inst {
+    let mut inst = branch;
+    while let Some(prev) = func.layout.prev_inst(inst) {
+        if !func.dfg[prev].opcode().is_branch() {
+            break;
+        }
+        inst = prev;
+    }
+    inst
+}
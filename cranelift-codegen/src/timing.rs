@@ -60,8 +60,12 @@ define_passes! {
     dce: "Dead code elimination",
     legalize: "Legalization",
     gvn: "Global value numbering",
+    alias_analysis: "Alias analysis",
     licm: "Loop invariant code motion",
+    strength_reduction: "Strength reduction",
+    unroll: "Loop unrolling",
     unreachable_code: "Remove unreachable blocks",
+    inline: "Function inlining",
 
     regalloc: "Register allocation",
     ra_liveness: "RA liveness analysis",
@@ -0,0 +1,432 @@
+//! Partial unrolling of small counted loops.
+//!
+//! A loop is a candidate for unrolling when it consists of a single EBB ending in a conditional
+//! branch and a jump, one of which is the back edge. Its exit condition must compare a basic
+//! induction variable with a constant, and the loop must always be entered with the same constant
+//! value of the induction variable, so the number of iterations is known. The loop body is then
+//! copied a number of times that divides the number of iterations, and only the last copy tests
+//! the exit condition.
+//!
+//! This pass must run before legalization, because it doesn't encode the instructions it inserts.
+
+use crate::cursor::{Cursor, FuncCursor};
+use crate::dominator_tree::DominatorTree;
+use crate::flowgraph::ControlFlowGraph;
+use crate::fx::FxHashMap;
+use crate::induction::{
+    branch_args, find_induction_variables, iconst_value, loop_edges, offset_from, value_ebb,
+};
+use crate::ir::condcodes::{CondCode, IntCC};
+use crate::ir::{
+    Ebb, Function, Inst, InstBuilder, InstructionData, Opcode, Type, Value, ValueDef, ValueList,
+};
+use crate::loop_analysis::{Loop, LoopAnalysis};
+use crate::timing;
+use alloc::vec::Vec;
+use core::convert::TryFrom;
+
+/// The unroll factors to try, in order of preference.
+const UNROLL_FACTORS: [usize; 2] = [4, 2];
+
+/// The maximum number of instructions in an unrolled loop.
+const MAX_UNROLLED_INSTS: usize = 64;
+
+/// A loop to unroll.
+struct Unroll {
+    header: Ebb,
+    /// The conditional branch at the end of the header.
+    branch: Inst,
+    /// The jump at the end of the header.
+    jump: Inst,
+    /// The one of `branch` and `jump` that goes back to the header.
+    back_edge: Inst,
+    /// The number of copies of the loop body.
+    factor: usize,
+}
+
+/// An operand of a comparison.
+#[derive(Clone, Copy)]
+enum Operand {
+    Value(Value),
+    Imm(i64),
+}
+
+/// Unroll the small loops of `func` with a constant number of iterations.
+pub fn do_loop_unrolling(
+    func: &mut Function,
+    cfg: &mut ControlFlowGraph,
+    domtree: &mut DominatorTree,
+    loop_analysis: &mut LoopAnalysis,
+) {
+    let _tt = timing::unroll();
+    debug_assert!(cfg.is_valid());
+    debug_assert!(domtree.is_valid());
+    debug_assert!(loop_analysis.is_valid());
+
+    let loops: Vec<Unroll> = loop_analysis
+        .loops()
+        .filter_map(|lp| analyze_loop(func, cfg, loop_analysis, lp))
+        .collect();
+    if loops.is_empty() {
+        return;
+    }
+    for unroll in &loops {
+        unroll_loop(func, unroll);
+    }
+
+    cfg.compute(func);
+    domtree.compute(func, cfg);
+    loop_analysis.compute(func, cfg, domtree);
+}
+
+/// Determine if and how the loop `lp` can be unrolled.
+fn analyze_loop(
+    func: &Function,
+    cfg: &ControlFlowGraph,
+    loop_analysis: &LoopAnalysis,
+    lp: Loop,
+) -> Option<Unroll> {
+    let dfg = &func.dfg;
+    let header = loop_analysis.loop_header(lp);
+    if func
+        .layout
+        .ebbs()
+        .any(|ebb| ebb != header && loop_analysis.is_in_loop(ebb, lp))
+    {
+        return None;
+    }
+
+    let jump = func.layout.last_inst(header)?;
+    let branch = func.layout.prev_inst(jump)?;
+    match (dfg[branch].opcode(), dfg[jump].opcode()) {
+        (Opcode::Brz, Opcode::Jump) | (Opcode::Brnz, Opcode::Jump) => {}
+        (Opcode::BrIcmp, Opcode::Jump) => {}
+        _ => return None,
+    }
+    let num_insts = func.layout.ebb_insts(header).count();
+    if func
+        .layout
+        .ebb_insts(header)
+        .take(num_insts - 2)
+        .any(|inst| {
+            let opcode = dfg[inst].opcode();
+            opcode.is_branch() || opcode.is_terminator()
+        })
+    {
+        return None;
+    }
+
+    // Get the condition under which the loop continues, as `lhs cc rhs`.
+    let (cc, lhs, rhs) = branch_condition(func, branch)?;
+    let (back_edge, cc) = match (
+        dfg[branch].branch_destination(),
+        dfg[jump].branch_destination(),
+    ) {
+        (Some(dest), Some(exit)) if dest == header && exit != header => (branch, cc),
+        (Some(exit), Some(dest)) if dest == header && exit != header => (jump, cc.inverse()),
+        _ => return None,
+    };
+
+    // The loop must be entered with the same constant value of the induction variable.
+    let (_, entry_edges) = loop_edges(cfg, loop_analysis, func, lp)?;
+    let ivs = find_induction_variables(func, cfg, loop_analysis, lp);
+    let trips = [(cc, lhs, rhs), (cc.reverse(), rhs, lhs)]
+        .iter()
+        .filter_map(|&(cc, x, bound)| {
+            let x = match x {
+                Operand::Value(x) => x,
+                Operand::Imm(_) => return None,
+            };
+            let bound = match bound {
+                Operand::Value(bound) => iconst_value(dfg, bound)?,
+                Operand::Imm(imm) => imm,
+            };
+            ivs.iter().find_map(|iv| {
+                let offset = offset_from(dfg, x, iv.param)?;
+                let mut inits = entry_edges
+                    .iter()
+                    .map(|&branch| iconst_value(dfg, branch_args(dfg, branch).unwrap()[iv.index]));
+                let init = inits.next()??;
+                if !inits.all(|other| other == Some(init)) {
+                    return None;
+                }
+                let bits = dfg.value_type(iv.param).bits();
+                trip_count(cc, init.wrapping_add(offset), iv.step, bound, bits)
+            })
+        })
+        .next()?;
+
+    // A value defined in the loop that is used after it would refer to the wrong copy.
+    for ebb in func.layout.ebbs().filter(|&ebb| ebb != header) {
+        for inst in func.layout.ebb_insts(ebb) {
+            if dfg
+                .inst_args(inst)
+                .iter()
+                .any(|&arg| value_ebb(func, dfg.resolve_aliases(arg)) == Some(header))
+            {
+                return None;
+            }
+        }
+    }
+
+    let factor = UNROLL_FACTORS
+        .iter()
+        .cloned()
+        .find(|&factor| trips % factor as u64 == 0 && num_insts * factor <= MAX_UNROLLED_INSTS)?;
+    Some(Unroll {
+        header,
+        branch,
+        jump,
+        back_edge,
+        factor,
+    })
+}
+
+/// Get the condition under which the conditional branch `branch` is taken, as `lhs cc rhs`.
+fn branch_condition(func: &Function, branch: Inst) -> Option<(IntCC, Operand, Operand)> {
+    let dfg = &func.dfg;
+    let args = dfg.inst_fixed_args(branch);
+    // `brnz` is taken if its argument is true, and `brz` if it is false.
+    let taken_if_true = match dfg[branch] {
+        InstructionData::BranchIcmp { cond, .. } => {
+            return Some((cond, Operand::Value(args[0]), Operand::Value(args[1])));
+        }
+        InstructionData::Branch { opcode, .. } => opcode == Opcode::Brnz,
+        _ => return None,
+    };
+
+    let inst = match dfg.value_def(dfg.resolve_aliases(args[0])) {
+        ValueDef::Result(inst, _) => inst,
+        ValueDef::Param(..) => return None,
+    };
+    let (cond, lhs, rhs) = match dfg[inst] {
+        InstructionData::IntCompare {
+            opcode: Opcode::Icmp,
+            cond,
+            args,
+        } => (cond, Operand::Value(args[0]), Operand::Value(args[1])),
+        InstructionData::IntCompareImm {
+            opcode: Opcode::IcmpImm,
+            cond,
+            arg,
+            imm,
+        } => (cond, Operand::Value(arg), Operand::Imm(imm.into())),
+        _ => return None,
+    };
+    if taken_if_true {
+        Some((cond, lhs, rhs))
+    } else {
+        Some((cond.inverse(), lhs, rhs))
+    }
+}
+
+/// Compute the number of iterations of a loop that continues while `x cc bound`, where `x` is
+/// `start` in the first iteration and grows by `step` in `bits`-bit arithmetic.
+///
+/// Returns `None` if `x` would wrap around before the loop exits.
+fn trip_count(cc: IntCC, start: i64, step: i64, bound: i64, bits: u16) -> Option<u64> {
+    let modulus = 1i128 << bits;
+    let signed = match cc {
+        IntCC::SignedLessThan
+        | IntCC::SignedLessThanOrEqual
+        | IntCC::SignedGreaterThan
+        | IntCC::SignedGreaterThanOrEqual => true,
+        _ => false,
+    };
+    let interpret = |value: i64| {
+        let value = i128::from(value) & (modulus - 1);
+        if signed && value >= modulus / 2 {
+            value - modulus
+        } else {
+            value
+        }
+    };
+    let (min, max) = if signed {
+        (-modulus / 2, modulus / 2 - 1)
+    } else {
+        (0, modulus - 1)
+    };
+    let (x, bound, step) = (interpret(start), interpret(bound), i128::from(step));
+
+    // The number of times the loop continues.
+    let n = match cc {
+        IntCC::Equal => {
+            // The step is non-zero, so `x` is only equal to `bound` in the first iteration.
+            return Some(if x == bound { 2 } else { 1 });
+        }
+        IntCC::NotEqual => {
+            // This is computed modulo 2^bits, so wrapping around is fine.
+            let distance = if step > 0 { bound - x } else { x - bound };
+            let distance = distance.rem_euclid(modulus);
+            if distance % step.abs() != 0 {
+                return None;
+            }
+            distance / step.abs()
+        }
+        IntCC::SignedLessThan | IntCC::UnsignedLessThan if x >= bound => 0,
+        IntCC::SignedLessThan | IntCC::UnsignedLessThan if step > 0 => {
+            (bound - x + step - 1) / step
+        }
+        IntCC::SignedLessThanOrEqual | IntCC::UnsignedLessThanOrEqual if x > bound => 0,
+        IntCC::SignedLessThanOrEqual | IntCC::UnsignedLessThanOrEqual if step > 0 => {
+            (bound - x) / step + 1
+        }
+        IntCC::SignedGreaterThan | IntCC::UnsignedGreaterThan if x <= bound => 0,
+        IntCC::SignedGreaterThan | IntCC::UnsignedGreaterThan if step < 0 => {
+            (x - bound - step - 1) / -step
+        }
+        IntCC::SignedGreaterThanOrEqual | IntCC::UnsignedGreaterThanOrEqual if x < bound => 0,
+        IntCC::SignedGreaterThanOrEqual | IntCC::UnsignedGreaterThanOrEqual if step < 0 => {
+            (x - bound) / -step + 1
+        }
+        _ => return None,
+    };
+
+    // Ordered comparisons only work as expected if `x` doesn't wrap around.
+    let last = x + n * step;
+    if cc != IntCC::NotEqual && (last < min || last > max) {
+        return None;
+    }
+    u64::try_from(n + 1).ok()
+}
+
+/// Unroll a loop, as determined by `analyze_loop`.
+fn unroll_loop(func: &mut Function, unroll: &Unroll) {
+    let header = unroll.header;
+    let body: Vec<Inst> = func
+        .layout
+        .ebb_insts(header)
+        .take_while(|&inst| inst != unroll.branch)
+        .collect();
+    let back_args = branch_args(&func.dfg, unroll.back_edge).unwrap().to_vec();
+    let param_types: Vec<Type> = func
+        .dfg
+        .ebb_params(header)
+        .iter()
+        .map(|&param| func.dfg.value_type(param))
+        .collect();
+
+    let mut copies = Vec::with_capacity(unroll.factor - 1);
+    let mut prev = header;
+    for _ in 1..unroll.factor {
+        let ebb = func.dfg.make_ebb();
+        for &ty in &param_types {
+            func.dfg.append_ebb_param(ebb, ty);
+        }
+        func.layout.insert_ebb_after(ebb, prev);
+        copies.push(ebb);
+        prev = ebb;
+    }
+
+    // Only the last copy tests the exit condition.
+    func.layout.remove_inst(unroll.branch);
+    func.layout.remove_inst(unroll.jump);
+    let mut pos = FuncCursor::new(func).at_bottom(header);
+    pos.ins().jump(copies[0], &back_args);
+
+    for (i, &ebb) in copies.iter().enumerate() {
+        let mut values: FxHashMap<Value, Value> = pos
+            .func
+            .dfg
+            .ebb_params(header)
+            .iter()
+            .cloned()
+            .zip(pos.func.dfg.ebb_params(ebb).iter().cloned())
+            .collect();
+        pos.goto_bottom(ebb);
+        for &inst in &body {
+            let copy = copy_inst(pos.func, inst, &mut values);
+            pos.insert_inst(copy);
+        }
+        match copies.get(i + 1) {
+            Some(&next) => {
+                let args: Vec<Value> = back_args.iter().map(|&arg| map(&values, arg)).collect();
+                pos.ins().jump(next, &args);
+            }
+            None => {
+                let branch = copy_inst(pos.func, unroll.branch, &mut values);
+                pos.insert_inst(branch);
+                let jump = copy_inst(pos.func, unroll.jump, &mut values);
+                pos.insert_inst(jump);
+            }
+        }
+    }
+}
+
+/// Get the copy of `value` in `values`, if any.
+fn map(values: &FxHashMap<Value, Value>, value: Value) -> Value {
+    values.get(&value).cloned().unwrap_or(value)
+}
+
+/// Create a copy of `inst` that uses the copies of its arguments in `values`, and add its results
+/// to `values`. The copy is not inserted in the layout.
+fn copy_inst(func: &mut Function, inst: Inst, values: &mut FxHashMap<Value, Value>) -> Inst {
+    let dfg = &mut func.dfg;
+    let args: Vec<Value> = dfg
+        .inst_args(inst)
+        .iter()
+        .map(|&arg| map(values, dfg.resolve_aliases(arg)))
+        .collect();
+    let mut data = dfg[inst].clone();
+    if data.take_value_list().is_some() {
+        data.put_value_list(ValueList::from_slice(&args, &mut dfg.value_lists));
+    } else {
+        data.arguments_mut(&mut dfg.value_lists)
+            .copy_from_slice(&args);
+    }
+
+    let copy = dfg.make_inst(data);
+    dfg.make_inst_results(copy, dfg.ctrl_typevar(inst));
+    for (&result, &new_result) in dfg.inst_results(inst).iter().zip(dfg.inst_results(copy)) {
+        values.insert(result, new_result);
+    }
+    func.srclocs[copy] = func.srclocs[inst];
+    copy
+}
+
+#[cfg(test)]
+mod tests {
+    use super::trip_count;
+    use crate::ir::condcodes::IntCC;
+
+    #[test]
+    fn trip_counts() {
+        // for (i = 0; i < 16; i++)
+        assert_eq!(trip_count(IntCC::SignedLessThan, 1, 1, 16, 32), Some(16));
+        assert_eq!(trip_count(IntCC::UnsignedLessThan, 1, 3, 16, 32), Some(6));
+        assert_eq!(
+            trip_count(IntCC::UnsignedLessThanOrEqual, 0, 4, 16, 32),
+            Some(6)
+        );
+        // The loop body always runs at least once.
+        assert_eq!(trip_count(IntCC::SignedLessThan, 20, 1, 16, 32), Some(1));
+        // Counting down.
+        assert_eq!(trip_count(IntCC::SignedGreaterThan, 9, -1, 0, 64), Some(10));
+        assert_eq!(
+            trip_count(IntCC::SignedGreaterThanOrEqual, 9, -2, 0, 64),
+            Some(6)
+        );
+        assert_eq!(trip_count(IntCC::UnsignedLessThan, 9, -1, 100, 32), None);
+        // Equality is modular.
+        assert_eq!(trip_count(IntCC::NotEqual, 10, -2, 0, 64), Some(6));
+        assert_eq!(trip_count(IntCC::NotEqual, -4, 1, 0, 8), Some(5));
+        assert_eq!(trip_count(IntCC::NotEqual, 1, 2, 8, 32), None);
+        assert_eq!(trip_count(IntCC::Equal, 8, 2, 8, 32), Some(2));
+        // Wrapping around.
+        assert_eq!(
+            trip_count(IntCC::SignedLessThan, 0, 1, i64::from(i32::max_value()), 32),
+            Some(0x8000_0000)
+        );
+        assert_eq!(
+            trip_count(
+                IntCC::SignedLessThanOrEqual,
+                0,
+                1,
+                i64::from(i32::max_value()),
+                32
+            ),
+            None
+        );
+        assert_eq!(trip_count(IntCC::UnsignedLessThan, 0, 7, -1, 8), None);
+    }
+}
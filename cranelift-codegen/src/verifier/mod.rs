@@ -40,6 +40,13 @@
 //!   function.
 //! - All return instructions must have return value operands matching the current
 //!   function signature.
+//! - Thread-local symbols can only be accessed on target ISAs that support them, with a
+//!   `tls_model` setting.
+//! - Tail calls must call a function with the same return types and calling convention as the
+//!   current function. Unless the callee pops its arguments, the stack arguments must fit in the
+//!   current function's incoming argument area.
+//! - The landing pad of an invoke must take the exception pointer as its only parameter, and can
+//!   only be reached by unwinding. Functions containing invokes must have a personality routine.
 //!
 //! Global values
 //!
@@ -65,12 +72,13 @@ use crate::ir;
 use crate::ir::entities::AnyEntity;
 use crate::ir::instructions::{BranchInfo, CallInfo, InstructionFormat, ResolvedConstraint};
 use crate::ir::{
-    types, ArgumentLoc, Ebb, FuncRef, Function, GlobalValue, Inst, InstructionData, JumpTable,
-    Opcode, SigRef, StackSlot, StackSlotKind, Type, Value, ValueDef, ValueList, ValueLoc,
+    types, ArgumentLoc, ArgumentPurpose, Ebb, FuncRef, Function, GlobalValue, Inst,
+    InstructionData, JumpTable, Opcode, SigRef, StackSlot, StackSlotKind, Type, Value, ValueDef,
+    ValueList, ValueLoc,
 };
-use crate::isa::TargetIsa;
+use crate::isa::{CallConv, TargetIsa};
 use crate::iterators::IteratorExtras;
-use crate::settings::FlagsOrIsa;
+use crate::settings::{FlagsOrIsa, TlsModel};
 use crate::timing;
 use alloc::collections::BTreeSet;
 use alloc::string::String;
@@ -540,9 +548,9 @@ impl<'a> Verifier<'a> {
         }
 
         let num_fixed_results = inst_data.opcode().constraints().num_fixed_results();
-        // var_results is 0 if we aren't a call instruction
+        // var_results is 0 if we aren't a call instruction, or if it is a tail call
         let var_results = dfg
-            .call_signature(inst)
+            .call_result_signature(inst)
             .map_or(0, |sig| dfg.signatures[sig].returns.len());
         let total_results = num_fixed_results + var_results;
 
@@ -643,6 +651,26 @@ impl<'a> Verifier<'a> {
                 self.verify_sig_ref(inst, sig_ref, errors)?;
                 self.verify_value_list(inst, args, errors)?;
             }
+            Invoke {
+                func_ref,
+                destination,
+                ref args,
+                ..
+            } => {
+                self.verify_func_ref(inst, func_ref, errors)?;
+                self.verify_ebb(inst, destination, errors)?;
+                self.verify_value_list(inst, args, errors)?;
+            }
+            InvokeIndirect {
+                sig_ref,
+                destination,
+                ref args,
+                ..
+            } => {
+                self.verify_sig_ref(inst, sig_ref, errors)?;
+                self.verify_ebb(inst, destination, errors)?;
+                self.verify_value_list(inst, args, errors)?;
+            }
             FuncAddr { func_ref, .. } => {
                 self.verify_func_ref(inst, func_ref, errors)?;
             }
@@ -698,6 +726,22 @@ impl<'a> Verifier<'a> {
                 self.verify_bitcast(inst, arg, errors)?;
             }
 
+            AtomicLoad { .. }
+            | AtomicStore { .. }
+            | AtomicRmw { .. }
+            | AtomicCas { .. }
+            | Fence { .. } => {
+                if let Some(isa) = &self.isa {
+                    if !isa.flags().enable_atomics() {
+                        return fatal!(
+                            errors,
+                            inst,
+                            "Atomic instructions cannot be used without enable_atomics"
+                        );
+                    }
+                }
+            }
+
             // Exhaustive list so we can't forget to add new formats
             Unary { .. }
             | UnaryImm { .. }
@@ -902,11 +946,18 @@ impl<'a> Verifier<'a> {
                         def_inst
                     );
                 }
-                // Defining instruction dominates the instruction that uses the value.
+                // Defining instruction dominates the instruction that uses the value. The results
+                // of an invoke are only available once the callee has returned normally, which
+                // excludes the landing pad.
+                let def_point = if dfg[def_inst].landing_pad().is_some() {
+                    self.func.layout.next_inst(def_inst).unwrap_or(def_inst)
+                } else {
+                    def_inst
+                };
                 if is_reachable {
                     if !self
                         .expected_domtree
-                        .dominates(def_inst, loc_inst, &self.func.layout)
+                        .dominates(def_point, loc_inst, &self.func.layout)
                     {
                         return fatal!(
                             errors,
@@ -1134,6 +1185,8 @@ impl<'a> Verifier<'a> {
         let _ = self.typecheck_fixed_args(inst, ctrl_type, errors);
         let _ = self.typecheck_variable_args(inst, errors);
         let _ = self.typecheck_return(inst, errors);
+        let _ = self.typecheck_tail_call(inst, errors);
+        let _ = self.typecheck_invoke(inst, errors);
         let _ = self.typecheck_special(inst, ctrl_type, errors);
 
         // Misuses of copy_nop instructions are fatal
@@ -1225,6 +1278,8 @@ impl<'a> Verifier<'a> {
         errors: &mut VerifierErrors,
     ) -> VerifierStepResult<()> {
         match self.func.dfg.analyze_branch(inst) {
+            // Landing pad parameters are checked by `typecheck_invoke`.
+            BranchInfo::SingleDest(_, _) if self.func.dfg[inst].landing_pad().is_some() => {}
             BranchInfo::SingleDest(ebb, _) => {
                 let iter = self
                     .func
@@ -1264,20 +1319,14 @@ impl<'a> Verifier<'a> {
         }
 
         match self.func.dfg[inst].analyze_call(&self.func.dfg.value_lists) {
-            CallInfo::Direct(func_ref, _) => {
+            CallInfo::Direct(func_ref, args) => {
                 let sig_ref = self.func.dfg.ext_funcs[func_ref].signature;
-                let arg_types = self.func.dfg.signatures[sig_ref]
-                    .params
-                    .iter()
-                    .map(|a| a.value_type);
+                let arg_types = self.call_arg_types(sig_ref, args);
                 self.typecheck_variable_args_iterator(inst, arg_types, errors)?;
                 self.check_outgoing_args(inst, sig_ref, errors)?;
             }
-            CallInfo::Indirect(sig_ref, _) => {
-                let arg_types = self.func.dfg.signatures[sig_ref]
-                    .params
-                    .iter()
-                    .map(|a| a.value_type);
+            CallInfo::Indirect(sig_ref, args) => {
+                let arg_types = self.call_arg_types(sig_ref, args);
                 self.typecheck_variable_args_iterator(inst, arg_types, errors)?;
                 self.check_outgoing_args(inst, sig_ref, errors)?;
             }
@@ -1286,6 +1335,26 @@ impl<'a> Verifier<'a> {
         Ok(())
     }
 
+    /// Get the expected types of the arguments `args` of a call with signature `sig_ref`.
+    ///
+    /// The variadic arguments that a call passes beyond the parameters of a variadic signature
+    /// can have any type.
+    fn call_arg_types<'b>(
+        &'b self,
+        sig_ref: SigRef,
+        args: &'b [Value],
+    ) -> impl Iterator<Item = Type> + 'b {
+        let sig = &self.func.dfg.signatures[sig_ref];
+        let extra_args = match sig.fixed_params() {
+            Some(_) if args.len() > sig.params.len() => &args[sig.params.len()..],
+            _ => &[],
+        };
+        sig.params
+            .iter()
+            .map(|a| a.value_type)
+            .chain(extra_args.iter().map(move |&v| self.func.dfg.value_type(v)))
+    }
+
     fn typecheck_variable_args_iterator<I: Iterator<Item = Type>>(
         &self,
         inst: Inst,
@@ -1428,6 +1497,146 @@ impl<'a> Verifier<'a> {
         Ok(())
     }
 
+    fn typecheck_tail_call(
+        &self,
+        inst: Inst,
+        errors: &mut VerifierErrors,
+    ) -> VerifierStepResult<()> {
+        let opcode = self.func.dfg[inst].opcode();
+        if !opcode.is_call() || !opcode.is_terminator() {
+            return Ok(());
+        }
+
+        if let Some(isa) = self.isa {
+            if !isa.supports_tail_calls() {
+                return nonfatal!(
+                    errors,
+                    inst,
+                    "tail calls are not supported on {}",
+                    isa.name()
+                );
+            }
+        }
+
+        let sig_ref = self.func.dfg.call_signature(inst).unwrap();
+        let callee = &self.func.dfg.signatures[sig_ref];
+        let caller = &self.func.signature;
+
+        match caller.call_conv {
+            // Cold functions preserve the registers holding their arguments, so restoring them
+            // before a tail call would clobber the arguments of the callee.
+            CallConv::BaldrdashSystemV
+            | CallConv::BaldrdashWindows
+            | CallConv::Probestack
+            | CallConv::Cold => {
+                return nonfatal!(
+                    errors,
+                    inst,
+                    "tail calls are not supported by the {} calling convention",
+                    caller.call_conv
+                );
+            }
+            _ => {}
+        }
+        if callee.call_conv != caller.call_conv {
+            return nonfatal!(
+                errors,
+                inst,
+                "tail call from a {} function to a {} function",
+                caller.call_conv,
+                callee.call_conv
+            );
+        }
+
+        // Ignore the frame pointer and callee-saved registers that the prologue and epilogue
+        // insertion adds to the function's own signature.
+        let caller_returns = caller
+            .returns
+            .iter()
+            .filter(|r| {
+                r.purpose != ArgumentPurpose::FramePointer
+                    && r.purpose != ArgumentPurpose::CalleeSaved
+            })
+            .map(|r| r.value_type);
+        if !caller_returns.eq(callee.returns.iter().map(|r| r.value_type)) {
+            return nonfatal!(
+                errors,
+                inst,
+                "tail call returns must match function signature"
+            );
+        }
+
+        // Our caller only pops the argument area it allocated for us, so the callee's arguments
+        // must fit in it unless the callee pops them itself.
+        if !caller.call_conv.callee_pops_args()
+            && caller.params_assigned()
+            && callee.params_assigned()
+        {
+            let available = caller.stack_args_size();
+            let needed = callee.stack_args_size();
+            if needed > available {
+                return nonfatal!(
+                    errors,
+                    inst,
+                    "tail call needs {} bytes of stack arguments, but only {} are available",
+                    needed,
+                    available
+                );
+            }
+        }
+        Ok(())
+    }
+
+    fn typecheck_invoke(&self, inst: Inst, errors: &mut VerifierErrors) -> VerifierStepResult<()> {
+        let landing_pad = match self.func.dfg[inst].landing_pad() {
+            Some(ebb) => ebb,
+            None => return Ok(()),
+        };
+
+        if self.func.personality.is_none() {
+            return nonfatal!(errors, inst, "invoke requires a personality routine");
+        }
+
+        if let Some(isa) = self.isa {
+            if isa.exception_pointer_reg().is_none() {
+                return nonfatal!(errors, inst, "invoke is not supported on {}", isa.name());
+            }
+        }
+
+        let sig_ref = self.func.dfg.call_signature(inst).unwrap();
+        let call_conv = self.func.dfg.signatures[sig_ref].call_conv;
+        if call_conv.callee_pops_args() {
+            return nonfatal!(errors, inst, "can't invoke a {} function", call_conv);
+        }
+
+        // The unwinder passes the exception pointer in a pointer-sized register.
+        let params = self.func.dfg.ebb_params(landing_pad);
+        let is_pointer = |ty: Type| match self.isa {
+            Some(isa) => ty == isa.pointer_type(),
+            None => ty.is_int(),
+        };
+        if params.len() != 1 || !is_pointer(self.func.dfg.value_type(params[0])) {
+            return nonfatal!(
+                errors,
+                inst,
+                "landing pad {} must take the exception pointer as its only parameter",
+                landing_pad
+            );
+        }
+
+        for BasicBlock { inst: pred, .. } in self.expected_cfg.pred_iter(landing_pad) {
+            if self.func.dfg[pred].landing_pad() != Some(landing_pad) {
+                return nonfatal!(
+                    errors,
+                    pred,
+                    "{} is a landing pad that can only be reached by unwinding",
+                    landing_pad
+                );
+            }
+        }
+        Ok(())
+    }
+
     // Check special-purpose type constraints that can't be expressed in the normal opcode
     // constraints.
     fn typecheck_special(
@@ -1509,8 +1718,34 @@ impl<'a> Verifier<'a> {
                     );
                 }
             }
-            ir::InstructionData::UnaryGlobalValue { global_value, .. } => {
+            ir::InstructionData::UnaryGlobalValue {
+                opcode,
+                global_value,
+            } => {
                 if let Some(isa) = self.isa {
+                    let is_tls = match self.func.global_values[global_value] {
+                        ir::GlobalValueData::Symbol { tls, .. } => tls,
+                        _ => false,
+                    };
+                    if is_tls && (opcode == Opcode::GlobalValue || opcode == Opcode::TlsValue) {
+                        if !isa.supports_tls_values() {
+                            return nonfatal!(
+                                errors,
+                                inst,
+                                "thread-local symbols are not supported on {}",
+                                isa.name()
+                            );
+                        }
+                        if isa.flags().tls_model() == TlsModel::None {
+                            return nonfatal!(
+                                errors,
+                                inst,
+                                "accessing thread-local symbol {} requires the tls_model setting",
+                                global_value
+                            );
+                        }
+                    }
+
                     let inst_type = self.func.dfg.value_type(self.func.dfg.first_result(inst));
                     let global_type = self.func.global_values[global_value].global_type(isa);
                     if inst_type != global_type {
@@ -1784,6 +2019,57 @@ impl<'a> Verifier<'a> {
                     Ok(())
                 }
             }
+            ir::InstructionData::AtomicLoad { ordering, .. } => {
+                if ordering.is_release() && ordering != ir::AtomicOrdering::SeqCst {
+                    fatal!(
+                        errors,
+                        inst,
+                        "An atomic load cannot have {} ordering",
+                        ordering
+                    )
+                } else {
+                    Ok(())
+                }
+            }
+            ir::InstructionData::AtomicStore {
+                ordering, flags, ..
+            } => {
+                if ordering.is_acquire() && ordering != ir::AtomicOrdering::SeqCst {
+                    fatal!(
+                        errors,
+                        inst,
+                        "An atomic store cannot have {} ordering",
+                        ordering
+                    )
+                } else if flags.readonly() {
+                    fatal!(
+                        errors,
+                        inst,
+                        "A store instruction cannot have the `readonly` MemFlag"
+                    )
+                } else {
+                    Ok(())
+                }
+            }
+            ir::InstructionData::AtomicRmw { flags, .. }
+            | ir::InstructionData::AtomicCas { flags, .. } => {
+                if flags.readonly() {
+                    fatal!(
+                        errors,
+                        inst,
+                        "A store instruction cannot have the `readonly` MemFlag"
+                    )
+                } else {
+                    Ok(())
+                }
+            }
+            ir::InstructionData::Fence { ordering, .. } => {
+                if ordering == ir::AtomicOrdering::Relaxed {
+                    fatal!(errors, inst, "A fence cannot have relaxed ordering")
+                } else {
+                    Ok(())
+                }
+            }
             ir::InstructionData::ExtractLane {
                 opcode: ir::instructions::Opcode::Extractlane,
                 lane,
@@ -1815,6 +2101,63 @@ impl<'a> Verifier<'a> {
         }
     }
 
+    /// Check that the region flags of a memory instruction are consistent with each other and
+    /// with the way its address is computed.
+    fn memflags_constraints(
+        &self,
+        inst: Inst,
+        errors: &mut VerifierErrors,
+    ) -> VerifierStepResult<()> {
+        let (flags, addr) = match self.func.dfg[inst] {
+            ir::InstructionData::Load { flags, arg, .. }
+            | ir::InstructionData::AtomicLoad { flags, arg, .. } => (flags, Some(arg)),
+            ir::InstructionData::Store { flags, args, .. }
+            | ir::InstructionData::AtomicStore { flags, args, .. } => (flags, Some(args[1])),
+            ir::InstructionData::AtomicRmw { flags, args, .. } => (flags, Some(args[0])),
+            ir::InstructionData::AtomicCas { flags, args, .. } => (flags, Some(args[0])),
+            ir::InstructionData::LoadComplex { flags, .. }
+            | ir::InstructionData::StoreComplex { flags, .. } => (flags, None),
+            _ => return Ok(()),
+        };
+
+        if flags.has_multiple_regions() {
+            return fatal!(
+                errors,
+                inst,
+                "A memory instruction cannot have more than one region MemFlag"
+            );
+        }
+
+        // The region of an address computed by one of these instructions is known.
+        let (region, addr) = match (flags.region(), addr) {
+            (Some(region), Some(addr)) => (region, self.func.dfg.resolve_aliases(addr)),
+            _ => return Ok(()),
+        };
+        let addr_region = match self.func.dfg.value_def(addr) {
+            ValueDef::Result(def, _) => match self.func.dfg[def] {
+                ir::InstructionData::HeapAddr { .. } => ir::MemRegion::Heap,
+                ir::InstructionData::TableAddr { .. } => ir::MemRegion::Table,
+                ir::InstructionData::StackLoad {
+                    opcode: Opcode::StackAddr,
+                    ..
+                } => ir::MemRegion::Stack,
+                _ => return Ok(()),
+            },
+            ValueDef::Param(..) => return Ok(()),
+        };
+        if region != addr_region {
+            return fatal!(
+                errors,
+                inst,
+                "The `{}` MemFlag doesn't match the {} address {}",
+                region,
+                addr_region,
+                addr
+            );
+        }
+        Ok(())
+    }
+
     fn verify_safepoint_unused(
         &self,
         inst: Inst,
@@ -1864,6 +2207,120 @@ impl<'a> Verifier<'a> {
                 )
             });
 
+        self.func
+            .signature
+            .returns
+            .iter()
+            .enumerate()
+            .filter(|(_, &ret)| ret.purpose.struct_layout().is_some())
+            .for_each(|(i, _)| {
+                report!(
+                    errors,
+                    AnyEntity::Function,
+                    "Return value at position {} cannot be a struct argument",
+                    i
+                )
+            });
+
+        self.func
+            .signature
+            .params
+            .iter()
+            .enumerate()
+            .filter_map(|(i, &param)| param.purpose.struct_layout().map(|layout| (i, layout)))
+            .filter(|(_, layout)| layout.size == 0 || !layout.align.is_power_of_two())
+            .for_each(|(i, layout)| {
+                report!(
+                    errors,
+                    AnyEntity::Function,
+                    "Parameter at position {} has an invalid struct layout {}",
+                    i,
+                    layout
+                )
+            });
+
+        if self.func.signature.is_variadic() {
+            report!(
+                errors,
+                AnyEntity::Function,
+                "Variadic functions can be called, but not defined"
+            );
+        }
+
+        if errors.has_error() {
+            Err(())
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Check that the target ISA can pass the struct arguments of the function and its callees.
+    fn verify_struct_args(&self, errors: &mut VerifierErrors) -> VerifierStepResult<()> {
+        let isa = match self.isa {
+            Some(isa) if !isa.supports_struct_args() => isa,
+            _ => return Ok(()),
+        };
+        let has_struct_args = |sig: &ir::Signature| {
+            sig.params
+                .iter()
+                .any(|param| param.purpose.struct_layout().is_some())
+        };
+
+        if has_struct_args(&self.func.signature) {
+            report!(
+                errors,
+                AnyEntity::Function,
+                "struct arguments are not supported on {}",
+                isa.name()
+            );
+        }
+        for (sig_ref, sig) in self.func.dfg.signatures.iter() {
+            if has_struct_args(sig) {
+                report!(
+                    errors,
+                    sig_ref,
+                    "struct arguments are not supported on {}",
+                    isa.name()
+                );
+            }
+        }
+
+        if errors.has_error() {
+            Err(())
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Check that the custom calling conventions used by the function and its callees are
+    /// registered with the target ISA, and that the ISA can handle them.
+    fn verify_call_convs(&self, errors: &mut VerifierErrors) -> VerifierStepResult<()> {
+        let isa = match self.isa {
+            Some(isa) => isa,
+            None => return Ok(()),
+        };
+        let check = |call_conv| match call_conv {
+            CallConv::Custom(index) => match isa.custom_call_conv(index) {
+                None => Err(format!(
+                    "Calling convention {} isn't registered with the target ISA",
+                    call_conv
+                )),
+                Some(conv) => isa
+                    .check_custom_call_conv(conv)
+                    .map_err(|err| format!("Calling convention {}: {}", call_conv, err)),
+            },
+            _ => Ok(()),
+        };
+
+        if let Err(message) = check(self.func.signature.call_conv) {
+            report!(errors, AnyEntity::Function, "{}", message);
+        }
+        for (sig_ref, sig) in self.func.dfg.signatures.iter() {
+            if let Err(message) = check(sig.call_conv) {
+                report!(errors, sig_ref, "{}", message);
+            }
+        }
+
         if errors.has_error() {
             Err(())
         } else {
@@ -1878,6 +2335,8 @@ impl<'a> Verifier<'a> {
         self.verify_jump_tables(errors)?;
         self.typecheck_entry_block_params(errors)?;
         self.typecheck_function_signature(errors)?;
+        self.verify_struct_args(errors)?;
+        self.verify_call_convs(errors)?;
 
         for ebb in self.func.layout.ebbs() {
             for inst in self.func.layout.ebb_insts(ebb) {
@@ -1887,6 +2346,7 @@ impl<'a> Verifier<'a> {
                 self.typecheck(inst, errors)?;
                 self.verify_encoding(inst, errors)?;
                 self.immediate_constraints(inst, errors)?;
+                self.memflags_constraints(inst, errors)?;
             }
 
             #[cfg(feature = "basic-blocks")]
@@ -1904,7 +2364,7 @@ mod tests {
     use super::{Verifier, VerifierError, VerifierErrors};
     use crate::entity::EntityList;
     use crate::ir::instructions::{InstructionData, Opcode};
-    use crate::ir::{types, AbiParam, Function};
+    use crate::ir::{types, AbiParam, ArgumentPurpose, Function, StructLayout};
     use crate::settings;
 
     macro_rules! assert_err_with_msg {
@@ -1989,4 +2449,24 @@ mod tests {
         let _ = verifier.typecheck_function_signature(&mut errors);
         assert_err_with_msg!(errors, "Return value at position 0 has an invalid type");
     }
+
+    #[test]
+    fn test_function_struct_return_value() {
+        let mut func = Function::new();
+        let layout = StructLayout::new(16, 8);
+        func.signature.returns.push(AbiParam::special(
+            types::I64,
+            ArgumentPurpose::StructArgument(layout),
+        ));
+
+        let mut errors = VerifierErrors::default();
+        let flags = &settings::Flags::new(settings::builder());
+        let verifier = Verifier::new(&func, flags.into());
+
+        let _ = verifier.typecheck_function_signature(&mut errors);
+        assert_err_with_msg!(
+            errors,
+            "Return value at position 0 cannot be a struct argument"
+        );
+    }
 }
@@ -102,6 +102,11 @@ pub trait FuncWriter {
             self.write_entity_definition(w, func, jt.into(), jt_data)?;
         }
 
+        if let Some(personality) = func.personality {
+            any = true;
+            writeln!(w, "    personality = {}", personality)?;
+        }
+
         Ok(any)
     }
 
@@ -600,6 +605,34 @@ pub fn write_operands(
                 DisplayValues(&args[1..])
             )
         }
+        Invoke {
+            func_ref,
+            destination,
+            ref args,
+            ..
+        } => write!(
+            w,
+            " {}({}), {}",
+            func_ref,
+            DisplayValues(args.as_slice(pool)),
+            destination
+        ),
+        InvokeIndirect {
+            sig_ref,
+            destination,
+            ref args,
+            ..
+        } => {
+            let args = args.as_slice(pool);
+            write!(
+                w,
+                " {}, {}({}), {}",
+                sig_ref,
+                args[0],
+                DisplayValues(&args[1..]),
+                destination
+            )
+        }
         FuncAddr { func_ref, .. } => write!(w, " {}", func_ref),
         StackLoad {
             stack_slot, offset, ..
@@ -652,6 +685,35 @@ pub fn write_operands(
                 offset
             )
         }
+        AtomicLoad {
+            ordering,
+            flags,
+            arg,
+            ..
+        } => write!(w, " {}{} {}", ordering, flags, arg),
+        AtomicStore {
+            ordering,
+            flags,
+            args,
+            ..
+        } => write!(w, " {}{} {}, {}", ordering, flags, args[0], args[1]),
+        AtomicRmw {
+            op,
+            ordering,
+            flags,
+            args,
+            ..
+        } => write!(w, " {} {}{} {}, {}", op, ordering, flags, args[0], args[1]),
+        AtomicCas {
+            ordering,
+            flags,
+            args,
+            ..
+        } => write!(
+            w,
+            " {}{} {}, {}, {}",
+            ordering, flags, args[0], args[1], args[2]
+        ),
         RegMove { arg, src, dst, .. } => {
             if let Some(isa) = isa {
                 let regs = isa.register_info();
@@ -704,6 +766,7 @@ pub fn write_operands(
             }
         }
         Trap { code, .. } => write!(w, " {}", code),
+        Fence { ordering, .. } => write!(w, " {}", ordering),
         CondTrap { arg, code, .. } => write!(w, " {}, {}", arg, code),
         IntCondTrap {
             cond, arg, code, ..
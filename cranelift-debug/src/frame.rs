@@ -0,0 +1,253 @@
+//! Generation of `.debug_frame` from the `.eh_frame` entries produced by the code generator.
+//!
+//! The two formats differ only in their headers: `.debug_frame` CIEs have an all-ones id and no
+//! augmentation, and FDEs refer to their CIE by section offset rather than relative position.
+//! The call frame instructions are copied verbatim.
+
+use crate::writer::Writer;
+use crate::{FunctionDebugInfo, SectionId};
+
+/// The CIE id that distinguishes CIEs from FDEs in `.debug_frame`.
+const DW_CIE_ID: u32 = 0xffff_ffff;
+
+/// The parts of an `.eh_frame` CIE that are carried over to `.debug_frame`.
+#[derive(PartialEq, Eq)]
+struct Cie {
+    code_align: u64,
+    data_align: i64,
+    return_address_register: u8,
+    instructions: Vec<u8>,
+}
+
+/// The `.eh_frame` CIE and FDE describing a function, as produced by
+/// `TargetIsa::emit_unwind_info`.
+pub struct EhFrame {
+    pub data: Vec<u8>,
+    pub entry: usize,
+}
+
+/// Write the `.debug_frame` entries for `functions` to `w`. Functions sharing a CIE share it in
+/// the output too.
+pub fn write_debug_frame<S: Copy>(
+    w: &mut Writer<S>,
+    functions: &[FunctionDebugInfo<S>],
+    big_endian: bool,
+) {
+    let mut cies: Vec<(Cie, usize)> = Vec::new();
+    for function in functions {
+        let frame = match function.frame {
+            Some(ref frame) => frame,
+            None => continue,
+        };
+        let (cie, fde_instructions) = parse_eh_frame(frame, w.address_size(), big_endian);
+        let cie_offset = match cies.iter().find(|(c, _)| *c == cie) {
+            Some(&(_, offset)) => offset,
+            None => {
+                let offset = w.len();
+                write_cie(w, &cie);
+                cies.push((cie, offset));
+                offset
+            }
+        };
+
+        let start = w.len();
+        w.u32(0);
+        w.section_offset(SectionId::DebugFrame, cie_offset);
+        w.address(function.symbol, 0);
+        let address_size = w.address_size();
+        w.udata(u64::from(function.size), address_size);
+        w.bytes(fde_instructions);
+        finish_entry(w, start);
+    }
+}
+
+fn write_cie<S: Copy>(w: &mut Writer<S>, cie: &Cie) {
+    let start = w.len();
+    w.u32(0);
+    w.u32(DW_CIE_ID);
+    w.u8(1); // version
+    w.u8(0); // augmentation
+    w.uleb128(cie.code_align);
+    w.sleb128(cie.data_align);
+    w.u8(cie.return_address_register);
+    w.bytes(&cie.instructions);
+    finish_entry(w, start);
+}
+
+/// Pad the entry starting at `start` with `DW_CFA_nop`s and fill in its length.
+fn finish_entry<S: Copy>(w: &mut Writer<S>, start: usize) {
+    let address_size = usize::from(w.address_size());
+    w.align(address_size, 0);
+    let length = w.len() - start - 4;
+    w.patch_u32(start, length as u32);
+}
+
+/// Split `frame` into its CIE and the call frame instructions of its FDE.
+fn parse_eh_frame(frame: &EhFrame, address_size: u8, big_endian: bool) -> (Cie, &[u8]) {
+    let mut r = Reader {
+        data: &frame.data,
+        position: 0,
+        big_endian,
+    };
+
+    let cie_end = r.u32() as usize + 4;
+    assert_eq!(r.u32(), 0, "expected a CIE");
+    assert_eq!(r.u8(), 1, "unsupported CIE version");
+    let augmentation = r.string();
+    let code_align = r.uleb128();
+    let data_align = r.sleb128();
+    let return_address_register = r.u8();
+    if augmentation.starts_with(b"z") {
+        r.position += r.uleb128() as usize;
+    }
+    let cie = Cie {
+        code_align,
+        data_align,
+        return_address_register,
+        instructions: frame.data[r.position..cie_end].to_vec(),
+    };
+
+    r.position = frame.entry;
+    let fde_end = frame.entry + r.u32() as usize + 4;
+    r.position += 4 + 2 * usize::from(address_size);
+    if augmentation.starts_with(b"z") {
+        r.position += r.uleb128() as usize;
+    }
+    (cie, &frame.data[r.position..fde_end])
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    position: usize,
+    big_endian: bool,
+}
+
+impl<'a> Reader<'a> {
+    fn u8(&mut self) -> u8 {
+        let byte = self.data[self.position];
+        self.position += 1;
+        byte
+    }
+
+    fn u32(&mut self) -> u32 {
+        let mut bytes = [0; 4];
+        bytes.copy_from_slice(&self.data[self.position..self.position + 4]);
+        self.position += 4;
+        if self.big_endian {
+            u32::from_be_bytes(bytes)
+        } else {
+            u32::from_le_bytes(bytes)
+        }
+    }
+
+    fn string(&mut self) -> &'a [u8] {
+        let start = self.position;
+        while self.u8() != 0 {}
+        &self.data[start..self.position - 1]
+    }
+
+    fn uleb128(&mut self) -> u64 {
+        let mut result = 0;
+        let mut shift = 0;
+        loop {
+            let byte = self.u8();
+            result |= u64::from(byte & 0x7f) << shift;
+            shift += 7;
+            if byte & 0x80 == 0 {
+                return result;
+            }
+        }
+    }
+
+    fn sleb128(&mut self) -> i64 {
+        let mut result = 0;
+        let mut shift = 0;
+        loop {
+            let byte = self.u8();
+            result |= i64::from(byte & 0x7f) << shift;
+            shift += 7;
+            if byte & 0x80 == 0 {
+                if shift < 64 && byte & 0x40 != 0 {
+                    result |= -1 << shift;
+                }
+                return result;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::DebugRelocTarget;
+
+    fn function(symbol: u32, size: u32, frame: EhFrame) -> FunctionDebugInfo<u32> {
+        FunctionDebugInfo {
+            symbol,
+            name: String::new(),
+            size,
+            lines: Vec::new(),
+            variables: Vec::new(),
+            frame: Some(frame),
+        }
+    }
+
+    #[test]
+    fn transcode() {
+        // The CIE and FDE of `push rbp; mov rbp, rsp; pop rbp; ret`.
+        let eh_frame = vec![
+            // CIE: "zR", code_align 1, data_align -8, ra r16, absptr addresses.
+            0x14, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01, 0x7a, 0x52, 0x00, 0x01, 0x78,
+            0x10, 0x01, 0x00, 0x0c, 0x07, 0x08, 0x90, 0x01, 0x00, 0x00, //
+            // FDE at 24.
+            0x24, 0x00, 0x00, 0x00, 0x1c, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+            0x00, 0x00, 0x06, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x41, 0x0e, 0x10,
+            0x86, 0x02, 0x43, 0x0d, 0x06, 0x41, 0x0c, 0x07, 0x08, 0x00, 0x00, 0x00,
+        ];
+        let functions = [
+            function(
+                1,
+                6,
+                EhFrame {
+                    data: eh_frame.clone(),
+                    entry: 24,
+                },
+            ),
+            function(
+                2,
+                6,
+                EhFrame {
+                    data: eh_frame,
+                    entry: 24,
+                },
+            ),
+        ];
+
+        let mut w = Writer::new(8, false);
+        write_debug_frame(&mut w, &functions, false);
+        let fde = [
+            0x24, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+            0x00, 0x00, 0x06, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x41, 0x0e, 0x10, 0x86,
+            0x02, 0x43, 0x0d, 0x06, 0x41, 0x0c, 0x07, 0x08, 0x00, 0x00, 0x00, 0x00,
+        ];
+        let mut expected = vec![
+            // CIE, shared by both functions.
+            0x14, 0x00, 0x00, 0x00, 0xff, 0xff, 0xff, 0xff, 0x01, 0x00, 0x01, 0x78, 0x10, 0x0c,
+            0x07, 0x08, 0x90, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        ];
+        expected.extend_from_slice(&fde);
+        expected.extend_from_slice(&fde);
+        assert_eq!(w.data, expected);
+
+        let targets: Vec<_> = w.relocs.iter().map(|r| (r.offset, r.target)).collect();
+        assert_eq!(
+            targets,
+            [
+                (28, DebugRelocTarget::Section(SectionId::DebugFrame)),
+                (32, DebugRelocTarget::Symbol(1)),
+                (68, DebugRelocTarget::Section(SectionId::DebugFrame)),
+                (72, DebugRelocTarget::Symbol(2)),
+            ]
+        );
+    }
+}
@@ -0,0 +1,263 @@
+//! Generation of the `.debug_info` compilation unit, along with the `.debug_abbrev`,
+//! `.debug_loc` and `.debug_ranges` data it refers to.
+
+use crate::regs::map_reg;
+use crate::writer::Writer;
+use crate::{FunctionDebugInfo, SectionId};
+use cranelift_codegen::ir::{Function, Type, ValueLoc};
+use cranelift_codegen::isa::TargetIsa;
+
+// Tags.
+const DW_TAG_COMPILE_UNIT: u8 = 0x11;
+const DW_TAG_BASE_TYPE: u8 = 0x24;
+const DW_TAG_SUBPROGRAM: u8 = 0x2e;
+const DW_TAG_VARIABLE: u8 = 0x34;
+
+// Attributes.
+const DW_AT_LOCATION: u8 = 0x02;
+const DW_AT_NAME: u8 = 0x03;
+const DW_AT_BYTE_SIZE: u8 = 0x0b;
+const DW_AT_STMT_LIST: u8 = 0x10;
+const DW_AT_LOW_PC: u8 = 0x11;
+const DW_AT_HIGH_PC: u8 = 0x12;
+const DW_AT_COMP_DIR: u8 = 0x1b;
+const DW_AT_PRODUCER: u8 = 0x25;
+const DW_AT_ENCODING: u8 = 0x3e;
+const DW_AT_FRAME_BASE: u8 = 0x40;
+const DW_AT_TYPE: u8 = 0x49;
+const DW_AT_RANGES: u8 = 0x55;
+
+// Forms.
+const DW_FORM_ADDR: u8 = 0x01;
+const DW_FORM_DATA4: u8 = 0x06;
+const DW_FORM_STRING: u8 = 0x08;
+const DW_FORM_DATA1: u8 = 0x0b;
+const DW_FORM_REF4: u8 = 0x13;
+const DW_FORM_SEC_OFFSET: u8 = 0x17;
+const DW_FORM_EXPRLOC: u8 = 0x18;
+
+// Base type encodings.
+const DW_ATE_BOOLEAN: u8 = 0x02;
+const DW_ATE_FLOAT: u8 = 0x04;
+const DW_ATE_SIGNED: u8 = 0x05;
+const DW_ATE_UNSIGNED: u8 = 0x08;
+
+// Location expression operations.
+const DW_OP_REG0: u8 = 0x50;
+const DW_OP_REGX: u8 = 0x90;
+const DW_OP_FBREG: u8 = 0x91;
+const DW_OP_CALL_FRAME_CFA: u8 = 0x9c;
+
+// The abbreviation codes of the entries we emit, in the order they appear in `.debug_abbrev`.
+const ABBREV_COMPILE_UNIT: u8 = 1;
+const ABBREV_BASE_TYPE: u8 = 2;
+const ABBREV_SUBPROGRAM: u8 = 3;
+const ABBREV_CHILDLESS_SUBPROGRAM: u8 = 4;
+const ABBREV_VARIABLE: u8 = 5;
+
+/// An abbreviation: tag, whether the entry has children, and (attribute, form) pairs.
+type Abbrev = (u8, bool, &'static [(u8, u8)]);
+
+/// The attributes of a subprogram, with or without children.
+const SUBPROGRAM_ATTRS: &[(u8, u8)] = &[
+    (DW_AT_NAME, DW_FORM_STRING),
+    (DW_AT_LOW_PC, DW_FORM_ADDR),
+    (DW_AT_HIGH_PC, DW_FORM_DATA4),
+    (DW_AT_FRAME_BASE, DW_FORM_EXPRLOC),
+];
+
+/// The abbreviation table.
+const ABBREVS: [Abbrev; 5] = [
+    (
+        DW_TAG_COMPILE_UNIT,
+        true,
+        &[
+            (DW_AT_PRODUCER, DW_FORM_STRING),
+            (DW_AT_NAME, DW_FORM_STRING),
+            (DW_AT_COMP_DIR, DW_FORM_STRING),
+            (DW_AT_STMT_LIST, DW_FORM_SEC_OFFSET),
+            (DW_AT_LOW_PC, DW_FORM_ADDR),
+            (DW_AT_RANGES, DW_FORM_SEC_OFFSET),
+        ],
+    ),
+    (
+        DW_TAG_BASE_TYPE,
+        false,
+        &[
+            (DW_AT_NAME, DW_FORM_STRING),
+            (DW_AT_ENCODING, DW_FORM_DATA1),
+            (DW_AT_BYTE_SIZE, DW_FORM_DATA1),
+        ],
+    ),
+    (DW_TAG_SUBPROGRAM, true, SUBPROGRAM_ATTRS),
+    (DW_TAG_SUBPROGRAM, false, SUBPROGRAM_ATTRS),
+    (
+        DW_TAG_VARIABLE,
+        false,
+        &[
+            (DW_AT_NAME, DW_FORM_STRING),
+            (DW_AT_TYPE, DW_FORM_REF4),
+            (DW_AT_LOCATION, DW_FORM_SEC_OFFSET),
+        ],
+    ),
+];
+
+/// A labelled value and where it lives over the course of its function.
+pub struct Variable {
+    pub name: String,
+    pub ty: Type,
+    /// The function offset ranges and the location expressions that apply to them.
+    pub ranges: Vec<(u32, u32, Vec<u8>)>,
+}
+
+/// The identification of the compilation unit.
+pub struct UnitInfo<'a> {
+    pub producer: &'a str,
+    pub name: &'a str,
+    pub comp_dir: &'a str,
+}
+
+/// Get the location expression of a value of type `ty` stored at `loc` in `func`, or `None` if
+/// it can't be described.
+pub fn location_expression(
+    isa: &dyn TargetIsa,
+    func: &Function,
+    loc: ValueLoc,
+    ty: Type,
+) -> Option<Vec<u8>> {
+    let mut w = Writer::<()>::new(0, false);
+    match loc {
+        ValueLoc::Reg(reg) => {
+            let reg = map_reg(isa, reg, ty)?;
+            if reg < 32 {
+                w.u8(DW_OP_REG0 + reg as u8);
+            } else {
+                w.u8(DW_OP_REGX);
+                w.uleb128(u64::from(reg));
+            }
+        }
+        ValueLoc::Stack(ss) => {
+            // Stack slot offsets are relative to the stack pointer on entry to the function,
+            // which is the CFA.
+            let offset = func.stack_slots[ss].offset?;
+            w.u8(DW_OP_FBREG);
+            w.sleb128(i64::from(offset));
+        }
+        ValueLoc::Unassigned => return None,
+    }
+    Some(w.data)
+}
+
+pub fn write_abbrev<S: Copy>(w: &mut Writer<S>) {
+    for (code, &(tag, children, attrs)) in ABBREVS.iter().enumerate() {
+        w.uleb128(code as u64 + 1);
+        w.uleb128(u64::from(tag));
+        w.u8(children as u8);
+        for &(name, form) in attrs {
+            w.uleb128(u64::from(name));
+            w.uleb128(u64::from(form));
+        }
+        w.u8(0);
+        w.u8(0);
+    }
+    w.u8(0);
+}
+
+/// Write the compilation unit describing `functions` to `info`, and their variables' location
+/// lists to `loc`.
+pub fn write_info<S: Copy>(
+    info: &mut Writer<S>,
+    loc: &mut Writer<S>,
+    unit: &UnitInfo,
+    functions: &[FunctionDebugInfo<S>],
+) {
+    let unit_start = info.len();
+    info.u32(0);
+    info.u16(4);
+    info.section_offset(SectionId::DebugAbbrev, 0);
+    info.u8(info.address_size());
+
+    info.uleb128(u64::from(ABBREV_COMPILE_UNIT));
+    info.string(unit.producer);
+    info.string(unit.name);
+    info.string(unit.comp_dir);
+    info.section_offset(SectionId::DebugLine, 0);
+    // The base address of location lists and ranges, which are all relocated absolutely.
+    let address_size = info.address_size();
+    info.udata(0, address_size);
+    info.section_offset(SectionId::DebugRanges, 0);
+
+    let mut types: Vec<(Type, usize)> = Vec::new();
+    for variable in functions.iter().flat_map(|f| &f.variables) {
+        if types.iter().any(|&(ty, _)| ty == variable.ty) {
+            continue;
+        }
+        types.push((variable.ty, info.len() - unit_start));
+        let ty = variable.ty;
+        let encoding = if ty.is_int() {
+            DW_ATE_SIGNED
+        } else if ty.is_float() {
+            DW_ATE_FLOAT
+        } else if ty.is_bool() {
+            DW_ATE_BOOLEAN
+        } else {
+            DW_ATE_UNSIGNED
+        };
+        info.uleb128(u64::from(ABBREV_BASE_TYPE));
+        info.string(&ty.to_string());
+        info.u8(encoding);
+        info.u8(ty.bytes() as u8);
+    }
+
+    for function in functions {
+        // A subprogram with children needs a null entry after them, so functions without
+        // variables use an abbreviation without children.
+        let has_children = !function.variables.is_empty();
+        info.uleb128(u64::from(if has_children {
+            ABBREV_SUBPROGRAM
+        } else {
+            ABBREV_CHILDLESS_SUBPROGRAM
+        }));
+        info.string(&function.name);
+        info.address(function.symbol, 0);
+        info.u32(function.size);
+        info.uleb128(1);
+        info.u8(DW_OP_CALL_FRAME_CFA);
+
+        for variable in &function.variables {
+            let type_offset = types.iter().find(|&&(ty, _)| ty == variable.ty).unwrap().1;
+            info.uleb128(u64::from(ABBREV_VARIABLE));
+            info.string(&variable.name);
+            info.u32(type_offset as u32);
+            info.section_offset(SectionId::DebugLoc, loc.len());
+
+            for &(start, end, ref expression) in &variable.ranges {
+                loc.address(function.symbol, i64::from(start));
+                loc.address(function.symbol, i64::from(end));
+                loc.u16(expression.len() as u16);
+                loc.bytes(expression);
+            }
+            let address_size = loc.address_size();
+            loc.udata(0, address_size);
+            loc.udata(0, address_size);
+        }
+        if has_children {
+            info.u8(0);
+        }
+    }
+    info.u8(0);
+
+    let unit_length = info.len() - unit_start - 4;
+    info.patch_u32(unit_start, unit_length as u32);
+}
+
+/// Write the address ranges covered by `functions` to `w`.
+pub fn write_ranges<S: Copy>(w: &mut Writer<S>, functions: &[FunctionDebugInfo<S>]) {
+    for function in functions {
+        w.address(function.symbol, 0);
+        w.address(function.symbol, i64::from(function.size));
+    }
+    let address_size = w.address_size();
+    w.udata(0, address_size);
+    w.udata(0, address_size);
+}
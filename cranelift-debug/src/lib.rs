@@ -0,0 +1,436 @@
+//! DWARF debug information for code generated by Cranelift.
+//!
+//! A `DebugContext` collects the source locations, value label ranges and unwind information of
+//! compiled functions, and then produces a single compilation unit describing all of them in the
+//! `.debug_info`, `.debug_line`, `.debug_loc`, `.debug_ranges` and `.debug_frame` sections.
+//!
+//! Instruction source locations are used as line numbers in the compilation unit's source file,
+//! and each value label becomes a variable in its function.
+//!
+//! The sections are produced as bytes along with the relocations needed to link them. The
+//! relocations refer either to the symbols of the functions, as identified by the `S` type
+//! parameter, or to the start of one of the other debug sections.
+
+#![deny(
+    missing_docs,
+    trivial_numeric_casts,
+    unused_extern_crates,
+    unstable_features
+)]
+#![warn(unused_import_braces)]
+#![cfg_attr(feature = "clippy", plugin(clippy(conf_file = "../../clippy.toml")))]
+#![cfg_attr(feature = "cargo-clippy", allow(clippy::new_without_default))]
+#![cfg_attr(
+    feature = "cargo-clippy",
+    warn(
+        clippy::float_arithmetic,
+        clippy::mut_mut,
+        clippy::nonminimal_bool,
+        clippy::option_map_unwrap_or,
+        clippy::option_map_unwrap_or_else,
+        clippy::print_stdout,
+        clippy::unicode_not_nfc,
+        clippy::use_self
+    )
+)]
+
+mod frame;
+mod info;
+mod line;
+mod regs;
+mod writer;
+
+use crate::frame::{write_debug_frame, EhFrame};
+use crate::info::{
+    location_expression, write_abbrev, write_info, write_ranges, UnitInfo, Variable,
+};
+use crate::line::write_line_program;
+use crate::writer::Writer;
+use cranelift_codegen::binemit::{FrameUnwindKind, FrameUnwindOffset, FrameUnwindSink, Reloc};
+use cranelift_codegen::entity::EntityRef;
+use cranelift_codegen::ir::{ExternalName, ValueLabel, ValueLabelAssignments};
+use cranelift_codegen::isa::TargetIsa;
+use cranelift_codegen::{CodegenResult, Context};
+use std::collections::HashMap;
+
+/// The debug sections produced by a `DebugContext`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum SectionId {
+    /// The `.debug_abbrev` section.
+    DebugAbbrev,
+    /// The `.debug_info` section.
+    DebugInfo,
+    /// The `.debug_line` section.
+    DebugLine,
+    /// The `.debug_loc` section.
+    DebugLoc,
+    /// The `.debug_ranges` section.
+    DebugRanges,
+    /// The `.debug_frame` section.
+    DebugFrame,
+}
+
+impl SectionId {
+    /// Get the ELF name of the section.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::DebugAbbrev => ".debug_abbrev",
+            Self::DebugInfo => ".debug_info",
+            Self::DebugLine => ".debug_line",
+            Self::DebugLoc => ".debug_loc",
+            Self::DebugRanges => ".debug_ranges",
+            Self::DebugFrame => ".debug_frame",
+        }
+    }
+}
+
+/// What a `DebugReloc` refers to.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum DebugRelocTarget<S> {
+    /// The start of a function's code, as identified by its symbol.
+    Symbol(S),
+    /// The start of a debug section.
+    Section(SectionId),
+}
+
+/// A relocation in a debug section.
+///
+/// The relocated value is the address of the target plus the addend. The addend is also stored
+/// in the section data, for formats that don't store it in the relocation.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct DebugReloc<S> {
+    /// The offset of the relocated value in the section.
+    pub offset: u32,
+    /// The size of the relocated value, in bytes.
+    pub size: u8,
+    /// The target of the relocation.
+    pub target: DebugRelocTarget<S>,
+    /// The addend to add to the target's address.
+    pub addend: i64,
+}
+
+/// The contents of a debug section.
+pub struct DebugSection<S> {
+    /// Which section this is.
+    pub id: SectionId,
+    /// The section's bytes.
+    pub data: Vec<u8>,
+    /// The relocations to apply to `data`.
+    pub relocs: Vec<DebugReloc<S>>,
+}
+
+/// The debug information collected for a single function.
+struct FunctionDebugInfo<S> {
+    symbol: S,
+    name: String,
+    size: u32,
+    /// The function offsets at which the line number changes, and the new line numbers.
+    lines: Vec<(u32, u32)>,
+    variables: Vec<Variable>,
+    frame: Option<EhFrame>,
+}
+
+/// Collects the debug information of the functions in a compilation unit.
+pub struct DebugContext<S> {
+    producer: String,
+    name: String,
+    comp_dir: String,
+    address_size: u8,
+    big_endian: bool,
+    functions: Vec<FunctionDebugInfo<S>>,
+}
+
+impl<S: Copy> DebugContext<S> {
+    /// Create a new `DebugContext` for code generated by `isa` from the source file `name`,
+    /// relative to `comp_dir`. The `producer` is the name of the compiler recorded in the
+    /// compilation unit.
+    pub fn new(isa: &dyn TargetIsa, name: &str, comp_dir: &str, producer: &str) -> Self {
+        Self {
+            producer: producer.to_string(),
+            name: name.to_string(),
+            comp_dir: comp_dir.to_string(),
+            address_size: isa.pointer_bytes(),
+            big_endian: isa.triple().endianness() == Ok(target_lexicon::Endianness::Big),
+            functions: Vec::new(),
+        }
+    }
+
+    /// Record the debug information of the function compiled by `ctx` into `code_size` bytes of
+    /// machine code, starting at `symbol`.
+    pub fn define_function(
+        &mut self,
+        symbol: S,
+        name: &str,
+        ctx: &Context,
+        isa: &dyn TargetIsa,
+        code_size: u32,
+    ) -> CodegenResult<()> {
+        let func = &ctx.func;
+        let encinfo = isa.encoding_info();
+
+        let mut lines: Vec<(u32, u32)> = Vec::new();
+        for ebb in func.layout.ebbs() {
+            for (offset, inst, _) in func.inst_offsets(ebb, &encinfo) {
+                let srcloc = func.srclocs[inst];
+                if srcloc.is_default() || lines.last().map(|&(_, l)| l) == Some(srcloc.bits()) {
+                    continue;
+                }
+                lines.push((offset, srcloc.bits()));
+            }
+        }
+        // Attribute the prologue to the first line, rather than leaving it without one.
+        if let Some(first) = lines.first_mut() {
+            first.0 = 0;
+        }
+
+        let mut types = HashMap::new();
+        if let Some(ref values_labels) = func.dfg.values_labels {
+            for (&value, assignments) in values_labels {
+                if let ValueLabelAssignments::Starts(ref starts) = *assignments {
+                    for start in starts {
+                        types.insert(start.label, func.dfg.value_type(value));
+                    }
+                }
+            }
+        }
+        let ranges = ctx.build_value_labels_ranges(isa)?;
+        let mut labels: Vec<ValueLabel> = ranges.keys().cloned().collect();
+        labels.sort_by_key(|label| label.index());
+        let mut variables = Vec::new();
+        for label in labels {
+            let ty = match types.get(&label) {
+                Some(&ty) => ty,
+                None => continue,
+            };
+            let ranges = ranges[&label]
+                .iter()
+                .filter_map(|range| {
+                    let expression = location_expression(isa, func, range.loc, ty)?;
+                    Some((range.start, range.end, expression))
+                })
+                .collect();
+            variables.push(Variable {
+                name: label.to_string(),
+                ty,
+                ranges,
+            });
+        }
+
+        let mut sink = DebugUnwindSink {
+            data: Vec::new(),
+            entry: 0,
+        };
+        ctx.emit_unwind_info(isa, FrameUnwindKind::Libunwind, &mut sink);
+        let frame = if sink.data.is_empty() {
+            None
+        } else {
+            Some(EhFrame {
+                data: sink.data,
+                entry: sink.entry,
+            })
+        };
+
+        self.functions.push(FunctionDebugInfo {
+            symbol,
+            name: name.to_string(),
+            size: code_size,
+            lines,
+            variables,
+            frame,
+        });
+        Ok(())
+    }
+
+    /// Produce the debug sections describing all the functions defined so far. There are no
+    /// sections if there are no functions.
+    pub fn emit(&self) -> Vec<DebugSection<S>> {
+        if self.functions.is_empty() {
+            return Vec::new();
+        }
+
+        let unit = UnitInfo {
+            producer: &self.producer,
+            name: &self.name,
+            comp_dir: &self.comp_dir,
+        };
+        let mut abbrev = self.writer();
+        let mut info = self.writer();
+        let mut line = self.writer();
+        let mut loc = self.writer();
+        let mut ranges = self.writer();
+        let mut frame = self.writer();
+        write_abbrev(&mut abbrev);
+        write_info(&mut info, &mut loc, &unit, &self.functions);
+        write_line_program(&mut line, &self.name, &self.functions);
+        write_ranges(&mut ranges, &self.functions);
+        write_debug_frame(&mut frame, &self.functions, self.big_endian);
+
+        vec![
+            (SectionId::DebugAbbrev, abbrev),
+            (SectionId::DebugInfo, info),
+            (SectionId::DebugLine, line),
+            (SectionId::DebugLoc, loc),
+            (SectionId::DebugRanges, ranges),
+            (SectionId::DebugFrame, frame),
+        ]
+        .into_iter()
+        .filter(|(_, w)| w.len() != 0)
+        .map(|(id, w)| DebugSection {
+            id,
+            data: w.data,
+            relocs: w.relocs,
+        })
+        .collect()
+    }
+
+    fn writer(&self) -> Writer<S> {
+        Writer::new(self.address_size, self.big_endian)
+    }
+}
+
+/// Collects the `.eh_frame` entries of a function. Their only relocation, the FDE's initial
+/// location, is recreated by `write_debug_frame`.
+struct DebugUnwindSink {
+    data: Vec<u8>,
+    entry: FrameUnwindOffset,
+}
+
+impl FrameUnwindSink for DebugUnwindSink {
+    fn len(&self) -> FrameUnwindOffset {
+        self.data.len()
+    }
+
+    fn bytes(&mut self, b: &[u8]) {
+        self.data.extend_from_slice(b);
+    }
+
+    fn reloc(&mut self, _reloc: Reloc, _offset: FrameUnwindOffset) {}
+
+    fn reloc_external(&mut self, _reloc: Reloc, _offset: FrameUnwindOffset, _name: &ExternalName) {}
+
+    fn lsda(&mut self, _lsda: &[u8], _reloc: Reloc, _offset: FrameUnwindOffset) {}
+
+    fn set_entry_offset(&mut self, offset: FrameUnwindOffset) {
+        self.entry = offset;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cranelift_codegen::binemit::{NullRelocSink, NullStackmapSink, NullTrapSink};
+    use cranelift_codegen::ir::{SourceLoc, ValueLabelStart};
+    use cranelift_codegen::{isa, settings};
+    use cranelift_reader::parse_functions;
+
+    #[test]
+    fn sections() {
+        let isa = match isa::lookup_by_name("x86_64") {
+            Ok(builder) => builder.finish(settings::Flags::new(settings::builder())),
+            Err(_) => return,
+        };
+        let mut func = parse_functions(
+            "function %add(i64, i64) -> i64 {
+             ebb0(v0: i64, v1: i64):
+             @0002 v2 = iadd v0, v1
+             @0003 v3 = imul v2, v0
+             @0004 return v3
+             }",
+        )
+        .unwrap()
+        .remove(0);
+        func.dfg.collect_debug_info();
+        let v0 = func.dfg.ebb_params(func.layout.entry_block().unwrap())[0];
+        func.dfg.values_labels.as_mut().unwrap().insert(
+            v0,
+            ValueLabelAssignments::Starts(vec![ValueLabelStart {
+                from: SourceLoc::new(2),
+                label: ValueLabel::new(0),
+            }]),
+        );
+
+        let mut ctx = Context::for_function(func);
+        let mut code = Vec::new();
+        let info = ctx
+            .compile_and_emit(
+                &*isa,
+                &mut code,
+                &mut NullRelocSink {},
+                &mut NullTrapSink {},
+                &mut NullStackmapSink {},
+            )
+            .unwrap();
+
+        let mut debug = DebugContext::new(&*isa, "add.clif", "/src", "test");
+        debug
+            .define_function(7u32, "add", &ctx, &*isa, info.code_size)
+            .unwrap();
+        let sections = debug.emit();
+
+        let ids: Vec<_> = sections.iter().map(|s| s.id).collect();
+        assert_eq!(
+            ids,
+            [
+                SectionId::DebugAbbrev,
+                SectionId::DebugInfo,
+                SectionId::DebugLine,
+                SectionId::DebugLoc,
+                SectionId::DebugRanges,
+                SectionId::DebugFrame,
+            ]
+        );
+        for section in &sections {
+            for reloc in &section.relocs {
+                if let DebugRelocTarget::Symbol(symbol) = reloc.target {
+                    assert_eq!(symbol, 7);
+                    assert!(reloc.addend >= 0 && reloc.addend <= i64::from(info.code_size));
+                }
+            }
+        }
+
+        // `v0` is in `rdi`, which is `DW_OP_reg5`, from its first use to its last.
+        let loc = &sections[3];
+        assert_eq!(loc.data.len(), 2 * 8 + 2 + 1 + 2 * 8);
+        assert_eq!(loc.data[16..19], [1, 0, 0x55]);
+    }
+
+    #[test]
+    fn function_without_variables() {
+        let isa = match isa::lookup_by_name("x86_64") {
+            Ok(builder) => builder.finish(settings::Flags::new(settings::builder())),
+            Err(_) => return,
+        };
+        let func = parse_functions(
+            "function %nop() {
+             ebb0:
+             @0001 return
+             }",
+        )
+        .unwrap()
+        .remove(0);
+
+        let mut ctx = Context::for_function(func);
+        let mut code = Vec::new();
+        let info = ctx
+            .compile_and_emit(
+                &*isa,
+                &mut code,
+                &mut NullRelocSink {},
+                &mut NullTrapSink {},
+                &mut NullStackmapSink {},
+            )
+            .unwrap();
+
+        let mut debug = DebugContext::new(&*isa, "nop.clif", "/src", "test");
+        debug
+            .define_function(7u32, "nop", &ctx, &*isa, info.code_size)
+            .unwrap();
+        let sections = debug.emit();
+
+        // The subprogram has no children, so its frame base, `DW_OP_call_frame_cfa`, is only
+        // followed by the null entry ending the children of the compilation unit.
+        let info = &sections[1];
+        assert_eq!(info.id, SectionId::DebugInfo);
+        assert_eq!(info.data[info.data.len() - 2..], [0x9c, 0]);
+    }
+}
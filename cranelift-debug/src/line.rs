@@ -0,0 +1,133 @@
+//! Generation of the `.debug_line` line number program.
+
+use crate::writer::Writer;
+use crate::FunctionDebugInfo;
+
+// Standard opcodes.
+const DW_LNS_COPY: u8 = 0x01;
+const DW_LNS_ADVANCE_PC: u8 = 0x02;
+const DW_LNS_ADVANCE_LINE: u8 = 0x03;
+
+// Extended opcodes.
+const DW_LNE_END_SEQUENCE: u8 = 0x01;
+const DW_LNE_SET_ADDRESS: u8 = 0x02;
+
+// Parameters of the special opcodes, the same as used by GCC and LLVM.
+const LINE_BASE: i64 = -5;
+const LINE_RANGE: u8 = 14;
+const OPCODE_BASE: u8 = 13;
+
+/// The number of operands of each standard opcode, starting with `DW_LNS_copy`.
+const STANDARD_OPCODE_LENGTHS: [u8; OPCODE_BASE as usize - 1] =
+    [0, 1, 1, 1, 1, 0, 0, 0, 1, 0, 0, 1];
+
+/// Write the line number program for `functions` to `w`, as a single sequence per function in
+/// the compilation unit's only file, `file`.
+pub fn write_line_program<S: Copy>(
+    w: &mut Writer<S>,
+    file: &str,
+    functions: &[FunctionDebugInfo<S>],
+) {
+    let unit_start = w.len();
+    w.u32(0);
+    w.u16(4);
+    let header_start = w.len();
+    w.u32(0);
+    w.u8(1); // minimum_instruction_length
+    w.u8(1); // maximum_operations_per_instruction
+    w.u8(1); // default_is_stmt
+    w.u8(LINE_BASE as u8);
+    w.u8(LINE_RANGE);
+    w.u8(OPCODE_BASE);
+    w.bytes(&STANDARD_OPCODE_LENGTHS);
+    // No include directories; the file is relative to the compilation directory.
+    w.u8(0);
+    w.string(file);
+    w.uleb128(0); // directory index
+    w.uleb128(0); // modification time
+    w.uleb128(0); // length
+    w.u8(0);
+    let header_length = w.len() - header_start - 4;
+    w.patch_u32(header_start, header_length as u32);
+
+    for function in functions {
+        write_sequence(w, function);
+    }
+
+    let unit_length = w.len() - unit_start - 4;
+    w.patch_u32(unit_start, unit_length as u32);
+}
+
+/// Write the rows of `function` as a sequence starting at its symbol.
+fn write_sequence<S: Copy>(w: &mut Writer<S>, function: &FunctionDebugInfo<S>) {
+    w.u8(0);
+    w.uleb128(1 + u64::from(w.address_size()));
+    w.u8(DW_LNE_SET_ADDRESS);
+    w.address(function.symbol, 0);
+
+    let mut address = 0;
+    let mut line = 1;
+    for &(offset, row_line) in &function.lines {
+        write_row(w, offset - address, i64::from(row_line) - line);
+        address = offset;
+        line = i64::from(row_line);
+    }
+
+    if function.size > address {
+        w.u8(DW_LNS_ADVANCE_PC);
+        w.uleb128(u64::from(function.size - address));
+    }
+    w.u8(0);
+    w.uleb128(1);
+    w.u8(DW_LNE_END_SEQUENCE);
+}
+
+/// Append a row to the line table, using a special opcode when the deltas are small enough.
+fn write_row<S: Copy>(w: &mut Writer<S>, address_delta: u32, line_delta: i64) {
+    if line_delta >= LINE_BASE && line_delta < LINE_BASE + i64::from(LINE_RANGE) {
+        let opcode = (line_delta - LINE_BASE) as u64
+            + u64::from(LINE_RANGE) * u64::from(address_delta)
+            + u64::from(OPCODE_BASE);
+        if opcode <= 255 {
+            w.u8(opcode as u8);
+            return;
+        }
+    }
+
+    if address_delta != 0 {
+        w.u8(DW_LNS_ADVANCE_PC);
+        w.uleb128(u64::from(address_delta));
+    }
+    if line_delta != 0 {
+        w.u8(DW_LNS_ADVANCE_LINE);
+        w.sleb128(line_delta);
+    }
+    w.u8(DW_LNS_COPY);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rows() {
+        let mut w = Writer::<()>::new(8, false);
+        // Same line and next line at the same address.
+        write_row(&mut w, 0, 0);
+        write_row(&mut w, 0, 1);
+        // Small steps fit a special opcode.
+        write_row(&mut w, 3, -2);
+        // Large ones don't.
+        write_row(&mut w, 17, 8);
+        write_row(&mut w, 18, 0);
+        write_row(&mut w, 1, 100);
+        write_row(&mut w, 300, -70);
+        assert_eq!(
+            w.data,
+            [
+                0x12, 0x13, 0x3a, 0x02, 0x11, 0x03, 0x08, 0x01, 0x02, 0x12, 0x01, 0x02, 0x01, 0x03,
+                0xe4, 0x00, 0x01, 0x02, 0xac, 0x02, 0x03, 0xba, 0x7f, 0x01
+            ]
+        );
+    }
+}
@@ -0,0 +1,56 @@
+//! Mapping of Cranelift register units to DWARF register numbers.
+
+use cranelift_codegen::ir::Type;
+use cranelift_codegen::isa::{RegUnit, TargetIsa};
+
+/// DWARF numbers of the x86-64 general purpose registers, in Cranelift's register unit order
+/// (`rax`, `rcx`, `rdx`, `rbx`, `rsp`, `rbp`, `rsi`, `rdi`, `r8`, ...).
+const X86_64_GPRS: [u16; 16] = [0, 2, 1, 3, 7, 6, 4, 5, 8, 9, 10, 11, 12, 13, 14, 15];
+
+/// Get the DWARF register number of `reg`, holding a value of type `ty`, or `None` if the
+/// register has no DWARF equivalent.
+pub fn map_reg(isa: &dyn TargetIsa, reg: RegUnit, ty: Type) -> Option<u16> {
+    let reginfo = isa.register_info();
+    let bank = reginfo.bank_containing_regunit(reg)?;
+    let index = reg - bank.first_unit;
+    match (isa.name(), bank.name) {
+        ("x86", "IntRegs") if isa.pointer_bits() == 64 => Some(X86_64_GPRS[usize::from(index)]),
+        ("x86", "IntRegs") => Some(index),
+        ("x86", "FloatRegs") if isa.pointer_bits() == 64 => Some(17 + index),
+        ("x86", "FloatRegs") => Some(21 + index),
+        ("riscv", "IntRegs") => Some(index),
+        ("riscv", "FloatRegs") => Some(32 + index),
+        ("arm64", "IntRegs") => Some(index),
+        ("arm64", "FloatRegs") => Some(64 + index),
+        ("arm32", "IntRegs") => Some(index),
+        // Double precision values occupy a pair of single precision register units, and only the
+        // first 32 single precision registers are addressable as such.
+        ("arm32", "FloatRegs") if ty.bits() == 64 => Some(256 + index / 2),
+        ("arm32", "FloatRegs") if index < 32 => Some(64 + index),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cranelift_codegen::ir::types;
+    use cranelift_codegen::isa;
+    use cranelift_codegen::settings;
+
+    #[test]
+    fn x86_64() {
+        let isa = match isa::lookup_by_name("x86_64") {
+            Ok(builder) => builder.finish(settings::Flags::new(settings::builder())),
+            Err(_) => return,
+        };
+        let reginfo = isa.register_info();
+        let reg = |name| reginfo.parse_regunit(name).unwrap();
+        assert_eq!(map_reg(&*isa, reg("rax"), types::I64), Some(0));
+        assert_eq!(map_reg(&*isa, reg("rdx"), types::I64), Some(1));
+        assert_eq!(map_reg(&*isa, reg("rsp"), types::I64), Some(7));
+        assert_eq!(map_reg(&*isa, reg("r15"), types::I64), Some(15));
+        assert_eq!(map_reg(&*isa, reg("xmm3"), types::F64), Some(20));
+        assert_eq!(map_reg(&*isa, reg("rflags"), types::IFLAGS), None);
+    }
+}
@@ -0,0 +1,174 @@
+//! A byte buffer for building DWARF sections along with their relocations.
+
+use crate::{DebugReloc, DebugRelocTarget, SectionId};
+
+/// The contents of a DWARF section under construction.
+pub struct Writer<S> {
+    pub data: Vec<u8>,
+    pub relocs: Vec<DebugReloc<S>>,
+    address_size: u8,
+    big_endian: bool,
+}
+
+impl<S: Copy> Writer<S> {
+    pub fn new(address_size: u8, big_endian: bool) -> Self {
+        Self {
+            data: Vec::new(),
+            relocs: Vec::new(),
+            address_size,
+            big_endian,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    pub fn address_size(&self) -> u8 {
+        self.address_size
+    }
+
+    pub fn u8(&mut self, value: u8) {
+        self.data.push(value);
+    }
+
+    pub fn u16(&mut self, value: u16) {
+        self.udata(u64::from(value), 2);
+    }
+
+    pub fn u32(&mut self, value: u32) {
+        self.udata(u64::from(value), 4);
+    }
+
+    /// Write the low `size` bytes of `value`.
+    pub fn udata(&mut self, value: u64, size: u8) {
+        let size = usize::from(size);
+        if self.big_endian {
+            self.data
+                .extend_from_slice(&value.to_be_bytes()[8 - size..]);
+        } else {
+            self.data.extend_from_slice(&value.to_le_bytes()[..size]);
+        }
+    }
+
+    pub fn bytes(&mut self, bytes: &[u8]) {
+        self.data.extend_from_slice(bytes);
+    }
+
+    /// Write a nul-terminated string.
+    pub fn string(&mut self, s: &str) {
+        self.data.extend_from_slice(s.as_bytes());
+        self.data.push(0);
+    }
+
+    pub fn uleb128(&mut self, mut value: u64) {
+        loop {
+            let byte = (value & 0x7f) as u8;
+            value >>= 7;
+            if value == 0 {
+                self.data.push(byte);
+                return;
+            }
+            self.data.push(byte | 0x80);
+        }
+    }
+
+    pub fn sleb128(&mut self, mut value: i64) {
+        loop {
+            let byte = (value & 0x7f) as u8;
+            value >>= 7;
+            if (value == 0 && byte & 0x40 == 0) || (value == -1 && byte & 0x40 != 0) {
+                self.data.push(byte);
+                return;
+            }
+            self.data.push(byte | 0x80);
+        }
+    }
+
+    /// Write a target address: `symbol` plus `addend`.
+    pub fn address(&mut self, symbol: S, addend: i64) {
+        let size = self.address_size;
+        self.reloc(DebugRelocTarget::Symbol(symbol), addend, size);
+    }
+
+    /// Write a 4-byte offset into the `section` output for the same compilation unit.
+    pub fn section_offset(&mut self, section: SectionId, offset: usize) {
+        self.reloc(DebugRelocTarget::Section(section), offset as i64, 4);
+    }
+
+    /// Overwrite the 4-byte value at `offset`, typically a length that is only known once the
+    /// data it covers has been written.
+    pub fn patch_u32(&mut self, offset: usize, value: u32) {
+        let bytes = if self.big_endian {
+            value.to_be_bytes()
+        } else {
+            value.to_le_bytes()
+        };
+        self.data[offset..offset + 4].copy_from_slice(&bytes);
+    }
+
+    /// Pad with `byte` up to a multiple of `align` bytes.
+    pub fn align(&mut self, align: usize, byte: u8) {
+        while self.data.len() % align != 0 {
+            self.data.push(byte);
+        }
+    }
+
+    fn reloc(&mut self, target: DebugRelocTarget<S>, addend: i64, size: u8) {
+        self.relocs.push(DebugReloc {
+            offset: self.data.len() as u32,
+            size,
+            target,
+            addend,
+        });
+        // The addend is written in place too, for formats whose relocations don't carry one.
+        self.udata(addend as u64, size);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn leb128() {
+        let mut w = Writer::<()>::new(8, false);
+        w.uleb128(2);
+        w.uleb128(127);
+        w.uleb128(128);
+        w.uleb128(624_485);
+        w.sleb128(2);
+        w.sleb128(-2);
+        w.sleb128(63);
+        w.sleb128(64);
+        w.sleb128(-64);
+        w.sleb128(-65);
+        w.sleb128(-123_456);
+        assert_eq!(
+            w.data,
+            [
+                0x02, 0x7f, 0x80, 0x01, 0xe5, 0x8e, 0x26, 0x02, 0x7e, 0x3f, 0xc0, 0x00, 0x40, 0xbf,
+                0x7f, 0xc0, 0xbb, 0x78
+            ]
+        );
+    }
+
+    #[test]
+    fn relocations() {
+        let mut w = Writer::new(4, true);
+        w.u8(1);
+        w.address(7u32, 0x10);
+        w.section_offset(SectionId::DebugLine, 0x20);
+        assert_eq!(w.data, [1, 0, 0, 0, 0x10, 0, 0, 0, 0x20]);
+        assert_eq!(w.relocs.len(), 2);
+        assert_eq!(w.relocs[0].offset, 1);
+        assert_eq!(w.relocs[0].size, 4);
+        assert_eq!(w.relocs[0].target, DebugRelocTarget::Symbol(7));
+        assert_eq!(w.relocs[1].offset, 5);
+        assert_eq!(
+            w.relocs[1].target,
+            DebugRelocTarget::Section(SectionId::DebugLine)
+        );
+        assert_eq!(w.relocs[1].addend, 0x20);
+    }
+}
@@ -135,11 +135,19 @@ impl Backend for FaerieBackend {
         name: &str,
         linkage: Linkage,
         writable: bool,
+        tls: bool,
         align: Option<u8>,
-    ) {
+    ) -> ModuleResult<()> {
+        if tls {
+            return Err(ModuleError::Backend(format!(
+                "Faerie doesn't yet support TLS data objects: {}",
+                name
+            )));
+        }
         self.artifact
             .declare(name, translate_data_linkage(linkage, writable, align))
             .expect("inconsistent declarations");
+        Ok(())
     }
 
     fn define_function(
@@ -205,10 +213,18 @@ impl Backend for FaerieBackend {
         _id: DataId,
         name: &str,
         _writable: bool,
+        tls: bool,
         _align: Option<u8>,
         data_ctx: &DataContext,
         namespace: &ModuleNamespace<Self>,
     ) -> ModuleResult<FaerieCompiledData> {
+        if tls {
+            return Err(ModuleError::Backend(format!(
+                "Faerie doesn't yet support TLS data objects: {}",
+                name
+            )));
+        }
+
         let &DataDescription {
             ref init,
             ref function_decls,
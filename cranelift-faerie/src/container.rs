@@ -29,9 +29,21 @@ pub fn raw_relocation(reloc: Reloc, triple: &Triple) -> (u32, i64) {
                             // R_X86_64_GOTPCRELX/R_X86_64_REX_GOTPCRELX.
                             Reloc::X86CallPLTRel4 => elf::reloc::R_X86_64_PLT32,
                             Reloc::X86GOTPCRel4 => elf::reloc::R_X86_64_GOTPCREL,
+                            Reloc::ElfX86_64TlsGd => elf::reloc::R_X86_64_TLSGD,
+                            Reloc::ElfX86_64GotTpOff => elf::reloc::R_X86_64_GOTTPOFF,
                             _ => unimplemented!(),
                         }
                     }
+                    Architecture::Aarch64(_) => match reloc {
+                        Reloc::Abs8 => elf::reloc::R_AARCH64_ABS64,
+                        Reloc::Arm64Call => elf::reloc::R_AARCH64_CALL26,
+                        _ => unimplemented!(),
+                    },
+                    Architecture::Arm(_) => match reloc {
+                        Reloc::Abs4 => elf::reloc::R_ARM_ABS32,
+                        Reloc::Arm32Call => elf::reloc::R_ARM_CALL,
+                        _ => unimplemented!(),
+                    },
                     _ => unimplemented!("unsupported architecture: {}", triple),
                 },
                 // Most ELF relocations do not include an implicit addend.
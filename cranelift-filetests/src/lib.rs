@@ -37,11 +37,14 @@ mod runner;
 mod runone;
 mod subtest;
 
+mod test_alias;
 mod test_binemit;
 mod test_cat;
 mod test_compile;
 mod test_dce;
 mod test_domtree;
+mod test_fde;
+mod test_inline;
 mod test_legalizer;
 mod test_licm;
 mod test_postopt;
@@ -54,6 +57,8 @@ mod test_safepoint;
 mod test_shrink;
 mod test_simple_gvn;
 mod test_simple_preopt;
+mod test_strength_reduce;
+mod test_unroll;
 mod test_unwind;
 mod test_verifier;
 
@@ -118,12 +123,15 @@ pub fn run_passes(
 /// a `.clif` test file.
 fn new_subtest(parsed: &TestCommand) -> subtest::SubtestResult<Box<dyn subtest::SubTest>> {
     match parsed.command {
+        "alias" => test_alias::subtest(parsed),
         "binemit" => test_binemit::subtest(parsed),
         "cat" => test_cat::subtest(parsed),
         "compile" => test_compile::subtest(parsed),
         "rodata" => test_rodata::subtest(parsed),
         "dce" => test_dce::subtest(parsed),
         "domtree" => test_domtree::subtest(parsed),
+        "fde" => test_fde::subtest(parsed),
+        "inline" => test_inline::subtest(parsed),
         "legalizer" => test_legalizer::subtest(parsed),
         "licm" => test_licm::subtest(parsed),
         "postopt" => test_postopt::subtest(parsed),
@@ -133,10 +141,12 @@ fn new_subtest(parsed: &TestCommand) -> subtest::SubtestResult<Box<dyn subtest::
         "run" => test_run::subtest(parsed),
         "shrink" => test_shrink::subtest(parsed),
         "simple-gvn" => test_simple_gvn::subtest(parsed),
+        "strength-reduce" => test_strength_reduce::subtest(parsed),
         "verifier" => test_verifier::subtest(parsed),
         "preopt" => test_preopt::subtest(parsed),
         "safepoint" => test_safepoint::subtest(parsed),
         "unwind" => test_unwind::subtest(parsed),
+        "unroll" => test_unroll::subtest(parsed),
         _ => Err(format!("unknown test command '{}'", parsed.command)),
     }
 }
@@ -109,6 +109,14 @@ pub fn run(path: &Path, passes: Option<&[String]>, target: Option<&str>) -> Test
         Some(t) => t,
     };
 
+    // Keep a pristine copy of every function around for the sub-tests that look across
+    // functions.
+    let functions: Vec<_> = testfile
+        .functions
+        .iter()
+        .map(|(func, _)| func.clone())
+        .collect();
+
     for (func, details) in testfile.functions {
         let mut context = Context {
             preamble_comments: &testfile.preamble_comments,
@@ -116,6 +124,7 @@ pub fn run(path: &Path, passes: Option<&[String]>, target: Option<&str>) -> Test
             verified: false,
             flags,
             isa: None,
+            functions: &functions,
         };
 
         for tuple in &tuples {
@@ -26,6 +26,10 @@ pub struct Context<'a> {
     /// Target ISA to test against. Only guaranteed to be present for sub-tests whose `needs_isa`
     /// method returned `true`. For other sub-tests, this is set if the test file has a unique ISA.
     pub isa: Option<&'a dyn TargetIsa>,
+
+    /// All the functions in the test file, as they were parsed. Calls to these functions may be
+    /// inlined by the `inline` sub-test.
+    pub functions: &'a [Function],
 }
 
 impl<'a> Context<'a> {
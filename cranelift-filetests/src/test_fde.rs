@@ -0,0 +1,306 @@
+//! Test command for verifying the `.eh_frame` entries emitted for each function.
+//!
+//! The `fde` test command runs each function through the full code generator pipeline and prints
+//! the decoded CIE and FDE describing it, along with the call-site table of its LSDA if it has
+//! one.
+
+use crate::subtest::{run_filecheck, Context, SubTest, SubtestResult};
+use byteorder::{LittleEndian, ReadBytesExt};
+use cranelift_codegen;
+use cranelift_codegen::binemit::{FrameUnwindKind, FrameUnwindOffset, FrameUnwindSink, Reloc};
+use cranelift_codegen::ir;
+use cranelift_reader::TestCommand;
+use std::borrow::Cow;
+use std::fmt::Write;
+use std::io::{BufRead, Cursor};
+
+struct TestFde;
+
+pub fn subtest(parsed: &TestCommand) -> SubtestResult<Box<dyn SubTest>> {
+    assert_eq!(parsed.command, "fde");
+    if !parsed.options.is_empty() {
+        Err(format!("No options allowed on {}", parsed))
+    } else {
+        Ok(Box::new(TestFde))
+    }
+}
+
+impl SubTest for TestFde {
+    fn name(&self) -> &'static str {
+        "fde"
+    }
+
+    fn is_mutating(&self) -> bool {
+        false
+    }
+
+    fn needs_isa(&self) -> bool {
+        true
+    }
+
+    fn run(&self, func: Cow<ir::Function>, context: &Context) -> SubtestResult<()> {
+        let isa = context.isa.expect("fde needs an ISA");
+        let mut comp_ctx = cranelift_codegen::Context::for_function(func.into_owned());
+
+        comp_ctx.compile(isa).expect("failed to compile function");
+
+        let mut sink = Sink {
+            mem: Vec::new(),
+            entry: None,
+            relocs: Vec::new(),
+            external_relocs: Vec::new(),
+            lsda: None,
+        };
+        comp_ctx.emit_unwind_info(isa, FrameUnwindKind::Libunwind, &mut sink);
+
+        let mut text = String::new();
+        if sink.mem.is_empty() {
+            writeln!(text, "No unwind information.").unwrap();
+        } else {
+            print_eh_frame(&mut text, &sink).map_err(|e| e.to_string())?;
+        }
+
+        run_filecheck(&text, context)
+    }
+}
+
+struct Sink {
+    mem: Vec<u8>,
+    entry: Option<FrameUnwindOffset>,
+    relocs: Vec<(Reloc, FrameUnwindOffset)>,
+    external_relocs: Vec<(Reloc, FrameUnwindOffset, ir::ExternalName)>,
+    lsda: Option<(Vec<u8>, Reloc, FrameUnwindOffset)>,
+}
+
+impl FrameUnwindSink for Sink {
+    fn len(&self) -> FrameUnwindOffset {
+        self.mem.len()
+    }
+
+    fn bytes(&mut self, b: &[u8]) {
+        self.mem.extend_from_slice(b);
+    }
+
+    fn reloc(&mut self, r: Reloc, off: FrameUnwindOffset) {
+        self.relocs.push((r, off));
+    }
+
+    fn reloc_external(&mut self, r: Reloc, off: FrameUnwindOffset, name: &ir::ExternalName) {
+        self.external_relocs.push((r, off, name.clone()));
+    }
+
+    fn lsda(&mut self, lsda: &[u8], r: Reloc, off: FrameUnwindOffset) {
+        assert!(self.lsda.is_none(), "more than one LSDA");
+        self.lsda = Some((lsda.to_vec(), r, off));
+    }
+
+    fn set_entry_offset(&mut self, off: FrameUnwindOffset) {
+        self.entry = Some(off);
+    }
+}
+
+fn print_eh_frame(text: &mut String, sink: &Sink) -> std::io::Result<()> {
+    let mut cursor = Cursor::new(&sink.mem[..]);
+    let mut data_align = 1;
+    let mut lsda_encoding = 0;
+
+    while (cursor.position() as usize) < sink.mem.len() {
+        let start = cursor.position() as usize;
+        let length = cursor.read_u32::<LittleEndian>()? as usize;
+        let end = start + 4 + length;
+        assert_eq!(end % 8, 0, "entry at {} is not padded", start);
+
+        let id = cursor.read_u32::<LittleEndian>()?;
+        if id == 0 {
+            let version = cursor.read_u8()?;
+            let mut augmentation = Vec::new();
+            cursor.read_until(0, &mut augmentation)?;
+            augmentation.pop();
+            let code_align = read_uleb128(&mut cursor)?;
+            data_align = read_sleb128(&mut cursor)?;
+            let ra_reg = read_uleb128(&mut cursor)?;
+            let augmentation_len = read_uleb128(&mut cursor)?;
+            let augmentation_start = cursor.position() as usize;
+            cursor.set_position(cursor.position() + augmentation_len);
+            writeln!(
+                text,
+                "{}: CIE version {}, augmentation \"{}\", code_align {}, data_align {}, ra r{}",
+                start,
+                version,
+                String::from_utf8_lossy(&augmentation),
+                code_align,
+                data_align,
+                ra_reg
+            )
+            .unwrap();
+            let mut personality_encoding = 0;
+            let mut data = &sink.mem[augmentation_start..];
+            for c in augmentation.iter().skip(1) {
+                match c {
+                    b'P' => {
+                        personality_encoding = data[0];
+                        data = &data[1 + pointer_size(personality_encoding)..];
+                    }
+                    b'L' => {
+                        lsda_encoding = data[0];
+                        data = &data[1..];
+                    }
+                    b'R' => data = &data[1..],
+                    _ => panic!("unsupported augmentation {}", *c as char),
+                }
+            }
+            for (reloc, offset, name) in &sink.external_relocs {
+                if (augmentation_start..cursor.position() as usize).contains(offset) {
+                    writeln!(
+                        text,
+                        "    personality {} ({}), encoding {:#x}",
+                        name, reloc, personality_encoding
+                    )
+                    .unwrap();
+                }
+            }
+        } else {
+            let cie = start + 4 - id as usize;
+            let location_offset = cursor.position() as usize;
+            let location = cursor.read_u64::<LittleEndian>()?;
+            let range = cursor.read_u64::<LittleEndian>()?;
+            let augmentation_len = read_uleb128(&mut cursor)?;
+            let augmentation_start = cursor.position() as usize;
+            cursor.set_position(cursor.position() + augmentation_len);
+            assert_eq!(sink.entry, Some(start), "FDE isn't the entry");
+            let reloc = sink
+                .relocs
+                .iter()
+                .find(|&&(_, offset)| offset == location_offset)
+                .map_or("none".to_string(), |(r, _)| r.to_string());
+            writeln!(
+                text,
+                "{}: FDE cie {}, location {:#x} ({}), range {:#x}",
+                start, cie, location, reloc, range
+            )
+            .unwrap();
+            if let Some((ref lsda, reloc, offset)) = sink.lsda {
+                if (augmentation_start..cursor.position() as usize).contains(&offset) {
+                    writeln!(text, "    lsda ({}), encoding {:#x}", reloc, lsda_encoding).unwrap();
+                    print_lsda(text, lsda)?;
+                }
+            }
+        }
+
+        while (cursor.position() as usize) < end {
+            print_cfa_instruction(text, &mut cursor, data_align)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// The size of a pointer with the `DW_EH_PE_*` encoding `encoding`.
+fn pointer_size(encoding: u8) -> usize {
+    match encoding & 0x0f {
+        0x00 => 8,
+        0x0b => 4,
+        _ => panic!("unsupported pointer encoding {:#x}", encoding),
+    }
+}
+
+fn print_lsda(text: &mut String, lsda: &[u8]) -> std::io::Result<()> {
+    let mut cursor = Cursor::new(lsda);
+    assert_eq!(cursor.read_u8()?, 0xff, "LPStart should be omitted");
+    assert_eq!(cursor.read_u8()?, 0xff, "TType should be omitted");
+    assert_eq!(cursor.read_u8()?, 0x01, "call sites should be uleb128");
+    let end = read_uleb128(&mut cursor)? + cursor.position();
+    while cursor.position() < end {
+        let start = read_uleb128(&mut cursor)?;
+        let length = read_uleb128(&mut cursor)?;
+        let landing_pad = read_uleb128(&mut cursor)?;
+        let action = read_uleb128(&mut cursor)?;
+        writeln!(
+            text,
+            "    call site {:#x}..{:#x}, landing pad {:#x}, action {}",
+            start,
+            start + length,
+            landing_pad,
+            action
+        )
+        .unwrap();
+    }
+    assert_eq!(
+        end as usize,
+        lsda.len(),
+        "unexpected data after the call-site table"
+    );
+    Ok(())
+}
+
+fn print_cfa_instruction(
+    text: &mut String,
+    cursor: &mut Cursor<&[u8]>,
+    data_align: i64,
+) -> std::io::Result<()> {
+    let opcode = cursor.read_u8()?;
+    let operand = u64::from(opcode & 0x3f);
+    match opcode >> 6 {
+        1 => writeln!(text, "    DW_CFA_advance_loc {}", operand),
+        2 => {
+            let offset = read_uleb128(cursor)? as i64 * data_align;
+            writeln!(text, "    DW_CFA_offset r{}, cfa{:+}", operand, offset)
+        }
+        3 => writeln!(text, "    DW_CFA_restore r{}", operand),
+        _ => match opcode {
+            0x00 => writeln!(text, "    DW_CFA_nop"),
+            0x02 => writeln!(text, "    DW_CFA_advance_loc {}", cursor.read_u8()?),
+            0x03 => writeln!(
+                text,
+                "    DW_CFA_advance_loc {}",
+                cursor.read_u16::<LittleEndian>()?
+            ),
+            0x04 => writeln!(
+                text,
+                "    DW_CFA_advance_loc {}",
+                cursor.read_u32::<LittleEndian>()?
+            ),
+            0x0a => writeln!(text, "    DW_CFA_remember_state"),
+            0x0b => writeln!(text, "    DW_CFA_restore_state"),
+            0x0c => {
+                let reg = read_uleb128(cursor)?;
+                let offset = read_uleb128(cursor)?;
+                writeln!(text, "    DW_CFA_def_cfa r{}, {}", reg, offset)
+            }
+            0x0d => writeln!(text, "    DW_CFA_def_cfa_register r{}", read_uleb128(cursor)?),
+            0x0e => writeln!(text, "    DW_CFA_def_cfa_offset {}", read_uleb128(cursor)?),
+            _ => panic!("unsupported call frame instruction {:#x}", opcode),
+        },
+    }
+    .unwrap();
+    Ok(())
+}
+
+fn read_uleb128(cursor: &mut Cursor<&[u8]>) -> std::io::Result<u64> {
+    let mut result = 0;
+    let mut shift = 0;
+    loop {
+        let byte = cursor.read_u8()?;
+        result |= u64::from(byte & 0x7f) << shift;
+        shift += 7;
+        if byte & 0x80 == 0 {
+            return Ok(result);
+        }
+    }
+}
+
+fn read_sleb128(cursor: &mut Cursor<&[u8]>) -> std::io::Result<i64> {
+    let mut result = 0;
+    let mut shift = 0;
+    loop {
+        let byte = cursor.read_u8()?;
+        result |= i64::from(byte & 0x7f) << shift;
+        shift += 7;
+        if byte & 0x80 == 0 {
+            if shift < 64 && byte & 0x40 != 0 {
+                result |= -1 << shift;
+            }
+            return Ok(result);
+        }
+    }
+}
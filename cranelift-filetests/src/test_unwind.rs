@@ -6,6 +6,7 @@
 use crate::subtest::{run_filecheck, Context, SubTest, SubtestResult};
 use byteorder::{LittleEndian, ReadBytesExt};
 use cranelift_codegen;
+use cranelift_codegen::binemit::{FrameUnwindKind, FrameUnwindOffset, FrameUnwindSink, Reloc};
 use cranelift_codegen::ir;
 use cranelift_reader::TestCommand;
 use std::borrow::Cow;
@@ -42,20 +43,46 @@ impl SubTest for TestUnwind {
 
         comp_ctx.compile(isa).expect("failed to compile function");
 
-        let mut mem = Vec::new();
-        comp_ctx.emit_unwind_info(isa, &mut mem);
+        let mut sink = Sink(Vec::new());
+        comp_ctx.emit_unwind_info(isa, FrameUnwindKind::Fastcall, &mut sink);
 
         let mut text = String::new();
-        if mem.is_empty() {
+        if sink.0.is_empty() {
             writeln!(text, "No unwind information.").unwrap();
         } else {
-            print_unwind_info(&mut text, &mem);
+            print_unwind_info(&mut text, &sink.0);
         }
 
         run_filecheck(&text, context)
     }
 }
 
+struct Sink(Vec<u8>);
+
+impl FrameUnwindSink for Sink {
+    fn len(&self) -> FrameUnwindOffset {
+        self.0.len()
+    }
+
+    fn bytes(&mut self, b: &[u8]) {
+        self.0.extend_from_slice(b);
+    }
+
+    fn reloc(&mut self, _: Reloc, _: FrameUnwindOffset) {
+        unreachable!("UNWIND_INFO has no relocations");
+    }
+
+    fn reloc_external(&mut self, _: Reloc, _: FrameUnwindOffset, _: &ir::ExternalName) {
+        unreachable!("UNWIND_INFO has no relocations");
+    }
+
+    fn lsda(&mut self, _: &[u8], _: Reloc, _: FrameUnwindOffset) {
+        unreachable!("UNWIND_INFO has no LSDA");
+    }
+
+    fn set_entry_offset(&mut self, _: FrameUnwindOffset) {}
+}
+
 fn print_unwind_info(text: &mut String, mem: &[u8]) {
     let info = UnwindInfo::from_cursor(&mut Cursor::new(mem)).expect("failed to read unwind info");
 
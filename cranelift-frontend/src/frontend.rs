@@ -896,7 +896,9 @@ mod tests {
     use alloc::string::ToString;
     use cranelift_codegen::entity::EntityRef;
     use cranelift_codegen::ir::types::*;
-    use cranelift_codegen::ir::{AbiParam, ExternalName, Function, InstBuilder, Signature};
+    use cranelift_codegen::ir::{
+        AbiParam, ExtFuncData, ExternalName, Function, InstBuilder, Signature,
+    };
     use cranelift_codegen::isa::CallConv;
     use cranelift_codegen::settings;
     use cranelift_codegen::verifier::verify_function;
@@ -1298,6 +1300,106 @@ ebb0:
         );
     }
 
+    #[test]
+    fn invokes_with_shared_landing_pad() {
+        // Two invokes unwind to the same landing pad, with a different value of `x` at each.
+        let mut sig = Signature::new(CallConv::SystemV);
+        sig.returns.push(AbiParam::new(I64));
+        sig.params.push(AbiParam::new(I64));
+
+        let mut fn_ctx = FunctionBuilderContext::new();
+        let mut func = Function::with_name_signature(ExternalName::testcase("sample"), sig);
+        {
+            let mut builder = FunctionBuilder::new(&mut func, &mut fn_ctx);
+
+            let mut callee_sig = Signature::new(CallConv::SystemV);
+            callee_sig.params.push(AbiParam::new(I64));
+            callee_sig.returns.push(AbiParam::new(I64));
+            let callee_sig = builder.import_signature(callee_sig);
+            let callee = builder.import_function(ExtFuncData {
+                name: ExternalName::testcase("callee"),
+                signature: callee_sig,
+                colocated: false,
+            });
+            let personality_sig = builder.import_signature(Signature::new(CallConv::SystemV));
+            let personality = builder.import_function(ExtFuncData {
+                name: ExternalName::testcase("personality"),
+                signature: personality_sig,
+                colocated: false,
+            });
+            builder.func.personality = Some(personality);
+
+            let block0 = builder.create_ebb();
+            let block1 = builder.create_ebb();
+            let block2 = builder.create_ebb();
+            let landing_pad = builder.create_ebb();
+            let x = Variable::new(0);
+            builder.declare_var(x, I64);
+            builder.append_ebb_params_for_function_params(block0);
+            builder.append_ebb_param(landing_pad, I64);
+
+            builder.switch_to_block(block0);
+            let arg = builder.ebb_params(block0)[0];
+            builder.def_var(x, arg);
+            let call = builder.ins().invoke(callee, landing_pad, &[arg]);
+            let result = builder.inst_results(call)[0];
+            builder.ins().jump(block1, &[]);
+
+            builder.switch_to_block(block1);
+            builder.def_var(x, result);
+            builder.ins().invoke(callee, landing_pad, &[result]);
+            builder.ins().jump(block2, &[]);
+
+            builder.switch_to_block(block2);
+            let arg = builder.use_var(x);
+            builder.ins().return_(&[arg]);
+
+            builder.switch_to_block(landing_pad);
+            let arg = builder.use_var(x);
+            builder.ins().return_(&[arg]);
+
+            builder.seal_all_blocks();
+            builder.finalize();
+        }
+
+        let flags = settings::Flags::new(settings::builder());
+        if let Err(errors) = verify_function(&func, &flags) {
+            panic!("{}\n{}", func.display(None), errors)
+        }
+        assert_eq!(
+            func.display(None).to_string(),
+            "function %sample(i64) -> i64 system_v {
+    sig0 = (i64) -> i64 system_v
+    sig1 = () system_v
+    fn0 = %callee sig0
+    fn1 = %personality sig1
+    personality = fn1
+
+ebb0(v0: i64):
+    v2 = invoke fn0(v0), ebb4
+    v4 -> v2
+    jump ebb1
+
+ebb1:
+    v3 = invoke fn0(v2), ebb5
+    jump ebb2
+
+ebb2:
+    return v4
+
+ebb3(v1: i64, v5: i64):
+    return v5
+
+ebb4(v6: i64):
+    jump ebb3(v6, v0)
+
+ebb5(v7: i64):
+    jump ebb3(v7, v2)
+}
+"
+        );
+    }
+
     #[test]
     fn test_greatest_divisible_power_of_two() {
         assert_eq!(64, greatest_divisible_power_of_two(64));
@@ -685,6 +685,24 @@ impl SSABuilder {
             BranchInfo::NotABranch => {
                 panic!("you have declared a non-branch instruction as a predecessor to an ebb");
             }
+            // The parameters of a landing pad are supplied by the unwinder, so the `invoke` can't
+            // pass the variable. Give it a landing pad of its own instead, which jumps to
+            // `dest_ebb` with the exception pointer and the value of the variable.
+            BranchInfo::SingleDest(_, _) if func.dfg[jump_inst].landing_pad().is_some() => {
+                let middle_ebb = func.dfg.make_ebb();
+                func.layout.append_ebb(middle_ebb);
+                let exception_type = func.dfg.value_type(func.dfg.ebb_params(dest_ebb)[0]);
+                let exception = func.dfg.append_ebb_param(middle_ebb, exception_type);
+                let middle_block = self.declare_ebb_header_block(middle_ebb);
+                self.blocks[middle_block].add_predecessor(jump_inst_block, jump_inst);
+                self.mark_ebb_header_block_sealed(middle_block);
+
+                func.change_branch_destination(jump_inst, middle_ebb);
+                let mut cur = FuncCursor::new(func).at_bottom(middle_ebb);
+                let middle_jump_inst = cur.ins().jump(dest_ebb, &[exception, val]);
+                self.def_var(var, val, middle_block);
+                Some((middle_ebb, middle_block, middle_jump_inst))
+            }
             // For a single destination appending a jump argument to the instruction
             // is sufficient.
             BranchInfo::SingleDest(_, _) => {
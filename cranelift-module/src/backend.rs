@@ -61,14 +61,18 @@ where
     fn declare_function(&mut self, id: FuncId, name: &str, linkage: Linkage);
 
     /// Declare a data object.
+    ///
+    /// Returns an error if the backend can't represent the data object, e.g. because it doesn't
+    /// support TLS.
     fn declare_data(
         &mut self,
         id: DataId,
         name: &str,
         linkage: Linkage,
         writable: bool,
+        tls: bool,
         align: Option<u8>,
-    );
+    ) -> ModuleResult<()>;
 
     /// Define a function, producing the function body from the given `Context`.
     ///
@@ -90,6 +94,7 @@ where
         id: DataId,
         name: &str,
         writable: bool,
+        tls: bool,
         align: Option<u8>,
         data_ctx: &DataContext,
         namespace: &ModuleNamespace<Self>,
@@ -162,5 +167,63 @@ pub fn default_libcall_names() -> Box<dyn Fn(ir::LibCall) -> String> {
         ir::LibCall::Memcpy => "memcpy".to_owned(),
         ir::LibCall::Memset => "memset".to_owned(),
         ir::LibCall::Memmove => "memmove".to_owned(),
+
+        ir::LibCall::UdivI128 => "__udivti3".to_owned(),
+        ir::LibCall::SdivI128 => "__divti3".to_owned(),
+        ir::LibCall::UremI128 => "__umodti3".to_owned(),
+        ir::LibCall::SremI128 => "__modti3".to_owned(),
+        ir::LibCall::FcvtFromSintF32I128 => "__floattisf".to_owned(),
+        ir::LibCall::FcvtFromSintF64I128 => "__floattidf".to_owned(),
+        ir::LibCall::FcvtFromUintF32I128 => "__floatuntisf".to_owned(),
+        ir::LibCall::FcvtFromUintF64I128 => "__floatuntidf".to_owned(),
+        ir::LibCall::FcvtToSintI128F32 => "__fixsfti".to_owned(),
+        ir::LibCall::FcvtToSintI128F64 => "__fixdfti".to_owned(),
+        ir::LibCall::FcvtToUintI128F32 => "__fixunssfti".to_owned(),
+        ir::LibCall::FcvtToUintI128F64 => "__fixunsdfti".to_owned(),
+
+        ir::LibCall::FaddF32 => "__addsf3".to_owned(),
+        ir::LibCall::FaddF64 => "__adddf3".to_owned(),
+        ir::LibCall::FsubF32 => "__subsf3".to_owned(),
+        ir::LibCall::FsubF64 => "__subdf3".to_owned(),
+        ir::LibCall::FmulF32 => "__mulsf3".to_owned(),
+        ir::LibCall::FmulF64 => "__muldf3".to_owned(),
+        ir::LibCall::FdivF32 => "__divsf3".to_owned(),
+        ir::LibCall::FdivF64 => "__divdf3".to_owned(),
+        ir::LibCall::SqrtF32 => "sqrtf".to_owned(),
+        ir::LibCall::SqrtF64 => "sqrt".to_owned(),
+        ir::LibCall::FmaF32 => "fmaf".to_owned(),
+        ir::LibCall::FmaF64 => "fma".to_owned(),
+        ir::LibCall::FpromoteF64F32 => "__extendsfdf2".to_owned(),
+        ir::LibCall::FdemoteF32F64 => "__truncdfsf2".to_owned(),
+        ir::LibCall::FcvtFromSintF32I32 => "__floatsisf".to_owned(),
+        ir::LibCall::FcvtFromSintF32I64 => "__floatdisf".to_owned(),
+        ir::LibCall::FcvtFromSintF64I32 => "__floatsidf".to_owned(),
+        ir::LibCall::FcvtFromSintF64I64 => "__floatdidf".to_owned(),
+        ir::LibCall::FcvtFromUintF32I32 => "__floatunsisf".to_owned(),
+        ir::LibCall::FcvtFromUintF32I64 => "__floatundisf".to_owned(),
+        ir::LibCall::FcvtFromUintF64I32 => "__floatunsidf".to_owned(),
+        ir::LibCall::FcvtFromUintF64I64 => "__floatundidf".to_owned(),
+        ir::LibCall::FcvtToSintI32F32 => "__fixsfsi".to_owned(),
+        ir::LibCall::FcvtToSintI32F64 => "__fixdfsi".to_owned(),
+        ir::LibCall::FcvtToSintI64F32 => "__fixsfdi".to_owned(),
+        ir::LibCall::FcvtToSintI64F64 => "__fixdfdi".to_owned(),
+        ir::LibCall::FcvtToUintI32F32 => "__fixunssfsi".to_owned(),
+        ir::LibCall::FcvtToUintI32F64 => "__fixunsdfsi".to_owned(),
+        ir::LibCall::FcvtToUintI64F32 => "__fixunssfdi".to_owned(),
+        ir::LibCall::FcvtToUintI64F64 => "__fixunsdfdi".to_owned(),
+        ir::LibCall::FcmpUnordF32 => "__unordsf2".to_owned(),
+        ir::LibCall::FcmpUnordF64 => "__unorddf2".to_owned(),
+        ir::LibCall::FcmpEqF32 => "__eqsf2".to_owned(),
+        ir::LibCall::FcmpEqF64 => "__eqdf2".to_owned(),
+        ir::LibCall::FcmpLtF32 => "__ltsf2".to_owned(),
+        ir::LibCall::FcmpLtF64 => "__ltdf2".to_owned(),
+        ir::LibCall::FcmpLeF32 => "__lesf2".to_owned(),
+        ir::LibCall::FcmpLeF64 => "__ledf2".to_owned(),
+        ir::LibCall::FcmpGtF32 => "__gtsf2".to_owned(),
+        ir::LibCall::FcmpGtF64 => "__gtdf2".to_owned(),
+        ir::LibCall::FcmpGeF32 => "__gesf2".to_owned(),
+        ir::LibCall::FcmpGeF64 => "__gedf2".to_owned(),
+
+        ir::LibCall::ElfTlsGetAddr => "__tls_get_addr".to_owned(),
     })
 }
@@ -10,7 +10,7 @@ use crate::data_context::DataContext;
 use crate::Backend;
 use cranelift_codegen::binemit::{self, CodeInfo};
 use cranelift_codegen::entity::{entity_impl, PrimaryMap};
-use cranelift_codegen::{ir, isa, CodegenError, Context};
+use cranelift_codegen::{ir, isa, CodegenError, Context, InlineCallees};
 use log::info;
 use std::borrow::ToOwned;
 use std::string::String;
@@ -85,6 +85,14 @@ impl Linkage {
         }
     }
 
+    /// Test whether this linkage makes a definition visible outside the module.
+    pub fn is_exported(self) -> bool {
+        match self {
+            Self::Import | Self::Local => false,
+            Self::Preemptible | Self::Export => true,
+        }
+    }
+
     /// Test whether this linkage will have a definition that cannot be preempted.
     pub fn is_final(self) -> bool {
         match self {
@@ -126,13 +134,18 @@ pub enum ModuleError {
     /// Indicates an identifier was used before it was declared
     #[error("Undeclared identifier: {0}")]
     Undeclared(String),
-    /// Indicates an identifier was used as data/function first, but then used as the other
+    /// Indicates an identifier was used as data/function first, but then used as the other, or a
+    /// data object was declared both as thread-local and as normal data
     #[error("Incompatible declaration of identifier: {0}")]
     IncompatibleDeclaration(String),
     /// Indicates a function identifier was declared with a
     /// different signature than declared previously
     #[error("Function {0} signature {2:?} is incompatible with previous declaration {1:?}")]
     IncompatibleSignature(String, ir::Signature, ir::Signature),
+    /// Indicates a function identifier was given a visible linkage, but uses a calling
+    /// convention that is private to Cranelift
+    #[error("Function {0} cannot be exported with the {1} calling convention")]
+    UnexportableCallConv(String, isa::CallConv),
     /// Indicates an identifier was defined more than once
     #[error("Duplicate definition of identifier: {0}")]
     DuplicateDefinition(String),
@@ -150,6 +163,23 @@ pub enum ModuleError {
 /// A convenient alias for a `Result` that uses `ModuleError` as the error type.
 pub type ModuleResult<T> = Result<T, ModuleError>;
 
+/// Check that a function visible outside the module uses a calling convention that code outside
+/// of Cranelift can call. The `fast` convention may change between compilations, so it is only
+/// suitable for functions local to the module.
+fn check_exported_call_conv(
+    name: &str,
+    linkage: Linkage,
+    signature: &ir::Signature,
+) -> ModuleResult<()> {
+    if linkage.is_exported() && signature.call_conv == isa::CallConv::Fast {
+        return Err(ModuleError::UnexportableCallConv(
+            name.to_owned(),
+            signature.call_conv,
+        ));
+    }
+    Ok(())
+}
+
 /// A function belonging to a `Module`.
 pub struct ModuleFunction<B>
 where
@@ -183,6 +213,7 @@ pub struct DataDeclaration {
     pub name: String,
     pub linkage: Linkage,
     pub writable: bool,
+    pub tls: bool,
     pub align: Option<u8>,
 }
 
@@ -201,10 +232,21 @@ impl<B> ModuleData<B>
 where
     B: Backend,
 {
-    fn merge(&mut self, linkage: Linkage, writable: bool, align: Option<u8>) {
+    fn merge(
+        &mut self,
+        linkage: Linkage,
+        writable: bool,
+        tls: bool,
+        align: Option<u8>,
+    ) -> Result<(), ModuleError> {
+        // A data object can't change between thread-local and normal storage.
+        if self.decl.tls != tls {
+            return Err(ModuleError::IncompatibleDeclaration(self.decl.name.clone()));
+        }
         self.decl.linkage = Linkage::merge(self.decl.linkage, linkage);
         self.decl.writable = self.decl.writable || writable;
         self.decl.align = self.decl.align.max(align);
+        Ok(())
     }
 }
 
@@ -339,9 +381,42 @@ where
     contents: ModuleContents<B>,
     functions_to_finalize: Vec<FuncId>,
     data_objects_to_finalize: Vec<DataId>,
+    inline_bodies: HashMap<FuncId, ir::Function>,
     backend: B,
 }
 
+/// The function bodies of a `Module` that can be inlined into the functions defined after them.
+struct ModuleInlineCallees<'a, B>
+where
+    B: Backend,
+{
+    contents: &'a ModuleContents<B>,
+    bodies: &'a HashMap<FuncId, ir::Function>,
+}
+
+impl<'a, B> InlineCallees for ModuleInlineCallees<'a, B>
+where
+    B: Backend,
+{
+    fn get_callee(&self, name: &ir::ExternalName) -> Option<&ir::Function> {
+        match *name {
+            ir::ExternalName::User {
+                namespace: 0,
+                index,
+            } => {
+                let id = FuncId::from_u32(index);
+                // A preemptible function may be replaced by another definition at link time.
+                if self.contents.functions.get(id)?.decl.linkage.is_final() {
+                    self.bodies.get(&id)
+                } else {
+                    None
+                }
+            }
+            _ => None,
+        }
+    }
+}
+
 impl<B> Module<B>
 where
     B: Backend,
@@ -356,6 +431,7 @@ where
             },
             functions_to_finalize: Vec::new(),
             data_objects_to_finalize: Vec::new(),
+            inline_bodies: HashMap::new(),
             backend: B::new(backend_builder),
         }
     }
@@ -419,6 +495,11 @@ where
             Occupied(entry) => match *entry.get() {
                 FuncOrDataId::Func(id) => {
                     let existing = &mut self.contents.functions[id];
+                    check_exported_call_conv(
+                        name,
+                        Linkage::merge(existing.decl.linkage, linkage),
+                        signature,
+                    )?;
                     existing.merge(linkage, signature)?;
                     self.backend
                         .declare_function(id, name, existing.decl.linkage);
@@ -429,6 +510,7 @@ where
                 }
             },
             Vacant(entry) => {
+                check_exported_call_conv(name, linkage, signature)?;
                 let id = self.contents.functions.push(ModuleFunction {
                     decl: FunctionDeclaration {
                         name: name.to_owned(),
@@ -455,6 +537,7 @@ where
         name: &str,
         linkage: Linkage,
         writable: bool,
+        tls: bool,
         align: Option<u8>, // An alignment bigger than 128 is unlikely
     ) -> ModuleResult<DataId> {
         // TODO: Can we avoid allocating names so often?
@@ -463,14 +546,15 @@ where
             Occupied(entry) => match *entry.get() {
                 FuncOrDataId::Data(id) => {
                     let existing = &mut self.contents.data_objects[id];
-                    existing.merge(linkage, writable, align);
+                    existing.merge(linkage, writable, tls, align)?;
                     self.backend.declare_data(
                         id,
                         name,
                         existing.decl.linkage,
                         existing.decl.writable,
+                        existing.decl.tls,
                         existing.decl.align,
-                    );
+                    )?;
                     Ok(id)
                 }
 
@@ -484,13 +568,14 @@ where
                         name: name.to_owned(),
                         linkage,
                         writable,
+                        tls,
                         align,
                     },
                     compiled: None,
                 });
                 entry.insert(FuncOrDataId::Data(id));
                 self.backend
-                    .declare_data(id, name, linkage, writable, align);
+                    .declare_data(id, name, linkage, writable, tls, align)?;
                 Ok(id)
             }
         }
@@ -521,6 +606,7 @@ where
             name: ir::ExternalName::user(1, data.as_u32()),
             offset: ir::immediates::Imm64::new(0),
             colocated,
+            tls: decl.tls,
         })
     }
 
@@ -538,6 +624,10 @@ where
     ///
     /// Returns the size of the function's code and constant data.
     ///
+    /// When the `inline_threshold` setting is enabled, calls to the small functions defined
+    /// earlier in this module are inlined first, and the body of this function is kept so it can
+    /// be inlined into the functions defined after it.
+    ///
     /// Note: After calling this function the given `Context` will contain the compiled function.
     pub fn define_function(
         &mut self,
@@ -549,6 +639,19 @@ where
             func,
             ctx.func.display(self.backend.isa())
         );
+        let inline_threshold = self.backend.isa().flags().inline_threshold() as usize;
+        let body = if inline_threshold != 0 {
+            ctx.inline(
+                self.backend.isa(),
+                &ModuleInlineCallees {
+                    contents: &self.contents,
+                    bodies: &self.inline_bodies,
+                },
+            )?;
+            Some(ctx.func.clone())
+        } else {
+            None
+        };
         let CodeInfo { total_size, .. } = ctx.compile(self.backend.isa())?;
         let info = &self.contents.functions[func];
         if info.compiled.is_some() {
@@ -570,6 +673,16 @@ where
 
         self.contents.functions[func].compiled = compiled;
         self.functions_to_finalize.push(func);
+        if let Some(body) = body {
+            let size: usize = body
+                .layout
+                .ebbs()
+                .map(|ebb| body.layout.ebb_insts(ebb).count())
+                .sum();
+            if size <= inline_threshold {
+                self.inline_bodies.insert(func, body);
+            }
+        }
         Ok(total_size)
     }
 
@@ -587,6 +700,7 @@ where
                 data,
                 &info.decl.name,
                 info.decl.writable,
+                info.decl.tls,
                 info.decl.align,
                 data_ctx,
                 &ModuleNamespace::<B> {
@@ -71,6 +71,9 @@ fn parse_x86_cpuid(isa_builder: &mut isa::Builder) -> Result<(), &'static str> {
         if info.has_popcnt() {
             isa_builder.enable("has_popcnt").unwrap();
         }
+        if info.has_fma() {
+            isa_builder.enable("has_fma").unwrap();
+        }
         if info.has_avx() {
             isa_builder.enable("has_avx").unwrap();
         }
@@ -79,6 +82,9 @@ fn parse_x86_cpuid(isa_builder: &mut isa::Builder) -> Result<(), &'static str> {
         if info.has_bmi1() {
             isa_builder.enable("has_bmi1").unwrap();
         }
+        if info.has_avx2() {
+            isa_builder.enable("has_avx2").unwrap();
+        }
         if info.has_bmi2() {
             isa_builder.enable("has_bmi2").unwrap();
         }
@@ -2,19 +2,31 @@
 
 use crate::traps::{ObjectTrapSink, ObjectTrapSite};
 use cranelift_codegen::binemit::{
-    Addend, CodeOffset, NullStackmapSink, NullTrapSink, Reloc, RelocSink,
+    Addend, CodeOffset, FrameUnwindKind, FrameUnwindOffset, FrameUnwindSink, NullStackmapSink,
+    NullTrapSink, Reloc, RelocSink,
 };
 use cranelift_codegen::entity::SecondaryMap;
 use cranelift_codegen::isa::TargetIsa;
 use cranelift_codegen::{self, binemit, ir};
+use cranelift_debug::{DebugContext, DebugRelocTarget};
 use cranelift_module::{
-    Backend, DataContext, DataDescription, DataId, FuncId, Init, Linkage, ModuleNamespace,
-    ModuleResult,
+    Backend, DataContext, DataDescription, DataId, FuncId, Init, Linkage, ModuleError,
+    ModuleNamespace, ModuleResult,
 };
 use object::write::{Object, Relocation, SectionId, StandardSection, Symbol, SymbolId};
-use object::{RelocationEncoding, RelocationKind, SymbolKind, SymbolScope};
+use object::{RelocationEncoding, RelocationKind, SectionKind, SymbolKind, SymbolScope};
 use std::collections::HashMap;
-use target_lexicon::PointerWidth;
+use target_lexicon::{BinaryFormat, PointerWidth};
+
+// ELF x86_64 TLS relocation types, which have no generic `RelocationKind`.
+const R_X86_64_TLSGD: u32 = 19;
+const R_X86_64_GOTTPOFF: u32 = 22;
+
+// ELF AArch64 `bl` relocation, patching a 26-bit word displacement.
+const R_AARCH64_CALL26: u32 = 283;
+
+// ELF ARM `bl` relocation, patching a 24-bit word displacement.
+const R_ARM_CALL: u32 = 28;
 
 #[derive(Debug)]
 /// Setting to enable collection of traps. Setting this to `Enabled` in
@@ -33,6 +45,7 @@ pub struct ObjectBuilder {
     collect_traps: ObjectTrapCollection,
     libcall_names: Box<dyn Fn(ir::LibCall) -> String>,
     function_alignment: u64,
+    debug_comp_dir: Option<String>,
 }
 
 impl ObjectBuilder {
@@ -58,6 +71,7 @@ impl ObjectBuilder {
             collect_traps,
             libcall_names,
             function_alignment: 1,
+            debug_comp_dir: None,
         })
     }
 
@@ -66,6 +80,16 @@ impl ObjectBuilder {
         self.function_alignment = alignment;
         self
     }
+
+    /// Emit DWARF debug information for the functions, as a compilation unit whose source file is
+    /// the object's `name`, relative to `comp_dir`. Source locations are used as line numbers,
+    /// and value labels are described as variables.
+    ///
+    /// Only ELF objects are supported; this is ignored for other formats.
+    pub fn debug_info(&mut self, comp_dir: String) -> &mut Self {
+        self.debug_comp_dir = Some(comp_dir);
+        self
+    }
 }
 
 /// A `ObjectBackend` implements `Backend` and emits ".o" files using the `object` library.
@@ -81,6 +105,9 @@ pub struct ObjectBackend {
     libcall_names: Box<dyn Fn(ir::LibCall) -> String>,
     collect_traps: ObjectTrapCollection,
     function_alignment: u64,
+    eh_frame: Option<SectionId>,
+    gcc_except_table: Option<SectionId>,
+    debug_context: Option<DebugContext<SymbolId>>,
 }
 
 impl Backend for ObjectBackend {
@@ -101,6 +128,18 @@ impl Backend for ObjectBackend {
         let triple = builder.isa.triple();
         let mut object = Object::new(triple.binary_format, triple.architecture);
         object.add_file_symbol(builder.name.as_bytes().to_vec());
+        let debug_context = match builder.debug_comp_dir {
+            Some(ref comp_dir) if triple.binary_format == BinaryFormat::Elf => {
+                let producer = format!("Cranelift {}", cranelift_codegen::VERSION);
+                Some(DebugContext::new(
+                    &*builder.isa,
+                    &builder.name,
+                    comp_dir,
+                    &producer,
+                ))
+            }
+            _ => None,
+        };
         Self {
             isa: builder.isa,
             object,
@@ -111,6 +150,9 @@ impl Backend for ObjectBackend {
             libcall_names: builder.libcall_names,
             collect_traps: builder.collect_traps,
             function_alignment: builder.function_alignment,
+            eh_frame: None,
+            gcc_except_table: None,
+            debug_context,
         }
     }
 
@@ -145,12 +187,19 @@ impl Backend for ObjectBackend {
         name: &str,
         linkage: Linkage,
         _writable: bool,
+        tls: bool,
         _align: Option<u8>,
-    ) {
+    ) -> ModuleResult<()> {
+        let kind = if tls {
+            SymbolKind::Tls
+        } else {
+            SymbolKind::Data
+        };
         let (scope, weak) = translate_linkage(linkage);
 
         if let Some(data) = self.data_objects[id] {
             let symbol = self.object.symbol_mut(data);
+            symbol.kind = kind;
             symbol.scope = scope;
             symbol.weak = weak;
         } else {
@@ -158,21 +207,22 @@ impl Backend for ObjectBackend {
                 name: name.as_bytes().to_vec(),
                 value: 0,
                 size: 0,
-                kind: SymbolKind::Data,
+                kind,
                 scope,
                 weak,
                 section: None,
             });
             self.data_objects[id] = Some(symbol_id);
         }
+        Ok(())
     }
 
     fn define_function(
         &mut self,
         func_id: FuncId,
-        _name: &str,
+        name: &str,
         ctx: &cranelift_codegen::Context,
-        _namespace: &ModuleNamespace<Self>,
+        namespace: &ModuleNamespace<Self>,
         code_size: u32,
     ) -> ModuleResult<ObjectCompiledFunction> {
         let mut code: Vec<u8> = vec![0; code_size as usize];
@@ -209,6 +259,23 @@ impl Backend for ObjectBackend {
             .object
             .add_symbol_data(symbol, section, &code, self.function_alignment);
         self.traps[func_id] = trap_sink.sites;
+
+        // Describe the function's frame to unwinders. Only ELF has a generic `.eh_frame`
+        // section; Windows uses `.pdata` and `.xdata` instead.
+        if self.isa.triple().binary_format == BinaryFormat::Elf {
+            let mut unwind_sink = ObjectUnwindSink::default();
+            ctx.emit_unwind_info(&*self.isa, FrameUnwindKind::Libunwind, &mut unwind_sink);
+            if !unwind_sink.frame.is_empty() {
+                self.define_eh_frame(symbol, unwind_sink, namespace)?;
+            }
+        }
+
+        if let Some(ref mut debug_context) = self.debug_context {
+            debug_context
+                .define_function(symbol, name, ctx, &*self.isa, code_size)
+                .map_err(ModuleError::Compilation)?;
+        }
+
         Ok(ObjectCompiledFunction {
             offset,
             size: code_size,
@@ -222,6 +289,7 @@ impl Backend for ObjectBackend {
         data_id: DataId,
         _name: &str,
         writable: bool,
+        tls: bool,
         align: Option<u8>,
         data_ctx: &DataContext,
         _namespace: &ModuleNamespace<Self>,
@@ -276,7 +344,9 @@ impl Backend for ObjectBackend {
         }
 
         let symbol = self.data_objects[data_id].unwrap();
-        let section = self.object.section_id(if writable {
+        let section = self.object.section_id(if tls {
+            StandardSection::Tls
+        } else if writable {
             StandardSection::Data
         } else if relocs.is_empty() {
             StandardSection::ReadOnlyData
@@ -390,7 +460,10 @@ impl Backend for ObjectBackend {
         // Nothing to do.
     }
 
-    fn finish(self) -> ObjectProduct {
+    fn finish(mut self) -> ObjectProduct {
+        if let Some(debug_context) = self.debug_context.take() {
+            self.define_debug_sections(&debug_context);
+        }
         ObjectProduct {
             object: self.object,
             functions: self.functions,
@@ -401,6 +474,101 @@ impl Backend for ObjectBackend {
 }
 
 impl ObjectBackend {
+    /// Append the `.eh_frame` entries for the function `symbol` to the `.eh_frame` section, and
+    /// its LSDA, if any, to the `.gcc_except_table` section.
+    fn define_eh_frame(
+        &mut self,
+        symbol: SymbolId,
+        unwind: ObjectUnwindSink,
+        namespace: &ModuleNamespace<Self>,
+    ) -> ModuleResult<()> {
+        // Check every relocation before adding anything to the object.
+        let mut targets = Vec::new();
+        for (reloc, reloc_offset) in unwind.relocs {
+            targets.push((eh_frame_reloc(reloc)?, reloc_offset, symbol, 0));
+        }
+        for (reloc, reloc_offset, name) in unwind.external_relocs {
+            let symbol = self.get_symbol(namespace, &name);
+            targets.push((eh_frame_reloc(reloc)?, reloc_offset, symbol, 0));
+        }
+        if let Some((lsda, reloc, reloc_offset)) = unwind.lsda {
+            let reloc = eh_frame_reloc(reloc)?;
+            let object = &mut self.object;
+            let section = *self.gcc_except_table.get_or_insert_with(|| {
+                object.add_section(
+                    Vec::new(),
+                    b".gcc_except_table".to_vec(),
+                    SectionKind::ReadOnlyData,
+                )
+            });
+            let lsda_offset = object.append_section_data(section, &lsda, 4);
+            let section_symbol = object.section_symbol(section);
+            targets.push((reloc, reloc_offset, section_symbol, lsda_offset as Addend));
+        }
+
+        let object = &mut self.object;
+        let section = *self.eh_frame.get_or_insert_with(|| {
+            object.add_section(Vec::new(), b".eh_frame".to_vec(), SectionKind::ReadOnlyData)
+        });
+        let offset = object.append_section_data(section, &unwind.frame, 8);
+        for ((kind, encoding, size), reloc_offset, symbol, addend) in targets {
+            object
+                .add_relocation(
+                    section,
+                    Relocation {
+                        offset: offset + reloc_offset as u64,
+                        size,
+                        kind,
+                        encoding,
+                        symbol,
+                        addend,
+                    },
+                )
+                .unwrap();
+        }
+        Ok(())
+    }
+
+    /// Add the debug sections describing the functions defined in `debug_context`.
+    fn define_debug_sections(&mut self, debug_context: &DebugContext<SymbolId>) {
+        let debug_sections = debug_context.emit();
+
+        // The sections refer to each other, so they must all exist before adding relocations.
+        let mut sections = HashMap::new();
+        for debug_section in &debug_sections {
+            let name = debug_section.id.name().as_bytes().to_vec();
+            let section = self
+                .object
+                .add_section(Vec::new(), name, SectionKind::Debug);
+            self.object
+                .append_section_data(section, &debug_section.data, 1);
+            sections.insert(debug_section.id, section);
+        }
+
+        for debug_section in &debug_sections {
+            let section = sections[&debug_section.id];
+            for reloc in &debug_section.relocs {
+                let symbol = match reloc.target {
+                    DebugRelocTarget::Symbol(symbol) => symbol,
+                    DebugRelocTarget::Section(id) => self.object.section_symbol(sections[&id]),
+                };
+                self.object
+                    .add_relocation(
+                        section,
+                        Relocation {
+                            offset: u64::from(reloc.offset),
+                            size: reloc.size * 8,
+                            kind: RelocationKind::Absolute,
+                            encoding: RelocationEncoding::Generic,
+                            symbol,
+                            addend: reloc.addend,
+                        },
+                    )
+                    .unwrap();
+            }
+        }
+    }
+
     // This should only be called during finalization because it creates
     // symbols for missing libcalls.
     fn get_symbol(
@@ -454,6 +622,19 @@ fn translate_linkage(linkage: Linkage) -> (SymbolScope, bool) {
     (scope, weak)
 }
 
+/// Get the object relocation for a relocation in the `.eh_frame` entries of a function.
+fn eh_frame_reloc(reloc: Reloc) -> ModuleResult<(RelocationKind, RelocationEncoding, u8)> {
+    match reloc {
+        Reloc::Abs8 => Ok((RelocationKind::Absolute, RelocationEncoding::Generic, 64)),
+        Reloc::X86PCRel4 => Ok((RelocationKind::Relative, RelocationEncoding::Generic, 32)),
+        Reloc::X86GOTPCRel4 => Ok((RelocationKind::GotRelative, RelocationEncoding::Generic, 32)),
+        _ => Err(ModuleError::Backend(format!(
+            "unsupported relocation {} in .eh_frame",
+            reloc
+        ))),
+    }
+}
+
 #[derive(Clone)]
 pub struct ObjectCompiledFunction {
     offset: u64,
@@ -514,6 +695,41 @@ struct RelocRecord {
     addend: Addend,
 }
 
+#[derive(Default)]
+struct ObjectUnwindSink {
+    frame: Vec<u8>,
+    relocs: Vec<(Reloc, FrameUnwindOffset)>,
+    external_relocs: Vec<(Reloc, FrameUnwindOffset, ir::ExternalName)>,
+    lsda: Option<(Vec<u8>, Reloc, FrameUnwindOffset)>,
+}
+
+impl FrameUnwindSink for ObjectUnwindSink {
+    fn len(&self) -> FrameUnwindOffset {
+        self.frame.len()
+    }
+
+    fn bytes(&mut self, b: &[u8]) {
+        self.frame.extend_from_slice(b);
+    }
+
+    fn reloc(&mut self, reloc: Reloc, offset: FrameUnwindOffset) {
+        self.relocs.push((reloc, offset));
+    }
+
+    fn reloc_external(&mut self, reloc: Reloc, offset: FrameUnwindOffset, name: &ir::ExternalName) {
+        self.external_relocs.push((reloc, offset, name.clone()));
+    }
+
+    fn lsda(&mut self, lsda: &[u8], reloc: Reloc, offset: FrameUnwindOffset) {
+        self.lsda = Some((lsda.to_vec(), reloc, offset));
+    }
+
+    fn set_entry_offset(&mut self, _offset: FrameUnwindOffset) {
+        // Each function's entries are self-contained, so the linker doesn't need to know where
+        // the FDE starts.
+    }
+}
+
 #[derive(Default)]
 struct ObjectRelocSink {
     relocs: Vec<RelocRecord>,
@@ -544,6 +760,26 @@ impl RelocSink for ObjectRelocSink {
                 32,
             ),
             Reloc::X86GOTPCRel4 => (RelocationKind::GotRelative, RelocationEncoding::Generic, 32),
+            Reloc::ElfX86_64TlsGd => (
+                RelocationKind::Other(R_X86_64_TLSGD),
+                RelocationEncoding::Generic,
+                32,
+            ),
+            Reloc::ElfX86_64GotTpOff => (
+                RelocationKind::Other(R_X86_64_GOTTPOFF),
+                RelocationEncoding::Generic,
+                32,
+            ),
+            Reloc::Arm64Call => (
+                RelocationKind::Other(R_AARCH64_CALL26),
+                RelocationEncoding::Generic,
+                26,
+            ),
+            Reloc::Arm32Call => (
+                RelocationKind::Other(R_ARM_CALL),
+                RelocationEncoding::Generic,
+                24,
+            ),
             // FIXME
             _ => unimplemented!(),
         };
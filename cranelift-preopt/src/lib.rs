@@ -20,7 +20,10 @@
 )]
 #![no_std]
 
+extern crate alloc;
+
 mod constant_folding;
+mod sccp;
 
 use cranelift_codegen::{isa::TargetIsa, settings::FlagsOrIsa, CodegenResult, Context};
 
@@ -42,6 +45,7 @@ where
     FOI: Into<FlagsOrIsa<'a>>,
 {
     constant_folding::fold_constants(&mut ctx.func);
+    sccp::do_sccp(&mut ctx.func);
     ctx.verify_if(fisa)?;
     Ok(())
 }
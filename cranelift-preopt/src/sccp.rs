@@ -0,0 +1,622 @@
+//! Sparse conditional constant propagation.
+//!
+//! This is the algorithm by Wegman and Zadeck. All values start out optimistically undefined, and
+//! are only lowered to a constant or to "overdefined" when an instruction that can actually be
+//! executed computes them. Likewise, an EBB only becomes executable once a branch to it is found
+//! to be feasible, so the arguments of branches that are never taken don't prevent the parameters
+//! of their destination from being constant.
+//!
+//! Integer and boolean values are tracked as their bit pattern truncated to the width of their
+//! type, so that arithmetic wraps around exactly as it does at run time. Booleans are tracked as a
+//! single bit, whatever their width.
+
+use alloc::vec::Vec;
+use cranelift_codegen::{
+    cursor::{Cursor, FuncCursor},
+    entity::{EntitySet, SecondaryMap},
+    ir::{
+        self, condcodes::IntCC, immediates::Imm64, instructions::BranchInfo, InstBuilder,
+        InstructionData, Opcode,
+    },
+};
+
+/// What is known about the value computed by an SSA value.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum LatticeValue {
+    /// The value has not been computed by any executable instruction yet.
+    Top,
+    /// The value is always this bit pattern.
+    Const(u64),
+    /// The value can't be determined at compile time.
+    Bottom,
+}
+
+impl Default for LatticeValue {
+    fn default() -> Self {
+        LatticeValue::Top
+    }
+}
+
+impl LatticeValue {
+    fn meet(self, other: Self) -> Self {
+        use self::LatticeValue::*;
+        match (self, other) {
+            (Top, x) | (x, Top) => x,
+            (Const(a), Const(b)) if a == b => Const(a),
+            _ => Bottom,
+        }
+    }
+
+    /// Apply `f` to a constant, truncating the result to `bits`.
+    fn map(self, bits: u32, f: impl FnOnce(u64) -> Option<u64>) -> Self {
+        use self::LatticeValue::*;
+        match self {
+            Top => Top,
+            Const(x) => f(x).map_or(Bottom, |r| Const(truncate(r, bits))),
+            Bottom => Bottom,
+        }
+    }
+
+    /// Apply `f` to two constants, truncating the result to `bits`.
+    fn map2(self, other: Self, bits: u32, f: impl FnOnce(u64, u64) -> Option<u64>) -> Self {
+        use self::LatticeValue::*;
+        match (self, other) {
+            (Bottom, _) | (_, Bottom) => Bottom,
+            (Top, _) | (_, Top) => Top,
+            (Const(a), Const(b)) => f(a, b).map_or(Bottom, |r| Const(truncate(r, bits))),
+        }
+    }
+}
+
+/// Which of the outgoing edges of a conditional branch can be taken.
+enum Feasibility {
+    /// The condition hasn't been computed yet.
+    Unknown,
+    /// The branch is always taken.
+    Taken,
+    /// The branch is never taken.
+    NotTaken,
+    /// The branch may or may not be taken.
+    Both,
+}
+
+/// The number of bits tracked for a value of type `ty`, or `None` if its values aren't tracked.
+fn tracked_bits(ty: ir::Type) -> Option<u32> {
+    if ty.is_vector() {
+        None
+    } else if ty.is_bool() {
+        Some(1)
+    } else if ty.is_int() && ty.bits() <= 64 {
+        Some(u32::from(ty.bits()))
+    } else {
+        None
+    }
+}
+
+/// Keep the low `bits` bits of `x`.
+fn truncate(x: u64, bits: u32) -> u64 {
+    if bits >= 64 {
+        x
+    } else {
+        x & ((1 << bits) - 1)
+    }
+}
+
+/// Keep the low `bits` bits of the immediate `imm`.
+fn truncate_imm(imm: Imm64, bits: u32) -> u64 {
+    let imm: i64 = imm.into();
+    truncate(imm as u64, bits)
+}
+
+/// Sign-extend the low `bits` bits of `x`.
+fn sign_extend(x: u64, bits: u32) -> i64 {
+    let shift = 64 - bits;
+    ((x << shift) as i64) >> shift
+}
+
+/// Evaluate a unary operation on a value of `bits` bits.
+fn evaluate_unary(opcode: Opcode, x: u64, bits: u32) -> Option<u64> {
+    match opcode {
+        Opcode::Bnot => Some(!x),
+        Opcode::Ineg => Some(x.wrapping_neg()),
+        Opcode::Clz => Some(u64::from(x.leading_zeros() - (64 - bits))),
+        Opcode::Ctz => Some(if x == 0 {
+            u64::from(bits)
+        } else {
+            u64::from(x.trailing_zeros())
+        }),
+        Opcode::Cls => {
+            let x = sign_extend(x, bits);
+            let ones = if x < 0 { !x } else { x };
+            Some(u64::from(ones.leading_zeros() - (64 - bits) - 1))
+        }
+        Opcode::Popcnt => Some(u64::from(x.count_ones())),
+        Opcode::Bitrev => Some(x.reverse_bits() >> (64 - bits)),
+        Opcode::Bmask => Some(if x == 0 { 0 } else { !0 }),
+        Opcode::Sextend => Some(sign_extend(x, bits) as u64),
+        Opcode::Bint
+        | Opcode::Bextend
+        | Opcode::Breduce
+        | Opcode::Uextend
+        | Opcode::Ireduce
+        | Opcode::Copy => Some(x),
+        _ => None,
+    }
+}
+
+/// Evaluate a binary operation on values of `bits` bits.
+///
+/// Operations that would trap, like a division by zero, aren't evaluated.
+fn evaluate_binary(opcode: Opcode, x: u64, y: u64, bits: u32) -> Option<u64> {
+    let (sx, sy) = (sign_extend(x, bits), sign_extend(y, bits));
+    let shift = (y % u64::from(bits)) as u32;
+    match opcode {
+        Opcode::Iadd | Opcode::IaddImm => Some(x.wrapping_add(y)),
+        Opcode::Isub => Some(x.wrapping_sub(y)),
+        Opcode::IrsubImm => Some(y.wrapping_sub(x)),
+        Opcode::Imul | Opcode::ImulImm => Some(x.wrapping_mul(y)),
+        Opcode::Umulhi => Some(((u128::from(x) * u128::from(y)) >> bits) as u64),
+        Opcode::Smulhi => Some(((i128::from(sx) * i128::from(sy)) >> bits) as u64),
+        Opcode::Udiv | Opcode::UdivImm if y != 0 => Some(x / y),
+        Opcode::Urem | Opcode::UremImm if y != 0 => Some(x % y),
+        Opcode::Sdiv | Opcode::SdivImm
+            if sy != 0 && !(sy == -1 && sx == sign_extend(1 << (bits - 1), bits)) =>
+        {
+            Some((sx / sy) as u64)
+        }
+        Opcode::Srem | Opcode::SremImm if sy != 0 => Some(sx.wrapping_rem(sy) as u64),
+        Opcode::Band | Opcode::BandImm => Some(x & y),
+        Opcode::Bor | Opcode::BorImm => Some(x | y),
+        Opcode::Bxor | Opcode::BxorImm => Some(x ^ y),
+        Opcode::BandNot => Some(x & !y),
+        Opcode::BorNot => Some(x | !y),
+        Opcode::BxorNot => Some(x ^ !y),
+        Opcode::Ishl | Opcode::IshlImm => Some(x << shift),
+        Opcode::Ushr | Opcode::UshrImm => Some(x >> shift),
+        Opcode::Sshr | Opcode::SshrImm => Some((sx >> shift) as u64),
+        Opcode::Rotl | Opcode::RotlImm if shift == 0 => Some(x),
+        Opcode::Rotl | Opcode::RotlImm => Some((x << shift) | (x >> (bits - shift))),
+        Opcode::Rotr | Opcode::RotrImm if shift == 0 => Some(x),
+        Opcode::Rotr | Opcode::RotrImm => Some((x >> shift) | (x << (bits - shift))),
+        _ => None,
+    }
+}
+
+/// Evaluate an integer comparison of values of `bits` bits.
+fn evaluate_icmp(cond: IntCC, x: u64, y: u64, bits: u32) -> Option<u64> {
+    let (sx, sy) = (sign_extend(x, bits), sign_extend(y, bits));
+    let result = match cond {
+        IntCC::Equal => x == y,
+        IntCC::NotEqual => x != y,
+        IntCC::SignedLessThan => sx < sy,
+        IntCC::SignedGreaterThanOrEqual => sx >= sy,
+        IntCC::SignedGreaterThan => sx > sy,
+        IntCC::SignedLessThanOrEqual => sx <= sy,
+        IntCC::UnsignedLessThan => x < y,
+        IntCC::UnsignedGreaterThanOrEqual => x >= y,
+        IntCC::UnsignedGreaterThan => x > y,
+        IntCC::UnsignedLessThanOrEqual => x <= y,
+        IntCC::Overflow | IntCC::NotOverflow => return None,
+    };
+    Some(result as u64)
+}
+
+/// The result of the analysis of a function.
+struct Sccp {
+    /// What is known about each value.
+    values: SecondaryMap<ir::Value, LatticeValue>,
+    /// The instructions using each value.
+    users: SecondaryMap<ir::Value, Vec<ir::Inst>>,
+    /// The EBBs that can be executed.
+    executable: EntitySet<ir::Ebb>,
+    /// The instructions that can be executed.
+    reached: EntitySet<ir::Inst>,
+    ebb_worklist: Vec<ir::Ebb>,
+    inst_worklist: Vec<ir::Inst>,
+}
+
+impl Sccp {
+    fn new(func: &ir::Function) -> Self {
+        let mut users = SecondaryMap::<ir::Value, Vec<ir::Inst>>::new();
+        for ebb in func.layout.ebbs() {
+            for inst in func.layout.ebb_insts(ebb) {
+                for &arg in func.dfg.inst_args(inst) {
+                    users[func.dfg.resolve_aliases(arg)].push(inst);
+                }
+            }
+        }
+
+        Self {
+            values: SecondaryMap::new(),
+            users,
+            executable: EntitySet::new(),
+            reached: EntitySet::new(),
+            ebb_worklist: Vec::new(),
+            inst_worklist: Vec::new(),
+        }
+    }
+
+    /// Propagate the constants from the entry block until nothing changes anymore.
+    fn analyze(&mut self, func: &ir::Function) {
+        let entry = match func.layout.entry_block() {
+            Some(entry) => entry,
+            None => return,
+        };
+        self.executable.insert(entry);
+        self.ebb_worklist.push(entry);
+        for &param in func.dfg.ebb_params(entry) {
+            self.update(param, LatticeValue::Bottom);
+        }
+
+        loop {
+            if let Some(ebb) = self.ebb_worklist.pop() {
+                if let Some(inst) = func.layout.first_inst(ebb) {
+                    self.visit_from(func, inst);
+                }
+            } else if let Some(inst) = self.inst_worklist.pop() {
+                if !self.reached.contains(inst) {
+                    continue;
+                }
+                // The instructions after a branch depend on whether it is taken.
+                if func.dfg[inst].opcode().is_branch() {
+                    self.visit_from(func, inst);
+                } else {
+                    self.visit_inst(func, inst);
+                }
+            } else {
+                break;
+            }
+        }
+    }
+
+    fn get(&self, dfg: &ir::DataFlowGraph, value: ir::Value) -> LatticeValue {
+        self.values[dfg.resolve_aliases(value)]
+    }
+
+    /// Lower the lattice value of `value` to `lv`, and revisit its users if that changed it.
+    fn update(&mut self, value: ir::Value, lv: LatticeValue) {
+        let old = self.values[value];
+        let new = old.meet(lv);
+        if new != old {
+            self.values[value] = new;
+            self.inst_worklist.extend_from_slice(&self.users[value]);
+        }
+    }
+
+    /// Visit `inst` and the instructions following it in its EBB, as long as they can be reached.
+    fn visit_from(&mut self, func: &ir::Function, inst: ir::Inst) {
+        let mut next = Some(inst);
+        while let Some(inst) = next {
+            self.reached.insert(inst);
+            if !self.visit_inst(func, inst) {
+                break;
+            }
+            next = func.layout.next_inst(inst);
+        }
+    }
+
+    /// Visit `inst`, and return whether control can continue to the next instruction.
+    fn visit_inst(&mut self, func: &ir::Function, inst: ir::Inst) -> bool {
+        let dfg = &func.dfg;
+        self.visit_results(dfg, inst);
+
+        match dfg.analyze_branch(inst) {
+            BranchInfo::NotABranch => !dfg[inst].opcode().is_terminator(),
+            BranchInfo::SingleDest(dest, args) => match self.feasibility(dfg, inst) {
+                Feasibility::Unknown => false,
+                Feasibility::Taken => {
+                    self.mark_edge(dfg, dest, args);
+                    false
+                }
+                Feasibility::NotTaken => true,
+                Feasibility::Both => {
+                    self.mark_edge(dfg, dest, args);
+                    true
+                }
+            },
+            BranchInfo::Table(jt, default) => {
+                let index = match dfg[inst] {
+                    InstructionData::BranchTable { arg, .. } => self.get(dfg, arg),
+                    _ => LatticeValue::Bottom,
+                };
+                let table = func.jump_tables[jt].as_slice();
+                match index {
+                    LatticeValue::Top => {}
+                    LatticeValue::Const(i) => {
+                        let dest = table.get(i as usize).cloned().or(default);
+                        if let Some(dest) = dest {
+                            self.mark_edge(dfg, dest, &[]);
+                        }
+                    }
+                    LatticeValue::Bottom => {
+                        for &dest in table.iter().chain(default.iter()) {
+                            self.mark_edge(dfg, dest, &[]);
+                        }
+                    }
+                }
+                false
+            }
+        }
+    }
+
+    /// Compute the lattice values of the results of `inst`.
+    fn visit_results(&mut self, dfg: &ir::DataFlowGraph, inst: ir::Inst) {
+        let results = dfg.inst_results(inst);
+        let lv = match results {
+            [result] => match tracked_bits(dfg.value_type(*result)) {
+                Some(bits) => self.evaluate(dfg, inst, bits),
+                None => LatticeValue::Bottom,
+            },
+            _ => LatticeValue::Bottom,
+        };
+        for &result in results {
+            self.update(result, lv);
+        }
+    }
+
+    /// Evaluate the single result of `inst`, which has `bits` bits.
+    fn evaluate(&self, dfg: &ir::DataFlowGraph, inst: ir::Inst, bits: u32) -> LatticeValue {
+        let arg_bits = |arg| tracked_bits(dfg.value_type(arg));
+        match dfg[inst] {
+            InstructionData::UnaryImm {
+                opcode: Opcode::Iconst,
+                imm,
+            } => LatticeValue::Const(truncate_imm(imm, bits)),
+            InstructionData::UnaryBool {
+                opcode: Opcode::Bconst,
+                imm,
+            } => LatticeValue::Const(imm as u64),
+            InstructionData::Unary { opcode, arg } => match arg_bits(arg) {
+                Some(from) => self
+                    .get(dfg, arg)
+                    .map(bits, |x| evaluate_unary(opcode, x, from)),
+                None => LatticeValue::Bottom,
+            },
+            InstructionData::Binary { opcode, args } => {
+                self.get(dfg, args[0])
+                    .map2(self.get(dfg, args[1]), bits, |x, y| {
+                        evaluate_binary(opcode, x, y, bits)
+                    })
+            }
+            InstructionData::BinaryImm { opcode, arg, imm } => {
+                let y = truncate_imm(imm, bits);
+                self.get(dfg, arg)
+                    .map(bits, |x| evaluate_binary(opcode, x, y, bits))
+            }
+            InstructionData::IntCompare { cond, args, .. } => match arg_bits(args[0]) {
+                Some(from) => self
+                    .get(dfg, args[0])
+                    .map2(self.get(dfg, args[1]), bits, |x, y| {
+                        evaluate_icmp(cond, x, y, from)
+                    }),
+                None => LatticeValue::Bottom,
+            },
+            InstructionData::IntCompareImm { cond, arg, imm, .. } => match arg_bits(arg) {
+                Some(from) => {
+                    let y = truncate_imm(imm, from);
+                    self.get(dfg, arg)
+                        .map(bits, |x| evaluate_icmp(cond, x, y, from))
+                }
+                None => LatticeValue::Bottom,
+            },
+            InstructionData::Ternary {
+                opcode: Opcode::Select,
+                args,
+            } => match self.get(dfg, args[0]) {
+                LatticeValue::Top => LatticeValue::Top,
+                LatticeValue::Const(c) => self.get(dfg, args[if c != 0 { 1 } else { 2 }]),
+                LatticeValue::Bottom => self.get(dfg, args[1]).meet(self.get(dfg, args[2])),
+            },
+            _ => LatticeValue::Bottom,
+        }
+    }
+
+    /// Determine whether the single-destination branch `inst` can be taken.
+    fn feasibility(&self, dfg: &ir::DataFlowGraph, inst: ir::Inst) -> Feasibility {
+        let cond = match dfg[inst] {
+            InstructionData::Jump { .. } => return Feasibility::Taken,
+            InstructionData::Branch {
+                opcode, ref args, ..
+            } => {
+                let branch_if_zero = opcode == Opcode::Brz;
+                self.get(dfg, args.as_slice(&dfg.value_lists)[0])
+                    .map(1, |c| Some(((c == 0) == branch_if_zero) as u64))
+            }
+            InstructionData::BranchIcmp { cond, ref args, .. } => {
+                let args = args.as_slice(&dfg.value_lists);
+                match tracked_bits(dfg.value_type(args[0])) {
+                    Some(bits) => self
+                        .get(dfg, args[0])
+                        .map2(self.get(dfg, args[1]), 1, |x, y| {
+                            evaluate_icmp(cond, x, y, bits)
+                        }),
+                    None => LatticeValue::Bottom,
+                }
+            }
+            _ => LatticeValue::Bottom,
+        };
+        match cond {
+            LatticeValue::Top => Feasibility::Unknown,
+            LatticeValue::Const(0) => Feasibility::NotTaken,
+            LatticeValue::Const(_) => Feasibility::Taken,
+            LatticeValue::Bottom => Feasibility::Both,
+        }
+    }
+
+    /// Mark the edge to `dest` passing `args` as feasible.
+    ///
+    /// Parameters without a matching argument, like those of a landing pad, are overdefined.
+    fn mark_edge(&mut self, dfg: &ir::DataFlowGraph, dest: ir::Ebb, args: &[ir::Value]) {
+        if self.executable.insert(dest) {
+            self.ebb_worklist.push(dest);
+        }
+        for (i, &param) in dfg.ebb_params(dest).iter().enumerate() {
+            let lv = match args.get(i) {
+                Some(&arg) => self.get(dfg, arg),
+                None => LatticeValue::Bottom,
+            };
+            self.update(param, lv);
+        }
+    }
+}
+
+/// A change to make to a branch once the analysis is done.
+enum BranchChange {
+    /// Replace the branch with a jump, and remove the instructions following it.
+    Jump(ir::Ebb, Vec<ir::Value>),
+    /// Remove the branch.
+    Remove,
+}
+
+/// Propagate constants through the function, folding the instructions and EBB parameters that
+/// always compute the same value, and the branches that are always or never taken.
+///
+/// Like `fold_constants`, this doesn't remove the instructions that become unused, nor the EBBs
+/// that become unreachable.
+pub fn do_sccp(func: &mut ir::Function) {
+    let mut sccp = Sccp::new(func);
+    sccp.analyze(func);
+
+    let mut constants = Vec::new();
+    let mut branches = Vec::new();
+    let mut params = Vec::new();
+    for ebb in func.layout.ebbs() {
+        if !sccp.executable.contains(ebb) {
+            continue;
+        }
+        if Some(ebb) != func.layout.entry_block() {
+            for (i, &param) in func.dfg.ebb_params(ebb).iter().enumerate() {
+                if let LatticeValue::Const(c) = sccp.values[param] {
+                    params.push((ebb, i, param, c));
+                }
+            }
+        }
+        for inst in func.layout.ebb_insts(ebb) {
+            if !sccp.reached.contains(inst) {
+                break;
+            }
+            let dfg = &func.dfg;
+            match dfg.analyze_branch(inst) {
+                BranchInfo::NotABranch => {
+                    if let [result] = *dfg.inst_results(inst) {
+                        let opcode = dfg[inst].opcode();
+                        if let LatticeValue::Const(c) = sccp.values[result] {
+                            if opcode != Opcode::Iconst && opcode != Opcode::Bconst {
+                                constants.push((inst, dfg.value_type(result), c));
+                            }
+                        }
+                    }
+                }
+                BranchInfo::SingleDest(dest, args) => match dfg[inst] {
+                    InstructionData::Branch { .. } | InstructionData::BranchIcmp { .. } => {
+                        match sccp.feasibility(dfg, inst) {
+                            Feasibility::Taken => {
+                                branches.push((inst, BranchChange::Jump(dest, args.to_vec())))
+                            }
+                            Feasibility::NotTaken => branches.push((inst, BranchChange::Remove)),
+                            _ => {}
+                        }
+                    }
+                    _ => {}
+                },
+                BranchInfo::Table(jt, default) => {
+                    if let InstructionData::BranchTable { arg, .. } = dfg[inst] {
+                        if let LatticeValue::Const(i) = sccp.get(dfg, arg) {
+                            let table = func.jump_tables[jt].as_slice();
+                            if let Some(dest) = table.get(i as usize).cloned().or(default) {
+                                branches.push((inst, BranchChange::Jump(dest, Vec::new())));
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    for (inst, ty, c) in constants {
+        if ty.is_bool() {
+            func.dfg.replace(inst).bconst(ty, c != 0);
+        } else {
+            let imm = sign_extend(c, u32::from(ty.bits()));
+            func.dfg.replace(inst).iconst(ty, imm);
+        }
+    }
+
+    for (inst, change) in branches {
+        match change {
+            BranchChange::Jump(dest, args) => {
+                func.dfg.replace(inst).jump(dest, &args);
+                remove_following_insts(func, inst);
+            }
+            BranchChange::Remove => func.layout.remove_inst(inst),
+        }
+    }
+
+    remove_constant_params(func, &params);
+}
+
+/// Remove the instructions following `inst` in its EBB, which can't be reached anymore.
+///
+/// If any of them defines a value, the unreachable code may still refer to it, so they are moved
+/// to a new unreachable EBB instead.
+fn remove_following_insts(func: &mut ir::Function, inst: ir::Inst) {
+    let next = match func.layout.next_inst(inst) {
+        Some(next) => next,
+        None => return,
+    };
+    let mut defines_values = false;
+    let mut cursor = Some(next);
+    while let Some(inst) = cursor {
+        defines_values |= !func.dfg.inst_results(inst).is_empty();
+        cursor = func.layout.next_inst(inst);
+    }
+
+    if defines_values {
+        let ebb = func.dfg.make_ebb();
+        func.layout.split_ebb(ebb, next);
+    } else {
+        while let Some(next) = func.layout.next_inst(inst) {
+            func.layout.remove_inst(next);
+        }
+    }
+}
+
+/// Replace the constant EBB parameters in `params` with constant instructions, and remove the
+/// matching arguments from the branches to their EBB.
+fn remove_constant_params(func: &mut ir::Function, params: &[(ir::Ebb, usize, ir::Value, u64)]) {
+    if params.is_empty() {
+        return;
+    }
+
+    let mut branches = Vec::new();
+    for ebb in func.layout.ebbs() {
+        for inst in func.layout.ebb_insts(ebb) {
+            if let BranchInfo::SingleDest(dest, _) = func.dfg.analyze_branch(inst) {
+                branches.push((inst, dest));
+            }
+        }
+    }
+
+    // Remove the arguments starting from the last one, so the indexes stay valid.
+    for &(inst, dest) in &branches {
+        let num_fixed = func.dfg.inst_fixed_args(inst).len();
+        let mut args = func.dfg[inst].take_value_list().unwrap();
+        for &(_, i, _, _) in params.iter().rev().filter(|p| p.0 == dest) {
+            args.remove(num_fixed + i, &mut func.dfg.value_lists);
+        }
+        func.dfg[inst].put_value_list(args);
+    }
+
+    let mut pos = FuncCursor::new(func);
+    for &(ebb, _, param, c) in params.iter().rev() {
+        pos.func.dfg.remove_ebb_param(param);
+        pos.goto_first_insertion_point(ebb);
+        let ty = pos.func.dfg.value_type(param);
+        if ty.is_bool() {
+            pos.ins().with_result(param).bconst(ty, c != 0);
+        } else {
+            let imm = sign_extend(c, u32::from(ty.bits()));
+            pos.ins().with_result(param).iconst(ty, imm);
+        }
+    }
+}
@@ -14,10 +14,10 @@ use cranelift_codegen::ir::instructions::{InstructionData, InstructionFormat, Va
 use cranelift_codegen::ir::types::INVALID;
 use cranelift_codegen::ir::types::*;
 use cranelift_codegen::ir::{
-    AbiParam, ArgumentExtension, ArgumentLoc, ConstantData, Ebb, ExtFuncData, ExternalName,
-    FuncRef, Function, GlobalValue, GlobalValueData, Heap, HeapData, HeapStyle, JumpTable,
-    JumpTableData, MemFlags, Opcode, SigRef, Signature, StackSlot, StackSlotData, StackSlotKind,
-    Table, TableData, Type, Value, ValueLoc,
+    AbiParam, ArgumentExtension, ArgumentLoc, ArgumentPurpose, ConstantData, Ebb, ExtFuncData,
+    ExternalName, FuncRef, Function, GlobalValue, GlobalValueData, Heap, HeapData, HeapStyle,
+    JumpTable, JumpTableData, MemFlags, Opcode, SigRef, Signature, StackSlot, StackSlotData,
+    StackSlotKind, StructLayout, Table, TableData, Type, Value, ValueLoc,
 };
 use cranelift_codegen::isa::{self, CallConv, Encoding, RegUnit, TargetIsa};
 use cranelift_codegen::packed_option::ReservedValue;
@@ -201,6 +201,7 @@ impl<'a> Context<'a> {
                 name: ExternalName::testcase(""),
                 offset: Imm64::new(0),
                 colocated: false,
+                tls: false,
             });
         }
         self.function.global_values[gv] = data;
@@ -1176,24 +1177,44 @@ impl<'a> Parser<'a> {
         let mut sig = Signature::new(self.default_calling_convention);
 
         self.match_token(Token::LPar, "expected function signature: ( args... )")?;
-        // signature ::=  "(" * [abi-param-list] ")" ["->" retlist] [callconv]
+        // signature ::=  "(" * [param-list] ")" ["->" retlist] [callconv]
         if self.token() != Some(Token::RPar) {
-            sig.params = self.parse_abi_param_list(unique_isa)?;
+            // param-list ::= * param-or-ellipsis { "," param-or-ellipsis }
+            // param-or-ellipsis ::= abi-param | "..."
+            loop {
+                if self.token() == Some(Token::Dot) {
+                    if sig.is_variadic() {
+                        return err!(self.loc, "variadic signature has more than one '...'");
+                    }
+                    for _ in 0..3 {
+                        self.match_token(Token::Dot, "expected '...' in variadic signature")?;
+                    }
+                    sig.make_variadic();
+                } else {
+                    sig.params.push(self.parse_abi_param(unique_isa)?);
+                }
+                if !self.optional(Token::Comma) {
+                    break;
+                }
+            }
         }
         self.match_token(Token::RPar, "expected ')' after function arguments")?;
         if self.optional(Token::Arrow) {
             sig.returns = self.parse_abi_param_list(unique_isa)?;
         }
 
-        // The calling convention is optional.
-        if let Some(Token::Identifier(text)) = self.token() {
-            match text.parse() {
+        // The calling convention is optional. A signature at the end of a function declaration
+        // can be followed by a `personality` declaration, which isn't one.
+        match self.token() {
+            Some(Token::Identifier("personality")) => {}
+            Some(Token::Identifier(text)) => match text.parse() {
                 Ok(cc) => {
                     self.consume();
                     sig.call_conv = cc;
                 }
                 _ => return err!(self.loc, "unknown calling convention: {}", text),
-            }
+            },
+            _ => {}
         }
 
         Ok(sig)
@@ -1231,6 +1252,16 @@ impl<'a> Parser<'a> {
             match s {
                 "uext" => arg.extension = ArgumentExtension::Uext,
                 "sext" => arg.extension = ArgumentExtension::Sext,
+                "sarg" => {
+                    self.consume();
+                    arg.purpose = ArgumentPurpose::StructArgument(self.parse_struct_layout()?);
+                    continue;
+                }
+                "sref" => {
+                    self.consume();
+                    arg.purpose = ArgumentPurpose::StructReference(self.parse_struct_layout()?);
+                    continue;
+                }
                 _ => {
                     if let Ok(purpose) = s.parse() {
                         arg.purpose = purpose;
@@ -1248,6 +1279,27 @@ impl<'a> Parser<'a> {
         Ok(arg)
     }
 
+    // Parse the layout of a struct argument.
+    //
+    // struct-layout ::= "(" uimm32 "," uimm32 [ "," uimm8 ] ")"
+    fn parse_struct_layout(&mut self) -> ParseResult<StructLayout> {
+        self.match_token(Token::LPar, "expected '(' before struct layout")?;
+        let size = self.match_uimm32("expected struct size")?.into();
+        self.match_token(Token::Comma, "expected ',' after struct size")?;
+        let align = self.match_uimm32("expected struct alignment")?.into();
+        let float_eightbytes = if self.optional(Token::Comma) {
+            self.match_uimm8("expected mask of float eightbytes")?
+        } else {
+            0
+        };
+        self.match_token(Token::RPar, "expected ')' after struct layout")?;
+        Ok(StructLayout {
+            size,
+            align,
+            float_eightbytes,
+        })
+    }
+
     // Parse an argument location specifier; either a register or a byte offset into the stack.
     fn parse_argument_location(
         &mut self,
@@ -1339,12 +1391,32 @@ impl<'a> Parser<'a> {
                     self.parse_jump_table_decl()
                         .and_then(|(jt, dat)| ctx.add_jt(jt, dat, self.loc))
                 }
+                Some(Token::Identifier("personality")) => {
+                    self.start_gathering_comments();
+                    self.parse_personality_decl(ctx)
+                }
                 // More to come..
                 _ => return Ok(()),
             }?;
         }
     }
 
+    // Parse the personality routine declaration.
+    //
+    // personality-decl ::= * "personality" "=" FuncRef(fnref)
+    fn parse_personality_decl(&mut self, ctx: &mut Context) -> ParseResult<()> {
+        self.consume();
+        self.match_token(Token::Equal, "expected '=' in personality declaration")?;
+        let fn_ = self.match_fn("expected function reference")?;
+        ctx.check_fn(fn_, self.loc)?;
+        ctx.function.personality = Some(fn_);
+
+        // Collect any trailing comments.
+        self.token();
+        self.claim_gathered_comments(AnyEntity::Function);
+        Ok(())
+    }
+
     // Parse a stack slot decl.
     //
     // stack-slot-decl ::= * StackSlot(ss) "=" stack-slot-kind Bytes {"," stack-slot-flag}
@@ -1391,7 +1463,7 @@ impl<'a> Parser<'a> {
     // global-val-desc ::= "vmctx"
     //                   | "load" "." type "notrap" "aligned" GlobalValue(base) [offset]
     //                   | "iadd_imm" "(" GlobalValue(base) ")" imm64
-    //                   | "symbol" ["colocated"] name + imm64
+    //                   | "symbol" ["colocated"] ["tls"] name + imm64
     //
     fn parse_global_value_decl(&mut self) -> ParseResult<(GlobalValue, GlobalValueData)> {
         let gv = self.match_gv("expected global value number: gv«n»")?;
@@ -1440,12 +1512,14 @@ impl<'a> Parser<'a> {
             }
             "symbol" => {
                 let colocated = self.optional(Token::Identifier("colocated"));
+                let tls = self.optional(Token::Identifier("tls"));
                 let name = self.parse_external_name()?;
                 let offset = self.optional_offset_imm64()?;
                 GlobalValueData::Symbol {
                     name,
                     offset,
                     colocated,
+                    tls,
                 }
             }
             other => return err!(self.loc, "Unknown global value kind '{}'", other),
@@ -2579,6 +2653,38 @@ impl<'a> Parser<'a> {
                     args: args.into_value_list(&[callee], &mut ctx.function.dfg.value_lists),
                 }
             }
+            InstructionFormat::Invoke => {
+                let func_ref = self.match_fn("expected function reference")?;
+                ctx.check_fn(func_ref, self.loc)?;
+                self.match_token(Token::LPar, "expected '(' before arguments")?;
+                let args = self.parse_value_list()?;
+                self.match_token(Token::RPar, "expected ')' after arguments")?;
+                self.match_token(Token::Comma, "expected ',' between operands")?;
+                let ebb_num = self.match_ebb("expected landing pad EBB")?;
+                InstructionData::Invoke {
+                    opcode,
+                    func_ref,
+                    destination: ebb_num,
+                    args: args.into_value_list(&[], &mut ctx.function.dfg.value_lists),
+                }
+            }
+            InstructionFormat::InvokeIndirect => {
+                let sig_ref = self.match_sig("expected signature reference")?;
+                ctx.check_sig(sig_ref, self.loc)?;
+                self.match_token(Token::Comma, "expected ',' between operands")?;
+                let callee = self.match_value("expected SSA value callee operand")?;
+                self.match_token(Token::LPar, "expected '(' before arguments")?;
+                let args = self.parse_value_list()?;
+                self.match_token(Token::RPar, "expected ')' after arguments")?;
+                self.match_token(Token::Comma, "expected ',' between operands")?;
+                let ebb_num = self.match_ebb("expected landing pad EBB")?;
+                InstructionData::InvokeIndirect {
+                    opcode,
+                    sig_ref,
+                    destination: ebb_num,
+                    args: args.into_value_list(&[callee], &mut ctx.function.dfg.value_lists),
+                }
+            }
             InstructionFormat::FuncAddr => {
                 let func_ref = self.match_fn("expected function reference")?;
                 ctx.check_fn(func_ref, self.loc)?;
@@ -2671,6 +2777,61 @@ impl<'a> Parser<'a> {
                 }
             }
 
+            InstructionFormat::AtomicLoad => {
+                let ordering = self.match_enum("expected atomic ordering")?;
+                let flags = self.optional_memflags();
+                let addr = self.match_value("expected SSA value address")?;
+                InstructionData::AtomicLoad {
+                    opcode,
+                    ordering,
+                    flags,
+                    arg: addr,
+                }
+            }
+            InstructionFormat::AtomicStore => {
+                let ordering = self.match_enum("expected atomic ordering")?;
+                let flags = self.optional_memflags();
+                let arg = self.match_value("expected SSA value operand")?;
+                self.match_token(Token::Comma, "expected ',' between operands")?;
+                let addr = self.match_value("expected SSA value address")?;
+                InstructionData::AtomicStore {
+                    opcode,
+                    ordering,
+                    flags,
+                    args: [arg, addr],
+                }
+            }
+            InstructionFormat::AtomicRmw => {
+                let op = self.match_enum("expected atomic read-modify-write operation")?;
+                let ordering = self.match_enum("expected atomic ordering")?;
+                let flags = self.optional_memflags();
+                let addr = self.match_value("expected SSA value address")?;
+                self.match_token(Token::Comma, "expected ',' between operands")?;
+                let arg = self.match_value("expected SSA value operand")?;
+                InstructionData::AtomicRmw {
+                    opcode,
+                    op,
+                    ordering,
+                    flags,
+                    args: [addr, arg],
+                }
+            }
+            InstructionFormat::AtomicCas => {
+                let ordering = self.match_enum("expected atomic ordering")?;
+                let flags = self.optional_memflags();
+                let addr = self.match_value("expected SSA value address")?;
+                self.match_token(Token::Comma, "expected ',' between operands")?;
+                let expected = self.match_value("expected SSA value operand")?;
+                self.match_token(Token::Comma, "expected ',' between operands")?;
+                let replacement = self.match_value("expected SSA value operand")?;
+                InstructionData::AtomicCas {
+                    opcode,
+                    ordering,
+                    flags,
+                    args: [addr, expected, replacement],
+                }
+            }
+
             InstructionFormat::StoreComplex => {
                 let flags = self.optional_memflags();
                 let src = self.match_value("expected SSA value operand")?;
@@ -2742,6 +2903,10 @@ impl<'a> Parser<'a> {
                 let code = self.match_enum("expected trap code")?;
                 InstructionData::Trap { opcode, code }
             }
+            InstructionFormat::Fence => {
+                let ordering = self.match_enum("expected atomic ordering")?;
+                InstructionData::Fence { opcode, ordering }
+            }
             InstructionFormat::CondTrap => {
                 let arg = self.match_value("expected SSA value operand")?;
                 self.match_token(Token::Comma, "expected ',' between operands")?;
@@ -2786,7 +2951,7 @@ mod tests {
     use cranelift_codegen::ir::entities::AnyEntity;
     use cranelift_codegen::ir::types;
     use cranelift_codegen::ir::StackSlotKind;
-    use cranelift_codegen::ir::{ArgumentExtension, ArgumentPurpose};
+    use cranelift_codegen::ir::{ArgumentExtension, ArgumentPurpose, StructLayout};
     use cranelift_codegen::isa::CallConv;
 
     #[test]
@@ -2806,6 +2971,26 @@ mod tests {
         assert!(!is_warning);
     }
 
+    #[test]
+    fn struct_argument() {
+        let mut p = Parser::new("i64 sarg(12, 4, 0x2) i64 sref(24, 8)");
+        let arg = p.parse_abi_param(None).unwrap();
+        assert_eq!(
+            arg.purpose,
+            ArgumentPurpose::StructArgument(StructLayout {
+                size: 12,
+                align: 4,
+                float_eightbytes: 2,
+            })
+        );
+        let arg = p.parse_abi_param(None).unwrap();
+        assert_eq!(
+            arg.purpose,
+            ArgumentPurpose::StructReference(StructLayout::new(24, 8))
+        );
+        assert_eq!(arg.to_string(), "i64 sref(24, 8)");
+    }
+
     #[test]
     fn aliases() {
         let (func, details) = Parser::new(
@@ -2848,6 +3033,33 @@ mod tests {
         );
         assert_eq!(sig2.call_conv, CallConv::BaldrdashSystemV);
 
+        let sig3 = Parser::new("(i64 vmctx) custom1")
+            .parse_signature(None)
+            .unwrap();
+        assert_eq!(sig3.to_string(), "(i64 vmctx) custom1");
+        assert_eq!(sig3.call_conv, CallConv::Custom(1));
+
+        let sig4 = Parser::new("(i64, ..., f64) -> i32 system_v")
+            .parse_signature(None)
+            .unwrap();
+        assert_eq!(sig4.to_string(), "(i64, ..., f64) -> i32 system_v");
+        assert_eq!(sig4.params.len(), 2);
+        assert_eq!(sig4.fixed_params(), Some(1));
+        assert_eq!(
+            Parser::new("(...) system_v")
+                .parse_signature(None)
+                .unwrap()
+                .fixed_params(),
+            Some(0)
+        );
+        assert_eq!(
+            Parser::new("(i64, ..., ...)")
+                .parse_signature(None)
+                .unwrap_err()
+                .to_string(),
+            "1: variadic signature has more than one '...'"
+        );
+
         // Old-style signature without a calling convention.
         assert_eq!(
             Parser::new("()").parse_signature(None).unwrap().to_string(),
@@ -153,6 +153,18 @@ pub enum SerInstData {
         args: Vec<String>,
         sig_ref: String,
     },
+    Invoke {
+        opcode: String,
+        args: Vec<String>,
+        func_ref: String,
+        destination: String,
+    },
+    InvokeIndirect {
+        opcode: String,
+        args: Vec<String>,
+        sig_ref: String,
+        destination: String,
+    },
     FuncAddr {
         opcode: String,
         func_ref: String,
@@ -181,6 +193,31 @@ pub enum SerInstData {
         flags: String,
         offset: String,
     },
+    AtomicLoad {
+        opcode: String,
+        arg: String,
+        ordering: String,
+        flags: String,
+    },
+    AtomicStore {
+        opcode: String,
+        args: [String; 2],
+        ordering: String,
+        flags: String,
+    },
+    AtomicRmw {
+        opcode: String,
+        args: [String; 2],
+        op: String,
+        ordering: String,
+        flags: String,
+    },
+    AtomicCas {
+        opcode: String,
+        args: [String; 3],
+        ordering: String,
+        flags: String,
+    },
     StackLoad {
         opcode: String,
         stack_slot: String,
@@ -235,6 +272,10 @@ pub enum SerInstData {
         opcode: String,
         code: String,
     },
+    Fence {
+        opcode: String,
+        ordering: String,
+    },
     CondTrap {
         opcode: String,
         arg: String,
@@ -559,6 +600,42 @@ pub fn get_inst_data(inst_index: Inst, func: &Function) -> SerInstData {
                 sig_ref: sig_ref.to_string(),
             }
         }
+        InstructionData::Invoke {
+            opcode,
+            ref args,
+            func_ref,
+            destination,
+        } => {
+            let mut hold_args = Vec::new();
+            let args_iter = args.as_slice(&func.dfg.value_lists);
+            for arg in args_iter {
+                hold_args.push(arg.to_string());
+            }
+            SerInstData::Invoke {
+                opcode: opcode.to_string(),
+                args: hold_args,
+                func_ref: func_ref.to_string(),
+                destination: destination.to_string(),
+            }
+        }
+        InstructionData::InvokeIndirect {
+            opcode,
+            ref args,
+            sig_ref,
+            destination,
+        } => {
+            let mut hold_args = Vec::new();
+            let args_iter = args.as_slice(&func.dfg.value_lists);
+            for arg in args_iter {
+                hold_args.push(arg.to_string());
+            }
+            SerInstData::InvokeIndirect {
+                opcode: opcode.to_string(),
+                args: hold_args,
+                sig_ref: sig_ref.to_string(),
+                destination: destination.to_string(),
+            }
+        }
         InstructionData::FuncAddr { opcode, func_ref } => SerInstData::FuncAddr {
             opcode: opcode.to_string(),
             func_ref: func_ref.to_string(),
@@ -624,6 +701,65 @@ pub fn get_inst_data(inst_index: Inst, func: &Function) -> SerInstData {
                 offset: offset.to_string(),
             }
         }
+        InstructionData::AtomicLoad {
+            opcode,
+            arg,
+            ordering,
+            flags,
+        } => SerInstData::AtomicLoad {
+            opcode: opcode.to_string(),
+            arg: arg.to_string(),
+            ordering: ordering.to_string(),
+            flags: flags.to_string(),
+        },
+        InstructionData::AtomicStore {
+            opcode,
+            args,
+            ordering,
+            flags,
+        } => {
+            let hold_args = [args[0].to_string(), args[1].to_string()];
+            SerInstData::AtomicStore {
+                opcode: opcode.to_string(),
+                args: hold_args,
+                ordering: ordering.to_string(),
+                flags: flags.to_string(),
+            }
+        }
+        InstructionData::AtomicRmw {
+            opcode,
+            args,
+            op,
+            ordering,
+            flags,
+        } => {
+            let hold_args = [args[0].to_string(), args[1].to_string()];
+            SerInstData::AtomicRmw {
+                opcode: opcode.to_string(),
+                args: hold_args,
+                op: op.to_string(),
+                ordering: ordering.to_string(),
+                flags: flags.to_string(),
+            }
+        }
+        InstructionData::AtomicCas {
+            opcode,
+            args,
+            ordering,
+            flags,
+        } => {
+            let hold_args = [
+                args[0].to_string(),
+                args[1].to_string(),
+                args[2].to_string(),
+            ];
+            SerInstData::AtomicCas {
+                opcode: opcode.to_string(),
+                args: hold_args,
+                ordering: ordering.to_string(),
+                flags: flags.to_string(),
+            }
+        }
         InstructionData::StackLoad {
             opcode,
             stack_slot,
@@ -712,6 +848,10 @@ pub fn get_inst_data(inst_index: Inst, func: &Function) -> SerInstData {
             opcode: opcode.to_string(),
             code: code.to_string(),
         },
+        InstructionData::Fence { opcode, ordering } => SerInstData::Fence {
+            opcode: opcode.to_string(),
+            ordering: ordering.to_string(),
+        },
         InstructionData::CondTrap { opcode, arg, code } => SerInstData::CondTrap {
             opcode: opcode.to_string(),
             arg: arg.to_string(),
@@ -908,3 +1048,33 @@ impl SerObj {
         Self::create_new(func_vec)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cranelift_reader::parse_functions;
+
+    #[test]
+    fn load_with_region() {
+        let funcs = parse_functions(
+            "function %f(i64) -> i32 {
+             ebb0(v0: i64):
+                 v1 = load.i32 notrap aligned heap v0+8
+                 return v1
+             }",
+        )
+        .unwrap();
+        let func = &funcs[0];
+        let ebb = func.layout.entry_block().unwrap();
+        let inst = func.layout.first_inst(ebb).unwrap();
+        assert_eq!(
+            get_inst_data(inst, func),
+            SerInstData::Load {
+                opcode: "load".to_string(),
+                arg: "v0".to_string(),
+                flags: " notrap aligned heap".to_string(),
+                offset: "+8".to_string(),
+            }
+        );
+    }
+}
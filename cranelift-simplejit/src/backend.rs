@@ -1,14 +1,16 @@
 //! Defines `SimpleJITBackend`.
 
 use crate::memory::Memory;
+use crate::unwind::UnwindRegistry;
 use cranelift_codegen::binemit::{
-    Addend, CodeOffset, NullTrapSink, Reloc, RelocSink, Stackmap, StackmapSink,
+    Addend, CodeOffset, FrameUnwindKind, FrameUnwindOffset, FrameUnwindSink, NullTrapSink, Reloc,
+    RelocSink, Stackmap, StackmapSink,
 };
 use cranelift_codegen::isa::TargetIsa;
 use cranelift_codegen::{self, ir, settings};
 use cranelift_module::{
-    Backend, DataContext, DataDescription, DataId, FuncId, Init, Linkage, ModuleNamespace,
-    ModuleResult,
+    Backend, DataContext, DataDescription, DataId, FuncId, Init, Linkage, ModuleError,
+    ModuleNamespace, ModuleResult,
 };
 use cranelift_native;
 #[cfg(not(windows))]
@@ -153,6 +155,7 @@ pub struct SimpleJITMemoryHandle {
     code: Memory,
     readonly: Memory,
     writable: Memory,
+    unwind: UnwindRegistry,
 }
 
 impl SimpleJITBackend {
@@ -223,6 +226,7 @@ impl<'simple_jit_backend> Backend for SimpleJITBackend {
             code: Memory::new(),
             readonly: Memory::new(),
             writable: Memory::new(),
+            unwind: UnwindRegistry::new(),
         };
 
         Self {
@@ -244,12 +248,20 @@ impl<'simple_jit_backend> Backend for SimpleJITBackend {
     fn declare_data(
         &mut self,
         _id: DataId,
-        _name: &str,
+        name: &str,
         _linkage: Linkage,
         _writable: bool,
+        tls: bool,
         _align: Option<u8>,
-    ) {
+    ) -> ModuleResult<()> {
+        if tls {
+            return Err(ModuleError::Backend(format!(
+                "SimpleJIT doesn't yet support TLS data objects: {}",
+                name
+            )));
+        }
         // Nothing to do.
+        Ok(())
     }
 
     fn define_function(
@@ -257,7 +269,7 @@ impl<'simple_jit_backend> Backend for SimpleJITBackend {
         _id: FuncId,
         name: &str,
         ctx: &cranelift_codegen::Context,
-        _namespace: &ModuleNamespace<Self>,
+        namespace: &ModuleNamespace<Self>,
         code_size: u32,
     ) -> ModuleResult<Self::CompiledFunction> {
         let size = code_size as usize;
@@ -292,6 +304,45 @@ impl<'simple_jit_backend> Backend for SimpleJITBackend {
             )
         };
 
+        let mut unwind_sink = SimpleJITUnwindSink::new();
+        ctx.emit_unwind_info(&*self.isa, FrameUnwindKind::Libunwind, &mut unwind_sink);
+        if !unwind_sink.frame.is_empty() {
+            let SimpleJITUnwindSink {
+                mut frame,
+                entry,
+                relocs,
+                external_relocs,
+                lsda,
+            } = unwind_sink;
+            for (reloc, offset) in relocs {
+                match reloc {
+                    Reloc::Abs8 => {
+                        frame[offset..offset + 8].copy_from_slice(&(ptr as u64).to_le_bytes())
+                    }
+                    _ => return Err(unsupported_unwind_reloc(reloc)),
+                }
+            }
+            for (reloc, offset, name) in external_relocs {
+                match reloc {
+                    Reloc::Abs8 => {
+                        let what = self.get_definition(namespace, &name);
+                        frame[offset..offset + 8].copy_from_slice(&(what as u64).to_le_bytes())
+                    }
+                    _ => return Err(unsupported_unwind_reloc(reloc)),
+                }
+            }
+            if let Some((lsda, reloc, offset)) = lsda {
+                match reloc {
+                    Reloc::Abs8 => {
+                        let what = self.memory.unwind.add_lsda(lsda);
+                        frame[offset..offset + 8].copy_from_slice(&(what as u64).to_le_bytes())
+                    }
+                    _ => return Err(unsupported_unwind_reloc(reloc)),
+                }
+            }
+            self.memory.unwind.register(frame, entry);
+        }
+
         Ok(Self::CompiledFunction {
             code: ptr,
             size,
@@ -302,12 +353,20 @@ impl<'simple_jit_backend> Backend for SimpleJITBackend {
     fn define_data(
         &mut self,
         _id: DataId,
-        _name: &str,
+        name: &str,
         writable: bool,
+        tls: bool,
         align: Option<u8>,
         data: &DataContext,
         _namespace: &ModuleNamespace<Self>,
     ) -> ModuleResult<Self::CompiledData> {
+        if tls {
+            return Err(ModuleError::Backend(format!(
+                "SimpleJIT doesn't yet support TLS data objects: {}",
+                name
+            )));
+        }
+
         let &DataDescription {
             ref init,
             ref function_decls,
@@ -397,7 +456,7 @@ impl<'simple_jit_backend> Backend for SimpleJITBackend {
         func: &Self::CompiledFunction,
         namespace: &ModuleNamespace<Self>,
     ) -> Self::FinalizedFunction {
-        use std::ptr::write_unaligned;
+        use std::ptr::{read_unaligned, write_unaligned};
 
         for &RelocRecord {
             reloc,
@@ -435,6 +494,29 @@ impl<'simple_jit_backend> Backend for SimpleJITBackend {
                     };
                 }
                 Reloc::X86GOTPCRel4 | Reloc::X86CallPLTRel4 => panic!("unexpected PIC relocation"),
+                Reloc::Arm64Call => {
+                    // The displacement is a word offset in the low 26 bits of `bl`.
+                    let pcrel = ((what as isize) - (at as isize)) >> 2;
+                    debug_assert!(pcrel >= -(1 << 25) && pcrel < (1 << 25));
+                    #[cfg_attr(feature = "cargo-clippy", allow(clippy::cast_ptr_alignment))]
+                    unsafe {
+                        let inst = at as *mut u32;
+                        let bits = read_unaligned(inst) & !0x03ff_ffff;
+                        write_unaligned(inst, bits | (pcrel as u32 & 0x03ff_ffff))
+                    };
+                }
+                Reloc::Arm32Call => {
+                    // The displacement is a word offset in the low 24 bits of `bl`. The addend
+                    // already accounts for `pc` reading 8 bytes ahead.
+                    let pcrel = ((what as isize) - (at as isize)) >> 2;
+                    debug_assert!(pcrel >= -(1 << 23) && pcrel < (1 << 23));
+                    #[cfg_attr(feature = "cargo-clippy", allow(clippy::cast_ptr_alignment))]
+                    unsafe {
+                        let inst = at as *mut u32;
+                        let bits = read_unaligned(inst) & !0x00ff_ffff;
+                        write_unaligned(inst, bits | (pcrel as u32 & 0x00ff_ffff))
+                    };
+                }
                 _ => unimplemented!(),
             }
         }
@@ -555,8 +637,17 @@ fn lookup_with_dlsym(name: &str) -> *const u8 {
     }
 }
 
+/// The error for a relocation in the unwind information of a function that SimpleJIT can't apply.
+fn unsupported_unwind_reloc(reloc: Reloc) -> ModuleError {
+    ModuleError::Backend(format!(
+        "SimpleJIT doesn't support {} relocations in unwind information",
+        reloc
+    ))
+}
+
 impl SimpleJITMemoryHandle {
-    /// Free memory allocated for code and data segments of compiled functions.
+    /// Free memory allocated for code and data segments of compiled functions, and deregister
+    /// their unwind information.
     ///
     /// # Safety
     ///
@@ -565,6 +656,7 @@ impl SimpleJITMemoryHandle {
     /// from that module are currently executing and none of the`fn` pointers
     /// are called afterwards.
     pub unsafe fn free_memory(&mut self) {
+        self.unwind.deregister();
         self.code.free_memory();
         self.readonly.free_memory();
         self.writable.free_memory();
@@ -626,6 +718,52 @@ impl RelocSink for SimpleJITRelocSink {
     }
 }
 
+struct SimpleJITUnwindSink {
+    frame: Vec<u8>,
+    entry: FrameUnwindOffset,
+    relocs: Vec<(Reloc, FrameUnwindOffset)>,
+    external_relocs: Vec<(Reloc, FrameUnwindOffset, ir::ExternalName)>,
+    lsda: Option<(Vec<u8>, Reloc, FrameUnwindOffset)>,
+}
+
+impl SimpleJITUnwindSink {
+    pub fn new() -> Self {
+        Self {
+            frame: Vec::new(),
+            entry: 0,
+            relocs: Vec::new(),
+            external_relocs: Vec::new(),
+            lsda: None,
+        }
+    }
+}
+
+impl FrameUnwindSink for SimpleJITUnwindSink {
+    fn len(&self) -> FrameUnwindOffset {
+        self.frame.len()
+    }
+
+    fn bytes(&mut self, b: &[u8]) {
+        self.frame.extend_from_slice(b);
+    }
+
+    fn reloc(&mut self, reloc: Reloc, offset: FrameUnwindOffset) {
+        self.relocs.push((reloc, offset));
+    }
+
+    fn reloc_external(&mut self, reloc: Reloc, offset: FrameUnwindOffset, name: &ir::ExternalName) {
+        self.external_relocs.push((reloc, offset, name.clone()));
+    }
+
+    fn lsda(&mut self, lsda: &[u8], reloc: Reloc, offset: FrameUnwindOffset) {
+        self.lsda = Some((lsda.to_vec(), reloc, offset));
+    }
+
+    fn set_entry_offset(&mut self, offset: FrameUnwindOffset) {
+        self.entry = offset;
+    }
+}
+
 struct SimpleJITStackmapSink {
     pub stackmaps: Vec<StackmapRecord>,
 }
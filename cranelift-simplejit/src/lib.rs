@@ -25,6 +25,7 @@
 
 mod backend;
 mod memory;
+mod unwind;
 
 pub use crate::backend::{SimpleJITBackend, SimpleJITBuilder};
 
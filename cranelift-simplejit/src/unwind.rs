@@ -0,0 +1,75 @@
+//! Registration of JIT-compiled functions' `.eh_frame` entries with the system unwinder, so that
+//! debuggers, profilers and panics can unwind through them.
+
+use std::mem;
+
+#[cfg(not(windows))]
+extern "C" {
+    // Provided by libgcc on Linux and by libunwind on macOS.
+    fn __register_frame(fde: *const u8);
+    fn __deregister_frame(fde: *const u8);
+}
+
+/// The unwind information registered for a module's functions. Like `Memory`, registrations are
+/// leaked by default so that the functions remain unwindable for the remainder of the program's
+/// life.
+pub struct UnwindRegistry {
+    frames: Vec<Vec<u8>>,
+    registrations: Vec<*const u8>,
+}
+
+impl UnwindRegistry {
+    pub fn new() -> Self {
+        Self {
+            frames: Vec::new(),
+            registrations: Vec::new(),
+        }
+    }
+
+    /// Register `frame`, a CIE followed by the FDE at `entry`, whose relocations have been
+    /// applied already.
+    #[cfg(not(windows))]
+    pub fn register(&mut self, mut frame: Vec<u8>, entry: usize) {
+        // libgcc's `__register_frame` takes a whole `.eh_frame` section, which ends with a zero
+        // length entry, while libunwind's takes a single FDE.
+        frame.extend_from_slice(&[0; 4]);
+        let registration = if cfg!(target_os = "macos") {
+            frame[entry..].as_ptr()
+        } else {
+            frame.as_ptr()
+        };
+        unsafe { __register_frame(registration) };
+        self.registrations.push(registration);
+        self.frames.push(frame);
+    }
+
+    /// Keep `lsda` alive for as long as the frames referencing it, and return its address.
+    pub fn add_lsda(&mut self, lsda: Vec<u8>) -> *const u8 {
+        let ptr = lsda.as_ptr();
+        self.frames.push(lsda);
+        ptr
+    }
+
+    /// Windows functions are described by `.pdata` instead, which isn't supported yet.
+    #[cfg(windows)]
+    pub fn register(&mut self, _frame: Vec<u8>, _entry: usize) {}
+
+    /// Deregister all the frames, which must happen before their functions' memory is freed.
+    pub unsafe fn deregister(&mut self) {
+        #[cfg(not(windows))]
+        {
+            for &registration in self.registrations.iter().rev() {
+                __deregister_frame(registration);
+            }
+        }
+        self.registrations.clear();
+        self.frames.clear();
+    }
+}
+
+impl Drop for UnwindRegistry {
+    fn drop(&mut self) {
+        // leak the registrations along with the code they describe
+        mem::forget(mem::replace(&mut self.frames, Vec::new()));
+    }
+}
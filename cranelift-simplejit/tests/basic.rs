@@ -10,11 +10,8 @@ use cranelift_simplejit::*;
 fn error_on_incompatible_sig_in_declare_function() {
     let mut module: Module<SimpleJITBackend> =
         Module::new(SimpleJITBuilder::new(default_libcall_names()));
-    let mut sig = Signature {
-        params: vec![AbiParam::new(types::I64)],
-        returns: vec![],
-        call_conv: CallConv::SystemV,
-    };
+    let mut sig = Signature::new(CallConv::SystemV);
+    sig.params.push(AbiParam::new(types::I64));
     module
         .declare_function("abc", Linkage::Local, &sig)
         .unwrap();
@@ -25,12 +22,50 @@ fn error_on_incompatible_sig_in_declare_function() {
         .unwrap(); // Make sure this is an error
 }
 
+#[test]
+fn error_on_exported_fast_function() {
+    let mut module: Module<SimpleJITBackend> =
+        Module::new(SimpleJITBuilder::new(default_libcall_names()));
+    let mut sig = Signature::new(CallConv::Fast);
+    sig.params.push(AbiParam::new(types::I64));
+    module
+        .declare_function("abc", Linkage::Local, &sig)
+        .unwrap();
+    match module.declare_function("abc", Linkage::Export, &sig) {
+        Err(ModuleError::UnexportableCallConv(name, CallConv::Fast)) => assert_eq!(name, "abc"),
+        other => panic!("unexpected result: {:?}", other.map(|_| ())),
+    }
+    module
+        .declare_function("def", Linkage::Preemptible, &sig)
+        .err()
+        .unwrap();
+}
+
+#[test]
+fn error_on_tls_data() {
+    let mut module: Module<SimpleJITBackend> =
+        Module::new(SimpleJITBuilder::new(default_libcall_names()));
+    match module.declare_data("tls", Linkage::Local, true, true, None) {
+        Err(ModuleError::Backend(_)) => {}
+        other => panic!("unexpected result: {:?}", other.map(|_| ())),
+    }
+}
+
+#[test]
+fn error_on_incompatible_tls_in_declare_data() {
+    let mut module: Module<SimpleJITBackend> =
+        Module::new(SimpleJITBuilder::new(default_libcall_names()));
+    module
+        .declare_data("abc", Linkage::Local, true, false, None)
+        .unwrap();
+    match module.declare_data("abc", Linkage::Local, true, true, None) {
+        Err(ModuleError::IncompatibleDeclaration(name)) => assert_eq!(name, "abc"),
+        other => panic!("unexpected result: {:?}", other.map(|_| ())),
+    }
+}
+
 fn define_simple_function(module: &mut Module<SimpleJITBackend>) -> FuncId {
-    let sig = Signature {
-        params: vec![],
-        returns: vec![],
-        call_conv: CallConv::SystemV,
-    };
+    let sig = Signature::new(CallConv::SystemV);
 
     let func_id = module
         .declare_function("abc", Linkage::Local, &sig)
@@ -79,11 +114,9 @@ fn panic_on_define_after_finalize() {
 fn switch_error() {
     use cranelift_codegen::settings;
 
-    let sig = Signature {
-        params: vec![AbiParam::new(types::I32)],
-        returns: vec![AbiParam::new(types::I32)],
-        call_conv: CallConv::SystemV,
-    };
+    let mut sig = Signature::new(CallConv::SystemV);
+    sig.params.push(AbiParam::new(types::I32));
+    sig.returns.push(AbiParam::new(types::I32));
 
     let mut func = Function::with_name_signature(ExternalName::user(0, 0), sig);
 
@@ -153,11 +186,7 @@ fn libcall_function() {
     let mut module: Module<SimpleJITBackend> =
         Module::new(SimpleJITBuilder::new(default_libcall_names()));
 
-    let sig = Signature {
-        params: vec![],
-        returns: vec![],
-        call_conv: CallConv::SystemV,
-    };
+    let sig = Signature::new(CallConv::SystemV);
 
     let func_id = module
         .declare_function("function", Linkage::Local, &sig)
@@ -195,3 +224,140 @@ fn libcall_function() {
 
     module.finalize_definitions();
 }
+
+#[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+#[test]
+fn unwind_through_jit_function() {
+    use std::cell::RefCell;
+    use std::os::raw::{c_int, c_void};
+
+    extern "C" {
+        fn _Unwind_Backtrace(
+            trace: extern "C" fn(*mut c_void, *mut c_void) -> c_int,
+            arg: *mut c_void,
+        ) -> c_int;
+        fn _Unwind_GetIP(ctx: *mut c_void) -> usize;
+    }
+
+    thread_local! {
+        static IPS: RefCell<Vec<usize>> = RefCell::new(Vec::new());
+    }
+
+    extern "C" fn trace(ctx: *mut c_void, _arg: *mut c_void) -> c_int {
+        let ip = unsafe { _Unwind_GetIP(ctx) };
+        IPS.with(|ips| ips.borrow_mut().push(ip));
+        0
+    }
+
+    extern "C" fn callback() {
+        unsafe { _Unwind_Backtrace(trace, std::ptr::null_mut()) };
+    }
+
+    let mut builder = SimpleJITBuilder::new(default_libcall_names());
+    builder.symbol("callback", callback as *const u8);
+    let mut module: Module<SimpleJITBackend> = Module::new(builder);
+
+    let sig = module.make_signature();
+    let func_id = module
+        .declare_function("function", Linkage::Local, &sig)
+        .unwrap();
+    let callback_id = module
+        .declare_function("callback", Linkage::Import, &sig)
+        .unwrap();
+
+    let mut ctx = Context::new();
+    ctx.func = Function::with_name_signature(ExternalName::user(0, func_id.as_u32()), sig);
+    let mut func_ctx = FunctionBuilderContext::new();
+    {
+        let mut bcx: FunctionBuilder = FunctionBuilder::new(&mut ctx.func, &mut func_ctx);
+        let ebb = bcx.create_ebb();
+        bcx.switch_to_block(ebb);
+        let callee = module.declare_func_in_func(callback_id, &mut bcx.func);
+        bcx.ins().call(callee, &[]);
+        bcx.ins().return_(&[]);
+    }
+
+    module.define_function(func_id, &mut ctx).unwrap();
+    module.finalize_definitions();
+
+    let code = module.get_finalized_function(func_id);
+    let function = unsafe { std::mem::transmute::<_, extern "C" fn()>(code) };
+    function();
+
+    // The unwinder must find the JIT-compiled frame and step past it into this test.
+    let ips = IPS.with(|ips| ips.borrow().clone());
+    let code = code as usize;
+    let jit_frame = ips
+        .iter()
+        .position(|&ip| ip > code && ip < code + 64)
+        .expect("JIT frame not found in the backtrace");
+    assert!(jit_frame + 1 < ips.len());
+}
+
+#[test]
+fn inline_local_function() {
+    use cranelift_codegen::isa;
+    use cranelift_codegen::settings::{self, Configurable};
+
+    let mut flag_builder = settings::builder();
+    flag_builder.set("inline_threshold", "10").unwrap();
+    let isa = isa::lookup_by_name("x86_64")
+        .unwrap()
+        .finish(settings::Flags::new(flag_builder));
+    let mut module: Module<SimpleJITBackend> =
+        Module::new(SimpleJITBuilder::with_isa(isa, default_libcall_names()));
+
+    let mut sig = Signature::new(CallConv::SystemV);
+    sig.params.push(AbiParam::new(types::I32));
+    sig.returns.push(AbiParam::new(types::I32));
+
+    let callee_id = module
+        .declare_function("add_one", Linkage::Local, &sig)
+        .unwrap();
+    let caller_id = module
+        .declare_function("caller", Linkage::Local, &sig)
+        .unwrap();
+
+    let mut ctx = Context::new();
+    let mut func_ctx = FunctionBuilderContext::new();
+
+    ctx.func =
+        Function::with_name_signature(ExternalName::user(0, callee_id.as_u32()), sig.clone());
+    {
+        let mut bcx: FunctionBuilder = FunctionBuilder::new(&mut ctx.func, &mut func_ctx);
+        let ebb = bcx.create_ebb();
+        bcx.append_ebb_params_for_function_params(ebb);
+        bcx.switch_to_block(ebb);
+        let arg = bcx.ebb_params(ebb)[0];
+        let res = bcx.ins().iadd_imm(arg, 1);
+        bcx.ins().return_(&[res]);
+        bcx.seal_all_blocks();
+        bcx.finalize();
+    }
+    module.define_function(callee_id, &mut ctx).unwrap();
+    module.clear_context(&mut ctx);
+
+    ctx.func = Function::with_name_signature(ExternalName::user(0, caller_id.as_u32()), sig);
+    {
+        let mut bcx: FunctionBuilder = FunctionBuilder::new(&mut ctx.func, &mut func_ctx);
+        let callee = module.declare_func_in_func(callee_id, &mut bcx.func);
+        let ebb = bcx.create_ebb();
+        bcx.append_ebb_params_for_function_params(ebb);
+        bcx.switch_to_block(ebb);
+        let arg = bcx.ebb_params(ebb)[0];
+        let call = bcx.ins().call(callee, &[arg]);
+        let res = bcx.inst_results(call)[0];
+        bcx.ins().return_(&[res]);
+        bcx.seal_all_blocks();
+        bcx.finalize();
+    }
+    module.define_function(caller_id, &mut ctx).unwrap();
+
+    // The call to `add_one` was replaced by its body.
+    let func = &ctx.func;
+    assert!(func
+        .layout
+        .ebbs()
+        .flat_map(|ebb| func.layout.ebb_insts(ebb))
+        .all(|inst| !func.dfg[inst].opcode().is_call()));
+}
@@ -28,13 +28,14 @@ use crate::state::{ControlStackFrame, ElseData, FuncTranslationState, ModuleTran
 use crate::translation_utils::{
     blocktype_params_results, ebb_with_params, f32_translation, f64_translation,
 };
-use crate::translation_utils::{FuncIndex, MemoryIndex, SignatureIndex, TableIndex};
-use crate::wasm_unsupported;
+use crate::translation_utils::{
+    DataIndex, ElemIndex, FuncIndex, MemoryIndex, SignatureIndex, TableIndex,
+};
 use core::{i32, u32};
 use cranelift_codegen::ir::condcodes::{FloatCC, IntCC};
 use cranelift_codegen::ir::types::*;
 use cranelift_codegen::ir::{
-    self, ConstantData, InstBuilder, JumpTableData, MemFlags, Value, ValueLabel,
+    self, ConstantData, InstBuilder, JumpTableData, MemFlags, MemRegion, Value, ValueLabel,
 };
 use cranelift_codegen::packed_option::ReservedValue;
 use cranelift_frontend::{FunctionBuilder, Variable};
@@ -91,7 +92,8 @@ pub fn translate_operator<FE: FuncEnvironment + ?Sized>(
                 GlobalVariable::Const(val) => val,
                 GlobalVariable::Memory { gv, offset, ty } => {
                     let addr = builder.ins().global_value(environ.pointer_type(), gv);
-                    let flags = ir::MemFlags::trusted();
+                    let mut flags = ir::MemFlags::trusted();
+                    flags.set_region(ir::MemRegion::Vmctx);
                     builder.ins().load(ty, flags, addr, offset)
                 }
             };
@@ -102,7 +104,8 @@ pub fn translate_operator<FE: FuncEnvironment + ?Sized>(
                 GlobalVariable::Const(_) => panic!("global #{} is a constant", *global_index),
                 GlobalVariable::Memory { gv, offset, ty } => {
                     let addr = builder.ins().global_value(environ.pointer_type(), gv);
-                    let flags = ir::MemFlags::trusted();
+                    let mut flags = ir::MemFlags::trusted();
+                    flags.set_region(ir::MemRegion::Vmctx);
                     let val = state.pop1();
                     debug_assert_eq!(ty, builder.func.dfg.value_type(val));
                     builder.ins().store(flags, val, addr, offset);
@@ -918,87 +921,417 @@ pub fn translate_operator<FE: FuncEnvironment + ?Sized>(
             let val = builder.ins().is_null(arg);
             state.push1(val);
         }
-        Operator::Wake { .. }
-        | Operator::I32Wait { .. }
-        | Operator::I64Wait { .. }
-        | Operator::I32AtomicLoad { .. }
-        | Operator::I64AtomicLoad { .. }
-        | Operator::I32AtomicLoad8U { .. }
-        | Operator::I32AtomicLoad16U { .. }
-        | Operator::I64AtomicLoad8U { .. }
-        | Operator::I64AtomicLoad16U { .. }
-        | Operator::I64AtomicLoad32U { .. }
-        | Operator::I32AtomicStore { .. }
-        | Operator::I64AtomicStore { .. }
-        | Operator::I32AtomicStore8 { .. }
-        | Operator::I32AtomicStore16 { .. }
-        | Operator::I64AtomicStore8 { .. }
-        | Operator::I64AtomicStore16 { .. }
-        | Operator::I64AtomicStore32 { .. }
-        | Operator::I32AtomicRmwAdd { .. }
-        | Operator::I64AtomicRmwAdd { .. }
-        | Operator::I32AtomicRmw8UAdd { .. }
-        | Operator::I32AtomicRmw16UAdd { .. }
-        | Operator::I64AtomicRmw8UAdd { .. }
-        | Operator::I64AtomicRmw16UAdd { .. }
-        | Operator::I64AtomicRmw32UAdd { .. }
-        | Operator::I32AtomicRmwSub { .. }
-        | Operator::I64AtomicRmwSub { .. }
-        | Operator::I32AtomicRmw8USub { .. }
-        | Operator::I32AtomicRmw16USub { .. }
-        | Operator::I64AtomicRmw8USub { .. }
-        | Operator::I64AtomicRmw16USub { .. }
-        | Operator::I64AtomicRmw32USub { .. }
-        | Operator::I32AtomicRmwAnd { .. }
-        | Operator::I64AtomicRmwAnd { .. }
-        | Operator::I32AtomicRmw8UAnd { .. }
-        | Operator::I32AtomicRmw16UAnd { .. }
-        | Operator::I64AtomicRmw8UAnd { .. }
-        | Operator::I64AtomicRmw16UAnd { .. }
-        | Operator::I64AtomicRmw32UAnd { .. }
-        | Operator::I32AtomicRmwOr { .. }
-        | Operator::I64AtomicRmwOr { .. }
-        | Operator::I32AtomicRmw8UOr { .. }
-        | Operator::I32AtomicRmw16UOr { .. }
-        | Operator::I64AtomicRmw8UOr { .. }
-        | Operator::I64AtomicRmw16UOr { .. }
-        | Operator::I64AtomicRmw32UOr { .. }
-        | Operator::I32AtomicRmwXor { .. }
-        | Operator::I64AtomicRmwXor { .. }
-        | Operator::I32AtomicRmw8UXor { .. }
-        | Operator::I32AtomicRmw16UXor { .. }
-        | Operator::I64AtomicRmw8UXor { .. }
-        | Operator::I64AtomicRmw16UXor { .. }
-        | Operator::I64AtomicRmw32UXor { .. }
-        | Operator::I32AtomicRmwXchg { .. }
-        | Operator::I64AtomicRmwXchg { .. }
-        | Operator::I32AtomicRmw8UXchg { .. }
-        | Operator::I32AtomicRmw16UXchg { .. }
-        | Operator::I64AtomicRmw8UXchg { .. }
-        | Operator::I64AtomicRmw16UXchg { .. }
-        | Operator::I64AtomicRmw32UXchg { .. }
-        | Operator::I32AtomicRmwCmpxchg { .. }
-        | Operator::I64AtomicRmwCmpxchg { .. }
-        | Operator::I32AtomicRmw8UCmpxchg { .. }
-        | Operator::I32AtomicRmw16UCmpxchg { .. }
-        | Operator::I64AtomicRmw8UCmpxchg { .. }
-        | Operator::I64AtomicRmw16UCmpxchg { .. }
-        | Operator::I64AtomicRmw32UCmpxchg { .. }
-        | Operator::Fence { .. } => {
-            return Err(wasm_unsupported!("proposed thread operator {:?}", op));
-        }
-        Operator::MemoryInit { .. }
-        | Operator::DataDrop { .. }
-        | Operator::MemoryCopy
-        | Operator::MemoryFill
-        | Operator::TableInit { .. }
-        | Operator::ElemDrop { .. }
-        | Operator::TableCopy
-        | Operator::TableGet { .. }
-        | Operator::TableSet { .. }
-        | Operator::TableGrow { .. }
-        | Operator::TableSize { .. } => {
-            return Err(wasm_unsupported!("proposed bulk memory operator {:?}", op));
+        /******************************* Atomic instructions *********************************
+         * All WebAssembly atomic accesses are sequentially consistent and must be naturally
+         * aligned; misaligned accesses trap.
+         ************************************************************************************/
+        Operator::Wake {
+            memarg: MemoryImmediate { flags: _, offset },
+        } => {
+            let (addr32, count) = state.pop2();
+            let heap_index = MemoryIndex::from_u32(0);
+            let heap = state.get_heap(builder.func, 0, environ)?;
+            let addr = get_atomic_heap_addr(heap, addr32, *offset, I32, environ, builder);
+            state.push1(environ.translate_atomic_notify(
+                builder.cursor(),
+                heap_index,
+                heap,
+                addr,
+                count,
+            )?);
+        }
+        Operator::I32Wait {
+            memarg: MemoryImmediate { flags: _, offset },
+        }
+        | Operator::I64Wait {
+            memarg: MemoryImmediate { flags: _, offset },
+        } => {
+            let (addr32, expected, timeout) = state.pop3();
+            let heap_index = MemoryIndex::from_u32(0);
+            let heap = state.get_heap(builder.func, 0, environ)?;
+            let expected_ty = builder.func.dfg.value_type(expected);
+            let addr = get_atomic_heap_addr(heap, addr32, *offset, expected_ty, environ, builder);
+            state.push1(environ.translate_atomic_wait(
+                builder.cursor(),
+                heap_index,
+                heap,
+                addr,
+                expected,
+                timeout,
+            )?);
+        }
+        Operator::I32AtomicLoad {
+            memarg: MemoryImmediate { flags: _, offset },
+        } => {
+            translate_atomic_load(*offset, I32, I32, builder, state, environ)?;
+        }
+        Operator::I64AtomicLoad {
+            memarg: MemoryImmediate { flags: _, offset },
+        } => {
+            translate_atomic_load(*offset, I64, I64, builder, state, environ)?;
+        }
+        Operator::I32AtomicLoad8U {
+            memarg: MemoryImmediate { flags: _, offset },
+        } => {
+            translate_atomic_load(*offset, I8, I32, builder, state, environ)?;
+        }
+        Operator::I32AtomicLoad16U {
+            memarg: MemoryImmediate { flags: _, offset },
+        } => {
+            translate_atomic_load(*offset, I16, I32, builder, state, environ)?;
+        }
+        Operator::I64AtomicLoad8U {
+            memarg: MemoryImmediate { flags: _, offset },
+        } => {
+            translate_atomic_load(*offset, I8, I64, builder, state, environ)?;
+        }
+        Operator::I64AtomicLoad16U {
+            memarg: MemoryImmediate { flags: _, offset },
+        } => {
+            translate_atomic_load(*offset, I16, I64, builder, state, environ)?;
+        }
+        Operator::I64AtomicLoad32U {
+            memarg: MemoryImmediate { flags: _, offset },
+        } => {
+            translate_atomic_load(*offset, I32, I64, builder, state, environ)?;
+        }
+        Operator::I32AtomicStore {
+            memarg: MemoryImmediate { flags: _, offset },
+        }
+        | Operator::I64AtomicStore32 {
+            memarg: MemoryImmediate { flags: _, offset },
+        } => {
+            translate_atomic_store(*offset, I32, builder, state, environ)?;
+        }
+        Operator::I64AtomicStore {
+            memarg: MemoryImmediate { flags: _, offset },
+        } => {
+            translate_atomic_store(*offset, I64, builder, state, environ)?;
+        }
+        Operator::I32AtomicStore8 {
+            memarg: MemoryImmediate { flags: _, offset },
+        }
+        | Operator::I64AtomicStore8 {
+            memarg: MemoryImmediate { flags: _, offset },
+        } => {
+            translate_atomic_store(*offset, I8, builder, state, environ)?;
+        }
+        Operator::I32AtomicStore16 {
+            memarg: MemoryImmediate { flags: _, offset },
+        }
+        | Operator::I64AtomicStore16 {
+            memarg: MemoryImmediate { flags: _, offset },
+        } => {
+            translate_atomic_store(*offset, I16, builder, state, environ)?;
+        }
+        Operator::I32AtomicRmwAdd {
+            memarg: MemoryImmediate { flags: _, offset },
+        }
+        | Operator::I64AtomicRmw32UAdd {
+            memarg: MemoryImmediate { flags: _, offset },
+        } => {
+            translate_atomic_rmw(*offset, ir::AtomicRmwOp::Add, I32, builder, state, environ)?;
+        }
+        Operator::I64AtomicRmwAdd {
+            memarg: MemoryImmediate { flags: _, offset },
+        } => {
+            translate_atomic_rmw(*offset, ir::AtomicRmwOp::Add, I64, builder, state, environ)?;
+        }
+        Operator::I32AtomicRmw8UAdd {
+            memarg: MemoryImmediate { flags: _, offset },
+        }
+        | Operator::I64AtomicRmw8UAdd {
+            memarg: MemoryImmediate { flags: _, offset },
+        } => {
+            translate_atomic_rmw(*offset, ir::AtomicRmwOp::Add, I8, builder, state, environ)?;
+        }
+        Operator::I32AtomicRmw16UAdd {
+            memarg: MemoryImmediate { flags: _, offset },
+        }
+        | Operator::I64AtomicRmw16UAdd {
+            memarg: MemoryImmediate { flags: _, offset },
+        } => {
+            translate_atomic_rmw(*offset, ir::AtomicRmwOp::Add, I16, builder, state, environ)?;
+        }
+        Operator::I32AtomicRmwSub {
+            memarg: MemoryImmediate { flags: _, offset },
+        }
+        | Operator::I64AtomicRmw32USub {
+            memarg: MemoryImmediate { flags: _, offset },
+        } => {
+            translate_atomic_rmw(*offset, ir::AtomicRmwOp::Sub, I32, builder, state, environ)?;
+        }
+        Operator::I64AtomicRmwSub {
+            memarg: MemoryImmediate { flags: _, offset },
+        } => {
+            translate_atomic_rmw(*offset, ir::AtomicRmwOp::Sub, I64, builder, state, environ)?;
+        }
+        Operator::I32AtomicRmw8USub {
+            memarg: MemoryImmediate { flags: _, offset },
+        }
+        | Operator::I64AtomicRmw8USub {
+            memarg: MemoryImmediate { flags: _, offset },
+        } => {
+            translate_atomic_rmw(*offset, ir::AtomicRmwOp::Sub, I8, builder, state, environ)?;
+        }
+        Operator::I32AtomicRmw16USub {
+            memarg: MemoryImmediate { flags: _, offset },
+        }
+        | Operator::I64AtomicRmw16USub {
+            memarg: MemoryImmediate { flags: _, offset },
+        } => {
+            translate_atomic_rmw(*offset, ir::AtomicRmwOp::Sub, I16, builder, state, environ)?;
+        }
+        Operator::I32AtomicRmwAnd {
+            memarg: MemoryImmediate { flags: _, offset },
+        }
+        | Operator::I64AtomicRmw32UAnd {
+            memarg: MemoryImmediate { flags: _, offset },
+        } => {
+            translate_atomic_rmw(*offset, ir::AtomicRmwOp::And, I32, builder, state, environ)?;
+        }
+        Operator::I64AtomicRmwAnd {
+            memarg: MemoryImmediate { flags: _, offset },
+        } => {
+            translate_atomic_rmw(*offset, ir::AtomicRmwOp::And, I64, builder, state, environ)?;
+        }
+        Operator::I32AtomicRmw8UAnd {
+            memarg: MemoryImmediate { flags: _, offset },
+        }
+        | Operator::I64AtomicRmw8UAnd {
+            memarg: MemoryImmediate { flags: _, offset },
+        } => {
+            translate_atomic_rmw(*offset, ir::AtomicRmwOp::And, I8, builder, state, environ)?;
+        }
+        Operator::I32AtomicRmw16UAnd {
+            memarg: MemoryImmediate { flags: _, offset },
+        }
+        | Operator::I64AtomicRmw16UAnd {
+            memarg: MemoryImmediate { flags: _, offset },
+        } => {
+            translate_atomic_rmw(*offset, ir::AtomicRmwOp::And, I16, builder, state, environ)?;
+        }
+        Operator::I32AtomicRmwOr {
+            memarg: MemoryImmediate { flags: _, offset },
+        }
+        | Operator::I64AtomicRmw32UOr {
+            memarg: MemoryImmediate { flags: _, offset },
+        } => {
+            translate_atomic_rmw(*offset, ir::AtomicRmwOp::Or, I32, builder, state, environ)?;
+        }
+        Operator::I64AtomicRmwOr {
+            memarg: MemoryImmediate { flags: _, offset },
+        } => {
+            translate_atomic_rmw(*offset, ir::AtomicRmwOp::Or, I64, builder, state, environ)?;
+        }
+        Operator::I32AtomicRmw8UOr {
+            memarg: MemoryImmediate { flags: _, offset },
+        }
+        | Operator::I64AtomicRmw8UOr {
+            memarg: MemoryImmediate { flags: _, offset },
+        } => {
+            translate_atomic_rmw(*offset, ir::AtomicRmwOp::Or, I8, builder, state, environ)?;
+        }
+        Operator::I32AtomicRmw16UOr {
+            memarg: MemoryImmediate { flags: _, offset },
+        }
+        | Operator::I64AtomicRmw16UOr {
+            memarg: MemoryImmediate { flags: _, offset },
+        } => {
+            translate_atomic_rmw(*offset, ir::AtomicRmwOp::Or, I16, builder, state, environ)?;
+        }
+        Operator::I32AtomicRmwXor {
+            memarg: MemoryImmediate { flags: _, offset },
+        }
+        | Operator::I64AtomicRmw32UXor {
+            memarg: MemoryImmediate { flags: _, offset },
+        } => {
+            translate_atomic_rmw(*offset, ir::AtomicRmwOp::Xor, I32, builder, state, environ)?;
+        }
+        Operator::I64AtomicRmwXor {
+            memarg: MemoryImmediate { flags: _, offset },
+        } => {
+            translate_atomic_rmw(*offset, ir::AtomicRmwOp::Xor, I64, builder, state, environ)?;
+        }
+        Operator::I32AtomicRmw8UXor {
+            memarg: MemoryImmediate { flags: _, offset },
+        }
+        | Operator::I64AtomicRmw8UXor {
+            memarg: MemoryImmediate { flags: _, offset },
+        } => {
+            translate_atomic_rmw(*offset, ir::AtomicRmwOp::Xor, I8, builder, state, environ)?;
+        }
+        Operator::I32AtomicRmw16UXor {
+            memarg: MemoryImmediate { flags: _, offset },
+        }
+        | Operator::I64AtomicRmw16UXor {
+            memarg: MemoryImmediate { flags: _, offset },
+        } => {
+            translate_atomic_rmw(*offset, ir::AtomicRmwOp::Xor, I16, builder, state, environ)?;
+        }
+        Operator::I32AtomicRmwXchg {
+            memarg: MemoryImmediate { flags: _, offset },
+        }
+        | Operator::I64AtomicRmw32UXchg {
+            memarg: MemoryImmediate { flags: _, offset },
+        } => {
+            translate_atomic_rmw(*offset, ir::AtomicRmwOp::Xchg, I32, builder, state, environ)?;
+        }
+        Operator::I64AtomicRmwXchg {
+            memarg: MemoryImmediate { flags: _, offset },
+        } => {
+            translate_atomic_rmw(*offset, ir::AtomicRmwOp::Xchg, I64, builder, state, environ)?;
+        }
+        Operator::I32AtomicRmw8UXchg {
+            memarg: MemoryImmediate { flags: _, offset },
+        }
+        | Operator::I64AtomicRmw8UXchg {
+            memarg: MemoryImmediate { flags: _, offset },
+        } => {
+            translate_atomic_rmw(*offset, ir::AtomicRmwOp::Xchg, I8, builder, state, environ)?;
+        }
+        Operator::I32AtomicRmw16UXchg {
+            memarg: MemoryImmediate { flags: _, offset },
+        }
+        | Operator::I64AtomicRmw16UXchg {
+            memarg: MemoryImmediate { flags: _, offset },
+        } => {
+            translate_atomic_rmw(*offset, ir::AtomicRmwOp::Xchg, I16, builder, state, environ)?;
+        }
+        Operator::I32AtomicRmwCmpxchg {
+            memarg: MemoryImmediate { flags: _, offset },
+        }
+        | Operator::I64AtomicRmw32UCmpxchg {
+            memarg: MemoryImmediate { flags: _, offset },
+        } => {
+            translate_atomic_cas(*offset, I32, builder, state, environ)?;
+        }
+        Operator::I64AtomicRmwCmpxchg {
+            memarg: MemoryImmediate { flags: _, offset },
+        } => {
+            translate_atomic_cas(*offset, I64, builder, state, environ)?;
+        }
+        Operator::I32AtomicRmw8UCmpxchg {
+            memarg: MemoryImmediate { flags: _, offset },
+        }
+        | Operator::I64AtomicRmw8UCmpxchg {
+            memarg: MemoryImmediate { flags: _, offset },
+        } => {
+            translate_atomic_cas(*offset, I8, builder, state, environ)?;
+        }
+        Operator::I32AtomicRmw16UCmpxchg {
+            memarg: MemoryImmediate { flags: _, offset },
+        }
+        | Operator::I64AtomicRmw16UCmpxchg {
+            memarg: MemoryImmediate { flags: _, offset },
+        } => {
+            translate_atomic_cas(*offset, I16, builder, state, environ)?;
+        }
+        Operator::Fence { flags: _ } => {
+            builder.ins().fence(ir::AtomicOrdering::SeqCst);
+        }
+        /****************************** Bulk memory and tables *******************************
+         * The bulk memory operators only support memory and table 0 for now; the accesses they
+         * make are bounds checked by the environment.
+         ************************************************************************************/
+        Operator::MemoryCopy => {
+            let heap_index = MemoryIndex::from_u32(0);
+            let heap = state.get_heap(builder.func, 0, environ)?;
+            let (dst, src, len) = state.pop3();
+            environ.translate_memory_copy(builder.cursor(), heap_index, heap, dst, src, len)?;
+        }
+        Operator::MemoryFill => {
+            let heap_index = MemoryIndex::from_u32(0);
+            let heap = state.get_heap(builder.func, 0, environ)?;
+            let (dst, val, len) = state.pop3();
+            environ.translate_memory_fill(builder.cursor(), heap_index, heap, dst, val, len)?;
+        }
+        Operator::MemoryInit { segment } => {
+            let heap_index = MemoryIndex::from_u32(0);
+            let heap = state.get_heap(builder.func, 0, environ)?;
+            let (dst, src, len) = state.pop3();
+            environ.translate_memory_init(
+                builder.cursor(),
+                heap_index,
+                heap,
+                DataIndex::from_u32(*segment),
+                dst,
+                src,
+                len,
+            )?;
+        }
+        Operator::DataDrop { segment } => {
+            environ.translate_data_drop(builder.cursor(), DataIndex::from_u32(*segment))?;
+        }
+        Operator::TableSize { table: index } => {
+            let table = state.get_table(builder.func, *index, environ)?;
+            state.push1(environ.translate_table_size(
+                builder.cursor(),
+                TableIndex::from_u32(*index),
+                table,
+            )?);
+        }
+        Operator::TableGrow { table: index } => {
+            let table = state.get_table(builder.func, *index, environ)?;
+            let (init_value, delta) = state.pop2();
+            state.push1(environ.translate_table_grow(
+                builder.cursor(),
+                TableIndex::from_u32(*index),
+                table,
+                delta,
+                init_value,
+            )?);
+        }
+        Operator::TableGet { table: index } => {
+            let table = state.get_table(builder.func, *index, environ)?;
+            let elem_index = state.pop1();
+            state.push1(environ.translate_table_get(
+                builder.cursor(),
+                TableIndex::from_u32(*index),
+                table,
+                elem_index,
+            )?);
+        }
+        Operator::TableSet { table: index } => {
+            let table = state.get_table(builder.func, *index, environ)?;
+            let (elem_index, value) = state.pop2();
+            environ.translate_table_set(
+                builder.cursor(),
+                TableIndex::from_u32(*index),
+                table,
+                value,
+                elem_index,
+            )?;
+        }
+        Operator::TableCopy => {
+            let table = state.get_table(builder.func, 0, environ)?;
+            let (dst, src, len) = state.pop3();
+            environ.translate_table_copy(
+                builder.cursor(),
+                TableIndex::from_u32(0),
+                table,
+                TableIndex::from_u32(0),
+                table,
+                dst,
+                src,
+                len,
+            )?;
+        }
+        Operator::TableInit { segment } => {
+            let table = state.get_table(builder.func, 0, environ)?;
+            let (dst, src, len) = state.pop3();
+            environ.translate_table_init(
+                builder.cursor(),
+                ElemIndex::from_u32(*segment),
+                TableIndex::from_u32(0),
+                table,
+                dst,
+                src,
+                len,
+            )?;
+        }
+        Operator::ElemDrop { segment } => {
+            environ.translate_elem_drop(builder.cursor(), ElemIndex::from_u32(*segment))?;
         }
         Operator::V128Const { value } => {
             let data = value.bytes().to_vec().into();
@@ -1018,6 +1351,30 @@ pub fn translate_operator<FE: FuncEnvironment + ?Sized>(
             let splatted = builder.ins().splat(ty, value_to_splat);
             state.push1(splatted)
         }
+        Operator::I8x16LoadSplat {
+            memarg: MemoryImmediate { flags: _, offset },
+        }
+        | Operator::I16x8LoadSplat {
+            memarg: MemoryImmediate { flags: _, offset },
+        }
+        | Operator::I32x4LoadSplat {
+            memarg: MemoryImmediate { flags: _, offset },
+        }
+        | Operator::I64x2LoadSplat {
+            memarg: MemoryImmediate { flags: _, offset },
+        } => {
+            let ty = type_of(op);
+            translate_load(
+                *offset,
+                ir::Opcode::Load,
+                ty.lane_type(),
+                builder,
+                state,
+                environ,
+            )?;
+            let value_to_splat = state.pop1();
+            state.push1(builder.ins().splat(ty, value_to_splat))
+        }
         Operator::I8x16ExtractLaneS { lane } | Operator::I16x8ExtractLaneS { lane } => {
             let vector = optionally_bitcast_vector(state.pop1(), type_of(op), builder);
             let extracted = builder.ins().extractlane(vector, lane.clone());
@@ -1066,6 +1423,12 @@ pub fn translate_operator<FE: FuncEnvironment + ?Sized>(
             // to WASM using the less specific v128 type for certain operations and more specific
             // types (e.g. i8x16) for others.
         }
+        Operator::V8x16Swizzle => {
+            let (a, b) = state.pop2();
+            let bitcast_a = optionally_bitcast_vector(a, I8X16, builder);
+            let bitcast_b = optionally_bitcast_vector(b, I8X16, builder);
+            state.push1(builder.ins().swizzle(bitcast_a, bitcast_b))
+        }
         Operator::I8x16Add | Operator::I16x8Add | Operator::I32x4Add | Operator::I64x2Add => {
             let (a, b) = state.pop2();
             state.push1(builder.ins().iadd(a, b))
@@ -1094,7 +1457,7 @@ pub fn translate_operator<FE: FuncEnvironment + ?Sized>(
             let a = state.pop1();
             state.push1(builder.ins().ineg(a))
         }
-        Operator::I16x8Mul | Operator::I32x4Mul => {
+        Operator::I8x16Mul | Operator::I16x8Mul | Operator::I32x4Mul => {
             let (a, b) = state.pop2();
             state.push1(builder.ins().imul(a, b))
         }
@@ -1114,28 +1477,28 @@ pub fn translate_operator<FE: FuncEnvironment + ?Sized>(
             let (a, b) = state.pop2();
             state.push1(builder.ins().bxor(a, b));
         }
-        Operator::I16x8Shl | Operator::I32x4Shl | Operator::I64x2Shl => {
+        Operator::I8x16Shl | Operator::I16x8Shl | Operator::I32x4Shl | Operator::I64x2Shl => {
             let (a, b) = state.pop2();
             let bitcast_a = optionally_bitcast_vector(a, type_of(op), builder);
-            let bitwidth = i64::from(builder.func.dfg.value_type(a).bits());
+            let bitwidth = i64::from(type_of(op).lane_bits());
             // The spec expects to shift with `b mod lanewidth`; so, e.g., for 16 bit lane-width
             // we do `b AND 15`; this means fewer instructions than `iconst + urem`.
             let b_mod_bitwidth = builder.ins().band_imm(b, bitwidth - 1);
             state.push1(builder.ins().ishl(bitcast_a, b_mod_bitwidth))
         }
-        Operator::I16x8ShrU | Operator::I32x4ShrU | Operator::I64x2ShrU => {
+        Operator::I8x16ShrU | Operator::I16x8ShrU | Operator::I32x4ShrU | Operator::I64x2ShrU => {
             let (a, b) = state.pop2();
             let bitcast_a = optionally_bitcast_vector(a, type_of(op), builder);
-            let bitwidth = i64::from(builder.func.dfg.value_type(a).bits());
+            let bitwidth = i64::from(type_of(op).lane_bits());
             // The spec expects to shift with `b mod lanewidth`; so, e.g., for 16 bit lane-width
             // we do `b AND 15`; this means fewer instructions than `iconst + urem`.
             let b_mod_bitwidth = builder.ins().band_imm(b, bitwidth - 1);
             state.push1(builder.ins().ushr(bitcast_a, b_mod_bitwidth))
         }
-        Operator::I16x8ShrS | Operator::I32x4ShrS => {
+        Operator::I8x16ShrS | Operator::I16x8ShrS | Operator::I32x4ShrS | Operator::I64x2ShrS => {
             let (a, b) = state.pop2();
             let bitcast_a = optionally_bitcast_vector(a, type_of(op), builder);
-            let bitwidth = i64::from(builder.func.dfg.value_type(a).bits());
+            let bitwidth = i64::from(type_of(op).lane_bits());
             // The spec expects to shift with `b mod lanewidth`; so, e.g., for 16 bit lane-width
             // we do `b AND 15`; this means fewer instructions than `iconst + urem`.
             let b_mod_bitwidth = builder.ins().band_imm(b, bitwidth - 1);
@@ -1197,55 +1560,91 @@ pub fn translate_operator<FE: FuncEnvironment + ?Sized>(
         Operator::I8x16LeU | Operator::I16x8LeU | Operator::I32x4LeU => {
             translate_vector_icmp(IntCC::UnsignedLessThanOrEqual, type_of(op), builder, state)
         }
-        Operator::F32x4Eq
-        | Operator::F32x4Ne
-        | Operator::F32x4Lt
-        | Operator::F32x4Gt
-        | Operator::F32x4Le
-        | Operator::F32x4Ge
-        | Operator::F64x2Eq
-        | Operator::F64x2Ne
-        | Operator::F64x2Lt
-        | Operator::F64x2Gt
-        | Operator::F64x2Le
-        | Operator::F64x2Ge
-        | Operator::I8x16Shl
-        | Operator::I8x16ShrS
-        | Operator::I8x16ShrU
-        | Operator::I8x16Mul
-        | Operator::I64x2ShrS
-        | Operator::F32x4Abs
-        | Operator::F32x4Neg
-        | Operator::F32x4Sqrt
-        | Operator::F32x4Add
-        | Operator::F32x4Sub
-        | Operator::F32x4Mul
-        | Operator::F32x4Div
-        | Operator::F32x4Min
-        | Operator::F32x4Max
-        | Operator::F64x2Abs
-        | Operator::F64x2Neg
-        | Operator::F64x2Sqrt
-        | Operator::F64x2Add
-        | Operator::F64x2Sub
-        | Operator::F64x2Mul
-        | Operator::F64x2Div
-        | Operator::F64x2Min
-        | Operator::F64x2Max
-        | Operator::I32x4TruncSF32x4Sat
-        | Operator::I32x4TruncUF32x4Sat
-        | Operator::I64x2TruncSF64x2Sat
-        | Operator::I64x2TruncUF64x2Sat
-        | Operator::F32x4ConvertSI32x4
-        | Operator::F32x4ConvertUI32x4
-        | Operator::F64x2ConvertSI64x2
-        | Operator::F64x2ConvertUI64x2 { .. }
-        | Operator::V8x16Swizzle
-        | Operator::I8x16LoadSplat { .. }
-        | Operator::I16x8LoadSplat { .. }
-        | Operator::I32x4LoadSplat { .. }
-        | Operator::I64x2LoadSplat { .. } => {
-            return Err(wasm_unsupported!("proposed SIMD operator {:?}", op));
+        Operator::F32x4Eq | Operator::F64x2Eq => {
+            translate_vector_fcmp(FloatCC::Equal, type_of(op), builder, state)
+        }
+        Operator::F32x4Ne | Operator::F64x2Ne => {
+            translate_vector_fcmp(FloatCC::NotEqual, type_of(op), builder, state)
+        }
+        Operator::F32x4Lt | Operator::F64x2Lt => {
+            translate_vector_fcmp(FloatCC::LessThan, type_of(op), builder, state)
+        }
+        Operator::F32x4Gt | Operator::F64x2Gt => {
+            translate_vector_fcmp(FloatCC::GreaterThan, type_of(op), builder, state)
+        }
+        Operator::F32x4Le | Operator::F64x2Le => {
+            translate_vector_fcmp(FloatCC::LessThanOrEqual, type_of(op), builder, state)
+        }
+        Operator::F32x4Ge | Operator::F64x2Ge => {
+            translate_vector_fcmp(FloatCC::GreaterThanOrEqual, type_of(op), builder, state)
+        }
+        Operator::F32x4Abs | Operator::F64x2Abs => {
+            let a = optionally_bitcast_vector(state.pop1(), type_of(op), builder);
+            state.push1(builder.ins().fabs(a))
+        }
+        Operator::F32x4Neg | Operator::F64x2Neg => {
+            let a = optionally_bitcast_vector(state.pop1(), type_of(op), builder);
+            state.push1(builder.ins().fneg(a))
+        }
+        Operator::F32x4Sqrt | Operator::F64x2Sqrt => {
+            let a = optionally_bitcast_vector(state.pop1(), type_of(op), builder);
+            state.push1(builder.ins().sqrt(a))
+        }
+        Operator::F32x4Add | Operator::F64x2Add => {
+            let (a, b) = pop2_with_bitcast(state, type_of(op), builder);
+            state.push1(builder.ins().fadd(a, b))
+        }
+        Operator::F32x4Sub | Operator::F64x2Sub => {
+            let (a, b) = pop2_with_bitcast(state, type_of(op), builder);
+            state.push1(builder.ins().fsub(a, b))
+        }
+        Operator::F32x4Mul | Operator::F64x2Mul => {
+            let (a, b) = pop2_with_bitcast(state, type_of(op), builder);
+            state.push1(builder.ins().fmul(a, b))
+        }
+        Operator::F32x4Div | Operator::F64x2Div => {
+            let (a, b) = pop2_with_bitcast(state, type_of(op), builder);
+            state.push1(builder.ins().fdiv(a, b))
+        }
+        Operator::F32x4Min | Operator::F64x2Min => {
+            let (a, b) = pop2_with_bitcast(state, type_of(op), builder);
+            state.push1(builder.ins().fmin(a, b))
+        }
+        Operator::F32x4Max | Operator::F64x2Max => {
+            let (a, b) = pop2_with_bitcast(state, type_of(op), builder);
+            state.push1(builder.ins().fmax(a, b))
+        }
+        Operator::I32x4TruncSF32x4Sat => {
+            let a = optionally_bitcast_vector(state.pop1(), type_of(op), builder);
+            state.push1(builder.ins().fcvt_to_sint_sat(I32X4, a))
+        }
+        Operator::I32x4TruncUF32x4Sat => {
+            let a = optionally_bitcast_vector(state.pop1(), type_of(op), builder);
+            state.push1(builder.ins().fcvt_to_uint_sat(I32X4, a))
+        }
+        Operator::I64x2TruncSF64x2Sat => {
+            let a = optionally_bitcast_vector(state.pop1(), type_of(op), builder);
+            state.push1(builder.ins().fcvt_to_sint_sat(I64X2, a))
+        }
+        Operator::I64x2TruncUF64x2Sat => {
+            let a = optionally_bitcast_vector(state.pop1(), type_of(op), builder);
+            state.push1(builder.ins().fcvt_to_uint_sat(I64X2, a))
+        }
+        Operator::F32x4ConvertSI32x4 => {
+            let a = optionally_bitcast_vector(state.pop1(), type_of(op), builder);
+            state.push1(builder.ins().fcvt_from_sint(F32X4, a))
+        }
+        Operator::F32x4ConvertUI32x4 => {
+            let a = optionally_bitcast_vector(state.pop1(), type_of(op), builder);
+            state.push1(builder.ins().fcvt_from_uint(F32X4, a))
+        }
+        Operator::F64x2ConvertSI64x2 => {
+            let a = optionally_bitcast_vector(state.pop1(), type_of(op), builder);
+            state.push1(builder.ins().fcvt_from_sint(F64X2, a))
+        }
+        Operator::F64x2ConvertUI64x2 => {
+            let a = optionally_bitcast_vector(state.pop1(), type_of(op), builder);
+            state.push1(builder.ins().fcvt_from_uint(F64X2, a))
         }
     };
     Ok(())
@@ -1431,7 +1830,8 @@ fn translate_load<FE: FuncEnvironment + ?Sized>(
     // Note that we don't set `is_aligned` here, even if the load instruction's
     // alignment immediate says it's aligned, because WebAssembly's immediate
     // field is just a hint, while Cranelift's aligned flag needs a guarantee.
-    let flags = MemFlags::new();
+    let mut flags = MemFlags::new();
+    flags.set_region(MemRegion::Heap);
     let (load, dfg) = builder
         .ins()
         .Load(opcode, result_ty, flags, offset.into(), base);
@@ -1454,13 +1854,163 @@ fn translate_store<FE: FuncEnvironment + ?Sized>(
     let heap = state.get_heap(builder.func, 0, environ)?;
     let (base, offset) = get_heap_addr(heap, addr32, offset, environ.pointer_type(), builder);
     // See the comments in `translate_load` about the flags.
-    let flags = MemFlags::new();
+    let mut flags = MemFlags::new();
+    flags.set_region(MemRegion::Heap);
     builder
         .ins()
         .Store(opcode, val_ty, flags, offset.into(), val, base);
     Ok(())
 }
 
+/// Get the native address to use for an atomic heap access of type `access_ty`.
+///
+/// Atomic instructions don't take an offset immediate, so the offset is folded into the
+/// returned address. WebAssembly requires atomic accesses to be naturally aligned, so a trap is
+/// inserted if the effective address is misaligned.
+fn get_atomic_heap_addr<FE: FuncEnvironment + ?Sized>(
+    heap: ir::Heap,
+    addr32: ir::Value,
+    offset: u32,
+    access_ty: Type,
+    environ: &FE,
+    builder: &mut FunctionBuilder,
+) -> ir::Value {
+    let (base, offset) = get_heap_addr(heap, addr32, offset, environ.pointer_type(), builder);
+    let addr = if offset != 0 {
+        builder.ins().iadd_imm(base, i64::from(offset))
+    } else {
+        base
+    };
+    let access_size = i64::from(access_ty.bytes());
+    if access_size > 1 {
+        let misalignment = builder.ins().band_imm(addr, access_size - 1);
+        builder
+            .ins()
+            .trapnz(misalignment, ir::TrapCode::HeapMisaligned);
+    }
+    addr
+}
+
+/// Memory flags for an atomic access through an address computed by `get_atomic_heap_addr`.
+fn atomic_mem_flags() -> MemFlags {
+    // Unlike plain loads and stores, the alignment of atomic accesses has been checked.
+    let mut flags = MemFlags::new();
+    flags.set_aligned();
+    flags.set_region(MemRegion::Heap);
+    flags
+}
+
+/// Translate an atomic load of `access_ty`, zero-extending the loaded value to `result_ty`.
+fn translate_atomic_load<FE: FuncEnvironment + ?Sized>(
+    offset: u32,
+    access_ty: Type,
+    result_ty: Type,
+    builder: &mut FunctionBuilder,
+    state: &mut FuncTranslationState,
+    environ: &mut FE,
+) -> WasmResult<()> {
+    let addr32 = state.pop1();
+    // We don't yet support multiple linear memories.
+    let heap = state.get_heap(builder.func, 0, environ)?;
+    let addr = get_atomic_heap_addr(heap, addr32, offset, access_ty, environ, builder);
+    let mut loaded = builder.ins().atomic_load(
+        access_ty,
+        ir::AtomicOrdering::SeqCst,
+        atomic_mem_flags(),
+        addr,
+    );
+    if access_ty != result_ty {
+        loaded = builder.ins().uextend(result_ty, loaded);
+    }
+    state.push1(loaded);
+    Ok(())
+}
+
+/// Translate an atomic store of the low `access_ty` bits of the operand.
+fn translate_atomic_store<FE: FuncEnvironment + ?Sized>(
+    offset: u32,
+    access_ty: Type,
+    builder: &mut FunctionBuilder,
+    state: &mut FuncTranslationState,
+    environ: &mut FE,
+) -> WasmResult<()> {
+    let (addr32, mut val) = state.pop2();
+    if builder.func.dfg.value_type(val) != access_ty {
+        val = builder.ins().ireduce(access_ty, val);
+    }
+    // We don't yet support multiple linear memories.
+    let heap = state.get_heap(builder.func, 0, environ)?;
+    let addr = get_atomic_heap_addr(heap, addr32, offset, access_ty, environ, builder);
+    builder
+        .ins()
+        .atomic_store(ir::AtomicOrdering::SeqCst, atomic_mem_flags(), val, addr);
+    Ok(())
+}
+
+/// Translate an atomic read-modify-write of `access_ty`. The old value is zero-extended back to
+/// the type of the operand.
+fn translate_atomic_rmw<FE: FuncEnvironment + ?Sized>(
+    offset: u32,
+    op: ir::AtomicRmwOp,
+    access_ty: Type,
+    builder: &mut FunctionBuilder,
+    state: &mut FuncTranslationState,
+    environ: &mut FE,
+) -> WasmResult<()> {
+    let (addr32, mut val) = state.pop2();
+    let result_ty = builder.func.dfg.value_type(val);
+    if result_ty != access_ty {
+        val = builder.ins().ireduce(access_ty, val);
+    }
+    // We don't yet support multiple linear memories.
+    let heap = state.get_heap(builder.func, 0, environ)?;
+    let addr = get_atomic_heap_addr(heap, addr32, offset, access_ty, environ, builder);
+    let mut old = builder.ins().atomic_rmw(
+        op,
+        ir::AtomicOrdering::SeqCst,
+        atomic_mem_flags(),
+        addr,
+        val,
+    );
+    if result_ty != access_ty {
+        old = builder.ins().uextend(result_ty, old);
+    }
+    state.push1(old);
+    Ok(())
+}
+
+/// Translate an atomic compare-and-swap of `access_ty`. Both operands are truncated to
+/// `access_ty` and the old value is zero-extended back to the type of the operands.
+fn translate_atomic_cas<FE: FuncEnvironment + ?Sized>(
+    offset: u32,
+    access_ty: Type,
+    builder: &mut FunctionBuilder,
+    state: &mut FuncTranslationState,
+    environ: &mut FE,
+) -> WasmResult<()> {
+    let (addr32, mut expected, mut replacement) = state.pop3();
+    let result_ty = builder.func.dfg.value_type(expected);
+    if result_ty != access_ty {
+        expected = builder.ins().ireduce(access_ty, expected);
+        replacement = builder.ins().ireduce(access_ty, replacement);
+    }
+    // We don't yet support multiple linear memories.
+    let heap = state.get_heap(builder.func, 0, environ)?;
+    let addr = get_atomic_heap_addr(heap, addr32, offset, access_ty, environ, builder);
+    let mut old = builder.ins().atomic_cas(
+        ir::AtomicOrdering::SeqCst,
+        atomic_mem_flags(),
+        addr,
+        expected,
+        replacement,
+    );
+    if result_ty != access_ty {
+        old = builder.ins().uextend(result_ty, old);
+    }
+    state.push1(old);
+    Ok(())
+}
+
 fn translate_icmp(cc: IntCC, builder: &mut FunctionBuilder, state: &mut FuncTranslationState) {
     let (arg0, arg1) = state.pop2();
     let val = builder.ins().icmp(cc, arg0, arg1);
@@ -1479,6 +2029,16 @@ fn translate_vector_icmp(
     state.push1(builder.ins().icmp(cc, bitcast_a, bitcast_b))
 }
 
+fn translate_vector_fcmp(
+    cc: FloatCC,
+    needed_type: Type,
+    builder: &mut FunctionBuilder,
+    state: &mut FuncTranslationState,
+) {
+    let (a, b) = pop2_with_bitcast(state, needed_type, builder);
+    state.push1(builder.ins().fcmp(cc, a, b))
+}
+
 fn translate_fcmp(cc: FloatCC, builder: &mut FunctionBuilder, state: &mut FuncTranslationState) {
     let (arg0, arg1) = state.pop2();
     let val = builder.ins().fcmp(cc, arg0, arg1);
@@ -1537,7 +2097,9 @@ fn type_of(operator: &Operator) -> Type {
         | Operator::V128Bitselect => I8X16, // default type representing V128
 
         Operator::V8x16Shuffle { .. }
+        | Operator::V8x16Swizzle
         | Operator::I8x16Splat
+        | Operator::I8x16LoadSplat { .. }
         | Operator::I8x16ExtractLaneS { .. }
         | Operator::I8x16ExtractLaneU { .. }
         | Operator::I8x16ReplaceLane { .. }
@@ -1566,6 +2128,7 @@ fn type_of(operator: &Operator) -> Type {
         | Operator::I8x16Mul => I8X16,
 
         Operator::I16x8Splat
+        | Operator::I16x8LoadSplat { .. }
         | Operator::I16x8ExtractLaneS { .. }
         | Operator::I16x8ExtractLaneU { .. }
         | Operator::I16x8ReplaceLane { .. }
@@ -1594,6 +2157,7 @@ fn type_of(operator: &Operator) -> Type {
         | Operator::I16x8Mul => I16X8,
 
         Operator::I32x4Splat
+        | Operator::I32x4LoadSplat { .. }
         | Operator::I32x4ExtractLane { .. }
         | Operator::I32x4ReplaceLane { .. }
         | Operator::I32x4Eq
@@ -1619,6 +2183,7 @@ fn type_of(operator: &Operator) -> Type {
         | Operator::F32x4ConvertUI32x4 => I32X4,
 
         Operator::I64x2Splat
+        | Operator::I64x2LoadSplat { .. }
         | Operator::I64x2ExtractLane { .. }
         | Operator::I64x2ReplaceLane { .. }
         | Operator::I64x2Neg
@@ -1674,10 +2239,7 @@ fn type_of(operator: &Operator) -> Type {
         | Operator::I64x2TruncSF64x2Sat
         | Operator::I64x2TruncUF64x2Sat => F64X2,
 
-        _ => unimplemented!(
-            "Currently only the SIMD instructions are translated to their return type: {:?}",
-            operator
-        ),
+        _ => unreachable!("only the SIMD operators have a vector type: {:?}", operator),
     }
 }
 
@@ -1694,3 +2256,16 @@ fn optionally_bitcast_vector(
         value
     }
 }
+
+/// Pop two vectors off the stack, converting them to `needed_type` if necessary; Wasm's `v128`
+/// values may reach a lane-typed operator with any CLIF vector type.
+fn pop2_with_bitcast(
+    state: &mut FuncTranslationState,
+    needed_type: Type,
+    builder: &mut FunctionBuilder,
+) -> (Value, Value) {
+    let (a, b) = state.pop2();
+    let bitcast_a = optionally_bitcast_vector(a, needed_type, builder);
+    let bitcast_b = optionally_bitcast_vector(b, needed_type, builder);
+    (bitcast_a, bitcast_b)
+}
@@ -8,7 +8,8 @@
 
 use crate::state::{FuncTranslationState, ModuleTranslationState};
 use crate::translation_utils::{
-    FuncIndex, Global, GlobalIndex, Memory, MemoryIndex, SignatureIndex, Table, TableIndex,
+    DataIndex, ElemIndex, FuncIndex, Global, GlobalIndex, Memory, MemoryIndex, SignatureIndex,
+    Table, TableIndex,
 };
 use core::convert::From;
 use cranelift_codegen::cursor::FuncCursor;
@@ -266,6 +267,202 @@ pub trait FuncEnvironment {
         heap: ir::Heap,
     ) -> WasmResult<ir::Value>;
 
+    /// Translates a `memory.atomic.wait32` or `memory.atomic.wait64` WebAssembly instruction.
+    ///
+    /// The `index` provided identifies the linear memory containing the value to wait on, and
+    /// `heap` is the heap reference returned by `make_heap` for the same index. `addr` is the
+    /// native address of the value; it has already been bounds checked and is known to be
+    /// naturally aligned for the type of `expected`. `timeout` is an `i64` number of nanoseconds,
+    /// where a negative value means no timeout.
+    ///
+    /// Returns an `i32` which is 0 if the thread was woken, 1 if the value at `addr` was not equal
+    /// to `expected`, and 2 if the wait timed out.
+    fn translate_atomic_wait(
+        &mut self,
+        _pos: FuncCursor,
+        _index: MemoryIndex,
+        _heap: ir::Heap,
+        _addr: ir::Value,
+        _expected: ir::Value,
+        _timeout: ir::Value,
+    ) -> WasmResult<ir::Value> {
+        Err(wasm_unsupported!("memory.atomic.wait"))
+    }
+
+    /// Translates a `memory.atomic.notify` WebAssembly instruction.
+    ///
+    /// The `index`, `heap` and `addr` arguments are as for `translate_atomic_wait`, with `addr`
+    /// known to be 4-byte aligned. `count` is the maximum number of waiters to wake.
+    ///
+    /// Returns the number of waiters that were woken, as an `i32`.
+    fn translate_atomic_notify(
+        &mut self,
+        _pos: FuncCursor,
+        _index: MemoryIndex,
+        _heap: ir::Heap,
+        _addr: ir::Value,
+        _count: ir::Value,
+    ) -> WasmResult<ir::Value> {
+        Err(wasm_unsupported!("memory.atomic.notify"))
+    }
+
+    /// Translates a `memory.copy` WebAssembly instruction.
+    ///
+    /// The `index` provided identifies the linear memory to copy within, and `heap` is the heap
+    /// reference returned by `make_heap` for the same index. `dst`, `src` and `len` are the
+    /// unchecked `i32` operands; the environment is responsible for trapping if either range is
+    /// out of bounds.
+    fn translate_memory_copy(
+        &mut self,
+        _pos: FuncCursor,
+        _index: MemoryIndex,
+        _heap: ir::Heap,
+        _dst: ir::Value,
+        _src: ir::Value,
+        _len: ir::Value,
+    ) -> WasmResult<()> {
+        Err(wasm_unsupported!("memory.copy"))
+    }
+
+    /// Translates a `memory.fill` WebAssembly instruction.
+    ///
+    /// Sets `len` bytes starting at `dst` to the low byte of `val`. The other arguments are as
+    /// for `translate_memory_copy`.
+    fn translate_memory_fill(
+        &mut self,
+        _pos: FuncCursor,
+        _index: MemoryIndex,
+        _heap: ir::Heap,
+        _dst: ir::Value,
+        _val: ir::Value,
+        _len: ir::Value,
+    ) -> WasmResult<()> {
+        Err(wasm_unsupported!("memory.fill"))
+    }
+
+    /// Translates a `memory.init` WebAssembly instruction.
+    ///
+    /// Copies `len` bytes starting at offset `src` of the passive data segment `seg_index` to
+    /// `dst` in the linear memory identified by `index` and `heap`.
+    #[cfg_attr(feature = "cargo-clippy", allow(clippy::too_many_arguments))]
+    fn translate_memory_init(
+        &mut self,
+        _pos: FuncCursor,
+        _index: MemoryIndex,
+        _heap: ir::Heap,
+        _seg_index: DataIndex,
+        _dst: ir::Value,
+        _src: ir::Value,
+        _len: ir::Value,
+    ) -> WasmResult<()> {
+        Err(wasm_unsupported!("memory.init"))
+    }
+
+    /// Translates a `data.drop` WebAssembly instruction.
+    fn translate_data_drop(&mut self, _pos: FuncCursor, _seg_index: DataIndex) -> WasmResult<()> {
+        Err(wasm_unsupported!("data.drop"))
+    }
+
+    /// Translates a `table.size` WebAssembly instruction.
+    ///
+    /// The `index` provided identifies the table to query, and `table` is the table reference
+    /// returned by `make_table` for the same index.
+    ///
+    /// Returns the number of elements in the table, as an `i32`.
+    fn translate_table_size(
+        &mut self,
+        _pos: FuncCursor,
+        _index: TableIndex,
+        _table: ir::Table,
+    ) -> WasmResult<ir::Value> {
+        Err(wasm_unsupported!("table.size"))
+    }
+
+    /// Translates a `table.grow` WebAssembly instruction.
+    ///
+    /// Grows the table by `delta` elements, all set to `init_value`.
+    ///
+    /// Returns the old number of elements in the table, or -1 if the table couldn't be grown.
+    fn translate_table_grow(
+        &mut self,
+        _pos: FuncCursor,
+        _index: TableIndex,
+        _table: ir::Table,
+        _delta: ir::Value,
+        _init_value: ir::Value,
+    ) -> WasmResult<ir::Value> {
+        Err(wasm_unsupported!("table.grow"))
+    }
+
+    /// Translates a `table.get` WebAssembly instruction.
+    ///
+    /// Returns the reference stored at `elem_index`, trapping if it is out of bounds.
+    fn translate_table_get(
+        &mut self,
+        _pos: FuncCursor,
+        _index: TableIndex,
+        _table: ir::Table,
+        _elem_index: ir::Value,
+    ) -> WasmResult<ir::Value> {
+        Err(wasm_unsupported!("table.get"))
+    }
+
+    /// Translates a `table.set` WebAssembly instruction.
+    ///
+    /// Stores the reference `value` at `elem_index`, trapping if it is out of bounds.
+    fn translate_table_set(
+        &mut self,
+        _pos: FuncCursor,
+        _index: TableIndex,
+        _table: ir::Table,
+        _value: ir::Value,
+        _elem_index: ir::Value,
+    ) -> WasmResult<()> {
+        Err(wasm_unsupported!("table.set"))
+    }
+
+    /// Translates a `table.copy` WebAssembly instruction.
+    ///
+    /// Copies `len` elements starting at `src` in the source table to `dst` in the destination
+    /// table, which may be the same table.
+    #[cfg_attr(feature = "cargo-clippy", allow(clippy::too_many_arguments))]
+    fn translate_table_copy(
+        &mut self,
+        _pos: FuncCursor,
+        _dst_table_index: TableIndex,
+        _dst_table: ir::Table,
+        _src_table_index: TableIndex,
+        _src_table: ir::Table,
+        _dst: ir::Value,
+        _src: ir::Value,
+        _len: ir::Value,
+    ) -> WasmResult<()> {
+        Err(wasm_unsupported!("table.copy"))
+    }
+
+    /// Translates a `table.init` WebAssembly instruction.
+    ///
+    /// Copies `len` elements starting at offset `src` of the passive element segment `seg_index`
+    /// to `dst` in the table identified by `table_index` and `table`.
+    #[cfg_attr(feature = "cargo-clippy", allow(clippy::too_many_arguments))]
+    fn translate_table_init(
+        &mut self,
+        _pos: FuncCursor,
+        _seg_index: ElemIndex,
+        _table_index: TableIndex,
+        _table: ir::Table,
+        _dst: ir::Value,
+        _src: ir::Value,
+        _len: ir::Value,
+    ) -> WasmResult<()> {
+        Err(wasm_unsupported!("table.init"))
+    }
+
+    /// Translates an `elem.drop` WebAssembly instruction.
+    fn translate_elem_drop(&mut self, _pos: FuncCursor, _seg_index: ElemIndex) -> WasmResult<()> {
+        Err(wasm_unsupported!("elem.drop"))
+    }
+
     /// Emit code at the beginning of every wasm loop.
     ///
     /// This can be used to insert explicit interrupt or safepoint checking at
@@ -337,6 +534,8 @@ pub trait ModuleEnvironment<'data> {
     ) -> WasmResult<()>;
 
     /// Declares a memory import to the environment.
+    ///
+    /// Shared memories are handled as in `declare_memory`.
     fn declare_memory_import(
         &mut self,
         memory: Memory,
@@ -381,7 +580,12 @@ pub trait ModuleEnvironment<'data> {
         Ok(())
     }
 
-    /// Declares a memory to the environment
+    /// Declares a memory to the environment.
+    ///
+    /// Memories with `shared` set may be accessed concurrently by several threads through atomic
+    /// instructions, so the environment must never move them, even when they grow. The translator
+    /// rejects shared memories without a maximum size, so the environment can reserve the whole
+    /// memory up front.
     fn declare_memory(&mut self, memory: Memory) -> WasmResult<()>;
 
     /// Provides the number of defined globals up front. By default this does nothing, but
@@ -443,6 +647,15 @@ pub trait ModuleEnvironment<'data> {
         elements: Box<[FuncIndex]>,
     ) -> WasmResult<()>;
 
+    /// Declares a passive element segment, which is only copied into a table by `table.init`.
+    fn declare_passive_element(
+        &mut self,
+        _elem_index: ElemIndex,
+        _elements: Box<[FuncIndex]>,
+    ) -> WasmResult<()> {
+        Err(wasm_unsupported!("passive element segments"))
+    }
+
     /// Provides the contents of a function body.
     ///
     /// Note there's no `reserve_function_bodies` function because the number of
@@ -460,6 +673,22 @@ pub trait ModuleEnvironment<'data> {
         Ok(())
     }
 
+    /// Provides the number of data segments declared by the data count section, which precedes
+    /// the code section of modules using `memory.init` or `data.drop`. By default this does
+    /// nothing.
+    fn reserve_passive_data(&mut self, _count: u32) -> WasmResult<()> {
+        Ok(())
+    }
+
+    /// Declares a passive data segment, which is only copied into memory by `memory.init`.
+    fn declare_passive_data(
+        &mut self,
+        _data_index: DataIndex,
+        _data: &'data [u8],
+    ) -> WasmResult<()> {
+        Err(wasm_unsupported!("passive data segments"))
+    }
+
     /// Fills a declared memory with bytes at module instantiation.
     fn declare_data_initialization(
         &mut self,
@@ -7,11 +7,11 @@
 //! The special case of the initialize expressions for table elements offsets or global variables
 //! is handled, according to the semantics of WebAssembly, to only specific expressions that are
 //! interpreted on the fly.
-use crate::environ::{ModuleEnvironment, WasmResult};
+use crate::environ::{ModuleEnvironment, WasmError, WasmResult};
 use crate::state::ModuleTranslationState;
 use crate::translation_utils::{
-    tabletype_to_type, type_to_type, FuncIndex, Global, GlobalIndex, GlobalInit, Memory,
-    MemoryIndex, SignatureIndex, Table, TableElementType, TableIndex,
+    tabletype_to_type, type_to_type, DataIndex, ElemIndex, FuncIndex, Global, GlobalIndex,
+    GlobalInit, Memory, MemoryIndex, SignatureIndex, Table, TableElementType, TableIndex,
 };
 use crate::{wasm_unsupported, HashMap};
 use core::convert::TryFrom;
@@ -27,6 +27,23 @@ use wasmparser::{
     Operator, TableSectionReader, TypeSectionReader,
 };
 
+/// Translate the type of a memory defined or imported at `offset`.
+///
+/// Shared memories can't be moved when they grow, so they must have a maximum size.
+fn translate_memory(memory: &MemoryType, offset: usize) -> WasmResult<Memory> {
+    if memory.shared && memory.limits.maximum.is_none() {
+        return Err(WasmError::InvalidWebAssembly {
+            message: "shared memory must have a maximum size",
+            offset,
+        });
+    }
+    Ok(Memory {
+        minimum: memory.limits.initial,
+        maximum: memory.limits.maximum,
+        shared: memory.shared,
+    })
+}
+
 /// Parses the Type section of the wasm module.
 pub fn parse_type_section(
     types: TypeSectionReader,
@@ -71,13 +88,14 @@ pub fn parse_type_section(
 
 /// Parses the Import section of the wasm module.
 pub fn parse_import_section<'data>(
-    imports: ImportSectionReader<'data>,
+    mut imports: ImportSectionReader<'data>,
     environ: &mut dyn ModuleEnvironment<'data>,
 ) -> WasmResult<()> {
     environ.reserve_imports(imports.get_count())?;
 
-    for entry in imports {
-        let import = entry?;
+    for _ in 0..imports.get_count() {
+        let offset = imports.original_position();
+        let import = imports.read()?;
         let module_name = import.module;
         let field_name = import.field;
 
@@ -89,16 +107,9 @@ pub fn parse_import_section<'data>(
                     field_name,
                 )?;
             }
-            ImportSectionEntryType::Memory(MemoryType {
-                limits: ref memlimits,
-                shared,
-            }) => {
+            ImportSectionEntryType::Memory(ref memory) => {
                 environ.declare_memory_import(
-                    Memory {
-                        minimum: memlimits.initial,
-                        maximum: memlimits.maximum,
-                        shared,
-                    },
+                    translate_memory(memory, offset)?,
                     module_name,
                     field_name,
                 )?;
@@ -174,18 +185,15 @@ pub fn parse_table_section(
 
 /// Parses the Memory section of the wasm module.
 pub fn parse_memory_section(
-    memories: MemorySectionReader,
+    mut memories: MemorySectionReader,
     environ: &mut dyn ModuleEnvironment,
 ) -> WasmResult<()> {
     environ.reserve_memories(memories.get_count())?;
 
-    for entry in memories {
-        let memory = entry?;
-        environ.declare_memory(Memory {
-            minimum: memory.limits.initial,
-            maximum: memory.limits.maximum,
-            shared: memory.shared,
-        })?;
+    for _ in 0..memories.get_count() {
+        let offset = memories.original_position();
+        let memory = memories.read()?;
+        environ.declare_memory(translate_memory(&memory, offset)?)?;
     }
 
     Ok(())
@@ -283,8 +291,14 @@ pub fn parse_element_section<'data>(
 ) -> WasmResult<()> {
     environ.reserve_table_elements(elements.get_count())?;
 
-    for entry in elements {
+    for (index, entry) in elements.into_iter().enumerate() {
         let Element { kind, items } = entry?;
+        let items_reader = items.get_items_reader()?;
+        let mut elems = Vec::with_capacity(usize::try_from(items_reader.get_count()).unwrap());
+        for item in items_reader {
+            let x = item?;
+            elems.push(FuncIndex::from_u32(x));
+        }
         if let ElementKind::Active {
             table_index,
             init_expr,
@@ -303,12 +317,6 @@ pub fn parse_element_section<'data>(
                     ));
                 }
             };
-            let items_reader = items.get_items_reader()?;
-            let mut elems = Vec::with_capacity(usize::try_from(items_reader.get_count()).unwrap());
-            for item in items_reader {
-                let x = item?;
-                elems.push(FuncIndex::from_u32(x));
-            }
             environ.declare_table_elements(
                 TableIndex::from_u32(table_index),
                 base,
@@ -316,10 +324,8 @@ pub fn parse_element_section<'data>(
                 elems.into_boxed_slice(),
             )?
         } else {
-            return Err(wasm_unsupported!(
-                "unsupported passive elements section: {:?}",
-                kind
-            ));
+            let index = ElemIndex::from_u32(index as u32);
+            environ.declare_passive_element(index, elems.into_boxed_slice())?;
         }
     }
     Ok(())
@@ -347,7 +353,7 @@ pub fn parse_data_section<'data>(
 ) -> WasmResult<()> {
     environ.reserve_data_initializers(data.get_count())?;
 
-    for entry in data {
+    for (index, entry) in data.into_iter().enumerate() {
         let Data { kind, data } = entry?;
         if let DataKind::Active {
             memory_index,
@@ -374,10 +380,8 @@ pub fn parse_data_section<'data>(
                 data,
             )?;
         } else {
-            return Err(wasm_unsupported!(
-                "unsupported passive data section: {:?}",
-                kind
-            ));
+            let index = DataIndex::from_u32(index as u32);
+            environ.declare_passive_data(index, data)?;
         }
     }
 
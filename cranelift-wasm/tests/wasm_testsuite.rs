@@ -2,7 +2,9 @@ use cranelift_codegen::isa;
 use cranelift_codegen::print_errors::pretty_verifier_error;
 use cranelift_codegen::settings::{self, Flags};
 use cranelift_codegen::verifier;
-use cranelift_wasm::{translate_module, DummyEnvironment, FuncIndex, ReturnMode};
+use cranelift_wasm::{
+    translate_module, DummyEnvironment, FuncIndex, MemoryIndex, ReturnMode, WasmError,
+};
 use std::fs;
 use std::fs::File;
 use std::io;
@@ -71,6 +73,51 @@ fn use_name_section() {
     );
 }
 
+#[test]
+fn shared_memory() {
+    let wat = r#"
+        (module
+            (memory 1 2 shared)
+        )"#;
+    let mut features = Features::new();
+    features.enable_threads();
+    let data = wat2wasm_with_features(wat, features)
+        .unwrap_or_else(|e| panic!("error converting wat to wasm: {:?}", e));
+
+    let flags = Flags::new(settings::builder());
+    let triple = triple!("riscv64");
+    let isa = isa::lookup(triple).unwrap().finish(flags.clone());
+    let return_mode = ReturnMode::NormalReturns;
+    let mut dummy_environ = DummyEnvironment::new(isa.frontend_config(), return_mode, false);
+
+    translate_module(data.as_ref(), &mut dummy_environ).unwrap();
+
+    let memory = dummy_environ.info.memories[MemoryIndex::from_u32(0)].entity;
+    assert!(memory.shared);
+    assert_eq!(memory.minimum, 1);
+    assert_eq!(memory.maximum, Some(2));
+}
+
+#[test]
+fn shared_memory_without_maximum() {
+    // A memory section declaring a shared memory with only a minimum size, which wat2wasm
+    // refuses to produce.
+    let data = [
+        0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, 0x05, 0x03, 0x01, 0x02, 0x01,
+    ];
+
+    let flags = Flags::new(settings::builder());
+    let triple = triple!("riscv64");
+    let isa = isa::lookup(triple).unwrap().finish(flags.clone());
+    let return_mode = ReturnMode::NormalReturns;
+    let mut dummy_environ = DummyEnvironment::new(isa.frontend_config(), return_mode, false);
+
+    match translate_module(&data, &mut dummy_environ) {
+        Err(WasmError::InvalidWebAssembly { offset, .. }) => assert_eq!(offset, 11),
+        other => panic!("unexpected result: {:?}", other),
+    }
+}
+
 fn read_file(path: &Path) -> io::Result<Vec<u8>> {
     let mut buf: Vec<u8> = Vec::new();
     let mut file = File::open(path)?;
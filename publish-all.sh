@@ -41,7 +41,7 @@ echo "# Don't forget to click the above link to open a pull-request!"
 echo git push origin v$version
 for crate in \
     entity bforest codegen/shared codegen/meta codegen frontend native \
-    preopt \
+    preopt debug \
     reader wasm module \
     faerie umbrella simplejit
 do
@@ -0,0 +1,37 @@
+(module
+  (memory 1 1 shared)
+
+  (func $load_store (param i32) (result i32)
+    get_local 0
+    i32.const 42
+    i32.atomic.store
+    get_local 0
+    i32.atomic.load8_u offset=1
+  )
+
+  (func $rmw (param i32 i64) (result i64)
+    get_local 0
+    i32.const 1
+    i32.atomic.rmw.add
+    drop
+    get_local 0
+    i64.const 0
+    get_local 1
+    i64.atomic.rmw.cmpxchg offset=8
+  )
+
+  (func $wait_notify (param i32) (result i32)
+    get_local 0
+    i32.const 0
+    i64.const -1
+    i32.atomic.wait
+    get_local 0
+    i32.const 1
+    atomic.notify
+    i32.add
+  )
+
+  (export "load_store" (func $load_store))
+  (export "rmw" (func $rmw))
+  (export "wait_notify" (func $wait_notify))
+)
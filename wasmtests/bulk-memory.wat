@@ -0,0 +1,57 @@
+(module
+  (table 2 anyfunc)
+  (memory 1)
+  (data "passive")
+  (elem funcref)
+
+  (func $copy (param i32 i32 i32)
+    get_local 0
+    get_local 1
+    get_local 2
+    memory.copy
+  )
+
+  (func $fill (param i32 i32 i32)
+    get_local 0
+    get_local 1
+    get_local 2
+    memory.fill
+  )
+
+  (func $table_copy (param i32 i32 i32)
+    get_local 0
+    get_local 1
+    get_local 2
+    table.copy
+  )
+
+  (func $init (param i32 i32 i32)
+    get_local 0
+    get_local 1
+    get_local 2
+    memory.init 0
+  )
+
+  (func $data_drop
+    data.drop 0
+  )
+
+  (func $table_init (param i32 i32 i32)
+    get_local 0
+    get_local 1
+    get_local 2
+    table.init 0
+  )
+
+  (func $elem_drop
+    elem.drop 0
+  )
+
+  (export "copy" (func $copy))
+  (export "fill" (func $fill))
+  (export "table_copy" (func $table_copy))
+  (export "init" (func $init))
+  (export "data_drop" (func $data_drop))
+  (export "table_init" (func $table_init))
+  (export "elem_drop" (func $elem_drop))
+)
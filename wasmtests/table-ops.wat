@@ -0,0 +1,38 @@
+(module
+  (table 3 anyfunc)
+
+  (func $size (result i32)
+    table.size 0
+  )
+
+  (func $grow (param i32) (result i32)
+    ref.null
+    get_local 0
+    table.grow 0
+  )
+
+  (func $get (param i32)
+    get_local 0
+    table.get 0
+    drop
+  )
+
+  (func $set (param i32)
+    get_local 0
+    ref.null
+    table.set 0
+  )
+
+  (func $move (param i32 i32)
+    get_local 0
+    get_local 1
+    table.get 0
+    table.set 0
+  )
+
+  (export "size" (func $size))
+  (export "grow" (func $grow))
+  (export "get" (func $get))
+  (export "set" (func $set))
+  (export "move" (func $move))
+)